@@ -0,0 +1,73 @@
+//! A round trip with the simulated gadget, as a frontend of your own would make it:
+//! agree on a protocol version, store display settings on the device, send numbered
+//! frames and match them to acknowledgements, retransmit one the way a lost
+//! acknowledgement would force, and read the device's state report back.
+//!
+//! ```bash
+//! cargo run --example mock_device_roundtrip
+//! ```
+
+use ccgadget_core::config::DisplayPage;
+use ccgadget_core::payload::DevicePayload;
+use ccgadget_core::protocol::{self, Ack, FrameKind};
+use ccgadget_core::settings::DeviceSettings;
+use ccgadget_core::transport::{self, Demo, Transport};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Send `payload` numbered `seq` and wait for the device to acknowledge it
+async fn send_acknowledged(device: &dyn Transport, version: u8, seq: u32, payload: &DevicePayload) -> Result<(), Box<dyn Error>> {
+    device.send(&protocol::encode(version, seq, payload)?).await?;
+    let reply = device.receive().await?.ok_or("The device did not answer")?;
+    match protocol::decode::<Ack>(&reply)? {
+        (Some(FrameKind::Ack), Ack { seq: Some(acked), .. }) if acked == seq => Ok(()),
+        (kind, ack) => Err(format!("Frame {} answered with {:?} {:?}", seq, kind, ack).into()),
+    }
+}
+
+/// Run the round trip, returning the settings the device reports at the end
+async fn roundtrip() -> Result<DeviceSettings, Box<dyn Error>> {
+    let mut device = Demo::new(DeviceSettings::default());
+    device.connect().await?;
+    let agreement = transport::handshake(&device).await?;
+    if agreement.version < protocol::ACKED_VERSION {
+        return Err(format!("The simulator agreed to protocol v{}, without acknowledgements", agreement.version).into());
+    }
+
+    let mut settings = DeviceSettings::default();
+    settings.display.widgets = vec!["cost_today".to_string(), "tokens_today".to_string()];
+    settings.display.page = DisplayPage::BlockCountdown;
+    send_acknowledged(&device, agreement.version, 1, &DevicePayload::settings(settings)).await?;
+
+    let usage = DevicePayload::usage(BTreeMap::from([("cost_today".to_string(), 3.2), ("tokens_today".to_string(), 812_000.0)]));
+    send_acknowledged(&device, agreement.version, 2, &usage).await?;
+    // Sent again, it is acknowledged again but not shown twice
+    send_acknowledged(&device, agreement.version, 2, &usage).await?;
+
+    let report = device.receive().await?.ok_or("The device sent no state report")?;
+    let (kind, state) = protocol::decode::<serde_json::Value>(&report)?;
+    device.close().await?;
+    if kind != Some(FrameKind::State) {
+        return Err(format!("Expected a state report, got {:?}", kind).into());
+    }
+    Ok(serde_json::from_value(state["settings"].clone())?)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let settings = roundtrip().await?;
+    println!("Device shows {} on {}", settings.display.widgets.join(", "), settings.display.page);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_device_keeps_pushed_settings() {
+        let settings = roundtrip().await.unwrap();
+        assert_eq!(settings.display.widgets, vec!["cost_today", "tokens_today"]);
+        assert_eq!(settings.display.page, DisplayPage::BlockCountdown);
+    }
+}
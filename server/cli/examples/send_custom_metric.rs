@@ -0,0 +1,68 @@
+//! Show a metric of your own on the gadget: a value measured outside ccgadget plus one
+//! derived from it, assigned to the display widgets and sent to the simulated device
+//! at whatever protocol version it agrees to.
+//!
+//! ```bash
+//! cargo run --example send_custom_metric
+//! ```
+
+use ccgadget_core::config::Config;
+use ccgadget_core::metrics::{self, MetricValues};
+use ccgadget_core::payload::DevicePayload;
+use ccgadget_core::protocol::{self, Ack, FrameKind};
+use ccgadget_core::settings::DeviceSettings;
+use ccgadget_core::transport::{self, Demo, Transport};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Send `tests_passed` and the derived `pass_rate` to a simulated device, returning
+/// its acknowledgement of the frame
+async fn send_custom_metric(tests_passed: f64, tests_run: f64) -> Result<Ack, Box<dyn Error>> {
+    let mut config = Config::default();
+    config.display.widgets = vec!["tests_passed".to_string(), "pass_rate".to_string()];
+    config.metrics.derived.insert("pass_rate".to_string(), "tests_passed / tests_run * 100".to_string());
+
+    let mut values = MetricValues::from([("tests_passed".to_string(), tests_passed), ("tests_run".to_string(), tests_run)]);
+    if let Some(warning) = metrics::resolve_derived(&mut values, &config.metrics.derived).first() {
+        return Err(warning.clone().into());
+    }
+    // Only metrics assigned to a widget go to the device
+    let widgets: BTreeMap<String, f64> = config
+        .display
+        .widgets
+        .iter()
+        .filter_map(|name| values.get(name).map(|value| (name.clone(), *value)))
+        .collect();
+
+    let mut device = Demo::new(DeviceSettings::from_config(&config));
+    device.connect().await?;
+    let agreement = transport::handshake(&device).await?;
+    let frame = protocol::encode(agreement.version, 1, &DevicePayload::usage(widgets))?;
+    device.send(&frame).await?;
+    let reply = device.receive().await?.ok_or("The device did not answer")?;
+    let (kind, ack): (_, Ack) = protocol::decode(&reply)?;
+    device.close().await?;
+    if kind != Some(FrameKind::Ack) {
+        return Err(format!("Expected an acknowledgement, got {:?}", kind).into());
+    }
+    Ok(ack)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let ack = send_custom_metric(42.0, 48.0).await?;
+    println!("Device acknowledged frame {}", ack.seq.unwrap_or_default());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_device_acknowledges_custom_metric() {
+        let ack = send_custom_metric(42.0, 48.0).await.unwrap();
+        assert_eq!(ack.seq, Some(1));
+        assert!(send_custom_metric(1.0, 0.0).await.is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::config::{Activity, ActivityConfig};
+
+/// Activities of Claude Code's own tools
+const TOOL_ACTIVITIES: &[(&str, Activity)] = &[
+    ("Edit", Activity::Editing),
+    ("MultiEdit", Activity::Editing),
+    ("Write", Activity::Editing),
+    ("NotebookEdit", Activity::Editing),
+    ("Bash", Activity::Running),
+    ("BashOutput", Activity::Running),
+    ("KillShell", Activity::Running),
+    ("WebSearch", Activity::Web),
+    ("WebFetch", Activity::Web),
+    ("Read", Activity::Reading),
+    ("Glob", Activity::Reading),
+    ("Grep", Activity::Reading),
+    ("LS", Activity::Reading),
+    ("NotebookRead", Activity::Reading),
+    ("Task", Activity::Thinking),
+    ("TodoWrite", Activity::Thinking),
+    ("ExitPlanMode", Activity::Thinking),
+];
+
+/// Shell commands that only look at files, or only fetch from the web
+const READING_COMMANDS: &[&str] = &["cat", "head", "tail", "less", "grep", "rg", "find", "fd", "ls", "tree", "wc"];
+const WEB_COMMANDS: &[&str] = &["curl", "wget", "http"];
+
+/// What a hook event shows Claude doing: configured rules first, then the built-in
+/// mapping. Shell commands that read files or fetch from the web count as such; a
+/// submitted prompt means Claude is thinking. Other events leave the icon as it was.
+pub fn classify(
+    config: &ActivityConfig,
+    event: Option<&str>,
+    tool: Option<&str>,
+    tool_input: Option<&serde_json::Value>,
+) -> Option<Activity> {
+    match (event, tool) {
+        (Some("PreToolUse" | "PostToolUse"), Some(tool)) => {
+            let command = tool_input.and_then(|input| input["command"].as_str());
+            config.find(tool, command).or_else(|| builtin(tool, command))
+        }
+        (Some("UserPromptSubmit"), _) => Some(Activity::Thinking),
+        _ => None,
+    }
+}
+
+/// Built-in activity of a tool call
+fn builtin(tool: &str, command: Option<&str>) -> Option<Activity> {
+    let activity = TOOL_ACTIVITIES.iter().find(|(name, _)| *name == tool).map(|(_, activity)| *activity)?;
+    let program = command
+        .and_then(|command| command.split_whitespace().next())
+        .map(|program| program.rsplit('/').next().unwrap_or(program));
+    match (activity, program) {
+        (Activity::Running, Some(program)) if READING_COMMANDS.contains(&program) => Some(Activity::Reading),
+        (Activity::Running, Some(program)) if WEB_COMMANDS.contains(&program) => Some(Activity::Web),
+        _ => Some(activity),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tools_and_commands_map_to_activities() {
+        let config = ActivityConfig::default();
+        let tool = |name: &str, input: serde_json::Value| classify(&config, Some("PreToolUse"), Some(name), Some(&input));
+        assert_eq!(tool("Edit", json!({"file_path": "src/main.rs"})), Some(Activity::Editing));
+        assert_eq!(tool("Bash", json!({"command": "cargo test"})), Some(Activity::Running));
+        assert_eq!(tool("Bash", json!({"command": "/usr/bin/grep -rn todo src"})), Some(Activity::Reading));
+        assert_eq!(tool("Bash", json!({"command": "curl -s https://example.com"})), Some(Activity::Web));
+        assert_eq!(tool("WebSearch", json!({"query": "rust"})), Some(Activity::Web));
+        assert_eq!(tool("mcp__github__create_issue", json!({})), None);
+        assert_eq!(classify(&config, Some("UserPromptSubmit"), None, None), Some(Activity::Thinking));
+        assert_eq!(classify(&config, Some("Stop"), None, None), None);
+    }
+
+    #[test]
+    fn test_configured_rules_come_first() {
+        let config: ActivityConfig = toml::from_str(
+            r#"
+            rules = [
+                { tool = "mcp__github__.*", activity = "web" },
+                { tool = "Bash", command = "^git (log|diff|show)", activity = "reading" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let tool = |name: &str, input: serde_json::Value| classify(&config, Some("PostToolUse"), Some(name), Some(&input));
+        assert_eq!(tool("mcp__github__create_issue", json!({})), Some(Activity::Web));
+        assert_eq!(tool("Bash", json!({"command": "git diff HEAD"})), Some(Activity::Reading));
+        assert_eq!(tool("Bash", json!({"command": "git commit -m x"})), Some(Activity::Running));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::payload::DevicePayload;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::debug;
+
+/// Longest wait for a client to send its request head
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request head accepted
+const MAX_HEAD: usize = 16 * 1024;
+
+/// What a REST endpoint asks the daemon for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Query {
+    /// `/status`: the daemon status, as `ccgadget status --output json` shows it
+    Status,
+    /// `/usage`: a usage frame for the focused session, as the device gets it
+    Usage,
+}
+
+/// The parts of an HTTP request the API looks at
+#[derive(Debug, Clone, PartialEq)]
+struct Request {
+    method: String,
+    /// Path without the query string
+    path: String,
+    origin: Option<String>,
+    /// `Sec-WebSocket-Key` of an upgrade request
+    websocket_key: Option<String>,
+}
+
+/// Parse a request head (request line and headers, without the blank line)
+fn parse_request(head: &str) -> Option<Request> {
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?;
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let mut request = Request { method, path, origin: None, websocket_key: None };
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim().to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "origin" => request.origin = Some(value),
+            "sec-websocket-key" => request.websocket_key = Some(value),
+            _ => {}
+        }
+    }
+    Some(request)
+}
+
+/// Whether a request from `origin` may be answered. Clients that are not browsers send
+/// no origin and are always served; browser pages only from the listed origins, or any
+/// with `*`, since a WebSocket is not subject to CORS.
+fn origin_allowed(origin: Option<&str>, allowed: &[String]) -> bool {
+    origin.is_none_or(|origin| allowed.iter().any(|allowed| allowed == "*" || allowed == origin))
+}
+
+/// Listen on `port` of the loopback interface only
+pub async fn bind(port: u16) -> std::io::Result<TcpListener> {
+    TcpListener::bind(("127.0.0.1", port)).await
+}
+
+/// Serve the API forever: `GET /status` and `GET /usage` answer `handler` as JSON, and
+/// `/events` upgrades to a WebSocket that streams each frame sent on `live`
+pub async fn serve<F>(listener: TcpListener, allowed_origins: Vec<String>, live: broadcast::Sender<DevicePayload>, handler: F)
+where
+    F: Fn(Query) -> Value + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let allowed_origins = Arc::new(allowed_origins);
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let handler = handler.clone();
+        let allowed_origins = allowed_origins.clone();
+        let events = live.subscribe();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &allowed_origins, events, handler).await {
+                debug!("API connection ended: {}", e);
+            }
+        });
+    }
+}
+
+/// Answer one request, keeping the connection open for an event stream
+async fn handle_connection<F>(
+    mut stream: TcpStream,
+    allowed_origins: &[String],
+    events: broadcast::Receiver<DevicePayload>,
+    handler: Arc<F>,
+) -> std::io::Result<()>
+where
+    F: Fn(Query) -> Value + Send + Sync + 'static,
+{
+    let head = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "no request"))??;
+    let Some(request) = parse_request(&head) else {
+        return respond(&mut stream, 400, None, &json!({ "error": "Malformed request" })).await;
+    };
+    debug!("API {} {}", request.method, request.path);
+    if !origin_allowed(request.origin.as_deref(), allowed_origins) {
+        return respond(&mut stream, 403, None, &json!({ "error": "Origin not allowed; add it to api.allowed_origins" })).await;
+    }
+    let origin = request.origin.as_deref();
+    let query = match (request.method.as_str(), request.path.as_str()) {
+        ("OPTIONS", _) => return respond(&mut stream, 204, origin, &Value::Null).await,
+        ("GET", "/events") => {
+            let Some(key) = &request.websocket_key else {
+                return respond(&mut stream, 426, origin, &json!({ "error": "/events is a WebSocket" })).await;
+            };
+            return stream_events(stream, key, events).await;
+        }
+        ("GET", "/status") => Query::Status,
+        ("GET", "/usage") => Query::Usage,
+        ("GET", _) => return respond(&mut stream, 404, origin, &json!({ "error": "Not found" })).await,
+        _ => return respond(&mut stream, 405, origin, &json!({ "error": "Method not allowed" })).await,
+    };
+    // Usage reads transcripts, so it is answered off the runtime
+    let body = tokio::task::spawn_blocking(move || handler(query)).await.map_err(std::io::Error::other)?;
+    respond(&mut stream, 200, origin, &body).await
+}
+
+/// Read up to the blank line that ends the request head
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" || line == "\n" {
+            return Ok(head);
+        }
+        if head.len() + line.len() > MAX_HEAD {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "request head too large"));
+        }
+        head.push_str(&line);
+    }
+}
+
+/// Write a JSON response and close the connection; `Null` sends no body
+async fn respond(stream: &mut TcpStream, status: u16, origin: Option<&str>, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Upgrade Required",
+    };
+    let body = if body.is_null() { String::new() } else { body.to_string() };
+    let cors = origin
+        .map(|origin| format!("Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: GET\r\nVary: Origin\r\n", origin))
+        .unwrap_or_default();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        body.len(),
+        cors
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Complete the WebSocket handshake and send each live frame as a JSON text message
+/// until the client goes away
+async fn stream_events(mut stream: TcpStream, key: &str, mut events: broadcast::Receiver<DevicePayload>) -> std::io::Result<()> {
+    let accept = derive_accept_key(key.as_bytes());
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    );
+    stream.write_all(head.as_bytes()).await?;
+    let mut socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(payload) => {
+                    let text = serde_json::to_string(&payload).map_err(std::io::Error::other)?;
+                    socket.send(Message::text(text)).await.map_err(std::io::Error::other)?;
+                }
+                // A slow client misses frames rather than holding the daemon up
+                Err(broadcast::error::RecvError::Lagged(missed)) => debug!("API client skipped {} frames", missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by the socket itself; anything else is ignored
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = socket.close(None).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{self, Endpoint};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_request_and_origins() {
+        let head = "GET /events?x=1 HTTP/1.1\r\nHost: localhost\r\nOrigin: http://localhost:3000\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+        let request = parse_request(head).unwrap();
+        assert_eq!(request.path, "/events");
+        assert_eq!(request.websocket_key.as_deref(), Some("dGhlIHNhbXBsZSBub25jZQ=="));
+
+        let allowed = vec!["http://localhost:3000".to_string()];
+        assert!(origin_allowed(None, &[]));
+        assert!(origin_allowed(request.origin.as_deref(), &allowed));
+        assert!(!origin_allowed(Some("https://example.com"), &allowed));
+        assert!(origin_allowed(Some("https://example.com"), &["*".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_rest_and_event_stream() {
+        let listener = bind(0).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (live, _) = broadcast::channel(16);
+        tokio::spawn(serve(listener, Vec::new(), live.clone(), |query| json!({ "query": format!("{:?}", query) })));
+
+        let endpoint = Endpoint { host: "127.0.0.1".to_string(), port, base: String::new() };
+        let response = http::request(&endpoint, "GET", "/usage", None, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&response.body).unwrap()["query"], "Usage");
+        let response = http::request(&endpoint, "GET", "/nothing", None, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(response.status, 404);
+
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let (mut socket, _) = tokio_tungstenite::client_async(format!("ws://127.0.0.1:{}/events", port), stream).await.unwrap();
+        live.send(DevicePayload::usage(BTreeMap::from([("cost_today".to_string(), 2.5)]))).unwrap();
+        let Some(Ok(Message::Text(text))) = socket.next().await else {
+            panic!("no event");
+        };
+        let payload: DevicePayload = serde_json::from_str(&text).unwrap();
+        assert_eq!(payload.widgets["cost_today"], 2.5);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::rng::XorShift;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Delays between reconnection attempts: doubling from `base` up to `max`, each with
+/// random jitter so that daemons which lost the same device do not retry in step
+#[derive(Debug)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    /// Failed attempts since the last success
+    attempts: u32,
+    rng: XorShift,
+}
+
+impl Backoff {
+    /// Backoff seeded from the process's random hasher keys
+    pub fn new(base: Duration, max: Duration) -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        Self::seeded(base, max, hasher.finish())
+    }
+
+    fn seeded(base: Duration, max: Duration, seed: u64) -> Self {
+        Self { base, max: max.max(base), attempts: 0, rng: XorShift::new(seed) }
+    }
+
+    /// Failed attempts since the last success
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Wait before the next attempt, counting the one that just failed. Half the
+    /// delay is fixed and half random, so retries never bunch up at zero.
+    pub fn next_delay(&mut self) -> Duration {
+        let ceiling = self.base.saturating_mul(1 << self.attempts.min(16)).min(self.max);
+        self.attempts = self.attempts.saturating_add(1);
+        ceiling / 2 + (ceiling / 2).mul_f64(self.rng.next_f64())
+    }
+
+    /// Start over from `base` after a successful connection
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delays_double_up_to_the_cap_with_jitter() {
+        let mut backoff = Backoff::seeded(Duration::from_secs(1), Duration::from_secs(10), 7);
+        for ceiling in [1, 2, 4, 8, 10, 10] {
+            let ceiling = Duration::from_secs(ceiling);
+            let delay = backoff.next_delay();
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "{:?} outside {:?}", delay, ceiling);
+        }
+        assert_eq!(backoff.attempts(), 6);
+
+        backoff.reset();
+        assert!(backoff.next_delay() <= Duration::from_secs(1));
+    }
+}
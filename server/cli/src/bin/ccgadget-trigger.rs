@@ -0,0 +1,76 @@
+//! Trigger-only build of ccgadget for remote servers and containers: reads the hook
+//! event on stdin, logs it, and hands it to the daemon socket. No Bluetooth, daemon,
+//! or async runtime is compiled in, so each hook invocation starts instantly.
+//!
+//! Installed as `ccgadget`, it keeps `ccgadget trigger` hook commands working unchanged.
+
+use ccgadget_core::error::ExitCode;
+use ccgadget_core::trigger::{self, EventFlags, Mode};
+use ccgadget_core::config;
+
+const USAGE: &str = "Usage: ccgadget-trigger [trigger] [--profile <name>] [--no-redact] [--wait] < hook-event.json
+       ccgadget-trigger [trigger] --batch [--profile <name>] [--no-redact] < hook-events.ndjson
+       ccgadget-trigger [trigger] --event <EVENT> [--message <text>] [--prompt <text>] [--tool <name>]
+                        [--tool-input <json>] [--tool-response <json>] [--session-id <id>] [--cwd <dir>]
+                        [--transcript-path <path>]";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut profile = std::env::var("CCGADGET_PROFILE").ok();
+    let mut redact = true;
+    let mut mode = Mode::Auto;
+    let mut batch = false;
+    let mut flags = EventFlags::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "trigger" => {}
+            "--profile" => profile = args.next(),
+            "--no-redact" => redact = false,
+            "--wait" => mode = Mode::Wait,
+            "--detached" => mode = Mode::Worker,
+            "--batch" => batch = true,
+            "-V" | "--version" => {
+                println!("ccgadget-trigger {}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            "-h" | "--help" => {
+                println!("Log a Claude Code hook event and forward it to the CCGadget daemon\n\n{}", USAGE);
+                return;
+            }
+            _ => {
+                if let Some(name) = arg.strip_prefix("--profile=") {
+                    profile = Some(name.to_string());
+                    continue;
+                }
+                let (flag, value) = match arg.split_once('=') {
+                    Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                    None => (arg.clone(), None),
+                };
+                match flags.set(&flag, value.or_else(|| args.next()).unwrap_or_default()) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        eprintln!("Unsupported argument `{}`: this build only triggers\n{}", arg, USAGE);
+                        ExitCode::Usage.exit();
+                    }
+                    Err(e) => {
+                        eprintln!("{}\n{}", e, USAGE);
+                        ExitCode::Usage.exit();
+                    }
+                }
+            }
+        }
+    }
+    if let Some(profile) = profile.filter(|profile| !profile.is_empty()) {
+        config::set_profile(&profile);
+    }
+
+    if batch {
+        if !flags.is_empty() {
+            eprintln!("--batch reads events from stdin and takes no event flags\n{}", USAGE);
+            ExitCode::Usage.exit();
+        }
+        trigger::run_batch(redact);
+    } else {
+        trigger::run(redact, &flags, mode);
+    }
+}
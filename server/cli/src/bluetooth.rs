@@ -1,94 +1,490 @@
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-use btleplug::platform::{Adapter, Manager, Peripheral};
-use log::{error, info};
-use std::error::Error;
+#[cfg(feature = "ble")]
+use crate::{info, wizard};
+use crate::config::Config;
+use crate::device::{AdapterChoice, PairedDevice, Registration};
+use crate::error::{self, Classify, ExitCode};
+use crate::scanner::{self, DeviceFilter};
+use crate::settings::DeviceSettings;
+use crate::transport;
+#[cfg(feature = "ble")]
+use crate::transport::Transport as _;
+#[cfg(feature = "ble")]
+use btleplug::api::{Central as _, Peripheral as _};
+use chrono::Utc;
+use serde::Serialize;
+#[cfg(feature = "ble")]
+use std::io::{self, Write};
 use std::time::Duration;
-use tokio::time;
-
-pub async fn pair_device(mac: Option<String>) -> Result<(), Box<dyn Error>> {
-    // Initialize Bluetooth manager
-    let manager = Manager::new().await?;
-    let adapters = manager.adapters().await?;
-    let adapter = adapters
-        .into_iter()
-        .next()
-        .ok_or("No Bluetooth adapters found")?;
-
-    // Start scanning for devices
-    info!("Scanning for Bluetooth devices...");
-    adapter
-        .start_scan(ScanFilter::default())
-        .await
-        .map_err(|e| format!("Failed to start scan: {}", e))?;
-
-    // Scan for 10 seconds
-    time::sleep(Duration::from_secs(10)).await;
-
-    // Get discovered devices
-    let peripherals = adapter.peripherals().await?;
-    if peripherals.is_empty() {
-        error!("No Bluetooth devices found. Ensure the target device is discoverable.");
-        return Err("No devices found".into());
-    }
-
-    let mut target_peripheral: Option<Peripheral> = None;
-
-    // If a MAC address is provided, find the matching device
-    if let Some(mac_addr) = mac {
-        for peripheral in peripherals.iter() {
-            if let Ok(Some(addr)) = peripheral.address().await {
-                if addr.to_string().to_uppercase() == mac_addr.to_uppercase() {
-                    target_peripheral = Some(peripheral.clone());
-                    break;
+
+/// Why Bluetooth could not be used or the device could not be reached over it
+#[derive(Debug, thiserror::Error)]
+pub enum BluetoothError {
+    #[error("This build of ccgadget has no Bluetooth support")]
+    Unsupported,
+    #[error("Bluetooth initialization failed: {0}")]
+    Init(String),
+    #[error("{0} timed out")]
+    Timeout(&'static str),
+    #[error("Bluetooth adapter access failed: {0}")]
+    Adapter(String),
+    #[error("Bluetooth permission denied")]
+    PermissionDenied,
+    #[error("No Bluetooth adapter found")]
+    NoAdapter,
+    #[error("Bluetooth adapter '{choice}' not found (available: {available})")]
+    AdapterNotFound { choice: String, available: String },
+    #[error("Device '{device}' not found within {seconds} seconds")]
+    DeviceNotFound { device: String, seconds: u64 },
+}
+
+impl Classify for BluetoothError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            BluetoothError::DeviceNotFound { .. } => ExitCode::DeviceNotFound,
+            _ => ExitCode::NoBluetooth,
+        }
+    }
+
+    fn hint(&self) -> Option<&str> {
+        Some(match self {
+            BluetoothError::Unsupported => "Reach the gadget with device.transport = \"wifi\", \"serial\", or \"mqtt\"",
+            BluetoothError::Init(_) | BluetoothError::Adapter(_) => {
+                "Enable Bluetooth and grant this terminal Bluetooth permission"
+            }
+            BluetoothError::Timeout(_) => "Check that the Bluetooth service is running and permitted",
+            BluetoothError::PermissionDenied => {
+                "Allow ccgadget (or the terminal running it) under System Settings > Privacy & Security > Bluetooth"
+            }
+            BluetoothError::NoAdapter => "Check that Bluetooth hardware is available and enabled",
+            BluetoothError::AdapterNotFound { .. } => "List adapters with `ccgadget scan --adapters`",
+            BluetoothError::DeviceNotFound { .. } => "Make sure the device is powered on, in pairing mode, and within range",
+        })
+    }
+}
+
+#[cfg(feature = "ble")]
+impl BluetoothError {
+    /// A btleplug error as `wrap` describes it, unless Bluetooth access was refused
+    pub fn from_btleplug(e: btleplug::Error, wrap: fn(String) -> Self) -> Self {
+        match e {
+            btleplug::Error::PermissionDenied => BluetoothError::PermissionDenied,
+            e => wrap(e.to_string()),
+        }
+    }
+}
+
+/// Outcome of `pair`, as printed by `--output json`
+#[derive(Serialize, Debug, Default)]
+pub struct PairResult {
+    pub paired: bool,
+    /// Demo-mode pairing; nothing was saved
+    pub simulated: bool,
+    pub device: Option<PairedDevice>,
+    /// Service UUIDs the device exposes
+    pub services: Vec<String>,
+    /// Protocol version agreed with the device during pairing
+    pub protocol: Option<u8>,
+}
+
+/// Pair with the named device, or one picked from the devices `filter` accepts, through
+/// `adapter` or the configured one, saving it where `registration` says; exits non-zero
+/// on failure. An adapter given here is remembered in the config for the daemon.
+pub async fn pair(
+    device: Option<&str>,
+    force: bool,
+    timeout: Duration,
+    filter: DeviceFilter,
+    adapter: Option<&AdapterChoice>,
+    registration: &Registration,
+) -> PairResult {
+    say!("🔵 Pairing with CCGadget device...");
+    
+    if force {
+        say!("   Force pairing enabled");
+    }
+
+    // Check if we're in a test environment or don't have Bluetooth permissions
+    if std::env::var("CCGADGET_DEMO_MODE").is_ok() {
+        say!("   🔧 Running in demo/test mode - simulating pairing");
+        return simulate_pairing(device, filter).await;
+    }
+
+    let remembered = Config::load_or_default().device.adapter;
+    let chosen = adapter.or(remembered.as_ref());
+    let result = pair_over_bluetooth(device, force, timeout, filter, chosen, registration).await;
+
+    if let Some(adapter) = adapter.filter(|adapter| result.paired && remembered.as_ref() != Some(*adapter)) {
+        let value = match adapter {
+            AdapterChoice::Index(index) => toml_edit::Value::from(*index as i64),
+            AdapterChoice::Name(name) => toml_edit::Value::from(name.as_str()),
+        };
+        match Config::set_value("device.adapter", value) {
+            Ok(path) => say!("   💾 Remembered adapter {} in {}", adapter, path.display()),
+            Err(e) => esay!("   ⚠️ Could not save the adapter to the config: {}", e),
+        }
+    }
+    result
+}
+
+/// Pair over Bluetooth: with the named device, in the wizard, or from a numbered list;
+/// exits non-zero on failure
+#[cfg(feature = "ble")]
+async fn pair_over_bluetooth(
+    device: Option<&str>,
+    force: bool,
+    timeout: Duration,
+    filter: DeviceFilter,
+    adapter: Option<&AdapterChoice>,
+    registration: &Registration,
+) -> PairResult {
+    say!("   💡 If this hangs or fails, use: CCGADGET_DEMO_MODE=1 ccgadget pair");
+
+    if let Some(device_name) = device {
+        say!("   Target device: {}", device_name);
+        match pair_with_device(device_name, force, timeout, adapter, registration).await {
+            Ok(result) => result,
+            Err(e) => {
+                esay!("   ❌ Failed to pair with device: {}", e);
+                esay!("   💡 To test without Bluetooth: CCGADGET_DEMO_MODE=1 ccgadget pair");
+                error::exit(&*e);
+            }
+        }
+    } else if wizard::supported() {
+        match pair_in_wizard(timeout, filter, adapter, registration).await {
+            Ok(Some(result)) => result,
+            Ok(None) => {
+                say!("   ℹ️ No device selected. Pairing cancelled.");
+                PairResult::default()
+            }
+            Err(e) => {
+                esay!("   ❌ Failed to pair: {}", e);
+                esay!("   💡 To test without Bluetooth: CCGADGET_DEMO_MODE=1 ccgadget pair");
+                error::exit(&*e);
+            }
+        }
+    } else {
+        say!("   Scanning for nearby Bluetooth devices...");
+        match scan_and_select_device(timeout, filter, adapter).await {
+            Ok(Some(selected_device)) => {
+                say!("   Selected device: {}", selected_device);
+                match pair_with_device(&selected_device, force, timeout, adapter, registration).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        esay!("   ❌ Failed to pair with selected device: {}", e);
+                        error::exit(&*e);
+                    }
                 }
             }
+            Ok(None) => {
+                say!("   ℹ️ No device selected. Pairing cancelled.");
+                PairResult::default()
+            }
+            Err(e) => {
+                esay!("   ❌ Error during device scanning: {}", e);
+                esay!("   💡 To test without Bluetooth: CCGADGET_DEMO_MODE=1 ccgadget pair");
+                error::exit(&*e);
+            }
         }
-        if target_peripheral.is_none() {
-            error!("Device with MAC {} not found.", mac_addr);
-            return Err("Specified device not found".into());
+    }
+}
+
+#[cfg(not(feature = "ble"))]
+async fn pair_over_bluetooth(
+    _device: Option<&str>,
+    _force: bool,
+    _timeout: Duration,
+    _filter: DeviceFilter,
+    _adapter: Option<&AdapterChoice>,
+    _registration: &Registration,
+) -> PairResult {
+    unsupported()
+}
+
+/// Refuse a Bluetooth command in a build without the `ble` feature
+#[cfg(not(feature = "ble"))]
+pub fn unsupported() -> ! {
+    let e = BluetoothError::Unsupported;
+    esay!("❌ {}", e);
+    error::exit(&e)
+}
+
+/// Simulate pairing for demo/test mode
+async fn simulate_pairing(device: Option<&str>, filter: DeviceFilter) -> PairResult {
+    let services = vec![
+        "12345678-1234-5678-9abc-123456789abc".to_string(),
+        "87654321-4321-8765-cba9-987654321abc".to_string(),
+    ];
+    let simulated = |name: &str| PairedDevice {
+        name: Some(name.to_string()),
+        address: "AA:BB:CC:DD:EE:FF".to_string(),
+        peripheral_id: None,
+        paired_at: Utc::now(),
+        group: None,
+    };
+    if let Some(device_name) = device {
+        say!("   🎯 Target device: {}", device_name);
+        say!("   🔍 Simulating Bluetooth scan...");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        say!("   ✅ Found simulated device: {}", device_name);
+        say!("   🔗 Connecting to device...");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        say!("   📋 Discovering services... ({} service(s) found)", services.len());
+        for service in &services {
+            say!("      - Service UUID: {}", service);
         }
+        let protocol = simulate_handshake().await;
+        say!("   ✅ Pairing completed successfully!");
+        say!("   ℹ️ Note: This was a simulated pairing for demo/testing purposes");
+        PairResult { paired: true, simulated: true, device: Some(simulated(device_name)), services, protocol }
     } else {
-        // List all discovered devices
-        info!("Discovered Bluetooth devices:");
-        for (i, peripheral) in peripherals.iter().enumerate() {
-            let properties = peripheral.properties().await?.unwrap_or_default();
-            let name = properties.local_name.unwrap_or("Unknown".to_string());
-            let addr = peripheral
-                .address()
-                .await
-                .map(|a| a.to_string())
-                .unwrap_or("Unknown".to_string());
-            info!("{}. {} ({})", i + 1, name, addr);
+        let devices = scanner::scan(Duration::from_secs(scanner::DEFAULT_SCAN_SECONDS), filter, None).await.unwrap_or_default();
+        say!("   📱 Found {} CCGadget device(s):", devices.len());
+        for (i, device) in devices.iter().enumerate() {
+            let firmware = device.firmware.as_ref().map(|version| format!(" - Firmware: {}", version)).unwrap_or_default();
+        say!("   {}. {} ({}) - Signal: {}{}", i + 1, device.display_name(), device.address, device.signal(), firmware);
+        }
+        say!("   0. Cancel");
+        say!("   ℹ️ Auto-selecting device 1 for demo");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        say!("   🔗 Connecting to CCGadget-Demo...");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let protocol = simulate_handshake().await;
+        say!("   ✅ Pairing completed successfully!");
+        say!("   ℹ️ Note: This was a simulated pairing for demo/testing purposes");
+        PairResult { paired: true, simulated: true, device: Some(simulated("CCGadget-Demo")), services, protocol }
+    }
+}
+
+/// Run the protocol handshake against the simulated device
+async fn simulate_handshake() -> Option<u8> {
+    let version = transport::handshake(&transport::Demo::new(DeviceSettings::default())).await.ok()?.version;
+    say!("   🤝 Speaking protocol v{}", version);
+    Some(version)
+}
+
+/// What to check when no CCGadget advertised during a pairing scan
+#[cfg(feature = "ble")]
+fn explain_no_devices() {
+    say!("   ⚠️ No CCGadget devices found");
+    say!("   💡 Make sure your CCGadget device is:");
+    say!("      - Powered on and in pairing mode");
+    say!("      - Within Bluetooth range (10 meters)");
+    say!("      - Running firmware that advertises the CCGadget service");
+    say!("      (use --loose to also match names starting with 'CCGadget', 'CCG-', or 'ESP32-CCG')");
+}
+
+/// Pick a device from a live list in the full-screen wizard and pair with it there;
+/// `None` when the user cancelled or nothing advertised
+#[cfg(feature = "ble")]
+async fn pair_in_wizard(
+    timeout: Duration,
+    filter: DeviceFilter,
+    adapter: Option<&AdapterChoice>,
+    registration: &Registration,
+) -> Result<Option<PairResult>, Box<dyn std::error::Error>> {
+    let central = scanner::open_adapter(adapter).await?;
+    let mut wizard = wizard::Wizard::enter()?;
+    let choice = wizard.choose(scanner::advertisements(&central, filter).await?, timeout).await?;
+    central.stop_scan().await?;
+    let (device, peripheral) = match choice {
+        wizard::Choice::Selected(device, peripheral) => (device, peripheral),
+        wizard::Choice::Cancelled => return Ok(None),
+        wizard::Choice::NothingFound => {
+            drop(wizard);
+            explain_no_devices();
+            return Ok(None);
         }
+    };
 
-        // For simplicity, select the first device (modify to allow user input)
-        target_peripheral = peripherals.into_iter().next();
+    let (reporter, progress) = tokio::sync::mpsc::unbounded_channel();
+    let report = move |line: &str| {
+        let _ = reporter.send(line.to_string());
+    };
+    let result = wizard.connect(&device, progress, register_device(&peripheral, registration, &report)).await?;
+    match &result {
+        Ok(_) => wizard.finish(&format!("✅ Paired with {} ({})", device.display_name(), device.address), true)?,
+        Err(e) => wizard.finish(&format!("❌ {}", e), false)?,
+    }
+    drop(wizard);
+
+    // The wizard's screen is gone, so leave a record in the scrollback
+    let result = result?;
+    say!("   ✅ Paired with {} ({})", device.display_name(), device.address);
+    if let Ok(path) = PairedDevice::path() {
+        say!("   💾 Saved paired device to {}", path.display());
+    }
+    Ok(Some(result))
+}
+
+/// Wait up to `timeout` for CCGadget devices to advertise and let user select one
+#[cfg(feature = "ble")]
+async fn scan_and_select_device(
+    timeout: Duration,
+    filter: DeviceFilter,
+    adapter: Option<&AdapterChoice>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let central = scanner::open_adapter(adapter).await?;
+    say!("   📡 Scanning for CCGadget devices (up to {} seconds)...", timeout.as_secs());
+    let devices = scanner::await_ccgadgets(&central, timeout, filter).await?;
+
+    if devices.is_empty() {
+        explain_no_devices();
+        return Ok(None);
+    }
+    
+    // Display found CCGadget devices
+    say!("   📱 Found {} CCGadget device(s):", devices.len());
+    for (i, device) in devices.iter().enumerate() {
+        let firmware = device.firmware.as_ref().map(|version| format!(" - Firmware: {}", version)).unwrap_or_default();
+        say!("   {}. {} ({}) - Signal: {}{}", i + 1, device.display_name(), device.address, device.signal(), firmware);
+    }
+    say!("   0. Cancel");
+    
+    // Get user selection
+    loop {
+        say_inline!("   Select a device to pair with (0-{}): ", devices.len());
+        io::stdout().flush()?;
+        
+        let stdin = io::stdin();
+        let mut line = String::new();
+        stdin.read_line(&mut line)?;
+        
+        match line.trim().parse::<usize>() {
+            Ok(0) => {
+                return Ok(None);
+            }
+            Ok(selection) if selection <= devices.len() => {
+                return Ok(Some(devices[selection - 1].address.clone()));
+            }
+            _ => {
+                say!("   ❌ Invalid selection. Please try again.");
+                continue;
+            }
+        }
     }
+}
+
+/// Attempt to pair with a specific device
+#[cfg(feature = "ble")]
+async fn pair_with_device(
+    device_identifier: &str,
+    _force: bool,
+    timeout: Duration,
+    adapter: Option<&AdapterChoice>,
+    registration: &Registration,
+) -> Result<PairResult, Box<dyn std::error::Error>> {
+    say!("   🔗 Attempting to pair with device: {}", device_identifier);
+    
+    let central = scanner::open_adapter(adapter).await?;
+    
+    // Stop scanning as soon as the target device advertises
+    say!("   📡 Scanning for target device (up to {} seconds)...", timeout.as_secs());
+    let found_peripheral = scanner::find(&central, device_identifier, timeout).await?;
+    
+    let peripheral = found_peripheral
+        .ok_or_else(|| BluetoothError::DeviceNotFound { device: device_identifier.to_string(), seconds: timeout.as_secs() })?;
+    
+    say!("   ✅ Found target device, attempting connection...");
+    register_device(&peripheral, registration, &|line| say!("   {}", line)).await
+}
 
-    let peripheral = target_peripheral.ok_or("No target device selected")?;
+/// Connect to a found device, agree on a protocol version, and save it where
+/// `registration` says, passing each progress line to `report`
+#[cfg(feature = "ble")]
+async fn register_device(
+    peripheral: &btleplug::platform::Peripheral,
+    registration: &Registration,
+    report: &dyn Fn(&str),
+) -> Result<PairResult, Box<dyn std::error::Error>> {
+    // Connect to the device
+    peripheral.connect().await?;
+    report("🎉 Successfully connected to device!");
+    
+    // Discover services
+    peripheral.discover_services().await?;
+    let services = peripheral.services();
+    
+    report(&format!("📋 Device services discovered: {} service(s)", services.len()));
+    for service in &services {
+        report(&format!("   - Service UUID: {}", service.uuid));
+    }
+    
     let properties = peripheral.properties().await?.unwrap_or_default();
-    let name = properties.local_name.unwrap_or("Unknown".to_string());
-    let addr = peripheral
-        .address()
-        .await
-        .map(|a| a.to_string())
-        .unwrap_or("Unknown".to_string());
-
-    // Attempt to pair
-    info!("Attempting to pair with {} ({})...", name, addr);
-    peripheral
-        .pair()
-        .await
-        .map_err(|e| format!("Pairing failed: {}", e))?;
-
-    info!("Successfully paired with {} ({})", name, addr);
-
-    // Optional: Connect to the device (if required for ccusage data transmission)
-    peripheral
-        .connect()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-    info!("Connected to {} ({})", name, addr);
-
-    Ok(())
+    let paired = PairedDevice {
+        name: properties.local_name,
+        address: peripheral.address().to_string(),
+        peripheral_id: Some(peripheral.id().to_string()),
+        paired_at: Utc::now(),
+        group: None,
+    };
+
+    // Agree on a protocol version now, so an incompatible device is refused up front
+    let protocol = match transport::Ble::attached(paired.clone(), peripheral.clone()) {
+        Ok(link) => match transport::handshake(&link).await {
+            Ok(agreement) => Some(agreement.version),
+            Err(e) => {
+                peripheral.disconnect().await?;
+                return Err(format!("Incompatible device: {}", e).into());
+            }
+        },
+        Err(e) => {
+            report(&format!("⚠️ {}; protocol not checked", e));
+            None
+        }
+    };
+    if let Some(version) = protocol {
+        report(&format!("🤝 Speaking protocol v{}", version));
+    }
+    peripheral.disconnect().await?;
+
+    // Remember the device so the daemon can reconnect to it
+    let registry_path = registration.save(&paired)?;
+    report(&format!("💾 Saved paired device to {}", registry_path.display()));
+    
+    report("✅ Pairing completed successfully!");
+    Ok(PairResult {
+        paired: true,
+        simulated: false,
+        device: Some(paired),
+        services: services.iter().map(|service| service.uuid.to_string()).collect(),
+        protocol,
+    })
+}
+
+/// Connect to the paired device over BLE and read its info characteristics
+#[cfg(feature = "ble")]
+pub async fn read_device_info() -> Result<info::DeviceInfo, Box<dyn std::error::Error>> {
+    if std::env::var("CCGADGET_DEMO_MODE").is_ok() {
+        return Ok(info::DeviceInfo::simulated());
+    }
+    let device = PairedDevice::require()?;
+    let config = Config::load_or_default();
+    let mut ble = transport::Ble::new(device, config.device.ble_mtu, config.device.adapter);
+    ble.connect().await?;
+    let info = match ble.peripheral() {
+        Some(peripheral) => info::read(peripheral).await,
+        None => Default::default(),
+    };
+    if let Err(e) = ble.close().await {
+        tracing::debug!("Disconnecting after reading device info failed: {}", e);
+    }
+    Ok(info)
+}
+
+#[cfg(all(test, feature = "ble"))]
+mod tests {
+    use btleplug::platform::Manager;
+
+    #[tokio::test]
+    async fn test_bluetooth_manager_creation() {
+        // Test that we can create a Bluetooth manager
+        // This will fail on systems without Bluetooth, but that's expected
+        let result = Manager::new().await;
+        // We don't assert success here since CI environments may not have Bluetooth
+        // Instead, we just verify the call doesn't panic
+        match result {
+            Ok(_) => say!("Bluetooth manager created successfully"),
+            Err(e) => say!("Bluetooth not available: {}", e),
+        }
+    }
 }
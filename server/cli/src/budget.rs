@@ -0,0 +1,256 @@
+use crate::burn;
+use crate::config::{BudgetsConfig, Config, LimitsConfig};
+use crate::cost::CostSummary;
+use crate::payload::Severity;
+use crate::pricing::PricingTable;
+use crate::timerange::{self, TimeRange};
+use crate::transcript::UsageRecord;
+use crate::usage;
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Span of time a budget covers
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    /// Local calendar day
+    Daily,
+    /// Week from Monday, local time
+    Weekly,
+    /// The active five-hour usage block
+    Block,
+}
+
+impl std::fmt::Display for BudgetPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BudgetPeriod::Daily => "daily",
+            BudgetPeriod::Weekly => "weekly",
+            BudgetPeriod::Block => "block",
+        })
+    }
+}
+
+/// What a budget caps
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetMetric {
+    /// Estimated spend in dollars
+    Cost,
+    Tokens,
+}
+
+impl std::fmt::Display for BudgetMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BudgetMetric::Cost => "cost",
+            BudgetMetric::Tokens => "token",
+        })
+    }
+}
+
+/// How much of one configured budget is used in its current period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BudgetUsage {
+    pub period: BudgetPeriod,
+    pub metric: BudgetMetric,
+    /// When the current period began; each period starts from zero
+    pub period_start: DateTime<Utc>,
+    pub used: f64,
+    pub limit: f64,
+    /// Share of the budget used, in percent; above 100 once it is exceeded
+    pub percent: f64,
+}
+
+impl BudgetUsage {
+    fn new(period: BudgetPeriod, metric: BudgetMetric, period_start: DateTime<Utc>, used: f64, limit: f64) -> Self {
+        Self { period, metric, period_start, used, limit, percent: used * 100.0 / limit }
+    }
+
+    /// A dollar amount or token count, as the budget is measured
+    fn amount(&self, value: f64) -> String {
+        match (self.metric, value) {
+            (BudgetMetric::Cost, value) => format!("${:.2}", value),
+            (BudgetMetric::Tokens, v) if v >= 1_000_000.0 => format!("{:.1}M", v / 1_000_000.0),
+            (BudgetMetric::Tokens, v) if v >= 1_000.0 => format!("{:.1}k", v / 1_000.0),
+            (BudgetMetric::Tokens, v) => format!("{:.0}", v),
+        }
+    }
+
+    /// Used and limit, e.g. "$16.40 of $20.00 (82%)"
+    pub fn describe(&self) -> String {
+        format!("{} of {} ({:.0}%)", self.amount(self.used), self.amount(self.limit), self.percent)
+    }
+}
+
+/// A budget that just reached one of the configured thresholds
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetAlert {
+    pub usage: BudgetUsage,
+    /// Highest threshold reached, in percent
+    pub threshold: f64,
+}
+
+impl BudgetAlert {
+    /// Error once the budget is used up, a warning before that
+    pub fn severity(&self) -> Severity {
+        if self.threshold >= 100.0 {
+            Severity::Error
+        } else {
+            Severity::Warn
+        }
+    }
+
+    /// e.g. "Daily cost budget at 80%: $16.40 of $20.00 (82%)"
+    pub fn message(&self) -> String {
+        let period = self.usage.period.to_string();
+        let mut chars = period.chars();
+        let period = chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default();
+        let state = if self.threshold >= 100.0 { "exceeded".to_string() } else { format!("at {:.0}%", self.threshold) };
+        format!("{} {} budget {}: {}", period, self.usage.metric, state, self.usage.describe())
+    }
+}
+
+/// Thresholds already alerted for, per budget, in its current period
+#[derive(Debug, Default)]
+pub struct Crossings {
+    reached: HashMap<(BudgetPeriod, BudgetMetric), (DateTime<Utc>, f64)>,
+}
+
+impl Crossings {
+    /// Budgets that reached a threshold since the last check, each with the highest one
+    /// it reached. A budget whose period started over can alert again.
+    pub fn update(&mut self, usages: &[BudgetUsage], thresholds: &[f64]) -> Vec<BudgetAlert> {
+        let mut alerts = Vec::new();
+        for usage in usages {
+            let Some(threshold) = thresholds.iter().copied().filter(|threshold| usage.percent >= *threshold).reduce(f64::max) else {
+                continue;
+            };
+            let key = (usage.period, usage.metric);
+            let already = self
+                .reached
+                .get(&key)
+                .filter(|(start, _)| *start == usage.period_start)
+                .is_some_and(|(_, reached)| *reached >= threshold);
+            if !already {
+                self.reached.insert(key, (usage.period_start, threshold));
+                alerts.push(BudgetAlert { usage: usage.clone(), threshold });
+            }
+        }
+        alerts
+    }
+}
+
+/// Whether any budget is configured
+pub fn configured(budgets: &BudgetsConfig) -> bool {
+    [budgets.daily_usd, budgets.weekly_usd, budgets.block_usd].iter().any(Option::is_some)
+        || [budgets.daily_tokens, budgets.weekly_tokens, budgets.block_tokens].iter().any(Option::is_some)
+}
+
+/// Start of the local day and of the week (Monday) containing `now`
+fn period_starts(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = now.with_timezone(&Local).date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    (timerange::start_of_day(today), timerange::start_of_day(monday))
+}
+
+/// How much of each configured budget `records` use at `now`. The block budgets are
+/// left out while no usage block is active.
+pub fn measure(
+    budgets: &BudgetsConfig,
+    records: &[UsageRecord],
+    limits: &LimitsConfig,
+    pricing: &PricingTable,
+    now: DateTime<Utc>,
+) -> Vec<BudgetUsage> {
+    let (day_start, week_start) = period_starts(now);
+    let day = CostSummary::from_records(records.iter().filter(|record| record.timestamp >= day_start), pricing);
+    let week = CostSummary::from_records(records.iter().filter(|record| record.timestamp >= week_start), pricing);
+    let block = burn::snapshot(records, limits, pricing, now);
+
+    let mut usages = Vec::new();
+    let mut add = |period, start, cost: f64, tokens: u64, usd: Option<f64>, token_limit: Option<u64>| {
+        if let Some(limit) = usd {
+            usages.push(BudgetUsage::new(period, BudgetMetric::Cost, start, cost, limit));
+        }
+        if let Some(limit) = token_limit.filter(|limit| *limit > 0) {
+            usages.push(BudgetUsage::new(period, BudgetMetric::Tokens, start, tokens as f64, limit as f64));
+        }
+    };
+    add(BudgetPeriod::Daily, day_start, day.cost_usd, day.usage.total(), budgets.daily_usd, budgets.daily_tokens);
+    add(BudgetPeriod::Weekly, week_start, week.cost_usd, week.usage.total(), budgets.weekly_usd, budgets.weekly_tokens);
+    if let Some(block) = block {
+        add(BudgetPeriod::Block, block.block_start, block.block_cost_usd, block.block_tokens, budgets.block_usd, budgets.block_tokens);
+    }
+    usages
+}
+
+/// Current consumption of the configured budgets, from usage since the week (or an
+/// active block that began before it) started
+pub fn current(config: &Config) -> Result<Vec<BudgetUsage>, Box<dyn Error>> {
+    let now = Utc::now();
+    let (_, week_start) = period_starts(now);
+    let block_lookback = timerange::start_of_day((now - Duration::hours(burn::BLOCK_HOURS)).with_timezone(&Local).date_naive());
+    let records = usage::load_records(&TimeRange { since: Some(week_start.min(block_lookback)), until: None })?;
+    let pricing = PricingTable::with_overrides(&config.pricing);
+    Ok(measure(&config.budgets, &records, &config.limits, &pricing, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::TokenUsage;
+    use chrono::NaiveDate;
+
+    fn record(timestamp: DateTime<Utc>, output_tokens: u64) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            session_id: None,
+            cwd: None,
+            model: "claude-sonnet-4".to_string(),
+            usage: TokenUsage { output_tokens, ..Default::default() },
+            dedup_key: None,
+            project: None,
+        }
+    }
+
+    #[test]
+    fn test_thresholds_alert_once_per_period() {
+        // Local noon, so records a second earlier fall on the same day
+        let noon = NaiveDate::from_ymd_opt(2025, 7, 16).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let now = noon.and_local_timezone(Local).unwrap().with_timezone(&Utc);
+        let budgets = BudgetsConfig { daily_usd: Some(20.0), daily_tokens: Some(2_000_000), ..Default::default() };
+        let pricing = PricingTable::builtin();
+        let limits = LimitsConfig::default();
+        let mut crossings = Crossings::default();
+
+        // $15 of output at sonnet prices is 75% of the daily budget
+        let mut records = vec![record(now - Duration::seconds(1), 1_000_000)];
+        let usages = measure(&budgets, &records, &limits, &pricing, now);
+        assert_eq!(usages.len(), 2);
+        assert_eq!(usages[0].describe(), "$15.00 of $20.00 (75%)");
+        assert_eq!(usages[1].describe(), "1.0M of 2.0M (50%)");
+        assert!(crossings.update(&usages, &[80.0, 100.0]).is_empty());
+
+        records.push(record(now - Duration::seconds(1), 100_000));
+        let usages = measure(&budgets, &records, &limits, &pricing, now);
+        let alerts = crossings.update(&usages, &[80.0, 100.0]);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity(), Severity::Warn);
+        assert_eq!(alerts[0].message(), "Daily cost budget at 80%: $16.50 of $20.00 (82%)");
+        assert!(crossings.update(&usages, &[80.0, 100.0]).is_empty());
+
+        records.push(record(now - Duration::seconds(1), 1_000_000));
+        let usages = measure(&budgets, &records, &limits, &pricing, now);
+        let alerts = crossings.update(&usages, &[80.0, 100.0]);
+        assert_eq!(alerts.iter().map(|alert| alert.severity()).collect::<Vec<_>>(), vec![Severity::Error, Severity::Error]);
+        assert!(alerts[0].message().starts_with("Daily cost budget exceeded"));
+
+        // A new day starts over
+        let mut tomorrow = usages.clone();
+        tomorrow.iter_mut().for_each(|usage| usage.period_start += Duration::days(1));
+        assert_eq!(crossings.update(&tomorrow, &[80.0, 100.0]).len(), 2);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::config::{Config, LimitsConfig};
+use crate::cost::CostSummary;
+use crate::pricing::PricingTable;
+use crate::timerange::{self, TimeRange};
+use crate::transcript::UsageRecord;
+use crate::usage;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Length of a Claude usage block
+pub const BLOCK_HOURS: i64 = 5;
+
+/// A contiguous usage block (starts on the hour, lasts five hours)
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+    pub records: Vec<UsageRecord>,
+}
+
+/// Current block consumption, velocity, and limit projection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BurnSnapshot {
+    pub block_start: DateTime<Utc>,
+    pub block_end: DateTime<Utc>,
+    pub block_tokens: u64,
+    pub block_cost_usd: f64,
+    pub tokens_per_minute: f64,
+    pub cost_per_hour: f64,
+    /// Token limit used for projection (configured or largest previous block)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_limit: Option<u64>,
+    /// When the limit will be reached at the current rate, if before the block resets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_at: Option<DateTime<Utc>>,
+}
+
+/// Split records into usage blocks; a new block begins after five hours or a five-hour gap
+pub fn blocks(records: &[UsageRecord]) -> Vec<Block> {
+    let mut sorted: Vec<&UsageRecord> = records.iter().collect();
+    sorted.sort_by_key(|record| record.timestamp);
+
+    let block_len = Duration::hours(BLOCK_HOURS);
+    let mut blocks: Vec<Block> = Vec::new();
+    for record in sorted {
+        let starts_new = match blocks.last() {
+            Some(block) => record.timestamp >= block.end || record.timestamp - block.last_activity >= block_len,
+            None => true,
+        };
+        if starts_new {
+            let start = record.timestamp.duration_trunc(Duration::hours(1)).unwrap_or(record.timestamp);
+            blocks.push(Block {
+                start,
+                end: start + block_len,
+                last_activity: record.timestamp,
+                records: Vec::new(),
+            });
+        }
+        let block = blocks.last_mut().expect("block just ensured");
+        block.last_activity = record.timestamp;
+        block.records.push(record.clone());
+    }
+    blocks
+}
+
+/// Compute burn rate and projection for the block active at `now`, if any
+pub fn snapshot(
+    records: &[UsageRecord],
+    limits: &LimitsConfig,
+    pricing: &PricingTable,
+    now: DateTime<Utc>,
+) -> Option<BurnSnapshot> {
+    let all_blocks = blocks(records);
+    let (active, previous) = all_blocks.split_last()?;
+    if now >= active.end || now - active.last_activity >= Duration::hours(BLOCK_HOURS) {
+        return None;
+    }
+
+    let block_summary = CostSummary::from_records(&active.records, pricing);
+
+    // Velocity over the trailing window, or since the block started if it is younger
+    let window_start = (now - Duration::minutes(limits.burn_window_minutes as i64)).max(active.start);
+    let window_minutes = ((now - window_start).num_seconds() as f64 / 60.0).max(1.0);
+    let recent = CostSummary::from_records(active.records.iter().filter(|r| r.timestamp >= window_start), pricing);
+    let tokens_per_minute = recent.usage.total() as f64 / window_minutes;
+    let cost_per_hour = recent.cost_usd / window_minutes * 60.0;
+
+    let token_limit = limits.block_tokens.or_else(|| {
+        previous
+            .iter()
+            .map(|block| block.records.iter().map(|r| r.usage.total()).sum::<u64>())
+            .max()
+    });
+    let limit_at = token_limit.and_then(|limit| {
+        let used = block_summary.usage.total();
+        if used >= limit {
+            return Some(now);
+        }
+        if tokens_per_minute <= 0.0 {
+            return None;
+        }
+        let minutes = (limit - used) as f64 / tokens_per_minute;
+        let at = now + Duration::seconds((minutes * 60.0) as i64);
+        (at < active.end).then_some(at)
+    });
+
+    Some(BurnSnapshot {
+        block_start: active.start,
+        block_end: active.end,
+        block_tokens: block_summary.usage.total(),
+        block_cost_usd: block_summary.cost_usd,
+        tokens_per_minute,
+        cost_per_hour,
+        token_limit,
+        limit_at,
+    })
+}
+
+/// Burn snapshot for the active block using configured limits and pricing
+pub fn current(config: &Config) -> Option<BurnSnapshot> {
+    // A block that is still active started at most five hours ago, possibly yesterday
+    let since = (chrono::Local::now() - Duration::hours(BLOCK_HOURS)).date_naive();
+    let range = TimeRange { since: Some(timerange::start_of_day(since)), until: None };
+    let records = usage::load_records(&range).ok()?;
+    let pricing = PricingTable::with_overrides(&config.pricing);
+    snapshot(&records, &config.limits, &pricing, Utc::now())
+}
+
+/// The latest burn snapshot, worked out again only once it is older than `max_age`,
+/// so a batch of events does not read every transcript for each one
+pub struct CachedBurn {
+    max_age: std::time::Duration,
+    latest: Option<(Instant, Option<BurnSnapshot>)>,
+}
+
+impl CachedBurn {
+    pub fn new(max_age: std::time::Duration) -> Self {
+        Self { max_age, latest: None }
+    }
+
+    /// The snapshot taken within `max_age` of `now`, else the one `compute` returns
+    pub fn get(&mut self, now: Instant, compute: impl FnOnce() -> Option<BurnSnapshot>) -> Option<BurnSnapshot> {
+        match &self.latest {
+            Some((taken, snapshot)) if now.duration_since(*taken) < self.max_age => snapshot.clone(),
+            _ => {
+                let snapshot = compute();
+                self.latest = Some((now, snapshot.clone()));
+                snapshot
+            }
+        }
+    }
+}
+
+/// Multi-line live view of the current block for `usage --live`
+pub fn render_live(snapshot: Option<&BurnSnapshot>, now: DateTime<Utc>) -> String {
+    let Some(burn) = snapshot else {
+        return "No active usage block\n".to_string();
+    };
+    let local = |time: DateTime<Utc>| time.with_timezone(&chrono::Local).format("%H:%M").to_string();
+    let remaining = burn.block_end - now;
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "Block:      {} - {} ({}h {:02}m left)\n",
+        local(burn.block_start),
+        local(burn.block_end),
+        remaining.num_hours(),
+        remaining.num_minutes() % 60
+    ));
+    out.push_str(&format!("Used:       {} tokens (${:.2})\n", burn.block_tokens, burn.block_cost_usd));
+    out.push_str(&format!(
+        "Burn rate:  {:.0} tokens/min (${:.2}/h)\n",
+        burn.tokens_per_minute, burn.cost_per_hour
+    ));
+    match (burn.token_limit, burn.limit_at) {
+        (Some(limit), Some(at)) => out.push_str(&format!(
+            "Projection: {} token limit reached at {} (in {}m)\n",
+            limit,
+            local(at),
+            (at - now).num_minutes().max(0)
+        )),
+        (Some(limit), None) => out.push_str(&format!("Projection: within {} token limit until block resets\n", limit)),
+        (None, _) => out.push_str("Projection: no token limit known (set limits.block_tokens)\n"),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::TokenUsage;
+
+    /// Sonnet usage record of `tokens` output tokens at a UTC timestamp
+    fn record(timestamp: &str, tokens: u64) -> UsageRecord {
+        UsageRecord {
+            timestamp: timestamp.parse().unwrap(),
+            session_id: None,
+            cwd: None,
+            model: "claude-sonnet-4".to_string(),
+            usage: TokenUsage { output_tokens: tokens, ..Default::default() },
+            dedup_key: None,
+            project: None,
+        }
+    }
+
+    #[test]
+    fn test_blocks_split_on_duration_and_gaps() {
+        let records = vec![
+            record("2025-07-20T09:30:00Z", 10),
+            record("2025-07-20T13:59:00Z", 10),
+            record("2025-07-20T14:10:00Z", 10),
+            record("2025-07-21T08:00:00Z", 10),
+        ];
+        let blocks = blocks(&records);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].start, "2025-07-20T09:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(blocks[0].records.len(), 2);
+        assert_eq!(blocks[1].start, "2025-07-20T14:00:00Z".parse::<DateTime<Utc>>().unwrap());
+    }
+
+    #[test]
+    fn test_projection_uses_window_rate() {
+        let records = vec![
+            record("2025-07-20T10:05:00Z", 1_000),
+            record("2025-07-20T11:55:00Z", 5_000),
+            record("2025-07-20T11:58:00Z", 5_000),
+        ];
+        let limits = LimitsConfig { block_tokens: Some(111_000), burn_window_minutes: 10, ..Default::default() };
+        let now = "2025-07-20T12:00:00Z".parse().unwrap();
+        let burn = snapshot(&records, &limits, &PricingTable::builtin(), now).unwrap();
+
+        assert_eq!(burn.block_tokens, 11_000);
+        assert!((burn.tokens_per_minute - 1_000.0).abs() < 1e-9);
+        // 100k tokens remaining at 1k/min
+        assert_eq!(burn.limit_at, Some(now + Duration::minutes(100)));
+    }
+
+    #[test]
+    fn test_no_active_block_after_reset() {
+        let records = vec![record("2025-07-20T10:05:00Z", 1_000)];
+        let now = "2025-07-20T15:01:00Z".parse().unwrap();
+        assert!(snapshot(&records, &LimitsConfig::default(), &PricingTable::builtin(), now).is_none());
+    }
+
+    #[test]
+    fn test_limit_defaults_to_largest_previous_block() {
+        let records = vec![
+            record("2025-07-19T10:00:00Z", 50_000),
+            record("2025-07-20T11:58:00Z", 1_000),
+        ];
+        let now = "2025-07-20T12:00:00Z".parse().unwrap();
+        let burn = snapshot(&records, &LimitsConfig::default(), &PricingTable::builtin(), now).unwrap();
+        assert_eq!(burn.token_limit, Some(50_000));
+        assert!(burn.limit_at.is_none());
+    }
+
+    #[test]
+    fn test_cached_burn_is_recomputed_once_stale() {
+        let mut cached = CachedBurn::new(std::time::Duration::from_secs(1));
+        let start = Instant::now();
+        let computed = std::cell::Cell::new(0);
+        let compute = || {
+            computed.set(computed.get() + 1);
+            None
+        };
+        cached.get(start, compute);
+        cached.get(start + std::time::Duration::from_millis(500), compute);
+        assert_eq!(computed.get(), 1);
+        cached.get(start + std::time::Duration::from_secs(1), compute);
+        assert_eq!(computed.get(), 2);
+    }
+}
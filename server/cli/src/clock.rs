@@ -0,0 +1,78 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Clock differences beyond this are logged and flagged by `status`
+pub const SKEW_WARN_MS: i64 = 2_000;
+
+/// Wall-clock time and monotonic instant the process clock was started at
+static ANCHOR: OnceLock<(DateTime<Utc>, Instant)> = OnceLock::new();
+
+/// A moment on both the wall clock and the process's monotonic clock. Durations are
+/// taken from `mono_ms`, which keeps counting steadily when the system clock is changed
+/// or stepped by NTP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stamp {
+    pub wall: DateTime<Utc>,
+    /// Milliseconds since the process clock was started
+    pub mono_ms: u64,
+}
+
+impl Stamp {
+    /// The current moment
+    pub fn now() -> Self {
+        let (_, started) = anchor();
+        Self { wall: Utc::now(), mono_ms: started.elapsed().as_millis() as u64 }
+    }
+
+    /// Milliseconds from `earlier` to this stamp on the monotonic clock
+    pub fn since(&self, earlier: Stamp) -> u64 {
+        self.mono_ms.saturating_sub(earlier.mono_ms)
+    }
+}
+
+/// Start the process clock on first use
+fn anchor() -> (DateTime<Utc>, Instant) {
+    *ANCHOR.get_or_init(|| (Utc::now(), Instant::now()))
+}
+
+/// Stamp of the moment the process clock was started (monotonic zero)
+pub fn start() -> Stamp {
+    Stamp { wall: anchor().0, mono_ms: 0 }
+}
+
+/// How far the wall clock has been moved since `earlier`, relative to the monotonic
+/// clock (positive: set forward)
+pub fn wall_jump_ms(earlier: Stamp, now: Stamp) -> i64 {
+    (now.wall - earlier.wall).num_milliseconds() - now.since(earlier) as i64
+}
+
+/// How far a device clock is ahead of the host (negative: behind), from an exchange
+/// where the request went out at `sent`, the device reported `device`, and the reply
+/// arrived at `received`. The device is assumed to have read its clock halfway through.
+pub fn skew_ms(sent: Stamp, device: DateTime<Utc>, received: Stamp) -> i64 {
+    let midpoint = sent.wall + Duration::milliseconds(received.since(sent) as i64 / 2);
+    (device - midpoint).num_milliseconds()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stamp at a wall time given as seconds past a fixed instant
+    fn stamp(wall_secs: i64, mono_ms: u64) -> Stamp {
+        let base: DateTime<Utc> = "2025-07-20T10:00:00Z".parse().unwrap();
+        Stamp { wall: base + Duration::seconds(wall_secs), mono_ms }
+    }
+
+    #[test]
+    fn test_wall_jump_and_device_skew() {
+        // Ten monotonic seconds passed but the wall clock moved 70s: stepped forward a minute
+        assert_eq!(wall_jump_ms(stamp(0, 5_000), stamp(70, 15_000)), 60_000);
+        assert_eq!(wall_jump_ms(stamp(0, 0), stamp(10, 10_000)), 0);
+
+        // Round trip of 400ms; the device read its clock 3s ahead of the midpoint
+        let device = stamp(3, 0).wall + Duration::milliseconds(200);
+        assert_eq!(skew_ms(stamp(0, 1_000), device, stamp(0, 1_400)), 3_000);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::payload::{
+    DevicePayload, Severity, ACKNOWLEDGE_EVENT, ALERT_EVENT, DND_EVENT, MAINTENANCE_EVENT, PAGE_EVENT,
+    PERMISSION_EVENT, PRESENCE_EVENT, TIME_SYNC_EVENT,
+};
+use crate::settings::SETTINGS_EVENT;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Frames that set up or steer the gadget rather than update its display
+const CONTROL_EVENTS: [&str; 8] = [
+    "LinkMode",
+    MAINTENANCE_EVENT,
+    PRESENCE_EVENT,
+    DND_EVENT,
+    PAGE_EVENT,
+    TIME_SYNC_EVENT,
+    ACKNOWLEDGE_EVENT,
+    SETTINGS_EVENT,
+];
+
+/// Whether a frame sets up or steers the gadget rather than updating its display
+pub fn is_control(payload: &DevicePayload) -> bool {
+    CONTROL_EVENTS.contains(&payload.event.as_str())
+}
+
+/// Whether a frame reports a tool call, the bulk of a busy session's traffic
+pub fn is_tool_event(payload: &DevicePayload) -> bool {
+    matches!(payload.event.as_str(), "PreToolUse" | "PostToolUse")
+}
+
+/// Whether a frame skips the rate limit: control frames, alerts, permission requests,
+/// and anything of warn severity or above
+pub fn is_urgent(payload: &DevicePayload) -> bool {
+    is_control(payload)
+        || payload.event == ALERT_EVENT
+        || payload.event == PERMISSION_EVENT
+        || payload.severity.is_some_and(|severity| severity >= Severity::Warn)
+}
+
+/// Whether a frame still goes to the gadget during do-not-disturb: control frames,
+/// usage updates, permission requests Claude is blocked on, and anything of error severity
+pub fn is_critical(payload: &DevicePayload) -> bool {
+    is_control(payload)
+        || payload.event == "Usage"
+        || payload.event == PERMISSION_EVENT
+        || payload.severity == Some(Severity::Error)
+}
+
+/// Fold a tool event into the one at the back of the queue when both come from the
+/// same session and screen, so a burst is sent as its latest call. Returns whether
+/// `payload` was merged; otherwise the caller queues it as usual.
+pub fn merge(queue: &mut VecDeque<DevicePayload>, payload: &mut DevicePayload) -> bool {
+    let Some(last) = queue.back_mut() else {
+        return false;
+    };
+    let mergeable = is_tool_event(payload)
+        && is_tool_event(last)
+        && last.session_id == payload.session_id
+        && last.background == payload.background;
+    if mergeable {
+        payload.merged = last.merged + 1;
+        *last = payload.clone();
+    }
+    mergeable
+}
+
+/// Replace the frames held while the gadget was offline for `offline_secs` with one
+/// frame summing them up, so it is not made to replay stale events. Control frames
+/// still go out first, and when Claude is `still_waiting` for permission the latest
+/// request keeps its own frame, last; usage is dropped, as the next update replaces
+/// it. Returns how many frames were summed up.
+pub fn catch_up(queue: &mut VecDeque<DevicePayload>, still_waiting: bool, offline_secs: u64) -> usize {
+    let mut kept = VecDeque::new();
+    let mut missed = Vec::new();
+    let mut request = None;
+    for frame in queue.drain(..) {
+        match frame.event.as_str() {
+            "Usage" => {}
+            PERMISSION_EVENT if still_waiting => {
+                missed.extend(request.replace(frame));
+            }
+            event if CONTROL_EVENTS.contains(&event) => kept.push_back(frame),
+            _ => missed.push(frame),
+        }
+    }
+    if !missed.is_empty() {
+        kept.push_back(DevicePayload::catch_up(&missed, offline_secs));
+    }
+    kept.extend(request);
+    *queue = kept;
+    missed.len()
+}
+
+/// Spaces out regular device updates so a busy session does not flood the link
+#[derive(Debug)]
+pub struct Throttle {
+    interval: Duration,
+    last_sent: Option<Instant>,
+}
+
+impl Throttle {
+    /// At most one regular frame per `interval`; zero sends every frame at once
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last_sent: None }
+    }
+
+    /// When the next regular frame may go out
+    pub fn ready_at(&self) -> Option<Instant> {
+        self.last_sent.map(|sent| sent + self.interval)
+    }
+
+    /// Whether a regular frame may go out at `now`
+    pub fn allows(&self, now: Instant) -> bool {
+        self.ready_at().is_none_or(|ready| now >= ready)
+    }
+
+    /// Note a regular frame sent at `now`
+    pub fn record(&mut self, now: Instant) {
+        self.last_sent = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_event(event: &str, tool: &str, session: &str) -> DevicePayload {
+        let mut payload = DevicePayload::usage(Default::default());
+        payload.event = event.to_string();
+        payload.tool = Some(tool.to_string());
+        payload.session_id = Some(session.to_string());
+        payload
+    }
+
+    #[test]
+    fn test_consecutive_tool_events_merge() {
+        let mut queue = VecDeque::new();
+        for (event, tool) in [("PreToolUse", "Read"), ("PostToolUse", "Read"), ("PreToolUse", "Edit")] {
+            let mut payload = tool_event(event, tool, "alpha");
+            if !merge(&mut queue, &mut payload) {
+                queue.push_back(payload);
+            }
+        }
+        assert_eq!(queue.len(), 1);
+        assert_eq!((queue[0].tool.as_deref(), queue[0].merged), (Some("Edit"), 2));
+
+        // Another session, or anything but a tool event, starts a new frame
+        let mut other = tool_event("PreToolUse", "Bash", "beta");
+        assert!(!merge(&mut queue, &mut other));
+        queue.push_back(other);
+        let mut stop = tool_event("Stop", "Bash", "beta");
+        stop.tool = None;
+        assert!(!merge(&mut queue, &mut stop));
+    }
+
+    #[test]
+    fn test_urgent_frames_skip_the_throttle() {
+        let mut notification = tool_event("Notification", "", "alpha");
+        notification.severity = Some(Severity::Info);
+        assert!(!is_urgent(&notification));
+        notification.severity = Some(Severity::Warn);
+        assert!(is_urgent(&notification));
+        assert!(is_urgent(&DevicePayload::time_sync()));
+        assert!(!is_urgent(&DevicePayload::usage(Default::default())));
+    }
+
+    #[test]
+    fn test_throttle_spaces_regular_frames() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(Duration::from_secs(2));
+        assert!(throttle.allows(start));
+        throttle.record(start);
+        assert!(!throttle.allows(start + Duration::from_secs(1)));
+        assert!(throttle.allows(start + Duration::from_secs(2)));
+
+        let mut unthrottled = Throttle::new(Duration::ZERO);
+        unthrottled.record(start);
+        assert!(unthrottled.allows(start));
+    }
+
+    #[test]
+    fn test_catch_up_sums_up_stale_frames() {
+        let mut queue = VecDeque::from([
+            DevicePayload::link_mode(Default::default()),
+            tool_event("PreToolUse", "Bash", "s1"),
+            DevicePayload::usage(Default::default()),
+            tool_event("Stop", "", "s1"),
+            tool_event(PERMISSION_EVENT, "Edit", "s2"),
+            tool_event("PostToolUse", "Bash", "s1"),
+            tool_event(PERMISSION_EVENT, "Write", "s2"),
+        ]);
+        queue[1].merged = 4;
+        assert_eq!(catch_up(&mut queue, true, 750), 4);
+        let events: Vec<&str> = queue.iter().map(|frame| frame.event.as_str()).collect();
+        assert_eq!(events, vec!["LinkMode", crate::payload::CATCH_UP_EVENT, PERMISSION_EVENT]);
+        assert_eq!(queue[1].message.as_deref(), Some("Offline 12m: 5 PreToolUse, 1 Stop, 1 PostToolUse"));
+        assert_eq!(queue[1].merged, 8);
+        assert_eq!(queue[2].tool.as_deref(), Some("Write"));
+
+        // Nothing stale, nothing to sum up
+        let mut queue = VecDeque::from([DevicePayload::usage(Default::default())]);
+        assert_eq!(catch_up(&mut queue, false, 5), 0);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,1577 @@
+use crate::device::{AdapterChoice, LinkMode, PairedDevice, TransportKind};
+use crate::focus::FocusPolicy;
+use crate::fs_util;
+use crate::paths;
+use crate::payload::Severity;
+use crate::pricing::PriceOverride;
+use crate::storage::Backend;
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::error::{Classify, ExitCode};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Problems with the config file, a profile, or an environment override
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Invalid config {}: {message}", path.display())]
+    Invalid { path: PathBuf, message: String },
+    #[error("Invalid profile '{name}' in {}: {message}", path.display())]
+    InvalidProfile { name: String, path: PathBuf, message: String },
+    #[error("Unknown profile '{name}' (defined: {defined})")]
+    UnknownProfile { name: String, defined: String },
+    #[error("Invalid config override ({variables}): {message}")]
+    InvalidOverride { variables: String, message: String },
+    #[error("Invalid value for '{key}': {message}")]
+    InvalidValue { key: String, message: String },
+    #[error("Empty config key")]
+    EmptyKey,
+    #[error("Config key '{0}' is not a table")]
+    NotATable(String),
+}
+
+impl Classify for ConfigError {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::Config
+    }
+
+    fn hint(&self) -> Option<&str> {
+        match self {
+            ConfigError::Invalid { .. } | ConfigError::InvalidProfile { .. } => {
+                Some("Fix the file with `ccgadget config edit`, which validates it on save")
+            }
+            ConfigError::UnknownProfile { .. } => Some("Add a [profiles.<name>] table, or pick a defined profile"),
+            ConfigError::InvalidOverride { .. } => Some("Correct or unset the CCGADGET_* environment variables"),
+            _ => None,
+        }
+    }
+}
+
+/// Profile selected with `--profile` / `CCGADGET_PROFILE` for the rest of the process
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Select the named profile for every config load in this process
+pub fn set_profile(name: &str) {
+    let _ = PROFILE.set(name.to_string());
+}
+
+/// The selected profile, if any
+pub fn profile() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
+/// Whether a profile name can be used as a TOML key and in a file name
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Key under the selected profile's table (e.g. `profiles.work.device.alias`), or the key itself
+pub fn scoped_key(key: &str) -> String {
+    match profile() {
+        Some(name) => format!("profiles.{}.{}", name, key),
+        None => key.to_string(),
+    }
+}
+
+/// User configuration loaded from config.toml in the config directory
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Price overrides keyed by model family (opus/sonnet/haiku) or model ID
+    pub pricing: BTreeMap<String, PriceOverride>,
+    /// Plugin and derived metric definitions
+    pub metrics: MetricsConfig,
+    /// Device display settings
+    pub display: DisplayConfig,
+    /// Usage block limits for burn-rate projection
+    pub limits: LimitsConfig,
+    /// Cost and token budgets the daemon alerts on
+    pub budgets: BudgetsConfig,
+    /// How hook notifications are forwarded
+    pub notifications: NotificationsConfig,
+    /// Conditions the daemon shows as desktop notifications
+    pub desktop: DesktopConfig,
+    /// How notifications are classified by severity and how the device alerts for each
+    pub alerts: AlertsConfig,
+    /// Times the device is kept dark and silent
+    pub quiet_hours: QuietHoursConfig,
+    /// Whether and how submitted prompts are shown on the device
+    pub prompts: PromptsConfig,
+    /// Which of several concurrent sessions drives the device display
+    pub sessions: SessionsConfig,
+    /// User commands run after an event is processed
+    pub on_event: OnEventConfig,
+    /// Actions for device buttons, keyed by button (`a`) or long press (`a_long`), on
+    /// top of the defaults in [`Config::button_action`]
+    pub buttons: BTreeMap<String, ButtonAction>,
+    /// Device link settings
+    pub device: DeviceConfig,
+    /// Mirroring frames to devices added with `pair --add`
+    pub broadcast: BroadcastConfig,
+    /// Broker for the mqtt transport
+    pub mqtt: MqttConfig,
+    /// OpenTelemetry collector the daemon exports hook events to
+    pub otel: OtelConfig,
+    /// Background daemon settings
+    pub daemon: DaemonConfig,
+    /// Local HTTP and WebSocket API the daemon serves
+    pub api: ApiConfig,
+    /// How `trigger` runs inside a hook
+    pub trigger: TriggerConfig,
+    /// Trigger log settings
+    pub logs: LogsConfig,
+    /// What is kept out of trigger logs and device frames
+    pub redaction: RedactionConfig,
+    /// Which hook events are logged, sent, or dropped
+    pub routing: RoutingConfig,
+    /// Decisions `trigger` returns to Claude Code, e.g. to block tools in focus mode
+    pub responses: ResponsesConfig,
+    /// Activity icons for tool calls, on top of the built-in mapping
+    pub activity: ActivityConfig,
+    /// Named sets of overrides (e.g. `[profiles.work.device]`) applied on top of the
+    /// settings above when selected with `--profile`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+/// Device link settings
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DeviceConfig {
+    /// Name shown for the paired device instead of its advertised name
+    pub alias: Option<String>,
+    /// How the daemon reaches the device
+    pub transport: TransportKind,
+    /// Transports tried in order when `transport` cannot connect
+    pub fallback: Vec<TransportKind>,
+    /// Failed connection attempts over `transport` before the fallbacks are tried
+    pub fallback_after: u32,
+    /// The gadget's web server for the wifi transport, e.g. `http://192.168.1.40`
+    pub wifi_url: Option<String>,
+    /// Serial port of a tethered gadget (e.g. `/dev/ttyACM0`); detected when unset
+    pub serial_port: Option<String>,
+    /// Serial line speed
+    pub serial_baud: u32,
+    /// Use a gadget plugged in over USB ahead of `transport`
+    pub prefer_usb: bool,
+    /// BLE ATT MTU used to size chunks when the firmware does not report its own
+    pub ble_mtu: u16,
+    /// Bluetooth adapter for pairing, scanning, and the daemon; the first one when unset
+    pub adapter: Option<AdapterChoice>,
+    /// low-latency (default) or battery-saver
+    pub link_mode: LinkMode,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            alias: None,
+            transport: TransportKind::default(),
+            fallback: Vec::new(),
+            fallback_after: 3,
+            wifi_url: None,
+            serial_port: None,
+            serial_baud: 115_200,
+            prefer_usb: true,
+            ble_mtu: 185,
+            adapter: None,
+            link_mode: LinkMode::default(),
+        }
+    }
+}
+
+impl DeviceConfig {
+    /// Transports to try, preferred first, each once
+    pub fn transports(&self) -> Vec<TransportKind> {
+        let mut order = vec![self.transport];
+        for kind in &self.fallback {
+            if !order.contains(kind) {
+                order.push(*kind);
+            }
+        }
+        order
+    }
+}
+
+/// Mirroring frames to devices added with `pair --add`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BroadcastConfig {
+    /// Send every frame the paired device gets to the added devices as well
+    pub enabled: bool,
+    /// Only mirror to added devices in this group; all of them when unset
+    pub group: Option<String>,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self { enabled: true, group: None }
+    }
+}
+
+impl BroadcastConfig {
+    /// The added devices in `devices` frames are mirrored to
+    pub fn targets(&self, devices: Vec<PairedDevice>) -> Vec<PairedDevice> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        devices.into_iter().filter(|device| self.group.is_none() || device.group == self.group).collect()
+    }
+}
+
+/// Broker for the mqtt transport
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// `mqtt://host[:port]`, or `mqtts://host[:port]` for TLS
+    pub broker: Option<String>,
+    /// Frames are published to `<topic>/<event>`, e.g. `ccgadget/Usage`
+    pub topic: String,
+    /// Client id presented to the broker; `ccgadget-<pid>` when unset
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    /// Best kept out of the file with `CCGADGET_MQTT_PASSWORD`
+    pub password: Option<String>,
+    /// CA certificate (PEM) to verify the broker with; the system roots when unset
+    pub ca_file: Option<PathBuf>,
+    /// Client certificate (PEM) for brokers that require mutual TLS
+    pub client_cert: Option<PathBuf>,
+    /// Private key (PEM) for `client_cert`
+    pub client_key: Option<PathBuf>,
+    /// Publish Home Assistant discovery topics so the gadget appears there as sensors
+    pub homeassistant: bool,
+    /// Topic prefix Home Assistant watches for discovery messages
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            topic: "ccgadget".to_string(),
+            client_id: None,
+            username: None,
+            password: None,
+            ca_file: None,
+            client_cert: None,
+            client_key: None,
+            homeassistant: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// What the daemon does when a device button is pressed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ButtonAction {
+    /// Dismiss the alert the device is showing
+    Acknowledge,
+    /// Toggle maintenance mode, pausing regular transmissions
+    Pause,
+    /// Pin the display to the session after the focused one
+    NextSession,
+    /// Toggle focus mode, which `[responses]` rules can act on
+    FocusMode,
+    /// Keep the device from buzzing or flashing for `alerts.mute_minutes`; pressed
+    /// again, alerts come back
+    MuteAlerts,
+    /// Switch the device to its next display page
+    NextPage,
+    /// Have the hook deny the next tool call Claude makes
+    BlockNextTool,
+    /// Toggle do-not-disturb, as `ccgadget dnd on` and `off` do
+    Dnd,
+    /// Run a shell command with the button event as JSON on stdin
+    Command(String),
+    /// Do nothing, e.g. to turn off a default
+    Ignore,
+}
+
+impl std::fmt::Display for ButtonAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ButtonAction::Acknowledge => f.write_str("acknowledge"),
+            ButtonAction::Pause => f.write_str("pause"),
+            ButtonAction::NextSession => f.write_str("next-session"),
+            ButtonAction::FocusMode => f.write_str("focus-mode"),
+            ButtonAction::MuteAlerts => f.write_str("mute-alerts"),
+            ButtonAction::NextPage => f.write_str("next-page"),
+            ButtonAction::Dnd => f.write_str("dnd"),
+            ButtonAction::BlockNextTool => f.write_str("block-next-tool"),
+            ButtonAction::Command(command) => write!(f, "command `{}`", command),
+            ButtonAction::Ignore => f.write_str("ignore"),
+        }
+    }
+}
+
+/// Background daemon settings
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Seconds between periodic usage updates when `start --interval` is not given
+    pub interval_seconds: u64,
+    /// Always run as if `start --observe-only` was given
+    pub observe_only: bool,
+    /// Seconds without traffic before the daemon checks the device link is still up;
+    /// 0 turns the checks off
+    pub keepalive_seconds: u64,
+    /// Checks in a row the device may fail before it is marked offline and the link
+    /// is reopened
+    pub heartbeat_misses: u32,
+    /// Minutes without hook events before the device is told to show its idle screen;
+    /// 0 keeps the metrics up
+    pub presence_idle_minutes: u64,
+    /// Close the device link after this many minutes without events, reopening it on
+    /// the next one; kept open when unset
+    pub idle_timeout_minutes: Option<u64>,
+    /// Longest wait between reconnection attempts, which doubles from 1s up to this
+    pub reconnect_max_seconds: u64,
+    /// Shortest gap between regular device updates; tool events arriving in between
+    /// are merged. Alerts and warnings always go out at once. 0 sends every frame.
+    pub min_frame_interval_ms: u64,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: 30,
+            observe_only: false,
+            keepalive_seconds: 30,
+            heartbeat_misses: 3,
+            presence_idle_minutes: 5,
+            idle_timeout_minutes: None,
+            reconnect_max_seconds: 300,
+            min_frame_interval_ms: 2000,
+        }
+    }
+}
+
+/// Local API for dashboards, overlays, and editor extensions: `/status`, `/usage`,
+/// and an `/events` WebSocket, on the loopback interface only
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Web page origins (e.g. `http://localhost:3000`) allowed to use the API, or `*`
+    /// for any; requests without an `Origin` header, i.e. not from a browser, are always
+    /// served
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: 8787, allowed_origins: Vec::new() }
+    }
+}
+
+/// How `trigger` runs inside a hook
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TriggerConfig {
+    /// Hand each event to a background process and return at once, so hooks never
+    /// hold up Claude Code; `trigger --wait` always runs in the foreground
+    pub detach: bool,
+    /// Longest a detaching trigger spends deciding a hook response, in milliseconds
+    pub deadline_ms: u64,
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self { detach: true, deadline_ms: 100 }
+    }
+}
+
+/// Trigger log settings
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogsConfig {
+    /// Daily trigger logs older than this many days are deleted; 0 keeps them forever
+    pub retention_days: u64,
+    /// Log filter used when `--log-level` is not given, e.g. `info,btleplug=debug`
+    pub level: Option<String>,
+    /// Where trigger log entries are stored: `jsonl` daily files or a `sqlite` database
+    pub backend: Backend,
+    /// Keep events, sessions, and usage in an indexed database (`metrics.db`) for
+    /// `usage` and `stats`, instead of reading every transcript each time
+    pub metrics_db: bool,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self { retention_days: 30, level: None, backend: Backend::Jsonl, metrics_db: true }
+    }
+}
+
+/// What is scrubbed from hook data before it is logged or sent to the device
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Hook input fields (e.g. `tool_input`, `prompt`) replaced with "[redacted]"
+    pub fields: Vec<String>,
+    /// Extra regexes whose matches are redacted; a `secret` group limits it to that part
+    pub patterns: Vec<String>,
+    /// Redact common secrets (API keys, tokens, private keys, passwords) out of the box
+    pub builtin: bool,
+    /// Prompts longer than this are cut before they are logged or sent
+    pub max_prompt_chars: Option<usize>,
+}
+
+impl RedactionConfig {
+    /// Fail on a pattern that is not a valid regex, rather than silently logging secrets
+    pub fn check(&self) -> Result<(), ConfigError> {
+        for pattern in &self.patterns {
+            regex::Regex::new(pattern).map_err(|e| ConfigError::InvalidValue {
+                key: "redaction.patterns".to_string(),
+                message: format!("{}: {}", pattern, e),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self { fields: Vec::new(), patterns: Vec::new(), builtin: true, max_prompt_chars: None }
+    }
+}
+
+/// What happens to a hook event
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RouteAction {
+    /// Log the event and send it to the device
+    #[default]
+    Send,
+    /// Log the event only
+    Log,
+    /// Neither log nor send the event
+    Drop,
+}
+
+impl fmt::Display for RouteAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RouteAction::Send => "send",
+            RouteAction::Log => "log",
+            RouteAction::Drop => "drop",
+        })
+    }
+}
+
+/// One routing rule; a rule without `event` or `tool` matches any
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RouteRule {
+    /// Hook event name, e.g. `PreToolUse`
+    pub event: Option<String>,
+    /// Tool name regex, matched in full as in Claude Code matchers (`Edit|Write`)
+    pub tool: Option<String>,
+    pub action: RouteAction,
+}
+
+/// Whether an event passes a rule's optional `event` name and `tool` regex
+fn event_matches(wanted_event: Option<&str>, tool_pattern: Option<&str>, event: Option<&str>, tool: Option<&str>) -> bool {
+    let event_matches = wanted_event.is_none_or(|wanted| event == Some(wanted));
+    let tool_matches = match (tool_pattern, tool) {
+        (None, _) => true,
+        (Some(pattern), Some(tool)) => regex::Regex::new(&format!("^(?:{})$", pattern)).is_ok_and(|re| re.is_match(tool)),
+        (Some(_), None) => false,
+    };
+    event_matches && tool_matches
+}
+
+/// Fail on a rule's tool pattern that is not a valid regex, which would never match
+fn check_tool_patterns<'a>(key: &str, patterns: impl Iterator<Item = &'a str>) -> Result<(), ConfigError> {
+    for pattern in patterns {
+        regex::Regex::new(pattern).map_err(|e| ConfigError::InvalidValue {
+            key: key.to_string(),
+            message: format!("{}: {}", pattern, e),
+        })?;
+    }
+    Ok(())
+}
+
+/// Which hook events are logged, sent to the device, or dropped
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct RoutingConfig {
+    /// Action for events no rule matches
+    pub default: RouteAction,
+    /// Rules checked in order; the first that matches decides
+    pub rules: Vec<RouteRule>,
+}
+
+impl RoutingConfig {
+    /// Action for an event, by its name and the tool it concerns
+    pub fn route(&self, event: Option<&str>, tool: Option<&str>) -> RouteAction {
+        self.rules
+            .iter()
+            .find(|rule| event_matches(rule.event.as_deref(), rule.tool.as_deref(), event, tool))
+            .map_or(self.default, |rule| rule.action)
+    }
+
+    /// Fail on a tool pattern that is not a valid regex, which would never match
+    pub fn check(&self) -> Result<(), ConfigError> {
+        check_tool_patterns("routing.rules", self.rules.iter().filter_map(|rule| rule.tool.as_deref()))
+    }
+}
+
+/// What Claude is busy with, shown as an icon on the device
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Activity {
+    /// Changing files
+    Editing,
+    /// Running a shell command
+    Running,
+    /// Searching or fetching from the web
+    Web,
+    /// Reading or searching files
+    Reading,
+    /// Working out what to do next
+    Thinking,
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Activity::Editing => "editing",
+            Activity::Running => "running",
+            Activity::Web => "web",
+            Activity::Reading => "reading",
+            Activity::Thinking => "thinking",
+        })
+    }
+}
+
+/// One activity rule; a rule without `tool` or `command` matches any tool call
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActivityRule {
+    /// Tool name regex, matched in full as in Claude Code matchers (`mcp__github__.*`)
+    pub tool: Option<String>,
+    /// Regex searched for in the tool input's `command`, e.g. `^cargo (test|build)`
+    pub command: Option<String>,
+    pub activity: Activity,
+}
+
+/// Activities for tool calls, checked before the built-in mapping
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ActivityConfig {
+    /// Rules checked in order; the first that matches decides
+    pub rules: Vec<ActivityRule>,
+}
+
+impl ActivityConfig {
+    /// Activity of the first rule matching a tool call
+    pub fn find(&self, tool: &str, command: Option<&str>) -> Option<Activity> {
+        self.rules
+            .iter()
+            .find(|rule| {
+                let command_matches = match (rule.command.as_deref(), command) {
+                    (None, _) => true,
+                    (Some(pattern), Some(command)) => regex::Regex::new(pattern).is_ok_and(|re| re.is_match(command)),
+                    (Some(_), None) => false,
+                };
+                event_matches(None, rule.tool.as_deref(), None, Some(tool)) && command_matches
+            })
+            .map(|rule| rule.activity)
+    }
+
+    /// Fail on a tool or command pattern that is not a valid regex
+    pub fn check(&self) -> Result<(), ConfigError> {
+        let patterns = self.rules.iter().flat_map(|rule| [rule.tool.as_deref(), rule.command.as_deref()]);
+        check_tool_patterns("activity.rules", patterns.flatten())
+    }
+}
+
+/// How `trigger` answers Claude Code when a response rule matches
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    /// Deny the tool call, or block the prompt or stop; other events get a warning
+    Block,
+    /// Ask the user to confirm the tool call; other events get a warning
+    Ask,
+    /// Show `reason` to the user and carry on
+    Warn,
+}
+
+/// One response rule; every condition given must hold
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseRule {
+    /// Hook event name, e.g. `PreToolUse`
+    pub event: Option<String>,
+    /// Tool name regex, matched in full as in Claude Code matchers
+    pub tool: Option<String>,
+    /// Only while focus mode is on (toggled by a `focus-mode` device button)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub focus_mode: bool,
+    /// Only once the session has cost more than this many dollars
+    pub session_cost_over: Option<f64>,
+    /// Only once today's usage has cost more than this many dollars
+    pub daily_cost_over: Option<f64>,
+    pub decision: Decision,
+    /// Why, shown to Claude or the user
+    pub reason: String,
+}
+
+/// What `trigger` knows about an event when evaluating response rules
+#[derive(Debug, Clone, Default)]
+pub struct ResponseContext<'a> {
+    pub event: Option<&'a str>,
+    pub tool: Option<&'a str>,
+    pub focus_mode: bool,
+    pub session_cost: Option<f64>,
+    pub daily_cost: Option<f64>,
+}
+
+/// Rules for answering hooks with a decision; without any, `trigger` prints no response
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ResponsesConfig {
+    /// Rules checked in order; the first that matches decides
+    pub rules: Vec<ResponseRule>,
+}
+
+impl ResponsesConfig {
+    /// The first rule matching the event, if any
+    pub fn find(&self, context: &ResponseContext) -> Option<&ResponseRule> {
+        let over = |cost: Option<f64>, limit: Option<f64>| limit.is_none_or(|limit| cost.is_some_and(|cost| cost > limit));
+        self.rules.iter().find(|rule| {
+            event_matches(rule.event.as_deref(), rule.tool.as_deref(), context.event, context.tool)
+                && (!rule.focus_mode || context.focus_mode)
+                && over(context.session_cost, rule.session_cost_over)
+                && over(context.daily_cost, rule.daily_cost_over)
+        })
+    }
+
+    /// Whether any rule depends on focus mode, which has to be asked of the daemon
+    pub fn needs_focus_mode(&self) -> bool {
+        self.rules.iter().any(|rule| rule.focus_mode)
+    }
+
+    /// Whether any rule depends on spend, which has to be estimated from transcripts
+    pub fn needs_cost(&self) -> bool {
+        self.rules.iter().any(|rule| rule.session_cost_over.is_some() || rule.daily_cost_over.is_some())
+    }
+
+    /// Fail on a tool pattern that is not a valid regex, which would never match
+    pub fn check(&self) -> Result<(), ConfigError> {
+        check_tool_patterns("responses.rules", self.rules.iter().filter_map(|rule| rule.tool.as_deref()))
+    }
+}
+
+/// Top-level sections that `CCGADGET_<SECTION>_<KEY>` environment variables can override
+const ENV_SECTIONS: [&str; 25] = [
+    "pricing",
+    "metrics",
+    "display",
+    "limits",
+    "budgets",
+    "notifications",
+    "desktop",
+    "alerts",
+    "quiet_hours",
+    "prompts",
+    "sessions",
+    "on_event",
+    "buttons",
+    "device",
+    "broadcast",
+    "mqtt",
+    "otel",
+    "daemon",
+    "api",
+    "trigger",
+    "logs",
+    "redaction",
+    "routing",
+    "responses",
+    "activity",
+];
+
+/// Dotted config key named by an environment variable, e.g.
+/// `CCGADGET_DEVICE_LINK_MODE` -> `device.link_mode`
+fn env_key(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("CCGADGET_")?.to_lowercase();
+    ENV_SECTIONS.iter().find_map(|section| {
+        let key = rest.strip_prefix(section)?.strip_prefix('_')?;
+        (!key.is_empty()).then(|| format!("{}.{}", section, key))
+    })
+}
+
+/// Config keys overridden by the environment, as (key, variable, raw value)
+pub fn env_overrides() -> Vec<(String, String, String)> {
+    let mut overrides: Vec<(String, String, String)> = std::env::vars()
+        .filter_map(|(name, value)| env_key(&name).map(|key| (key, name, value)))
+        .collect();
+    overrides.sort();
+    overrides
+}
+
+/// Parse a value given on the command line or in the environment: TOML syntax when it
+/// parses (numbers, booleans, arrays, quoted strings), otherwise a bare string
+pub fn parse_value(raw: &str) -> toml_edit::Value {
+    raw.trim()
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| toml_edit::Value::from(raw))
+}
+
+/// Set one dotted key in a TOML document, creating intermediate tables
+fn insert_value(document: &mut toml_edit::DocumentMut, key: &str, value: toml_edit::Value) -> Result<(), Box<dyn Error>> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let leaf = parts.pop().filter(|leaf| !leaf.is_empty()).ok_or(ConfigError::EmptyKey)?;
+    let mut table = document.as_table_mut();
+    for part in parts {
+        let item = table.entry(part).or_insert_with(|| {
+            // Implicit so `profiles.work.device` doesn't also write empty `[profiles]` headers
+            let mut created = toml_edit::Table::new();
+            created.set_implicit(true);
+            toml_edit::Item::Table(created)
+        });
+        table = item
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::NotATable(part.to_string()))?;
+    }
+    table.insert(leaf, toml_edit::value(value));
+    Ok(())
+}
+
+/// A raw environment value as a TOML value, using the same rules as `parse_value`
+fn toml_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("v = {}", parse_value(raw)))
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Set one dotted key in a TOML table, creating intermediate tables
+fn set_dotted(table: &mut toml::Table, key: &str, value: toml::Value) -> Result<(), Box<dyn Error>> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let leaf = parts.pop().filter(|leaf| !leaf.is_empty()).ok_or(ConfigError::EmptyKey)?;
+    let mut table = table;
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::NotATable(part.to_string()))?;
+    }
+    table.insert(leaf.to_string(), value);
+    Ok(())
+}
+
+/// Recursively overlay `overlay` onto `base`; tables merge, anything else replaces
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge_tables(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Apply a profile's overrides to a parsed config file
+fn apply_profile(table: &mut toml::Table, name: &str) -> Result<(), Box<dyn Error>> {
+    let profiles = table.get("profiles").and_then(toml::Value::as_table);
+    let Some(overlay) = profiles.and_then(|profiles| profiles.get(name)).and_then(toml::Value::as_table) else {
+        let defined: Vec<&str> = profiles.map(|p| p.keys().map(String::as_str).collect()).unwrap_or_default();
+        let defined = if defined.is_empty() { "none".to_string() } else { defined.join(", ") };
+        return Err(ConfigError::UnknownProfile { name: name.to_string(), defined }.into());
+    };
+    let mut overlay = overlay.clone();
+    overlay.remove("profiles");
+    merge_tables(table, overlay);
+    Ok(())
+}
+
+/// Flatten a TOML table into `dotted.key = value` pairs
+fn flatten(prefix: &str, table: &toml::Table, out: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::Table(inner) => flatten(&key, inner, out),
+            value => out.push((key, value.to_string())),
+        }
+    }
+}
+
+/// Commands keyed by hook event name (e.g. `Stop = "say done"`), given the payload on stdin
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OnEventConfig {
+    /// Kill a command still running after this long
+    pub timeout_seconds: u64,
+    /// Commands allowed to run at once; further events are skipped
+    pub max_concurrent: usize,
+    #[serde(flatten)]
+    pub commands: BTreeMap<String, String>,
+}
+
+impl Default for OnEventConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 10,
+            max_concurrent: 4,
+            commands: BTreeMap::new(),
+        }
+    }
+}
+
+/// Notification forwarding behaviour
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Batch notifications into a digest sent every N minutes (and on Stop);
+    /// permission requests are still sent immediately. Unset sends each one.
+    pub digest_minutes: Option<u64>,
+}
+
+/// Conditions the daemon shows as desktop notifications
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DesktopConfig {
+    /// The device went offline
+    pub disconnected: bool,
+    /// A budget is used up; `budgets.desktop_notification` also covers the thresholds
+    /// before that
+    pub budget_exceeded: bool,
+    /// Claude has waited for permission this many seconds, so nobody is at the
+    /// gadget to answer; 0 turns it off
+    pub permission_after_seconds: u64,
+    /// The device reports a firmware update it can install
+    pub firmware_update: bool,
+}
+
+impl Default for DesktopConfig {
+    fn default() -> Self {
+        Self {
+            disconnected: true,
+            budget_exceeded: true,
+            permission_after_seconds: 60,
+            firmware_update: true,
+        }
+    }
+}
+
+/// Message patterns that mark a notification above `info`, matched case-insensitively
+const ALERT_PATTERNS: [(&str, Severity); 4] = [
+    ("permission", Severity::Warn),
+    ("rate limit", Severity::Error),
+    ("usage limit", Severity::Error),
+    ("limit reached", Severity::Error),
+];
+
+/// How the device's buzzer sounds for an alert
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Buzz {
+    #[default]
+    None,
+    Short,
+    Long,
+    /// Repeated short buzzes until acknowledged
+    Pulse,
+}
+
+/// How the device draws attention to a notification
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct AlertStyle {
+    pub buzz: Buzz,
+    /// Flash the screen backlight
+    pub flash: bool,
+}
+
+impl AlertStyle {
+    /// Whether the style does nothing, so no alert frame is worth sending
+    pub fn is_silent(&self) -> bool {
+        self.buzz == Buzz::None && !self.flash
+    }
+}
+
+/// Notification severities and the alert for each
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// Message patterns and the severity they mark, on top of the built-in ones
+    /// (permission requests warn, rate and usage limits are errors)
+    pub patterns: BTreeMap<String, Severity>,
+    pub info: AlertStyle,
+    pub warn: AlertStyle,
+    pub error: AlertStyle,
+    /// Alert for a permission request, which blocks Claude until it is answered
+    pub permission: AlertStyle,
+    /// How long a `mute-alerts` button press keeps the device quiet
+    pub mute_minutes: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            patterns: BTreeMap::new(),
+            info: AlertStyle::default(),
+            warn: AlertStyle { buzz: Buzz::Short, flash: true },
+            error: AlertStyle { buzz: Buzz::Pulse, flash: true },
+            permission: AlertStyle { buzz: Buzz::Long, flash: true },
+            mute_minutes: 30,
+        }
+    }
+}
+
+impl AlertsConfig {
+    /// Severity of a notification message: the highest of the patterns it contains,
+    /// `info` when none match. A configured pattern replaces a built-in one.
+    pub fn classify(&self, message: &str) -> Severity {
+        let message = message.to_lowercase();
+        let builtin = ALERT_PATTERNS
+            .iter()
+            .filter(|(pattern, _)| !self.patterns.contains_key(*pattern))
+            .map(|(pattern, severity)| (pattern.to_string(), *severity));
+        let configured = self.patterns.iter().map(|(pattern, severity)| (pattern.to_lowercase(), *severity));
+        builtin
+            .chain(configured)
+            .filter(|(pattern, _)| message.contains(pattern.as_str()))
+            .map(|(_, severity)| severity)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The alert configured for `severity`
+    pub fn style(&self, severity: Severity) -> AlertStyle {
+        match severity {
+            Severity::Info => self.info,
+            Severity::Warn => self.warn,
+            Severity::Error => self.error,
+        }
+    }
+}
+
+/// Times the daemon treats as do-not-disturb: the device does not buzz or flash, and
+/// gets only the frames do-not-disturb lets through
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct QuietHoursConfig {
+    /// When quiet hours are; none by default
+    pub windows: Vec<QuietWindow>,
+    /// IANA time zone the windows are in, e.g. "Europe/Berlin"; the system's when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Let alerts of error severity, such as an exhausted budget, buzz and flash anyway
+    pub urgent_alerts: bool,
+}
+
+impl Default for QuietHoursConfig {
+    fn default() -> Self {
+        Self { windows: Vec::new(), timezone: None, urgent_alerts: true }
+    }
+}
+
+/// One stretch of quiet hours, e.g. from "22:00" to "08:00". An end at or before the
+/// start is on the next day; the default, midnight to midnight, is the whole day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuietWindow {
+    #[serde(default)]
+    pub start: NaiveTime,
+    #[serde(default)]
+    pub end: NaiveTime,
+    /// Days the window starts on, e.g. ["sat", "sun"]; every day when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+}
+
+impl QuietWindow {
+    /// Whether the window covers a local date and time
+    fn contains(&self, local: NaiveDateTime) -> bool {
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let (day, time) = (local.weekday(), local.time());
+        if self.start < self.end {
+            starts_on(day) && self.start <= time && time < self.end
+        } else {
+            (starts_on(day) && time >= self.start) || (starts_on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+impl QuietHoursConfig {
+    /// Whether `now` falls in quiet hours, read in the configured time zone
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let local = match self.timezone.as_deref().and_then(|tz| tz.parse::<chrono_tz::Tz>().ok()) {
+            Some(tz) => now.with_timezone(&tz).naive_local(),
+            None => now.with_timezone(&Local).naive_local(),
+        };
+        self.windows.iter().any(|window| window.contains(local))
+    }
+
+    /// Fail on a time zone that is not in the IANA database
+    pub fn check(&self) -> Result<(), ConfigError> {
+        match &self.timezone {
+            Some(tz) if tz.parse::<chrono_tz::Tz>().is_err() => Err(ConfigError::InvalidValue {
+                key: "quiet_hours.timezone".to_string(),
+                message: format!("unknown time zone '{}'", tz),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Prompt text sent to the device with `UserPromptSubmit` events
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PromptsConfig {
+    /// Send a summary of each prompt to the device; off by default, as prompts can be
+    /// sensitive. Never sent while `prompt` is in `redaction.fields`.
+    pub send: bool,
+    /// Longest summary sent, in characters; longer prompts are cut at a word boundary
+    pub max_chars: usize,
+}
+
+impl Default for PromptsConfig {
+    fn default() -> Self {
+        Self { send: false, max_chars: 120 }
+    }
+}
+
+/// Arbitration between sessions running at the same time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SessionsConfig {
+    /// most-recent (default), highest-burn, or pinned; switchable live with
+    /// `ccgadget session focus`
+    pub focus: FocusPolicy,
+    /// Add totals across sessions to usage frames while several are active: how many,
+    /// their combined burn rate, and the most urgent alert
+    pub aggregate: bool,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self { focus: FocusPolicy::default(), aggregate: true }
+    }
+}
+
+/// Plan limits used to project when the current block will run out
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Token limit per five-hour block; defaults to the largest previous block
+    pub block_tokens: Option<u64>,
+    /// Trailing window used to measure token velocity
+    pub burn_window_minutes: u64,
+    /// Size of the model's context window, in tokens
+    pub context_tokens: u64,
+    /// Share of the context window, in percent, from which it counts as nearly full
+    pub context_warn_percent: f64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            block_tokens: None,
+            burn_window_minutes: 10,
+            context_tokens: 200_000,
+            context_warn_percent: 80.0,
+        }
+    }
+}
+
+/// OTLP export of hook events: sessions as traces, prompts and tool calls as spans,
+/// and event counts as metrics
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OtelConfig {
+    /// OTLP/HTTP collector, e.g. `http://localhost:4318`; nothing is exported when unset
+    pub endpoint: Option<String>,
+    /// `service.name` of the exported resource
+    pub service_name: String,
+    /// Extra resource attributes, e.g. `{ "team" = "platform" }`
+    pub resource_attributes: BTreeMap<String, String>,
+    /// How often finished spans and the counters are sent
+    pub export_interval_seconds: u64,
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            service_name: "ccgadget".to_string(),
+            resource_attributes: BTreeMap::new(),
+            export_interval_seconds: 10,
+        }
+    }
+}
+
+/// Daily, weekly, and per-block budgets, in dollars or tokens. Each alerts once per
+/// period as it passes each threshold.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BudgetsConfig {
+    /// Estimated spend per local calendar day
+    pub daily_usd: Option<f64>,
+    /// Estimated spend per week, from Monday
+    pub weekly_usd: Option<f64>,
+    /// Estimated spend per five-hour usage block
+    pub block_usd: Option<f64>,
+    pub daily_tokens: Option<u64>,
+    pub weekly_tokens: Option<u64>,
+    pub block_tokens: Option<u64>,
+    /// Shares of a budget, in percent, that alert when reached; 100 and above are errors
+    pub thresholds: Vec<f64>,
+    /// Also show a desktop notification when a threshold is reached
+    pub desktop_notification: bool,
+}
+
+impl Default for BudgetsConfig {
+    fn default() -> Self {
+        Self {
+            daily_usd: None,
+            weekly_usd: None,
+            block_usd: None,
+            daily_tokens: None,
+            weekly_tokens: None,
+            block_tokens: None,
+            thresholds: vec![80.0, 100.0],
+            desktop_notification: false,
+        }
+    }
+}
+
+impl BudgetsConfig {
+    /// Fail on a budget or threshold that is not positive
+    pub fn check(&self) -> Result<(), ConfigError> {
+        let amounts = [("daily_usd", self.daily_usd), ("weekly_usd", self.weekly_usd), ("block_usd", self.block_usd)];
+        for (key, amount) in amounts {
+            if amount.is_some_and(|amount| amount <= 0.0) {
+                return Err(ConfigError::InvalidValue { key: format!("budgets.{}", key), message: "must be above 0".to_string() });
+            }
+        }
+        if self.thresholds.iter().any(|threshold| *threshold <= 0.0) {
+            return Err(ConfigError::InvalidValue { key: "budgets.thresholds".to_string(), message: "percentages must be above 0".to_string() });
+        }
+        Ok(())
+    }
+}
+
+/// Custom metrics available to device widgets alongside the built-in ones
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Shell commands whose numeric stdout becomes a metric (e.g. git_commits_today)
+    pub plugins: BTreeMap<String, String>,
+    /// Arithmetic expressions over other metrics (e.g. cost_today / git_commits_today)
+    pub derived: BTreeMap<String, String>,
+}
+
+/// Screen the device shows when no alert is up
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayPage {
+    /// The widgets, led by the focused session's cost
+    #[default]
+    SessionCost,
+    /// Time and tokens left in the current 5-hour block
+    BlockCountdown,
+    /// The latest tool calls as they happen
+    ToolActivity,
+}
+
+impl DisplayPage {
+    /// The page after this one, wrapping around
+    pub fn next(self) -> Self {
+        match self {
+            DisplayPage::SessionCost => DisplayPage::BlockCountdown,
+            DisplayPage::BlockCountdown => DisplayPage::ToolActivity,
+            DisplayPage::ToolActivity => DisplayPage::SessionCost,
+        }
+    }
+}
+
+impl std::fmt::Display for DisplayPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DisplayPage::SessionCost => "session-cost",
+            DisplayPage::BlockCountdown => "block-countdown",
+            DisplayPage::ToolActivity => "tool-activity",
+        })
+    }
+}
+
+/// What the device shows
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Metric names assigned to the device's widgets, in display order
+    pub widgets: Vec<String>,
+    /// Screen shown by default
+    pub page: DisplayPage,
+    /// Backlight level, 0-100
+    pub brightness: u8,
+    /// Light text on a dark background
+    pub dark_mode: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            widgets: vec!["cost_session".to_string(), "cost_today".to_string()],
+            page: DisplayPage::default(),
+            brightness: 80,
+            dark_mode: true,
+        }
+    }
+}
+
+impl Config {
+    /// Location of the configuration file
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        Ok(paths::config_dir()?.join("config.toml"))
+    }
+
+    /// Action for a press of `button`: the one under `buttons`, else the default
+    /// (`a` acknowledges, `b` moves to the next session, a long `b` pauses)
+    pub fn button_action(&self, button: &str, long: bool) -> ButtonAction {
+        let key = if long { format!("{}_long", button) } else { button.to_string() };
+        if let Some(action) = self.buttons.get(&key) {
+            return action.clone();
+        }
+        match key.as_str() {
+            "a" => ButtonAction::Acknowledge,
+            "b" => ButtonAction::NextSession,
+            "b_long" => ButtonAction::Pause,
+            _ => ButtonAction::Ignore,
+        }
+    }
+
+    /// Whether a button can block the next tool call, so `trigger` has to ask the
+    /// daemon before answering a `PreToolUse` hook
+    pub fn button_blocks_tools(&self) -> bool {
+        self.buttons.values().any(|action| *action == ButtonAction::BlockNextTool)
+    }
+
+    /// Whether `trigger` may answer the hook with a decision for Claude Code
+    pub fn gives_hook_responses(&self) -> bool {
+        !self.responses.rules.is_empty() || self.button_blocks_tools()
+    }
+
+    /// Load the configuration file (defaults when it does not exist), then apply the
+    /// selected profile and `CCGADGET_<SECTION>_<KEY>` environment overrides
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let config = Self::read()?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Reject settings that parse but could never work, such as an invalid regex
+    fn check(&self) -> Result<(), ConfigError> {
+        self.budgets.check()?;
+        self.redaction.check()?;
+        self.routing.check()?;
+        self.responses.check()?;
+        self.quiet_hours.check()?;
+        self.activity.check()
+    }
+
+    /// The config file with the selected profile and environment overrides applied
+    fn read() -> Result<Self, Box<dyn Error>> {
+        let path = Self::path()?;
+        let content = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+        let config: Self =
+            toml::from_str(&content).map_err(|e| ConfigError::Invalid { path: path.clone(), message: e.to_string() })?;
+
+        let overrides = env_overrides();
+        if overrides.is_empty() && profile().is_none() {
+            return Ok(config);
+        }
+        let mut table: toml::Table = toml::from_str(&content)?;
+        if let Some(name) = profile() {
+            apply_profile(&mut table, name)?;
+            toml::Table::try_into::<Self>(table.clone())
+                .map_err(|e| ConfigError::InvalidProfile { name: name.to_string(), path, message: e.to_string() })?;
+        }
+        for (key, name, raw) in &overrides {
+            set_dotted(&mut table, key, toml_value(raw))
+                .map_err(|e| ConfigError::InvalidOverride { variables: name.clone(), message: e.to_string() })?;
+        }
+        table.try_into().map_err(|e| {
+            let names: Vec<&str> = overrides.iter().map(|(_, name, _)| name.as_str()).collect();
+            ConfigError::InvalidOverride { variables: names.join(", "), message: e.to_string() }.into()
+        })
+    }
+
+    /// Every effective setting as `dotted.key = value` pairs; unset optional keys are omitted
+    pub fn entries(&self) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let table = toml::Table::try_from(self)?;
+        let mut entries = Vec::new();
+        flatten("", &table, &mut entries);
+        Ok(entries)
+    }
+
+    /// Effective value of one dotted key, or of a whole section
+    pub fn get(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let mut value = toml::Value::Table(toml::Table::try_from(self)?);
+        for part in key.split('.') {
+            match value.get(part) {
+                Some(inner) => value = inner.clone(),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(match value {
+            toml::Value::Table(table) => toml::to_string(&table)?.trim_end().to_string(),
+            value => value.to_string(),
+        }))
+    }
+
+    /// Set one dotted key (e.g. `device.link_mode`) in the config file, keeping the rest of
+    /// the file, including comments, as it was. With a profile selected the key is set in
+    /// that profile's table.
+    pub fn set_value(key: &str, value: toml_edit::Value) -> Result<PathBuf, Box<dyn Error>> {
+        let key = &scoped_key(key);
+        let path = Self::path()?;
+        let content = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+        let mut document: toml_edit::DocumentMut = content
+            .parse()
+            .map_err(|e: toml_edit::TomlError| ConfigError::Invalid { path: path.clone(), message: e.to_string() })?;
+
+        insert_value(&mut document, key, value)?;
+
+        // Refuse to write a file the loader would reject, with or without each profile
+        let updated = document.to_string();
+        let table: toml::Table = toml::from_str(&updated)?;
+        let invalid = |e: &dyn std::fmt::Display| ConfigError::InvalidValue { key: key.clone(), message: e.to_string() };
+        let config: Config = toml::Table::try_into(table.clone()).map_err(|e| invalid(&e))?;
+        config.check()?;
+        for name in config.profiles.keys() {
+            let mut resolved = table.clone();
+            apply_profile(&mut resolved, name)?;
+            toml::Table::try_into::<Config>(resolved).map_err(|e| invalid(&e))?;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs_util::atomic_write(&path, updated.as_bytes())?;
+        Ok(path)
+    }
+
+    /// Load the configuration, warning and using defaults if it cannot be read
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            esay!("   ⚠️ {}", e);
+            Self::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_mode_parses_kebab_case() {
+        let config: Config = toml::from_str("[device]\nlink_mode = \"battery-saver\"\n").unwrap();
+        assert_eq!(config.device.link_mode, LinkMode::BatterySaver);
+        assert_eq!(Config::default().device.link_mode, LinkMode::LowLatency);
+    }
+
+    #[test]
+    fn test_on_event_commands_sit_beside_limits() {
+        let config: Config = toml::from_str(
+            "[on_event]\nStop = \"say done\"\ntimeout_seconds = 3\n",
+        )
+        .unwrap();
+        assert_eq!(config.on_event.commands.get("Stop").map(String::as_str), Some("say done"));
+        assert_eq!(config.on_event.timeout_seconds, 3);
+        assert_eq!(config.on_event.max_concurrent, 4);
+        assert_eq!(config.on_event.commands.len(), 1);
+    }
+
+    #[test]
+    fn test_env_key_mapping() {
+        assert_eq!(env_key("CCGADGET_DEVICE_LINK_MODE").as_deref(), Some("device.link_mode"));
+        assert_eq!(env_key("CCGADGET_ON_EVENT_TIMEOUT_SECONDS").as_deref(), Some("on_event.timeout_seconds"));
+        assert_eq!(env_key("CCGADGET_DESKTOP_DISCONNECTED").as_deref(), Some("desktop.disconnected"));
+        assert_eq!(env_key("CCGADGET_BROADCAST_ENABLED").as_deref(), Some("broadcast.enabled"));
+        assert_eq!(env_key("CCGADGET_QUIET_HOURS_URGENT_ALERTS").as_deref(), Some("quiet_hours.urgent_alerts"));
+        assert_eq!(env_key("CCGADGET_DEMO_MODE"), None);
+        assert_eq!(env_key("CCGADGET_DEVICE"), None);
+    }
+
+    #[test]
+    fn test_every_section_can_be_overridden_from_the_environment() {
+        let toml::Value::Table(sections) = toml::Value::try_from(Config::default()).unwrap() else {
+            panic!("the config serializes to a table");
+        };
+        let missing: Vec<&String> = sections
+            .keys()
+            .filter(|section| *section != "profiles" && !ENV_SECTIONS.contains(&section.as_str()))
+            .collect();
+        assert!(missing.is_empty(), "not in ENV_SECTIONS: {:?}", missing);
+    }
+
+    #[test]
+    fn test_parse_value_and_lookup() {
+        let mut document = toml_edit::DocumentMut::new();
+        insert_value(&mut document, "daemon.interval_seconds", parse_value("5")).unwrap();
+        insert_value(&mut document, "device.alias", parse_value("desk gadget")).unwrap();
+        insert_value(&mut document, "display.widgets", parse_value("[\"cost_today\"]")).unwrap();
+        let config: Config = toml::from_str(&document.to_string()).unwrap();
+
+        assert_eq!(config.daemon.interval_seconds, 5);
+        assert_eq!(config.get("device.alias").unwrap().as_deref(), Some("\"desk gadget\""));
+        assert_eq!(config.get("display.widgets").unwrap().as_deref(), Some("[\"cost_today\"]"));
+        assert_eq!(config.get("device.nope").unwrap(), None);
+        assert!(config.entries().unwrap().contains(&("logs.retention_days".to_string(), "30".to_string())));
+    }
+
+    #[test]
+    fn test_profile_overlays_base_settings() {
+        let mut table: toml::Table = toml::from_str(
+            "[device]\nalias = \"home\"\nlink_mode = \"battery-saver\"\n\
+             [profiles.work.device]\nalias = \"office\"\n\
+             [profiles.work.limits]\nblock_tokens = 100\n",
+        )
+        .unwrap();
+        apply_profile(&mut table, "work").unwrap();
+        let config: Config = table.clone().try_into().unwrap();
+        assert_eq!(config.device.alias.as_deref(), Some("office"));
+        assert_eq!(config.device.link_mode, LinkMode::BatterySaver);
+        assert_eq!(config.limits.block_tokens, Some(100));
+
+        let error = apply_profile(&mut table, "home").unwrap_err().to_string();
+        assert_eq!(error, "Unknown profile 'home' (defined: work)");
+    }
+
+    #[test]
+    fn test_button_actions_override_defaults() {
+        let config: Config = toml::from_str(
+            "[buttons]\na = \"ignore\"\nc_long = { command = \"say hi\" }\n",
+        )
+        .unwrap();
+        assert_eq!(config.button_action("a", false), ButtonAction::Ignore);
+        assert_eq!(config.button_action("b", false), ButtonAction::NextSession);
+        assert_eq!(config.button_action("b", true), ButtonAction::Pause);
+        assert_eq!(config.button_action("c", true), ButtonAction::Command("say hi".to_string()));
+        assert_eq!(config.button_action("c", false), ButtonAction::Ignore);
+        assert!(!config.gives_hook_responses());
+
+        let config: Config = toml::from_str("[buttons]\nd = \"mute-alerts\"\nd_long = \"block-next-tool\"\n").unwrap();
+        assert_eq!(config.button_action("d", false), ButtonAction::MuteAlerts);
+        assert!(config.button_blocks_tools() && config.gives_hook_responses());
+    }
+
+    #[test]
+    fn test_quiet_hours_windows() {
+        let config: Config = toml::from_str(
+            "[quiet_hours]\ntimezone = \"America/New_York\"\n\
+             [[quiet_hours.windows]]\nstart = \"22:00\"\nend = \"08:00\"\ndays = [\"mon\", \"tue\", \"wed\", \"thu\", \"fri\"]\n\
+             [[quiet_hours.windows]]\ndays = [\"Sat\", \"sunday\"]\n",
+        )
+        .unwrap();
+        let quiet = &config.quiet_hours;
+        assert!(quiet.urgent_alerts);
+        let at = |utc: &str| quiet.contains(utc.parse().unwrap());
+        // 2025-07-18 is a Friday; New York is four hours behind UTC
+        assert!(!at("2025-07-18T21:00:00Z"));
+        assert!(at("2025-07-19T02:30:00Z"));
+        assert!(at("2025-07-19T13:00:00Z"));
+        assert!(at("2025-07-21T03:59:00Z"));
+        // Weeknight windows start on Monday evening, so Monday morning is not quiet
+        assert!(!at("2025-07-21T04:00:00Z"));
+        assert!(at("2025-07-22T11:59:00Z"));
+        assert!(!at("2025-07-22T12:00:00Z"));
+
+        let config: Config = toml::from_str("[quiet_hours]\ntimezone = \"Mars/Olympus\"\n").unwrap();
+        assert!(config.check().is_err());
+    }
+
+    #[test]
+    fn test_broadcast_targets_by_group() {
+        let added = |name: &str, group: Option<&str>| PairedDevice {
+            name: Some(name.to_string()),
+            address: String::new(),
+            peripheral_id: None,
+            paired_at: chrono::Utc::now(),
+            group: group.map(str::to_string),
+        };
+        let devices = vec![added("Desk", Some("office")), added("Shelf", None), added("Door", Some("office"))];
+        let names = |config: &BroadcastConfig| -> Vec<String> {
+            config.targets(devices.clone()).into_iter().filter_map(|device| device.name).collect()
+        };
+        assert_eq!(names(&BroadcastConfig::default()), ["Desk", "Shelf", "Door"]);
+        let config: Config = toml::from_str("[broadcast]\ngroup = \"office\"\n").unwrap();
+        assert_eq!(names(&config.broadcast), ["Desk", "Door"]);
+        assert!(names(&BroadcastConfig { enabled: false, group: None }).is_empty());
+    }
+
+    #[test]
+    fn test_alerts_classify_notifications_by_pattern() {
+        let config: Config = toml::from_str(
+            "[alerts.patterns]\n\"Deploy\" = \"error\"\npermission = \"info\"\n[alerts.warn]\nbuzz = \"long\"\n",
+        )
+        .unwrap();
+        let alerts = &config.alerts;
+        assert_eq!(alerts.classify("Claude is waiting for your input"), Severity::Info);
+        assert_eq!(alerts.classify("Approaching usage limit; rate limit reached"), Severity::Error);
+        assert_eq!(alerts.classify("deploy is waiting"), Severity::Error);
+        // The configured pattern replaced the built-in one
+        assert_eq!(alerts.classify("Claude needs your permission to use Bash"), Severity::Info);
+        assert_eq!(AlertsConfig::default().classify("Claude needs your permission to use Bash"), Severity::Warn);
+
+        assert_eq!(alerts.style(Severity::Warn), AlertStyle { buzz: Buzz::Long, flash: false });
+        assert!(alerts.style(Severity::Info).is_silent());
+        assert_eq!(alerts.style(Severity::Error).buzz, Buzz::Pulse);
+    }
+
+    #[test]
+    fn test_env_value_types() {
+        assert_eq!(toml_value("5"), toml::Value::Integer(5));
+        assert_eq!(toml_value("true"), toml::Value::Boolean(true));
+        assert_eq!(toml_value("desk gadget"), toml::Value::String("desk gadget".to_string()));
+    }
+
+    #[test]
+    fn test_routing_first_matching_rule_wins() {
+        let routing: RoutingConfig = toml::from_str(
+            r#"
+            default = "log"
+            rules = [
+                { event = "PreToolUse", tool = "Read|Glob", action = "drop" },
+                { event = "Notification", action = "send" },
+                { event = "Stop", action = "send" },
+                { tool = "Bash", action = "send" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(routing.route(Some("PreToolUse"), Some("Read")), RouteAction::Drop);
+        assert_eq!(routing.route(Some("PreToolUse"), Some("ReadMe")), RouteAction::Log);
+        assert_eq!(routing.route(Some("PostToolUse"), Some("Glob")), RouteAction::Log);
+        assert_eq!(routing.route(Some("PreToolUse"), Some("Bash")), RouteAction::Send);
+        assert_eq!(routing.route(Some("Notification"), None), RouteAction::Send);
+        assert_eq!(routing.route(None, None), RouteAction::Log);
+        assert_eq!(RoutingConfig::default().route(Some("Stop"), None), RouteAction::Send);
+
+        let invalid = RoutingConfig {
+            rules: vec![RouteRule { event: None, tool: Some("(".to_string()), action: RouteAction::Drop }],
+            ..Default::default()
+        };
+        assert!(invalid.check().is_err());
+    }
+
+    #[test]
+    fn test_init_profile_names() {
+        assert!(is_valid_profile_name("work"));
+        assert!(is_valid_profile_name("home_lab-2"));
+        assert!(!is_valid_profile_name(""));
+        assert!(!is_valid_profile_name("my profile"));
+        assert!(!is_valid_profile_name("work.device"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::LimitsConfig;
+use crate::transcript::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// How full a session's context window is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ContextUsage {
+    /// Tokens the latest request sent, cached or not
+    pub tokens: u64,
+    /// Size of the context window
+    pub limit: u64,
+    /// Share of the window in use, 0 to 100
+    pub percent: f64,
+    /// At or over `limits.context_warn_percent`, so compaction is near
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub nearly_full: bool,
+    /// Claude Code is compacting the conversation (`PreCompact`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compacting: bool,
+}
+
+impl ContextUsage {
+    /// Usage of `tokens` out of the configured window
+    pub fn new(tokens: u64, config: &LimitsConfig) -> Self {
+        let limit = config.context_tokens.max(1);
+        let percent = tokens as f64 * 100.0 / limit as f64;
+        Self { tokens, limit, percent, nearly_full: percent >= config.context_warn_percent, compacting: false }
+    }
+}
+
+/// Context size of the main conversation's latest response in transcript text:
+/// everything its request sent, fresh and cached. Subagent (sidechain) responses have
+/// their own context and are skipped.
+pub fn latest_context_tokens(content: &str) -> Option<u64> {
+    content.lines().rev().find_map(|line| {
+        let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+        if entry["type"] != "assistant" || entry["isSidechain"] == true || entry["message"]["model"] == "<synthetic>" {
+            return None;
+        }
+        let usage: TokenUsage = serde_json::from_value(entry["message"]["usage"].clone()).ok()?;
+        Some(usage.input_tokens + usage.cache_creation_input_tokens + usage.cache_read_input_tokens)
+    })
+}
+
+/// How full the context window of the session with this transcript is
+pub fn measure(transcript: &Path, config: &LimitsConfig) -> Option<ContextUsage> {
+    let content = fs::read_to_string(transcript).ok()?;
+    latest_context_tokens(&content).map(|tokens| ContextUsage::new(tokens, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assistant(input: u64, cache_read: u64, sidechain: bool) -> String {
+        serde_json::json!({
+            "type": "assistant",
+            "isSidechain": sidechain,
+            "message": {
+                "model": "claude-sonnet-4-20250514",
+                "usage": { "input_tokens": input, "output_tokens": 500, "cache_read_input_tokens": cache_read }
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_latest_main_response_sets_context_size() {
+        let transcript = [
+            assistant(10, 50_000, false),
+            assistant(20, 150_000, false),
+            r#"{"type": "user", "message": {"content": "next"}}"#.to_string(),
+            assistant(5, 2_000, true),
+        ]
+        .join("\n");
+        assert_eq!(latest_context_tokens(&transcript), Some(150_020));
+        assert_eq!(latest_context_tokens(r#"{"type": "user"}"#), None);
+
+        let config = LimitsConfig::default();
+        let usage = ContextUsage::new(170_000, &config);
+        assert_eq!((usage.limit, usage.percent, usage.nearly_full), (200_000, 85.0, true));
+        assert!(!ContextUsage::new(100_000, &config).nearly_full);
+    }
+}
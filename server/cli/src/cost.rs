@@ -0,0 +1,144 @@
+use crate::config::Config;
+use crate::pricing::PricingTable;
+use crate::transcript::{self, TokenUsage, UsageRecord};
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Aggregated token usage and estimated cost
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CostSummary {
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+    /// Tokens from models with no known pricing (excluded from cost_usd)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unpriced_tokens: u64,
+}
+
+/// Estimated spend at the time of a trigger, as shown on the device
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CostSnapshot {
+    pub session: Option<CostSummary>,
+    pub today: Option<CostSummary>,
+}
+
+/// Serde helper to omit zero counters
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+impl CostSummary {
+    /// Add a single usage record, priced with the given table
+    pub fn add_record(&mut self, record: &UsageRecord, pricing: &PricingTable) {
+        self.usage.add(&record.usage);
+        match pricing.lookup(&record.model) {
+            Some(rates) => self.cost_usd += rates.cost(&record.usage),
+            None => self.unpriced_tokens += record.usage.total(),
+        }
+    }
+
+    /// Add another summary's tokens and cost
+    pub fn add(&mut self, other: &CostSummary) {
+        self.usage.add(&other.usage);
+        self.cost_usd += other.cost_usd;
+        self.unpriced_tokens += other.unpriced_tokens;
+    }
+
+    /// Summarize a set of usage records
+    pub fn from_records<'a>(
+        records: impl IntoIterator<Item = &'a UsageRecord>,
+        pricing: &PricingTable,
+    ) -> Self {
+        let mut summary = Self::default();
+        for record in records {
+            summary.add_record(record, pricing);
+        }
+        summary
+    }
+}
+
+/// Session (if a transcript is given) and daily cost using configured pricing
+pub fn snapshot(config: &Config, transcript_path: Option<&Path>) -> Option<CostSnapshot> {
+    let pricing = PricingTable::with_overrides(&config.pricing);
+    let session = transcript_path
+        .filter(|path| path.exists())
+        .and_then(|path| session_cost(path, &pricing).ok());
+    let today = daily_cost(Local::now().date_naive(), &pricing).ok();
+
+    if session.is_none() && today.is_none() {
+        return None;
+    }
+    Some(CostSnapshot { session, today })
+}
+
+/// Estimated cost of a single session from its transcript file
+pub fn session_cost(transcript_path: &Path, pricing: &PricingTable) -> Result<CostSummary, Box<dyn Error>> {
+    let records = transcript::read_usage_records(transcript_path)?;
+    Ok(CostSummary::from_records(&records, pricing))
+}
+
+/// Estimated cost of all sessions on a local calendar day
+pub fn daily_cost(date: NaiveDate, pricing: &PricingTable) -> Result<CostSummary, Box<dyn Error>> {
+    let start_of_day = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(SystemTime::from);
+
+    let mut summary = CostSummary::default();
+    let mut seen = HashSet::new();
+    for file in transcript::transcript_files(start_of_day)? {
+        let Ok(records) = transcript::read_usage_records(&file) else {
+            continue;
+        };
+        for record in records {
+            if record.timestamp.with_timezone(&Local).date_naive() != date {
+                continue;
+            }
+            if let Some(key) = &record.dedup_key {
+                if !seen.insert(key.clone()) {
+                    continue;
+                }
+            }
+            summary.add_record(&record, pricing);
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_tracks_unpriced_models() {
+        let pricing = PricingTable::builtin();
+        let usage = TokenUsage { input_tokens: 1_000_000, ..Default::default() };
+        let records = vec![
+            UsageRecord {
+                timestamp: chrono::Utc::now(),
+                session_id: None,
+                cwd: None,
+                model: "claude-opus-4".to_string(),
+                usage,
+                dedup_key: None,
+                project: None,
+            },
+            UsageRecord {
+                timestamp: chrono::Utc::now(),
+                session_id: None,
+                cwd: None,
+                model: "mystery-model".to_string(),
+                usage,
+                dedup_key: None,
+                project: None,
+            },
+        ];
+        let summary = CostSummary::from_records(&records, &pricing);
+        assert!((summary.cost_usd - 15.0).abs() < 1e-9);
+        assert_eq!(summary.unpriced_tokens, 1_000_000);
+        assert_eq!(summary.usage.input_tokens, 2_000_000);
+    }
+}
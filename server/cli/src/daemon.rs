@@ -0,0 +1,2631 @@
+use crate::api::{self, Query};
+use crate::backoff::Backoff;
+use crate::burn;
+use crate::clock::{self, Stamp};
+use crate::coalesce::{self, Throttle};
+use crate::budget::{self, BudgetAlert, BudgetUsage, Crossings};
+use crate::config::{
+    AlertStyle, AlertsConfig, ApiConfig, BudgetsConfig, ButtonAction, Config, DaemonConfig, DesktopConfig, DisplayPage,
+    QuietHoursConfig,
+};
+use crate::cost;
+use crate::desktop;
+use crate::device::{self, DeviceEvent, LinkMode, PairedDevice, TransportKind};
+use crate::digest::{self, Digest};
+use crate::dnd::{self, DoNotDisturb};
+use crate::fault::{FaultConfig, FaultInjector};
+use crate::focus::{self, SessionAlert, Sessions};
+use crate::ipc::{
+    self, AckedEvent, DaemonStatus, FeedEntry, IpcRequest, IpcResponse, LinkState, MirrorStatus, PermissionWait, SessionCost,
+};
+use crate::lock::{self, DaemonLock};
+use crate::metrics;
+use crate::on_event::{self, EventCommands};
+use crate::otel;
+use crate::payload::{self, DevicePayload, Severity, BUDGET_EVENT, MAINTENANCE_EVENT};
+use crate::protocol::{self, Ack, FrameKind};
+use crate::reload::{self, ConfigChange};
+use crate::settings::{self, DeviceSettings, SETTINGS_EVENT};
+use crate::transcript;
+use crate::trigger::{self, HookInput};
+use crate::transport::{self, EventStream, Transport};
+use chrono::{DateTime, Timelike, Utc};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures::StreamExt;
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{debug, info, warn};
+
+/// Maximum number of events held while the device link is down
+const MAX_QUEUED_EVENTS: usize = 100;
+
+/// Hook events kept for `ccgadget top`
+const FEED_LEN: usize = 200;
+
+/// Frames held for each local API client that falls behind
+const LIVE_FRAMES: usize = 64;
+
+/// How often budget consumption is measured
+const BUDGET_CHECK: Duration = Duration::from_secs(60);
+
+/// First delay between reconnection attempts; it doubles up to `daemon.reconnect_max_seconds`
+const RETRY_BASE: Duration = Duration::from_secs(1);
+
+/// Wait after a change to the config file before reading it, as editors save in steps
+const RELOAD_SETTLE: Duration = Duration::from_millis(250);
+
+/// Longest wait for a link to an added device to close when the daemon stops
+const MIRROR_CLOSE: Duration = Duration::from_secs(2);
+
+/// State shared between the daemon loop and IPC handlers
+struct Shared {
+    status: DaemonStatus,
+    queue: VecDeque<DevicePayload>,
+    /// Transcript of the latest event, for events that carry no session id
+    last_transcript: Option<String>,
+    /// Concurrent sessions and which one drives the device display
+    sessions: Sessions,
+    /// The focused session changed; the usage screen should follow right away
+    focus_changed: bool,
+    /// Display page the device was last switched to
+    page: DisplayPage,
+    /// Where do-not-disturb is saved for the next daemon; `None` keeps it in memory
+    dnd_path: Option<PathBuf>,
+    /// Notifications held for the next digest; `None` when digests are disabled
+    digest: Option<Digest>,
+    /// How notifications are classified and alerted for
+    alerts: AlertsConfig,
+    /// Budgets checked every [`BUDGET_CHECK`]
+    budgets: BudgetsConfig,
+    /// Budget thresholds already alerted for
+    budget_crossings: Crossings,
+    /// Conditions shown as desktop notifications
+    desktop: DesktopConfig,
+    /// When the device is kept quiet as under do-not-disturb
+    quiet_hours: QuietHoursConfig,
+    /// Settings last read from the device; `None` if it does not report them
+    device_settings: Option<DeviceSettings>,
+    /// Completed reads of the device settings
+    settings_reads: u64,
+    /// The device settings should be read again
+    settings_refresh: bool,
+    /// Last request that calls for the device link, for the idle timeout
+    last_activity: Instant,
+    /// Last hook event, for the device's idle screen
+    last_event: Instant,
+    /// Rate limit on regular device updates
+    throttle: Throttle,
+    /// Latest hook events received, oldest first
+    feed: VecDeque<FeedEntry>,
+    /// Feed position of the latest event
+    feed_seq: u64,
+    /// Frames queued for the device, streamed to clients of the local API and mirrored
+    /// to added devices
+    live: broadcast::Sender<DevicePayload>,
+    /// Signalled when a client asks the daemon to shut down
+    stop: Arc<Notify>,
+    /// Run loop settings edited in the config file, for the loop to pick up
+    reloaded: Option<Reloaded>,
+}
+
+/// `[daemon]` settings reloaded from the config file
+struct Reloaded {
+    daemon: DaemonConfig,
+    /// `daemon.interval_seconds` itself changed, and replaces any `--interval`
+    interval_changed: bool,
+}
+
+impl Shared {
+    /// Take up the settings the daemon holds that `changes` touch. Settings read for
+    /// each event or tick need nothing here.
+    fn apply_config(&mut self, config: &Config, changes: &[ConfigChange]) {
+        let changed = |key: &str| changes.iter().any(|change| change.key == key || change.key.split('.').next() == Some(key));
+        if changed("alerts") {
+            self.alerts = config.alerts.clone();
+        }
+        if changed("budgets") {
+            self.budgets = config.budgets.clone();
+        }
+        if changed("desktop") {
+            self.desktop = config.desktop.clone();
+        }
+        if changed("quiet_hours") {
+            self.quiet_hours = config.quiet_hours.clone();
+            self.update_quiet_hours(Utc::now());
+        }
+        if changed("sessions.focus") {
+            self.sessions.set_policy(config.sessions.focus);
+            self.focus_changed = true;
+        }
+        if changed("device.link_mode") {
+            self.status.link_mode = config.device.link_mode;
+            self.queue_link_mode();
+        }
+        if changed("daemon.min_frame_interval_ms") {
+            self.throttle = Throttle::new(Duration::from_millis(config.daemon.min_frame_interval_ms));
+        }
+        let upkeep = [
+            "daemon.interval_seconds",
+            "daemon.keepalive_seconds",
+            "daemon.heartbeat_misses",
+            "daemon.presence_idle_minutes",
+            "daemon.idle_timeout_minutes",
+            "daemon.reconnect_max_seconds",
+        ];
+        if upkeep.into_iter().any(changed) {
+            self.reloaded = Some(Reloaded { daemon: config.daemon.clone(), interval_changed: changed("daemon.interval_seconds") });
+        }
+    }
+
+    /// Id of the session currently driving the display
+    fn focused_id(&self) -> Option<String> {
+        self.sessions.focused().map(|session| session.session_id.clone())
+    }
+
+    /// Flag a usage refresh if the focus moved away from `before`
+    fn note_focus(&mut self, before: Option<String>) {
+        let after = self.focused_id();
+        if after != before {
+            debug!("Display focus moved to session {:?}", after);
+            self.focus_changed = true;
+        }
+    }
+
+    /// Active sessions and the current focus decision
+    fn sessions_response(&self) -> IpcResponse {
+        IpcResponse::Sessions {
+            policy: self.sessions.policy,
+            pinned: self.sessions.pinned.clone(),
+            focused: self.focused_id(),
+            sessions: self.sessions.active().to_vec(),
+        }
+    }
+
+    /// Add a hook event to the feed, dropping the oldest one when it is full
+    fn record_feed(&mut self, payload: &DevicePayload) {
+        self.feed_seq += 1;
+        if self.feed.len() >= FEED_LEN {
+            self.feed.pop_front();
+        }
+        self.feed.push_back(FeedEntry {
+            seq: self.feed_seq,
+            at: payload.timestamp,
+            event: payload.event.clone(),
+            session_id: payload.session_id.clone(),
+            tool: payload.tool.clone(),
+            activity: payload.activity,
+            message: payload.message.clone(),
+        });
+    }
+
+    /// Queue an event, dropping the oldest one when the queue is full. A tool event
+    /// replaces one from the same session still waiting at the back of the queue.
+    fn enqueue(&mut self, mut payload: DevicePayload) {
+        if self.quiet() && !coalesce::is_critical(&payload) {
+            debug!("Do not disturb: {} not sent", payload.event);
+            return;
+        }
+        payload.mono_ms.get_or_insert_with(|| Stamp::now().mono_ms);
+        // Fails only while no API client is listening
+        let _ = self.live.send(payload.clone());
+        if coalesce::merge(&mut self.queue, &mut payload) {
+            debug!("Merged {} into the queued tool event", payload.event);
+            return;
+        }
+        if self.queue.len() >= MAX_QUEUED_EVENTS {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(payload);
+        self.status.queued = self.queue.len();
+    }
+
+    /// Queue a periodic usage update; in battery-saver mode it replaces any update still waiting
+    fn enqueue_usage(&mut self, payload: DevicePayload) {
+        if self.status.link_mode == LinkMode::BatterySaver {
+            self.queue.retain(|queued| queued.event != "Usage");
+        }
+        self.enqueue(payload);
+    }
+
+    /// Put a control frame at the front of the queue, replacing any unsent one of its kind
+    fn queue_control(&mut self, mut payload: DevicePayload) {
+        payload.mono_ms.get_or_insert_with(|| Stamp::now().mono_ms);
+        // Added devices are steered like the paired one
+        let _ = self.live.send(payload.clone());
+        self.queue.retain(|queued| queued.event != payload.event);
+        self.queue.push_front(payload);
+        self.status.queued = self.queue.len();
+    }
+
+    /// Put the link-mode control frame at the front of the queue
+    fn queue_link_mode(&mut self) {
+        self.queue_control(DevicePayload::link_mode(self.status.link_mode));
+    }
+
+    /// Frames that set the device up on every connection: the link mode, led by the
+    /// display settings last pushed from here (`pushed`) in case the device lost them
+    fn queue_setup(&mut self, pushed: Option<DeviceSettings>) {
+        if let Some(settings) = pushed {
+            self.queue_control(DevicePayload::settings(settings));
+        }
+        if self.status.idle_since.is_some() {
+            self.queue_control(DevicePayload::presence(true));
+        }
+        if self.quiet() {
+            self.queue_control(DevicePayload::dnd(true));
+        }
+        self.queue_link_mode();
+    }
+
+    /// Note a hook event, bringing the device back from its idle screen
+    fn note_event(&mut self) {
+        self.last_event = Instant::now();
+        if self.status.idle_since.take().is_some() {
+            info!("Activity resumed; back to the metrics screen");
+            self.queue_control(DevicePayload::presence(false));
+        }
+    }
+
+    /// Send the device to its idle screen after `after` without hook events
+    fn go_idle(&mut self, after: Duration) {
+        info!("No hook events for {} min; the device shows its idle screen", after.as_secs() / 60);
+        self.status.idle_since = Some(Utc::now());
+        self.queue_control(DevicePayload::presence(true));
+    }
+
+    /// `style` for an alert of `severity`, or neither buzz nor flash while a
+    /// `mute-alerts` press is in effect, do-not-disturb is on, or it is quiet hours and
+    /// the alert is not urgent enough to get through
+    fn unless_muted(&self, style: AlertStyle, severity: Severity) -> AlertStyle {
+        let urgent = severity == Severity::Error && self.quiet_hours.urgent_alerts;
+        match self.status.alerts_muted_until {
+            Some(until) if until > Utc::now() => AlertStyle::default(),
+            _ if self.dnd_active() || (self.status.quiet_hours && !urgent) => AlertStyle::default(),
+            _ => style,
+        }
+    }
+
+    /// Whether the device is kept quiet, by do-not-disturb or quiet hours
+    fn quiet(&self) -> bool {
+        self.dnd_active() || self.status.quiet_hours
+    }
+
+    /// Note whether `now` is in quiet hours, telling the device when that changes
+    /// whether it is kept quiet
+    fn update_quiet_hours(&mut self, now: DateTime<Utc>) {
+        let quiet_hours = self.quiet_hours.contains(now);
+        if quiet_hours == self.status.quiet_hours {
+            return;
+        }
+        let was_quiet = self.quiet();
+        self.status.quiet_hours = quiet_hours;
+        info!("Quiet hours {}", if quiet_hours { "began" } else { "ended" });
+        if self.quiet() != was_quiet {
+            self.queue_control(DevicePayload::dnd(quiet_hours));
+        }
+    }
+
+    /// Whether do-not-disturb is on and has not run out
+    fn dnd_active(&self) -> bool {
+        self.status.dnd.is_some_and(|dnd| dnd.active_at(Utc::now()))
+    }
+
+    /// Turn do-not-disturb on (`Some`) or off, showing it on the device and saving it
+    /// so a restarted daemon keeps it
+    fn set_dnd(&mut self, dnd: Option<DoNotDisturb>) {
+        self.status.dnd = dnd;
+        // Quiet hours keep the icon up after do-not-disturb ends
+        let quiet = self.quiet();
+        self.queue_control(DevicePayload::dnd(quiet));
+        match &dnd {
+            Some(dnd) => info!("Do not disturb {}", dnd),
+            None => info!("Do not disturb off"),
+        }
+        if let Some(path) = &self.dnd_path {
+            if let Err(e) = dnd::save(path, dnd.as_ref()) {
+                warn!("Could not save do-not-disturb: {}", e);
+            }
+        }
+    }
+
+    /// Turn do-not-disturb off once its time is up
+    fn expire_dnd(&mut self) {
+        if self.status.dnd.is_some() && !self.dnd_active() {
+            self.set_dnd(None);
+        }
+    }
+
+    /// Classify a notification by severity, queueing an alert ahead of it when the
+    /// severity has one configured. Returns whether an alert was queued.
+    fn queue_alert(&mut self, notification: &mut DevicePayload) -> bool {
+        let message = notification.message.as_deref().unwrap_or_default();
+        let severity = self.alerts.classify(message);
+        notification.severity = Some(severity);
+        if payload::is_permission_request(message) {
+            // Claude is blocked until someone answers, so this gets its own frame
+            let request = DevicePayload::permission_request(notification, self.unless_muted(self.alerts.permission, severity));
+            info!("Claude is waiting for permission{}", request.tool.as_deref().map(|tool| format!(" to use {}", tool)).unwrap_or_default());
+            self.status.awaiting_permission = Some(PermissionWait {
+                session_id: notification.session_id.clone(),
+                tool: request.tool.clone(),
+                since: Utc::now(),
+            });
+            self.enqueue(request);
+            return true;
+        }
+        let style = self.unless_muted(self.alerts.style(severity), severity);
+        if style.is_silent() {
+            return false;
+        }
+        debug!("Alerting for {} notification", severity);
+        self.enqueue(DevicePayload::alert(notification, severity, style));
+        true
+    }
+
+    /// Keep a classified notification on its session for the aggregate usage frame,
+    /// if it is a permission request or above `info`
+    fn note_session_alert(&mut self, notification: &DevicePayload) {
+        let (Some(session_id), Some(message)) = (&notification.session_id, &notification.message) else {
+            return;
+        };
+        let severity = notification.severity.unwrap_or_default();
+        let permission = payload::is_permission_request(message);
+        if permission || severity > Severity::Info {
+            self.sessions.set_alert(SessionAlert { session_id: session_id.clone(), severity, permission, message: message.clone() });
+        }
+    }
+
+    /// Record budget consumption for `status` and queue an alert for each budget that
+    /// reached a new threshold. Returns those alerts.
+    fn check_budgets(&mut self, usages: Vec<BudgetUsage>) -> Vec<BudgetAlert> {
+        let alerts = self.budget_crossings.update(&usages, &self.budgets.thresholds);
+        self.status.budgets = usages;
+        for alert in &alerts {
+            let message = alert.message();
+            info!("{}", message);
+            let severity = alert.severity();
+            self.enqueue(DevicePayload::budget_alert(message, severity, self.unless_muted(self.alerts.style(severity), severity)));
+        }
+        alerts
+    }
+
+    /// Enter or leave maintenance mode, telling the device
+    fn set_maintenance(&mut self, enabled: bool) {
+        self.status.maintenance = enabled;
+        self.queue_control(DevicePayload::maintenance(enabled));
+        info!("Maintenance mode {}", if enabled { "on: transmissions paused" } else { "off" });
+    }
+
+    /// Next payload that may be sent at `now`; during maintenance only maintenance frames
+    /// go out, and while regular updates are throttled only urgent ones do
+    fn next_sendable(&self, now: Instant) -> Option<&DevicePayload> {
+        if self.status.maintenance {
+            return self.queue.front().filter(|payload| payload.event == MAINTENANCE_EVENT);
+        }
+        if self.throttle.allows(now) {
+            return self.queue.front();
+        }
+        self.queue.iter().find(|payload| coalesce::is_urgent(payload))
+    }
+
+    /// When queued frames held back only by the rate limit may go out
+    fn throttled_until(&self, now: Instant) -> Option<Instant> {
+        if self.status.maintenance || self.queue.is_empty() || self.next_sendable(now).is_some() {
+            return None;
+        }
+        self.throttle.ready_at()
+    }
+
+    /// Take a sent or expired payload off the queue. Frames may have been queued or
+    /// merged while it was in flight, so it is found by value.
+    fn remove(&mut self, payload: &DevicePayload) {
+        if let Some(index) = self.queue.iter().position(|queued| queued == payload) {
+            self.queue.remove(index);
+        }
+        self.status.queued = self.queue.len();
+    }
+
+    /// Reply to a client whose frame was just queued
+    fn delivery_response(&self) -> IpcResponse {
+        if self.status.maintenance {
+            return IpcResponse::Paused { queued: self.queue.len() };
+        }
+        match self.status.state {
+            LinkState::Ready | LinkState::Observing => IpcResponse::Accepted,
+            state => IpcResponse::Queued { state, queued: self.queue.len() },
+        }
+    }
+
+    /// Move any pending notification digest onto the send queue
+    fn flush_digest(&mut self) {
+        if let Some(payload) = self.digest.as_mut().and_then(Digest::take) {
+            self.enqueue(payload);
+        }
+    }
+}
+
+/// An established connection to the device
+struct Link {
+    transport: Box<dyn Transport>,
+    /// Protocol version agreed with the device
+    version: u8,
+    /// Frames larger than this are chunked, when the protocol version allows
+    max_write: Option<usize>,
+    /// Number of the next chunked frame
+    next_message: AtomicU8,
+    /// Sequence number of the next numbered frame
+    next_seq: AtomicU32,
+}
+
+/// Longest wait for the device to confirm it reassembled a chunked frame
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest wait for the device to acknowledge a numbered frame
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Times an unacknowledged frame is written again before the link is given up
+const MAX_RETRANSMITS: u32 = 3;
+
+/// How the device confirmed a payload
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Delivery {
+    /// Sequence number the device acknowledged
+    acked: Option<u32>,
+    /// Times the frame was written again
+    retransmits: u32,
+}
+
+/// What reading the device for an ack turned up
+enum Confirmation {
+    Acked,
+    /// The transport cannot be read, so the frame stays unconfirmed
+    Unreadable,
+    TimedOut,
+}
+
+impl Link {
+    /// Link over a connected transport, speaking JSON
+    fn new(transport: Box<dyn Transport>) -> Self {
+        let max_write = transport.max_write();
+        Self {
+            transport,
+            version: protocol::JSON_VERSION,
+            max_write,
+            next_message: AtomicU8::new(0),
+            next_seq: AtomicU32::new(1),
+        }
+    }
+
+    /// Link over a connected transport, in the protocol version agreed with the device.
+    /// An MTU the device reports sizes chunks in place of the transport's own limit.
+    async fn open(transport: Box<dyn Transport>) -> Result<Self, Box<dyn Error>> {
+        match transport::handshake(transport.as_ref()).await {
+            Ok(agreement) => {
+                let mut link = Self::new(transport);
+                link.version = agreement.version;
+                if let (Some(_), Some(mtu)) = (link.max_write, agreement.mtu) {
+                    link.max_write = Some((mtu as usize).saturating_sub(transport::ATT_OVERHEAD));
+                }
+                Ok(link)
+            }
+            Err(e) => {
+                Self::new(transport).close().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Write one frame, in chunks the device reassembles if it is too large for a
+    /// single write
+    async fn write(&self, frame: &[u8]) -> Result<(), Box<dyn Error>> {
+        let size = match self.max_write {
+            Some(size) if self.version >= protocol::CHUNKED_VERSION && frame.len() > size => size,
+            _ => return self.transport.send(frame).await,
+        };
+        let message = self.next_message.fetch_add(1, Ordering::Relaxed);
+        let chunks = protocol::chunk(frame, self.version, message, size)?;
+        debug!("Writing {} byte frame as message {} in {} chunks", frame.len(), message, chunks.len());
+        for chunk in &chunks {
+            self.transport.send(chunk).await?;
+        }
+        if self.version >= protocol::ACKED_VERSION {
+            // The reassembled frame is acknowledged by its sequence number instead
+            return Ok(());
+        }
+        match self.await_ack(|ack| ack.message == Some(message), REASSEMBLY_TIMEOUT).await? {
+            Confirmation::Acked => Ok(()),
+            Confirmation::Unreadable => {
+                debug!("Device cannot be read; message {} is unconfirmed", message);
+                Ok(())
+            }
+            Confirmation::TimedOut => Err(format!("Device did not confirm reassembly of message {}", message).into()),
+        }
+    }
+
+    /// Read the device until it sends an ack `wanted` accepts, for up to `timeout`
+    async fn await_ack(&self, wanted: impl Fn(&Ack) -> bool, timeout: Duration) -> Result<Confirmation, Box<dyn Error>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let Some(reply) = self.transport.receive().await? else {
+                return Ok(Confirmation::Unreadable);
+            };
+            if let Ok((Some(FrameKind::Ack), ack)) = protocol::decode::<Ack>(&reply) {
+                if wanted(&ack) {
+                    return Ok(Confirmation::Acked);
+                }
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(Confirmation::TimedOut);
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Write one payload to the device, passing it through the fault injector if enabled.
+    /// From protocol v4 the frame is numbered and written again until the device
+    /// acknowledges it.
+    async fn send(&self, payload: &DevicePayload, mut faults: Option<&mut FaultInjector>) -> Result<Delivery, Box<dyn Error>> {
+        let frame = protocol::encode(self.version, self.next_seq.fetch_add(1, Ordering::Relaxed), payload)?;
+        let numbered = protocol::sequence(&frame);
+        let mut delivery = Delivery::default();
+        loop {
+            let frames = match faults.as_deref_mut() {
+                Some(injector) => injector.apply(&frame),
+                None => vec![(Duration::ZERO, frame.clone())],
+            };
+            for (delay, frame) in frames {
+                sleep(delay).await;
+                self.write(&frame).await?;
+            }
+            let Some(seq) = numbered else {
+                return Ok(delivery);
+            };
+            match self.await_ack(|ack| ack.seq == Some(seq), ACK_TIMEOUT).await? {
+                Confirmation::Acked => {
+                    delivery.acked = Some(seq);
+                    return Ok(delivery);
+                }
+                Confirmation::Unreadable => return Ok(delivery),
+                Confirmation::TimedOut if delivery.retransmits < MAX_RETRANSMITS => {
+                    delivery.retransmits += 1;
+                    debug!("Frame {} not acknowledged; retransmitting ({}/{})", seq, delivery.retransmits, MAX_RETRANSMITS);
+                }
+                Confirmation::TimedOut => {
+                    return Err(format!("Device did not acknowledge frame {} after {} retransmits", seq, MAX_RETRANSMITS).into())
+                }
+            }
+        }
+    }
+
+    /// The device's state report as a JSON object, if it sends one
+    async fn read_state(&self) -> Result<Option<serde_json::Value>, Box<dyn Error>> {
+        match self.transport.receive().await? {
+            Some(reply) => Ok(Some(protocol::decode(&reply)?.1)),
+            None => Ok(None),
+        }
+    }
+
+    /// The device's current clock, if it reports one in the `timestamp` field
+    async fn device_time(&self) -> Result<Option<DateTime<Utc>>, Box<dyn Error>> {
+        let reply = self.read_state().await?;
+        Ok(reply.and_then(|reply| reply["timestamp"].as_str().and_then(|ts| ts.parse().ok())))
+    }
+
+    /// The settings stored on the device, if it reports them in the `settings` field
+    async fn device_settings(&self) -> Result<Option<DeviceSettings>, Box<dyn Error>> {
+        match self.read_state().await? {
+            Some(reply) if !reply["settings"].is_null() => Ok(Some(serde_json::from_value(reply["settings"].clone())?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Confirm the device still answers
+    async fn check(&self) -> Result<(), Box<dyn Error>> {
+        self.transport.check().await
+    }
+
+    /// Close the transport after the link is lost or the daemon stops
+    async fn close(mut self) {
+        if let Err(e) = self.transport.close().await {
+            debug!("Closing the {} transport failed: {}", self.transport.name(), e);
+        }
+    }
+}
+
+/// How the daemon looks after the device link between transmissions
+struct Upkeep {
+    /// Time without traffic after which the link is checked
+    keepalive: Option<Duration>,
+    /// Failed checks in a row after which the device is offline
+    heartbeat_misses: u32,
+    /// Time without hook events before the device shows its idle screen
+    presence_idle: Option<Duration>,
+    /// Time without activity after which the link is closed until the next request
+    idle_timeout: Option<Duration>,
+    backoff: Backoff,
+}
+
+impl Upkeep {
+    fn new(config: &DaemonConfig) -> Self {
+        Self {
+            keepalive: (config.keepalive_seconds > 0).then(|| Duration::from_secs(config.keepalive_seconds)),
+            heartbeat_misses: config.heartbeat_misses.max(1),
+            presence_idle: (config.presence_idle_minutes > 0).then(|| Duration::from_secs(config.presence_idle_minutes * 60)),
+            idle_timeout: config.idle_timeout_minutes.filter(|minutes| *minutes > 0).map(|minutes| Duration::from_secs(minutes * 60)),
+            backoff: Backoff::new(RETRY_BASE, Duration::from_secs(config.reconnect_max_seconds)),
+        }
+    }
+}
+
+/// Measure how far the device clock is off, then set it to the host time. Returns the
+/// skew before correction, if the device reports its time.
+async fn sync_clock(link: &Link) -> Result<Option<i64>, Box<dyn Error>> {
+    let sent = Stamp::now();
+    let skew = link.device_time().await?.map(|device| clock::skew_ms(sent, device, Stamp::now()));
+    let mut payload = DevicePayload::time_sync();
+    payload.mono_ms = Some(Stamp::now().mono_ms);
+    link.send(&payload, None).await?;
+    match skew {
+        Some(skew) if skew.abs() > clock::SKEW_WARN_MS => warn!("Device clock was {:+.1}s off; corrected", skew as f64 / 1000.0),
+        Some(skew) => debug!("Device clock within {}ms of host", skew),
+        None => debug!("Device does not report its clock; sent host time"),
+    }
+    Ok(skew)
+}
+
+/// A device added with `pair --add`, sent the frames the paired device is sent
+struct Mirror {
+    device: PairedDevice,
+    /// Position of this device in `status.mirrors`
+    index: usize,
+    state: LinkState,
+    queue: VecDeque<DevicePayload>,
+    delivered: u64,
+    throttle: Throttle,
+    /// When its established link was lost, until it is back
+    offline_since: Option<DateTime<Utc>>,
+}
+
+impl Mirror {
+    fn new(index: usize, device: PairedDevice, min_frame_interval: Duration) -> Self {
+        Self {
+            device,
+            index,
+            state: LinkState::Starting,
+            queue: VecDeque::new(),
+            delivered: 0,
+            throttle: Throttle::new(min_frame_interval),
+            offline_since: None,
+        }
+    }
+
+    /// Queue a frame the paired device was sent. Control frames go first, replacing any
+    /// unsent one of their kind; a usage update replaces the one still waiting, and tool
+    /// events are merged as in the daemon queue.
+    fn push(&mut self, mut payload: DevicePayload) {
+        payload.mono_ms.get_or_insert_with(|| Stamp::now().mono_ms);
+        if coalesce::is_control(&payload) {
+            self.queue.retain(|queued| queued.event != payload.event);
+            self.queue.push_front(payload);
+            return;
+        }
+        if payload.event == "Usage" {
+            self.queue.retain(|queued| queued.event != "Usage");
+        }
+        if coalesce::merge(&mut self.queue, &mut payload) {
+            return;
+        }
+        if self.queue.len() >= MAX_QUEUED_EVENTS {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(payload);
+    }
+
+    /// Position of the next frame that may be sent at `now`, by the rules of the daemon
+    /// queue
+    fn next_sendable(&self, now: Instant, maintenance: bool) -> Option<usize> {
+        if maintenance {
+            return self.queue.front().filter(|payload| payload.event == MAINTENANCE_EVENT).map(|_| 0);
+        }
+        if self.throttle.allows(now) {
+            return (!self.queue.is_empty()).then_some(0);
+        }
+        self.queue.iter().position(coalesce::is_urgent)
+    }
+
+    /// Connect over Bluetooth, or to a simulated device in demo mode
+    async fn connect(&self, demo_mode: bool) -> Result<Link, Box<dyn Error>> {
+        let config = Config::load_or_default();
+        let mut transport = if demo_mode {
+            Box::new(transport::Demo::new(DeviceSettings::from_config(&config)))
+        } else {
+            transport::for_kind(TransportKind::Ble, &self.device, &config)
+        };
+        transport.connect().await?;
+        Link::open(transport).await
+    }
+
+    /// Sum up what was held during an outage and queue the frames that set the device
+    /// up, as the paired device gets them on every connection
+    fn connected(&mut self, shared: &Mutex<Shared>) {
+        let pushed = settings::load_pushed();
+        let shared = shared.lock().unwrap();
+        if let Some(since) = self.offline_since.take() {
+            let offline_secs = (Utc::now() - since).num_seconds().max(0) as u64;
+            let missed = coalesce::catch_up(&mut self.queue, shared.status.awaiting_permission.is_some(), offline_secs);
+            info!("{} back after {}s offline; {} held frames summed up", self.device.label(), offline_secs, missed);
+        }
+        if let Some(settings) = pushed {
+            self.push(DevicePayload::settings(settings));
+        }
+        if shared.status.idle_since.is_some() {
+            self.push(DevicePayload::presence(true));
+        }
+        self.push(DevicePayload::link_mode(shared.status.link_mode));
+        self.push(DevicePayload::time_sync());
+        self.state = LinkState::Ready;
+    }
+
+    /// Send the queued frames that may go out now
+    async fn flush(&mut self, link: &Link, maintenance: bool) -> Result<(), Box<dyn Error>> {
+        while let Some(index) = self.next_sendable(Instant::now(), maintenance) {
+            let payload = self.queue[index].clone();
+            if !payload.expired(Utc::now()) {
+                link.send(&payload, None).await?;
+                self.delivered += 1;
+                if !coalesce::is_urgent(&payload) {
+                    self.throttle.record(Instant::now());
+                }
+            }
+            self.queue.remove(index);
+        }
+        Ok(())
+    }
+
+    /// Show this device's link, queue, and deliveries in the daemon status
+    fn report(&self, shared: &Mutex<Shared>) {
+        if let Some(status) = shared.lock().unwrap().status.mirrors.get_mut(self.index) {
+            status.state = self.state;
+            status.queued = self.queue.len();
+            status.delivered = self.delivered;
+        }
+    }
+}
+
+/// Mirror to the `added` devices `[broadcast]` selects until `stopped`, each on its
+/// own link. They subscribe to the frames right away; the returned future drives them.
+/// The paired device is never mirrored to, even when it was also added.
+fn start_mirrors(
+    shared: &Arc<Mutex<Shared>>,
+    config: &Config,
+    added: Vec<PairedDevice>,
+    primary: Option<&PairedDevice>,
+    demo_mode: bool,
+    stopped: watch::Receiver<bool>,
+) -> impl std::future::Future<Output = Vec<()>> {
+    let mut targets = config.broadcast.targets(added);
+    targets.retain(|device| primary.is_none_or(|primary| primary.address != device.address));
+    if !targets.is_empty() {
+        info!("Mirroring frames to {} added device(s)", targets.len());
+    }
+    let min_frame_interval = Duration::from_millis(config.daemon.min_frame_interval_ms);
+    let reconnect_max = Duration::from_secs(config.daemon.reconnect_max_seconds);
+    let mirrors: Vec<_> = targets
+        .into_iter()
+        .enumerate()
+        .map(|(index, device)| {
+            let frames = {
+                let mut shared = shared.lock().unwrap();
+                let status = MirrorStatus { device: device.label(), state: LinkState::Starting, queued: 0, delivered: 0 };
+                shared.status.mirrors.push(status);
+                shared.live.subscribe()
+            };
+            let mirror = Mirror::new(index, device, min_frame_interval);
+            let backoff = Backoff::new(RETRY_BASE, reconnect_max);
+            run_mirror(shared.clone(), mirror, frames, demo_mode, backoff, stopped.clone())
+        })
+        .collect();
+    futures::future::join_all(mirrors)
+}
+
+/// Keep an added device in step with the paired one until the daemon stops: connect
+/// with its own backoff, and send it each frame published on `frames`
+async fn run_mirror(
+    shared: Arc<Mutex<Shared>>,
+    mut mirror: Mirror,
+    mut frames: broadcast::Receiver<DevicePayload>,
+    demo_mode: bool,
+    mut backoff: Backoff,
+    mut stopped: watch::Receiver<bool>,
+) {
+    let label = mirror.device.label();
+    let mut link: Option<Link> = None;
+    let mut retry_at = Instant::now();
+    loop {
+        if link.is_none() && Instant::now() >= retry_at {
+            mirror.state = LinkState::Starting;
+            mirror.report(&shared);
+            tokio::select! {
+                result = mirror.connect(demo_mode) => match result {
+                    Ok(connected) => {
+                        info!("Link to added device {} established over {}", label, connected.transport.name());
+                        backoff.reset();
+                        mirror.connected(&shared);
+                        link = Some(connected);
+                    }
+                    Err(e) => {
+                        let delay = backoff.next_delay();
+                        warn!("Connection to added device {} failed: {}; retrying in {:.1}s", label, e, delay.as_secs_f64());
+                        retry_at = Instant::now() + delay;
+                        mirror.state = LinkState::Disconnected;
+                    }
+                },
+                _ = stopped.changed() => break,
+            }
+        }
+        let maintenance = shared.lock().unwrap().status.maintenance;
+        if let Some(active) = &link {
+            // The error is not `Send`, so it is not held across the close
+            let failed = mirror.flush(active, maintenance).await.map_err(|e| e.to_string());
+            if let Err(e) = failed {
+                warn!("Link to added device {} lost: {}", label, e);
+                if let Some(lost) = link.take() {
+                    lost.close().await;
+                }
+                mirror.state = LinkState::Disconnected;
+                mirror.offline_since.get_or_insert_with(Utc::now);
+            }
+        }
+        mirror.report(&shared);
+        let throttled_until = mirror.throttle.ready_at().filter(|_| link.is_some() && !maintenance && !mirror.queue.is_empty());
+
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(payload) => mirror.push(payload),
+                Err(broadcast::error::RecvError::Lagged(missed)) => warn!("Added device {} skipped {} frames", label, missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = sleep_until(retry_at), if link.is_none() => {}
+            _ = sleep_until(throttled_until.unwrap_or(retry_at)), if throttled_until.is_some() => {}
+            _ = stopped.changed() => break,
+        }
+    }
+    if let Some(active) = link {
+        let _ = tokio::time::timeout(MIRROR_CLOSE, active.close()).await;
+    }
+}
+
+/// Run the monitoring daemon in the foreground until interrupted. With `observe_only`
+/// events are ingested and aggregated as usual, but nothing is sent to a device and no
+/// `on_event` commands are run.
+pub async fn run(interval: u64, faults: Option<FaultConfig>, observe_only: bool) -> Result<(), Box<dyn Error>> {
+    let (_lock, stale) = DaemonLock::acquire(&lock::lock_path()?)?;
+    if let Some(stale) = stale {
+        warn!("Recovered the lock of a daemon that did not exit cleanly ({})", stale);
+    }
+    let listener = ipc::bind().await?;
+    clock::start();
+    let demo_mode = std::env::var("CCGADGET_DEMO_MODE").is_ok();
+    let mut config = Config::load_or_default();
+    let loaded = config.clone();
+    if observe_only {
+        // User commands could reach the network, so they are off too
+        config.on_event.commands.clear();
+    }
+    let digest_interval = config
+        .notifications
+        .digest_minutes
+        .filter(|minutes| *minutes > 0)
+        .map(|minutes| Duration::from_secs(minutes * 60));
+    let upkeep = Upkeep::new(&config.daemon);
+    let device = if demo_mode || observe_only {
+        None
+    } else {
+        PairedDevice::load()?.or_else(|| PairedDevice::unpaired(&config.device.transports()))
+    };
+
+    let initial_state = if observe_only {
+        LinkState::Observing
+    } else if demo_mode || device.is_some() {
+        LinkState::Starting
+    } else {
+        LinkState::Unpaired
+    };
+    // Do-not-disturb outlasts the daemon, unless its time ran out meanwhile
+    let dnd_path = dnd::path().ok();
+    let shared = Arc::new(Mutex::new(Shared {
+        status: DaemonStatus {
+            state: initial_state,
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            device: if demo_mode {
+                Some(config.device.alias.clone().unwrap_or_else(|| "CCGadget-Demo".to_string()))
+            } else {
+                device.as_ref().map(|device| config.device.alias.clone().unwrap_or_else(|| device.label()))
+            },
+            queued: 0,
+            delivered: 0,
+            link_mode: config.device.link_mode,
+            profile: crate::config::profile().map(str::to_string),
+            maintenance: false,
+            focus_mode: false,
+            alerts_muted_until: None,
+            tool_block_armed: false,
+            dnd: dnd_path.as_deref().and_then(dnd::load),
+            quiet_hours: false,
+            idle_since: None,
+            context: None,
+            uptime_secs: None,
+            clock_jump_ms: 0,
+            clock_skew_ms: None,
+            transport: None,
+            protocol: None,
+            device_status: None,
+            last_acked: None,
+            retransmits: 0,
+            reconnects: 0,
+            retry_at: None,
+            link_error: None,
+            offline_since: None,
+            missed_heartbeats: 0,
+            mirrors: Vec::new(),
+            awaiting_permission: None,
+            limit: None,
+            budgets: Vec::new(),
+            burn: None,
+            session_cost: None,
+        },
+        queue: VecDeque::new(),
+        last_transcript: None,
+        sessions: Sessions::new(config.sessions.focus),
+        focus_changed: false,
+        page: config.display.page,
+        dnd_path,
+        digest: digest_interval.map(|_| Digest::default()),
+        alerts: config.alerts.clone(),
+        budgets: config.budgets.clone(),
+        budget_crossings: Crossings::default(),
+        desktop: config.desktop.clone(),
+        quiet_hours: config.quiet_hours.clone(),
+        device_settings: None,
+        settings_reads: 0,
+        settings_refresh: false,
+        last_activity: Instant::now(),
+        last_event: Instant::now(),
+        throttle: Throttle::new(Duration::from_millis(config.daemon.min_frame_interval_ms)),
+        feed: VecDeque::new(),
+        feed_seq: 0,
+        live: broadcast::channel(LIVE_FRAMES).0,
+        stop: Arc::new(Notify::new()),
+        reloaded: None,
+    }));
+    shared.lock().unwrap().update_quiet_hours(Utc::now());
+    let wake = Arc::new(Notify::new());
+    watch_config(&shared, &wake, loaded);
+    let (stopping, stopped) = watch::channel(false);
+    let added = if observe_only {
+        Vec::new()
+    } else {
+        PairedDevice::load_added().unwrap_or_else(|e| {
+            warn!("Not mirroring to added devices: {}", e);
+            Vec::new()
+        })
+    };
+    let mirrors = start_mirrors(&shared, &config, added, device.as_ref(), demo_mode, stopped);
+
+    let event_commands = EventCommands::new(config.on_event);
+    // Like user commands, export would send events off this machine
+    let exporter = match otel::Exporter::new(&config.otel) {
+        Ok(exporter) => exporter.filter(|_| !observe_only),
+        Err(e) => {
+            warn!("OpenTelemetry export disabled: {}", e);
+            None
+        }
+    };
+    if let Some(exporter) = &exporter {
+        exporter.start();
+    }
+    if config.api.enabled {
+        serve_api(&config.api, &shared, &wake).await;
+    }
+    tokio::spawn(ipc::serve(listener, {
+        let shared = shared.clone();
+        let wake = wake.clone();
+        move |request| {
+            if let (IpcRequest::Event { payload, .. }, Some(exporter)) = (&request, &exporter) {
+                exporter.record(payload);
+            }
+            serve_request(&shared, &wake, request, |payload| event_commands.dispatch(payload))
+        }
+    }));
+
+    info!("Daemon listening for events (pid {})", std::process::id());
+    if let Some(profile) = crate::config::profile() {
+        info!("Profile: {}", profile);
+    }
+    if observe_only {
+        info!("Observe-only: nothing is sent to devices or off this machine");
+    } else {
+        info!("Link mode: {} over {}", config.device.link_mode, config.device.transport);
+    }
+    if let Some(interval) = digest_interval {
+        info!("Notification digest every {} min", interval.as_secs() / 60);
+    }
+    if let Some(dnd) = shared.lock().unwrap().status.dnd {
+        info!("Do not disturb {}", dnd);
+    }
+    if let (false, Some(timeout)) = (observe_only, upkeep.idle_timeout) {
+        info!("Device link closes after {} min without events", timeout.as_secs() / 60);
+    }
+    let mut faults = faults.map(FaultInjector::new);
+    let daemon = async {
+        let result = run_loop(
+            &shared,
+            &wake,
+            device.as_ref(),
+            demo_mode,
+            interval,
+            digest_interval,
+            upkeep,
+            faults.as_mut(),
+        )
+        .await;
+        let _ = stopping.send(true);
+        result
+    };
+    let (result, _) = tokio::join!(daemon, mirrors);
+    ipc::cleanup();
+    result
+}
+
+/// Apply edits to the config file as they are saved, logging which settings changed
+/// and which only take effect after a restart. A config that does not load is
+/// reported and the daemon keeps the settings it has.
+fn watch_config(shared: &Arc<Mutex<Shared>>, wake: &Arc<Notify>, mut current: Config) {
+    let watching = Config::path().and_then(|path| reload::watch(&path).map_err(|e| format!("{}: {}", path.display(), e).into()));
+    let (watcher, mut changes) = match watching {
+        Ok(watching) => watching,
+        Err(e) => {
+            warn!("Not watching the config file, so edits need a restart: {}", e);
+            return;
+        }
+    };
+    let shared = shared.clone();
+    let wake = wake.clone();
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while changes.recv().await.is_some() {
+            sleep(RELOAD_SETTLE).await;
+            while changes.try_recv().is_ok() {}
+            let config = match Config::load() {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!("Config not reloaded: {}", e);
+                    continue;
+                }
+            };
+            let edited = reload::diff(&current, &config);
+            if edited.is_empty() {
+                continue;
+            }
+            let (live, restart): (Vec<_>, Vec<_>) = edited.iter().partition(|change| !change.restart);
+            let keys = |changes: &[&ConfigChange]| changes.iter().map(|change| change.key.as_str()).collect::<Vec<_>>().join(", ");
+            if !live.is_empty() {
+                info!("Config reloaded: {}", keys(&live));
+            }
+            if !restart.is_empty() {
+                warn!("Config changed, but these settings take effect after a restart: {}", keys(&restart));
+            }
+            shared.lock().unwrap().apply_config(&config, &edited);
+            wake.notify_one();
+            current = config;
+        }
+    });
+}
+
+/// Resolves on Ctrl-C, on SIGTERM from a service manager, or when a client asks
+async fn shutdown_requested(stop: &Notify) {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+        _ = stop.notified() => {}
+    }
+}
+
+/// Start the local API, or warn and go on without it when its port is taken
+async fn serve_api(config: &ApiConfig, shared: &Arc<Mutex<Shared>>, wake: &Arc<Notify>) {
+    let listener = match api::bind(config.port).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Local API disabled: could not listen on port {}: {}", config.port, e);
+            return;
+        }
+    };
+    info!("Local API on http://127.0.0.1:{}", config.port);
+    let live = shared.lock().unwrap().live.clone();
+    tokio::spawn(api::serve(listener, config.allowed_origins.clone(), live, {
+        let shared = shared.clone();
+        let wake = wake.clone();
+        move |query| match query {
+            Query::Status => match handle_request(&shared, &wake, IpcRequest::Status) {
+                IpcResponse::Status(status) => serde_json::to_value(status).unwrap_or_default(),
+                _ => serde_json::Value::Null,
+            },
+            Query::Usage => {
+                let (sessions, fallback) = {
+                    let shared = shared.lock().unwrap();
+                    (shared.sessions.clone(), shared.last_transcript.clone())
+                };
+                serde_json::to_value(usage_update(sessions, fallback).0).unwrap_or_default()
+            }
+        }
+    }));
+}
+
+/// Answer one IPC request, then pass an event the daemon took on to `handled`, so
+/// `on_event` commands see it only once it is queued, held, or digested
+fn serve_request(shared: &Mutex<Shared>, wake: &Notify, request: IpcRequest, handled: impl FnOnce(&DevicePayload)) -> IpcResponse {
+    let event = match &request {
+        IpcRequest::Event { payload, .. } => Some(payload.clone()),
+        _ => None,
+    };
+    let response = handle_request(shared, wake, request);
+    if let Some(payload) = event {
+        if matches!(
+            response,
+            IpcResponse::Accepted | IpcResponse::Queued { .. } | IpcResponse::Paused { .. } | IpcResponse::Digested { .. }
+        ) {
+            handled(&payload);
+        }
+    }
+    response
+}
+
+/// Answer an IPC request against the shared daemon state
+fn handle_request(shared: &Mutex<Shared>, wake: &Notify, request: IpcRequest) -> IpcResponse {
+    let mut shared = shared.lock().unwrap();
+    // Anything but a query keeps an idle link open, or reopens it
+    if !matches!(
+        request,
+        IpcRequest::Status
+            | IpcRequest::Sessions
+            | IpcRequest::Feed { .. }
+            | IpcRequest::ReadSettings { refresh: false }
+            | IpcRequest::TakeToolBlock
+    ) {
+        shared.last_activity = Instant::now();
+    }
+    match request {
+        IpcRequest::Status => {
+            let now = Stamp::now();
+            let mut status = shared.status.clone();
+            status.uptime_secs = Some(now.since(clock::start()) / 1000);
+            status.clock_jump_ms = clock::wall_jump_ms(clock::start(), now);
+            status.limit = status.limit.filter(|limit| limit.active(now.wall));
+            IpcResponse::Status(Box::new(status))
+        }
+        IpcRequest::Event { mut payload, transcript_path } => {
+            shared.note_event();
+            if let Some(session_id) = payload.session_id.as_deref() {
+                let before = shared.focused_id();
+                shared.sessions.record(session_id, transcript_path.clone(), Utc::now());
+                shared.note_focus(before);
+                // Events from other sessions must not take over the primary screen
+                payload.background = !shared.sessions.is_focused(Some(session_id));
+            }
+            shared.record_feed(&payload);
+            if let (false, Some(context)) = (payload.background, payload.context) {
+                shared.status.context = Some(context);
+            }
+            if let Some(limit) = &payload.limit {
+                shared.status.limit = Some(limit.clone());
+            }
+            if transcript_path.is_some() {
+                shared.last_transcript = transcript_path;
+            }
+            let answered = shared
+                .status
+                .awaiting_permission
+                .as_ref()
+                .is_some_and(|wait| payload.event != "Notification" && wait.session_id == payload.session_id);
+            if answered {
+                // The session moved on, so the request was answered; clear it on the device
+                shared.status.awaiting_permission = None;
+                shared.enqueue(DevicePayload::acknowledge());
+            }
+            let alerted = payload.event == "Notification" && shared.queue_alert(&mut payload);
+            if payload.event == "Notification" {
+                shared.note_session_alert(&payload);
+            }
+            if payload.event == "Stop" {
+                // The session paused; deliver held notifications ahead of the Stop
+                shared.flush_digest();
+            } else if digest::is_digestible(&payload) && !alerted {
+                if let Some(digest) = shared.digest.as_mut() {
+                    digest.push(*payload);
+                    return IpcResponse::Digested { pending: digest.len() };
+                }
+            }
+            shared.enqueue(*payload);
+            wake.notify_one();
+            shared.delivery_response()
+        }
+        IpcRequest::SetLinkMode { mode } => {
+            shared.status.link_mode = mode;
+            shared.queue_link_mode();
+            wake.notify_one();
+            info!("Link mode: {}", mode);
+            IpcResponse::Accepted
+        }
+        IpcRequest::SetMaintenance { enabled } => {
+            shared.set_maintenance(enabled);
+            wake.notify_one();
+            IpcResponse::Accepted
+        }
+        IpcRequest::SetDnd { enabled, until } => {
+            shared.set_dnd(enabled.then(|| DoNotDisturb::new(until)));
+            wake.notify_one();
+            IpcResponse::Accepted
+        }
+        IpcRequest::ReadSettings { refresh } => {
+            if refresh {
+                shared.settings_refresh = true;
+                wake.notify_one();
+            }
+            IpcResponse::Settings {
+                state: shared.status.state,
+                settings: shared.device_settings.clone(),
+                reads: shared.settings_reads,
+            }
+        }
+        IpcRequest::PushSettings { settings } => {
+            shared.queue_control(DevicePayload::settings(settings));
+            wake.notify_one();
+            info!("Pushing display settings to the device");
+            shared.delivery_response()
+        }
+        IpcRequest::Sessions => {
+            shared.sessions.expire(Utc::now());
+            shared.sessions_response()
+        }
+        IpcRequest::Focus { session, policy, next } => {
+            let before = shared.focused_id();
+            shared.sessions.expire(Utc::now());
+            if let Some(policy) = policy {
+                shared.sessions.set_policy(policy);
+            }
+            if let Some(prefix) = session {
+                if let Err(message) = shared.sessions.pin(&prefix) {
+                    return IpcResponse::Error { message };
+                }
+            } else if next && shared.sessions.pin_next().is_none() {
+                return IpcResponse::Error { message: "No active sessions".to_string() };
+            }
+            info!("Session focus: {}", shared.sessions.policy);
+            shared.note_focus(before);
+            wake.notify_one();
+            shared.sessions_response()
+        }
+        IpcRequest::DeviceEvent { event } => {
+            handle_device_event(&mut shared, event);
+            wake.notify_one();
+            IpcResponse::Accepted
+        }
+        IpcRequest::Feed { after } => {
+            let events = shared.feed.iter().filter(|entry| entry.seq > after).cloned().collect();
+            IpcResponse::Feed { events }
+        }
+        IpcRequest::TakeToolBlock => {
+            let armed = std::mem::take(&mut shared.status.tool_block_armed);
+            if armed {
+                info!("Denying the tool call, as asked from the device");
+            }
+            IpcResponse::ToolBlock { armed }
+        }
+        IpcRequest::Shutdown => {
+            info!("Shutdown requested by another ccgadget");
+            shared.stop.notify_one();
+            IpcResponse::ShuttingDown
+        }
+    }
+}
+
+/// Carry out what a device event calls for: the configured action for a button
+/// press, or recording a status change
+fn handle_device_event(shared: &mut Shared, event: DeviceEvent) {
+    match &event {
+        DeviceEvent::Button { button, long } => {
+            let action = Config::load_or_default().button_action(button, *long);
+            info!("Button {}{} pressed: {}", button, if *long { " (long)" } else { "" }, action);
+            run_button_action(shared, action, button, *long);
+        }
+        DeviceEvent::Status { status, detail } => {
+            match detail {
+                Some(detail) => info!("Device status: {} ({})", status, detail),
+                None => info!("Device status: {}", status),
+            }
+            let reported = shared.status.device_status.as_ref() == Some(status);
+            if status == device::UPDATE_AVAILABLE && !reported && shared.desktop.firmware_update {
+                let version = detail.as_deref().map(|version| format!(" ({})", version)).unwrap_or_default();
+                let device = shared.status.device.clone().unwrap_or_else(|| "the device".to_string());
+                show_notification("CCGadget firmware update", format!("A firmware update{} is available for {}", version, device));
+            }
+            shared.status.device_status = Some(status.clone());
+        }
+    }
+}
+
+/// Carry out the action configured for a button press
+fn run_button_action(shared: &mut Shared, action: ButtonAction, button: &str, long: bool) {
+    match action {
+        ButtonAction::Acknowledge => shared.queue_control(DevicePayload::acknowledge()),
+        ButtonAction::Pause => {
+            let enabled = !shared.status.maintenance;
+            shared.set_maintenance(enabled);
+        }
+        ButtonAction::NextSession => {
+            let before = shared.focused_id();
+            shared.sessions.expire(Utc::now());
+            if shared.sessions.pin_next().is_none() {
+                debug!("No active sessions to move the focus to");
+            }
+            shared.note_focus(before);
+        }
+        ButtonAction::FocusMode => {
+            shared.status.focus_mode = !shared.status.focus_mode;
+            let label = if shared.status.focus_mode { "Focus mode on" } else { "Focus mode off" };
+            info!("{}", label);
+            shared.enqueue(DevicePayload::screen_message(label, 3, Severity::Info));
+        }
+        ButtonAction::MuteAlerts => {
+            let muted = shared.status.alerts_muted_until.is_some_and(|until| until > Utc::now());
+            let minutes = shared.alerts.mute_minutes;
+            shared.status.alerts_muted_until = (!muted).then(|| Utc::now() + chrono::Duration::minutes(minutes as i64));
+            let label = if muted { "Alerts back on".to_string() } else { format!("Alerts muted for {} min", minutes) };
+            info!("{}", label);
+            shared.enqueue(DevicePayload::screen_message(&label, 3, Severity::Info));
+        }
+        ButtonAction::NextPage => {
+            shared.page = shared.page.next();
+            info!("Display page: {}", shared.page);
+            let page = shared.page;
+            shared.queue_control(DevicePayload::page(page));
+        }
+        ButtonAction::BlockNextTool => {
+            shared.status.tool_block_armed = !shared.status.tool_block_armed;
+            let label = if shared.status.tool_block_armed { "Next tool call will be denied" } else { "Tool call block cancelled" };
+            info!("{}", label);
+            shared.enqueue(DevicePayload::screen_message(label, 3, Severity::Info));
+        }
+        ButtonAction::Dnd => {
+            let dnd = (!shared.dnd_active()).then(|| DoNotDisturb::new(None));
+            shared.set_dnd(dnd);
+        }
+        ButtonAction::Command(command) => {
+            let config = Config::load_or_default();
+            let input = serde_json::to_vec(&DeviceEvent::Button { button: button.to_string(), long }).unwrap_or_default();
+            let timeout = Duration::from_secs(config.on_event.timeout_seconds);
+            let label = format!("buttons.{}{}", button, if long { "_long" } else { "" });
+            tokio::spawn(async move {
+                if let Err(e) = on_event::run(&command, &input, timeout).await {
+                    warn!("{} failed: {}", label, e);
+                }
+            });
+        }
+        ButtonAction::Ignore => {}
+    }
+}
+
+/// Update the link state reported to clients
+fn set_state(shared: &Mutex<Shared>, state: LinkState) {
+    shared.lock().unwrap().status.state = state;
+}
+
+/// Mark the device offline after its link was lost; events queue until it is back.
+/// The first loss of an outage shows a desktop notification, if configured.
+fn went_offline(shared: &Mutex<Shared>) {
+    let mut shared = shared.lock().unwrap();
+    shared.status.state = LinkState::Disconnected;
+    if shared.status.offline_since.is_some() {
+        return;
+    }
+    shared.status.offline_since = Some(Utc::now());
+    if shared.desktop.disconnected {
+        let device = shared.status.device.clone().unwrap_or_else(|| "The device".to_string());
+        show_notification("CCGadget offline", format!("{} stopped answering; events are held until it is back", device));
+    }
+}
+
+/// Show a desktop notification off the runtime, logging when it cannot be shown
+fn show_notification(summary: &'static str, body: String) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = desktop::notify(summary, &body) {
+            warn!("Could not show a desktop notification: {}", e);
+        }
+    });
+}
+
+/// Connect, deliver queued events, and push periodic usage updates. The link stays
+/// open between transmissions: it is checked after `keepalive` without traffic,
+/// closed after `idle_timeout` without activity, and reopened with backoff when lost.
+#[allow(clippy::too_many_arguments)]
+async fn run_loop(
+    shared: &Mutex<Shared>,
+    wake: &Notify,
+    device: Option<&PairedDevice>,
+    demo_mode: bool,
+    interval: u64,
+    digest_interval: Option<Duration>,
+    mut upkeep: Upkeep,
+    mut faults: Option<&mut FaultInjector>,
+) -> Result<(), Box<dyn Error>> {
+    // Observe-only daemons start in the Observing state and never connect
+    let observe_only = shared.lock().unwrap().status.state == LinkState::Observing;
+    let can_connect = !observe_only && (demo_mode || device.is_some());
+    let mut link: Option<Link> = observe_only.then(|| Link::new(Box::new(transport::Observe)));
+    let mut connector = transport::Connector::default();
+    let mut events: Option<EventStream> = None;
+    let mut last_flush = Instant::now();
+    // Last frame exchanged with the device, for the keepalive check
+    let mut last_traffic = Instant::now();
+    let mut last_check = Instant::now();
+    // Permission wait already shown as a desktop notification, by when it began
+    let mut notified_wait = None;
+    let mut retry_at = Instant::now();
+    let mut connected_before = false;
+    let mut clock_jump_ms = 0;
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+    let digest_period = digest_interval.unwrap_or(Duration::from_secs(3600));
+    let mut digest_ticker = tokio::time::interval_at(Instant::now() + digest_period, digest_period);
+    let mut budget_ticker = tokio::time::interval(BUDGET_CHECK);
+    let stop = shared.lock().unwrap().stop.clone();
+    let shutdown = shutdown_requested(&stop);
+    tokio::pin!(shutdown);
+
+    loop {
+        let idle_at = upkeep.idle_timeout.filter(|_| can_connect).map(|timeout| shared.lock().unwrap().last_activity + timeout);
+        let idle = idle_at.is_some_and(|at| Instant::now() >= at);
+        if idle && (link.is_none() || shared.lock().unwrap().queue.is_empty()) {
+            // Frames still waiting keep the link open until they are delivered
+            if let Some(active) = link.take() {
+                active.close().await;
+            }
+            events = None;
+            let mut shared = shared.lock().unwrap();
+            if shared.status.state != LinkState::Idle {
+                let minutes = upkeep.idle_timeout.unwrap_or_default().as_secs() / 60;
+                info!("No events for {} min; closing the device link until the next one", minutes);
+                shared.status.state = LinkState::Idle;
+                shared.status.retry_at = None;
+            }
+        }
+
+        if link.is_none() && can_connect && !idle && Instant::now() >= retry_at {
+            set_state(shared, LinkState::Starting);
+            let attempt = async {
+                match device {
+                    Some(device) if !demo_mode => {
+                        let config = Config::load_or_default();
+                        Link::open(connector.connect(device, &config).await?).await
+                    }
+                    _ => {
+                        sleep(Duration::from_secs(1)).await;
+                        let settings = DeviceSettings::from_config(&Config::load_or_default());
+                        Link::open(Box::new(transport::Demo::new(settings))).await
+                    }
+                }
+            };
+            tokio::select! {
+                result = attempt => match result {
+                    Ok(connected) => {
+                        info!("Device link established over {} (protocol v{})", connected.transport.name(), connected.version);
+                        upkeep.backoff.reset();
+                        match sync_clock(&connected).await {
+                            Ok(skew) => shared.lock().unwrap().status.clock_skew_ms = skew,
+                            Err(e) => warn!("Time sync failed: {}", e),
+                        }
+                        events = match connected.transport.events().await {
+                            Ok(events) => events,
+                            Err(e) => {
+                                warn!("Could not subscribe to device events: {}", e);
+                                None
+                            }
+                        };
+                        last_traffic = Instant::now();
+                        let mut shared = shared.lock().unwrap();
+                        shared.status.transport = Some(connected.transport.name().to_string());
+                        shared.status.protocol = Some(connected.version);
+                        shared.status.retry_at = None;
+                        shared.status.link_error = None;
+                        if connected_before {
+                            shared.status.reconnects += 1;
+                        }
+                        connected_before = true;
+                        link = Some(connected);
+                        shared.status.missed_heartbeats = 0;
+                        if let Some(since) = shared.status.offline_since.take() {
+                            let offline_secs = (Utc::now() - since).num_seconds().max(0) as u64;
+                            let still_waiting = shared.status.awaiting_permission.is_some();
+                            let missed = coalesce::catch_up(&mut shared.queue, still_waiting, offline_secs);
+                            shared.status.queued = shared.queue.len();
+                            info!("Device back after {}s offline; {} held frames summed up", offline_secs, missed);
+                        }
+                        shared.queue_setup(settings::load_pushed());
+                        shared.settings_refresh = true;
+                        shared.status.state = LinkState::Ready;
+                    }
+                    Err(e) => {
+                        let delay = upkeep.backoff.next_delay();
+                        let attempts = upkeep.backoff.attempts();
+                        warn!("Device connection failed ({} in a row): {}; retrying in {:.1}s", attempts, e, delay.as_secs_f64());
+                        retry_at = Instant::now() + delay;
+                        let mut shared = shared.lock().unwrap();
+                        shared.status.state = LinkState::Disconnected;
+                        shared.status.retry_at = chrono::Duration::from_std(delay).ok().map(|delay| Utc::now() + delay);
+                        shared.status.link_error = Some(e.to_string());
+                    }
+                },
+                _ = &mut shutdown => break,
+            }
+        }
+
+        // Battery-saver holds frames for a batch window so the radio wakes less often
+        let batch_window = shared.lock().unwrap().status.link_mode.batch_window();
+        let flush_at = last_flush + batch_window;
+        if let Some(active) = &link {
+            if Instant::now() >= flush_at {
+                match flush_queue(shared, active, faults.as_deref_mut()).await {
+                    Ok(0) => {}
+                    Ok(_) => last_traffic = Instant::now(),
+                    Err(e) => {
+                        warn!("Device link lost: {}", e);
+                        if let Some(lost) = link.take() {
+                            lost.close().await;
+                        }
+                        events = None;
+                        went_offline(shared);
+                    }
+                }
+                last_flush = Instant::now();
+            }
+        }
+        // Read the device settings after connecting, on request, and after a push
+        let refresh = link.is_some() && std::mem::take(&mut shared.lock().unwrap().settings_refresh);
+        if let (true, Some(active)) = (refresh, &link) {
+            match active.device_settings().await {
+                Ok(settings) => {
+                    last_traffic = Instant::now();
+                    let mut shared = shared.lock().unwrap();
+                    shared.device_settings = settings;
+                    shared.settings_reads += 1;
+                }
+                Err(e) => {
+                    warn!("Device link lost while reading settings: {}", e);
+                    if let Some(lost) = link.take() {
+                        lost.close().await;
+                    }
+                    events = None;
+                    shared.lock().unwrap().settings_refresh = true;
+                    went_offline(shared);
+                }
+            }
+        }
+        let (held, throttled_until) = {
+            let shared = shared.lock().unwrap();
+            let now = Instant::now();
+            let connected = link.is_some();
+            (connected && shared.next_sendable(now).is_some(), shared.throttled_until(now).filter(|_| connected))
+        };
+        // Redraw usage for the newly focused session without waiting for the interval
+        if std::mem::take(&mut shared.lock().unwrap().focus_changed) {
+            ticker.reset_immediately();
+        }
+        let reloaded = shared.lock().unwrap().reloaded.take();
+        if let Some(reloaded) = reloaded {
+            if reloaded.interval_changed {
+                ticker = tokio::time::interval(Duration::from_secs(reloaded.daemon.interval_seconds.max(1)));
+            }
+            // Starts the reconnection backoff over
+            upkeep = Upkeep::new(&reloaded.daemon);
+        }
+        let check_at = upkeep
+            .keepalive
+            .filter(|_| can_connect && link.is_some())
+            .map(|keepalive| last_traffic.max(last_check) + keepalive);
+        let retrying = link.is_none() && can_connect && !idle;
+        let check_budgets = budget::configured(&shared.lock().unwrap().budgets);
+        let presence_at = {
+            let shared = shared.lock().unwrap();
+            upkeep.presence_idle.filter(|_| shared.status.idle_since.is_none()).map(|after| shared.last_event + after)
+        };
+        let dnd_at = shared
+            .lock()
+            .unwrap()
+            .status
+            .dnd
+            .and_then(|dnd| dnd.until)
+            .map(|until| Instant::now() + (until - Utc::now()).to_std().unwrap_or_default());
+        // Windows start and end on the minute
+        let quiet_check_at = (!shared.lock().unwrap().quiet_hours.windows.is_empty())
+            .then(|| Instant::now() + Duration::from_secs(60 - Utc::now().second() as u64));
+        let permission_at = {
+            let shared = shared.lock().unwrap();
+            let after = shared.desktop.permission_after_seconds;
+            shared
+                .status
+                .awaiting_permission
+                .as_ref()
+                .filter(|wait| after > 0 && Some(wait.since) != notified_wait)
+                .map(|wait| Instant::now() + (wait.since + chrono::Duration::seconds(after as i64) - Utc::now()).to_std().unwrap_or_default())
+        };
+
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = ticker.tick(), if link.is_some() => {
+                let (sessions, fallback, maintenance) = {
+                    let mut shared = shared.lock().unwrap();
+                    shared.sessions.expire(Utc::now());
+                    (shared.sessions.clone(), shared.last_transcript.clone(), shared.status.maintenance)
+                };
+                // Re-sync the device after the system clock is stepped (manual change, NTP)
+                let jump = clock::wall_jump_ms(clock::start(), Stamp::now());
+                if (jump - clock_jump_ms).abs() > clock::SKEW_WARN_MS {
+                    warn!("System clock moved {:+.1}s; re-syncing the device", (jump - clock_jump_ms) as f64 / 1000.0);
+                    clock_jump_ms = jump;
+                    shared.lock().unwrap().queue_control(DevicePayload::time_sync());
+                }
+                if maintenance {
+                    continue;
+                }
+                let (update, cost_usd, rates) = tokio::task::spawn_blocking(move || usage_update(sessions, fallback)).await?;
+                let mut shared = shared.lock().unwrap();
+                let before = shared.focused_id();
+                shared.sessions.set_burn(&rates);
+                shared.note_focus(before);
+                shared.status.burn = update.burn.clone();
+                shared.status.session_cost = cost_usd.map(|cost_usd| SessionCost { session_id: update.session_id.clone(), cost_usd });
+                shared.enqueue_usage(update);
+            }
+            _ = digest_ticker.tick(), if digest_interval.is_some() => {
+                shared.lock().unwrap().flush_digest();
+            }
+            _ = budget_ticker.tick(), if check_budgets => {
+                let config = Config::load_or_default();
+                let usages = match tokio::task::spawn_blocking({
+                    let config = config.clone();
+                    move || budget::current(&config).map_err(|e| e.to_string())
+                })
+                .await?
+                {
+                    Ok(usages) => usages,
+                    Err(e) => {
+                        warn!("Could not measure budget consumption: {}", e);
+                        continue;
+                    }
+                };
+                let alerts = shared.lock().unwrap().check_budgets(usages);
+                if !alerts.is_empty() {
+                    tokio::task::spawn_blocking(move || record_budget_alerts(&config, &alerts));
+                }
+            }
+            _ = sleep_until(flush_at), if held => {}
+            _ = sleep_until(presence_at.unwrap_or(flush_at)), if presence_at.is_some() => {
+                shared.lock().unwrap().go_idle(upkeep.presence_idle.unwrap_or_default());
+            }
+            _ = sleep_until(dnd_at.unwrap_or(flush_at)), if dnd_at.is_some() => {
+                shared.lock().unwrap().expire_dnd();
+            }
+            _ = sleep_until(quiet_check_at.unwrap_or(flush_at)), if quiet_check_at.is_some() => {
+                shared.lock().unwrap().update_quiet_hours(Utc::now());
+            }
+            _ = sleep_until(permission_at.unwrap_or(flush_at)), if permission_at.is_some() => {
+                let Some(wait) = shared.lock().unwrap().status.awaiting_permission.clone() else { continue };
+                let waited = (Utc::now() - wait.since).num_seconds();
+                if Some(wait.since) == notified_wait || waited < shared.lock().unwrap().desktop.permission_after_seconds as i64 {
+                    continue;
+                }
+                notified_wait = Some(wait.since);
+                let tool = wait.tool.map(|tool| format!(" to use {}", tool)).unwrap_or_default();
+                show_notification("Claude is waiting", format!("Claude has been waiting {}s for permission{}", waited, tool));
+            }
+            _ = sleep_until(throttled_until.unwrap_or(flush_at)), if throttled_until.is_some() => {}
+            _ = sleep_until(check_at.unwrap_or(retry_at)), if check_at.is_some() => {
+                let Some(active) = &link else { continue };
+                last_check = Instant::now();
+                match active.check().await {
+                    Ok(()) => {
+                        debug!("Device link is up");
+                        last_traffic = Instant::now();
+                        shared.lock().unwrap().status.missed_heartbeats = 0;
+                    }
+                    Err(e) => {
+                        let missed = {
+                            let mut shared = shared.lock().unwrap();
+                            shared.status.missed_heartbeats += 1;
+                            shared.status.missed_heartbeats
+                        };
+                        if missed < upkeep.heartbeat_misses {
+                            warn!("Device missed a heartbeat ({} of {}): {}", missed, upkeep.heartbeat_misses, e);
+                            continue;
+                        }
+                        warn!("Device offline after {} missed heartbeats: {}", missed, e);
+                        if let Some(lost) = link.take() {
+                            lost.close().await;
+                        }
+                        events = None;
+                        went_offline(shared);
+                    }
+                }
+            }
+            // Wakes the loop to close the link once the idle timeout passes
+            _ = sleep_until(idle_at.unwrap_or(retry_at)), if idle_at.is_some() && link.is_some() && !idle => {}
+            event = next_event(&mut events), if events.is_some() => match event {
+                Some(frame) => {
+                    last_traffic = Instant::now();
+                    match protocol::decode::<DeviceEvent>(&frame) {
+                        Ok((_, event)) => handle_device_event(&mut shared.lock().unwrap(), event),
+                        Err(e) => warn!("Unreadable device event: {}", e),
+                    }
+                }
+                None => {
+                    debug!("Device event subscription ended");
+                    events = None;
+                }
+            },
+            _ = sleep_until(retry_at), if retrying => {}
+            _ = &mut shutdown => break,
+        }
+    }
+
+    info!("Daemon shutting down");
+    if let Some(active) = link {
+        active.close().await;
+    }
+    Ok(())
+}
+
+/// Next frame the device sends on its own; `None` once the subscription ends
+async fn next_event(events: &mut Option<EventStream>) -> Option<Vec<u8>> {
+    events.as_mut()?.next().await
+}
+
+/// Send every queued payload, keeping unsent ones on failure. Returns how many were sent.
+async fn flush_queue(
+    shared: &Mutex<Shared>,
+    link: &Link,
+    mut faults: Option<&mut FaultInjector>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut sent = 0;
+    loop {
+        let next = shared.lock().unwrap().next_sendable(Instant::now()).cloned();
+        let Some(payload) = next else {
+            return Ok(sent);
+        };
+        if payload.expired(Utc::now()) {
+            debug!("Dropping {} frame that expired while queued", payload.event);
+            shared.lock().unwrap().remove(&payload);
+            continue;
+        }
+        let delivery = link.send(&payload, faults.as_deref_mut()).await?;
+        sent += 1;
+        let mut shared = shared.lock().unwrap();
+        shared.remove(&payload);
+        if !coalesce::is_urgent(&payload) {
+            shared.throttle.record(Instant::now());
+        }
+        shared.status.delivered += 1;
+        shared.status.retransmits += u64::from(delivery.retransmits);
+        if let Some(seq) = delivery.acked {
+            shared.status.last_acked = Some(AckedEvent { event: payload.event.clone(), seq, at: Utc::now() });
+        }
+        if payload.event == SETTINGS_EVENT {
+            shared.settings_refresh = true;
+        }
+    }
+}
+
+/// Log each budget alert as a `Budget` event, and show it on the desktop if configured
+fn record_budget_alerts(config: &Config, alerts: &[BudgetAlert]) {
+    for alert in alerts {
+        let event = HookInput {
+            hook_event_name: Some(BUDGET_EVENT.to_string()),
+            message: Some(alert.message()),
+            ..Default::default()
+        };
+        if let Err(e) = trigger::log_trigger_payload(config, Some(&event), None, None, None) {
+            warn!("Could not log the budget alert: {}", e);
+        }
+        if config.budgets.desktop_notification || (config.desktop.budget_exceeded && alert.threshold >= 100.0) {
+            if let Err(e) = desktop::notify("CCGadget budget", &alert.message()) {
+                warn!("Could not show a desktop notification: {}", e);
+            }
+        }
+    }
+}
+
+/// Build a periodic usage payload for the focused session, falling back to the latest
+/// transcript, along with that session's estimated cost. Under the highest-burn policy,
+/// also measures each session's token velocity; the rates are returned by session id.
+fn usage_update(mut sessions: Sessions, fallback: Option<String>) -> (DevicePayload, Option<f64>, Vec<(String, f64)>) {
+    let config = Config::load_or_default();
+    let mut rates = Vec::new();
+    let aggregate = config.sessions.aggregate && sessions.active().len() > 1;
+    if sessions.policy == focus::FocusPolicy::HighestBurn || aggregate {
+        let now = Utc::now();
+        for session in sessions.active() {
+            let Some(path) = &session.transcript else { continue };
+            match transcript::read_usage_records(PathBuf::from(path).as_path()) {
+                Ok(records) => rates.push((
+                    session.session_id.clone(),
+                    focus::tokens_per_minute(&records, config.limits.burn_window_minutes, now),
+                )),
+                Err(e) => debug!("Could not read {} for burn rate: {}", path, e),
+            }
+        }
+        sessions.set_burn(&rates);
+    }
+
+    let focused = sessions.focused();
+    let transcript = focused.and_then(|session| session.transcript.clone()).or(fallback).map(PathBuf::from);
+    let cost = cost::snapshot(&config, transcript.as_deref());
+    let (widgets, _) = metrics::widget_values(&config, cost.as_ref(), None);
+    let mut payload = DevicePayload::usage(widgets);
+    payload.session_id = focused.map(|session| session.session_id.clone());
+    payload.burn = burn::current(&config);
+    if aggregate {
+        payload.sessions = sessions.aggregate();
+    }
+    let session_cost = cost.and_then(|cost| cost.session).map(|session| session.cost_usd);
+    (payload, session_cost, rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::{ACKNOWLEDGE_EVENT, ALERT_EVENT, DND_EVENT, PAGE_EVENT, PERMISSION_EVENT};
+
+    /// Fresh shared state in the given link state
+    fn shared_in(state: LinkState) -> Mutex<Shared> {
+        Mutex::new(Shared {
+            status: DaemonStatus {
+                state,
+                pid: 1,
+                started_at: Utc::now(),
+                device: None,
+                queued: 0,
+                delivered: 0,
+                link_mode: LinkMode::LowLatency,
+                profile: None,
+                maintenance: false,
+                focus_mode: false,
+                alerts_muted_until: None,
+                tool_block_armed: false,
+                dnd: None,
+                quiet_hours: false,
+                idle_since: None,
+                context: None,
+                uptime_secs: None,
+                clock_jump_ms: 0,
+                clock_skew_ms: None,
+                transport: None,
+                protocol: None,
+                device_status: None,
+                last_acked: None,
+                retransmits: 0,
+                reconnects: 0,
+                retry_at: None,
+                link_error: None,
+                offline_since: None,
+                missed_heartbeats: 0,
+                mirrors: Vec::new(),
+                awaiting_permission: None,
+                limit: None,
+                budgets: Vec::new(),
+                burn: None,
+                session_cost: None,
+            },
+            queue: VecDeque::new(),
+            last_transcript: None,
+            sessions: Sessions::default(),
+            focus_changed: false,
+            page: DisplayPage::default(),
+            dnd_path: None,
+            digest: None,
+            alerts: AlertsConfig::default(),
+            budgets: BudgetsConfig::default(),
+            budget_crossings: Crossings::default(),
+            // Tests must not pop up notifications
+            desktop: DesktopConfig {
+                disconnected: false,
+                budget_exceeded: false,
+                permission_after_seconds: 0,
+                firmware_update: false,
+            },
+            quiet_hours: QuietHoursConfig::default(),
+            device_settings: None,
+            settings_reads: 0,
+            settings_refresh: false,
+            last_activity: Instant::now(),
+            last_event: Instant::now(),
+            throttle: Throttle::new(Duration::ZERO),
+            feed: VecDeque::new(),
+            feed_seq: 0,
+            live: broadcast::channel(1).0,
+            stop: Arc::new(Notify::new()),
+            reloaded: None,
+        })
+    }
+
+    #[test]
+    fn test_events_queue_while_starting() {
+        let shared = shared_in(LinkState::Starting);
+        let wake = Notify::new();
+        let event = IpcRequest::Event {
+            payload: Box::new(DevicePayload::usage(Default::default())),
+            transcript_path: Some("/tmp/t.jsonl".to_string()),
+        };
+
+        let response = handle_request(&shared, &wake, event.clone());
+        assert!(matches!(response, IpcResponse::Queued { state: LinkState::Starting, queued: 1 }));
+
+        set_state(&shared, LinkState::Ready);
+        assert!(matches!(handle_request(&shared, &wake, event), IpcResponse::Accepted));
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.status.queued, 2);
+        assert_eq!(shared.last_transcript.as_deref(), Some("/tmp/t.jsonl"));
+    }
+
+    #[test]
+    fn test_event_commands_see_events_once_handled() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let mut payload = DevicePayload::usage(Default::default());
+        payload.event = "Stop".to_string();
+        let event = IpcRequest::Event { payload: Box::new(payload), transcript_path: None };
+
+        let mut handled = Vec::new();
+        let response = serve_request(&shared, &wake, event, |payload| {
+            // The daemon has already queued the event when its command starts
+            let queued: Vec<String> = shared.lock().unwrap().queue.iter().map(|queued| queued.event.clone()).collect();
+            handled.push((payload.event.clone(), queued));
+        });
+        assert!(matches!(response, IpcResponse::Accepted));
+        assert_eq!(handled, [("Stop".to_string(), vec!["Stop".to_string()])]);
+
+        serve_request(&shared, &wake, IpcRequest::Status, |_| panic!("only events run commands"));
+    }
+
+    #[test]
+    fn test_unfocused_sessions_send_background_events() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let event = |session: &str| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = "PreToolUse".to_string();
+            payload.session_id = Some(session.to_string());
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+        handle_request(&shared, &wake, event("alpha"));
+        handle_request(&shared, &wake, event("beta"));
+        let pin = IpcRequest::Focus { session: Some("al".to_string()), policy: None, next: false };
+        let IpcResponse::Sessions { focused, sessions, .. } = handle_request(&shared, &wake, pin) else {
+            panic!("expected the session listing");
+        };
+        assert_eq!(focused.as_deref(), Some("alpha"));
+        assert_eq!(sessions.len(), 2);
+
+        handle_request(&shared, &wake, event("beta"));
+        let shared = shared.lock().unwrap();
+        assert!(shared.focus_changed);
+        let background: Vec<bool> = shared.queue.iter().map(|payload| payload.background).collect();
+        assert_eq!(background, [false, false, true]);
+    }
+
+    #[test]
+    fn test_sessions_keep_their_alerts_for_the_aggregate() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let event = |session: &str, name: &str, message: Option<&str>| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = name.to_string();
+            payload.session_id = Some(session.to_string());
+            payload.message = message.map(str::to_string);
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+        handle_request(&shared, &wake, event("alpha", "Notification", Some("Claude needs your permission to use Bash")));
+        handle_request(&shared, &wake, event("beta", "Notification", Some("Claude is waiting for your input")));
+        let aggregate = shared.lock().unwrap().sessions.aggregate().unwrap();
+        assert_eq!(aggregate.active, 2);
+        let alert = aggregate.alert.unwrap();
+        assert_eq!((alert.session_id.as_str(), alert.permission), ("alpha", true));
+
+        handle_request(&shared, &wake, event("alpha", "PostToolUse", None));
+        assert_eq!(shared.lock().unwrap().sessions.aggregate().unwrap().alert, None);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_holds_regular_frames() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let link = Link::new(Box::new(transport::Observe));
+        handle_request(&shared, &wake, IpcRequest::SetMaintenance { enabled: true });
+        let event = IpcRequest::Event {
+            payload: Box::new(DevicePayload::usage(Default::default())),
+            transcript_path: None,
+        };
+        assert!(matches!(handle_request(&shared, &wake, event), IpcResponse::Paused { queued: 2 }));
+
+        // Only the maintenance frame goes out while paused
+        flush_queue(&shared, &link, None).await.unwrap();
+        assert_eq!(shared.lock().unwrap().status.delivered, 1);
+        assert!(shared.lock().unwrap().next_sendable(Instant::now()).is_none());
+
+        handle_request(&shared, &wake, IpcRequest::SetMaintenance { enabled: false });
+        flush_queue(&shared, &link, None).await.unwrap();
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.status.delivered, 3);
+        assert!(shared.queue.is_empty());
+    }
+
+    #[test]
+    fn test_dnd_holds_back_all_but_critical_frames() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let dir = std::env::temp_dir().join(format!("ccgadget-daemon-dnd-{}", std::process::id()));
+        let path = dir.join("dnd.json");
+        shared.lock().unwrap().dnd_path = Some(path.clone());
+        let until = Utc::now() + chrono::Duration::hours(1);
+        handle_request(&shared, &wake, IpcRequest::SetDnd { enabled: true, until: Some(until) });
+        assert_eq!(dnd::load(&path).and_then(|dnd| dnd.until), Some(until));
+
+        let notify = |message: &str| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = "Notification".to_string();
+            payload.message = Some(message.to_string());
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+        handle_request(&shared, &wake, notify("Claude is waiting for your input"));
+        handle_request(&shared, &wake, notify("Claude usage limit reached"));
+        let mut shared = shared.lock().unwrap();
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        // The usage limit still shows, without the alert that would buzz
+        assert_eq!(events, vec![DND_EVENT, "Notification"]);
+        assert_eq!(shared.queue[1].severity, Some(Severity::Error));
+
+        // The icon comes back with every connection until the time is up
+        shared.queue.clear();
+        shared.queue_setup(None);
+        assert!(shared.queue.iter().any(|p| p.event == DND_EVENT));
+        shared.status.dnd = Some(DoNotDisturb { since: Utc::now(), until: Some(Utc::now() - chrono::Duration::seconds(1)) });
+        shared.expire_dnd();
+        assert_eq!(shared.status.dnd, None);
+        let front = shared.queue.front().unwrap();
+        assert_eq!((front.event.as_str(), front.message.as_deref()), (DND_EVENT, Some("off")));
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_quiet_hours_let_urgent_alerts_through() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let notify = |message: &str| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = "Notification".to_string();
+            payload.message = Some(message.to_string());
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+        {
+            let mut shared = shared.lock().unwrap();
+            // Midnight to midnight every day
+            shared.quiet_hours.windows = vec![toml::from_str("").unwrap()];
+            shared.update_quiet_hours(Utc::now());
+            assert!(shared.status.quiet_hours);
+            assert_eq!(shared.queue.front().unwrap().message.as_deref(), Some("on"));
+            shared.queue.clear();
+        }
+        handle_request(&shared, &wake, notify("Claude needs your permission to use Bash"));
+        handle_request(&shared, &wake, notify("Claude usage limit reached"));
+        {
+            let mut shared = shared.lock().unwrap();
+            let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+            assert_eq!(events, vec![PERMISSION_EVENT, ALERT_EVENT, "Notification"]);
+            assert_eq!(shared.queue[0].alert, Some(AlertStyle::default()));
+            assert_eq!(shared.queue[1].alert, Some(AlertsConfig::default().error));
+
+            shared.queue.clear();
+            shared.quiet_hours.urgent_alerts = false;
+        }
+        handle_request(&shared, &wake, notify("Claude usage limit reached"));
+        let mut shared = shared.lock().unwrap();
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(events, vec!["Notification"]);
+
+        shared.quiet_hours.windows.clear();
+        shared.update_quiet_hours(Utc::now());
+        let front = shared.queue.front().unwrap();
+        assert_eq!((front.event.as_str(), front.message.as_deref()), (DND_EVENT, Some("off")));
+    }
+
+    #[tokio::test]
+    async fn test_device_events_run_button_actions() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let press = |button: &str, long| IpcRequest::DeviceEvent {
+            event: DeviceEvent::Button { button: button.to_string(), long },
+        };
+        handle_request(&shared, &wake, press("a", false));
+        assert_eq!(shared.lock().unwrap().next_sendable(Instant::now()).unwrap().event, ACKNOWLEDGE_EVENT);
+
+        // A long press on `b` toggles maintenance mode
+        handle_request(&shared, &wake, press("b", true));
+        assert!(shared.lock().unwrap().status.maintenance);
+        handle_request(&shared, &wake, press("b", true));
+        assert!(!shared.lock().unwrap().status.maintenance);
+
+        let status = DeviceEvent::Status { status: "battery_low".to_string(), detail: Some("12%".to_string()) };
+        handle_request(&shared, &wake, IpcRequest::DeviceEvent { event: status });
+        assert_eq!(shared.lock().unwrap().status.device_status.as_deref(), Some("battery_low"));
+    }
+
+    #[tokio::test]
+    async fn test_observe_only_accepts_and_drains_locally() {
+        let shared = shared_in(LinkState::Observing);
+        let wake = Notify::new();
+        let event = IpcRequest::Event {
+            payload: Box::new(DevicePayload::usage(Default::default())),
+            transcript_path: None,
+        };
+        assert!(matches!(handle_request(&shared, &wake, event), IpcResponse::Accepted));
+
+        flush_queue(&shared, &Link::new(Box::new(transport::Observe)), None).await.unwrap();
+        let shared = shared.lock().unwrap();
+        assert!(shared.queue.is_empty());
+        assert_eq!(shared.status.delivered, 1);
+    }
+
+    #[tokio::test]
+    async fn test_messages_that_expired_while_queued_are_dropped() {
+        let shared = shared_in(LinkState::Ready);
+        let mut stale = DevicePayload::screen_message("build finished", 10, Severity::Warn);
+        stale.timestamp -= chrono::Duration::seconds(11);
+        shared.lock().unwrap().enqueue(stale);
+        shared.lock().unwrap().enqueue(DevicePayload::screen_message("deploy done", 10, Severity::Info));
+
+        flush_queue(&shared, &Link::new(Box::new(transport::Observe)), None).await.unwrap();
+        let shared = shared.lock().unwrap();
+        assert!(shared.queue.is_empty());
+        assert_eq!(shared.status.delivered, 1);
+    }
+
+    #[test]
+    fn test_buttons_mute_alerts_switch_pages_and_block_tools() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        run_button_action(&mut shared.lock().unwrap(), ButtonAction::MuteAlerts, "d", false);
+        let mut payload = DevicePayload::usage(Default::default());
+        payload.event = "Notification".to_string();
+        payload.message = Some("Claude needs your permission to use Bash".to_string());
+        handle_request(&shared, &wake, IpcRequest::Event { payload: Box::new(payload), transcript_path: None });
+        {
+            let mut shared = shared.lock().unwrap();
+            let request = shared.queue.iter().find(|payload| payload.event == PERMISSION_EVENT).unwrap();
+            // The request still shows, without a buzz
+            assert_eq!(request.alert, Some(AlertStyle::default()));
+            run_button_action(&mut shared, ButtonAction::MuteAlerts, "d", false);
+            assert_eq!(shared.status.alerts_muted_until, None);
+
+            run_button_action(&mut shared, ButtonAction::NextPage, "c", false);
+            let front = shared.queue.front().unwrap();
+            assert_eq!((front.event.as_str(), front.message.as_deref()), (PAGE_EVENT, Some("block-countdown")));
+            run_button_action(&mut shared, ButtonAction::BlockNextTool, "c", true);
+        }
+        // The first hook to ask takes the block
+        assert!(matches!(handle_request(&shared, &wake, IpcRequest::TakeToolBlock), IpcResponse::ToolBlock { armed: true }));
+        assert!(matches!(handle_request(&shared, &wake, IpcRequest::TakeToolBlock), IpcResponse::ToolBlock { armed: false }));
+    }
+
+    #[test]
+    fn test_notifications_alert_by_severity() {
+        let shared = shared_in(LinkState::Ready);
+        shared.lock().unwrap().digest = Some(Digest::default());
+        let wake = Notify::new();
+        let notify = |message: &str| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = "Notification".to_string();
+            payload.message = Some(message.to_string());
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+
+        let response = handle_request(&shared, &wake, notify("Claude is waiting for your input"));
+        assert!(matches!(response, IpcResponse::Digested { pending: 1 }));
+        // A usage limit alerts and skips the digest
+        handle_request(&shared, &wake, notify("Claude usage limit reached"));
+        let shared = shared.lock().unwrap();
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(events, vec![ALERT_EVENT, "Notification"]);
+        assert_eq!(shared.queue[0].alert, Some(AlertsConfig::default().error));
+        assert_eq!(shared.queue[1].severity, Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_only_requests_for_the_device_count_as_activity() {
+        let shared = shared_in(LinkState::Idle);
+        let wake = Notify::new();
+        let quiet = Instant::now() - Duration::from_secs(600);
+        shared.lock().unwrap().last_activity = quiet;
+        handle_request(&shared, &wake, IpcRequest::Status);
+        handle_request(&shared, &wake, IpcRequest::ReadSettings { refresh: false });
+        assert_eq!(shared.lock().unwrap().last_activity, quiet);
+
+        let event = IpcRequest::Event {
+            payload: Box::new(DevicePayload::usage(Default::default())),
+            transcript_path: None,
+        };
+        let response = handle_request(&shared, &wake, event);
+        assert!(matches!(response, IpcResponse::Queued { state: LinkState::Idle, queued: 1 }));
+        assert!(shared.lock().unwrap().last_activity > quiet);
+
+        let upkeep = Upkeep::new(&DaemonConfig { keepalive_seconds: 0, idle_timeout_minutes: Some(5), ..Default::default() });
+        assert_eq!((upkeep.keepalive, upkeep.idle_timeout), (None, Some(Duration::from_secs(300))));
+    }
+
+    #[tokio::test]
+    async fn test_busy_sessions_are_throttled_and_merged() {
+        let shared = shared_in(LinkState::Ready);
+        shared.lock().unwrap().throttle = Throttle::new(Duration::from_secs(60));
+        let wake = Notify::new();
+        let link = Link::new(Box::new(transport::Observe));
+        let event = |name: &str, tool: Option<&str>, message: Option<&str>| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = name.to_string();
+            payload.session_id = Some("alpha".to_string());
+            payload.tool = tool.map(str::to_string);
+            payload.message = message.map(str::to_string);
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+
+        handle_request(&shared, &wake, event("PreToolUse", Some("Read"), None));
+        flush_queue(&shared, &link, None).await.unwrap();
+        for tool in ["Read", "Grep", "Edit"] {
+            handle_request(&shared, &wake, event("PostToolUse", Some(tool), None));
+        }
+        // The burst waits as one frame, carrying the latest tool
+        flush_queue(&shared, &link, None).await.unwrap();
+        {
+            let shared = shared.lock().unwrap();
+            assert_eq!(shared.status.delivered, 1);
+            assert_eq!(shared.queue.len(), 1);
+            assert_eq!((shared.queue[0].tool.as_deref(), shared.queue[0].merged), (Some("Edit"), 2));
+            assert!(shared.throttled_until(Instant::now()).is_some());
+        }
+
+        // A permission request overtakes it, led by its alert
+        handle_request(&shared, &wake, event("Notification", None, Some("Claude needs your permission to use Bash")));
+        flush_queue(&shared, &link, None).await.unwrap();
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.status.delivered, 3);
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(events, vec!["PostToolUse"]);
+    }
+
+    #[test]
+    fn test_mirrors_queue_like_the_paired_device() {
+        let shared = shared_in(LinkState::Ready);
+        let mut frames = shared.lock().unwrap().live.subscribe();
+        shared.lock().unwrap().set_maintenance(true);
+        let mut mirror = Mirror::new(0, shelf(), Duration::from_secs(60));
+        mirror.push(DevicePayload::usage(Default::default()));
+        mirror.push(DevicePayload::usage(Default::default()));
+        mirror.push(DevicePayload::link_mode(LinkMode::BatterySaver));
+        // Control frames reach added devices too
+        mirror.push(frames.try_recv().unwrap());
+        let events: Vec<&str> = mirror.queue.iter().map(|payload| payload.event.as_str()).collect();
+        assert_eq!(events, [MAINTENANCE_EVENT, "LinkMode", "Usage"]);
+
+        let now = Instant::now();
+        assert_eq!(mirror.next_sendable(now, true), Some(0));
+        mirror.queue.pop_front();
+        assert_eq!(mirror.next_sendable(now, true), None);
+        // Once a regular frame went out, only urgent ones skip the rate limit
+        mirror.throttle.record(now);
+        assert_eq!(mirror.next_sendable(now, false), Some(0));
+        mirror.queue.pop_front();
+        assert_eq!(mirror.next_sendable(now, false), None);
+    }
+
+    fn shelf() -> PairedDevice {
+        PairedDevice {
+            name: Some("Shelf".to_string()),
+            address: "11:22:33:44:55:66".to_string(),
+            peripheral_id: None,
+            paired_at: Utc::now(),
+            group: Some("office".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_added_devices_get_the_frames_on_their_own_link() {
+        let shared = Arc::new(shared_in(LinkState::Ready));
+        let desk = PairedDevice { name: Some("Desk".to_string()), address: "AA:BB:CC:DD:EE:FF".to_string(), ..shelf() };
+        let (stopping, stopped) = watch::channel(false);
+        // The paired device is not mirrored to, even when also added
+        let mirrors = start_mirrors(&shared, &Config::default(), vec![desk.clone(), shelf()], Some(&desk), true, stopped);
+        shared.lock().unwrap().enqueue(DevicePayload::usage(Default::default()));
+        let check = async {
+            for _ in 0..100 {
+                let status = shared.lock().unwrap().status.mirrors[0].clone();
+                // Time sync and link mode set the device up ahead of the usage frame
+                if status.state == LinkState::Ready && status.queued == 0 && status.delivered >= 3 {
+                    break;
+                }
+                sleep(Duration::from_millis(20)).await;
+            }
+            stopping.send(true).unwrap();
+        };
+        tokio::join!(mirrors, check);
+        let status = &shared.lock().unwrap().status;
+        assert_eq!(status.mirrors.len(), 1);
+        assert_eq!(status.mirrors[0].device, "Shelf (11:22:33:44:55:66)");
+        assert_eq!((status.mirrors[0].state, status.mirrors[0].queued), (LinkState::Ready, 0));
+        assert!(status.mirrors[0].delivered >= 3);
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let shared = shared_in(LinkState::Starting);
+        let mut shared = shared.lock().unwrap();
+        for _ in 0..MAX_QUEUED_EVENTS + 5 {
+            shared.enqueue(DevicePayload::usage(Default::default()));
+        }
+        assert_eq!(shared.queue.len(), MAX_QUEUED_EVENTS);
+    }
+
+    #[tokio::test]
+    async fn test_faulty_link_shows_each_event_once() {
+        let shared = shared_in(LinkState::Ready);
+        for _ in 0..20 {
+            shared.lock().unwrap().enqueue(DevicePayload::usage(Default::default()));
+        }
+        let config = FaultConfig::parse("drop=0.2,dup=0.5,corrupt=0.2,delay=0.2,max-delay-ms=1,seed=3").unwrap();
+        let mut faults = FaultInjector::new(config);
+
+        let demo = transport::Demo::new(DeviceSettings::default());
+        let shown = demo.shown();
+        let link = Link::open(Box::new(demo)).await.unwrap();
+        assert_eq!(link.version, protocol::ACKED_VERSION);
+        flush_queue(&shared, &link, Some(&mut faults)).await.unwrap();
+        // Dropped and corrupted frames are sent again, duplicates are shown only once
+        assert_eq!(*shown.lock().unwrap(), (1..=20).collect::<Vec<u32>>());
+        let shared = shared.lock().unwrap();
+        assert!(shared.queue.is_empty());
+        assert_eq!(shared.status.delivered, 20);
+        assert!(shared.status.retransmits > 0);
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_frames_are_retransmitted() {
+        let shared = shared_in(LinkState::Ready);
+        for _ in 0..4 {
+            shared.lock().unwrap().enqueue(DevicePayload::usage(Default::default()));
+        }
+        let mut faults = FaultInjector::new(FaultConfig::parse("drop=0.5,seed=5").unwrap());
+
+        let link = Link::open(Box::new(transport::Demo::new(DeviceSettings::default()))).await.unwrap();
+        assert_eq!(link.version, protocol::ACKED_VERSION);
+        flush_queue(&shared, &link, Some(&mut faults)).await.unwrap();
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.status.delivered, 4);
+        assert!(shared.status.retransmits > 0);
+        let acked = shared.status.last_acked.as_ref().unwrap();
+        assert_eq!((acked.event.as_str(), acked.seq), ("Usage", 4));
+    }
+
+    #[test]
+    fn test_idle_screen_until_the_next_hook_event() {
+        let shared = shared_in(LinkState::Ready);
+        let mut shared = shared.lock().unwrap();
+        shared.go_idle(Duration::from_secs(300));
+        assert!(shared.status.idle_since.is_some());
+        assert_eq!((shared.queue[0].event.as_str(), shared.queue[0].message.as_deref()), ("Presence", Some("idle")));
+
+        // Waking replaces the idle frame if it has not gone out yet
+        shared.note_event();
+        assert_eq!(shared.status.idle_since, None);
+        assert_eq!(shared.queue.len(), 1);
+        assert_eq!(shared.queue[0].message.as_deref(), Some("active"));
+        shared.note_event();
+        assert_eq!(shared.queue.len(), 1);
+    }
+
+    #[test]
+    fn test_offline_since_marks_the_start_of_an_outage() {
+        let shared = shared_in(LinkState::Ready);
+        went_offline(&shared);
+        let since = shared.lock().unwrap().status.offline_since.unwrap();
+        assert_eq!(shared.lock().unwrap().status.state, LinkState::Disconnected);
+        // Failing again while offline is the same outage
+        went_offline(&shared);
+        assert_eq!(shared.lock().unwrap().status.offline_since, Some(since));
+    }
+
+    #[test]
+    fn test_digest_holds_notifications_until_stop() {
+        let shared = shared_in(LinkState::Ready);
+        shared.lock().unwrap().digest = Some(Digest::default());
+        let wake = Notify::new();
+        let event = |name: &str, message: &str| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = name.to_string();
+            payload.message = Some(message.to_string());
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+
+        let response = handle_request(&shared, &wake, event("Notification", "Claude is waiting for your input"));
+        assert!(matches!(response, IpcResponse::Digested { pending: 1 }));
+        let response = handle_request(&shared, &wake, event("Notification", "Claude needs your permission to use Bash"));
+        assert!(matches!(response, IpcResponse::Accepted));
+        // Sent straight away, led by its permission frame
+        assert_eq!(shared.lock().unwrap().queue.len(), 2);
+
+        handle_request(&shared, &wake, event("Stop", ""));
+        let shared = shared.lock().unwrap();
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(
+            events,
+            vec![PERMISSION_EVENT, "Notification", ACKNOWLEDGE_EVENT, digest::DIGEST_EVENT, "Stop"]
+        );
+    }
+
+    #[test]
+    fn test_permission_requests_wait_until_the_session_moves_on() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let event = |name: &str, session: &str, message: Option<&str>| {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = name.to_string();
+            payload.session_id = Some(session.to_string());
+            payload.message = message.map(str::to_string);
+            IpcRequest::Event { payload: Box::new(payload), transcript_path: None }
+        };
+
+        handle_request(&shared, &wake, event("Notification", "alpha", Some("Claude needs your permission to use Bash")));
+        {
+            let shared = shared.lock().unwrap();
+            let request = &shared.queue[0];
+            assert_eq!((request.event.as_str(), request.tool.as_deref()), (PERMISSION_EVENT, Some("Bash")));
+            assert_eq!(request.alert, Some(shared.alerts.permission));
+            assert!(coalesce::is_urgent(request));
+            let wait = shared.status.awaiting_permission.as_ref().unwrap();
+            assert_eq!((wait.session_id.as_deref(), wait.tool.as_deref()), (Some("alpha"), Some("Bash")));
+        }
+
+        // Another session's events leave it waiting; its own next event answers it
+        handle_request(&shared, &wake, event("PreToolUse", "beta", None));
+        assert!(shared.lock().unwrap().status.awaiting_permission.is_some());
+        handle_request(&shared, &wake, event("PostToolUse", "alpha", None));
+        let shared = shared.lock().unwrap();
+        assert!(shared.status.awaiting_permission.is_none());
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(events, vec![PERMISSION_EVENT, "Notification", "PreToolUse", ACKNOWLEDGE_EVENT, "PostToolUse"]);
+    }
+
+    #[test]
+    fn test_feed_lists_events_after_a_position() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        for name in ["UserPromptSubmit", "PreToolUse", "Stop"] {
+            let mut payload = DevicePayload::usage(Default::default());
+            payload.event = name.to_string();
+            handle_request(&shared, &wake, IpcRequest::Event { payload: Box::new(payload), transcript_path: None });
+        }
+        let IpcResponse::Feed { events } = handle_request(&shared, &wake, IpcRequest::Feed { after: 1 }) else {
+            panic!("expected the feed");
+        };
+        let events: Vec<(u64, &str)> = events.iter().map(|entry| (entry.seq, entry.event.as_str())).collect();
+        assert_eq!(events, vec![(2, "PreToolUse"), (3, "Stop")]);
+    }
+
+    #[test]
+    fn test_budget_threshold_queues_one_alert() {
+        let shared = shared_in(LinkState::Ready);
+        let mut shared = shared.lock().unwrap();
+        let today = Utc::now();
+        let usage = |used: f64| BudgetUsage {
+            period: budget::BudgetPeriod::Daily,
+            metric: budget::BudgetMetric::Cost,
+            period_start: today,
+            used,
+            limit: 20.0,
+            percent: used * 5.0,
+        };
+        assert!(shared.check_budgets(vec![usage(10.0)]).is_empty());
+        assert_eq!(shared.check_budgets(vec![usage(17.0)]).len(), 1);
+        assert!(shared.check_budgets(vec![usage(18.0)]).is_empty());
+        assert_eq!(shared.status.budgets[0].used, 18.0);
+
+        let alerts: Vec<&DevicePayload> = shared.queue.iter().filter(|p| p.event == BUDGET_EVENT).collect();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Some(Severity::Warn));
+        assert!(alerts[0].alert.is_some());
+    }
+
+    #[test]
+    fn test_reloaded_config_updates_held_settings() {
+        let shared = shared_in(LinkState::Ready);
+        let mut shared = shared.lock().unwrap();
+        let old = Config::default();
+        let mut new = Config::default();
+        new.budgets.daily_usd = Some(5.0);
+        new.device.link_mode = LinkMode::BatterySaver;
+        new.daemon.interval_seconds = 10;
+        new.device.transport = crate::device::TransportKind::Wifi;
+        new.desktop.permission_after_seconds = 0;
+        shared.apply_config(&new, &reload::diff(&old, &new));
+
+        assert_eq!(shared.budgets.daily_usd, Some(5.0));
+        assert_eq!(shared.desktop, new.desktop);
+        assert_eq!(shared.status.link_mode, LinkMode::BatterySaver);
+        assert_eq!(shared.queue[0].event, "LinkMode");
+        let reloaded = shared.reloaded.take().unwrap();
+        assert!(reloaded.interval_changed);
+        assert_eq!(reloaded.daemon.interval_seconds, 10);
+
+        // Another daemon setting keeps an --interval given on the command line
+        let mut newer = new.clone();
+        newer.daemon.keepalive_seconds = 5;
+        shared.apply_config(&newer, &reload::diff(&new, &newer));
+        assert!(!shared.reloaded.take().unwrap().interval_changed);
+    }
+
+    #[test]
+    fn test_battery_saver_collapses_usage_and_leads_with_link_mode() {
+        let shared = shared_in(LinkState::Ready);
+        let wake = Notify::new();
+        let response = handle_request(&shared, &wake, IpcRequest::SetLinkMode { mode: LinkMode::BatterySaver });
+        assert!(matches!(response, IpcResponse::Accepted));
+
+        let mut shared = shared.lock().unwrap();
+        shared.enqueue_usage(DevicePayload::usage(Default::default()));
+        shared.enqueue_usage(DevicePayload::usage(Default::default()));
+        shared.queue_link_mode();
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(events, vec!["LinkMode", "Usage"]);
+        assert_eq!(shared.queue[0].message.as_deref(), Some("battery-saver"));
+    }
+
+    #[test]
+    fn test_reconnects_reapply_pushed_display_settings() {
+        let shared = shared_in(LinkState::Ready);
+        let mut shared = shared.lock().unwrap();
+        shared.enqueue(DevicePayload::usage(Default::default()));
+        let mut pushed = DeviceSettings::default();
+        pushed.display.brightness = 30;
+        // A stale queued push is replaced by the latest one
+        shared.queue_control(DevicePayload::settings(DeviceSettings::default()));
+        shared.queue_setup(Some(pushed.clone()));
+        let events: Vec<&str> = shared.queue.iter().map(|p| p.event.as_str()).collect();
+        assert_eq!(events, vec!["LinkMode", SETTINGS_EVENT, "Usage"]);
+        assert_eq!(shared.queue[1].settings, Some(pushed));
+
+        shared.queue.clear();
+        shared.queue_setup(None);
+        assert_eq!(shared.queue.len(), 1);
+    }
+}
@@ -0,0 +1,41 @@
+use std::io;
+#[cfg(not(feature = "native-notifications"))]
+use std::process::{Command, Stdio};
+
+/// Show a desktop notification through the platform's notification service: D-Bus on
+/// Linux and the BSDs, the notification center on macOS, a toast on Windows. Blocks
+/// until the service has taken it.
+#[cfg(feature = "native-notifications")]
+pub fn notify(summary: &str, body: &str) -> io::Result<()> {
+    notify_rust::Notification::new()
+        .appname("CCGadget")
+        .summary(summary)
+        .body(body)
+        .show()
+        .map(drop)
+        .map_err(io::Error::other)
+}
+
+/// Show a desktop notification with the platform's own tool: `notify-send` on Linux
+/// and the BSDs, AppleScript on macOS. Fails where neither is available.
+#[cfg(not(feature = "native-notifications"))]
+pub fn notify(summary: &str, body: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!("display notification {} with title {}", quote(body), quote(summary)));
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", "CCGadget", summary, body]);
+        command
+    } else {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "desktop notifications are not supported on this platform"));
+    };
+    let status = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("notification command failed ({})", status)))
+    }
+}
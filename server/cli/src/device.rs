@@ -0,0 +1,386 @@
+use crate::error::{Classify, ExitCode};
+use crate::paths;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Trade-off between update responsiveness and gadget battery life
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkMode {
+    /// Short connection interval; every event is written immediately
+    #[default]
+    LowLatency,
+    /// Long connection interval with peripheral latency; writes are batched
+    BatterySaver,
+}
+
+/// Device status reported when the gadget has found a firmware update to install; the
+/// detail names the new version
+pub const UPDATE_AVAILABLE: &str = "update_available";
+
+/// Something the device reports on its own, sent as a notification
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DeviceEvent {
+    /// A button was pressed; `long` for a long press
+    Button {
+        button: String,
+        #[serde(default)]
+        long: bool,
+    },
+    /// The device's own state changed, e.g. `battery_low` or `charging`
+    Status {
+        status: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+}
+
+/// How the daemon reaches the device
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransportKind {
+    /// Bluetooth Low Energy
+    #[default]
+    Ble,
+    /// HTTP to the gadget's web server on the local network
+    Wifi,
+    /// USB serial to a tethered gadget
+    Serial,
+    /// Publish to an MQTT broker the gadget subscribes to
+    Mqtt,
+}
+
+impl TransportKind {
+    /// Config and CLI spelling of the transport
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportKind::Ble => "ble",
+            TransportKind::Wifi => "wifi",
+            TransportKind::Serial => "serial",
+            TransportKind::Mqtt => "mqtt",
+        }
+    }
+}
+
+impl std::fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which Bluetooth adapter to use: an index from `scan --adapters`, or part of an
+/// adapter's name (e.g. `hci1`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AdapterChoice {
+    Index(usize),
+    Name(String),
+}
+
+impl AdapterChoice {
+    /// Position of the chosen adapter among adapters described by `names`
+    pub fn position(&self, names: &[String]) -> Option<usize> {
+        match self {
+            AdapterChoice::Index(index) => (*index < names.len()).then_some(*index),
+            AdapterChoice::Name(name) => {
+                let name = name.to_lowercase();
+                names.iter().position(|candidate| candidate.to_lowercase().contains(&name))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for AdapterChoice {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("Adapter must be an index or a name".to_string());
+        }
+        Ok(value.parse().map(AdapterChoice::Index).unwrap_or_else(|_| AdapterChoice::Name(value.to_string())))
+    }
+}
+
+impl std::fmt::Display for AdapterChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdapterChoice::Index(index) => write!(f, "{}", index),
+            AdapterChoice::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// BLE connection parameters the gadget should request for a link mode.
+/// Desktop BLE stacks do not let the central choose these, so they are sent to the
+/// firmware, which requests them from its side of the connection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkPreferences {
+    pub interval_min_ms: f64,
+    pub interval_max_ms: f64,
+    /// Connection events the peripheral may skip
+    pub peripheral_latency: u16,
+    pub supervision_timeout_ms: u32,
+}
+
+impl LinkMode {
+    /// Config and CLI spelling of the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkMode::LowLatency => "low-latency",
+            LinkMode::BatterySaver => "battery-saver",
+        }
+    }
+
+    /// Connection parameters to request from the gadget
+    pub fn preferences(&self) -> LinkPreferences {
+        match self {
+            LinkMode::LowLatency => LinkPreferences {
+                interval_min_ms: 7.5,
+                interval_max_ms: 15.0,
+                peripheral_latency: 0,
+                supervision_timeout_ms: 2_000,
+            },
+            LinkMode::BatterySaver => LinkPreferences {
+                interval_min_ms: 100.0,
+                interval_max_ms: 200.0,
+                peripheral_latency: 4,
+                supervision_timeout_ms: 6_000,
+            },
+        }
+    }
+
+    /// How long the daemon holds queued frames so they go out together
+    pub fn batch_window(&self) -> Duration {
+        match self {
+            LinkMode::LowLatency => Duration::ZERO,
+            LinkMode::BatterySaver => Duration::from_secs(5),
+        }
+    }
+}
+
+impl std::fmt::Display for LinkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The device remembered after a successful `ccgadget pair`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PairedDevice {
+    pub name: Option<String>,
+    pub address: String,
+    /// The OS Bluetooth stack's identifier for the peripheral (a BlueZ device path or a
+    /// CoreBluetooth UUID), used to connect without scanning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peripheral_id: Option<String>,
+    pub paired_at: DateTime<Utc>,
+    /// Group an added device was put in with `pair --add --group`, for `broadcast.group`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// Where a newly paired device is saved
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Registration {
+    /// The paired device, replacing the one before
+    #[default]
+    Primary,
+    /// Added to the devices the daemon mirrors frames to, in `group` if given
+    Added { group: Option<String> },
+}
+
+impl Registration {
+    /// Save `device` where this registration puts it
+    pub fn save(&self, device: &PairedDevice) -> Result<PathBuf, Box<dyn Error>> {
+        match self {
+            Registration::Primary => device.save(),
+            Registration::Added { group } => PairedDevice { group: group.clone(), ..device.clone() }.add(),
+        }
+    }
+}
+
+/// Why the paired device is unavailable
+#[derive(Debug, thiserror::Error)]
+pub enum DeviceError {
+    #[error("No paired device")]
+    NotPaired,
+    #[error("Unreadable paired device registry {}: {source}", path.display())]
+    Registry { path: PathBuf, source: serde_json::Error },
+}
+
+impl Classify for DeviceError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            DeviceError::NotPaired => ExitCode::NotPaired,
+            DeviceError::Registry { .. } => ExitCode::Config,
+        }
+    }
+
+    fn hint(&self) -> Option<&str> {
+        Some(match self {
+            DeviceError::NotPaired => "Run `ccgadget pair` first",
+            DeviceError::Registry { .. } => "Pair again with `ccgadget pair --force`",
+        })
+    }
+}
+
+impl PairedDevice {
+    /// Location of the paired device registry (`device-<profile>.json` under a profile)
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        let name = match crate::config::profile() {
+            Some(profile) => format!("device-{}.json", profile),
+            None => "device.json".to_string(),
+        };
+        Ok(paths::state_dir()?.join(name))
+    }
+
+    /// Load the paired device, if any; a profile without its own pairing uses the default one
+    pub fn load() -> Result<Option<Self>, Box<dyn Error>> {
+        let path = Self::path()?;
+        let path = if path.exists() { path } else { paths::state_dir()?.join("device.json") };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&content).map_err(|source| DeviceError::Registry { path, source })?))
+    }
+
+    /// The paired device, failing with `DeviceError::NotPaired` when there is none
+    pub fn require() -> Result<Self, Box<dyn Error>> {
+        Ok(Self::load()?.ok_or(DeviceError::NotPaired)?)
+    }
+
+    /// Stand-in for a gadget the daemon reaches only over `transports`, when none of
+    /// them is Bluetooth and so there is nothing to pair
+    pub fn unpaired(transports: &[TransportKind]) -> Option<Self> {
+        (!transports.contains(&TransportKind::Ble)).then(|| Self {
+            name: Some("CCGadget".to_string()),
+            address: String::new(),
+            peripheral_id: None,
+            paired_at: Utc::now(),
+            group: None,
+        })
+    }
+
+    /// Persist this device as the paired device
+    pub fn save(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Location of the added devices' registry (`devices-<profile>.json` under a profile)
+    pub fn added_path() -> Result<PathBuf, Box<dyn Error>> {
+        let name = match crate::config::profile() {
+            Some(profile) => format!("devices-{}.json", profile),
+            None => "devices.json".to_string(),
+        };
+        Ok(paths::state_dir()?.join(name))
+    }
+
+    /// Devices added with `pair --add`, in the order they were added
+    pub fn load_added() -> Result<Vec<Self>, Box<dyn Error>> {
+        let path = Self::added_path()?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content).map_err(|source| DeviceError::Registry { path, source })?)
+    }
+
+    fn save_added(devices: &[Self]) -> Result<PathBuf, Box<dyn Error>> {
+        let path = Self::added_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(devices)?)?;
+        Ok(path)
+    }
+
+    /// Add this device to the added devices, replacing an earlier entry for it
+    pub fn add(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let mut devices = Self::load_added()?;
+        devices.retain(|device| device.address != self.address || device.peripheral_id != self.peripheral_id);
+        devices.push(self.clone());
+        Self::save_added(&devices)
+    }
+
+    /// Remove the added device whose name or address is `query`; returns it, or
+    /// `None` when no added device matches
+    pub fn remove_added(query: &str) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut devices = Self::load_added()?;
+        let Some(index) = devices.iter().position(|device| device.matches(query)) else {
+            return Ok(None);
+        };
+        let removed = devices.remove(index);
+        Self::save_added(&devices)?;
+        Ok(Some(removed))
+    }
+
+    /// Whether `query` is this device's name or address, ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        self.address.eq_ignore_ascii_case(query) || self.name.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(query))
+    }
+
+    /// Whether a peripheral the adapter knows, given by identifier and address, is this device
+    pub fn is_peripheral(&self, id: &str, address: &str) -> bool {
+        // CoreBluetooth hides addresses and reports every peripheral as all zeros
+        let known_address = self.address != "00:00:00:00:00:00" && self.address.eq_ignore_ascii_case(address);
+        self.peripheral_id.as_deref() == Some(id) || known_address
+    }
+
+    /// Human-readable label: name and address
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", name, self.address),
+            None => self.address.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_bluetooth_needs_pairing() {
+        assert!(PairedDevice::unpaired(&[TransportKind::Wifi, TransportKind::Serial]).is_some());
+        assert!(PairedDevice::unpaired(&[TransportKind::Mqtt, TransportKind::Ble]).is_none());
+    }
+
+    #[test]
+    fn test_paired_device_matches_known_peripheral() {
+        let device = PairedDevice {
+            name: None,
+            address: "AA:BB:CC:DD:EE:FF".to_string(),
+            peripheral_id: Some("hci0/dev_AA_BB_CC_DD_EE_FF".to_string()),
+            paired_at: Utc::now(),
+            group: None,
+        };
+        assert!(device.is_peripheral("hci0/dev_AA_BB_CC_DD_EE_FF", "AA:BB:CC:DD:EE:FF"));
+        assert!(device.is_peripheral("hci1/dev_AA_BB_CC_DD_EE_FF", "aa:bb:cc:dd:ee:ff"));
+        assert!(!device.is_peripheral("hci0/dev_11_22_33_44_55_66", "11:22:33:44:55:66"));
+
+        assert!(device.matches("aa:bb:cc:dd:ee:ff"));
+        assert!(!device.matches("CCGadget"));
+        assert!(PairedDevice { name: Some("CCGadget-Shelf".to_string()), ..device.clone() }.matches("ccgadget-shelf"));
+
+        let hidden = PairedDevice { address: "00:00:00:00:00:00".to_string(), peripheral_id: Some("5C1E".to_string()), ..device };
+        assert!(hidden.is_peripheral("5C1E", "00:00:00:00:00:00"));
+        assert!(!hidden.is_peripheral("0D0E", "00:00:00:00:00:00"));
+
+        // Registries written before the identifier was stored still load
+        let legacy: PairedDevice =
+            serde_json::from_str(r#"{"name": null, "address": "AA:BB:CC:DD:EE:FF", "paired_at": "2025-07-01T00:00:00Z"}"#).unwrap();
+        assert_eq!(legacy.peripheral_id, None);
+    }
+}
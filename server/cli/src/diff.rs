@@ -0,0 +1,121 @@
+/// Lines of unchanged context shown around each change
+const CONTEXT: usize = 3;
+
+/// One line of an edit script
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// Line-level edit script from `old` to `new` via longest common subsequence
+fn edit_script<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut script = Vec::with_capacity(n + m);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            script.push((Op::Keep, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            script.push((Op::Remove, old[i]));
+            i += 1;
+        } else {
+            script.push((Op::Add, new[j]));
+            j += 1;
+        }
+    }
+    script
+}
+
+/// Unified diff between two texts; empty when they are identical
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let script = edit_script(&old_lines, &new_lines);
+    if script.iter().all(|(op, _)| *op == Op::Keep) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let changes: Vec<usize> = (0..script.len()).filter(|&k| script[k].0 != Op::Keep).collect();
+
+    // Group changes whose context windows touch into hunks
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &k in &changes {
+        let start = k.saturating_sub(CONTEXT);
+        let end = (k + CONTEXT + 1).min(script.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        // Line numbers (1-based) where the hunk begins in each file
+        let old_start = script[..start].iter().filter(|(op, _)| *op != Op::Add).count();
+        let new_start = script[..start].iter().filter(|(op, _)| *op != Op::Remove).count();
+        let old_len = script[start..end].iter().filter(|(op, _)| *op != Op::Add).count();
+        let new_len = script[start..end].iter().filter(|(op, _)| *op != Op::Remove).count();
+        let position = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            position(old_start, old_len),
+            old_len,
+            position(new_start, new_len),
+            new_len
+        ));
+        for (op, line) in &script[start..end] {
+            let marker = match op {
+                Op::Keep => ' ',
+                Op::Remove => '-',
+                Op::Add => '+',
+            };
+            out.push(marker);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts_have_no_diff() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "old", "new"), "");
+    }
+
+    #[test]
+    fn test_unified_hunk() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let diff = unified(old, new, "a/settings.json", "b/settings.json");
+        assert_eq!(
+            diff,
+            "--- a/settings.json\n+++ b/settings.json\n@@ -2,7 +2,8 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n+9\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_from_empty_file() {
+        let diff = unified("", "{}\n", "old", "new");
+        assert!(diff.contains("@@ -0,0 +1,1 @@\n+{}\n"));
+    }
+}
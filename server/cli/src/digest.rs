@@ -0,0 +1,112 @@
+use crate::payload::{self, DevicePayload};
+use chrono::Utc;
+
+/// Event name of the combined payload sent when a digest is flushed
+pub const DIGEST_EVENT: &str = "NotificationDigest";
+
+/// Longest digest message sent to the device
+const MAX_MESSAGE_CHARS: usize = 240;
+
+/// Whether a payload may be held for the digest; permission requests and limit warnings
+/// always go out immediately
+pub fn is_digestible(payload: &DevicePayload) -> bool {
+    payload.event == "Notification"
+        && payload.limit.is_none()
+        && !payload.message.as_deref().is_some_and(payload::is_permission_request)
+}
+
+/// Notifications held back to be sent as a single summary
+#[derive(Debug, Default)]
+pub struct Digest {
+    pending: Vec<DevicePayload>,
+}
+
+impl Digest {
+    /// Hold a notification until the next flush
+    pub fn push(&mut self, payload: DevicePayload) {
+        self.pending.push(payload);
+    }
+
+    /// Number of notifications waiting
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no notifications are waiting
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Combine pending notifications into one payload, emptying the digest
+    pub fn take(&mut self) -> Option<DevicePayload> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let messages: Vec<&str> = pending.iter().filter_map(|p| p.message.as_deref()).collect();
+
+        let mut message = format!("{} notification(s): {}", pending.len(), messages.join(" | "));
+        if message.chars().count() > MAX_MESSAGE_CHARS {
+            message = message.chars().take(MAX_MESSAGE_CHARS - 3).collect::<String>() + "...";
+        }
+
+        let last = pending.last().expect("digest is not empty");
+        Some(DevicePayload {
+            timestamp: Utc::now(),
+            mono_ms: None,
+            event: DIGEST_EVENT.to_string(),
+            session_id: last.session_id.clone(),
+            background: false,
+            tool: None,
+            activity: None,
+            message: Some(message),
+            prompt: None,
+            prompt_truncated: false,
+            widgets: last.widgets.clone(),
+            burn: last.burn.clone(),
+            link: None,
+            settings: None,
+            severity: None,
+            duration_secs: None,
+            alert: None,
+            merged: 0,
+            summary: None,
+            context: last.context,
+            limit: None,
+            sessions: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Notification payload with the given message
+    fn notification(message: &str) -> DevicePayload {
+        let mut payload = DevicePayload::usage(Default::default());
+        payload.event = "Notification".to_string();
+        payload.message = Some(message.to_string());
+        payload
+    }
+
+    #[test]
+    fn test_permission_requests_are_not_digested() {
+        assert!(is_digestible(&notification("Claude is waiting for your input")));
+        assert!(!is_digestible(&notification("Claude needs your permission to use Bash")));
+        assert!(!is_digestible(&DevicePayload::usage(Default::default())));
+    }
+
+    #[test]
+    fn test_take_combines_and_empties() {
+        let mut digest = Digest::default();
+        assert!(digest.take().is_none());
+
+        digest.push(notification("first"));
+        digest.push(notification("second"));
+        let combined = digest.take().unwrap();
+        assert_eq!(combined.event, DIGEST_EVENT);
+        assert_eq!(combined.message.as_deref(), Some("2 notification(s): first | second"));
+        assert_eq!(digest.len(), 0);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::config;
+use crate::fs_util;
+use crate::paths;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Do-not-disturb, set with `ccgadget dnd` or a device button: the gadget shows its DND
+/// icon, never buzzes or flashes, and gets only the frames it cannot do without
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DoNotDisturb {
+    pub since: DateTime<Utc>,
+    /// When it ends on its own; `None` keeps it on until turned off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl DoNotDisturb {
+    /// Do-not-disturb from now until `until`, or until turned off
+    pub fn new(until: Option<DateTime<Utc>>) -> Self {
+        Self { since: Utc::now(), until }
+    }
+
+    /// Whether it is still on at `now`
+    pub fn active_at(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_none_or(|until| until > now)
+    }
+}
+
+impl std::fmt::Display for DoNotDisturb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.until {
+            Some(until) => write!(f, "on until {}", until.with_timezone(&Local).format("%H:%M")),
+            None => f.write_str("on until turned off"),
+        }
+    }
+}
+
+/// Location of the saved state (`dnd-<profile>.json` under a profile), read by the
+/// daemon when it starts
+pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+    let name = match config::profile() {
+        Some(profile) => format!("dnd-{}.json", profile),
+        None => "dnd.json".to_string(),
+    };
+    Ok(paths::state_dir()?.join(name))
+}
+
+/// Do-not-disturb saved at `path`, unless it has ended since
+pub fn load(path: &Path) -> Option<DoNotDisturb> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str::<DoNotDisturb>(&content).ok().filter(|dnd| dnd.active_at(Utc::now()))
+}
+
+/// Save `dnd` at `path`; `None` removes what was saved
+pub fn save(path: &Path, dnd: Option<&DoNotDisturb>) -> Result<(), Box<dyn Error>> {
+    let Some(dnd) = dnd else {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs_util::atomic_write(path, serde_json::to_string_pretty(dnd)?.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_saved_until_turned_off_or_over() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-dnd-{}", std::process::id()));
+        let path = dir.join("dnd.json");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let dnd = DoNotDisturb::new(Some(Utc::now() + Duration::hours(1)));
+        save(&path, Some(&dnd)).unwrap();
+        assert_eq!(load(&path), Some(dnd));
+        save(&path, None).unwrap();
+        assert_eq!(load(&path), None);
+        save(&path, None).unwrap();
+
+        // One that ended while nothing was running is not picked up again
+        let over = DoNotDisturb { since: dnd.since - Duration::hours(2), until: Some(dnd.since - Duration::hours(1)) };
+        save(&path, Some(&over)).unwrap();
+        assert_eq!(load(&path), None);
+        assert!(DoNotDisturb::new(None).active_at(Utc::now() + Duration::weeks(52)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
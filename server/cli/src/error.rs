@@ -0,0 +1,105 @@
+#[cfg(feature = "full")]
+use crate::{bluetooth::BluetoothError, hooks::HookError, lock::LockError};
+use crate::config::ConfigError;
+use crate::device::DeviceError;
+use crate::ipc::IpcError;
+use std::error::Error;
+
+/// Exit status of a failed command. Scripts may rely on these values, which are listed
+/// in the README; anything unclassified exits with `Failure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Failure = 1,
+    /// Bluetooth is not compiled in, has no adapter, or is not permitted
+    NoBluetooth = 2,
+    /// The device did not advertise in time
+    DeviceNotFound = 3,
+    /// No device has been paired yet
+    NotPaired = 4,
+    /// The config file, a config override, or a Claude settings file is invalid
+    Config = 5,
+    /// The command needs the daemon, which is not running
+    DaemonNotRunning = 6,
+    /// The command line could not be parsed (`EX_USAGE`)
+    Usage = 64,
+}
+
+impl ExitCode {
+    /// Exit the process with this status
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// A subsystem error that knows its exit status and what the user can do about it
+pub trait Classify: Error {
+    fn exit_code(&self) -> ExitCode;
+
+    /// Next step for the user, printed after the error itself
+    fn hint(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// The classified error `error` is, or was caused by
+fn classified<'a>(error: &'a (dyn Error + 'static)) -> Option<&'a dyn Classify> {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(e) = error.downcast_ref::<ConfigError>() {
+            return Some(e);
+        }
+        if let Some(e) = error.downcast_ref::<DeviceError>() {
+            return Some(e);
+        }
+        if let Some(e) = error.downcast_ref::<IpcError>() {
+            return Some(e);
+        }
+        #[cfg(feature = "full")]
+        if let Some(e) = error.downcast_ref::<BluetoothError>() {
+            return Some(e);
+        }
+        #[cfg(feature = "full")]
+        if let Some(e) = error.downcast_ref::<HookError>() {
+            return Some(e);
+        }
+        #[cfg(feature = "full")]
+        if let Some(e) = error.downcast_ref::<LockError>() {
+            return Some(e);
+        }
+        current = error.source();
+    }
+    None
+}
+
+/// Exit status for `error`
+pub fn exit_code(error: &(dyn Error + 'static)) -> ExitCode {
+    classified(error).map_or(ExitCode::Failure, Classify::exit_code)
+}
+
+/// Print the hint for `error`, if it has one, and exit with its status. Callers print
+/// the error itself first, with their own context.
+pub fn exit(error: &(dyn Error + 'static)) -> ! {
+    if let Some(hint) = classified(error).and_then(Classify::hint) {
+        esay!("   💡 {}", hint);
+    }
+    exit_code(error).exit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("request failed")]
+    struct Wrapped(#[source] IpcError);
+
+    #[test]
+    fn test_exit_codes_follow_the_source_chain() {
+        let not_paired: Box<dyn Error> = DeviceError::NotPaired.into();
+        assert_eq!(exit_code(&*not_paired), ExitCode::NotPaired);
+        assert_eq!(exit_code(&Wrapped(IpcError::DaemonNotRunning)), ExitCode::DaemonNotRunning);
+
+        let plain: Box<dyn Error> = "something else".into();
+        assert_eq!(exit_code(&*plain), ExitCode::Failure);
+    }
+}
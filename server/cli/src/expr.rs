@@ -0,0 +1,214 @@
+use std::collections::BTreeSet;
+
+/// Parsed arithmetic expression over named metrics
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Metric(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+}
+
+/// Supported binary operators
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+}
+
+impl Expr {
+    /// Parse an expression such as `cost_today / git_commits_today`
+    pub fn parse(input: &str) -> Result<Expr, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_sum()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("Unexpected token {:?} in '{}'", parser.tokens[parser.pos], input));
+        }
+        Ok(expr)
+    }
+
+    /// Names of all metrics referenced by the expression
+    pub fn metrics(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        self.collect_metrics(&mut names);
+        names
+    }
+
+    /// Recursively gather referenced metric names
+    fn collect_metrics(&self, names: &mut BTreeSet<String>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Metric(name) => {
+                names.insert(name.clone());
+            }
+            Expr::Neg(inner) => inner.collect_metrics(names),
+            Expr::Binary(lhs, _, rhs) => {
+                lhs.collect_metrics(names);
+                rhs.collect_metrics(names);
+            }
+        }
+    }
+
+    /// Evaluate the expression, resolving metric names with `lookup`
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Result<f64, String> {
+        match self {
+            Expr::Number(value) => Ok(*value),
+            Expr::Metric(name) => lookup(name).ok_or_else(|| format!("Unknown metric '{}'", name)),
+            Expr::Neg(inner) => Ok(-inner.eval(lookup)?),
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(lookup)?, rhs.eval(lookup)?);
+                match op {
+                    BinOp::Add => Ok(lhs + rhs),
+                    BinOp::Sub => Ok(lhs - rhs),
+                    BinOp::Mul => Ok(lhs * rhs),
+                    BinOp::Div if rhs == 0.0 => Err("Division by zero".to_string()),
+                    BinOp::Div => Ok(lhs / rhs),
+                }
+            }
+        }
+    }
+}
+
+/// Split an expression string into tokens
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text.parse().map_err(|_| format!("Invalid number '{}'", text))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(match c {
+                '+' | '-' | '*' | '/' => Token::Op(c),
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                _ => return Err(format!("Unexpected character '{}' in '{}'", c, input)),
+            });
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over a token list
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Parse `term (('+' | '-') term)*`
+    fn parse_sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_product()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.tokens.get(self.pos).cloned() {
+            self.pos += 1;
+            let rhs = self.parse_product()?;
+            let op = if op == '+' { BinOp::Add } else { BinOp::Sub };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    /// Parse `factor (('*' | '/') factor)*`
+    fn parse_product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_factor()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.tokens.get(self.pos).cloned() {
+            self.pos += 1;
+            let rhs = self.parse_factor()?;
+            let op = if op == '*' { BinOp::Mul } else { BinOp::Div };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    /// Parse a number, metric name, negation, or parenthesized expression
+    fn parse_factor(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("Unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Ident(name) => Ok(Expr::Metric(name)),
+            Token::Op('-') => Ok(Expr::Neg(Box::new(self.parse_factor()?))),
+            Token::LParen => {
+                let expr = self.parse_sum()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            other => Err(format!("Unexpected token {:?}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed metric values for evaluation tests
+    fn lookup(name: &str) -> Option<f64> {
+        match name {
+            "cost_today" => Some(12.0),
+            "git_commits_today" => Some(4.0),
+            "zero" => Some(0.0),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_precedence_and_parentheses() {
+        assert_eq!(Expr::parse("1 + 2 * 3").unwrap().eval(&lookup), Ok(7.0));
+        assert_eq!(Expr::parse("(1 + 2) * 3").unwrap().eval(&lookup), Ok(9.0));
+        assert_eq!(Expr::parse("-2 * -3").unwrap().eval(&lookup), Ok(6.0));
+        assert_eq!(Expr::parse("10 - 4 - 3").unwrap().eval(&lookup), Ok(3.0));
+    }
+
+    #[test]
+    fn test_metric_references() {
+        let expr = Expr::parse("cost_today / git_commits_today").unwrap();
+        assert_eq!(expr.eval(&lookup), Ok(3.0));
+        assert_eq!(
+            expr.metrics().into_iter().collect::<Vec<_>>(),
+            vec!["cost_today".to_string(), "git_commits_today".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("(1 + 2").is_err());
+        assert!(Expr::parse("1 $ 2").is_err());
+        assert!(Expr::parse("cost_today / zero").unwrap().eval(&lookup).is_err());
+        assert!(Expr::parse("missing * 2").unwrap().eval(&lookup).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::rng::XorShift;
+use std::time::Duration;
+
+/// Probabilities for each kind of injected transport fault
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    pub drop: f64,
+    pub delay: f64,
+    pub duplicate: f64,
+    pub corrupt: f64,
+    pub max_delay: Duration,
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            drop: 0.0,
+            delay: 0.0,
+            duplicate: 0.0,
+            corrupt: 0.0,
+            max_delay: Duration::from_millis(500),
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl FaultConfig {
+    /// Parse a spec such as `drop=0.1,delay=0.2,dup=0.05,corrupt=0.05,max-delay-ms=300,seed=7`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", part))?;
+            let probability = || -> Result<f64, String> {
+                let p: f64 = value.parse().map_err(|_| format!("Invalid probability '{}'", value))?;
+                if (0.0..=1.0).contains(&p) {
+                    Ok(p)
+                } else {
+                    Err(format!("Probability for '{}' must be between 0 and 1", key))
+                }
+            };
+            match key {
+                "drop" => config.drop = probability()?,
+                "delay" => config.delay = probability()?,
+                "dup" | "duplicate" => config.duplicate = probability()?,
+                "corrupt" => config.corrupt = probability()?,
+                "max-delay-ms" => {
+                    let ms = value.parse().map_err(|_| format!("Invalid delay '{}'", value))?;
+                    config.max_delay = Duration::from_millis(ms);
+                }
+                "seed" => config.seed = value.parse().map_err(|_| format!("Invalid seed '{}'", value))?,
+                _ => return Err(format!("Unknown fault '{}'", key)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Deterministic fault injector applied to outgoing frames
+#[derive(Debug)]
+pub struct FaultInjector {
+    config: FaultConfig,
+    rng: XorShift,
+}
+
+impl FaultInjector {
+    /// Create an injector seeded from the config
+    pub fn new(config: FaultConfig) -> Self {
+        let rng = XorShift::new(config.seed);
+        Self { config, rng }
+    }
+
+    /// Decide what actually goes on the wire for one frame: zero, one, or two
+    /// copies, each with a delay to wait before writing it
+    pub fn apply(&mut self, frame: &[u8]) -> Vec<(Duration, Vec<u8>)> {
+        if self.rng.next_f64() < self.config.drop {
+            return Vec::new();
+        }
+
+        let mut bytes = frame.to_vec();
+        if !bytes.is_empty() && self.rng.next_f64() < self.config.corrupt {
+            let index = (self.rng.next_f64() * bytes.len() as f64) as usize;
+            bytes[index] ^= 0xFF;
+        }
+
+        let delay = if self.rng.next_f64() < self.config.delay {
+            self.config.max_delay.mul_f64(self.rng.next_f64())
+        } else {
+            Duration::ZERO
+        };
+
+        let mut frames = vec![(delay, bytes.clone())];
+        if self.rng.next_f64() < self.config.duplicate {
+            frames.push((Duration::ZERO, bytes));
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        let config = FaultConfig::parse("drop=0.1, dup=0.2,corrupt=0.3,delay=0.4,max-delay-ms=50,seed=9").unwrap();
+        assert_eq!(config.drop, 0.1);
+        assert_eq!(config.duplicate, 0.2);
+        assert_eq!(config.corrupt, 0.3);
+        assert_eq!(config.delay, 0.4);
+        assert_eq!(config.max_delay, Duration::from_millis(50));
+        assert_eq!(config.seed, 9);
+
+        assert!(FaultConfig::parse("drop=2").is_err());
+        assert!(FaultConfig::parse("explode=0.5").is_err());
+        assert!(FaultConfig::parse("drop").is_err());
+    }
+
+    #[test]
+    fn test_certain_faults() {
+        let frame = b"hello".to_vec();
+
+        let mut dropper = FaultInjector::new(FaultConfig { drop: 1.0, ..Default::default() });
+        assert!(dropper.apply(&frame).is_empty());
+
+        let mut duplicator = FaultInjector::new(FaultConfig { duplicate: 1.0, ..Default::default() });
+        assert_eq!(duplicator.apply(&frame).len(), 2);
+
+        let mut corrupter = FaultInjector::new(FaultConfig { corrupt: 1.0, ..Default::default() });
+        let sent = corrupter.apply(&frame);
+        assert_eq!(sent.len(), 1);
+        assert_ne!(sent[0].1, frame);
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let config = FaultConfig::parse("drop=0.3,dup=0.3,corrupt=0.3,delay=0.3,seed=42").unwrap();
+        let mut a = FaultInjector::new(config.clone());
+        let mut b = FaultInjector::new(config);
+        for i in 0..50u8 {
+            assert_eq!(a.apply(&[i; 8]), b.apply(&[i; 8]));
+        }
+    }
+}
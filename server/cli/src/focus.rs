@@ -0,0 +1,323 @@
+use crate::payload::Severity;
+use crate::transcript::UsageRecord;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Sessions without an event for this long no longer compete for the display
+pub const ACTIVE_MINUTES: i64 = 30;
+
+/// How the daemon picks the session that drives the device's primary screen
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FocusPolicy {
+    /// The session that sent the latest event
+    #[default]
+    MostRecent,
+    /// The session using tokens fastest
+    HighestBurn,
+    /// The session chosen with `session focus`, while it stays active
+    Pinned,
+}
+
+impl FocusPolicy {
+    /// Config and CLI spelling of the policy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FocusPolicy::MostRecent => "most-recent",
+            FocusPolicy::HighestBurn => "highest-burn",
+            FocusPolicy::Pinned => "pinned",
+        }
+    }
+}
+
+impl std::fmt::Display for FocusPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A session that sent events recently
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// Transcript the session's usage is read from
+    pub transcript: Option<String>,
+    pub last_event: DateTime<Utc>,
+    /// Token velocity over the burn window, once measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<f64>,
+    /// Latest notification above `info` the session raised, until its next event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert: Option<SessionAlert>,
+}
+
+/// A notification a session is waiting on someone to see
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionAlert {
+    pub session_id: String,
+    pub severity: Severity,
+    /// Claude is blocked on a permission request, which outranks any severity
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub permission: bool,
+    pub message: String,
+}
+
+/// All active sessions at once, sent with usage frames while several are running
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionsAggregate {
+    pub active: usize,
+    /// Combined token velocity of the sessions measured so far
+    pub tokens_per_minute: f64,
+    /// Most urgent alert of any session: a permission request, then the highest
+    /// severity, then the latest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert: Option<SessionAlert>,
+}
+
+/// Active sessions and which of them has the device's attention
+#[derive(Debug, Clone, Default)]
+pub struct Sessions {
+    /// Active sessions, oldest event first
+    active: Vec<SessionInfo>,
+    pub policy: FocusPolicy,
+    /// Session chosen with `session focus`
+    pub pinned: Option<String>,
+}
+
+impl Sessions {
+    /// No sessions yet, arbitrated by `policy`
+    pub fn new(policy: FocusPolicy) -> Self {
+        Self { policy, ..Default::default() }
+    }
+
+    /// Note an event from a session, keeping its transcript if the event names one
+    pub fn record(&mut self, session_id: &str, transcript: Option<String>, at: DateTime<Utc>) {
+        let mut session = match self.active.iter().position(|session| session.session_id == session_id) {
+            Some(index) => self.active.remove(index),
+            None => SessionInfo {
+                session_id: session_id.to_string(),
+                transcript: None,
+                last_event: at,
+                tokens_per_minute: None,
+                alert: None,
+            },
+        };
+        session.last_event = at;
+        // Whatever it alerted about, the session has moved on
+        session.alert = None;
+        if transcript.is_some() {
+            session.transcript = transcript;
+        }
+        self.active.push(session);
+    }
+
+    /// Forget sessions idle for longer than [`ACTIVE_MINUTES`]
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        self.active.retain(|session| now - session.last_event < Duration::minutes(ACTIVE_MINUTES));
+    }
+
+    /// Record measured token velocities, by session id
+    pub fn set_burn(&mut self, rates: &[(String, f64)]) {
+        for (session_id, rate) in rates {
+            if let Some(session) = self.active.iter_mut().find(|session| &session.session_id == session_id) {
+                session.tokens_per_minute = Some(*rate);
+            }
+        }
+    }
+
+    /// Keep `alert` on its session until the session's next event
+    pub fn set_alert(&mut self, alert: SessionAlert) {
+        if let Some(session) = self.active.iter_mut().find(|session| session.session_id == alert.session_id) {
+            session.alert = Some(alert);
+        }
+    }
+
+    /// Totals across the active sessions; `None` with fewer than two, when the
+    /// focused session is all there is to show
+    pub fn aggregate(&self) -> Option<SessionsAggregate> {
+        if self.active.len() < 2 {
+            return None;
+        }
+        Some(SessionsAggregate {
+            active: self.active.len(),
+            tokens_per_minute: self.active.iter().filter_map(|session| session.tokens_per_minute).sum(),
+            alert: self
+                .active
+                .iter()
+                .filter_map(|session| session.alert.as_ref())
+                .max_by_key(|alert| (alert.permission, alert.severity))
+                .cloned(),
+        })
+    }
+
+    /// Active sessions, oldest event first
+    pub fn active(&self) -> &[SessionInfo] {
+        &self.active
+    }
+
+    /// The active session whose id is or starts with `prefix`
+    pub fn resolve(&self, prefix: &str) -> Result<&SessionInfo, String> {
+        let matches: Vec<&SessionInfo> = self.active.iter().filter(|session| session.session_id.starts_with(prefix)).collect();
+        match matches.as_slice() {
+            [session] => Ok(session),
+            [] => Err(format!("No active session matches '{}'", prefix)),
+            _ => Err(format!("'{}' matches {} sessions; give more of the id", prefix, matches.len())),
+        }
+    }
+
+    /// Pin the focus to the session matching `prefix`; returns its full id
+    pub fn pin(&mut self, prefix: &str) -> Result<String, String> {
+        let session_id = self.resolve(prefix)?.session_id.clone();
+        self.pinned = Some(session_id.clone());
+        self.policy = FocusPolicy::Pinned;
+        Ok(session_id)
+    }
+
+    /// Pin the session after the focused one, wrapping around (for a device button)
+    pub fn pin_next(&mut self) -> Option<String> {
+        let current = self.focused().map(|session| session.session_id.clone());
+        let index = current
+            .and_then(|id| self.active.iter().position(|session| session.session_id == id))
+            .map_or(0, |index| (index + 1) % self.active.len());
+        let session_id = self.active.get(index)?.session_id.clone();
+        self.pinned = Some(session_id.clone());
+        self.policy = FocusPolicy::Pinned;
+        Some(session_id)
+    }
+
+    /// Switch policy; leaving `pinned` drops the pin
+    pub fn set_policy(&mut self, policy: FocusPolicy) {
+        self.policy = policy;
+        if policy != FocusPolicy::Pinned {
+            self.pinned = None;
+        }
+    }
+
+    /// The session that drives the primary screen under the current policy. A pin on
+    /// a session that went idle, and sessions with no measured burn rate, fall back to
+    /// the most recent session.
+    pub fn focused(&self) -> Option<&SessionInfo> {
+        let most_recent = self.active.last();
+        match self.policy {
+            FocusPolicy::MostRecent => most_recent,
+            FocusPolicy::Pinned => self
+                .pinned
+                .as_ref()
+                .and_then(|pinned| self.active.iter().find(|session| &session.session_id == pinned))
+                .or(most_recent),
+            FocusPolicy::HighestBurn => self
+                .active
+                .iter()
+                .rev()
+                .filter(|session| session.tokens_per_minute.is_some_and(|rate| rate > 0.0))
+                .max_by(|a, b| a.tokens_per_minute.partial_cmp(&b.tokens_per_minute).unwrap_or(std::cmp::Ordering::Equal))
+                .or(most_recent),
+        }
+    }
+
+    /// Whether an event from `session_id` belongs on the primary screen
+    pub fn is_focused(&self, session_id: Option<&str>) -> bool {
+        match (session_id, self.focused()) {
+            (Some(id), Some(focused)) => focused.session_id == id,
+            _ => true,
+        }
+    }
+}
+
+/// Tokens per minute in a session's records over the trailing `window_minutes`
+pub fn tokens_per_minute(records: &[UsageRecord], window_minutes: u64, now: DateTime<Utc>) -> f64 {
+    let since = now - Duration::minutes(window_minutes as i64);
+    let tokens: u64 = records.iter().filter(|record| record.timestamp >= since).map(|record| record.usage.total()).sum();
+    tokens as f64 / window_minutes.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Time `minutes` after a fixed instant
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2025-07-20T10:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes)
+    }
+
+    /// Sessions a, b, c with events in that order
+    fn three_sessions(policy: FocusPolicy) -> Sessions {
+        let mut sessions = Sessions::new(policy);
+        sessions.record("aaa-1", Some("/t/a.jsonl".into()), at(0));
+        sessions.record("bbb-2", Some("/t/b.jsonl".into()), at(1));
+        sessions.record("ccc-3", None, at(2));
+        sessions
+    }
+
+    /// Id of the focused session
+    fn focused(sessions: &Sessions) -> &str {
+        &sessions.focused().unwrap().session_id
+    }
+
+    #[test]
+    fn test_most_recent_follows_events() {
+        let mut sessions = three_sessions(FocusPolicy::MostRecent);
+        assert_eq!(focused(&sessions), "ccc-3");
+        sessions.record("aaa-1", None, at(3));
+        assert_eq!(focused(&sessions), "aaa-1");
+        // The transcript is kept from the earlier event
+        assert_eq!(sessions.focused().unwrap().transcript.as_deref(), Some("/t/a.jsonl"));
+        assert!(!sessions.is_focused(Some("bbb-2")));
+        assert!(sessions.is_focused(None));
+    }
+
+    #[test]
+    fn test_highest_burn_falls_back_until_measured() {
+        let mut sessions = three_sessions(FocusPolicy::HighestBurn);
+        assert_eq!(focused(&sessions), "ccc-3");
+        sessions.set_burn(&[("aaa-1".into(), 900.0), ("bbb-2".into(), 1200.0)]);
+        assert_eq!(focused(&sessions), "bbb-2");
+    }
+
+    #[test]
+    fn test_pin_by_prefix_and_expiry() {
+        let mut sessions = three_sessions(FocusPolicy::MostRecent);
+        assert_eq!(sessions.pin("aa"), Ok("aaa-1".to_string()));
+        sessions.record("bbb-2", None, at(5));
+        assert_eq!(focused(&sessions), "aaa-1");
+        assert!(sessions.pin("zzz").is_err());
+
+        // The button cycles through sessions in event order
+        assert_eq!(sessions.pin_next().as_deref(), Some("ccc-3"));
+        assert_eq!(sessions.pin_next().as_deref(), Some("bbb-2"));
+        assert_eq!(sessions.pin_next().as_deref(), Some("aaa-1"));
+
+        // A pinned session that went idle gives way to the most recent one
+        sessions.expire(at(33));
+        assert_eq!(sessions.active().len(), 1);
+        assert_eq!(focused(&sessions), "bbb-2");
+
+        sessions.set_policy(FocusPolicy::MostRecent);
+        assert_eq!(sessions.pinned, None);
+    }
+
+    #[test]
+    fn test_aggregate_sums_burn_and_ranks_alerts() {
+        let mut sessions = three_sessions(FocusPolicy::MostRecent);
+        sessions.set_burn(&[("aaa-1".into(), 900.0), ("bbb-2".into(), 1200.0)]);
+        let alert = |session: &str, severity, permission| SessionAlert {
+            session_id: session.to_string(),
+            severity,
+            permission,
+            message: format!("from {}", session),
+        };
+        sessions.set_alert(alert("aaa-1", Severity::Warn, true));
+        sessions.set_alert(alert("bbb-2", Severity::Error, false));
+        sessions.set_alert(alert("ccc-3", Severity::Warn, false));
+        let aggregate = sessions.aggregate().unwrap();
+        assert_eq!((aggregate.active, aggregate.tokens_per_minute), (3, 2100.0));
+        assert_eq!(aggregate.alert.unwrap().session_id, "aaa-1");
+
+        // Answering the permission request clears it
+        sessions.record("aaa-1", None, at(3));
+        assert_eq!(sessions.aggregate().unwrap().alert.unwrap().session_id, "bbb-2");
+
+        sessions.expire(at(32));
+        assert_eq!(sessions.aggregate(), None);
+    }
+}
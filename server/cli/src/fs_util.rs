@@ -0,0 +1,87 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Replace a file's contents atomically: write a sibling temp file, sync it, then rename
+/// over the target so a crash leaves either the old or the new contents, never a mix
+pub fn atomic_write(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let tmp_path = dir.join(format!(".{}.tmp-{}", file_name, std::process::id()));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Exclusive advisory lock on `path`, held until dropped. Parallel trigger processes
+/// take it around read-modify-write updates so one cannot undo another's.
+pub struct FileLock {
+    file: fs::File,
+}
+
+impl FileLock {
+    /// Wait for the lock, creating the lock file if needed
+    pub fn acquire(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+        file.lock()?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_write_replaces_contents() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+
+        atomic_write(&path, b"first").unwrap();
+        atomic_write(&path, b"second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_lock_serializes_updates() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let counter = dir.join("counter");
+        fs::write(&counter, "0").unwrap();
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (dir, counter) = (dir.clone(), counter.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        let _lock = FileLock::acquire(&dir.join(".lock")).unwrap();
+                        let value: u32 = fs::read_to_string(&counter).unwrap().parse().unwrap();
+                        atomic_write(&counter, (value + 1).to_string().as_bytes()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+        assert_eq!(fs::read_to_string(&counter).unwrap(), "200");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use crate::config::MqttConfig;
+use crate::payload::{DevicePayload, PERMISSION_EVENT};
+use crate::trigger::EVENT_NAMES;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Availability published while the daemon is connected; the broker publishes
+/// [`OFFLINE`] as the last will when it is not
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+/// Retained `online`/`offline` topic for the connection sensor and availability
+pub fn availability_topic(config: &MqttConfig) -> String {
+    format!("{}/availability", config.topic.trim_end_matches('/'))
+}
+
+/// Retained JSON document the sensors read their values from
+pub fn state_topic(config: &MqttConfig) -> String {
+    format!("{}/state", config.topic.trim_end_matches('/'))
+}
+
+/// Topic the gadget publishes its battery level (0-100) to
+pub fn battery_topic(config: &MqttConfig) -> String {
+    format!("{}/battery", config.topic.trim_end_matches('/'))
+}
+
+/// Id of the gadget's Home Assistant device, from the client id or base topic, keeping
+/// only characters discovery topics allow
+fn node_id(config: &MqttConfig) -> String {
+    let name = config.client_id.clone().unwrap_or_else(|| config.topic.clone());
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
+}
+
+/// Discovery messages, as (topic, retained config), that make Home Assistant list the
+/// gadget as a device with sensors for its connection, battery, session and daily
+/// cost, activity, latest event, and whether Claude is waiting for permission
+pub fn discovery(config: &MqttConfig) -> Vec<(String, Value)> {
+    let node = node_id(config);
+    let prefix = config.discovery_prefix.trim_end_matches('/');
+    let device = json!({
+        "identifiers": [format!("ccgadget_{}", node)],
+        "name": "CCGadget",
+        "manufacturer": "CCGadget",
+        "model": "CCGadget",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let state = state_topic(config);
+    let availability = availability_topic(config);
+
+    let entity = |component: &str, object: &str, name: &str, extra: Value| {
+        let mut entity = json!({
+            "name": name,
+            "unique_id": format!("ccgadget_{}_{}", node, object),
+            "object_id": format!("ccgadget_{}", object),
+            "device": device,
+        });
+        if object != "connection" {
+            entity["availability_topic"] = availability.clone().into();
+        }
+        if let (Some(entity), Value::Object(extra)) = (entity.as_object_mut(), extra) {
+            entity.extend(extra);
+        }
+        (format!("{}/{}/ccgadget_{}/{}/config", prefix, component, node, object), entity)
+    };
+    vec![
+        entity("binary_sensor", "connection", "Connection", json!({
+            "state_topic": availability,
+            "payload_on": ONLINE,
+            "payload_off": OFFLINE,
+            "device_class": "connectivity",
+            "entity_category": "diagnostic",
+        })),
+        entity("sensor", "battery", "Battery", json!({
+            "state_topic": battery_topic(config),
+            "device_class": "battery",
+            "unit_of_measurement": "%",
+            "state_class": "measurement",
+            "entity_category": "diagnostic",
+        })),
+        entity("sensor", "cost_session", "Session cost", json!({
+            "state_topic": state,
+            "value_template": "{{ value_json.cost_session }}",
+            "device_class": "monetary",
+            "unit_of_measurement": "USD",
+        })),
+        entity("sensor", "cost_today", "Cost today", json!({
+            "state_topic": state,
+            "value_template": "{{ value_json.cost_today }}",
+            "device_class": "monetary",
+            "unit_of_measurement": "USD",
+        })),
+        entity("sensor", "activity", "Activity", json!({
+            "state_topic": state,
+            "value_template": "{{ value_json.activity }}",
+            "icon": "mdi:robot",
+        })),
+        entity("sensor", "event", "Last event", json!({
+            "state_topic": state,
+            "value_template": "{{ value_json.event }}",
+            "icon": "mdi:message-text",
+        })),
+        entity("binary_sensor", "waiting_for_permission", "Waiting for permission", json!({
+            "state_topic": state,
+            "value_template": "{{ 'ON' if value_json.waiting_for_permission else 'OFF' }}",
+            "icon": "mdi:hand-back-right",
+        })),
+    ]
+}
+
+/// What the Home Assistant sensors show, folded from the frames sent to the gadget
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SensorState {
+    pub cost_session: Option<f64>,
+    pub cost_today: Option<f64>,
+    /// Icon name of what Claude is doing, `idle` between turns
+    pub activity: String,
+    pub event: Option<String>,
+    pub session_id: Option<String>,
+    pub waiting_for_permission: bool,
+}
+
+impl Default for SensorState {
+    fn default() -> Self {
+        Self {
+            cost_session: None,
+            cost_today: None,
+            activity: "idle".to_string(),
+            event: None,
+            session_id: None,
+            waiting_for_permission: false,
+        }
+    }
+}
+
+impl SensorState {
+    /// Update from a frame; returns whether anything changed. Usage frames carry the
+    /// costs when `cost_session` and `cost_today` are among the display widgets.
+    pub fn apply(&mut self, frame: &[u8]) -> bool {
+        let Ok(payload) = serde_json::from_slice::<DevicePayload>(frame) else {
+            return false;
+        };
+        let before = self.clone();
+        match payload.event.as_str() {
+            "Usage" => {
+                self.cost_session = payload.widgets.get("cost_session").copied().or(self.cost_session);
+                self.cost_today = payload.widgets.get("cost_today").copied().or(self.cost_today);
+            }
+            PERMISSION_EVENT => {
+                self.waiting_for_permission = true;
+                self.session_id = payload.session_id;
+            }
+            event if EVENT_NAMES.contains(&event) => {
+                // Claude moved on, so any permission request was answered
+                if event != "Notification" {
+                    self.waiting_for_permission = false;
+                }
+                self.activity = match (event, payload.activity) {
+                    ("Stop" | "SessionEnd", _) => "idle".to_string(),
+                    (_, Some(activity)) => activity.to_string(),
+                    (_, None) => self.activity.clone(),
+                };
+                self.event = Some(payload.event);
+                self.session_id = payload.session_id.or(self.session_id.take());
+            }
+            _ => {}
+        }
+        *self != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_discovery_topics_and_device() {
+        let config = MqttConfig { topic: "ccgadget/desk".to_string(), ..Default::default() };
+        let messages = discovery(&config);
+        let (topic, connection) = &messages[0];
+        assert_eq!(topic, "homeassistant/binary_sensor/ccgadget_ccgadget_desk/connection/config");
+        assert_eq!(connection["state_topic"], "ccgadget/desk/availability");
+        assert!(connection.get("availability_topic").is_none());
+
+        let (_, cost) = messages.iter().find(|(topic, _)| topic.contains("/cost_session/")).unwrap();
+        assert_eq!(cost["state_topic"], "ccgadget/desk/state");
+        assert_eq!(cost["availability_topic"], "ccgadget/desk/availability");
+        assert_eq!(cost["device"]["identifiers"][0], "ccgadget_ccgadget_desk");
+    }
+
+    #[test]
+    fn test_state_follows_frames() {
+        let mut state = SensorState::default();
+        let frame = |payload: DevicePayload| serde_json::to_vec(&payload).unwrap();
+
+        let usage = DevicePayload::usage(BTreeMap::from([("cost_session".to_string(), 1.25)]));
+        assert!(state.apply(&frame(usage.clone())));
+        assert!(!state.apply(&frame(usage)));
+        assert_eq!(state.cost_session, Some(1.25));
+
+        let mut notification = DevicePayload::usage(BTreeMap::new());
+        notification.event = "Notification".to_string();
+        notification.message = Some("Claude needs your permission to use Bash".to_string());
+        let request = DevicePayload::permission_request(&notification, Default::default());
+        assert!(state.apply(&frame(request)));
+        assert!(state.waiting_for_permission);
+
+        let mut tool = DevicePayload::usage(BTreeMap::new());
+        tool.event = "PreToolUse".to_string();
+        tool.activity = Some(crate::config::Activity::Running);
+        state.apply(&frame(tool));
+        assert!(!state.waiting_for_permission);
+        assert_eq!(state.activity, "running");
+
+        let mut stop = DevicePayload::usage(BTreeMap::new());
+        stop.event = "Stop".to_string();
+        state.apply(&frame(stop));
+        assert_eq!((state.activity.as_str(), state.event.as_deref()), ("idle", Some("Stop")));
+    }
+}
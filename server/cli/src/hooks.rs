@@ -0,0 +1,845 @@
+use crate::error::{Classify, ExitCode};
+use crate::{diff, fs_util, paths};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HookScope {
+    /// Install hooks at user level (~/.claude/settings.json)
+    User,
+    /// Install hooks in the shared project settings (.claude/settings.json)
+    Project,
+    /// Install hooks at project local level (.claude/settings.local.json)
+    Local,
+}
+
+impl HookScope {
+    /// Every scope, highest precedence first (local overrides project overrides user)
+    pub const BY_PRECEDENCE: [HookScope; 3] = [HookScope::Local, HookScope::Project, HookScope::User];
+}
+
+/// Problems with a Claude settings file or its backups
+#[derive(Debug, thiserror::Error)]
+pub enum HookError {
+    #[error("{} is not valid JSON: {source}", path.display())]
+    InvalidSettings { path: PathBuf, source: serde_json::Error },
+    #[error("No backups found in {}", .0.display())]
+    NoBackups(PathBuf),
+}
+
+impl Classify for HookError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            HookError::InvalidSettings { .. } => ExitCode::Config,
+            HookError::NoBackups(_) => ExitCode::Failure,
+        }
+    }
+
+    fn hint(&self) -> Option<&str> {
+        match self {
+            HookError::InvalidSettings { .. } => {
+                Some("Fix the file by hand, or roll back with `ccgadget setup-hook --restore-backup`")
+            }
+            HookError::NoBackups(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum HookSetupResult {
+    /// Hook was successfully added
+    Added,
+    /// Hook was skipped (user declined or other reason)
+    Skipped,
+    /// Hook already exists and no action was needed
+    AlreadyExists,
+}
+
+/// Setup Claude Code hooks by detecting settings files and configuring hooks
+pub fn setup_claude_hooks(
+    scope: &HookScope,
+    force: bool,
+    auto_approve: bool,
+    events: &[String],
+    matchers: &[String],
+    dry_run: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    // Find Claude settings file based on scope (a dry run must not create it)
+    let settings_path = if dry_run { claude_settings_path(scope)? } else { find_claude_settings_file(scope)? };
+    say!("   📁 Found Claude settings: {}", settings_path.display());
+    
+    // Read existing settings
+    let mut settings = read_claude_settings(&settings_path)?;
+    
+    // Get hooks to configure (setup all hooks unless --events narrows it)
+    let hooks_config = selected_hooks_config(events);
+    
+    // Setup hooks in settings
+    let mut updated_hooks = 0;
+    let mut skipped_hooks = Vec::new();
+    
+    for (event_name, hook_command) in hooks_config {
+        let matcher = matcher_for(matchers, event_name);
+        match setup_hook_for_event(&mut settings, event_name, hook_command, matcher, force, auto_approve)? {
+            HookSetupResult::Added => {
+                updated_hooks += 1;
+            }
+            HookSetupResult::Skipped => {
+                skipped_hooks.push(event_name);
+            }
+            HookSetupResult::AlreadyExists => {
+                // Hook already exists, no action needed
+            }
+        }
+    }
+    
+    if !skipped_hooks.is_empty() {
+        say!("   ℹ️ Skipped hooks for events: {}", skipped_hooks.join(", "));
+    }
+    
+    if dry_run {
+        preview_claude_settings(&settings_path, &serde_json::to_string_pretty(&settings)?);
+        return Ok(format!("Dry run: {} hook(s) would be configured; no files changed", updated_hooks));
+    }
+
+    // Claude merges hooks from every scope, so entries elsewhere still run alongside these
+    for (other, path) in other_scopes_with_hooks(scope) {
+        say!("   ℹ️ ccgadget hooks also exist in {:?} settings ({}); Claude runs hooks from every scope", other, path.display());
+    }
+
+    // Write settings back to file
+    write_claude_settings(&settings_path, &settings)?;
+    
+    Ok(format!("Successfully configured {} hook(s) in {}", updated_hooks, settings_path.display()))
+}
+
+/// Path of the Claude settings file for a scope, without creating it
+pub fn claude_settings_path(scope: &HookScope) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    match scope {
+        HookScope::Local => Ok(PathBuf::from(".claude").join("settings.local.json")),
+        HookScope::Project => Ok(PathBuf::from(".claude").join("settings.json")),
+        HookScope::User => Ok(paths::home_dir()?.join(".claude").join("settings.json")),
+    }
+}
+
+/// Find the appropriate Claude settings file based on scope, creating it if missing
+fn find_claude_settings_file(scope: &HookScope) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let settings_path = claude_settings_path(scope)?;
+
+    // Create the .claude directory if it doesn't exist
+    if let Some(claude_dir) = settings_path.parent() {
+        if !claude_dir.exists() {
+            fs::create_dir_all(claude_dir)?;
+        }
+    }
+
+    // Create empty settings file if it doesn't exist
+    if !settings_path.exists() {
+        let empty_settings = serde_json::json!({});
+        fs::write(&settings_path, serde_json::to_string_pretty(&empty_settings)?)?;
+        say!("   📝 Created new {:?} settings file: {}", scope, settings_path.display());
+    }
+
+    Ok(settings_path)
+}
+
+/// Scopes other than `scope` whose settings files contain ccgadget hooks, highest precedence first
+pub fn other_scopes_with_hooks(scope: &HookScope) -> Vec<(HookScope, PathBuf)> {
+    HookScope::BY_PRECEDENCE
+        .into_iter()
+        .filter(|other| other != scope)
+        .filter_map(|other| {
+            let path = claude_settings_path(&other).ok()?;
+            let mut settings = read_claude_settings(&path).ok()?;
+            (!remove_ccgadget_hooks(&mut settings, &[]).is_empty()).then_some((other, path))
+        })
+        .collect()
+}
+
+/// Read Claude settings from file
+pub fn read_claude_settings(path: &PathBuf) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    if path.exists() {
+        let content = fs::read_to_string(path)?;
+        if content.trim().is_empty() {
+            Ok(serde_json::json!({}))
+        } else {
+            Ok(serde_json::from_str(&content).map_err(|source| HookError::InvalidSettings { path: path.clone(), source })?)
+        }
+    } else {
+        Ok(serde_json::json!({}))
+    }
+}
+
+/// Number of backups kept per settings file
+const MAX_SETTINGS_BACKUPS: usize = 20;
+
+/// Directory holding settings backups (`backups/` beside the settings file)
+pub fn settings_backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join("backups")
+}
+
+/// Existing backups of a settings file, oldest first
+pub fn settings_backups(path: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+    let mut backups: Vec<PathBuf> = fs::read_dir(settings_backup_dir(path))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|backup| {
+                    let name = backup.file_name().unwrap_or_default().to_string_lossy();
+                    name.starts_with(&prefix) && name.ends_with(".bak")
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    // Timestamped names sort chronologically
+    backups.sort();
+    backups
+}
+
+/// Copy the current settings file into the backup directory, pruning old backups
+fn backup_claude_settings(path: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let backup_dir = settings_backup_dir(path);
+    fs::create_dir_all(&backup_dir)?;
+    let backup = backup_dir.join(format!(
+        "{}.{}.bak",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        chrono::Local::now().format("%Y%m%d-%H%M%S%3f")
+    ));
+    fs::copy(path, &backup)?;
+
+    let backups = settings_backups(path);
+    for old in backups.iter().take(backups.len().saturating_sub(MAX_SETTINGS_BACKUPS)) {
+        let _ = fs::remove_file(old);
+    }
+    Ok(Some(backup))
+}
+
+/// Write raw settings contents atomically after backing up the current file
+pub fn replace_claude_settings(path: &Path, contents: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if fs::read_to_string(path).is_ok_and(|current| current == contents) {
+        return Ok(());
+    }
+    if let Some(backup) = backup_claude_settings(path)? {
+        say!("   💾 Backed up settings to {}", backup.display());
+    }
+    fs_util::atomic_write(path, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Print the unified diff a settings write would make, without writing anything
+pub fn preview_claude_settings(path: &Path, contents: &str) {
+    let current = fs::read_to_string(path).unwrap_or_default();
+    let label = path.display().to_string();
+    let diff = diff::unified(&current, contents, &label, &format!("{} (proposed)", label));
+    if diff.is_empty() {
+        say!("   ℹ️ No changes to {}", label);
+    } else {
+        say!("   🔎 Proposed changes:");
+        print!("{}", diff);
+    }
+}
+
+/// Write Claude settings to file
+pub fn write_claude_settings(path: &Path, settings: &serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+    let formatted = serde_json::to_string_pretty(settings)?;
+    replace_claude_settings(path, &formatted)
+}
+
+/// Claude Code hook events ccgadget installs a trigger for
+pub const HOOK_EVENTS: [&str; 6] = ["UserPromptSubmit", "PreToolUse", "PostToolUse", "Notification", "Stop", "PreCompact"];
+
+/// Get all hook configurations for CCGadget
+fn get_all_hooks_config() -> Vec<(&'static str, &'static str)> {
+    HOOK_EVENTS.iter().map(|event| (*event, "ccgadget trigger")).collect()
+}
+
+/// Events whose hooks Claude Code filters by tool name
+const TOOL_EVENTS: [&str; 2] = ["PreToolUse", "PostToolUse"];
+
+/// Validate a `--matcher` value: `PATTERN`, or `EVENT=PATTERN` for a known event
+pub fn parse_matcher(value: &str) -> Result<String, String> {
+    if let Some((event, _)) = value.split_once('=') {
+        if !HOOK_EVENTS.contains(&event) {
+            return Err(format!("Unknown event '{}' (expected one of {})", event, HOOK_EVENTS.join(", ")));
+        }
+    }
+    Ok(value.to_string())
+}
+
+/// Matcher to write for an event: a per-event override, else the bare pattern for tool
+/// events, else match everything
+pub fn matcher_for<'a>(matchers: &'a [String], event_name: &str) -> &'a str {
+    let per_event = matchers
+        .iter()
+        .filter_map(|m| m.split_once('='))
+        .find(|(event, _)| *event == event_name)
+        .map(|(_, pattern)| pattern);
+    let default = || {
+        matchers
+            .iter()
+            .rev()
+            .find(|m| !m.contains('='))
+            .filter(|_| TOOL_EVENTS.contains(&event_name))
+            .map(String::as_str)
+    };
+    per_event.or_else(default).unwrap_or("")
+}
+
+/// Hook configurations limited to the `--events` selection (all when empty)
+pub fn selected_hooks_config(events: &[String]) -> Vec<(&'static str, &'static str)> {
+    get_all_hooks_config()
+        .into_iter()
+        .filter(|(event_name, _)| events.is_empty() || events.iter().any(|e| e == event_name))
+        .collect()
+}
+
+/// Setup a hook for a specific event in the settings
+fn setup_hook_for_event(
+    settings: &mut serde_json::Value,
+    event_name: &str,
+    hook_command: &str,
+    matcher: &str,
+    force: bool,
+    auto_approve: bool,
+) -> Result<HookSetupResult, Box<dyn std::error::Error>> {
+    // Ensure hooks object exists
+    if settings.get("hooks").is_none() {
+        settings["hooks"] = serde_json::json!({});
+    }
+    
+    let hooks = settings["hooks"].as_object_mut()
+        .ok_or("Failed to get hooks object")?;
+    
+    // Check if event already has hooks configured
+    if hooks.contains_key(event_name) {
+        // First check the current state (need immutable borrow)
+        let event_hooks_value = hooks.get(event_name).unwrap();
+        let exact_hook_exists = exact_hook_exists(event_hooks_value, hook_command, matcher);
+        let any_ccgadget_exists = any_ccgadget_hook_exists(event_hooks_value, hook_command);
+        let has_other_hooks = event_hooks_value.as_array()
+            .map(|arr| !arr.is_empty())
+            .unwrap_or(false);
+        
+        if exact_hook_exists {
+            // Perfect match - hook is already correctly configured
+            say!("   ✅ Hook for {} already correctly configured", event_name);
+            return Ok(HookSetupResult::AlreadyExists);
+        }
+        
+        // Determine what action to take
+        let action = if any_ccgadget_exists {
+            // ccgadget hook exists but with wrong configuration - ask user
+            if force {
+                say!("   🔧 Forcing update of mismatched hook for {}", event_name);
+                HookAction::Replace
+            } else if auto_approve {
+                say!("   ✅ Auto-approving hook update for {} (--yes flag)", event_name);
+                HookAction::Replace
+            } else {
+                ask_user_fix_hook_action(event_name, event_hooks_value, hook_command, matcher)?
+            }
+        } else if has_other_hooks {
+            // Check if there are any non-ccgadget hooks
+            let has_non_ccgadget_hooks = event_hooks_value.as_array()
+                .map(|arr| arr.iter().any(|hook_group| !hook_group_contains_command(hook_group, "ccgadget")))
+                .unwrap_or(false);
+                
+            if has_non_ccgadget_hooks {
+                // Other non-ccgadget hooks exist - ask user what to do
+                if auto_approve {
+                    say!("   ✅ Auto-approving hook addition for {} (--yes flag)", event_name);
+                    HookAction::Append
+                } else {
+                    ask_user_hook_action(event_name, event_hooks_value)?
+                }
+            } else {
+                // No actual hooks, just add
+                HookAction::Append
+            }
+        } else {
+            // No hooks at all, just add
+            HookAction::Append
+        };
+        
+        // Handle user choice
+        match action {
+            HookAction::Skip => {
+                say!("   ⏭️ Skipping hook for {} (user chose skip)", event_name);
+                return Ok(HookSetupResult::Skipped);
+            }
+            HookAction::Replace => {
+                // Replace all existing hooks with just our ccgadget hook
+                let hook_config = serde_json::json!([
+                    {
+                        "matcher": matcher,
+                        "hooks": [
+                            {
+                                "type": "command",
+                                "command": hook_command
+                            }
+                        ]
+                    }
+                ]);
+                hooks.insert(event_name.to_string(), hook_config);
+                say!("   🔄 Replaced all hooks for {} with ccgadget hook", event_name);
+            }
+            HookAction::Append => {
+                // Add ccgadget hook alongside existing hooks
+                let event_hooks_array = hooks.get_mut(event_name).unwrap()
+                    .as_array_mut()
+                    .ok_or("Event hooks must be an array")?;
+                
+                // Remove existing ccgadget hooks first (if any) to avoid duplicates
+                if any_ccgadget_exists {
+                    event_hooks_array.retain(|hook_group| {
+                        !hook_group_contains_command(hook_group, "ccgadget")
+                    });
+                }
+                
+                // Add our hook to the existing array
+                let ccgadget_hook = serde_json::json!({
+                    "matcher": matcher,
+                    "hooks": [
+                        {
+                            "type": "command",
+                            "command": hook_command
+                        }
+                    ]
+                });
+                
+                event_hooks_array.push(ccgadget_hook);
+                say!("   ➕ Added ccgadget hook alongside existing hooks for {}", event_name);
+            }
+        }
+    } else {
+        // No existing hooks for this event - create new array
+        let hook_config = serde_json::json!([
+            {
+                "matcher": matcher,
+                "hooks": [
+                    {
+                        "type": "command",
+                        "command": hook_command
+                    }
+                ]
+            }
+        ]);
+        
+        hooks.insert(event_name.to_string(), hook_config);
+    }
+    
+    say!("   ✅ Configured hook for {}", event_name);
+    Ok(HookSetupResult::Added)
+}
+
+/// Check if the exact expected hook configuration already exists
+fn exact_hook_exists(event_hooks: &serde_json::Value, target_command: &str, expected_matcher: &str) -> bool {
+    if let Some(hooks_array) = event_hooks.as_array() {
+        for hook_group in hooks_array {
+            // Check if this hook group matches our expected configuration exactly
+            if is_exact_ccgadget_hook(hook_group, target_command, expected_matcher) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Check if a hook group is exactly the ccgadget hook we expect
+fn is_exact_ccgadget_hook(hook_group: &serde_json::Value, target_command: &str, expected_matcher: &str) -> bool {
+    // Expected: {"matcher": "<matcher>", "hooks": [{"type": "command", "command": "ccgadget trigger"}]}
+
+    // Check matcher
+    let matcher = hook_group.get("matcher")
+        .and_then(|m| m.as_str())
+        .unwrap_or("");
+    
+    if matcher != expected_matcher {
+        return false;
+    }
+    
+    // Check hooks array
+    if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
+        if hooks.len() != 1 {
+            return false;
+        }
+        
+        let hook = &hooks[0];
+        let hook_type = hook.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let hook_command = hook.get("command").and_then(|c| c.as_str()).unwrap_or("");
+        
+        return hook_type == "command" && hook_command == target_command;
+    }
+    
+    false
+}
+
+/// Check if any ccgadget-related hook exists (even if not exact match)
+fn any_ccgadget_hook_exists(event_hooks: &serde_json::Value, target_command: &str) -> bool {
+    if let Some(hooks_array) = event_hooks.as_array() {
+        for hook_group in hooks_array {
+            if hook_group_contains_command(hook_group, target_command) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Check if a specific hook group contains a command
+fn hook_group_contains_command(hook_group: &serde_json::Value, target_command: &str) -> bool {
+    if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
+        for hook in hooks {
+            if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
+                if command.contains(target_command) {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Whether a hook command invokes ccgadget (bare or by path)
+fn is_ccgadget_command(command: &str) -> bool {
+    command
+        .split_whitespace()
+        .next()
+        .and_then(|program| std::path::Path::new(program).file_stem())
+        .is_some_and(|stem| stem == "ccgadget")
+}
+
+/// Installation state of the ccgadget hook for one event in one settings file
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HookStatus {
+    /// Exactly the hook `setup-hook` installs
+    Installed,
+    /// A ccgadget command is present but not in the expected form
+    Misconfigured,
+    Missing,
+}
+
+/// Inspect an event's hooks in a settings document
+pub fn hook_event_status(settings: &serde_json::Value, event_name: &str, hook_command: &str, matcher: &str) -> HookStatus {
+    let Some(event_hooks) = settings.get("hooks").and_then(|hooks| hooks.get(event_name)) else {
+        return HookStatus::Missing;
+    };
+    let has_ccgadget_command = event_hooks.as_array().is_some_and(|groups| {
+        groups.iter().any(|group| {
+            group.get("hooks").and_then(|h| h.as_array()).is_some_and(|entries| {
+                entries.iter().any(|hook| {
+                    hook.get("command").and_then(|c| c.as_str()).is_some_and(is_ccgadget_command)
+                })
+            })
+        })
+    });
+
+    if exact_hook_exists(event_hooks, hook_command, matcher) {
+        HookStatus::Installed
+    } else if has_ccgadget_command {
+        HookStatus::Misconfigured
+    } else {
+        HookStatus::Missing
+    }
+}
+
+/// Remove ccgadget hook entries from the selected events (every event when empty),
+/// pruning groups and events left empty. Returns the number of entries removed per event.
+pub fn remove_ccgadget_hooks(settings: &mut serde_json::Value, events: &[String]) -> Vec<(String, usize)> {
+    let mut removed = Vec::new();
+    let Some(hooks) = settings.get_mut("hooks").and_then(|h| h.as_object_mut()) else {
+        return removed;
+    };
+
+    for (event_name, event_hooks) in hooks.iter_mut() {
+        if !events.is_empty() && !events.contains(event_name) {
+            continue;
+        }
+        let Some(groups) = event_hooks.as_array_mut() else {
+            continue;
+        };
+        let mut count = 0;
+        for group in groups.iter_mut() {
+            if let Some(entries) = group.get_mut("hooks").and_then(|h| h.as_array_mut()) {
+                let before = entries.len();
+                entries.retain(|hook| {
+                    !hook.get("command").and_then(|c| c.as_str()).is_some_and(is_ccgadget_command)
+                });
+                count += before - entries.len();
+            }
+        }
+        if count > 0 {
+            // Drop groups that only held our hooks
+            groups.retain(|group| {
+                group.get("hooks").and_then(|h| h.as_array()).is_none_or(|entries| !entries.is_empty())
+            });
+            removed.push((event_name.clone(), count));
+        }
+    }
+
+    hooks.retain(|_, event_hooks| event_hooks.as_array().is_none_or(|groups| !groups.is_empty()));
+    if hooks.is_empty() {
+        if let Some(settings) = settings.as_object_mut() {
+            settings.remove("hooks");
+        }
+    }
+    removed
+}
+
+/// User choice for handling hook conflicts
+#[derive(Debug, PartialEq)]
+enum HookAction {
+    Replace,  // Replace existing hooks with ccgadget hook
+    Append,   // Add ccgadget hook alongside existing hooks
+    Skip,     // Skip this event, leave existing hooks unchanged
+}
+
+/// Ask user what to do with existing hooks for a specific event
+fn ask_user_hook_action(event_name: &str, existing_hooks: &serde_json::Value) -> Result<HookAction, Box<dyn std::error::Error>> {
+    say!("   ⚠️ Event '{}' already has existing hooks configured:", event_name);
+    
+    // Display existing hooks in a user-friendly way
+    if let Some(hooks_array) = existing_hooks.as_array() {
+        for (i, hook_group) in hooks_array.iter().enumerate() {
+            if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
+                for (j, hook) in hooks.iter().enumerate() {
+                    if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
+                        let matcher = hook_group.get("matcher")
+                            .and_then(|m| m.as_str())
+                            .unwrap_or("");
+                        let matcher_display = if matcher.is_empty() { "all" } else { matcher };
+                        say!("     {}.{}: {} (matcher: {})", i + 1, j + 1, command, matcher_display);
+                    }
+                }
+            }
+        }
+    }
+    
+    say!("   How would you like to handle 'ccgadget trigger' for {}?", event_name);
+    say!("     [r] Replace - Remove existing hooks and add ccgadget hook");
+    say!("     [a] Append  - Add ccgadget hook alongside existing hooks");
+    say!("     [s] Skip    - Keep existing hooks unchanged");
+    say_inline!("   Choose [r/a/s]: ");
+    std::io::stdout().flush()?;
+    
+    // Read user input
+    loop {
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        let input = input.trim().to_lowercase();
+        
+        match input.as_str() {
+            "r" | "replace" => return Ok(HookAction::Replace),
+            "a" | "append" => return Ok(HookAction::Append),
+            "s" | "skip" => return Ok(HookAction::Skip),
+            _ => {
+                say_inline!("   Invalid choice. Please enter [r]eplace, [a]ppend, or [s]kip: ");
+                std::io::stdout().flush()?;
+                continue;
+            }
+        }
+    }
+}
+
+/// Ask user what to do with mismatched ccgadget hooks
+fn ask_user_fix_hook_action(
+    event_name: &str,
+    existing_hooks: &serde_json::Value,
+    expected_command: &str,
+    expected_matcher: &str,
+) -> Result<HookAction, Box<dyn std::error::Error>> {
+    say!("   ⚠️ Event '{}' has ccgadget hooks but with incorrect configuration:", event_name);
+    
+    // Show current vs expected
+    say!("   Current ccgadget hooks:");
+    if let Some(hooks_array) = existing_hooks.as_array() {
+        for (i, hook_group) in hooks_array.iter().enumerate() {
+            if hook_group_contains_command(hook_group, "ccgadget") {
+                if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
+                    for (j, hook) in hooks.iter().enumerate() {
+                        if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
+                            if command.contains("ccgadget") {
+                                let matcher = hook_group.get("matcher")
+                                    .and_then(|m| m.as_str())
+                                    .unwrap_or("");
+                                let matcher_display = if matcher.is_empty() { "all" } else { &format!("'{}'", matcher) };
+                                say!("     {}.{}: {} (matcher: {})", i + 1, j + 1, command, matcher_display);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    // Show non-ccgadget hooks if any
+    let has_non_ccgadget = existing_hooks.as_array()
+        .map(|arr| arr.iter().any(|hook_group| !hook_group_contains_command(hook_group, "ccgadget")))
+        .unwrap_or(false);
+        
+    if has_non_ccgadget {
+        say!("   Other existing hooks:");
+        if let Some(hooks_array) = existing_hooks.as_array() {
+            for (i, hook_group) in hooks_array.iter().enumerate() {
+                if !hook_group_contains_command(hook_group, "ccgadget") {
+                    if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
+                        for (j, hook) in hooks.iter().enumerate() {
+                            if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
+                                let matcher = hook_group.get("matcher")
+                                    .and_then(|m| m.as_str())
+                                    .unwrap_or("");
+                                let matcher_display = if matcher.is_empty() { "all" } else { matcher };
+                                say!("     {}.{}: {} (matcher: {})", i + 1, j + 1, command, matcher_display);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    let expected_display = if expected_matcher.is_empty() { "all".to_string() } else { format!("'{}'", expected_matcher) };
+    say!("   Expected ccgadget hook: {} (matcher: {})", expected_command, expected_display);
+    say!("   How would you like to handle the incorrect ccgadget hook for {}?", event_name);
+    say!("     [r] Replace - Fix ccgadget hook to correct configuration");
+    say!("     [a] Append  - Add correct ccgadget hook alongside current ones"); 
+    say!("     [s] Skip    - Keep current hooks unchanged");
+    say_inline!("   Choose [r/a/s]: ");
+    std::io::stdout().flush()?;
+    
+    // Read user input
+    loop {
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        let input = input.trim().to_lowercase();
+        
+        match input.as_str() {
+            "r" | "replace" => return Ok(HookAction::Replace),
+            "a" | "append" => return Ok(HookAction::Append),
+            "s" | "skip" => return Ok(HookAction::Skip),
+            _ => {
+                say_inline!("   Invalid choice. Please enter [r]eplace, [a]ppend, or [s]kip: ");
+                std::io::stdout().flush()?;
+                continue;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_ccgadget_hooks_preserves_other_hooks() {
+        let mut settings = serde_json::json!({
+            "model": "opus",
+            "hooks": {
+                "Stop": [{"matcher": "", "hooks": [{"type": "command", "command": "ccgadget trigger"}]}],
+                "PreToolUse": [{"matcher": "Bash", "hooks": [
+                    {"type": "command", "command": "/usr/local/bin/ccgadget trigger"},
+                    {"type": "command", "command": "audit-log"}
+                ]}],
+                "PostToolUse": [{"matcher": "", "hooks": [{"type": "command", "command": "prettier --write"}]}]
+            }
+        });
+
+        let removed = remove_ccgadget_hooks(&mut settings, &[]);
+        assert_eq!(removed, vec![("PreToolUse".to_string(), 1), ("Stop".to_string(), 1)]);
+        assert!(settings["hooks"].get("Stop").is_none());
+        assert_eq!(settings["hooks"]["PreToolUse"][0]["hooks"][0]["command"], "audit-log");
+        assert_eq!(settings["hooks"]["PostToolUse"][0]["hooks"][0]["command"], "prettier --write");
+        assert_eq!(settings["model"], "opus");
+
+        let mut only_ours = serde_json::json!({
+            "hooks": {"Stop": [{"hooks": [{"type": "command", "command": "ccgadget trigger"}]}]}
+        });
+        let mut selected = only_ours.clone();
+        assert!(remove_ccgadget_hooks(&mut selected, &["Notification".to_string()]).is_empty());
+        assert_eq!(selected, only_ours);
+        remove_ccgadget_hooks(&mut only_ours, &["Stop".to_string()]);
+        assert_eq!(only_ours, serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_selected_hooks_config() {
+        assert_eq!(selected_hooks_config(&[]).len(), HOOK_EVENTS.len());
+        let selected = selected_hooks_config(&["Stop".to_string(), "Notification".to_string()]);
+        assert_eq!(selected, vec![("Notification", "ccgadget trigger"), ("Stop", "ccgadget trigger")]);
+    }
+
+    #[test]
+    fn test_settings_write_backs_up_previous_contents() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.local.json");
+        fs::write(&path, "{\"model\": \"opus\"}").unwrap();
+
+        write_claude_settings(&path, &serde_json::json!({"hooks": {}})).unwrap();
+        let backups = settings_backups(&path);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "{\"model\": \"opus\"}");
+
+        // Unchanged settings are not rewritten or backed up again
+        write_claude_settings(&path, &serde_json::json!({"hooks": {}})).unwrap();
+        assert_eq!(settings_backups(&path).len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_matcher_resolution() {
+        let matchers = vec!["Bash".to_string(), "PostToolUse=Edit|Write".to_string()];
+        assert_eq!(matcher_for(&matchers, "PreToolUse"), "Bash");
+        assert_eq!(matcher_for(&matchers, "PostToolUse"), "Edit|Write");
+        assert_eq!(matcher_for(&matchers, "Stop"), "");
+        assert_eq!(matcher_for(&[], "PreToolUse"), "");
+
+        assert!(parse_matcher("Edit|Write").is_ok());
+        assert!(parse_matcher("Bogus=Bash").is_err());
+    }
+
+    #[test]
+    fn test_hook_event_status() {
+        let settings = serde_json::json!({
+            "hooks": {
+                "Stop": [{"matcher": "", "hooks": [{"type": "command", "command": "ccgadget trigger"}]}],
+                "Notification": [{"matcher": "", "hooks": [{"type": "command", "command": "ccgadget trigger --verbose"}]}],
+                "PreToolUse": [{"matcher": "", "hooks": [{"type": "command", "command": "audit-log"}]}]
+            }
+        });
+        assert_eq!(hook_event_status(&settings, "Stop", "ccgadget trigger", ""), HookStatus::Installed);
+        assert_eq!(hook_event_status(&settings, "Notification", "ccgadget trigger", ""), HookStatus::Misconfigured);
+        assert_eq!(hook_event_status(&settings, "PreToolUse", "ccgadget trigger", ""), HookStatus::Missing);
+        assert_eq!(hook_event_status(&settings, "UserPromptSubmit", "ccgadget trigger", ""), HookStatus::Missing);
+    }
+
+    #[test]
+    fn test_hook_scope_enum() {
+        // Test that all hook scopes exist and can be used
+        let _user = HookScope::User;
+        let _project = HookScope::Project;
+        let _local = HookScope::Local;
+        
+        // Test Debug implementation
+        assert_eq!(format!("{:?}", HookScope::User), "User");
+        assert_eq!(format!("{:?}", HookScope::Project), "Project");
+        assert_eq!(format!("{:?}", HookScope::Local), "Local");
+    }
+
+    #[test]
+    fn test_hook_scope_paths_and_precedence() {
+        assert_eq!(claude_settings_path(&HookScope::Project).unwrap(), PathBuf::from(".claude").join("settings.json"));
+        assert_eq!(claude_settings_path(&HookScope::Local).unwrap(), PathBuf::from(".claude").join("settings.local.json"));
+        assert_eq!(HookScope::BY_PRECEDENCE, [HookScope::Local, HookScope::Project, HookScope::User]);
+    }
+}
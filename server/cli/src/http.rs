@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Where a URL points: host, port, and path prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    /// Path prefix without a trailing slash ("" for the root)
+    pub base: String,
+}
+
+impl Endpoint {
+    /// Parse an `http://host[:port][/path]` URL; a bare host or IP is taken as http
+    pub fn parse(url: &str) -> Result<Self, Box<dyn Error>> {
+        if url.starts_with("https://") {
+            return Err("HTTPS is not supported; use the gadget's http:// address".into());
+        }
+        let rest = url.strip_prefix("http://").unwrap_or(url);
+        let (authority, base) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], rest[slash..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("Invalid port in {}", url))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("No host in {}", url).into());
+        }
+        Ok(Self { host: host.to_string(), port, base: base.to_string() })
+    }
+}
+
+/// Status code and body of a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Whether the status is 2xx
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Send one request to `path` under `endpoint`, with a JSON body if given. Each request
+/// uses its own connection, as the gadget's web server serves one at a time.
+pub async fn request(
+    endpoint: &Endpoint,
+    method: &str,
+    path: &str,
+    body: Option<&[u8]>,
+    limit: Duration,
+) -> Result<Response, Box<dyn Error>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect((endpoint.host.as_str(), endpoint.port)).await?;
+        let body = body.unwrap_or_default();
+        let head = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}\r\nUser-Agent: ccgadget/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            endpoint.base,
+            path,
+            endpoint.host,
+            env!("CARGO_PKG_VERSION"),
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).await?;
+        parse_response(&raw)
+    };
+    timeout(limit, exchange)
+        .await
+        .map_err(|_| format!("No response from {}:{} within {}s", endpoint.host, endpoint.port, limit.as_secs()))?
+}
+
+/// Split a raw response into status and body, decoding a chunked body
+fn parse_response(raw: &[u8]) -> Result<Response, Box<dyn Error>> {
+    let split = raw.windows(4).position(|window| window == b"\r\n\r\n").ok_or("Malformed HTTP response")?;
+    let head = std::str::from_utf8(&raw[..split])?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or("Malformed HTTP status line")?;
+    let chunked = lines.any(|line| {
+        let lower = line.to_ascii_lowercase();
+        lower.starts_with("transfer-encoding:") && lower.contains("chunked")
+    });
+    let body = &raw[split + 4..];
+    let body = if chunked { decode_chunked(body)? } else { body.to_vec() };
+    Ok(Response { status, body })
+}
+
+/// Join the chunks of a `Transfer-Encoding: chunked` body
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|window| window == b"\r\n").ok_or("Truncated chunked body")?;
+        let size_field = std::str::from_utf8(&body[..line_end])?;
+        let size = usize::from_str_radix(size_field.split(';').next().unwrap_or("").trim(), 16)?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let start = line_end + 2;
+        let chunk = body.get(start..start + size).ok_or("Truncated chunked body")?;
+        decoded.extend_from_slice(chunk);
+        body = body.get(start + size + 2..).ok_or("Truncated chunked body")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoint() {
+        let endpoint = Endpoint::parse("http://192.168.1.40:8080/ccg/").unwrap();
+        assert_eq!(endpoint, Endpoint { host: "192.168.1.40".to_string(), port: 8080, base: "/ccg".to_string() });
+        assert_eq!(Endpoint::parse("ccgadget.local").unwrap().port, 80);
+        assert!(Endpoint::parse("https://192.168.1.40").is_err());
+        assert!(Endpoint::parse("http://:80").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}").unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.body, b"{}");
+
+        let chunked = b"HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nnone\r\n4\r\n yet\r\n0\r\n\r\n";
+        let response = parse_response(chunked).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, b"none yet");
+    }
+}
@@ -0,0 +1,343 @@
+use crate::paths;
+use crate::transcript::{TokenUsage, UsageRecord};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Export formats `ccgadget import` understands
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ImportSource {
+    /// `ccusage daily --json` or `ccusage session --json` output
+    Ccusage,
+    /// CSV with a header row (date or timestamp, model, token columns)
+    Csv,
+}
+
+impl ImportSource {
+    /// Name recorded in imported dedup keys
+    fn as_str(&self) -> &'static str {
+        match self {
+            ImportSource::Ccusage => "ccusage",
+            ImportSource::Csv => "csv",
+        }
+    }
+}
+
+/// Outcome of adding records to the import store
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub duplicates: usize,
+}
+
+/// Where imported usage is kept (JSON lines of usage records)
+pub fn store_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(paths::state_dir()?.join("imported.jsonl"))
+}
+
+/// Load every imported usage record
+pub fn load_imported() -> Result<Vec<UsageRecord>, Box<dyn Error>> {
+    let path = store_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append records to the import store, skipping any imported before
+pub fn save_imported(records: Vec<UsageRecord>) -> Result<ImportSummary, Box<dyn Error>> {
+    let mut seen: HashSet<String> = load_imported()?.into_iter().filter_map(|r| r.dedup_key).collect();
+    let path = store_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    let mut summary = ImportSummary::default();
+    for record in records {
+        let key = record.dedup_key.clone().unwrap_or_default();
+        if !seen.insert(key) {
+            summary.duplicates += 1;
+            continue;
+        }
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        summary.added += 1;
+    }
+    Ok(summary)
+}
+
+/// Drop imported records already covered by transcript data: same session, or (for
+/// aggregates without a session) the same local day and model
+pub fn merge(mut transcript: Vec<UsageRecord>, imported: Vec<UsageRecord>) -> Vec<UsageRecord> {
+    let sessions: HashSet<&str> = transcript.iter().filter_map(|r| r.session_id.as_deref()).collect();
+    let day_models: BTreeSet<(NaiveDate, &str)> = transcript
+        .iter()
+        .map(|r| (r.timestamp.with_timezone(&Local).date_naive(), r.model.as_str()))
+        .collect();
+
+    let kept: Vec<UsageRecord> = imported
+        .into_iter()
+        .filter(|record| match &record.session_id {
+            Some(session) => !sessions.contains(session.as_str()),
+            None => !day_models.contains(&(record.timestamp.with_timezone(&Local).date_naive(), record.model.as_str())),
+        })
+        .collect();
+    transcript.extend(kept);
+    transcript
+}
+
+/// Midday local time on a date, used to place day-level aggregates
+fn midday(date: NaiveDate) -> DateTime<Utc> {
+    let noon = date.and_hms_opt(12, 0, 0).expect("valid time");
+    Local
+        .from_local_datetime(&noon)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&noon))
+}
+
+/// Build an imported record with a stable dedup key
+fn imported_record(
+    source: ImportSource,
+    timestamp: DateTime<Utc>,
+    session_id: Option<String>,
+    cwd: Option<String>,
+    model: String,
+    usage: TokenUsage,
+) -> UsageRecord {
+    let dedup_key = format!(
+        "import:{}:{}:{}:{}",
+        source.as_str(),
+        timestamp.to_rfc3339(),
+        session_id.as_deref().unwrap_or(""),
+        model
+    );
+    UsageRecord { timestamp, session_id, cwd, model, usage, dedup_key: Some(dedup_key), project: None }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CcusageBreakdown {
+    model_name: String,
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    cache_creation_tokens: u64,
+    #[serde(default)]
+    cache_read_tokens: u64,
+}
+
+impl CcusageBreakdown {
+    /// Token counts in ccgadget's representation
+    fn usage(&self) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            cache_creation_input_tokens: self.cache_creation_tokens,
+            cache_read_input_tokens: self.cache_read_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CcusageDay {
+    date: String,
+    #[serde(default)]
+    model_breakdowns: Vec<CcusageBreakdown>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CcusageSession {
+    session_id: String,
+    last_activity: String,
+    project_path: Option<String>,
+    #[serde(default)]
+    model_breakdowns: Vec<CcusageBreakdown>,
+}
+
+#[derive(Deserialize)]
+struct CcusageExport {
+    daily: Option<Vec<CcusageDay>>,
+    sessions: Option<Vec<CcusageSession>>,
+}
+
+/// Parse a date or RFC 3339 timestamp from an export
+fn parse_when(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    crate::usage::parse_date(value).map(midday)
+}
+
+/// Convert ccusage `daily` or `session` JSON into usage records, one per model
+pub fn parse_ccusage(json: &str) -> Result<Vec<UsageRecord>, String> {
+    let export: CcusageExport = serde_json::from_str(json).map_err(|e| format!("Invalid ccusage JSON: {}", e))?;
+    let mut records = Vec::new();
+
+    if let Some(days) = export.daily {
+        for day in days {
+            let timestamp = parse_when(&day.date)?;
+            for model in day.model_breakdowns {
+                let usage = model.usage();
+                records.push(imported_record(ImportSource::Ccusage, timestamp, None, None, model.model_name, usage));
+            }
+        }
+    } else if let Some(sessions) = export.sessions {
+        for session in sessions {
+            let timestamp = parse_when(&session.last_activity)?;
+            for model in session.model_breakdowns {
+                let usage = model.usage();
+                records.push(imported_record(
+                    ImportSource::Ccusage,
+                    timestamp,
+                    Some(session.session_id.clone()),
+                    session.project_path.clone(),
+                    model.model_name,
+                    usage,
+                ));
+            }
+        }
+    } else {
+        return Err("Unsupported ccusage export; use `ccusage daily --json` or `ccusage session --json`".to_string());
+    }
+    Ok(records)
+}
+
+/// Split one CSV line into fields, honouring double-quoted fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Convert a CSV export into usage records. Columns are matched by header name:
+/// `date`/`timestamp`, `model`, `input_tokens`, `output_tokens`,
+/// `cache_creation_tokens`, `cache_read_tokens`, and optional `session_id`/`project`.
+pub fn parse_csv(text: &str) -> Result<Vec<UsageRecord>, String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or("CSV is empty")?;
+    let columns: Vec<String> = csv_fields(header)
+        .into_iter()
+        .map(|name| name.to_lowercase().replace([' ', '-'], "_"))
+        .collect();
+    let find = |names: &[&str]| columns.iter().position(|column| names.contains(&column.as_str()));
+
+    let when = find(&["timestamp", "date", "day"]).ok_or("CSV needs a 'date' or 'timestamp' column")?;
+    let model = find(&["model", "model_name", "modelname"]).ok_or("CSV needs a 'model' column")?;
+    let input = find(&["input_tokens", "inputtokens", "input"]);
+    let output = find(&["output_tokens", "outputtokens", "output"]);
+    let cache_write = find(&["cache_creation_tokens", "cachecreationtokens", "cache_creation_input_tokens", "cache_write_tokens", "cache_write"]);
+    let cache_read = find(&["cache_read_tokens", "cachereadtokens", "cache_read_input_tokens", "cache_read"]);
+    let session = find(&["session_id", "sessionid", "session"]);
+    let project = find(&["project", "project_path", "projectpath", "cwd"]);
+
+    let mut records = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let fields = csv_fields(line);
+        let row = index + 2;
+        let text = |column: Option<usize>| column.and_then(|c| fields.get(c)).filter(|v| !v.is_empty()).cloned();
+        let tokens = |column: Option<usize>| -> Result<u64, String> {
+            match text(column) {
+                Some(value) => value.replace('_', "").parse().map_err(|_| format!("Row {}: invalid token count '{}'", row, value)),
+                None => Ok(0),
+            }
+        };
+
+        let timestamp = parse_when(&text(Some(when)).ok_or(format!("Row {}: missing date", row))?)
+            .map_err(|e| format!("Row {}: {}", row, e))?;
+        let usage = TokenUsage {
+            input_tokens: tokens(input)?,
+            output_tokens: tokens(output)?,
+            cache_creation_input_tokens: tokens(cache_write)?,
+            cache_read_input_tokens: tokens(cache_read)?,
+        };
+        records.push(imported_record(
+            ImportSource::Csv,
+            timestamp,
+            text(session),
+            text(project),
+            text(Some(model)).ok_or(format!("Row {}: missing model", row))?,
+            usage,
+        ));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ccusage_daily() {
+        let json = r#"{"daily":[{"date":"2025-07-20","inputTokens":10,"outputTokens":20,
+            "modelBreakdowns":[
+                {"modelName":"claude-sonnet-4","inputTokens":10,"outputTokens":15,"cacheCreationTokens":1,"cacheReadTokens":2,"cost":0.1},
+                {"modelName":"claude-opus-4","inputTokens":0,"outputTokens":5,"cacheCreationTokens":0,"cacheReadTokens":0,"cost":0.2}
+            ]}],"totals":{}}"#;
+        let records = parse_ccusage(json).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].model, "claude-sonnet-4");
+        assert_eq!(records[0].usage.cache_read_input_tokens, 2);
+        assert_eq!(records[0].timestamp.with_timezone(&Local).date_naive(), NaiveDate::from_ymd_opt(2025, 7, 20).unwrap());
+        assert!(parse_ccusage(r#"{"monthly":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_with_quotes_and_aliases() {
+        let csv = "Date,Session ID,Model,Input Tokens,Output Tokens,Cache Read Tokens,Project\n\
+                   2025-07-20,abc,claude-sonnet-4,100,200,5,\"/work/api, v2\"\n";
+        let records = parse_csv(csv).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].session_id.as_deref(), Some("abc"));
+        assert_eq!(records[0].usage.output_tokens, 200);
+        assert_eq!(records[0].cwd.as_deref(), Some("/work/api, v2"));
+        assert!(parse_csv("date,model,input_tokens\n2025-07-20,sonnet,lots\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_skips_imports_covered_by_transcripts() {
+        let at = midday(NaiveDate::from_ymd_opt(2025, 7, 20).unwrap());
+        let transcript = vec![UsageRecord {
+            timestamp: at,
+            session_id: Some("live".to_string()),
+            cwd: None,
+            model: "claude-sonnet-4".to_string(),
+            usage: TokenUsage::default(),
+            dedup_key: None,
+            project: None,
+        }];
+        let imported = vec![
+            imported_record(ImportSource::Csv, at, None, None, "claude-sonnet-4".to_string(), TokenUsage::default()),
+            imported_record(ImportSource::Csv, at, None, None, "claude-opus-4".to_string(), TokenUsage::default()),
+            imported_record(ImportSource::Csv, at, Some("live".to_string()), None, "claude-haiku".to_string(), TokenUsage::default()),
+        ];
+        let merged = merge(transcript, imported);
+        let models: Vec<&str> = merged.iter().map(|r| r.model.as_str()).collect();
+        assert_eq!(models, vec!["claude-sonnet-4", "claude-opus-4"]);
+    }
+}
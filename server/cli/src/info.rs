@@ -0,0 +1,138 @@
+use crate::protocol;
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{CharPropFlags, Peripheral as _};
+use btleplug::platform::Peripheral;
+use serde::Serialize;
+use tracing::debug;
+use uuid::Uuid;
+
+/// Battery Level characteristic of the standard Battery Service
+const BATTERY_LEVEL: u16 = 0x2A19;
+
+/// Firmware Revision String of the standard Device Information Service
+const FIRMWARE_REVISION: u16 = 0x2A26;
+
+/// Hardware Revision String of the standard Device Information Service
+const HARDWARE_REVISION: u16 = 0x2A27;
+
+/// What the device reports about itself; fields the firmware does not expose stay `None`
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub battery_percent: Option<u8>,
+    pub firmware: Option<String>,
+    pub hardware: Option<String>,
+    pub uptime_secs: Option<u64>,
+    pub free_memory_bytes: Option<u64>,
+    pub display: Option<String>,
+    pub wifi: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Record the value read from the characteristic `uuid`; unknown characteristics
+    /// and malformed values are skipped
+    pub fn record(&mut self, uuid: Uuid, value: &[u8]) {
+        let text = || Some(String::from_utf8_lossy(value).trim_end_matches('\0').trim().to_string()).filter(|s| !s.is_empty());
+        let number = || Some(u32::from_le_bytes(value.get(..4)?.try_into().ok()?) as u64);
+        match uuid {
+            uuid if uuid == uuid_from_u16(BATTERY_LEVEL) => self.battery_percent = value.first().copied().filter(|level| *level <= 100),
+            uuid if uuid == uuid_from_u16(FIRMWARE_REVISION) => self.firmware = text(),
+            uuid if uuid == uuid_from_u16(HARDWARE_REVISION) => self.hardware = text(),
+            protocol::UPTIME_CHARACTERISTIC_UUID => self.uptime_secs = number(),
+            protocol::FREE_MEMORY_CHARACTERISTIC_UUID => self.free_memory_bytes = number(),
+            protocol::DISPLAY_TYPE_CHARACTERISTIC_UUID => self.display = text(),
+            protocol::WIFI_STATUS_CHARACTERISTIC_UUID => self.wifi = text(),
+            _ => {}
+        }
+    }
+
+    /// Rows for the text table, with readable units
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let unknown = || "unknown".to_string();
+        vec![
+            ("Battery", self.battery_percent.map(|level| format!("{}%", level)).unwrap_or_else(unknown)),
+            ("Firmware", self.firmware.clone().unwrap_or_else(unknown)),
+            ("Hardware", self.hardware.clone().unwrap_or_else(unknown)),
+            ("Uptime", self.uptime_secs.map(format_uptime).unwrap_or_else(unknown)),
+            ("Free memory", self.free_memory_bytes.map(|bytes| format!("{:.1} KiB", bytes as f64 / 1024.0)).unwrap_or_else(unknown)),
+            ("Display", self.display.clone().unwrap_or_else(unknown)),
+            ("Wi-Fi", self.wifi.clone().unwrap_or_else(unknown)),
+        ]
+    }
+
+    /// What the simulated device reports in demo mode
+    pub fn simulated() -> Self {
+        Self {
+            battery_percent: Some(87),
+            firmware: Some("1.3.0".to_string()),
+            hardware: Some("ccgadget-s3 rev B".to_string()),
+            uptime_secs: Some(3 * 86_400 + 4 * 3_600 + 5 * 60),
+            free_memory_bytes: Some(182_272),
+            display: Some("ST7789 240x240".to_string()),
+            wifi: Some("connected: home (-58 dBm)".to_string()),
+        }
+    }
+}
+
+/// Uptime as days, hours, and minutes, e.g. `3d 4h 5m`
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3_600, secs % 3_600 / 60);
+    match days {
+        0 => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// Characteristics [`DeviceInfo`] is read from
+fn characteristics() -> [Uuid; 7] {
+    [
+        uuid_from_u16(BATTERY_LEVEL),
+        uuid_from_u16(FIRMWARE_REVISION),
+        uuid_from_u16(HARDWARE_REVISION),
+        protocol::UPTIME_CHARACTERISTIC_UUID,
+        protocol::FREE_MEMORY_CHARACTERISTIC_UUID,
+        protocol::DISPLAY_TYPE_CHARACTERISTIC_UUID,
+        protocol::WIFI_STATUS_CHARACTERISTIC_UUID,
+    ]
+}
+
+/// Read the info characteristics a connected peripheral exposes
+pub async fn read(peripheral: &Peripheral) -> DeviceInfo {
+    let wanted = characteristics();
+    let mut info = DeviceInfo::default();
+    for characteristic in peripheral.characteristics() {
+        if !wanted.contains(&characteristic.uuid) || !characteristic.properties.contains(CharPropFlags::READ) {
+            continue;
+        }
+        match peripheral.read(&characteristic).await {
+            Ok(value) => info.record(characteristic.uuid, &value),
+            Err(e) => debug!("Reading {} failed: {}", characteristic.uuid, e),
+        }
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_decodes_standard_and_custom_values() {
+        let mut info = DeviceInfo::default();
+        info.record(uuid_from_u16(BATTERY_LEVEL), &[64]);
+        info.record(uuid_from_u16(FIRMWARE_REVISION), b"1.3.0\0");
+        info.record(protocol::UPTIME_CHARACTERISTIC_UUID, &90_061u32.to_le_bytes());
+        info.record(protocol::FREE_MEMORY_CHARACTERISTIC_UUID, &[0, 0x10]);
+        info.record(uuid_from_u16(0x2A00), b"CCGadget");
+        assert_eq!(
+            info,
+            DeviceInfo {
+                battery_percent: Some(64),
+                firmware: Some("1.3.0".to_string()),
+                uptime_secs: Some(90_061),
+                ..Default::default()
+            }
+        );
+        assert!(info.rows().contains(&("Uptime", "1d 1h 1m".to_string())));
+        assert!(info.rows().contains(&("Wi-Fi", "unknown".to_string())));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::paths;
+use clap_complete::Shell;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// launchd label for the daemon agent
+pub const LAUNCHD_LABEL: &str = "com.ccgadget.daemon";
+
+/// Name of the daemon's systemd user unit
+pub const SYSTEMD_UNIT: &str = "ccgadget.service";
+
+/// Name of the daemon's Task Scheduler task on Windows
+pub const SCHEDULED_TASK: &str = "CCGadget";
+
+/// Per-user completion script locations that shells load automatically
+pub fn completion_paths() -> Result<Vec<(Shell, PathBuf)>, Box<dyn Error>> {
+    if cfg!(windows) {
+        return Ok(Vec::new());
+    }
+    let home = paths::home_dir()?;
+    Ok(vec![
+        (Shell::Bash, home.join(".local/share/bash-completion/completions/ccgadget")),
+        (Shell::Zsh, home.join(".local/share/zsh/site-functions/_ccgadget")),
+        (Shell::Fish, home.join(".config/fish/completions/ccgadget.fish")),
+    ])
+}
+
+/// Where the per-user service definition for the daemon lives, if supported
+pub fn service_unit_path() -> Result<Option<PathBuf>, Box<dyn Error>> {
+    #[cfg(target_os = "macos")]
+    return Ok(Some(
+        paths::home_dir()?
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", LAUNCHD_LABEL)),
+    ));
+    #[cfg(all(unix, not(target_os = "macos")))]
+    return Ok(Some(paths::home_dir()?.join(".config/systemd/user").join(SYSTEMD_UNIT)));
+    #[cfg(windows)]
+    return Ok(Some(paths::state_dir()?.join("ccgadget-task.xml")));
+    #[cfg(not(any(unix, windows)))]
+    return Ok(None);
+}
+
+/// Service definition that runs `ccgadget start --foreground` for the current user
+pub fn service_unit(exe: &Path) -> Option<String> {
+    #[cfg(target_os = "macos")]
+    return Some(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>start</string>
+        <string>--foreground</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+</dict>
+</plist>
+"#,
+        label = LAUNCHD_LABEL,
+        exe = exe.display()
+    ));
+    #[cfg(all(unix, not(target_os = "macos")))]
+    return Some(format!(
+        "[Unit]\n\
+         Description=CCGadget daemon\n\
+         \n\
+         [Service]\n\
+         ExecStart={} start --foreground\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exe.display()
+    ));
+    #[cfg(windows)]
+    return Some(scheduled_task(exe));
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = exe;
+        None
+    }
+}
+
+/// Task Scheduler definition that starts the daemon when the current user logs on.
+/// Without an execution time limit of zero, Windows stops tasks after three days.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn scheduled_task(exe: &Path) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>CCGadget daemon</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger>
+      <Enabled>true</Enabled>
+    </LogonTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <LogonType>InteractiveToken</LogonType>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <RestartOnFailure>
+      <Interval>PT1M</Interval>
+      <Count>3</Count>
+    </RestartOnFailure>
+    <Hidden>true</Hidden>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{exe}</Command>
+      <Arguments>start --foreground</Arguments>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        exe = xml_escape(&exe.display().to_string())
+    )
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Bytes of a service definition as written to disk: `schtasks` only reads task XML
+/// as UTF-16 with a byte order mark
+pub fn service_unit_bytes(unit: &str) -> Vec<u8> {
+    if cfg!(windows) {
+        std::iter::once(0xfeff).chain(unit.encode_utf16()).flat_map(u16::to_le_bytes).collect()
+    } else {
+        unit.as_bytes().to_vec()
+    }
+}
+
+/// Write shell completions and the service definition; returns the files written
+pub fn post_install(
+    command: &mut clap::Command,
+    completions: bool,
+    service: bool,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut written = Vec::new();
+
+    if completions {
+        for (shell, path) in completion_paths()? {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, command, "ccgadget", &mut script);
+            write_file(&path, &script)?;
+            written.push(path);
+        }
+    }
+
+    if service {
+        let exe = std::env::current_exe()?;
+        if let (Some(path), Some(unit)) = (service_unit_path()?, service_unit(&exe)) {
+            write_file(&path, &service_unit_bytes(&unit))?;
+            written.push(path);
+        }
+    }
+
+    Ok(written)
+}
+
+/// Write a file, creating its parent directory first
+pub fn write_file(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_service_unit_runs_foreground_daemon() {
+        let unit = service_unit(Path::new("/opt/bin/ccgadget")).unwrap();
+        assert!(unit.contains("/opt/bin/ccgadget"));
+        assert!(unit.contains("--foreground"));
+    }
+
+    #[test]
+    fn test_scheduled_task_runs_foreground_daemon() {
+        let task = scheduled_task(Path::new(r"C:\Users\me & you\ccgadget.exe"));
+        assert!(task.contains(r"<Command>C:\Users\me &amp; you\ccgadget.exe</Command>"));
+        assert!(task.contains("<Arguments>start --foreground</Arguments>"));
+        assert!(task.contains("<ExecutionTimeLimit>PT0S</ExecutionTimeLimit>"));
+    }
+}
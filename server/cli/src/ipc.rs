@@ -0,0 +1,448 @@
+use crate::budget::BudgetUsage;
+use crate::burn::BurnSnapshot;
+use crate::config::Activity;
+use crate::context::ContextUsage;
+use crate::device::{DeviceEvent, LinkMode};
+use crate::dnd::DoNotDisturb;
+use crate::error::{Classify, ExitCode};
+use crate::focus::{FocusPolicy, SessionInfo};
+use crate::payload::DevicePayload;
+use crate::ratelimit::LimitWarning;
+use crate::settings::DeviceSettings;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "full")]
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "full")]
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+
+/// Localhost port used for IPC where Unix sockets are unavailable
+#[cfg(not(unix))]
+const IPC_PORT: u16 = 47321;
+
+/// Request sent from a CLI invocation to the running daemon
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IpcRequest {
+    /// Query the daemon's current state
+    Status,
+    /// Hand a hook event over for transmission to the device
+    Event {
+        payload: Box<DevicePayload>,
+        transcript_path: Option<String>,
+    },
+    /// Switch the device link mode without restarting
+    SetLinkMode { mode: LinkMode },
+    /// Pause (or resume) regular transmissions while the device is being serviced
+    SetMaintenance { enabled: bool },
+    /// Turn do-not-disturb on until `until` (until turned off when `None`), or off
+    SetDnd {
+        enabled: bool,
+        #[serde(default)]
+        until: Option<DateTime<Utc>>,
+    },
+    /// The device's settings as last read; `refresh` asks for them to be read again
+    ReadSettings { refresh: bool },
+    /// Store settings on the device
+    PushSettings { settings: DeviceSettings },
+    /// Active sessions and which one drives the device display
+    Sessions,
+    /// Change which session drives the display: pin `session` (an id or unique prefix),
+    /// switch `policy`, or pin the session after the focused one (`next`)
+    Focus {
+        #[serde(default)]
+        session: Option<String>,
+        #[serde(default)]
+        policy: Option<FocusPolicy>,
+        #[serde(default)]
+        next: bool,
+    },
+    /// Act on a device event as if the device had sent it, e.g. a button press
+    DeviceEvent { event: DeviceEvent },
+    /// Hook events received after feed position `after` (0 for all the daemon keeps)
+    Feed { after: u64 },
+    /// Whether to deny the tool call a hook is asking about, disarming a
+    /// `block-next-tool` button press if one is waiting
+    TakeToolBlock,
+    /// Shut down as on Ctrl-C, for `start --takeover`
+    Shutdown,
+}
+
+/// Daemon reply to an [`IpcRequest`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IpcResponse {
+    Status(Box<DaemonStatus>),
+    /// Event accepted while the device link is up
+    Accepted,
+    /// Event queued because the device link is not ready yet
+    Queued { state: LinkState, queued: usize },
+    /// Notification held for the next digest
+    Digested { pending: usize },
+    /// Event held because the device is in maintenance mode
+    Paused { queued: usize },
+    /// Settings last read from the device (`None` if it does not report them) and how
+    /// many reads have completed, so a client can wait for a fresh one
+    Settings {
+        state: LinkState,
+        settings: Option<DeviceSettings>,
+        reads: u64,
+    },
+    /// Active sessions, oldest event first, and the focus decision
+    Sessions {
+        policy: FocusPolicy,
+        pinned: Option<String>,
+        focused: Option<String>,
+        sessions: Vec<SessionInfo>,
+    },
+    /// Recent hook events, oldest first
+    Feed { events: Vec<FeedEntry> },
+    /// Whether a `block-next-tool` button press was waiting; it is disarmed now
+    ToolBlock { armed: bool },
+    /// The daemon is shutting down
+    ShuttingDown,
+    Error { message: String },
+}
+
+/// Lifecycle of the daemon's device link
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkState {
+    /// Scanning for / connecting to the paired device
+    Starting,
+    /// Device link established
+    Ready,
+    /// Link lost or connection attempt failed; retrying
+    Disconnected,
+    /// No paired device registered
+    Unpaired,
+    /// Observe-only mode: events are processed locally and never sent to a device
+    Observing,
+    /// Link closed after `daemon.idle_timeout_minutes` without events; the next one reopens it
+    Idle,
+}
+
+impl std::fmt::Display for LinkState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            LinkState::Starting => "starting",
+            LinkState::Ready => "ready",
+            LinkState::Disconnected => "disconnected",
+            LinkState::Unpaired => "unpaired",
+            LinkState::Observing => "observing",
+            LinkState::Idle => "idle",
+        };
+        f.write_str(label)
+    }
+}
+
+/// Snapshot of daemon state reported over IPC
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DaemonStatus {
+    pub state: LinkState,
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub device: Option<String>,
+    pub queued: usize,
+    pub delivered: u64,
+    #[serde(default)]
+    pub link_mode: LinkMode,
+    /// Config profile the daemon was started with
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Regular transmissions are paused for device maintenance
+    #[serde(default)]
+    pub maintenance: bool,
+    /// Focus mode is on, toggled by a `focus-mode` device button
+    #[serde(default)]
+    pub focus_mode: bool,
+    /// The device does not buzz or flash until then, after a `mute-alerts` button press
+    #[serde(default)]
+    pub alerts_muted_until: Option<DateTime<Utc>>,
+    /// A `block-next-tool` button press is waiting for the next tool call to deny
+    #[serde(default)]
+    pub tool_block_armed: bool,
+    /// Do-not-disturb, while it is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnd: Option<DoNotDisturb>,
+    /// It is quiet hours, which keep the device as quiet as do-not-disturb
+    #[serde(default)]
+    pub quiet_hours: bool,
+    /// No hook events since then, for `daemon.presence_idle_minutes`; the device shows
+    /// its idle screen
+    #[serde(default)]
+    pub idle_since: Option<DateTime<Utc>>,
+    /// Context window of the focused session, as of its latest event
+    #[serde(default)]
+    pub context: Option<ContextUsage>,
+    /// Seconds since the daemon started, on its monotonic clock
+    #[serde(default)]
+    pub uptime_secs: Option<u64>,
+    /// How far the system clock has been moved since the daemon started (positive:
+    /// forward), measured against the monotonic clock
+    #[serde(default)]
+    pub clock_jump_ms: i64,
+    /// How far the device clock was ahead of the host (negative: behind) at the last time
+    /// sync, when the device reports its time
+    #[serde(default)]
+    pub clock_skew_ms: Option<i64>,
+    /// Transport carrying the current device link
+    #[serde(default)]
+    pub transport: Option<String>,
+    /// Protocol version agreed with the device
+    #[serde(default)]
+    pub protocol: Option<u8>,
+    /// Last status the device reported, e.g. `battery_low`
+    #[serde(default)]
+    pub device_status: Option<String>,
+    /// Latest frame the device confirmed receiving
+    #[serde(default)]
+    pub last_acked: Option<AckedEvent>,
+    /// Frames written again because the device did not acknowledge them in time
+    #[serde(default)]
+    pub retransmits: u64,
+    /// Times the device link was re-established after being lost or closed while idle
+    #[serde(default)]
+    pub reconnects: u64,
+    /// When the next connection attempt is due, while the link is down
+    #[serde(default)]
+    pub retry_at: Option<DateTime<Utc>>,
+    /// Why the last connection attempt failed, while the link is down
+    #[serde(default)]
+    pub link_error: Option<String>,
+    /// When an established link was lost, until the device is back
+    #[serde(default)]
+    pub offline_since: Option<DateTime<Utc>>,
+    /// Keepalive checks the device has failed in a row
+    #[serde(default)]
+    pub missed_heartbeats: u32,
+    /// Added devices the frames are mirrored to, per `[broadcast]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<MirrorStatus>,
+    /// Permission request Claude is blocked on, until the session moves on
+    #[serde(default)]
+    pub awaiting_permission: Option<PermissionWait>,
+    /// Rate or usage limit Claude last hit, until it lifts
+    #[serde(default)]
+    pub limit: Option<LimitWarning>,
+    /// Consumption of the configured budgets at the last check
+    #[serde(default)]
+    pub budgets: Vec<BudgetUsage>,
+    /// Usage block as of the last usage update
+    #[serde(default)]
+    pub burn: Option<BurnSnapshot>,
+    /// Estimated cost of the focused session as of the last usage update
+    #[serde(default)]
+    pub session_cost: Option<SessionCost>,
+}
+
+/// Link to an added device that mirrors the paired one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MirrorStatus {
+    pub device: String,
+    pub state: LinkState,
+    pub queued: usize,
+    pub delivered: u64,
+}
+
+/// Estimated cost of one session so far
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionCost {
+    pub session_id: Option<String>,
+    pub cost_usd: f64,
+}
+
+/// A hook event the daemon received, as listed by `ccgadget top`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    /// Position in the daemon's feed, counting from 1 when it started
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub event: String,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub activity: Option<Activity>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A permission request Claude is waiting on the user for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PermissionWait {
+    pub session_id: Option<String>,
+    /// Tool Claude asked to use, when the message names it
+    pub tool: Option<String>,
+    pub since: DateTime<Utc>,
+}
+
+/// A frame the device acknowledged, by the event it carried
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AckedEvent {
+    pub event: String,
+    pub seq: u32,
+    pub at: DateTime<Utc>,
+}
+
+/// Path of the daemon's Unix socket
+#[cfg(unix)]
+pub fn socket_path() -> Result<std::path::PathBuf, Box<dyn Error>> {
+    Ok(crate::paths::state_dir()?.join("daemon.sock"))
+}
+
+/// Localhost address of the daemon's IPC endpoint
+#[cfg(not(unix))]
+pub fn ipc_address() -> String {
+    format!("127.0.0.1:{}", IPC_PORT)
+}
+
+/// Why a request did not reach the daemon
+#[derive(Debug, thiserror::Error)]
+pub enum IpcError {
+    #[error("Daemon not running")]
+    DaemonNotRunning,
+}
+
+impl Classify for IpcError {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::DaemonNotRunning
+    }
+
+    fn hint(&self) -> Option<&str> {
+        Some("Start it with `ccgadget start`")
+    }
+}
+
+/// A failed connection to the daemon endpoint: nothing listening is `DaemonNotRunning`
+fn connect_error(e: std::io::Error) -> Box<dyn Error> {
+    match e.kind() {
+        std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused => IpcError::DaemonNotRunning.into(),
+        _ => e.into(),
+    }
+}
+
+/// Send one request to the daemon and wait up to `timeout` for the reply
+pub fn request(req: &IpcRequest, timeout: Duration) -> Result<IpcResponse, Box<dyn Error>> {
+    #[cfg(unix)]
+    let stream = std::os::unix::net::UnixStream::connect(socket_path()?).map_err(connect_error)?;
+    #[cfg(not(unix))]
+    let stream =
+        std::net::TcpStream::connect_timeout(&([127, 0, 0, 1], IPC_PORT).into(), timeout).map_err(connect_error)?;
+
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut writer = &stream;
+    writeln!(writer, "{}", serde_json::to_string(req)?)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Listener accepting IPC connections
+#[cfg(all(unix, feature = "full"))]
+pub type Listener = tokio::net::UnixListener;
+#[cfg(all(not(unix), feature = "full"))]
+pub type Listener = tokio::net::TcpListener;
+
+/// Bind the IPC endpoint, refusing if another daemon is already listening
+#[cfg(feature = "full")]
+pub async fn bind() -> Result<Listener, Box<dyn Error>> {
+    #[cfg(unix)]
+    {
+        let path = socket_path()?;
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err("CCGadget daemon is already running".into());
+            }
+            // Leftover socket from a daemon that did not shut down cleanly
+            std::fs::remove_file(&path)?;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(tokio::net::UnixListener::bind(&path)?)
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::net::TcpListener::bind(("127.0.0.1", IPC_PORT))
+            .await
+            .map_err(|_| "CCGadget daemon is already running".into())
+    }
+}
+
+/// Remove the IPC endpoint on shutdown
+pub fn cleanup() {
+    #[cfg(unix)]
+    if let Ok(path) = socket_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Accept connections forever, answering each request with `handler`
+#[cfg(feature = "full")]
+pub async fn serve<F>(listener: Listener, handler: F)
+where
+    F: Fn(IpcRequest) -> IpcResponse + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let _ = handle_connection(stream, handler.as_ref()).await;
+        });
+    }
+}
+
+/// Read newline-delimited requests from a connection and write replies
+#[cfg(feature = "full")]
+async fn handle_connection<S, F>(stream: S, handler: &F) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(IpcRequest) -> IpcResponse,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(request) => handler(request),
+            Err(e) => {
+                tracing::debug!("Rejected IPC request: {}", e);
+                IpcResponse::Error { message: format!("Invalid request: {}", e) }
+            }
+        };
+        tracing::trace!("IPC {} -> {:?}", line, response);
+        let mut json = serde_json::to_string(&response).unwrap_or_default();
+        json.push('\n');
+        writer.write_all(json.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_wire_format() {
+        let json = serde_json::to_string(&IpcRequest::Status).unwrap();
+        assert_eq!(json, r#"{"type":"status"}"#);
+
+        let queued = IpcResponse::Queued { state: LinkState::Starting, queued: 2 };
+        let json = serde_json::to_string(&queued).unwrap();
+        assert_eq!(json, r#"{"type":"queued","state":"starting","queued":2}"#);
+        assert!(matches!(
+            serde_json::from_str::<IpcResponse>(&json).unwrap(),
+            IpcResponse::Queued { state: LinkState::Starting, queued: 2 }
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+//! Core of ccgadget: device pairing and transports, Claude Code hook setup, logging,
+//! configuration, the wire protocol, and usage metrics. The `ccgadget` binary is a
+//! clap front-end over these modules; `ccgadget-trigger` uses only the trigger path,
+//! which builds without the `full` feature.
+
+#[macro_use]
+pub mod output;
+
+pub mod activity;
+#[cfg(feature = "full")]
+pub mod api;
+#[cfg(feature = "full")]
+pub mod backoff;
+#[cfg(feature = "full")]
+pub mod bluetooth;
+pub mod budget;
+pub mod burn;
+#[cfg(feature = "full")]
+pub mod clock;
+#[cfg(feature = "full")]
+pub mod coalesce;
+pub mod config;
+pub mod context;
+pub mod cost;
+#[cfg(feature = "full")]
+pub mod daemon;
+#[cfg(feature = "full")]
+pub mod desktop;
+pub mod device;
+#[cfg(feature = "full")]
+pub mod diff;
+#[cfg(feature = "full")]
+pub mod digest;
+pub mod dnd;
+pub mod error;
+pub mod expr;
+#[cfg(feature = "full")]
+pub mod fault;
+pub mod focus;
+pub mod fs_util;
+#[cfg(feature = "full")]
+pub mod hooks;
+#[cfg(feature = "full")]
+pub mod homeassistant;
+#[cfg(feature = "full")]
+pub mod http;
+pub mod import;
+#[cfg(feature = "ble")]
+pub mod info;
+#[cfg(feature = "full")]
+pub mod install;
+pub mod ipc;
+#[cfg(feature = "full")]
+pub mod lock;
+#[cfg(feature = "full")]
+pub mod logging;
+#[cfg(feature = "full")]
+pub mod logs;
+pub mod metrics;
+#[cfg(feature = "full")]
+pub mod metrics_db;
+#[cfg(feature = "full")]
+pub mod mqtt;
+#[cfg(feature = "full")]
+pub mod on_event;
+#[cfg(feature = "full")]
+pub mod otel;
+pub mod paths;
+pub mod payload;
+pub mod pricing;
+pub mod project;
+pub mod prompt;
+#[cfg(feature = "full")]
+pub mod protocol;
+pub mod ratelimit;
+pub mod redact;
+#[cfg(feature = "full")]
+pub mod reload;
+#[cfg(feature = "full")]
+pub mod rng;
+#[cfg(feature = "full")]
+pub mod scanner;
+#[cfg(feature = "full")]
+pub mod screen;
+#[cfg(feature = "full")]
+pub mod selftest;
+pub mod session;
+#[cfg(feature = "full")]
+pub mod serial;
+#[cfg(feature = "full")]
+pub mod service;
+pub mod settings;
+#[cfg(feature = "full")]
+pub mod stats;
+#[cfg(feature = "full")]
+pub mod statusline;
+pub mod storage;
+#[cfg(feature = "full")]
+pub mod timeline;
+pub mod timerange;
+#[cfg(feature = "full")]
+pub mod top;
+pub mod transcript;
+#[cfg(feature = "full")]
+pub mod transport;
+pub mod trigger;
+pub mod usage;
+#[cfg(feature = "ble")]
+pub mod wizard;
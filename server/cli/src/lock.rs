@@ -0,0 +1,210 @@
+use crate::error::{Classify, ExitCode};
+use crate::ipc::{self, IpcRequest, IpcResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How often a takeover checks whether the old daemon has let go of the lock
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Who holds the daemon lock
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+}
+
+impl std::fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pid {}, started {}", self.pid, self.started_at.format("%Y-%m-%d %H:%M:%S UTC"))
+    }
+}
+
+/// Why the daemon lock could not be taken
+#[derive(Debug, thiserror::Error)]
+pub enum LockError {
+    #[error("CCGadget daemon is already running ({0})")]
+    Held(LockInfo),
+    /// Held by a daemon whose details could not be read, as on Windows where the lock
+    /// keeps others from reading the file
+    #[error("CCGadget daemon is already running")]
+    HeldByUnknown,
+    #[error("The running daemon did not stop within {0} seconds")]
+    TakeoverTimedOut(u64),
+}
+
+impl Classify for LockError {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::Failure
+    }
+
+    fn hint(&self) -> Option<&str> {
+        Some(match self {
+            LockError::Held(_) | LockError::HeldByUnknown => "Replace it with `ccgadget start --takeover`",
+            LockError::TakeoverTimedOut(_) => "Stop it by hand, or try again once it has finished shutting down",
+        })
+    }
+}
+
+/// The lock file in the state directory, next to the daemon socket
+pub fn lock_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(crate::paths::state_dir()?.join("daemon.lock"))
+}
+
+/// Exclusive hold on the lock file for as long as the daemon runs. The operating system
+/// releases it when the process dies, however it dies, so a crashed daemon never blocks
+/// the next one; the file is emptied on a clean exit, and details left in an unlocked
+/// file mean the last daemon did not get to exit cleanly.
+#[derive(Debug)]
+pub struct DaemonLock {
+    file: File,
+}
+
+impl DaemonLock {
+    /// Take the lock at `path` for this process. Also returns the details of a daemon
+    /// that exited without releasing it, whose lock is recovered.
+    pub fn acquire(path: &Path) -> Result<(Self, Option<LockInfo>), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if let Some(holder) = try_lock(&mut file)? {
+            return Err(match holder {
+                Some(info) => LockError::Held(info).into(),
+                None => LockError::HeldByUnknown.into(),
+            });
+        }
+        let stale = read_info(&mut file);
+        let info = LockInfo { pid: std::process::id(), started_at: Utc::now() };
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(serde_json::to_string(&info)?.as_bytes())?;
+        file.sync_all()?;
+        Ok((Self { file }, stale))
+    }
+}
+
+impl Drop for DaemonLock {
+    fn drop(&mut self) {
+        // Empty means clean; the lock itself goes with the file handle
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Lock `file`, or say who holds it: `Some(None)` when its details cannot be read
+fn try_lock(file: &mut File) -> Result<Option<Option<LockInfo>>, Box<dyn Error>> {
+    match file.try_lock() {
+        Ok(()) => Ok(None),
+        Err(TryLockError::WouldBlock) => Ok(Some(read_info(file).or_else(running_daemon))),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Details written to the lock file, if it has any and they can be read
+fn read_info(file: &mut File) -> Option<LockInfo> {
+    let mut text = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut text).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Details of the daemon answering on the IPC endpoint
+fn running_daemon() -> Option<LockInfo> {
+    match ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+        Ok(IpcResponse::Status(status)) => Some(LockInfo { pid: status.pid, started_at: status.started_at }),
+        _ => None,
+    }
+}
+
+/// The daemon holding the lock at `path`, if one does, without disturbing the file;
+/// `Some(None)` when one does but its details cannot be read
+fn holder(path: &Path) -> Result<Option<Option<LockInfo>>, Box<dyn Error>> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // Closing the file releases the lock if this took it
+    try_lock(&mut file)
+}
+
+/// Stop the running daemon, if there is one, and wait up to `timeout` for it to exit.
+/// It is asked over IPC to shut down as it would on Ctrl-C; one that does not answer
+/// is sent a termination signal. Returns the details of the daemon that was stopped.
+pub fn take_over(timeout: Duration) -> Result<Option<LockInfo>, Box<dyn Error>> {
+    let path = lock_path()?;
+    let Some(info) = holder(&path)? else {
+        return Ok(None);
+    };
+    let info = info.ok_or(LockError::HeldByUnknown)?;
+    if !matches!(ipc::request(&IpcRequest::Shutdown, Duration::from_secs(1)), Ok(IpcResponse::ShuttingDown)) {
+        terminate(info.pid)?;
+    }
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if holder(&path)?.is_none() {
+            return Ok(Some(info));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Err(LockError::TakeoverTimedOut(timeout.as_secs()).into())
+}
+
+/// Ask process `pid` to terminate
+fn terminate(pid: u32) -> Result<(), Box<dyn Error>> {
+    let pid = pid.to_string();
+    #[cfg(unix)]
+    let mut command = Command::new("kill");
+    #[cfg(unix)]
+    command.args(["-TERM", &pid]);
+    #[cfg(not(unix))]
+    let mut command = Command::new("taskkill");
+    #[cfg(not(unix))]
+    command.args(["/PID", &pid, "/F"]);
+    let status = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status()?;
+    if !status.success() {
+        return Err(format!("Could not stop the daemon (pid {})", pid).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_and_recovers_stale_details() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-daemon-lock-{}", std::process::id()));
+        let path = dir.join("daemon.lock");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let (lock, stale) = DaemonLock::acquire(&path).unwrap();
+        assert_eq!(stale, None);
+        let error = DaemonLock::acquire(&path).unwrap_err();
+        let Ok(LockError::Held(info)) = error.downcast::<LockError>().map(|e| *e) else {
+            panic!("lock taken twice");
+        };
+        assert_eq!(info.pid, std::process::id());
+        assert_eq!(holder(&path).unwrap(), Some(Some(info.clone())));
+
+        // A clean exit leaves nothing to recover
+        drop(lock);
+        assert_eq!(holder(&path).unwrap(), None);
+        let (lock, stale) = DaemonLock::acquire(&path).unwrap();
+        assert_eq!(stale, None);
+
+        // A crash leaves the details behind without the lock
+        drop(lock);
+        let crashed = LockInfo { pid: 4242, started_at: info.started_at };
+        std::fs::write(&path, serde_json::to_string(&crashed).unwrap()).unwrap();
+        assert_eq!(holder(&path).unwrap(), None);
+        let (_lock, stale) = DaemonLock::acquire(&path).unwrap();
+        assert_eq!(stale, Some(crashed));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
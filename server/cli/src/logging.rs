@@ -0,0 +1,163 @@
+use crate::config::Config;
+use crate::output;
+use crate::paths;
+use std::fs::{self, OpenOptions};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// File the daemon writes its log to, inside the logs directory
+pub const DAEMON_LOG: &str = "daemon.log";
+
+/// Levels selectable with `-q`/`-v`, quietest first
+const LEVELS: &[&str] = &["error", "warn", "info", "debug", "trace"];
+
+/// Verbosity flags as given on the command line, so a spawned daemon can inherit them
+static FORWARDED: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Global verbosity flags
+#[derive(clap::Args, Debug, Default)]
+pub struct LogArgs {
+    /// Log more detail (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log filter with per-module levels, e.g. `debug` or `ccgadget=info,btleplug=debug`
+    /// (overrides -v/-q and `logs.level`)
+    #[arg(long, global = true, value_name = "FILTER")]
+    pub log_level: Option<String>,
+}
+
+/// Filter directives for the flags: `--log-level`, then `logs.level`, then -v/-q
+/// relative to the default level (`info` for the daemon, `warn` otherwise)
+pub fn filter_directives(args: &LogArgs, configured: Option<&str>, daemon: bool) -> String {
+    if let Some(filter) = args.log_level.as_deref().or(configured) {
+        return filter.to_string();
+    }
+    let base = if daemon { 2 } else { 1 };
+    let level = if args.quiet { 0 } else { (base + args.verbose as usize).min(LEVELS.len() - 1) };
+    // Extra verbosity applies to ccgadget only; dependencies stay at warn unless named
+    if level > 1 {
+        format!("warn,ccgadget={}", LEVELS[level])
+    } else {
+        LEVELS[level].to_string()
+    }
+}
+
+/// Command-line form of the verbosity flags this process was started with
+pub fn forwarded_args() -> &'static [String] {
+    FORWARDED.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Flags that reproduce `args` on another command line
+fn to_args(args: &LogArgs) -> Vec<String> {
+    let mut forwarded = Vec::new();
+    if args.verbose > 0 {
+        forwarded.push(format!("-{}", "v".repeat(args.verbose as usize)));
+    }
+    if args.quiet {
+        forwarded.push("--quiet".to_string());
+    }
+    if let Some(filter) = &args.log_level {
+        forwarded.push(format!("--log-level={}", filter));
+    }
+    forwarded
+}
+
+/// Path of the daemon log file
+pub fn daemon_log_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(paths::logs_dir()?.join(DAEMON_LOG))
+}
+
+/// The logs directory, created if missing
+pub fn log_directory() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let log_dir = paths::logs_dir()?;
+    if !log_dir.exists() {
+        fs::create_dir_all(&log_dir)?;
+    }
+    Ok(log_dir)
+}
+
+/// Install the global subscriber. Commands log to stderr; the daemon logs to
+/// `daemon.log`, and to stderr as well when that is a terminal.
+pub fn init(args: &LogArgs, daemon: bool) {
+    output::set_quiet(args.quiet);
+    let _ = FORWARDED.set(to_args(args));
+    let configured = Config::load().ok().and_then(|config| config.logs.level);
+    let directives = filter_directives(args, configured.as_deref(), daemon);
+    let filter = EnvFilter::try_new(&directives).unwrap_or_else(|e| {
+        esay!("⚠️ Invalid log filter '{}': {}", directives, e);
+        let defaults = LogArgs { verbose: args.verbose, quiet: args.quiet, log_level: None };
+        EnvFilter::new(filter_directives(&defaults, None, daemon))
+    });
+
+    let file = if daemon {
+        let opened = daemon_log_path().and_then(|path| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Ok(OpenOptions::new().create(true).append(true).open(path)?)
+        });
+        match opened {
+            Ok(file) => Some(fmt::layer().with_ansi(false).with_writer(Mutex::new(file))),
+            Err(e) => {
+                esay!("⚠️ Could not open the daemon log: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    // A daemon without its log file still needs somewhere to write
+    let to_stderr = !daemon || file.is_none() || std::io::stderr().is_terminal();
+    let stderr = to_stderr.then(|| fmt::layer().with_writer(std::io::stderr));
+
+    let _ = tracing_subscriber::registry().with(filter).with(stderr).with(file).try_init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flags as parsed from the command line
+    fn args(verbose: u8, quiet: bool, log_level: Option<&str>) -> LogArgs {
+        LogArgs { verbose, quiet, log_level: log_level.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_filter_directives_precedence() {
+        assert_eq!(filter_directives(&args(0, false, None), None, false), "warn");
+        assert_eq!(filter_directives(&args(0, false, None), None, true), "warn,ccgadget=info");
+        assert_eq!(filter_directives(&args(2, false, None), None, false), "warn,ccgadget=debug");
+        assert_eq!(filter_directives(&args(5, false, None), None, true), "warn,ccgadget=trace");
+        assert_eq!(filter_directives(&args(0, true, None), None, true), "error");
+        assert_eq!(filter_directives(&args(1, false, None), Some("btleplug=debug"), true), "btleplug=debug");
+        assert_eq!(
+            filter_directives(&args(1, false, Some("ccgadget::daemon=trace")), Some("info"), true),
+            "ccgadget::daemon=trace"
+        );
+    }
+
+    #[test]
+    fn test_to_args_round_trips_flags() {
+        assert!(to_args(&args(0, false, None)).is_empty());
+        assert_eq!(to_args(&args(2, false, Some("btleplug=debug"))), vec!["-vv", "--log-level=btleplug=debug"]);
+        assert_eq!(to_args(&args(0, true, None)), vec!["--quiet"]);
+    }
+
+    #[test]
+    fn test_log_directory_creation() {
+        let result = log_directory();
+        assert!(result.is_ok());
+        let log_dir = result.unwrap();
+        assert!(log_dir.exists());
+        assert!(log_dir.is_dir());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::storage::EventStore;
+use crate::timerange::TimeRange;
+use chrono::{DateTime, Local, Utc};
+use serde_json::Value;
+use std::error::Error;
+
+/// Which trigger log entries to show
+#[derive(Debug, Default, Clone)]
+pub struct LogFilter {
+    /// Hook event name, matched case-insensitively
+    pub event: Option<String>,
+    /// Session id or a prefix of one
+    pub session: Option<String>,
+    /// Time window the entry's timestamp must fall in
+    pub range: TimeRange,
+}
+
+impl LogFilter {
+    /// Whether a parsed log entry passes the filter
+    pub fn matches(&self, entry: &Value) -> bool {
+        let input = &entry["hook_input"];
+        if let Some(event) = &self.event {
+            if !input["hook_event_name"].as_str().is_some_and(|name| name.eq_ignore_ascii_case(event)) {
+                return false;
+            }
+        }
+        if let Some(session) = &self.session {
+            if !input["session_id"].as_str().is_some_and(|id| id.starts_with(session.as_str())) {
+                return false;
+            }
+        }
+        if self.range != TimeRange::default() {
+            let timestamp = entry["timestamp"].as_str().and_then(|ts| ts.parse::<DateTime<Utc>>().ok());
+            if !timestamp.is_some_and(|timestamp| self.range.contains(timestamp)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// One-line, human-readable summary of a log entry
+pub fn format_entry(entry: &Value) -> String {
+    let input = &entry["hook_input"];
+    let time = entry["timestamp"]
+        .as_str()
+        .and_then(|ts| ts.parse::<DateTime<Utc>>().ok())
+        .map(|ts| ts.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".repeat(19));
+    let mut line = format!("{}  {:<17}", time, input["hook_event_name"].as_str().unwrap_or("-"));
+
+    if let Some(session) = input["session_id"].as_str() {
+        line.push_str(&format!("  [{}]", session.chars().take(8).collect::<String>()));
+    }
+    if let Some(tool) = input["tool_name"].as_str() {
+        line.push_str(&format!("  {}", tool));
+    }
+    // The most telling detail the event carries, on one line
+    let detail = ["command", "file_path", "pattern", "url"]
+        .iter()
+        .find_map(|field| input["tool_input"][field].as_str())
+        .or_else(|| input["message"].as_str())
+        .or_else(|| input["prompt"].as_str());
+    if let Some(detail) = detail {
+        let detail: String = detail.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut shown: String = detail.chars().take(80).collect();
+        if detail.chars().count() > 80 {
+            shown.push('…');
+        }
+        line.push_str(&format!("  {}", shown));
+    }
+    if let Some(cost) = entry["cost"]["session"]["cost_usd"].as_f64() {
+        line.push_str(&format!("  ${:.2} session", cost));
+    }
+    line
+}
+
+/// Print one raw entry if it matches, raw (`json`) or formatted; returns whether it did
+fn print_if_matching(line: &str, filter: &LogFilter, json: bool) -> bool {
+    let Ok(entry) = serde_json::from_str::<Value>(line) else {
+        return false;
+    };
+    if !filter.matches(&entry) {
+        return false;
+    }
+    if json {
+        println!("{}", line);
+    } else {
+        println!("{}", format_entry(&entry));
+    }
+    true
+}
+
+/// Print the matching stored entries; returns how many were shown
+pub fn show(store: &dyn EventStore, filter: &LogFilter, json: bool) -> Result<usize, Box<dyn Error>> {
+    let entries = store.read(&filter.range)?;
+    Ok(entries.iter().filter(|line| print_if_matching(line, filter, json)).count())
+}
+
+/// Print new matching entries as they are stored, until interrupted
+pub fn follow(store: &dyn EventStore, filter: &LogFilter, json: bool) -> Result<(), Box<dyn Error>> {
+    store.follow(&mut |line| {
+        print_if_matching(line, filter, json);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trigger log entry as written by `ccgadget trigger`
+    fn entry(timestamp: &str, event: &str, session: &str) -> Value {
+        serde_json::json!({
+            "timestamp": timestamp,
+            "hook_input": {
+                "hook_event_name": event,
+                "session_id": session,
+                "tool_name": "Bash",
+                "tool_input": {"command": "cargo   test\n--all"}
+            },
+            "cost": {"session": {"usage": {}, "cost_usd": 1.5}}
+        })
+    }
+
+    #[test]
+    fn test_filter_by_event_session_and_time() {
+        let logged = entry("2025-07-20T10:00:00Z", "PreToolUse", "abc123");
+        let since = |ts: &str| TimeRange { since: Some(ts.parse::<DateTime<Utc>>().unwrap()), until: None };
+
+        assert!(LogFilter::default().matches(&logged));
+        assert!(LogFilter { event: Some("pretooluse".into()), session: Some("abc".into()), range: since("2025-07-20T09:00:00Z") }.matches(&logged));
+        assert!(!LogFilter { event: Some("Stop".into()), ..Default::default() }.matches(&logged));
+        assert!(!LogFilter { session: Some("xyz".into()), ..Default::default() }.matches(&logged));
+        assert!(!LogFilter { range: since("2025-07-20T10:00:01Z"), ..Default::default() }.matches(&logged));
+    }
+
+    #[test]
+    fn test_format_entry_summarises_on_one_line() {
+        let line = format_entry(&entry("2025-07-20T10:00:00Z", "PreToolUse", "abc12345-6789"));
+        assert!(line.contains("PreToolUse"));
+        assert!(line.contains("[abc12345]"));
+        assert!(line.contains("Bash  cargo test --all"));
+        assert!(line.ends_with("$1.50 session"));
+    }
+}
@@ -1,14 +1,36 @@
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-use btleplug::platform::Manager;
+use clap::builder::PossibleValuesParser;
 use clap::{CommandFactory, Parser, Subcommand};
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local, Utc};
+#[cfg(feature = "ble")]
+use serde::Serialize;
 use std::fs::{self, OpenOptions};
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::time::sleep;
 
+use ccgadget_core::{
+    bluetooth, burn, clock, config, cost, daemon, device, diff, dnd, error, fault, focus, hooks, import, install, ipc, lock,
+    logging, logs, metrics, metrics_db, output, paths, payload, pricing, redact, scanner, screen, selftest, service, settings, stats, statusline, storage, timeline,
+    timerange, top, trigger, usage,
+};
+use ccgadget_core::{esay, say, say_inline};
+
+use error::ExitCode;
+use hooks::{HookError, HookScope, HookStatus};
+use config::Config;
+use device::{AdapterChoice, DeviceError, PairedDevice, Registration};
+use ipc::{IpcRequest, IpcResponse, LinkState};
+use payload::DevicePayload;
+use pricing::PricingTable;
+use redact::Redactor;
+use scanner::DeviceFilter;
+use settings::{DeviceSettings, PushCheck};
+use timeline::TimelineFormat;
+use timerange::TimeRange;
+use usage::{GroupBy, UsageFormat};
+use std::collections::BTreeMap;
+
 #[derive(Parser)]
 #[command(name = "ccgadget")]
 #[command(version = "0.1.0")]
@@ -17,12 +39,57 @@ use tokio::time::sleep;
 real-time Claude Code usage metrics. This CLI tool manages device pairing, data transmission, \
 and integration with Claude Code hooks.")]
 struct Cli {
+    /// Plain, screen-reader-friendly output (no emoji or screen redraws)
+    #[arg(long, global = true, env = "CCGADGET_ACCESSIBLE")]
+    accessible: bool,
+
+    /// Result format: human-readable text, or JSON on stdout for scripts (pair, status,
+    /// statusline, usage, stats, setup-hook status, logs, paths)
+    #[arg(long, global = true, value_enum, default_value_t = output::OutputFormat::Text)]
+    output: output::OutputFormat,
+
+    /// Config profile to use (a `[profiles.<name>]` table in config.toml)
+    #[arg(long, global = true, env = "CCGADGET_PROFILE")]
+    profile: Option<String>,
+
+    #[command(flatten)]
+    log: logging::LogArgs,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// First-run setup: choose a profile, pair a device, set the update interval, and
+    /// install Claude Code hooks
+    Init {
+        /// Update interval in seconds, instead of asking
+        #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
+        /// Where to install hooks, instead of asking
+        #[arg(short, long)]
+        scope: Option<HookScope>,
+    },
+    /// List nearby CCGadget devices (or every BLE device with --all)
+    Scan {
+        /// How long to scan, in seconds
+        #[arg(short, long, default_value_t = scanner::DEFAULT_SCAN_SECONDS)]
+        duration: u64,
+        /// List every BLE device, not only CCGadgets
+        #[arg(short, long)]
+        all: bool,
+        /// Also list devices whose name looks like a CCGadget but that do not
+        /// advertise the CCGadget service
+        #[arg(short, long, conflicts_with = "all")]
+        loose: bool,
+        /// Bluetooth adapter to scan with, by index or name [default: device.adapter]
+        #[arg(long, value_name = "INDEX|NAME")]
+        adapter: Option<AdapterChoice>,
+        /// List this machine's Bluetooth adapters instead of scanning
+        #[arg(long, conflicts_with_all = ["duration", "all", "loose", "adapter"])]
+        adapters: bool,
+    },
     /// Pair with CCGadget device via Bluetooth
     Pair {
         /// Device name or address to pair with
@@ -31,21 +98,94 @@ enum Commands {
         /// Force pairing even if already paired
         #[arg(short, long)]
         force: bool,
+        /// Give up if no device advertises within this many seconds
+        #[arg(short, long, default_value_t = scanner::DEFAULT_PAIR_TIMEOUT_SECONDS)]
+        timeout: u64,
+        /// Also offer devices whose name looks like a CCGadget but that do not
+        /// advertise the CCGadget service
+        #[arg(short, long)]
+        loose: bool,
+        /// Bluetooth adapter to pair through, by index or name; saved as device.adapter
+        #[arg(long, value_name = "INDEX|NAME")]
+        adapter: Option<AdapterChoice>,
+        /// Add the device to those the daemon mirrors frames to, keeping the paired one
+        #[arg(long)]
+        add: bool,
+        /// Put the added device in this group, for `broadcast.group`
+        #[arg(long, requires = "add")]
+        group: Option<String>,
     },
     /// Start background daemon to monitor and transmit usage data
     Start {
         /// Run in foreground mode (don't daemonize)
         #[arg(short, long)]
         foreground: bool,
-        /// Update interval in seconds
-        #[arg(short, long, default_value = "30")]
-        interval: u64,
+        /// Update interval in seconds [default: daemon.interval_seconds, 30]
+        #[arg(short, long)]
+        interval: Option<u64>,
+        /// Block until the device link is established (for service managers)
+        #[arg(long, conflicts_with = "foreground")]
+        wait_ready: bool,
+        /// Seconds to wait for readiness with --wait-ready
+        #[arg(long, default_value = "60", requires = "wait_ready")]
+        timeout: u64,
+        /// Process and aggregate events locally without sending anything to a device
+        /// or off this machine (also `daemon.observe_only` in the config)
+        #[arg(long)]
+        observe_only: bool,
+        /// Reach the device over this transport instead of `device.transport`
+        #[arg(long, value_enum)]
+        transport: Option<device::TransportKind>,
+        /// Bluetooth adapter to connect with instead of `device.adapter`
+        #[arg(long, value_name = "INDEX|NAME")]
+        adapter: Option<AdapterChoice>,
+        /// Randomly drop/delay/duplicate/corrupt device frames (protocol hardening)
+        #[arg(long, hide = true, value_name = "SPEC")]
+        fault_inject: Option<String>,
+        /// Shut down a daemon that is already running and replace it
+        #[arg(long)]
+        takeover: bool,
     },
+    /// Show whether the daemon is running and the state of its device link
+    Status,
+    /// Print a one-line summary (model, session cost, block time left, device link) for
+    /// Claude Code's statusLine command or a tmux or starship segment
+    Statusline,
     /// Trigger immediate data transmission (for Claude Code hooks)
-    Trigger,
+    Trigger {
+        /// Show, log, and send the event without redacting secrets (for debugging hooks)
+        #[arg(long)]
+        no_redact: bool,
+        /// Do all the work before returning instead of handing it to a background
+        /// process (the default unless `trigger.detach` is off)
+        #[arg(long)]
+        wait: bool,
+        /// Run as the background process of a detaching trigger
+        #[arg(long, hide = true, conflicts_with = "wait")]
+        detached: bool,
+        /// Read newline-delimited hook events from stdin and process each in turn,
+        /// e.g. to replay recorded events
+        #[arg(long, conflicts_with_all = ["detached", "event"])]
+        batch: bool,
+        #[command(flatten)]
+        event: trigger::EventFlags,
+    },
+    /// Check the whole pipeline end to end: send a synthetic event through trigger,
+    /// daemon, and transport and report each stage (exits non-zero on failure)
+    SelfTest {
+        /// Seconds to wait for the device link and delivery
+        #[arg(long, default_value_t = 20)]
+        timeout: u64,
+        /// Test against the simulated device even if a device is paired
+        #[arg(long)]
+        simulator: bool,
+    },
     /// Setup Claude Code hooks for automatic monitoring
+    #[command(args_conflicts_with_subcommands = true)]
     SetupHook {
-        /// Scope for hook installation (local or user)
+        #[command(subcommand)]
+        action: Option<HookCommand>,
+        /// Scope for hook installation (local, project, or user)
         #[arg(short, long, default_value = "local")]
         scope: HookScope,
         /// Force reinstall if hook already exists
@@ -54,78 +194,524 @@ enum Commands {
         /// Automatically approve adding hooks alongside existing ones
         #[arg(short, long)]
         yes: bool,
+        /// Remove every ccgadget hook from the scope's settings, keeping other hooks
+        #[arg(long, conflicts_with_all = ["force", "yes"])]
+        uninstall: bool,
+        /// Restore the scope's settings from a backup (default: the most recent)
+        #[arg(long, value_name = "BACKUP", num_args = 0..=1, conflicts_with_all = ["force", "yes", "uninstall"])]
+        restore_backup: Option<Option<PathBuf>>,
+        /// Only configure these events (comma-separated; default: all)
+        #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(hooks::HOOK_EVENTS))]
+        events: Vec<String>,
+        /// Print a diff of the settings change instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+        /// Tool matcher for the ccgadget hook, e.g. `Bash` or `Edit|Write` (applies to
+        /// PreToolUse/PostToolUse); repeat as EVENT=PATTERN to override one event
+        #[arg(long = "matcher", value_name = "[EVENT=]PATTERN", value_parser = hooks::parse_matcher)]
+        matchers: Vec<String>,
+    },
+    /// Show token usage and estimated cost from Claude Code transcripts
+    Usage {
+        /// Only include usage from this time on (e.g. 2025-07-01, yesterday, 2h ago)
+        #[arg(long, value_parser = timerange::parse_since)]
+        since: Option<DateTime<Utc>>,
+        /// Only include usage up to this time (a date includes that whole day)
+        #[arg(long, value_parser = timerange::parse_until)]
+        until: Option<DateTime<Utc>>,
+        /// How to group usage rows
+        #[arg(short, long, value_enum, default_value = "day")]
+        group_by: GroupBy,
+        /// Report layout; `ccusage` prints the JSON ccusage would for the same grouping
+        #[arg(long, value_enum, default_value = "table")]
+        format: UsageFormat,
+        /// Also push a summary frame to the device via the daemon
+        #[arg(long)]
+        push: bool,
+        /// Continuously show the active block's burn rate and limit projection
+        #[arg(long, conflicts_with_all = ["since", "until", "push", "format"])]
+        live: bool,
+    },
+    /// Live dashboard of sessions, burn rate, block countdown, device link, and hook
+    /// events, refreshed from the daemon
+    Top,
+    /// Import usage history exported from other Claude usage trackers
+    Import {
+        /// Export format
+        #[arg(long, value_enum)]
+        from: import::ImportSource,
+        /// Export file, or - for stdin
+        file: PathBuf,
+    },
+    /// Manage the paired device's link
+    Device {
+        #[command(subcommand)]
+        action: DeviceCommand,
+    },
+    /// Inspect what the device displays and sync its settings
+    Display {
+        #[command(subcommand)]
+        action: DisplayCommand,
+    },
+    /// Choose which of several concurrent sessions drives the device display
+    Session {
+        #[command(subcommand)]
+        action: SessionCommand,
+    },
+    /// Show or set do-not-disturb: no buzzing, and only critical frames reach the device
+    Dnd {
+        /// Turn it on or off; omit to show whether it is on
+        #[arg(value_enum)]
+        state: Option<Toggle>,
+        /// Turn it on for this long, e.g. 45m or 1h
+        #[arg(long = "for", value_name = "DURATION", value_parser = timerange::parse_for)]
+        until: Option<DateTime<Utc>>,
+    },
+    /// Show the trigger history recorded by hooks, optionally following new entries
+    Logs {
+        /// Keep printing new entries as they are logged
+        #[arg(short, long)]
+        follow: bool,
+        /// Only this hook event (e.g. PreToolUse)
+        #[arg(long)]
+        event: Option<String>,
+        /// Only this session (id or prefix)
+        #[arg(long)]
+        session: Option<String>,
+        /// Only entries from this time on (e.g. 2h ago, yesterday 9am, 2025-07-20)
+        #[arg(long, value_parser = timerange::parse_since)]
+        since: Option<DateTime<Utc>>,
+        /// Only entries before this time (a date includes that whole day)
+        #[arg(long, value_parser = timerange::parse_until, conflicts_with = "follow")]
+        until: Option<DateTime<Utc>>,
+        /// Print the raw JSON lines (for piping into jq)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Summarize the trigger history: events, busiest sessions, top tools, and error rates
+    Stats {
+        /// Only count events from this time on (e.g. 7d, yesterday, 2025-07-01)
+        #[arg(long, value_parser = timerange::parse_since, default_value = "7d")]
+        since: DateTime<Utc>,
+        /// Only count events before this time (a date includes that whole day)
+        #[arg(long, value_parser = timerange::parse_until)]
+        until: Option<DateTime<Utc>>,
+        /// How many sessions and tools to list
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Manage where trigger log entries are stored
+    Storage {
+        #[command(subcommand)]
+        action: StorageCommand,
+    },
+    /// Export session data for sharing or later review
+    Export {
+        #[command(subcommand)]
+        action: ExportCommand,
+    },
+    /// View and change settings in the config file (see `ccgadget paths`)
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Print every directory and file location ccgadget uses
+    Paths {
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Packaging helpers for Homebrew, WinGet, and other package managers
+    Install {
+        /// Install shell completions and the daemon service definition
+        #[arg(long, required = true)]
+        post: bool,
+        /// Skip shell completions
+        #[arg(long)]
+        no_completions: bool,
+        /// Skip the service definition
+        #[arg(long)]
+        no_service: bool,
+    },
+    /// Start the daemon at login as a per-user service (a launchd agent on macOS, a
+    /// systemd user unit on Linux)
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum HookCommand {
+    /// Report per-event hook installation across user, project, and local settings
+    /// (exits non-zero when hooks are missing or misconfigured)
+    Status {
+        /// Only check these events (comma-separated; default: all)
+        #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(hooks::HOOK_EVENTS))]
+        events: Vec<String>,
+        /// Expected tool matchers, as given to `setup-hook --matcher`
+        #[arg(long = "matcher", value_name = "[EVENT=]PATTERN", value_parser = hooks::parse_matcher)]
+        matchers: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective value of a key (e.g. device.link_mode) or section
+    Get {
+        key: String,
+    },
+    /// Set a key in the config file, keeping its comments and layout
+    Set {
+        key: String,
+        /// TOML value (number, boolean, array, quoted string); anything else is a string
+        value: String,
+    },
+    /// List every effective setting, including environment overrides
+    List,
+    /// Open the config file in $VISUAL or $EDITOR and validate it afterwards
+    Edit,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Subcommand)]
+enum DeviceCommand {
+    /// Show or set the link mode (low-latency or battery-saver)
+    Linkmode {
+        /// New link mode; omit to show the current one
+        #[arg(value_enum)]
+        mode: Option<device::LinkMode>,
+    },
+    /// Pause regular transmissions and show the maintenance screen (e.g. during OTA)
+    Maintenance {
+        #[arg(value_enum)]
+        state: Toggle,
+    },
+    /// Connect to the paired device and show its battery, firmware, and diagnostics
+    Info,
+    /// List the paired device and those added with `pair --add`
+    List,
+    /// Stop mirroring frames to a device added with `pair --add`
+    Remove {
+        /// Name or address of the added device
+        device: String,
+    },
+    /// Act as if a device button was pressed, running its `[buttons]` action
+    Press {
+        /// Button name, e.g. `a`
+        button: String,
+        /// A long press
+        #[arg(long)]
+        long: bool,
     },
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
-enum HookScope {
-    /// Install hooks at user level (~/.claude/settings.json)
-    User,
-    /// Install hooks at project local level (.claude/settings.local.json)
-    Local,
+#[derive(Subcommand)]
+enum DisplayCommand {
+    /// Render the configured widget layout with current metric values in the terminal
+    Preview {
+        /// Show this message in the status area
+        #[arg(long)]
+        message: Option<String>,
+    },
+    /// Read the display settings stored on the device (through the daemon)
+    Pull {
+        /// Also write them into the config file, replacing the local settings
+        #[arg(long)]
+        save: bool,
+    },
+    /// Send the configured display settings to the device
+    Push {
+        /// Print how the device's settings differ from the config instead of pushing
+        #[arg(long)]
+        diff: bool,
+        /// Push even if the settings were changed on the device since the last push
+        #[arg(long, conflicts_with = "diff")]
+        force: bool,
+    },
+    /// Show a short text on the device, e.g. from a script or CI job
+    Message {
+        /// Text to show (up to 120 characters)
+        text: String,
+        /// Seconds to keep it on screen
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        #[arg(long, value_enum, default_value_t = payload::Severity::Info)]
+        severity: payload::Severity,
+    },
+    /// Choose the device's page, brightness, and theme; saved to the config and pushed
+    #[command(group = clap::ArgGroup::new("changes").required(true).multiple(true))]
+    Set {
+        /// Screen shown by default
+        #[arg(long, value_enum, group = "changes")]
+        page: Option<config::DisplayPage>,
+        /// Backlight level, 0-100
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100), group = "changes")]
+        brightness: Option<u8>,
+        /// Light text on a dark background
+        #[arg(long, value_enum, group = "changes")]
+        dark_mode: Option<Toggle>,
+    },
 }
 
-#[derive(Debug)]
-enum HookSetupResult {
-    /// Hook was successfully added
-    Added,
-    /// Hook was skipped (user declined or other reason)
-    Skipped,
-    /// Hook already exists and no action was needed
-    AlreadyExists,
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// List active sessions and show which one the device is following
+    List,
+    /// Pin the display to a session, switch the focus policy, or move to the next session
+    Focus {
+        /// Session id (or a unique prefix) to pin
+        #[arg(required_unless_present_any = ["policy", "next"], conflicts_with_all = ["policy", "next"])]
+        id: Option<String>,
+        /// Focus policy until the daemon restarts (see `sessions.focus` to keep it)
+        #[arg(long, value_enum, conflicts_with = "next")]
+        policy: Option<focus::FocusPolicy>,
+        /// Pin the session after the focused one, like the device's focus button
+        #[arg(long)]
+        next: bool,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TriggerLogEntry {
-    timestamp: DateTime<Utc>,
-    hook_input: Option<HookInput>,
-    metadata: LogMetadata,
+#[derive(Subcommand)]
+enum StorageCommand {
+    /// Copy trigger log entries between backends, skipping ones already copied
+    Migrate {
+        /// Backend to copy from
+        #[arg(value_enum)]
+        from: storage::Backend,
+        /// Backend to copy to
+        #[arg(value_enum)]
+        to: storage::Backend,
+    },
+    /// Rebuild the metrics database behind `usage` from the logs and transcripts
+    Reindex,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct LogMetadata {
-    version: String,
-    source: String,
+#[derive(Subcommand)]
+enum ServiceCommand {
+    /// Write the service definition, load it, and check that the daemon comes up
+    Install,
+    /// Stop the service and remove its definition
+    Uninstall,
+    /// Show whether the service is installed and loaded, and how its daemon is doing
+    Status,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct HookInput {
-    // Common fields for all events
-    session_id: Option<String>,
-    transcript_path: Option<String>,
-    cwd: Option<String>,
-    hook_event_name: Option<String>,
-    
-    // UserPromptSubmit specific
-    prompt: Option<String>,
-    
-    // Notification specific
-    message: Option<String>,
-    
-    // PreToolUse specific
-    tool_name: Option<String>,
-    tool_input: Option<serde_json::Value>,
-    
-    // PostToolUse specific (includes tool_name and tool_input from PreToolUse)
-    tool_response: Option<serde_json::Value>,
+#[derive(Subcommand)]
+enum ExportCommand {
+    /// Timeline of one session's prompts, responses, and tool calls with durations and costs
+    Timeline {
+        /// Session id (or a unique prefix), or the path to a transcript file
+        #[arg(long)]
+        session: String,
+        /// Output format
+        #[arg(long, value_enum, default_value = "html")]
+        format: TimelineFormat,
+        /// File to write, or `-` for stdout [default: timeline-<session>.<format>]
+        #[arg(short = 'o', long)]
+        output_file: Option<PathBuf>,
+        /// Only events from this time on (e.g. 1h ago, today 9am)
+        #[arg(long, value_parser = timerange::parse_since)]
+        since: Option<DateTime<Utc>>,
+        /// Only events before this time
+        #[arg(long, value_parser = timerange::parse_until)]
+        until: Option<DateTime<Utc>>,
+    },
 }
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
+fn main() {
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        if e.use_stderr() {
+            ExitCode::Usage.exit()
+        }
+        // --help and --version
+        std::process::exit(0)
+    });
+    output::set_accessible(cli.accessible);
+    output::set_format(cli.output);
+    if let Some(profile) = &cli.profile {
+        config::set_profile(profile);
+    }
+    let daemon = matches!(cli.command, Some(Commands::Start { foreground: true, .. }));
+    logging::init(&cli.log, daemon);
+    match paths::migrate_legacy() {
+        Ok(moved) if !moved.is_empty() => {
+            esay!("📦 Moved {} item(s) from ~/.ccgadget to the XDG directories", moved.len());
+            esay!("   💡 Run `ccgadget paths` to see the new locations");
+        }
+        Ok(_) => {}
+        Err(e) => esay!("⚠️ Could not migrate ~/.ccgadget: {}", e),
+    }
+
+    match cli.command {
+        // Hooks run `trigger` on every event, so it starts neither the async runtime nor Bluetooth
+        Some(Commands::Trigger { no_redact, batch: true, .. }) => trigger::run_batch(!no_redact),
+        Some(Commands::Trigger { no_redact, wait, detached, event, .. }) => {
+            let mode = match (wait, detached) {
+                (_, true) => trigger::Mode::Worker,
+                (true, _) => trigger::Mode::Wait,
+                _ => trigger::Mode::Auto,
+            };
+            trigger::run(!no_redact, &event, mode)
+        }
+        _ => match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime.block_on(run(cli)),
+            Err(e) => {
+                esay!("❌ Could not start the async runtime: {}", e);
+                ExitCode::Failure.exit();
+            }
+        },
+    }
+}
 
+/// Run every command but `trigger`
+async fn run(cli: Cli) {
     match &cli.command {
-        Some(Commands::Pair { device, force }) => {
-            handle_pair(device.as_deref(), *force).await;
+        Some(Commands::Init { interval, scope }) => {
+            handle_init(*interval, *scope).await;
+        }
+        Some(Commands::Scan { adapters: true, .. }) => {
+            handle_list_adapters().await;
+        }
+        Some(Commands::Scan { duration, all, loose, adapter, adapters: false }) => {
+            let adapter = adapter.clone().or(Config::load_or_default().device.adapter);
+            handle_scan(*duration, DeviceFilter::from_flags(*all, *loose), adapter.as_ref()).await;
+        }
+        Some(Commands::Pair { device, force, timeout, loose, adapter, add, group }) => {
+            let filter = DeviceFilter::from_flags(false, *loose);
+            let registration = if *add { Registration::Added { group: group.clone() } } else { Registration::Primary };
+            handle_pair(device.as_deref(), *force, Duration::from_secs(*timeout), filter, adapter.as_ref(), &registration).await;
+        }
+        Some(Commands::Start { foreground, interval, observe_only, wait_ready, timeout, transport, adapter, fault_inject, takeover }) => {
+            // Environment overrides, so a background daemon inherits them too
+            if let Some(transport) = transport {
+                std::env::set_var("CCGADGET_DEVICE_TRANSPORT", transport.as_str());
+            }
+            if let Some(adapter) = adapter {
+                std::env::set_var("CCGADGET_DEVICE_ADAPTER", adapter.to_string());
+            }
+            let config = Config::load_or_default();
+            let interval = interval.unwrap_or(config.daemon.interval_seconds);
+            let observe_only = *observe_only || config.daemon.observe_only;
+            handle_start(*foreground, interval, observe_only, *wait_ready, *timeout, fault_inject.as_deref(), *takeover).await;
+        }
+        Some(Commands::Status) => {
+            handle_status();
+        }
+        Some(Commands::Statusline) => {
+            handle_statusline();
+        }
+        Some(Commands::SelfTest { timeout, simulator }) => {
+            handle_self_test(Duration::from_secs(*timeout), *simulator).await;
+        }
+        Some(Commands::Trigger { .. }) => unreachable!("trigger runs before the async runtime starts"),
+        Some(Commands::SetupHook { action, scope, force, yes, uninstall, restore_backup, events, dry_run, matchers }) => {
+            if let Some(HookCommand::Status { events, matchers }) = action {
+                handle_hook_status(events, matchers);
+            } else if let Some(backup) = restore_backup {
+                handle_restore_backup(scope, backup.as_deref(), *dry_run);
+            } else if *uninstall {
+                handle_uninstall_hooks(scope, events, *dry_run);
+            } else {
+                handle_setup_hook(scope, *force, *yes, events, matchers, *dry_run);
+            }
+        }
+        Some(Commands::Usage { since, until, group_by, format, push, live }) => {
+            if *live {
+                handle_usage_live().await;
+            } else {
+                handle_usage(&TimeRange { since: *since, until: *until }, *group_by, *format, *push);
+            }
+        }
+        Some(Commands::Top) => {
+            handle_top();
+        }
+        Some(Commands::Import { from, file }) => {
+            handle_import(*from, file);
+        }
+        Some(Commands::Device { action: DeviceCommand::Linkmode { mode } }) => {
+            handle_link_mode(*mode);
+        }
+        Some(Commands::Device { action: DeviceCommand::Maintenance { state } }) => {
+            handle_maintenance(*state == Toggle::On);
+        }
+        Some(Commands::Device { action: DeviceCommand::Press { button, long } }) => {
+            handle_press(button, *long);
         }
-        Some(Commands::Start { foreground, interval }) => {
-            handle_start(*foreground, *interval);
+        Some(Commands::Device { action: DeviceCommand::Info }) => {
+            handle_device_info().await;
         }
-        Some(Commands::Trigger) => {
-            handle_trigger();
+        Some(Commands::Device { action: DeviceCommand::List }) => {
+            handle_device_list();
         }
-        Some(Commands::SetupHook { scope, force, yes }) => {
-            handle_setup_hook(scope, *force, *yes);
+        Some(Commands::Device { action: DeviceCommand::Remove { device } }) => {
+            handle_device_remove(device);
+        }
+        Some(Commands::Display { action: DisplayCommand::Preview { message } }) => {
+            handle_display_preview(message.as_deref());
+        }
+        Some(Commands::Display { action: DisplayCommand::Pull { save } }) => {
+            handle_display_pull(*save);
+        }
+        Some(Commands::Display { action: DisplayCommand::Push { diff, force } }) => {
+            handle_display_push(*diff, *force);
+        }
+        Some(Commands::Display { action: DisplayCommand::Message { text, duration, severity } }) => {
+            handle_display_message(text, *duration, *severity);
+        }
+        Some(Commands::Display { action: DisplayCommand::Set { page, brightness, dark_mode } }) => {
+            handle_display_set(*page, *brightness, dark_mode.map(|toggle| toggle == Toggle::On));
+        }
+        Some(Commands::Dnd { state, until }) => {
+            handle_dnd(*state, *until);
+        }
+        Some(Commands::Session { action: SessionCommand::List }) => {
+            handle_session_focus(IpcRequest::Sessions);
+        }
+        Some(Commands::Session { action: SessionCommand::Focus { id, policy, next } }) => {
+            handle_session_focus(IpcRequest::Focus { session: id.clone(), policy: *policy, next: *next });
+        }
+        Some(Commands::Logs { follow, event, session, since, until, json }) => {
+            let range = TimeRange { since: *since, until: *until };
+            let filter = logs::LogFilter { event: event.clone(), session: session.clone(), range };
+            handle_logs(&filter, *follow, *json || output::json());
+        }
+        Some(Commands::Export { action: ExportCommand::Timeline { session, format, output_file, since, until } }) => {
+            let range = TimeRange { since: *since, until: *until };
+            handle_export_timeline(session, *format, output_file.as_deref(), &range);
+        }
+        Some(Commands::Stats { since, until, top }) => {
+            handle_stats(&TimeRange { since: Some(*since), until: *until }, *top);
+        }
+        Some(Commands::Storage { action: StorageCommand::Migrate { from, to } }) => {
+            handle_storage_migrate(*from, *to);
+        }
+        Some(Commands::Storage { action: StorageCommand::Reindex }) => {
+            handle_storage_reindex();
+        }
+        Some(Commands::Config { action }) => {
+            handle_config(action);
+        }
+        Some(Commands::Paths { json }) => {
+            handle_paths(*json || output::json());
+        }
+        Some(Commands::Install { post: _, no_completions, no_service }) => {
+            handle_post_install(!*no_completions, !*no_service);
+        }
+        Some(Commands::Service { action: ServiceCommand::Install }) => {
+            handle_service_install().await;
+        }
+        Some(Commands::Service { action: ServiceCommand::Uninstall }) => {
+            handle_service_uninstall();
+        }
+        Some(Commands::Service { action: ServiceCommand::Status }) => {
+            handle_service_status();
         }
         None => {
             // No subcommand provided, show help
@@ -136,1006 +722,1723 @@ async fn main() {
     }
 }
 
-/// Handle device pairing with Bluetooth scanning and user selection
-async fn handle_pair(device: Option<&str>, force: bool) {
-    println!("🔵 Pairing with CCGadget device...");
-    
-    if force {
-        println!("   Force pairing enabled");
+/// Ask a question on the terminal, returning `default` for an empty answer or at the
+/// end of input
+fn ask(question: &str, default: &str) -> io::Result<String> {
+    if default.is_empty() {
+        say_inline!("   {}: ", question);
+    } else {
+        say_inline!("   {} [{}]: ", question, default);
     }
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
 
-    // Check if we're in a test environment or don't have Bluetooth permissions
-    if std::env::var("CCGADGET_DEMO_MODE").is_ok() {
-        println!("   🔧 Running in demo/test mode - simulating pairing");
-        simulate_pairing(device).await;
-        return;
+/// Run first-run setup, exiting non-zero when a step fails
+async fn handle_init(interval: Option<u64>, scope: Option<HookScope>) {
+    if let Err(e) = init(interval, scope).await {
+        esay!("   ❌ Setup failed: {}", e);
+        error::exit(&*e);
     }
+}
 
-    println!("   💡 If this hangs or fails, use: CCGADGET_DEMO_MODE=1 ccgadget pair");
+/// Walk through profile, pairing, interval, and hook setup; later steps still run when
+/// no device was paired
+async fn init(interval: Option<u64>, scope: Option<HookScope>) -> Result<(), Box<dyn std::error::Error>> {
+    say!("👋 Setting up CCGadget");
 
-    if let Some(device_name) = device {
-        println!("   Target device: {}", device_name);
-        if let Err(e) = pair_with_device(device_name, force).await {
-            eprintln!("   ❌ Failed to pair with device: {}", e);
-            eprintln!("   💡 To test without Bluetooth: CCGADGET_DEMO_MODE=1 ccgadget pair");
-            std::process::exit(1);
-        }
-    } else {
-        println!("   Scanning for nearby Bluetooth devices...");
-        match scan_and_select_device().await {
-            Ok(Some(selected_device)) => {
-                println!("   Selected device: {}", selected_device);
-                if let Err(e) = pair_with_device(&selected_device, force).await {
-                    eprintln!("   ❌ Failed to pair with selected device: {}", e);
-                    std::process::exit(1);
-                }
-            }
-            Ok(None) => {
-                println!("   ℹ️ No device selected. Pairing cancelled.");
+    say!("\nStep 1 of 4: Profile");
+    match config::profile() {
+        Some(profile) => say!("   Using profile '{}' from --profile", profile),
+        None => loop {
+            let name = ask("Profile name, for separate devices and settings (blank for none)", "")?;
+            if name.is_empty() {
+                break;
             }
-            Err(e) => {
-                eprintln!("   ❌ Error during device scanning: {}", e);
-                eprintln!("   💡 To test without Bluetooth: CCGADGET_DEMO_MODE=1 ccgadget pair");
-                std::process::exit(1);
+            if config::is_valid_profile_name(&name) {
+                config::set_profile(&name);
+                break;
             }
-        }
+            say!("   ❌ Use letters, digits, '-' and '_' only");
+        },
     }
-}
 
-/// Check if a device name matches CCGadget patterns
-pub fn is_ccgadget_device(name: &str) -> bool {
-    let name_lower = name.to_lowercase();
-    name_lower.contains("ccgadget") || 
-    name_lower.starts_with("ccg-") ||
-    name_lower.contains("esp32-ccg")
-}
+    say!("\nStep 2 of 4: Device");
+    // Only this profile's own pairing counts; `load` would fall back to the default one
+    let paired = if PairedDevice::path()?.exists() { PairedDevice::load()? } else { None };
+    let pair_again = match &paired {
+        Some(device) => {
+            say!("   Already paired with {}", device.label());
+            ask("Pair a different device? (y/N)", "")?.eq_ignore_ascii_case("y")
+        }
+        None => true,
+    };
+    if pair_again {
+        let timeout = Duration::from_secs(scanner::DEFAULT_PAIR_TIMEOUT_SECONDS);
+        if !bluetooth::pair(None, false, timeout, DeviceFilter::Service, None, &Registration::Primary).await.paired {
+            say!("   💡 Pair later with: ccgadget pair");
+        }
+    }
 
-/// Simulate pairing for demo/test mode
-async fn simulate_pairing(device: Option<&str>) {
-    if let Some(device_name) = device {
-        println!("   🎯 Target device: {}", device_name);
-        println!("   🔍 Simulating Bluetooth scan...");
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        println!("   ✅ Found simulated device: {}", device_name);
-        println!("   🔗 Connecting to device...");
-        tokio::time::sleep(Duration::from_millis(300)).await;
-        println!("   📋 Discovering services... (2 service(s) found)");
-        println!("      - Service UUID: 12345678-1234-5678-9abc-123456789abc");
-        println!("      - Service UUID: 87654321-4321-8765-cba9-987654321abc");
-        println!("   ✅ Pairing completed successfully!");
-        println!("   ℹ️ Note: This was a simulated pairing for demo/testing purposes");
-    } else {
-        println!("   📡 Simulating device scan...");
-        tokio::time::sleep(Duration::from_millis(800)).await;
-        println!("   📱 Found 3 CCGadget device(s):");
-        println!("   1. CCGadget-Demo (AA:BB:CC:DD:EE:FF) - Signal: -45dBm");
-        println!("   2. CCG-Office (11:22:33:44:55:66) - Signal: -67dBm");
-        println!("   3. ESP32-CCG-Lab (99:88:77:66:55:44) - Signal: -72dBm");
-        println!("   0. Cancel");
-        println!("   ℹ️ Auto-selecting device 1 for demo");
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        println!("   🔗 Connecting to CCGadget-Demo...");
-        tokio::time::sleep(Duration::from_millis(300)).await;
-        println!("   ✅ Pairing completed successfully!");
-        println!("   ℹ️ Note: This was a simulated pairing for demo/testing purposes");
-    }
-}
-
-/// Scan for Bluetooth devices and let user select one
-async fn scan_and_select_device() -> Result<Option<String>, Box<dyn std::error::Error>> {
-    // Get the Bluetooth manager with timeout and better error handling
-    println!("   🔍 Initializing Bluetooth manager...");
-    let manager = match tokio::time::timeout(Duration::from_secs(5), Manager::new()).await {
-        Ok(Ok(manager)) => {
-            println!("   ✅ Bluetooth manager initialized");
-            manager
-        },
-        Ok(Err(e)) => {
-            eprintln!("   ❌ Failed to initialize Bluetooth manager: {}", e);
-            eprintln!("   💡 Possible solutions:");
-            eprintln!("      - Enable Bluetooth in System Settings");
-            eprintln!("      - Grant Bluetooth permission to Terminal/CLI in Privacy & Security settings");
-            eprintln!("      - Run: sudo xcode-select --install (if needed)");
-            return Err("Bluetooth initialization failed".into());
+    say!("\nStep 3 of 4: Update interval");
+    // A new profile has no table yet, so its settings are the base ones
+    let current = Config::load().unwrap_or_default().daemon.interval_seconds;
+    let interval = match interval {
+        Some(interval) => interval,
+        None => loop {
+            match ask("Seconds between usage updates", &current.to_string())?.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => break seconds,
+                _ => say!("   ❌ Enter a whole number of seconds"),
+            }
         },
-        Err(_) => {
-            eprintln!("   ❌ Bluetooth manager initialization timed out");
-            eprintln!("   💡 This may indicate:");
-            eprintln!("      - Bluetooth service is not running");
-            eprintln!("      - Permission issues (check Privacy & Security settings)");
-            eprintln!("      - Hardware compatibility issues");
-            return Err("Bluetooth timeout - check system settings and permissions".into());
-        }
     };
-    
-    // Get the first Bluetooth adapter
-    println!("   🔍 Finding Bluetooth adapters...");
-    let adapters = match tokio::time::timeout(Duration::from_secs(2), manager.adapters()).await {
-        Ok(Ok(adapters)) => adapters,
-        Ok(Err(e)) => {
-            eprintln!("   ❌ Failed to get Bluetooth adapters: {}", e);
-            eprintln!("   💡 This usually indicates permission or hardware issues");
-            return Err("Bluetooth adapter access failed".into());
+    // Written even when unchanged, so a new profile gets its table
+    let path = Config::set_value("daemon.interval_seconds", (interval as i64).into())?;
+    say!("   ✅ Updates every {}s ({})", interval, path.display());
+
+    say!("\nStep 4 of 4: Claude Code hooks");
+    let scope = match scope {
+        Some(scope) => scope,
+        None => loop {
+            let answer = ask("Install for [u]ser (every project), [p]roject, or [l]ocal", "u")?;
+            match answer.to_lowercase().as_str() {
+                "u" | "user" => break HookScope::User,
+                "p" | "project" => break HookScope::Project,
+                "l" | "local" => break HookScope::Local,
+                _ => say!("   ❌ Enter u, p, or l"),
+            }
         },
-        Err(_) => {
-            eprintln!("   ❌ Bluetooth adapter detection timed out");
-            eprintln!("   💡 Bluetooth adapters are taking too long to respond");
-            eprintln!("      This often means permission issues or system Bluetooth problems");
-            return Err("Bluetooth adapter timeout".into());
+    };
+    say!("   ✅ {}", hooks::setup_claude_hooks(&scope, false, false, &[], &[], false)?);
+
+    let profile = config::profile().map(|profile| format!("--profile {} ", profile)).unwrap_or_default();
+    say!("\n🎉 Setup complete. Next:");
+    say!("   ccgadget {}start        # run the daemon", profile);
+    say!("   ccgadget {}self-test    # check the whole pipeline", profile);
+    Ok(())
+}
+
+/// Adapter row printed by `scan --adapters`
+#[cfg(feature = "ble")]
+#[derive(Serialize, Debug)]
+struct AdapterListing {
+    index: usize,
+    name: String,
+    /// Chosen by `device.adapter` (or first, when unset)
+    selected: bool,
+}
+
+/// List the Bluetooth adapters `--adapter` can choose from
+#[cfg(feature = "ble")]
+async fn handle_list_adapters() {
+    let adapters = match scanner::list_adapters().await {
+        Ok(adapters) => adapters,
+        Err(e) => {
+            esay!("❌ Could not list Bluetooth adapters: {}", e);
+            error::exit(&*e);
         }
     };
-    
-    let central = adapters
+    let configured = Config::load_or_default().device.adapter;
+    let names = scanner::describe_adapters(&adapters).await;
+    let selected = configured.as_ref().map_or(Some(0), |choice| choice.position(&names));
+    let listing: Vec<AdapterListing> = names
         .into_iter()
-        .next()
-        .ok_or("No Bluetooth adapter found. \n   💡 Check if Bluetooth hardware is available and enabled.")?;
-    
-    println!("   ✅ Bluetooth adapter found");
-    
-    println!("   📡 Starting Bluetooth scan (10 seconds)...");
-    
-    // Start scanning
-    central.start_scan(ScanFilter::default()).await?;
-    
-    // Scan for 10 seconds
-    sleep(Duration::from_secs(10)).await;
-    
-    // Stop scanning
-    central.stop_scan().await?;
-    
-    // Get discovered peripherals
-    let peripherals = central.peripherals().await?;
-    
-    if peripherals.is_empty() {
-        println!("   ⚠️ No Bluetooth devices found");
-        return Ok(None);
-    }
-    
-    // Collect CCGadget device information (filtered)
-    let mut devices = Vec::new();
-    for peripheral in peripherals {
-        let properties = peripheral.properties().await?;
-        if let Some(props) = properties {
-            let name = props.local_name.unwrap_or_else(|| "Unknown Device".to_string());
-            
-            // Filter: only include CCGadget devices
-            if is_ccgadget_device(&name) {
-                let address = props.address.to_string();
-                let rssi = props.rssi.map(|r| format!("{}dBm", r)).unwrap_or_else(|| "N/A".to_string());
-                devices.push((name, address, rssi));
-            }
-        }
+        .enumerate()
+        .map(|(index, name)| AdapterListing { index, name, selected: selected == Some(index) })
+        .collect();
+    if output::json() {
+        output::emit(&listing);
+        return;
     }
-    
-    if devices.is_empty() {
-        println!("   ⚠️ No CCGadget devices found");
-        println!("   💡 Make sure your CCGadget device is:");
-        println!("      - Powered on and in pairing mode");
-        println!("      - Within Bluetooth range (10 meters)");
-        println!("      - Named with 'CCGadget', 'CCG-', or 'ESP32-CCG' prefix");
-        return Ok(None);
+    if listing.is_empty() {
+        say!("⚠️ No Bluetooth adapters found");
+        return;
     }
-    
-    // Display found CCGadget devices
-    println!("   📱 Found {} CCGadget device(s):", devices.len());
-    for (i, (name, address, rssi)) in devices.iter().enumerate() {
-        println!("   {}. {} ({}) - Signal: {}", i + 1, name, address, rssi);
+    for adapter in &listing {
+        say!("{} {}  {}", if adapter.selected { "▶" } else { " " }, adapter.index, adapter.name);
     }
-    println!("   0. Cancel");
-    
-    // Get user selection
-    loop {
-        print!("   Select a device to pair with (0-{}): ", devices.len());
-        io::stdout().flush()?;
-        
-        let stdin = io::stdin();
-        let mut line = String::new();
-        stdin.read_line(&mut line)?;
-        
-        match line.trim().parse::<usize>() {
-            Ok(0) => {
-                return Ok(None);
-            }
-            Ok(selection) if selection <= devices.len() => {
-                let selected = &devices[selection - 1];
-                return Ok(Some(selected.1.clone())); // Return the address
-            }
-            _ => {
-                println!("   ❌ Invalid selection. Please try again.");
-                continue;
-            }
-        }
+    if let (Some(choice), None) = (&configured, selected) {
+        esay!("⚠️ device.adapter is '{}', which matches none of these", choice);
     }
 }
 
-/// Attempt to pair with a specific device
-async fn pair_with_device(device_identifier: &str, _force: bool) -> Result<(), Box<dyn std::error::Error>> {
-    println!("   🔗 Attempting to pair with device: {}", device_identifier);
-    
-    // Get the Bluetooth manager with timeout and better error handling
-    println!("   🔍 Initializing Bluetooth manager...");
-    let manager = match tokio::time::timeout(Duration::from_secs(5), Manager::new()).await {
-        Ok(Ok(manager)) => {
-            println!("   ✅ Bluetooth manager initialized");
-            manager
-        },
-        Ok(Err(e)) => {
-            eprintln!("   ❌ Failed to initialize Bluetooth manager: {}", e);
-            eprintln!("   💡 Possible solutions:");
-            eprintln!("      - Enable Bluetooth in System Settings");
-            eprintln!("      - Grant Bluetooth permission to Terminal/CLI in Privacy & Security settings");
-            eprintln!("      - Run: sudo xcode-select --install (if needed)");
-            return Err("Bluetooth initialization failed".into());
-        },
-        Err(_) => {
-            eprintln!("   ❌ Bluetooth manager initialization timed out");
-            eprintln!("   💡 This may indicate:");
-            eprintln!("      - Bluetooth service is not running");
-            eprintln!("      - Permission issues (check Privacy & Security settings)");
-            eprintln!("      - Hardware compatibility issues");
-            return Err("Bluetooth timeout - check system settings and permissions".into());
-        }
-    };
-    
-    // Get the first Bluetooth adapter
-    println!("   🔍 Finding Bluetooth adapters...");
-    let adapters = match tokio::time::timeout(Duration::from_secs(2), manager.adapters()).await {
-        Ok(Ok(adapters)) => adapters,
-        Ok(Err(e)) => {
-            eprintln!("   ❌ Failed to get Bluetooth adapters: {}", e);
-            eprintln!("   💡 This usually indicates permission or hardware issues");
-            return Err("Bluetooth adapter access failed".into());
-        },
-        Err(_) => {
-            eprintln!("   ❌ Bluetooth adapter detection timed out");
-            eprintln!("   💡 Bluetooth adapters are taking too long to respond");
-            eprintln!("      This often means permission issues or system Bluetooth problems");
-            return Err("Bluetooth adapter timeout".into());
+#[cfg(not(feature = "ble"))]
+async fn handle_list_adapters() {
+    bluetooth::unsupported()
+}
+
+/// List nearby devices as a table, or as a JSON array with `--output json`
+async fn handle_scan(duration: u64, filter: DeviceFilter, adapter: Option<&AdapterChoice>) {
+    let devices = match scanner::scan(Duration::from_secs(duration), filter, adapter).await {
+        Ok(devices) => devices,
+        Err(e) => {
+            esay!("❌ Scan failed: {}", e);
+            esay!("💡 To test without Bluetooth: CCGADGET_DEMO_MODE=1 ccgadget scan");
+            error::exit(&*e);
         }
     };
-    
-    let central = adapters
-        .into_iter()
-        .next()
-        .ok_or("No Bluetooth adapter found. \n   💡 Check if Bluetooth hardware is available and enabled.")?;
-    
-    println!("   ✅ Bluetooth adapter found");
-    
-    // Start scanning to find the device
-    println!("   📡 Scanning for target device...");
-    central.start_scan(ScanFilter::default()).await?;
-    
-    // Scan for up to 15 seconds to find the target device
-    let mut found_peripheral = None;
-    for _ in 0..15 {
-        sleep(Duration::from_secs(1)).await;
-        
-        let peripherals = central.peripherals().await?;
-        for peripheral in peripherals {
-            let properties = peripheral.properties().await?;
-            if let Some(props) = properties {
-                let address = props.address.to_string();
-                let name = props.local_name.unwrap_or_default();
-                
-                // Match by address or name
-                if address.eq_ignore_ascii_case(device_identifier) ||
-                   name.eq_ignore_ascii_case(device_identifier) {
-                    found_peripheral = Some(peripheral);
-                    break;
-                }
-            }
-        }
-        
-        if found_peripheral.is_some() {
-            break;
+    if output::json() {
+        output::emit(&devices);
+        return;
+    }
+    if devices.is_empty() {
+        let kind = if filter == DeviceFilter::All { "Bluetooth" } else { "CCGadget" };
+        say!("⚠️ No {} devices found in {}s", kind, duration);
+        if filter == DeviceFilter::Service {
+            say!("💡 Devices that do not advertise the CCGadget service are listed with --loose");
         }
+        return;
     }
-    
-    central.stop_scan().await?;
-    
-    let peripheral = found_peripheral
-        .ok_or_else(|| format!("Device '{}' not found", device_identifier))?;
-    
-    println!("   ✅ Found target device, attempting connection...");
-    
-    // Connect to the device
-    peripheral.connect().await?;
-    println!("   🎉 Successfully connected to device!");
-    
-    // Discover services
-    peripheral.discover_services().await?;
-    let services = peripheral.services();
-    
-    println!("   📋 Device services discovered: {} service(s)", services.len());
-    for service in services {
-        println!("      - Service UUID: {}", service.uuid);
+
+    let width = devices.iter().map(|device| device.display_name().chars().count()).max().unwrap_or(0).max(4);
+    let listed = |items: &[String]| if items.is_empty() { "-".to_string() } else { items.join(", ") };
+    let capability_width = devices.iter().map(|device| listed(&device.capabilities).chars().count()).max().unwrap_or(0).max(12);
+    say!(
+        "{:<width$}  {:<17}  {:>7}  {:<8}  {:<capability_width$}  SERVICES",
+        "NAME", "ADDRESS", "RSSI", "FIRMWARE", "CAPABILITIES"
+    );
+    for device in &devices {
+        say!(
+            "{:<width$}  {:<17}  {:>7}  {:<8}  {:<capability_width$}  {}",
+            device.display_name(),
+            device.address,
+            device.signal(),
+            device.firmware.as_deref().unwrap_or("-"),
+            listed(&device.capabilities),
+            listed(&device.services)
+        );
     }
-    
-    // For now, just disconnect after discovery
-    // In a real implementation, you'd establish the pairing here
-    peripheral.disconnect().await?;
-    
-    println!("   ✅ Pairing completed successfully!");
-    Ok(())
 }
 
-fn handle_start(foreground: bool, interval: u64) {
-    println!("🚀 Starting CCGadget monitoring daemon...");
-    println!("   Mode: {}", if foreground { "Foreground" } else { "Background" });
-    println!("   Update interval: {}s", interval);
-    println!("   Status: Not yet implemented");
+/// Handle device pairing with Bluetooth scanning and user selection
+async fn handle_pair(
+    device: Option<&str>,
+    force: bool,
+    timeout: Duration,
+    filter: DeviceFilter,
+    adapter: Option<&AdapterChoice>,
+    registration: &Registration,
+) {
+    let result = bluetooth::pair(device, force, timeout, filter, adapter, registration).await;
+    if output::json() {
+        output::emit(&result);
+    }
 }
 
-fn handle_trigger() {
-    println!("⚡ Triggering immediate data transmission...");
-    
-    // Read hook input from stdin
-    let hook_input = read_hook_input_from_stdin();
-    
-    // Log the payload for debugging
-    match log_trigger_payload(hook_input.as_ref()) {
-        Ok(log_path) => {
-            println!("   ✅ Payload logged to: {}", log_path.display());
-        }
+
+/// Longest wait for a running daemon to exit on `start --takeover`
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Start the daemon in the foreground, or spawn it in the background
+async fn handle_start(
+    foreground: bool,
+    interval: u64,
+    observe_only: bool,
+    wait_ready: bool,
+    timeout: u64,
+    fault_inject: Option<&str>,
+    takeover: bool,
+) {
+    say!("🚀 Starting CCGadget monitoring daemon...");
+    say!("   Mode: {}", if foreground { "Foreground" } else { "Background" });
+    say!("   Update interval: {}s", interval);
+    if observe_only {
+        say!("   👁️ Observe-only: events stay on this machine");
+    }
+
+    let faults = match fault_inject.map(fault::FaultConfig::parse).transpose() {
+        Ok(faults) => faults,
         Err(e) => {
-            eprintln!("   ❌ Failed to log payload: {}", e);
+            esay!("   ❌ Invalid --fault-inject spec: {}", e);
+            ExitCode::Failure.exit();
         }
+    };
+    if let Some(faults) = &faults {
+        say!("   🧪 Fault injection enabled: {:?}", faults);
     }
-    if let Some(ref hook_data) = hook_input {
-        println!("   Hook Event: {:?}", hook_data.hook_event_name);
-        if let Some(ref session_id) = hook_data.session_id {
-            println!("   Session ID: {}", session_id);
-        }
-        if let Some(ref cwd) = hook_data.cwd {
-            println!("   Working Directory: {}", cwd);
-        }
-        
-        // Event-specific data
-        match hook_data.hook_event_name.as_deref() {
-            Some("UserPromptSubmit") => {
-                if let Some(ref prompt) = hook_data.prompt {
-                    println!("   Prompt: {}", prompt);
-                }
-            }
-            Some("Notification") => {
-                if let Some(ref message) = hook_data.message {
-                    println!("   Message: {}", message);
-                }
-            }
-            Some("PreToolUse") => {
-                if let Some(ref tool_name) = hook_data.tool_name {
-                    println!("   Tool: {}", tool_name);
-                }
-                if let Some(ref tool_input) = hook_data.tool_input {
-                    println!("   Tool Input: {}", serde_json::to_string_pretty(tool_input).unwrap_or_default());
-                }
-            }
-            Some("PostToolUse") => {
-                if let Some(ref tool_name) = hook_data.tool_name {
-                    println!("   Tool: {}", tool_name);
-                }
-                if let Some(ref tool_response) = hook_data.tool_response {
-                    println!("   Tool Response: {}", serde_json::to_string_pretty(tool_response).unwrap_or_default());
-                }
+
+    if takeover {
+        match lock::take_over(TAKEOVER_TIMEOUT) {
+            Ok(Some(previous)) => say!("   🔁 Stopped the running daemon ({})", previous),
+            Ok(None) => say!("   ℹ️ No daemon was running"),
+            Err(e) => {
+                esay!("   ❌ Could not take over: {}", e);
+                error::exit(&*e);
             }
-            _ => {}
         }
     }
-    println!("   Status: Payload logged for debugging");
-}
 
-fn get_log_directory() -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let home_dir = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .map_err(|_| "Could not determine home directory")?;
-    
-    let log_dir = PathBuf::from(home_dir).join(".ccgadget").join("logs");
-    
-    if !log_dir.exists() {
-        fs::create_dir_all(&log_dir)?;
+    if foreground {
+        if let Err(e) = daemon::run(interval, faults, observe_only).await {
+            esay!("   ❌ Daemon failed: {}", e);
+            error::exit(&*e);
+        }
+        return;
     }
-    
-    Ok(log_dir)
-}
-
-fn read_hook_input_from_stdin() -> Option<HookInput> {
-    let mut buffer = String::new();
-    match io::stdin().read_to_string(&mut buffer) {
-        Ok(_) if !buffer.trim().is_empty() => {
-            match serde_json::from_str::<HookInput>(&buffer) {
-                Ok(hook_input) => Some(hook_input),
-                Err(e) => {
-                    eprintln!("   ⚠️ Failed to parse hook input: {}", e);
-                    None
-                }
+
+    if let Ok(IpcResponse::Status(status)) = ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+        say!("   ℹ️ Daemon already running (pid {}, {}); replace it with --takeover", status.pid, status.state);
+        if observe_only && status.state != LinkState::Observing {
+            esay!("   ⚠️ The running daemon is not in observe-only mode; stop it and start again");
+        }
+    } else {
+        match spawn_background_daemon(interval, observe_only, fault_inject) {
+            Ok(pid) => say!("   ✅ Daemon started in background (pid {})", pid),
+            Err(e) => {
+                esay!("   ❌ Failed to start daemon: {}", e);
+                error::exit(&*e);
             }
         }
-        _ => None,
+    }
+
+    if wait_ready {
+        if let Err(e) = wait_for_daemon_ready(Duration::from_secs(timeout)).await {
+            esay!("   ❌ {}", e);
+            error::exit(&*e);
+        }
+        if observe_only {
+            say!("   👁️ Daemon ready (observe-only)");
+        } else {
+            say!("   🔗 Device link established");
+        }
     }
 }
 
-fn log_trigger_payload(hook_input: Option<&HookInput>) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let log_dir = get_log_directory()?;
-    
-    // Create daily log file
-    let now = Utc::now();
-    let date_str = now.format("%Y-%m-%d");
-    let log_file_path = log_dir.join(format!("trigger-{}.log", date_str));
-    
-    // Create log entry
-    let log_entry = TriggerLogEntry {
-        timestamp: now,
-        hook_input: hook_input.cloned(),
-        metadata: LogMetadata {
-            version: "0.1.0".to_string(),
-            source: "ccgadget-cli".to_string(),
-        },
-    };
-    
-    // Serialize to JSON
-    let json_line = serde_json::to_string(&log_entry)?;
-    
-    // Append to log file
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_file_path)?;
-    
-    writeln!(file, "{}", json_line)?;
-    
-    Ok(log_file_path)
+/// Re-launch this binary as a detached foreground daemon, logging to the log directory
+fn spawn_background_daemon(
+    interval: u64,
+    observe_only: bool,
+    fault_inject: Option<&str>,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    let log_path = logging::log_directory()?.join(logging::DAEMON_LOG);
+    // The daemon writes its own log; this only catches panics and anything else on stderr
+    let log_file = OpenOptions::new().create(true).append(true).open(&log_path)?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(["start", "--foreground", "--interval", &interval.to_string()])
+        .args(observe_only.then_some("--observe-only"))
+        .args(config::profile().map(|profile| format!("--profile={}", profile)))
+        .args(logging::forwarded_args())
+        .args(fault_inject.map(|spec| format!("--fault-inject={}", spec)))
+        .stdin(std::process::Stdio::null())
+        .stdout(log_file.try_clone()?)
+        .stderr(log_file);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        command.creation_flags(DETACHED_PROCESS);
+    }
+
+    let child = command.spawn()?;
+    say!("   📝 Daemon output: {}", log_path.display());
+    Ok(child.id())
 }
 
-/// Setup Claude Code hooks by detecting settings files and configuring hooks
-fn setup_claude_hooks(scope: &HookScope, force: bool, auto_approve: bool) -> Result<String, Box<dyn std::error::Error>> {
-    // Find Claude settings file based on scope
-    let settings_path = find_claude_settings_file(scope)?;
-    println!("   📁 Found Claude settings: {}", settings_path.display());
-    
-    // Read existing settings
-    let mut settings = read_claude_settings(&settings_path)?;
-    
-    // Get all hooks to configure (setup all hooks by default)
-    let hooks_config = get_all_hooks_config();
-    
-    // Setup hooks in settings
-    let mut updated_hooks = 0;
-    let mut skipped_hooks = Vec::new();
-    
-    for (event_name, hook_command) in hooks_config {
-        match setup_hook_for_event(&mut settings, &event_name, &hook_command, force, auto_approve)? {
-            HookSetupResult::Added => {
-                updated_hooks += 1;
-            }
-            HookSetupResult::Skipped => {
-                skipped_hooks.push(event_name);
-            }
-            HookSetupResult::AlreadyExists => {
-                // Hook already exists, no action needed
+/// Poll the daemon over IPC until its device link is ready or the timeout expires
+async fn wait_for_daemon_ready(timeout: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    say!("   ⏳ Waiting up to {}s for device link...", timeout.as_secs());
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut last_state = None;
+
+    while tokio::time::Instant::now() < deadline {
+        if let Ok(IpcResponse::Status(status)) = ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+            match status.state {
+                LinkState::Ready | LinkState::Observing => return Ok(()),
+                LinkState::Unpaired => {
+                    return Err(DeviceError::NotPaired.into());
+                }
+                state => last_state = Some(state),
             }
         }
+        sleep(Duration::from_millis(250)).await;
     }
-    
-    if !skipped_hooks.is_empty() {
-        println!("   ℹ️ Skipped hooks for events: {}", skipped_hooks.join(", "));
+
+    match last_state {
+        Some(state) => Err(format!("Timed out waiting for device link (daemon state: {})", state).into()),
+        None => Err("Timed out waiting for daemon to start".into()),
     }
-    
-    // Write settings back to file
-    write_claude_settings(&settings_path, &settings)?;
-    
-    Ok(format!("Successfully configured {} hook(s) in {}", updated_hooks, settings_path.display()))
-}
-
-/// Find the appropriate Claude settings file based on scope
-fn find_claude_settings_file(scope: &HookScope) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    match scope {
-        HookScope::Local => {
-            // Use project-local settings
-            let project_local = PathBuf::from(".claude").join("settings.local.json");
-            
-            // Create .claude directory if it doesn't exist
-            let claude_dir = PathBuf::from(".claude");
-            if !claude_dir.exists() {
-                fs::create_dir_all(&claude_dir)?;
-            }
-            
-            // Create empty settings file if it doesn't exist
-            if !project_local.exists() {
-                let empty_settings = serde_json::json!({});
-                fs::write(&project_local, serde_json::to_string_pretty(&empty_settings)?)?;
-                println!("   📝 Created new local settings file: {}", project_local.display());
-            }
-            
-            Ok(project_local)
-        }
-        HookScope::User => {
-            // Use user settings
-            let home_dir = std::env::var("HOME")
-                .or_else(|_| std::env::var("USERPROFILE"))
-                .map_err(|_| "Could not determine home directory")?;
-            let home_path = PathBuf::from(&home_dir);
-            let user_settings = home_path.join(".claude").join("settings.json");
-            
-            // Create ~/.claude directory if it doesn't exist
-            let claude_dir = home_path.join(".claude");
-            if !claude_dir.exists() {
-                fs::create_dir_all(&claude_dir)?;
+}
+
+/// Print the running daemon's state; exits non-zero when no daemon answers
+/// Print the statusline from Claude Code's piped input and the daemon's state. Never
+/// fails, so a stopped daemon shows in the line instead of breaking it.
+fn handle_statusline() {
+    let input = statusline::read_input();
+    let status = match ipc::request(&IpcRequest::Status, statusline::DAEMON_TIMEOUT) {
+        Ok(IpcResponse::Status(status)) => Some(*status),
+        _ => None,
+    };
+    let line = statusline::Statusline::new(&input, status.as_ref(), Utc::now());
+    if output::json() {
+        output::emit(&line);
+    } else {
+        say!("{}", line.render());
+    }
+}
+
+fn handle_status() {
+    let status = match ipc::request(&IpcRequest::Status, Duration::from_secs(2)) {
+        Ok(IpcResponse::Status(status)) => *status,
+        Ok(other) => {
+            esay!("❌ Unexpected daemon response: {:?}", other);
+            ExitCode::Failure.exit();
+        }
+        Err(_) => {
+            if output::json() {
+                output::emit(&serde_json::json!({ "running": false }));
             }
-            
-            // Create empty settings file if it doesn't exist
-            if !user_settings.exists() {
-                let empty_settings = serde_json::json!({});
-                fs::write(&user_settings, serde_json::to_string_pretty(&empty_settings)?)?;
-                println!("   📝 Created new user settings file: {}", user_settings.display());
+            say!("ℹ️ Daemon not running; start it with `ccgadget start`");
+            ExitCode::DaemonNotRunning.exit();
+        }
+    };
+
+    // Older daemons do not report a monotonic uptime
+    let uptime = status
+        .uptime_secs
+        .map(|secs| secs as i64)
+        .unwrap_or_else(|| (Utc::now() - status.started_at).num_seconds().max(0));
+    if output::json() {
+        let mut report = serde_json::to_value(&status).unwrap_or_default();
+        report["running"] = true.into();
+        report["uptime_secs"] = uptime.into();
+        output::emit(&report);
+        return;
+    }
+    say!("🟢 Daemon running (pid {}, up {}m {}s)", status.pid, uptime / 60, uptime % 60);
+    say!("   Profile:   {}", status.profile.as_deref().unwrap_or("default"));
+    match (&status.transport, status.state) {
+        (Some(transport), LinkState::Ready) => match status.protocol {
+            Some(protocol) => say!("   Link:      {} over {} (protocol v{})", status.state, transport, protocol),
+            None => say!("   Link:      {} over {}", status.state, transport),
+        },
+        (_, LinkState::Disconnected) if status.retry_at.is_some() => {
+            let wait = status.retry_at.map(|at| (at - Utc::now()).num_seconds().max(0)).unwrap_or_default();
+            say!("   Link:      {} (retrying in {}s)", status.state, wait);
+        }
+        _ => say!("   Link:      {}", status.state),
+    }
+    if let Some(error) = status.link_error.as_deref().filter(|_| status.state == LinkState::Disconnected) {
+        say!("   Last error: {}", error);
+    }
+    if let Some(since) = status.offline_since {
+        let minutes = (Utc::now() - since).num_minutes().max(0);
+        say!("   Offline:   for {}m, since {} ({} queued)", minutes, since.with_timezone(&Local).format("%H:%M"), status.queued);
+    } else if status.missed_heartbeats > 0 {
+        say!("   Heartbeat: {} missed", status.missed_heartbeats);
+    }
+    match &status.device_status {
+        Some(device_status) => say!("   Device:    {} ({})", status.device.as_deref().unwrap_or("none"), device_status),
+        None => say!("   Device:    {}", status.device.as_deref().unwrap_or("none")),
+    }
+    for mirror in &status.mirrors {
+        say!("   Mirror:    {} ({}, {} sent, {} queued)", mirror.device, mirror.state, mirror.delivered, mirror.queued);
+    }
+    say!("   Link mode: {}", status.link_mode);
+    if status.maintenance {
+        say!("   Maintenance: on (regular transmissions paused)");
+    }
+    if status.focus_mode {
+        say!("   Focus mode: on");
+    }
+    if let Some(until) = status.alerts_muted_until.filter(|until| *until > Utc::now()) {
+        say!("   Alerts:    muted until {}", until.with_timezone(&Local).format("%H:%M"));
+    }
+    if status.tool_block_armed {
+        say!("   ⛔ The next tool call will be denied");
+    }
+    if let Some(dnd) = &status.dnd {
+        say!("   Do not disturb: {}", dnd);
+    }
+    if status.quiet_hours {
+        say!("   Quiet hours: on (device kept quiet)");
+    }
+    if let Some(since) = status.idle_since {
+        say!("   Presence:  idle since {} (no hook events)", since.with_timezone(&Local).format("%H:%M"));
+    }
+    if let Some(wait) = &status.awaiting_permission {
+        let ago = (Utc::now() - wait.since).num_seconds().max(0);
+        match &wait.tool {
+            Some(tool) => say!("   ⏸️ Claude is waiting for permission to use {} ({}s)", tool, ago),
+            None => say!("   ⏸️ Claude is waiting for permission ({}s)", ago),
+        }
+    }
+    if let Some(limit) = &status.limit {
+        match limit.countdown(Utc::now()) {
+            Some(countdown) => say!("   ⚠️ Claude hit its {}; resets in {}", limit.kind, countdown),
+            None => say!("   ⚠️ Claude hit its {} ({})", limit.kind, limit.seen_at.with_timezone(&Local).format("%H:%M")),
+        }
+    }
+    for budget in &status.budgets {
+        let marker = if budget.percent >= 100.0 { "⚠️ " } else { "" };
+        say!("   {}Budget:    {} {}, {}", marker, budget.period, budget.metric, budget.describe());
+    }
+    if let Some(context) = status.context {
+        let state = match (context.compacting, context.nearly_full) {
+            (true, _) => ", compacting",
+            (false, true) => ", nearly full",
+            _ => "",
+        };
+        say!(
+            "   Context:   {} of {} tokens ({:.0}%{})",
+            screen::format_metric("tokens", context.tokens as f64),
+            screen::format_metric("tokens", context.limit as f64),
+            context.percent,
+            state
+        );
+    }
+    say!("   Queued:    {}", status.queued);
+    say!("   Delivered: {}", status.delivered);
+    if let Some(acked) = &status.last_acked {
+        let ago = (Utc::now() - acked.at).num_seconds().max(0);
+        say!("   Last acked: {} (#{}, {}s ago)", acked.event, acked.seq, ago);
+    }
+    if status.retransmits > 0 {
+        say!("   Retransmits: {}", status.retransmits);
+    }
+    if status.reconnects > 0 {
+        say!("   Reconnects: {}", status.reconnects);
+    }
+    if let Some(skew) = status.clock_skew_ms {
+        say!("   Device clock: {:+.1}s vs host at last sync", skew as f64 / 1000.0);
+        if skew.abs() > clock::SKEW_WARN_MS {
+            esay!("   ⚠️ The device clock was off by {:.1}s; it was corrected, but check its RTC if this recurs", skew.abs() as f64 / 1000.0);
+        }
+    }
+    if status.clock_jump_ms.abs() > clock::SKEW_WARN_MS {
+        esay!(
+            "   ⚠️ The system clock moved {:+.1}s while the daemon was running; wall-clock times may be off",
+            status.clock_jump_ms as f64 / 1000.0
+        );
+    }
+}
+
+/// Run the end-to-end self-test and print one line per stage
+async fn handle_self_test(timeout: Duration, simulator: bool) {
+    say!("🧪 Running self-test...");
+    let reports = selftest::run(timeout, simulator).await;
+    let passed = reports.iter().all(|report| report.outcome == selftest::Outcome::Pass);
+    if output::json() {
+        output::emit(&serde_json::json!({ "passed": passed, "stages": reports }));
+    } else {
+        for report in &reports {
+            match report.outcome {
+                selftest::Outcome::Pass => say!("   ✅ {:<9} {} ({}ms)", report.stage, report.detail, report.millis),
+                selftest::Outcome::Fail => esay!("   ❌ {:<9} {}", report.stage, report.detail),
+                selftest::Outcome::Skip => say!("   ⏭️ {:<9} skipped", report.stage),
             }
-            
-            Ok(user_settings)
         }
+        if passed {
+            say!("✅ Self-test passed");
+        } else {
+            esay!("❌ Self-test failed");
+        }
+    }
+    if !passed {
+        ExitCode::Failure.exit();
     }
 }
 
-/// Read Claude settings from file
-fn read_claude_settings(path: &PathBuf) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-    if path.exists() {
-        let content = fs::read_to_string(path)?;
-        if content.trim().is_empty() {
-            Ok(serde_json::json!({}))
+/// Restore a settings file from a backup (the most recent one by default)
+fn handle_restore_backup(scope: &HookScope, backup: Option<&Path>, dry_run: bool) {
+    say!("♻️ Restoring Claude settings from backup...");
+    let result = (|| -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+        let settings_path = hooks::claude_settings_path(scope)?;
+        let backup = match backup {
+            Some(backup) => backup.to_path_buf(),
+            None => hooks::settings_backups(&settings_path)
+                .pop()
+                .ok_or_else(|| HookError::NoBackups(hooks::settings_backup_dir(&settings_path)))?,
+        };
+        let contents = fs::read_to_string(&backup)?;
+        serde_json::from_str::<serde_json::Value>(&contents)
+            .map_err(|e| format!("Backup {} is not valid JSON: {}", backup.display(), e))?;
+        if dry_run {
+            hooks::preview_claude_settings(&settings_path, &contents);
         } else {
-            Ok(serde_json::from_str(&content)?)
+            hooks::replace_claude_settings(&settings_path, &contents)?;
+        }
+        Ok((settings_path, backup))
+    })();
+
+    match result {
+        Ok((settings_path, backup)) if dry_run => {
+            say!("   ✅ Dry run: would restore {} from {}", settings_path.display(), backup.display());
+        }
+        Ok((settings_path, backup)) => {
+            say!("   ✅ Restored {} from {}", settings_path.display(), backup.display());
+        }
+        Err(e) => {
+            esay!("   ❌ Failed to restore backup: {}", e);
+            error::exit(&*e);
         }
-    } else {
-        Ok(serde_json::json!({}))
     }
 }
 
-/// Write Claude settings to file
-fn write_claude_settings(path: &PathBuf, settings: &serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
-    let formatted = serde_json::to_string_pretty(settings)?;
-    fs::write(path, formatted)?;
-    Ok(())
+/// Report hook installation in every settings file and exit non-zero on drift
+fn handle_hook_status(events: &[String], matchers: &[String]) {
+    say!("🔍 Checking Claude Code hook installation...");
+
+    // Highest precedence first, so the first scope an event is installed in is the one in effect
+    let files: Result<Vec<(HookScope, PathBuf)>, _> = HookScope::BY_PRECEDENCE
+        .into_iter()
+        .map(|scope| hooks::claude_settings_path(&scope).map(|path| (scope, path)))
+        .collect();
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => {
+            esay!("   ❌ {}", e);
+            error::exit(&*e);
+        }
+    };
+
+    let hooks_config = hooks::selected_hooks_config(events);
+    let mut installed: BTreeMap<&str, Option<HookScope>> = hooks_config.iter().map(|(event, _)| (*event, None)).collect();
+    let mut misconfigured: BTreeMap<&str, bool> = hooks_config.iter().map(|(event, _)| (*event, false)).collect();
+    // Per-file results for `--output json`
+    let mut scopes = Vec::new();
+
+    for (label, path) in &files {
+        let mut statuses = BTreeMap::new();
+        if !path.exists() {
+            say!("   📁 {:?}: {} (not found)", label, path.display());
+            scopes.push(serde_json::json!({ "scope": label, "path": path, "exists": false, "events": statuses }));
+            continue;
+        }
+        let settings = match hooks::read_claude_settings(path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                esay!("   ❌ {:?}: failed to read {}: {}", label, path.display(), e);
+                error::exit(&*e);
+            }
+        };
+        say!("   📁 {:?}: {}", label, path.display());
+        for (event_name, hook_command) in &hooks_config {
+            let status = hooks::hook_event_status(&settings, event_name, hook_command, hooks::matcher_for(matchers, event_name));
+            statuses.insert(*event_name, status);
+            match status {
+                HookStatus::Installed => {
+                    installed.entry(event_name).or_default().get_or_insert(*label);
+                    say!("      ✅ {}: installed", event_name);
+                }
+                HookStatus::Misconfigured => {
+                    misconfigured.insert(event_name, true);
+                    say!("      ⚠️ {}: misconfigured", event_name);
+                }
+                HookStatus::Missing => say!("      ➖ {}: missing", event_name),
+            }
+        }
+        scopes.push(serde_json::json!({ "scope": label, "path": path, "exists": true, "events": statuses }));
+    }
+
+    let drift: Vec<String> = hooks_config
+        .iter()
+        .filter_map(|(event_name, _)| {
+            if misconfigured[event_name] {
+                Some(format!("{} (misconfigured)", event_name))
+            } else if installed[event_name].is_none() {
+                Some(format!("{} (missing)", event_name))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let mut effective: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (event_name, scope) in &installed {
+        if let Some(scope) = scope {
+            effective.entry(format!("{:?}", scope)).or_default().push(event_name);
+        }
+    }
+    if !effective.is_empty() {
+        let sources: Vec<String> = effective
+            .iter()
+            .map(|(scope, events)| format!("{} ({})", scope, events.join(", ")))
+            .collect();
+        say!("   ℹ️ In effect from: {}", sources.join("; "));
+    }
+
+    if output::json() {
+        let drifted: BTreeMap<&str, HookStatus> = hooks_config
+            .iter()
+            .filter_map(|(event_name, _)| match (misconfigured[event_name], installed[event_name]) {
+                (true, _) => Some((*event_name, HookStatus::Misconfigured)),
+                (false, None) => Some((*event_name, HookStatus::Missing)),
+                _ => None,
+            })
+            .collect();
+        output::emit(&serde_json::json!({
+            "ok": drift.is_empty(),
+            "scopes": scopes,
+            "in_effect": installed,
+            "drift": drifted,
+        }));
+    }
+    if drift.is_empty() {
+        say!("   ✅ All ccgadget hooks installed");
+    } else {
+        esay!("   ❌ Hook drift detected: {}", drift.join(", "));
+        ExitCode::Failure.exit();
+    }
 }
 
-/// Get all hook configurations for CCGadget
-fn get_all_hooks_config() -> Vec<(&'static str, &'static str)> {
-    vec![
-        ("UserPromptSubmit", "ccgadget trigger"),
-        ("PreToolUse", "ccgadget trigger"),
-        ("PostToolUse", "ccgadget trigger"),
-        ("Notification", "ccgadget trigger"),
-        ("Stop", "ccgadget trigger"),
-    ]
+/// Remove ccgadget hooks from the scope's settings file and report what was removed
+fn handle_uninstall_hooks(scope: &HookScope, events: &[String], dry_run: bool) {
+    say!("🔧 Removing CCGadget hooks from Claude Code...");
+    say!("   Scope: {:?}", scope);
+
+    let result = (|| -> Result<Vec<(String, usize)>, Box<dyn std::error::Error>> {
+        let settings_path = hooks::claude_settings_path(scope)?;
+        if !settings_path.exists() {
+            say!("   ℹ️ No settings file at {}", settings_path.display());
+            return Ok(Vec::new());
+        }
+        say!("   📁 Found Claude settings: {}", settings_path.display());
+
+        let mut settings = hooks::read_claude_settings(&settings_path)?;
+        let removed = hooks::remove_ccgadget_hooks(&mut settings, events);
+        if dry_run {
+            hooks::preview_claude_settings(&settings_path, &serde_json::to_string_pretty(&settings)?);
+        } else if !removed.is_empty() {
+            hooks::write_claude_settings(&settings_path, &settings)?;
+        }
+        Ok(removed)
+    })();
+
+    match result {
+        Ok(removed) if removed.is_empty() => say!("   ℹ️ No ccgadget hooks found"),
+        Ok(removed) => {
+            for (event_name, count) in &removed {
+                say!("   ➖ {}: removed {} hook(s)", event_name, count);
+            }
+            let total: usize = removed.iter().map(|(_, count)| count).sum();
+            let verb = if dry_run { "Dry run: would remove" } else { "Removed" };
+            say!("   ✅ {} {} ccgadget hook(s) from {} event(s)", verb, total, removed.len());
+        }
+        Err(e) => {
+            esay!("   ❌ Failed to remove hooks: {}", e);
+            error::exit(&*e);
+        }
+    }
+
+    for (other, path) in hooks::other_scopes_with_hooks(scope) {
+        say!("   ⚠️ ccgadget hooks are still installed in {:?} settings ({})", other, path.display());
+        say!("   💡 Remove them with: ccgadget setup-hook --uninstall --scope {}", format!("{:?}", other).to_lowercase());
+    }
 }
 
-/// Setup a hook for a specific event in the settings
-fn setup_hook_for_event(
-    settings: &mut serde_json::Value,
-    event_name: &str,
-    hook_command: &str,
+fn handle_setup_hook(
+    scope: &HookScope,
     force: bool,
     auto_approve: bool,
-) -> Result<HookSetupResult, Box<dyn std::error::Error>> {
-    // Ensure hooks object exists
-    if !settings.get("hooks").is_some() {
-        settings["hooks"] = serde_json::json!({});
+    events: &[String],
+    matchers: &[String],
+    dry_run: bool,
+) {
+    say!("🔧 Setting up Claude Code hooks...");
+    say!("   Scope: {:?}", scope);
+    if force {
+        say!("   Force reinstall enabled");
+    }
+    if auto_approve {
+        say!("   Auto-approve enabled");
     }
     
-    let hooks = settings["hooks"].as_object_mut()
-        .ok_or("Failed to get hooks object")?;
-    
-    // Check if event already has hooks configured
-    if hooks.contains_key(event_name) {
-        // First check the current state (need immutable borrow)
-        let event_hooks_value = hooks.get(event_name).unwrap();
-        let exact_hook_exists = exact_hook_exists(event_hooks_value, hook_command);
-        let any_ccgadget_exists = any_ccgadget_hook_exists(event_hooks_value, hook_command);
-        let has_other_hooks = event_hooks_value.as_array()
-            .map(|arr| !arr.is_empty())
-            .unwrap_or(false);
-        
-        if exact_hook_exists {
-            // Perfect match - hook is already correctly configured
-            println!("   ✅ Hook for {} already correctly configured", event_name);
-            return Ok(HookSetupResult::AlreadyExists);
+    if !events.is_empty() {
+        say!("   Events: {}", events.join(", "));
+    }
+
+    if !matchers.is_empty() {
+        say!("   Matchers: {}", matchers.join(", "));
+    }
+
+    if dry_run {
+        say!("   Dry run: settings will not be modified");
+    }
+
+    match hooks::setup_claude_hooks(scope, force, auto_approve, events, matchers, dry_run) {
+        Ok(message) => {
+            say!("   ✅ {}", message);
         }
-        
-        // Determine what action to take
-        let action = if any_ccgadget_exists {
-            // ccgadget hook exists but with wrong configuration - ask user
-            if force {
-                println!("   🔧 Forcing update of mismatched hook for {}", event_name);
-                HookAction::Replace
-            } else if auto_approve {
-                println!("   ✅ Auto-approving hook update for {} (--yes flag)", event_name);
-                HookAction::Replace
-            } else {
-                ask_user_fix_hook_action(event_name, event_hooks_value, hook_command)?
-            }
-        } else if has_other_hooks {
-            // Check if there are any non-ccgadget hooks
-            let has_non_ccgadget_hooks = event_hooks_value.as_array()
-                .map(|arr| arr.iter().any(|hook_group| !hook_group_contains_command(hook_group, "ccgadget")))
-                .unwrap_or(false);
-                
-            if has_non_ccgadget_hooks {
-                // Other non-ccgadget hooks exist - ask user what to do
-                if auto_approve {
-                    println!("   ✅ Auto-approving hook addition for {} (--yes flag)", event_name);
-                    HookAction::Append
-                } else {
-                    ask_user_hook_action(event_name, event_hooks_value)?
-                }
-            } else {
-                // No actual hooks, just add
-                HookAction::Append
-            }
-        } else {
-            // No hooks at all, just add
-            HookAction::Append
-        };
-        
-        // Handle user choice
-        match action {
-            HookAction::Skip => {
-                println!("   ⏭️ Skipping hook for {} (user chose skip)", event_name);
-                return Ok(HookSetupResult::Skipped);
-            }
-            HookAction::Replace => {
-                // Replace all existing hooks with just our ccgadget hook
-                let hook_config = serde_json::json!([
-                    {
-                        "matcher": "",
-                        "hooks": [
-                            {
-                                "type": "command",
-                                "command": hook_command
-                            }
-                        ]
-                    }
-                ]);
-                hooks.insert(event_name.to_string(), hook_config);
-                println!("   🔄 Replaced all hooks for {} with ccgadget hook", event_name);
-            }
-            HookAction::Append => {
-                // Add ccgadget hook alongside existing hooks
-                let event_hooks_array = hooks.get_mut(event_name).unwrap()
-                    .as_array_mut()
-                    .ok_or("Event hooks must be an array")?;
-                
-                // Remove existing ccgadget hooks first (if any) to avoid duplicates
-                if any_ccgadget_exists {
-                    event_hooks_array.retain(|hook_group| {
-                        !hook_group_contains_command(hook_group, "ccgadget")
-                    });
-                }
-                
-                // Add our hook to the existing array
-                let ccgadget_hook = serde_json::json!({
-                    "matcher": "",
-                    "hooks": [
-                        {
-                            "type": "command",
-                            "command": hook_command
-                        }
-                    ]
-                });
-                
-                event_hooks_array.push(ccgadget_hook);
-                println!("   ➕ Added ccgadget hook alongside existing hooks for {}", event_name);
+        Err(e) => {
+            esay!("   ❌ Failed to setup hooks: {}", e);
+            error::exit(&*e);
+        }
+    }
+}
+
+
+/// Print aggregated usage and optionally push a summary to the device
+fn handle_usage(range: &TimeRange, group_by: GroupBy, format: UsageFormat, push: bool) {
+    if format == UsageFormat::Ccusage && group_by.ccusage_names().is_none() {
+        esay!("❌ ccusage has no report by project or model; use --group-by day, week, month, or session");
+        ExitCode::Usage.exit();
+    }
+    if format == UsageFormat::Ccusage {
+        // The report is JSON on stdout, so messages move to stderr as with `--output json`
+        output::set_format(output::OutputFormat::Json);
+    }
+    let config = Config::load_or_default();
+    let pricing = PricingTable::with_overrides(&config.pricing);
+
+    let records = match usage::load_records(range) {
+        Ok(records) => records,
+        Err(e) => {
+            esay!("❌ Failed to read usage data: {}", e);
+            error::exit(&*e);
+        }
+    };
+    if records.is_empty() && !output::json() {
+        say!("ℹ️ No usage found for the selected period");
+        return;
+    }
+
+    let rows = usage::aggregate(&records, group_by, &pricing);
+    let total = usage::total(&rows);
+    if format == UsageFormat::Ccusage {
+        match usage::ccusage_report(&records, group_by, &pricing) {
+            Ok(report) => output::emit(&report),
+            Err(e) => {
+                esay!("❌ {}", e);
+                ExitCode::Usage.exit();
             }
         }
+    } else if output::json() {
+        output::emit(&serde_json::json!({
+            "since": range.since,
+            "until": range.until,
+            "group_by": group_by,
+            "rows": rows,
+            "total": total,
+        }));
     } else {
-        // No existing hooks for this event - create new array
-        let hook_config = serde_json::json!([
-            {
-                "matcher": "",
-                "hooks": [
-                    {
-                        "type": "command",
-                        "command": hook_command
-                    }
-                ]
+        say_inline!("{}", usage::render_table(&rows, group_by));
+    }
+    if total.unpriced_tokens > 0 {
+        say!("⚠️ {} tokens from models without known pricing are excluded from cost", total.unpriced_tokens);
+    }
+
+    if push {
+        let mut payload = DevicePayload::usage(BTreeMap::from([
+            ("cost".to_string(), total.cost_usd),
+            ("tokens".to_string(), total.usage.total() as f64),
+        ]));
+        payload.event = "UsageSummary".to_string();
+        payload.message = Some(format!("{}: ${:.2}", range.describe(), total.cost_usd));
+
+        let request = IpcRequest::Event { payload: Box::new(payload), transcript_path: None };
+        match ipc::request(&request, Duration::from_secs(1)) {
+            Ok(IpcResponse::Accepted) => say!("📡 Summary sent to device"),
+            Ok(IpcResponse::Queued { state, .. }) => say!("⏳ Daemon {}; summary queued", state),
+            Ok(IpcResponse::Paused { .. }) => say!("🛠️ Device in maintenance; summary queued"),
+            Ok(_) => esay!("⚠️ Daemon did not accept the summary"),
+            Err(_) => {
+                esay!("❌ Daemon not running; start it with `ccgadget start` to push summaries");
+                ExitCode::DaemonNotRunning.exit();
             }
-        ]);
-        
-        hooks.insert(event_name.to_string(), hook_config);
+        }
     }
-    
-    println!("   ✅ Configured hook for {}", event_name);
-    Ok(HookSetupResult::Added)
 }
 
-/// Check if the exact expected hook configuration already exists
-fn exact_hook_exists(event_hooks: &serde_json::Value, target_command: &str) -> bool {
-    if let Some(hooks_array) = event_hooks.as_array() {
-        for hook_group in hooks_array {
-            // Check if this hook group matches our expected configuration exactly
-            if is_exact_ccgadget_hook(hook_group, target_command) {
-                return true;
+/// Redraw the active block's burn rate and projection until interrupted
+async fn handle_usage_live() {
+    let config = Config::load_or_default();
+    let mut last_report = String::new();
+    if output::accessible() {
+        say!("CCGadget live usage, updated when it changes (Ctrl-C to exit)");
+    }
+    loop {
+        let snapshot = tokio::task::spawn_blocking({
+            let config = config.clone();
+            move || burn::current(&config)
+        })
+        .await
+        .unwrap_or(None);
+
+        let report = burn::render_live(snapshot.as_ref(), Utc::now());
+        if output::accessible() {
+            // Screen readers cannot follow redraws; append a new report only when it changes
+            if report != last_report {
+                say!("\nUpdated {}", chrono::Local::now().format("%H:%M:%S"));
+                say_inline!("{}", report);
+                last_report = report;
             }
+        } else {
+            // Clear the screen and move the cursor home before redrawing
+            print!("\x1b[2J\x1b[H");
+            say!("🔥 CCGadget live usage (Ctrl-C to exit)\n");
+            print!("{}", report);
+        }
+        io::stdout().flush().ok();
+
+        tokio::select! {
+            _ = sleep(Duration::from_secs(5)) => {}
+            _ = tokio::signal::ctrl_c() => break,
         }
     }
-    false
 }
 
-/// Check if a hook group is exactly the ccgadget hook we expect
-fn is_exact_ccgadget_hook(hook_group: &serde_json::Value, target_command: &str) -> bool {
-    // Expected: {"matcher": "", "hooks": [{"type": "command", "command": "ccgadget trigger"}]}
-    let expected_matcher = "";
-    
-    // Check matcher
-    let matcher = hook_group.get("matcher")
-        .and_then(|m| m.as_str())
-        .unwrap_or("");
-    
-    if matcher != expected_matcher {
-        return false;
+/// Take over the terminal with the live dashboard
+fn handle_top() {
+    if !top::supported() {
+        esay!("❌ `top` needs an interactive terminal; try `ccgadget status` or `ccgadget usage --live`");
+        ExitCode::Usage.exit();
     }
-    
-    // Check hooks array
-    if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
-        if hooks.len() != 1 {
-            return false;
+    if let Err(e) = top::run(&Config::load_or_default()) {
+        esay!("❌ Dashboard failed: {}", e);
+        ExitCode::Failure.exit();
+    }
+}
+
+/// Show the configured link mode, or save a new one and apply it to a running daemon
+fn handle_link_mode(mode: Option<device::LinkMode>) {
+    let Some(mode) = mode else {
+        let configured = Config::load_or_default().device.link_mode;
+        say!("📶 Link mode: {}", configured);
+        if let Ok(IpcResponse::Status(status)) = ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+            if status.link_mode != configured {
+                say!("   ℹ️ Running daemon is using {}", status.link_mode);
+            }
+        }
+        let prefs = configured.preferences();
+        say!(
+            "   Requested interval {}-{} ms, latency {}, supervision timeout {} ms",
+            prefs.interval_min_ms, prefs.interval_max_ms, prefs.peripheral_latency, prefs.supervision_timeout_ms
+        );
+        return;
+    };
+
+    match Config::set_value("device.link_mode", mode.as_str().into()) {
+        Ok(path) => say!("✅ Link mode set to {} in {}", mode, path.display()),
+        Err(e) => {
+            esay!("❌ Failed to save link mode: {}", e);
+            error::exit(&*e);
         }
-        
-        let hook = &hooks[0];
-        let hook_type = hook.get("type").and_then(|t| t.as_str()).unwrap_or("");
-        let hook_command = hook.get("command").and_then(|c| c.as_str()).unwrap_or("");
-        
-        return hook_type == "command" && hook_command == target_command;
     }
-    
-    false
+    match ipc::request(&IpcRequest::SetLinkMode { mode }, Duration::from_millis(500)) {
+        Ok(IpcResponse::Accepted) => say!("   📡 Applied to running daemon"),
+        Ok(_) => esay!("   ⚠️ Daemon did not accept the link mode; restart it to apply"),
+        Err(_) => say!("   ℹ️ Daemon not running; applies on next start"),
+    }
 }
 
-/// Check if any ccgadget-related hook exists (even if not exact match)
-fn any_ccgadget_hook_exists(event_hooks: &serde_json::Value, target_command: &str) -> bool {
-    if let Some(hooks_array) = event_hooks.as_array() {
-        for hook_group in hooks_array {
-            if hook_group_contains_command(hook_group, target_command) {
-                return true;
+/// Switch the running daemon into or out of maintenance mode
+fn handle_maintenance(enabled: bool) {
+    match ipc::request(&IpcRequest::SetMaintenance { enabled }, Duration::from_secs(1)) {
+        Ok(IpcResponse::Accepted) if enabled => {
+            say!("🛠️ Maintenance mode on: regular transmissions paused");
+            say!("   💡 Resume with: ccgadget device maintenance off");
+        }
+        Ok(IpcResponse::Accepted) => say!("✅ Maintenance mode off: transmissions resumed"),
+        Ok(_) => {
+            esay!("❌ Daemon did not accept the maintenance request");
+            ExitCode::Failure.exit();
+        }
+        Err(_) => {
+            esay!("❌ Daemon not running; there are no transmissions to pause");
+            ExitCode::DaemonNotRunning.exit();
+        }
+    }
+}
+
+/// Show do-not-disturb, or turn it on or off in the running daemon. Without a daemon
+/// it is saved for the next one to pick up.
+fn handle_dnd(state: Option<Toggle>, until: Option<DateTime<Utc>>) {
+    if state.is_none() && until.is_none() {
+        let (dnd, quiet_hours) = match ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+            Ok(IpcResponse::Status(status)) => (status.dnd, status.quiet_hours),
+            _ => (dnd::path().ok().and_then(|path| dnd::load(&path)), false),
+        };
+        if output::json() {
+            output::emit(&serde_json::json!({ "dnd": dnd, "quiet_hours": quiet_hours }));
+            return;
+        }
+        match dnd {
+            Some(dnd) => say!("🔕 Do not disturb {}", dnd),
+            None => say!("🔔 Do not disturb is off"),
+        }
+        if quiet_hours {
+            say!("   🌙 Quiet hours are on, keeping the device just as quiet");
+        }
+        return;
+    }
+    if state == Some(Toggle::Off) && until.is_some() {
+        esay!("❌ --for only applies when turning do-not-disturb on");
+        ExitCode::Usage.exit();
+    }
+
+    let enabled = state != Some(Toggle::Off);
+    let dnd = enabled.then(|| dnd::DoNotDisturb::new(until));
+    let running = match ipc::request(&IpcRequest::SetDnd { enabled, until }, Duration::from_secs(1)) {
+        Ok(IpcResponse::Accepted) => true,
+        Ok(_) => {
+            esay!("❌ Daemon did not accept the do-not-disturb request");
+            ExitCode::Failure.exit();
+        }
+        Err(_) => {
+            if let Err(e) = dnd::path().and_then(|path| dnd::save(&path, dnd.as_ref())) {
+                esay!("❌ Failed to save do-not-disturb: {}", e);
+                ExitCode::Failure.exit();
             }
+            false
         }
+    };
+    if output::json() {
+        output::emit(&serde_json::json!({ "dnd": dnd, "daemon": running }));
+        return;
+    }
+    match dnd {
+        Some(dnd) => {
+            say!("🔕 Do not disturb {}", dnd);
+            say!("   💡 End it with: ccgadget dnd off");
+        }
+        None => say!("🔔 Do not disturb off"),
+    }
+    if !running {
+        say!("   ℹ️ Daemon not running; applies on next start");
     }
-    false
 }
 
-/// Check if a specific hook group contains a command
-fn hook_group_contains_command(hook_group: &serde_json::Value, target_command: &str) -> bool {
-    if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
-        for hook in hooks {
-            if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
-                if command.contains(target_command) {
-                    return true;
-                }
+/// Print the paired device's info as a table or JSON; exits non-zero when it cannot be read
+#[cfg(feature = "ble")]
+async fn handle_device_info() {
+    let info = match bluetooth::read_device_info().await {
+        Ok(info) => info,
+        Err(e) => {
+            esay!("❌ Could not read device info: {}", e);
+            error::exit(&*e);
+        }
+    };
+    if output::json() {
+        output::emit(&info);
+        return;
+    }
+    for (label, value) in info.rows() {
+        say!("   {:<12} {}", format!("{}:", label), value);
+    }
+}
+
+#[cfg(not(feature = "ble"))]
+async fn handle_device_info() {
+    bluetooth::unsupported()
+}
+
+/// Show the paired device and the added ones with their groups
+fn handle_device_list() {
+    let (paired, added) = match PairedDevice::load().and_then(|paired| Ok((paired, PairedDevice::load_added()?))) {
+        Ok(devices) => devices,
+        Err(e) => {
+            esay!("❌ Could not read the device registry: {}", e);
+            error::exit(&*e);
+        }
+    };
+    if output::json() {
+        output::emit(&serde_json::json!({ "paired": paired, "added": added }));
+        return;
+    }
+    match &paired {
+        Some(device) => say!("📟 Paired: {}", device.label()),
+        None => say!("📟 Paired: none (run `ccgadget pair`)"),
+    }
+    if added.is_empty() {
+        say!("   No added devices; add one with `ccgadget pair --add`");
+        return;
+    }
+    say!("   Added:");
+    for device in &added {
+        let group = device.group.as_deref().map(|group| format!(" [{}]", group)).unwrap_or_default();
+        say!("   - {}{}", device.label(), group);
+    }
+}
+
+/// Remove an added device; the daemon stops mirroring to it when restarted
+fn handle_device_remove(query: &str) {
+    match PairedDevice::remove_added(query) {
+        Ok(Some(device)) => {
+            if output::json() {
+                output::emit(&device);
+                return;
             }
+            say!("🗑️ Removed {}", device.label());
+            say!("   💡 Restart the daemon to stop mirroring to it");
+        }
+        Ok(None) => {
+            esay!("❌ No added device named {}; see `ccgadget device list`", query);
+            ExitCode::Failure.exit();
+        }
+        Err(e) => {
+            esay!("❌ Could not update the device registry: {}", e);
+            error::exit(&*e);
         }
     }
-    false
 }
 
-/// User choice for handling hook conflicts
-#[derive(Debug, PartialEq)]
-enum HookAction {
-    Replace,  // Replace existing hooks with ccgadget hook
-    Append,   // Add ccgadget hook alongside existing hooks
-    Skip,     // Skip this event, leave existing hooks unchanged
+/// Hand a simulated button press to the daemon
+fn handle_press(button: &str, long: bool) {
+    let action = Config::load_or_default().button_action(button, long);
+    let event = device::DeviceEvent::Button { button: button.to_string(), long };
+    match ipc::request(&IpcRequest::DeviceEvent { event }, Duration::from_secs(1)) {
+        Ok(IpcResponse::Accepted) => say!("🔘 Button {}{} pressed: {}", button, if long { " (long)" } else { "" }, action),
+        Ok(_) => {
+            esay!("❌ Daemon did not accept the button press");
+            ExitCode::Failure.exit();
+        }
+        Err(_) => {
+            esay!("❌ Daemon not running; start it with `ccgadget start`");
+            ExitCode::DaemonNotRunning.exit();
+        }
+    }
 }
 
-/// Ask user what to do with existing hooks for a specific event
-fn ask_user_hook_action(event_name: &str, existing_hooks: &serde_json::Value) -> Result<HookAction, Box<dyn std::error::Error>> {
-    println!("   ⚠️ Event '{}' already has existing hooks configured:", event_name);
-    
-    // Display existing hooks in a user-friendly way
-    if let Some(hooks_array) = existing_hooks.as_array() {
-        for (i, hook_group) in hooks_array.iter().enumerate() {
-            if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
-                for (j, hook) in hooks.iter().enumerate() {
-                    if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
-                        let matcher = hook_group.get("matcher")
-                            .and_then(|m| m.as_str())
-                            .unwrap_or("");
-                        let matcher_display = if matcher.is_empty() { "all" } else { matcher };
-                        println!("     {}.{}: {} (matcher: {})", i + 1, j + 1, command, matcher_display);
-                    }
-                }
+/// Send a session request to the daemon and print the resulting focus
+fn handle_session_focus(request: IpcRequest) {
+    let listing = matches!(request, IpcRequest::Sessions);
+    let (policy, pinned, focused, sessions) = match ipc::request(&request, Duration::from_secs(1)) {
+        Ok(IpcResponse::Sessions { policy, pinned, focused, sessions }) => (policy, pinned, focused, sessions),
+        Ok(IpcResponse::Error { message }) => {
+            esay!("❌ {}", message);
+            ExitCode::Failure.exit();
+        }
+        Ok(_) => {
+            esay!("❌ Daemon did not accept the session request");
+            ExitCode::Failure.exit();
+        }
+        Err(_) => {
+            esay!("❌ Daemon not running; sessions are tracked by the daemon");
+            ExitCode::DaemonNotRunning.exit();
+        }
+    };
+    if output::json() {
+        output::emit(&serde_json::json!({
+            "policy": policy,
+            "pinned": pinned,
+            "focused": focused,
+            "sessions": sessions,
+        }));
+        return;
+    }
+
+    if !listing {
+        match &focused {
+            Some(id) => say!("🎯 Display following session {} ({})", id, policy),
+            None => say!("🎯 Focus policy set to {}; no active sessions yet", policy),
+        }
+        return;
+    }
+    if sessions.is_empty() {
+        say!("No active sessions (policy: {})", policy);
+        return;
+    }
+    say!("Focus policy: {}", policy);
+    let now = Utc::now();
+    for session in sessions.iter().rev() {
+        let marker = if focused.as_ref() == Some(&session.session_id) { "▶" } else { " " };
+        let idle = (now - session.last_event).num_minutes();
+        let burn = session
+            .tokens_per_minute
+            .map(|rate| format!(", {}/min", screen::format_metric("tokens", rate)))
+            .unwrap_or_default();
+        let alert = match &session.alert {
+            Some(alert) if alert.permission => ", waiting for permission".to_string(),
+            Some(alert) => format!(", {} alert", alert.severity),
+            None => String::new(),
+        };
+        let pin = if pinned.as_ref() == Some(&session.session_id) { " 📌" } else { "" };
+        say!("{} {}  last event {}m ago{}{}{}", marker, session.session_id, idle, burn, alert, pin);
+    }
+}
+
+/// Render the device screen as it would look with current usage
+fn handle_display_preview(message: Option<&str>) {
+    use std::io::IsTerminal;
+
+    let config = Config::load_or_default();
+    let cost = cost::snapshot(&config, None);
+    let (widgets, warnings) = metrics::widget_values(&config, cost.as_ref(), None);
+    for warning in warnings {
+        esay!("⚠️ {}", warning);
+    }
+
+    let mut payload = DevicePayload::usage(widgets);
+    payload.message = message.map(str::to_string);
+    payload.burn = burn::current(&config);
+
+    let ansi = io::stdout().is_terminal() && !output::accessible();
+    print!("{}", screen::render(&payload, &config.display.widgets, ansi));
+}
+
+/// Read the device's settings back through the daemon, exiting when they are unavailable
+fn pull_device_settings() -> DeviceSettings {
+    match settings::pull() {
+        Ok(Some(settings)) => settings,
+        Ok(None) => {
+            esay!("❌ The device does not report its settings (firmware too old?)");
+            ExitCode::Failure.exit();
+        }
+        Err(e) => {
+            esay!("❌ {}", e);
+            error::exit(&*e);
+        }
+    }
+}
+
+/// Print the device's display settings as config TOML, optionally adopting them locally
+fn handle_display_pull(save: bool) {
+    let device = pull_device_settings();
+    print!("{}", device.to_toml());
+    if device == DeviceSettings::from_config(&Config::load_or_default()) {
+        esay!("✅ Device settings match the config");
+    } else if !save {
+        esay!("💡 Keep them with: ccgadget display pull --save");
+    }
+    if !save {
+        return;
+    }
+    let result = device.save_to_config().and_then(|path| settings::save_pushed(&device).map(|_| path));
+    match result {
+        Ok(path) => esay!("✅ Saved device settings to {}", path.display()),
+        Err(e) => {
+            esay!("❌ {}", e);
+            error::exit(&*e);
+        }
+    }
+}
+
+/// Push the configured display settings, refusing to overwrite changes made on the device
+fn handle_display_push(show_diff: bool, force: bool) {
+    let local = DeviceSettings::from_config(&Config::load_or_default());
+    // Without a way to read the device, a forced push can still be queued blind
+    let device = if force { settings::pull().ok().flatten() } else { Some(pull_device_settings()) };
+    let check = settings::check_push(&local, device.as_ref(), settings::load_pushed().as_ref());
+    let diff = device
+        .as_ref()
+        .map(|device| diff::unified(&device.to_toml(), &local.to_toml(), "device", "config"))
+        .unwrap_or_default();
+
+    if show_diff {
+        if diff.is_empty() {
+            say!("✅ Device settings match the config");
+        } else {
+            print!("{}", diff);
+            if check == PushCheck::Conflict {
+                esay!("⚠️ The device has settings that were not pushed from here (changed on the device?)");
             }
         }
+        return;
     }
-    
-    println!("   How would you like to handle 'ccgadget trigger' for {}?", event_name);
-    println!("     [r] Replace - Remove existing hooks and add ccgadget hook");
-    println!("     [a] Append  - Add ccgadget hook alongside existing hooks");
-    println!("     [s] Skip    - Keep existing hooks unchanged");
-    print!("   Choose [r/a/s]: ");
-    std::io::stdout().flush()?;
-    
-    // Read user input
-    loop {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        let input = input.trim().to_lowercase();
-        
-        match input.as_str() {
-            "r" | "replace" => return Ok(HookAction::Replace),
-            "a" | "append" => return Ok(HookAction::Append),
-            "s" | "skip" => return Ok(HookAction::Skip),
-            _ => {
-                print!("   Invalid choice. Please enter [r]eplace, [a]ppend, or [s]kip: ");
-                std::io::stdout().flush()?;
-                continue;
+    match check {
+        PushCheck::InSync => {
+            let _ = settings::save_pushed(&local);
+            say!("✅ Device settings already match the config");
+            return;
+        }
+        PushCheck::Conflict if !force => {
+            esay!("❌ The device has settings that were not pushed from here (changed on the device?):");
+            eprint!("{}", diff);
+            esay!("   💡 Keep them with `ccgadget display pull --save`, or overwrite with `ccgadget display push --force`");
+            ExitCode::Failure.exit();
+        }
+        _ => {}
+    }
+
+    match send_settings(&local) {
+        Ok(true) => say!("✅ Pushed display settings to the device"),
+        Ok(false) => say!("⏳ Display settings queued; they are sent when the device is reachable"),
+        Err(e) => {
+            esay!("❌ {}", e);
+            error::exit(&*e);
+        }
+    }
+}
+
+/// Hand `settings` to the daemon for the device and record them as pushed; `true` when
+/// they went out straight away, `false` when they were queued
+fn send_settings(settings: &DeviceSettings) -> Result<bool, Box<dyn std::error::Error>> {
+    let sent = match ipc::request(&IpcRequest::PushSettings { settings: settings.clone() }, Duration::from_secs(1)) {
+        Ok(IpcResponse::Accepted) => true,
+        Ok(IpcResponse::Queued { .. } | IpcResponse::Paused { .. }) => false,
+        Ok(_) => return Err("Daemon did not accept the settings".into()),
+        Err(_) => return Err("Daemon not running; start it with `ccgadget start`".into()),
+    };
+    if let Err(e) = settings::save_pushed(settings) {
+        esay!("⚠️ Could not record the pushed settings: {}", e);
+    }
+    Ok(sent)
+}
+
+/// Send ad-hoc text to the device through the daemon; exits non-zero if it is not running
+fn handle_display_message(text: &str, duration: u64, severity: payload::Severity) {
+    if text.trim().is_empty() {
+        esay!("❌ Nothing to show");
+        ExitCode::Failure.exit();
+    }
+    if text.trim().chars().count() > payload::MAX_MESSAGE_CHARS {
+        esay!("⚠️ Message cut to {} characters", payload::MAX_MESSAGE_CHARS);
+    }
+    let payload = DevicePayload::screen_message(text, duration, severity);
+    match ipc::request(&IpcRequest::Event { payload: Box::new(payload), transcript_path: None }, Duration::from_secs(1)) {
+        Ok(IpcResponse::Accepted) => say!("✅ Showing on the device for {}s", duration),
+        Ok(IpcResponse::Queued { .. }) => say!("⏳ Message queued; shown if the device is reachable within {}s", duration),
+        Ok(IpcResponse::Paused { .. }) => say!("⏳ Device in maintenance; message held until it ends"),
+        Ok(_) => {
+            esay!("❌ Daemon did not accept the message");
+            ExitCode::Failure.exit();
+        }
+        Err(_) => {
+            esay!("❌ Daemon not running; start it with `ccgadget start`");
+            ExitCode::DaemonNotRunning.exit();
+        }
+    }
+}
+
+/// Save the chosen display options to the config and push the resulting settings. When
+/// the daemon is not running they are applied once it connects.
+fn handle_display_set(page: Option<config::DisplayPage>, brightness: Option<u8>, dark_mode: Option<bool>) {
+    let changes = [
+        page.map(|page| ("display.page", toml_edit::Value::from(page.to_string()))),
+        brightness.map(|level| ("display.brightness", toml_edit::Value::from(level as i64))),
+        dark_mode.map(|dark| ("display.dark_mode", toml_edit::Value::from(dark))),
+    ];
+    for (key, value) in changes.into_iter().flatten() {
+        if let Err(e) = Config::set_value(key, value) {
+            esay!("❌ Could not save {}: {}", key, e);
+            error::exit(&*e);
+        }
+    }
+    let local = DeviceSettings::from_config(&Config::load_or_default());
+    let display = &local.display;
+    let summary = format!(
+        "page {}, brightness {}%, {} mode",
+        display.page,
+        display.brightness,
+        if display.dark_mode { "dark" } else { "light" }
+    );
+    match send_settings(&local) {
+        Ok(true) => say!("✅ Display set: {}", summary),
+        Ok(false) => say!("⏳ Display set: {}; sent when the device is reachable", summary),
+        Err(_) => {
+            if let Err(e) = settings::save_pushed(&local) {
+                esay!("⚠️ Could not record the settings: {}", e);
             }
+            say!("💾 Display set: {}; applied when the daemon connects", summary);
         }
     }
 }
 
-/// Ask user what to do with mismatched ccgadget hooks
-fn ask_user_fix_hook_action(event_name: &str, existing_hooks: &serde_json::Value, expected_command: &str) -> Result<HookAction, Box<dyn std::error::Error>> {
-    println!("   ⚠️ Event '{}' has ccgadget hooks but with incorrect configuration:", event_name);
-    
-    // Show current vs expected
-    println!("   Current ccgadget hooks:");
-    if let Some(hooks_array) = existing_hooks.as_array() {
-        for (i, hook_group) in hooks_array.iter().enumerate() {
-            if hook_group_contains_command(hook_group, "ccgadget") {
-                if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
-                    for (j, hook) in hooks.iter().enumerate() {
-                        if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
-                            if command.contains("ccgadget") {
-                                let matcher = hook_group.get("matcher")
-                                    .and_then(|m| m.as_str())
-                                    .unwrap_or("");
-                                let matcher_display = if matcher.is_empty() { "all" } else { &format!("'{}'", matcher) };
-                                println!("     {}.{}: {} (matcher: {})", i + 1, j + 1, command, matcher_display);
-                            }
-                        }
-                    }
-                }
+/// Print trigger log entries matching the filter, then follow new ones if asked
+fn handle_logs(filter: &logs::LogFilter, follow: bool, json: bool) {
+    let config = Config::load_or_default();
+    let result = storage::open(config.logs.backend).and_then(|store| {
+        let shown = logs::show(store.as_ref(), filter, json)?;
+        if shown == 0 && !follow {
+            esay!("ℹ️ No matching trigger log entries in {}", store.location().display());
+        }
+        if follow {
+            logs::follow(store.as_ref(), filter, json)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        esay!("❌ {}", e);
+        error::exit(&*e);
+    }
+}
+
+/// Copy trigger log entries from one storage backend to another
+fn handle_storage_migrate(from: storage::Backend, to: storage::Backend) {
+    if from == to {
+        esay!("❌ Source and destination are both {}", from);
+        ExitCode::Failure.exit();
+    }
+    let result = storage::open(from).and_then(|source| {
+        let destination = storage::open(to)?;
+        let (copied, total) = storage::migrate(source.as_ref(), destination.as_ref())?;
+        Ok((copied, total, destination.location()))
+    });
+    match result {
+        Ok((copied, total, location)) => {
+            say!("✅ Copied {} of {} entries to {} ({})", copied, total, to, location.display());
+            if copied < total {
+                say!("   ℹ️ {} were already there", total - copied);
+            }
+            if Config::load_or_default().logs.backend != to {
+                say!("   💡 Switch to it with: ccgadget config set logs.backend {}", to);
+                say!("      Until then, run the migration again to copy newer entries");
             }
         }
+        Err(e) => {
+            esay!("❌ Migration failed: {}", e);
+            error::exit(&*e);
+        }
     }
-    
-    // Show non-ccgadget hooks if any
-    let has_non_ccgadget = existing_hooks.as_array()
-        .map(|arr| arr.iter().any(|hook_group| !hook_group_contains_command(hook_group, "ccgadget")))
-        .unwrap_or(false);
-        
-    if has_non_ccgadget {
-        println!("   Other existing hooks:");
-        if let Some(hooks_array) = existing_hooks.as_array() {
-            for (i, hook_group) in hooks_array.iter().enumerate() {
-                if !hook_group_contains_command(hook_group, "ccgadget") {
-                    if let Some(hooks) = hook_group.get("hooks").and_then(|h| h.as_array()) {
-                        for (j, hook) in hooks.iter().enumerate() {
-                            if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
-                                let matcher = hook_group.get("matcher")
-                                    .and_then(|m| m.as_str())
-                                    .unwrap_or("");
-                                let matcher_display = if matcher.is_empty() { "all" } else { matcher };
-                                println!("     {}.{}: {} (matcher: {})", i + 1, j + 1, command, matcher_display);
-                            }
-                        }
-                    }
-                }
+}
+
+/// Print trigger history stats from the metrics database
+fn handle_stats(range: &TimeRange, top: usize) {
+    let config = Config::load_or_default();
+    let result = metrics_db::MetricsDb::open(&config).and_then(|db| stats::Stats::collect(&db, range, top));
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            esay!("❌ Failed to read the metrics database: {}", e);
+            error::exit(&*e);
+        }
+    };
+    if output::json() {
+        output::emit(&stats);
+    } else if stats.is_empty() {
+        say!("ℹ️ No trigger events recorded {}", range.describe());
+    } else {
+        say_inline!("{}", stats.render());
+    }
+}
+
+/// Delete the metrics database and import everything into a new one
+fn handle_storage_reindex() {
+    let config = Config::load_or_default();
+    let result = paths::metrics_db().and_then(|path| {
+        metrics_db::reset(&path)?;
+        let db = metrics_db::MetricsDb::open_at(&path)?;
+        Ok((db.sync(&config)?, path))
+    });
+    match result {
+        Ok((report, path)) => {
+            if output::json() {
+                output::emit(&report);
+                return;
             }
+            say!("✅ Rebuilt {}", path.display());
+            say!("   {} events, {} sessions, {} API responses", report.events, report.sessions, report.usage);
+            if !config.logs.metrics_db {
+                say!("   💡 `usage` reads transcripts until you run: ccgadget config set logs.metrics_db true");
+            }
+        }
+        Err(e) => {
+            esay!("❌ Reindex failed: {}", e);
+            error::exit(&*e);
         }
     }
-    
-    println!("   Expected ccgadget hook: {} (matcher: all)", expected_command);
-    println!("   How would you like to handle the incorrect ccgadget hook for {}?", event_name);
-    println!("     [r] Replace - Fix ccgadget hook to correct configuration");
-    println!("     [a] Append  - Add correct ccgadget hook alongside current ones"); 
-    println!("     [s] Skip    - Keep current hooks unchanged");
-    print!("   Choose [r/a/s]: ");
-    std::io::stdout().flush()?;
-    
-    // Read user input
-    loop {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        let input = input.trim().to_lowercase();
-        
-        match input.as_str() {
-            "r" | "replace" => return Ok(HookAction::Replace),
-            "a" | "append" => return Ok(HookAction::Append),
-            "s" | "skip" => return Ok(HookAction::Skip),
+}
+
+/// Export a session timeline as HTML or JSON, applying the configured redaction
+fn handle_export_timeline(session: &str, format: TimelineFormat, output: Option<&Path>, range: &TimeRange) {
+    let config = Config::load_or_default();
+    let pricing = PricingTable::with_overrides(&config.pricing);
+    let result = timeline::find_transcript(session).and_then(|path| {
+        let content = fs::read_to_string(&path)?;
+        let session_id = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut timeline = timeline::build(&session_id, &content, &pricing);
+        timeline.retain(range);
+        let mut value = serde_json::to_value(timeline)?;
+        Redactor::new(&config.redaction).value(&mut value);
+        let rendered = match format {
+            TimelineFormat::Html => timeline::render_html(&value),
+            TimelineFormat::Json => serde_json::to_string_pretty(&value)?,
+        };
+
+        let events = value["events"].as_array().map_or(0, Vec::len);
+        match output {
+            Some(path) if path == Path::new("-") => println!("{}", rendered),
             _ => {
-                print!("   Invalid choice. Please enter [r]eplace, [a]ppend, or [s]kip: ");
-                std::io::stdout().flush()?;
-                continue;
+                let short: String = session_id.chars().take(8).collect();
+                let path = output
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| PathBuf::from(format!("timeline-{}.{}", short, format.extension())));
+                fs::write(&path, rendered)?;
+                say!("✅ Wrote {} events to {}", events, path.display());
             }
         }
+        Ok(())
+    });
+    if let Err(e) = result {
+        esay!("❌ {}", e);
+        error::exit(&*e);
     }
 }
 
-fn handle_setup_hook(scope: &HookScope, force: bool, auto_approve: bool) {
-    println!("🔧 Setting up Claude Code hooks...");
-    println!("   Scope: {:?}", scope);
-    if force {
-        println!("   Force reinstall enabled");
+/// Get, set, list, or edit configuration values
+fn handle_config(action: &ConfigCommand) {
+    let result = match action {
+        ConfigCommand::Get { key } => Config::load().and_then(|config| match config.get(key)? {
+            Some(value) => {
+                println!("{}", value);
+                Ok(())
+            }
+            None => Err(format!("'{}' is not set", key).into()),
+        }),
+        ConfigCommand::Set { key, value } => Config::set_value(key, config::parse_value(value)).map(|path| {
+            say!("✅ Set {} in {}", config::scoped_key(key), path.display());
+            if let Some((_, name, _)) = config::env_overrides().into_iter().find(|(k, _, _)| k == key) {
+                say!("   ⚠️ {} is set and overrides this value", name);
+            }
+        }),
+        ConfigCommand::List => Config::load().and_then(|config| {
+            let overrides = config::env_overrides();
+            for (key, value) in config.entries()? {
+                match overrides.iter().find(|(k, _, _)| *k == key) {
+                    Some((_, name, _)) => println!("{} = {}  # from {}", key, value, name),
+                    None => println!("{} = {}", key, value),
+                }
+            }
+            Ok(())
+        }),
+        ConfigCommand::Edit => edit_config(),
+    };
+    if let Err(e) = result {
+        esay!("❌ {}", e);
+        error::exit(&*e);
     }
-    if auto_approve {
-        println!("   Auto-approve enabled");
+}
+
+/// Open the config file in the user's editor, then check that it still loads
+fn edit_config() -> Result<(), Box<dyn std::error::Error>> {
+    let path = Config::path()?;
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, "# CCGadget configuration; see `ccgadget config list` for every setting\n")?;
     }
-    
-    match setup_claude_hooks(scope, force, auto_approve) {
-        Ok(message) => {
-            println!("   ✅ {}", message);
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad".to_string() } else { "vi".to_string() });
+    // Run through the shell so editors configured with arguments (e.g. "code -w") work
+    let status = if cfg!(windows) {
+        std::process::Command::new("cmd").arg("/C").arg(format!("{} \"{}\"", editor, path.display())).status()?
+    } else {
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path)
+            .status()?
+    };
+    if !status.success() {
+        return Err(format!("Editor '{}' exited with {}", editor, status).into());
+    }
+
+    Config::load()?;
+    say!("✅ {} is valid", path.display());
+    Ok(())
+}
+
+/// Print the tool's paths for humans or as JSON for packaging scripts
+fn handle_paths(json: bool) {
+    let tool_paths = match paths::ToolPaths::resolve() {
+        Ok(tool_paths) => tool_paths,
+        Err(e) => {
+            esay!("❌ Failed to resolve paths: {}", e);
+            error::exit(&*e);
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tool_paths).unwrap_or_default());
+        return;
+    }
+    for (label, value) in tool_paths.rows() {
+        say!("{:<14} {}", format!("{}:", label), value);
+    }
+}
+
+/// Post-install step for package managers: completions and service definition
+fn handle_post_install(completions: bool, service: bool) {
+    say!("📦 Running CCGadget post-install...");
+    match install::post_install(&mut Cli::command(), completions, service) {
+        Ok(written) => {
+            for path in &written {
+                say!("   📝 Wrote {}", path.display());
+            }
+            say!("   ✅ Post-install complete ({} file(s))", written.len());
+            if service && service::ServiceManager::current().is_some() {
+                say!("   💡 Start the daemon at login with: ccgadget service install");
+            }
         }
         Err(e) => {
-            eprintln!("   ❌ Failed to setup hooks: {}", e);
-            std::process::exit(1);
+            esay!("   ❌ Post-install failed: {}", e);
+            error::exit(&*e);
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Longest wait for a newly installed service to bring the daemon up
+const SERVICE_START_TIMEOUT: Duration = Duration::from_secs(15);
 
-    #[test]
-    fn test_log_directory_creation() {
-        let result = get_log_directory();
-        assert!(result.is_ok());
-        let log_dir = result.unwrap();
-        assert!(log_dir.exists());
-        assert!(log_dir.is_dir());
+/// Install the daemon as a login service, then wait for it to come up and tell the
+/// user what to do if it was refused Bluetooth access
+async fn handle_service_install() {
+    say!("🛠️ Installing the CCGadget daemon service...");
+    let already_running = ipc::request(&IpcRequest::Status, Duration::from_millis(500)).ok();
+    let checked = match Config::load() {
+        Ok(config) => service::check_bluetooth(&config).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = checked {
+        esay!("   ❌ Bluetooth is not usable, so the service was not installed: {}", e);
+        error::exit(&*e);
+    }
+    let (manager, path) = match service::install() {
+        Ok(installed) => installed,
+        Err(e) => {
+            esay!("   ❌ Could not install the service: {}", e);
+            error::exit(&*e);
+        }
+    };
+    say!("   📝 Wrote {}", path.display());
+    say!("   ✅ Loaded with {}; the daemon now starts at login", manager);
+    if let Some(IpcResponse::Status(status)) = already_running {
+        say!("   ⚠️ A daemon started by hand is still running (pid {}); the service takes over once it exits", status.pid);
+        return;
     }
 
-    #[test]
-    fn test_hook_input_parsing() {
-        let test_json = r#"{
-            "session_id": "test-session",
-            "hook_event_name": "UserPromptSubmit",
-            "prompt": "test prompt"
-        }"#;
-        
-        let parsed: Result<HookInput, _> = serde_json::from_str(test_json);
-        assert!(parsed.is_ok());
-        
-        let hook_input = parsed.unwrap();
-        assert_eq!(hook_input.session_id, Some("test-session".to_string()));
-        assert_eq!(hook_input.hook_event_name, Some("UserPromptSubmit".to_string()));
-        assert_eq!(hook_input.prompt, Some("test prompt".to_string()));
+    say!("   ⏳ Waiting up to {}s for the daemon...", SERVICE_START_TIMEOUT.as_secs());
+    let deadline = tokio::time::Instant::now() + SERVICE_START_TIMEOUT;
+    let mut last_state = None;
+    while tokio::time::Instant::now() < deadline {
+        if let Ok(IpcResponse::Status(status)) = ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+            if service::bluetooth_denied(status.link_error.as_deref()) {
+                bluetooth_permission_help(manager);
+                ExitCode::NoBluetooth.exit();
+            }
+            match status.state {
+                LinkState::Ready | LinkState::Observing => {
+                    say!("   ✅ Daemon running (pid {}), device link {}", status.pid, status.state);
+                    return;
+                }
+                LinkState::Unpaired => {
+                    say!("   ✅ Daemon running (pid {})", status.pid);
+                    say!("   💡 No device paired yet; run `ccgadget pair`");
+                    return;
+                }
+                state => last_state = Some(state),
+            }
+        }
+        sleep(Duration::from_millis(250)).await;
+    }
+    match last_state {
+        Some(state) => say!("   ⚠️ The daemon is running but its device link is {}; see `ccgadget service status`", state),
+        None => say!("   ⚠️ The daemon has not started yet; see `ccgadget service status`"),
     }
+}
 
-    #[test]
-    fn test_trigger_log_entry_serialization() {
-        let log_entry = TriggerLogEntry {
-            timestamp: chrono::Utc::now(),
-            hook_input: Some(HookInput {
-                session_id: Some("test-session".to_string()),
-                transcript_path: None,
-                cwd: None,
-                hook_event_name: Some("TestEvent".to_string()),
-                prompt: Some("test prompt".to_string()),
-                message: None,
-                tool_name: None,
-                tool_input: None,
-                tool_response: None,
-            }),
-            metadata: LogMetadata {
-                version: "0.1.0".to_string(),
-                source: "test".to_string(),
-            },
-        };
+/// What to do when the service's daemon may not use Bluetooth
+fn bluetooth_permission_help(manager: service::ServiceManager) {
+    esay!("   ⚠️ The daemon was refused Bluetooth access");
+    if manager != service::ServiceManager::Launchd {
+        esay!("   💡 Check that your user may use Bluetooth, then restart the service");
+        return;
+    }
+    let exe = std::env::current_exe().map(|exe| exe.display().to_string()).unwrap_or_else(|_| "ccgadget".to_string());
+    esay!("   💡 macOS keeps Bluetooth permission per program and cannot always ask on behalf of an agent:");
+    esay!("      1. Open System Settings > Privacy & Security > Bluetooth");
+    esay!("      2. Allow ccgadget, adding {} with + if it is not listed", exe);
+    esay!("      3. Restart the agent: launchctl kickstart -k gui/$(id -u)/{}", install::LAUNCHD_LABEL);
+    esay!("      Upgrading ccgadget replaces the binary, which can reset the permission.");
+}
 
-        let serialized = serde_json::to_string(&log_entry);
-        assert!(serialized.is_ok());
-        assert!(serialized.unwrap().contains("test-session"));
+/// Stop the daemon service and remove its definition
+fn handle_service_uninstall() {
+    match service::uninstall() {
+        Ok(Some(path)) => say!("🗑️ Removed {}; the daemon no longer starts at login", path.display()),
+        Ok(None) => say!("ℹ️ The daemon service is not installed"),
+        Err(e) => {
+            esay!("❌ Could not remove the service: {}", e);
+            error::exit(&*e);
+        }
     }
+}
 
-    #[tokio::test]
-    async fn test_bluetooth_manager_creation() {
-        // Test that we can create a Bluetooth manager
-        // This will fail on systems without Bluetooth, but that's expected
-        let result = Manager::new().await;
-        // We don't assert success here since CI environments may not have Bluetooth
-        // Instead, we just verify the call doesn't panic
-        match result {
-            Ok(_) => println!("Bluetooth manager created successfully"),
-            Err(e) => println!("Bluetooth not available: {}", e),
+/// Report the daemon service; exits non-zero when it is not installed
+fn handle_service_status() {
+    let status = match service::status() {
+        Ok(status) => status,
+        Err(e) => {
+            esay!("❌ {}", e);
+            error::exit(&*e);
+        }
+    };
+    if output::json() {
+        output::emit(&status);
+    } else {
+        let state = match (status.installed, status.loaded) {
+            (false, _) => "not installed",
+            (true, true) => "installed and loaded",
+            (true, false) => "installed, not loaded",
+        };
+        say!("🛠️ Service ({}): {}", status.manager, state);
+        say!("   Definition: {}", status.unit.display());
+        match status.daemon {
+            Some(state) => say!("   Daemon:     running, device link {}", state),
+            None => say!("   Daemon:     not running"),
         }
+        if status.bluetooth_denied {
+            bluetooth_permission_help(status.manager);
+        }
+    }
+    if !status.installed {
+        ExitCode::Failure.exit();
     }
+}
+
+/// Import another tracker's export into the local usage store
+fn handle_import(from: import::ImportSource, file: &Path) {
+    say!("📥 Importing usage history...");
+    let text = if file == Path::new("-") {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        fs::read_to_string(file)
+    };
+    let text = match text {
+        Ok(text) => text,
+        Err(e) => {
+            esay!("   ❌ Failed to read {}: {}", file.display(), e);
+            ExitCode::Failure.exit();
+        }
+    };
+
+    let records = match from {
+        import::ImportSource::Ccusage => import::parse_ccusage(&text),
+        import::ImportSource::Csv => import::parse_csv(&text),
+    };
+    let records = match records {
+        Ok(records) => records,
+        Err(e) => {
+            esay!("   ❌ {}", e);
+            ExitCode::Failure.exit();
+        }
+    };
+
+    match import::save_imported(records) {
+        Ok(summary) => {
+            say!(
+                "   ✅ Imported {} record(s) ({} already imported)",
+                summary.added,
+                summary.duplicates
+            );
+            say!("   💡 Imported usage is included in `ccgadget usage`; days already covered by transcripts are skipped");
+        }
+        Err(e) => {
+            esay!("   ❌ Failed to save imported usage: {}", e);
+            error::exit(&*e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
     fn test_cli_parsing() {
@@ -1146,43 +2449,11 @@ mod tests {
         let cli = Cli::try_parse_from(args);
         assert!(cli.is_ok());
         
-        if let Some(Commands::Pair { device, force }) = cli.unwrap().command {
+        if let Some(Commands::Pair { device, force, .. }) = cli.unwrap().command {
             assert_eq!(device, Some("test-device".to_string()));
             assert!(force);
         } else {
             panic!("Expected Pair command");
         }
     }
-
-    #[test]
-    fn test_device_name_filtering() {
-        // Test CCGadget device name patterns
-        assert!(is_ccgadget_device("CCGadget-Demo"));
-        assert!(is_ccgadget_device("ccgadget-home")); // case insensitive
-        assert!(is_ccgadget_device("My CCGadget Device"));
-        assert!(is_ccgadget_device("CCG-Office"));
-        assert!(is_ccgadget_device("ccg-lab"));
-        assert!(is_ccgadget_device("ESP32-CCG-Test"));
-        assert!(is_ccgadget_device("esp32-ccg-home"));
-        
-        // Test non-CCGadget device names should be filtered out
-        assert!(!is_ccgadget_device("iPhone"));
-        assert!(!is_ccgadget_device("MacBook Pro"));
-        assert!(!is_ccgadget_device("AirPods"));
-        assert!(!is_ccgadget_device("Unknown Device"));
-        assert!(!is_ccgadget_device("ESP32-Other"));
-        assert!(!is_ccgadget_device("CCG")); // too short
-        assert!(!is_ccgadget_device(""));
-    }
-
-    #[test]
-    fn test_hook_scope_enum() {
-        // Test that all hook scopes exist and can be used
-        let _user = HookScope::User;
-        let _local = HookScope::Local;
-        
-        // Test Debug implementation
-        assert_eq!(format!("{:?}", HookScope::User), "User");
-        assert_eq!(format!("{:?}", HookScope::Local), "Local");
-    }
 }
@@ -0,0 +1,185 @@
+use crate::config::{Config, MetricsConfig};
+use crate::cost::CostSnapshot;
+use crate::expr::Expr;
+use crate::session::SessionState;
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Named metric values that can be assigned to device widgets
+pub type MetricValues = BTreeMap<String, f64>;
+
+/// Maximum time a plugin metric command may run
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Built-in metrics derived from the cost snapshot and the session's running totals
+pub fn builtin_metrics(cost: Option<&CostSnapshot>, session: Option<&SessionState>) -> MetricValues {
+    let mut values = MetricValues::new();
+    if let Some(session) = cost.and_then(|c| c.session.as_ref()) {
+        values.insert("cost_session".to_string(), session.cost_usd);
+        values.insert("tokens_session".to_string(), session.usage.total() as f64);
+    }
+    if let Some(today) = cost.and_then(|c| c.today.as_ref()) {
+        values.insert("cost_today".to_string(), today.cost_usd);
+        values.insert("tokens_today".to_string(), today.usage.total() as f64);
+    }
+    if let Some(session) = session {
+        values.insert("prompts_session".to_string(), session.prompts as f64);
+        values.insert("tools_session".to_string(), session.tools as f64);
+        values.insert("events_session".to_string(), session.events as f64);
+        values.insert("minutes_session".to_string(), session.minutes(chrono::Utc::now()));
+    }
+    values
+}
+
+/// Collect built-in, plugin, and derived metrics; returns values plus warnings
+pub fn collect(
+    config: &MetricsConfig,
+    cost: Option<&CostSnapshot>,
+    session: Option<&SessionState>,
+) -> (MetricValues, Vec<String>) {
+    let mut values = builtin_metrics(cost, session);
+    let mut warnings = Vec::new();
+
+    for (name, command) in &config.plugins {
+        match run_plugin(command) {
+            Ok(value) => {
+                values.insert(name.clone(), value);
+            }
+            Err(e) => warnings.push(format!("Plugin metric '{}' failed: {}", name, e)),
+        }
+    }
+
+    warnings.extend(resolve_derived(&mut values, &config.derived));
+    (values, warnings)
+}
+
+/// Values of the metrics assigned to device widgets, plus collection warnings
+pub fn widget_values(
+    config: &Config,
+    cost: Option<&CostSnapshot>,
+    session: Option<&SessionState>,
+) -> (MetricValues, Vec<String>) {
+    let (values, warnings) = collect(&config.metrics, cost, session);
+    let widgets = config
+        .display
+        .widgets
+        .iter()
+        .filter_map(|name| values.get(name).map(|value| (name.clone(), *value)))
+        .collect();
+    (widgets, warnings)
+}
+
+/// Evaluate derived metric expressions in dependency order
+pub fn resolve_derived(values: &mut MetricValues, derived: &BTreeMap<String, String>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut pending = Vec::new();
+    for (name, source) in derived {
+        if values.contains_key(name) {
+            warnings.push(format!("Derived metric '{}' shadows an existing metric; ignored", name));
+            continue;
+        }
+        match Expr::parse(source) {
+            Ok(expr) => pending.push((name.clone(), expr)),
+            Err(e) => warnings.push(format!("Derived metric '{}': {}", name, e)),
+        }
+    }
+
+    // Repeatedly evaluate metrics whose inputs are all known, so derived
+    // metrics may reference other derived metrics in any order
+    loop {
+        let ready = pending
+            .iter()
+            .position(|(_, expr)| expr.metrics().iter().all(|m| values.contains_key(m)));
+        let Some(index) = ready else { break };
+        let (name, expr) = pending.remove(index);
+        match expr.eval(&|metric| values.get(metric).copied()) {
+            Ok(value) => {
+                values.insert(name, value);
+            }
+            Err(e) => warnings.push(format!("Derived metric '{}': {}", name, e)),
+        }
+    }
+
+    for (name, expr) in pending {
+        let missing: Vec<String> = expr.metrics().into_iter().filter(|m| !values.contains_key(m)).collect();
+        warnings.push(format!(
+            "Derived metric '{}' unavailable (missing or circular: {})",
+            name,
+            missing.join(", ")
+        ));
+    }
+    warnings
+}
+
+/// Run a plugin metric command through the shell and parse its stdout as a number
+fn run_plugin(command: &str) -> Result<f64, String> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    let deadline = Instant::now() + PLUGIN_TIMEOUT;
+    loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(_) => break,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", PLUGIN_TIMEOUT.as_secs()));
+            }
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("output '{}' is not a number", stdout.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derived_metrics_chain_and_cycles() {
+        let mut values = MetricValues::new();
+        values.insert("cost_today".to_string(), 12.0);
+        values.insert("git_commits_today".to_string(), 3.0);
+
+        let mut derived = BTreeMap::new();
+        derived.insert("a_double".to_string(), "cost_per_commit * 2".to_string());
+        derived.insert("cost_per_commit".to_string(), "cost_today / git_commits_today".to_string());
+        derived.insert("loop_a".to_string(), "loop_b + 1".to_string());
+        derived.insert("loop_b".to_string(), "loop_a + 1".to_string());
+        derived.insert("cost_today".to_string(), "1".to_string());
+
+        let warnings = resolve_derived(&mut values, &derived);
+        assert_eq!(values.get("cost_per_commit"), Some(&4.0));
+        assert_eq!(values.get("a_double"), Some(&8.0));
+        assert_eq!(values.get("cost_today"), Some(&12.0));
+        assert!(!values.contains_key("loop_a"));
+        assert_eq!(warnings.len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plugin_metric_command() {
+        assert_eq!(run_plugin("echo 42"), Ok(42.0));
+        assert!(run_plugin("echo nope").is_err());
+    }
+}
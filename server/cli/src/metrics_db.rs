@@ -0,0 +1,700 @@
+use crate::config::{AlertsConfig, Config};
+use crate::paths;
+use crate::payload::Severity;
+use crate::session::SessionSummary;
+use crate::storage::{self, sortable_time, EventStore};
+use crate::timerange::TimeRange;
+use crate::transcript::{self, TokenUsage, UsageRecord};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use rusqlite::{Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Schema changes in order; the database's `user_version` counts those applied
+const MIGRATIONS: &[&str] = &[
+    // Hook events, from the trigger log
+    "CREATE TABLE events (
+        timestamp TEXT NOT NULL,
+        event TEXT NOT NULL,
+        session_id TEXT NOT NULL,
+        tool TEXT,
+        cwd TEXT,
+        transcript_path TEXT,
+        UNIQUE (timestamp, event, session_id)
+    );
+    CREATE INDEX events_by_session ON events (session_id, timestamp);
+
+    -- Latest summary of each session, from the session history
+    CREATE TABLE sessions (
+        session_id TEXT PRIMARY KEY,
+        started_at TEXT NOT NULL,
+        ended_at TEXT NOT NULL,
+        minutes REAL NOT NULL,
+        prompts INTEGER NOT NULL,
+        tools INTEGER NOT NULL,
+        tokens INTEGER NOT NULL,
+        cost_usd REAL NOT NULL,
+        cwd TEXT
+    );
+    CREATE INDEX sessions_by_end ON sessions (ended_at);
+
+    -- One row per API response, from transcripts
+    CREATE TABLE usage (
+        key TEXT PRIMARY KEY,
+        timestamp TEXT NOT NULL,
+        session_id TEXT,
+        cwd TEXT,
+        project TEXT,
+        model TEXT NOT NULL,
+        input_tokens INTEGER NOT NULL,
+        output_tokens INTEGER NOT NULL,
+        cache_creation_tokens INTEGER NOT NULL,
+        cache_read_tokens INTEGER NOT NULL
+    );
+    CREATE INDEX usage_by_time ON usage (timestamp);
+
+    -- Usage summed per local day, project, and model, kept up to date on insert
+    CREATE TABLE usage_daily (
+        day TEXT NOT NULL,
+        project TEXT NOT NULL,
+        model TEXT NOT NULL,
+        responses INTEGER NOT NULL,
+        input_tokens INTEGER NOT NULL,
+        output_tokens INTEGER NOT NULL,
+        cache_creation_tokens INTEGER NOT NULL,
+        cache_read_tokens INTEGER NOT NULL,
+        PRIMARY KEY (day, project, model)
+    );
+
+    CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+    // Failed tool calls and error notifications; events synced before count as successes
+    "ALTER TABLE events ADD COLUMN error INTEGER NOT NULL DEFAULT 0;",
+];
+
+/// Meta key holding when transcripts were last scanned
+const TRANSCRIPTS_SYNCED: &str = "transcripts_synced_at";
+
+/// Transcripts modified this long before the last scan are read again, in case their
+/// modification time lags the write
+const SYNC_OVERLAP: Duration = Duration::from_secs(60);
+
+/// How many rows a sync added or updated
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SyncReport {
+    pub events: usize,
+    pub sessions: usize,
+    pub usage: usize,
+}
+
+/// Usage on one local day, for one project and model
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    /// Project root or working directory; empty when unknown
+    pub project: String,
+    pub model: String,
+    /// API responses counted
+    pub responses: u64,
+    pub usage: TokenUsage,
+}
+
+/// A session's activity within a time range
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SessionActivity {
+    pub session_id: String,
+    pub events: u64,
+    pub prompts: u64,
+    /// Tool calls started (`PreToolUse`)
+    pub tools: u64,
+    pub first_event_at: DateTime<Utc>,
+    pub last_event_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+/// How often a tool was called within a time range, and how often it failed
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ToolCount {
+    pub tool: String,
+    pub calls: u64,
+    pub errors: u64,
+}
+
+/// Events, session summaries, and usage in one indexed SQLite database. It is filled
+/// from the trigger log, the session history, and transcripts by `sync`, which reads
+/// only what is new since the last one; the first sync imports all existing history.
+pub struct MetricsDb {
+    path: PathBuf,
+    connection: Connection,
+}
+
+impl MetricsDb {
+    /// Open the database in the state directory and bring it up to date
+    pub fn open(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let db = Self::open_at(&paths::metrics_db()?)?;
+        db.sync(config)?;
+        Ok(db)
+    }
+
+    /// Open (creating if needed) the database at `path`, applying pending migrations
+    pub fn open_at(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path)?;
+        // Hooks from parallel sessions may sync at the same time
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        let db = Self { path: path.to_path_buf(), connection };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    /// Where the database is kept
+    pub fn location(&self) -> &Path {
+        &self.path
+    }
+
+    /// Apply the migrations this database has not had yet
+    fn migrate(&self) -> Result<(), Box<dyn Error>> {
+        // Take the write lock first, so two processes do not both migrate
+        let tx = Transaction::new_unchecked(&self.connection, TransactionBehavior::Immediate)?;
+        let version: usize = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for migration in MIGRATIONS.iter().skip(version) {
+            tx.execute_batch(migration)?;
+        }
+        if version < MIGRATIONS.len() {
+            tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Add what the trigger log, session history, and transcripts gained since the
+    /// last sync
+    pub fn sync(&self, config: &Config) -> Result<SyncReport, Box<dyn Error>> {
+        let store = storage::open(config.logs.backend)?;
+        Ok(SyncReport {
+            events: self.sync_events(store.as_ref(), &config.alerts)?,
+            sessions: self.sync_sessions(&paths::logs_dir()?)?,
+            usage: self.sync_usage()?,
+        })
+    }
+
+    /// Copy trigger log entries from the latest one stored on, flagging failed tool calls
+    /// and notifications `alerts` rates as errors; returns how many were new
+    pub fn sync_events(&self, store: &dyn EventStore, alerts: &AlertsConfig) -> Result<usize, Box<dyn Error>> {
+        let latest: Option<String> = self.connection.query_row("SELECT MAX(timestamp) FROM events", [], |row| row.get(0))?;
+        let since = latest.and_then(|at| at.parse::<DateTime<Utc>>().ok());
+        let entries = store.read(&TimeRange { since, until: None })?;
+
+        let tx = self.connection.unchecked_transaction()?;
+        let mut added = 0;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR IGNORE INTO events (timestamp, event, session_id, tool, cwd, transcript_path, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for entry in entries {
+                let Ok(value) = serde_json::from_str::<serde_json::Value>(&entry) else {
+                    continue;
+                };
+                let Some(at) = value["timestamp"].as_str().and_then(|ts| ts.parse::<DateTime<Utc>>().ok()) else {
+                    continue;
+                };
+                let input = &value["hook_input"];
+                added += insert.execute((
+                    sortable_time(at),
+                    input["hook_event_name"].as_str().unwrap_or("Unknown"),
+                    input["session_id"].as_str().unwrap_or_default(),
+                    input["tool_name"].as_str(),
+                    input["cwd"].as_str(),
+                    input["transcript_path"].as_str(),
+                    is_error(input, alerts),
+                ))?;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Copy session summaries from the monthly history files (`sessions-YYYY-MM.jsonl`)
+    /// in `dir`, starting with the month of the latest one stored; returns how many
+    /// sessions were added or updated
+    pub fn sync_sessions(&self, dir: &Path) -> Result<usize, Box<dyn Error>> {
+        let latest: Option<String> = self.connection.query_row("SELECT MAX(ended_at) FROM sessions", [], |row| row.get(0))?;
+        let first = latest.map(|at| format!("sessions-{}.jsonl", &at[..7.min(at.len())])).unwrap_or_default();
+        let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+            Err(_) => return Ok(0),
+        };
+        files.retain(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with("sessions-") && name.ends_with(".jsonl") && *name >= *first
+        });
+        files.sort();
+
+        let tx = self.connection.unchecked_transaction()?;
+        let mut changed = 0;
+        {
+            // A later summary of the same session replaces the earlier one
+            let mut upsert = tx.prepare_cached(
+                "INSERT INTO sessions (session_id, started_at, ended_at, minutes, prompts, tools, tokens, cost_usd, cwd)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT (session_id) DO UPDATE SET
+                    started_at = excluded.started_at, ended_at = excluded.ended_at,
+                    minutes = excluded.minutes, prompts = excluded.prompts, tools = excluded.tools,
+                    tokens = excluded.tokens, cost_usd = excluded.cost_usd, cwd = excluded.cwd
+                 WHERE excluded.ended_at > sessions.ended_at",
+            )?;
+            for file in files {
+                let content = fs::read_to_string(&file)?;
+                for summary in content.lines().filter_map(|line| serde_json::from_str::<SessionSummary>(line).ok()) {
+                    changed += upsert.execute((
+                        &summary.session_id,
+                        sortable_time(summary.started_at),
+                        sortable_time(summary.ended_at),
+                        summary.minutes,
+                        summary.prompts,
+                        summary.tools,
+                        summary.tokens,
+                        summary.cost_usd,
+                        &summary.cwd,
+                    ))?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Read the transcripts modified since the last scan, and those named in the trigger
+    /// log, into the usage table; returns how many responses were new
+    fn sync_usage(&self) -> Result<usize, Box<dyn Error>> {
+        let started = Utc::now();
+        let since = self
+            .meta(TRANSCRIPTS_SYNCED)?
+            .and_then(|at| at.parse::<DateTime<Utc>>().ok())
+            .map(|at| SystemTime::from(at) - SYNC_OVERLAP);
+
+        let mut files: BTreeSet<PathBuf> = transcript::transcript_files(since)?.into_iter().collect();
+        let mut statement = self.connection.prepare("SELECT DISTINCT transcript_path FROM events WHERE transcript_path IS NOT NULL")?;
+        let logged = statement.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        files.extend(logged.into_iter().map(PathBuf::from).filter(|path| modified_since(path, since)));
+
+        let mut added = 0;
+        for file in files {
+            if let Ok(records) = transcript::read_usage_records(&file) {
+                added += self.insert_usage(&records)?;
+            }
+        }
+        self.set_meta(TRANSCRIPTS_SYNCED, &sortable_time(started))?;
+        Ok(added)
+    }
+
+    /// Store usage records not stored yet and add them to the daily rollup; returns how
+    /// many were new
+    pub fn insert_usage(&self, records: &[UsageRecord]) -> Result<usize, Box<dyn Error>> {
+        let tx = self.connection.unchecked_transaction()?;
+        let mut added = 0;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR IGNORE INTO usage (key, timestamp, session_id, cwd, project, model,
+                    input_tokens, output_tokens, cache_creation_tokens, cache_read_tokens)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            let mut rollup = tx.prepare_cached(
+                "INSERT INTO usage_daily (day, project, model, responses,
+                    input_tokens, output_tokens, cache_creation_tokens, cache_read_tokens)
+                 VALUES (?1, ?2, ?3, 1, ?4, ?5, ?6, ?7)
+                 ON CONFLICT (day, project, model) DO UPDATE SET
+                    responses = responses + 1,
+                    input_tokens = input_tokens + excluded.input_tokens,
+                    output_tokens = output_tokens + excluded.output_tokens,
+                    cache_creation_tokens = cache_creation_tokens + excluded.cache_creation_tokens,
+                    cache_read_tokens = cache_read_tokens + excluded.cache_read_tokens",
+            )?;
+            for record in records {
+                let usage = &record.usage;
+                let inserted = insert.execute((
+                    usage_key(record),
+                    sortable_time(record.timestamp),
+                    &record.session_id,
+                    &record.cwd,
+                    &record.project,
+                    &record.model,
+                    usage.input_tokens,
+                    usage.output_tokens,
+                    usage.cache_creation_input_tokens,
+                    usage.cache_read_input_tokens,
+                ))?;
+                if inserted == 0 {
+                    continue;
+                }
+                rollup.execute((
+                    record.timestamp.with_timezone(&Local).date_naive().to_string(),
+                    record.project.as_deref().or(record.cwd.as_deref()).unwrap_or_default(),
+                    &record.model,
+                    usage.input_tokens,
+                    usage.output_tokens,
+                    usage.cache_creation_input_tokens,
+                    usage.cache_read_input_tokens,
+                ))?;
+                added += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Usage records within `range`, oldest first
+    pub fn usage(&self, range: &TimeRange) -> Result<Vec<UsageRecord>, Box<dyn Error>> {
+        let (since, until) = bounds(range);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT key, timestamp, session_id, cwd, project, model,
+                input_tokens, output_tokens, cache_creation_tokens, cache_read_tokens
+             FROM usage WHERE timestamp >= ?1 AND (?2 IS NULL OR timestamp < ?2) ORDER BY timestamp",
+        )?;
+        let records = statement
+            .query_map((since, until), |row| {
+                Ok(UsageRecord {
+                    dedup_key: Some(row.get(0)?),
+                    timestamp: row.get::<_, String>(1)?.parse().unwrap_or_default(),
+                    session_id: row.get(2)?,
+                    cwd: row.get(3)?,
+                    project: row.get(4)?,
+                    model: row.get(5)?,
+                    usage: TokenUsage {
+                        input_tokens: row.get(6)?,
+                        output_tokens: row.get(7)?,
+                        cache_creation_input_tokens: row.get(8)?,
+                        cache_read_input_tokens: row.get(9)?,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
+    /// Daily usage rollups from `since` to `until` (inclusive local days), oldest first
+    pub fn daily(&self, since: Option<NaiveDate>, until: Option<NaiveDate>) -> Result<Vec<DailyUsage>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT day, project, model, responses,
+                input_tokens, output_tokens, cache_creation_tokens, cache_read_tokens
+             FROM usage_daily WHERE day >= ?1 AND (?2 IS NULL OR day <= ?2) ORDER BY day, project, model",
+        )?;
+        let since = since.map(|day| day.to_string()).unwrap_or_default();
+        let rows = statement
+            .query_map((since, until.map(|day| day.to_string())), |row| {
+                Ok(DailyUsage {
+                    day: row.get::<_, String>(0)?.parse().unwrap_or_default(),
+                    project: row.get(1)?,
+                    model: row.get(2)?,
+                    responses: row.get(3)?,
+                    usage: TokenUsage {
+                        input_tokens: row.get(4)?,
+                        output_tokens: row.get(5)?,
+                        cache_creation_input_tokens: row.get(6)?,
+                        cache_read_input_tokens: row.get(7)?,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Summaries of sessions last active within `range`, oldest first
+    pub fn sessions(&self, range: &TimeRange) -> Result<Vec<SessionSummary>, Box<dyn Error>> {
+        let (since, until) = bounds(range);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT session_id, started_at, ended_at, minutes, prompts, tools, tokens, cost_usd, cwd
+             FROM sessions WHERE ended_at >= ?1 AND (?2 IS NULL OR ended_at < ?2) ORDER BY ended_at",
+        )?;
+        let sessions = statement
+            .query_map((since, until), |row| {
+                Ok(SessionSummary {
+                    session_id: row.get(0)?,
+                    started_at: row.get::<_, String>(1)?.parse().unwrap_or_default(),
+                    ended_at: row.get::<_, String>(2)?.parse().unwrap_or_default(),
+                    minutes: row.get(3)?,
+                    prompts: row.get(4)?,
+                    tools: row.get(5)?,
+                    tokens: row.get(6)?,
+                    cost_usd: row.get(7)?,
+                    cwd: row.get(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(sessions)
+    }
+
+    /// How many of each hook event were recorded within `range`
+    pub fn event_counts(&self, range: &TimeRange) -> Result<BTreeMap<String, u64>, Box<dyn Error>> {
+        let (since, until) = bounds(range);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT event, COUNT(*) FROM events
+             WHERE timestamp >= ?1 AND (?2 IS NULL OR timestamp < ?2) GROUP BY event",
+        )?;
+        let counts = statement
+            .query_map((since, until), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        Ok(counts)
+    }
+
+    /// How many hook events of each kind within `range` were errors
+    pub fn error_counts(&self, range: &TimeRange) -> Result<BTreeMap<String, u64>, Box<dyn Error>> {
+        let (since, until) = bounds(range);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT event, COUNT(*) FROM events
+             WHERE error AND timestamp >= ?1 AND (?2 IS NULL OR timestamp < ?2) GROUP BY event",
+        )?;
+        let counts = statement
+            .query_map((since, until), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        Ok(counts)
+    }
+
+    /// Tools called within `range`, most used first
+    pub fn tool_counts(&self, range: &TimeRange) -> Result<Vec<ToolCount>, Box<dyn Error>> {
+        let (since, until) = bounds(range);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT tool,
+                SUM(event = 'PreToolUse') AS calls,
+                SUM(event = 'PostToolUse' AND error) AS errors
+             FROM events
+             WHERE tool IS NOT NULL AND timestamp >= ?1 AND (?2 IS NULL OR timestamp < ?2)
+             GROUP BY tool HAVING calls > 0 ORDER BY calls DESC, tool",
+        )?;
+        let tools = statement
+            .query_map((since, until), |row| Ok(ToolCount { tool: row.get(0)?, calls: row.get(1)?, errors: row.get(2)? }))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tools)
+    }
+
+    /// The `limit` sessions with the most events within `range`, busiest first
+    pub fn busiest_sessions(&self, range: &TimeRange, limit: usize) -> Result<Vec<SessionActivity>, Box<dyn Error>> {
+        let (since, until) = bounds(range);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT session_id, COUNT(*) AS events,
+                SUM(event = 'UserPromptSubmit'), SUM(event = 'PreToolUse'),
+                MIN(timestamp), MAX(timestamp), MAX(cwd)
+             FROM events
+             WHERE session_id != '' AND timestamp >= ?1 AND (?2 IS NULL OR timestamp < ?2)
+             GROUP BY session_id ORDER BY events DESC, session_id LIMIT ?3",
+        )?;
+        let sessions = statement
+            .query_map((since, until, limit as i64), |row| {
+                Ok(SessionActivity {
+                    session_id: row.get(0)?,
+                    events: row.get(1)?,
+                    prompts: row.get(2)?,
+                    tools: row.get(3)?,
+                    first_event_at: row.get::<_, String>(4)?.parse().unwrap_or_default(),
+                    last_event_at: row.get::<_, String>(5)?.parse().unwrap_or_default(),
+                    cwd: row.get(6)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(sessions)
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self
+            .connection
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2) ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+}
+
+/// Delete the database so the next open rebuilds it from the logs and transcripts
+pub fn reset(path: &Path) -> Result<(), Box<dyn Error>> {
+    for suffix in ["", "-wal", "-shm"] {
+        let file = PathBuf::from(format!("{}{}", path.display(), suffix));
+        match fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether a hook event reports a failure: a tool result marked as an error, or a
+/// notification rated `error` (such as a usage limit)
+fn is_error(input: &serde_json::Value, alerts: &AlertsConfig) -> bool {
+    match input["hook_event_name"].as_str() {
+        Some("PostToolUse") => {
+            let response = &input["tool_response"];
+            response["is_error"] == true || response["success"] == false || !response["error"].is_null()
+        }
+        Some("Notification") => input["message"].as_str().is_some_and(|message| alerts.classify(message) == Severity::Error),
+        _ => false,
+    }
+}
+
+/// A response's row key: its message and request IDs, or where and when it was
+/// recorded when the transcript has no IDs
+fn usage_key(record: &UsageRecord) -> String {
+    record.dedup_key.clone().unwrap_or_else(|| {
+        format!(
+            "{}:{}:{}",
+            record.session_id.as_deref().unwrap_or_default(),
+            sortable_time(record.timestamp),
+            record.model
+        )
+    })
+}
+
+/// Range bounds as stored timestamps
+fn bounds(range: &TimeRange) -> (String, Option<String>) {
+    (range.since.map(sortable_time).unwrap_or_default(), range.until.map(sortable_time))
+}
+
+/// Whether a file exists and was modified at or after `since` (always, without one)
+fn modified_since(path: &Path, since: Option<SystemTime>) -> bool {
+    let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return false;
+    };
+    since.is_none_or(|since| modified >= since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::JsonlStore;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ccgadget-metrics-db-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn record(key: &str, timestamp: &str, model: &str, output_tokens: u64) -> UsageRecord {
+        UsageRecord {
+            timestamp: timestamp.parse().unwrap(),
+            session_id: Some("abc".to_string()),
+            cwd: Some("/work/api".to_string()),
+            model: model.to_string(),
+            usage: TokenUsage { output_tokens, ..Default::default() },
+            dedup_key: Some(key.to_string()),
+            project: None,
+        }
+    }
+
+    #[test]
+    fn test_usage_is_stored_once_and_rolled_up() {
+        let dir = temp_dir("usage");
+        let db = MetricsDb::open_at(&dir.join("metrics.db")).unwrap();
+        let records = vec![
+            record("m1:r1", "2025-07-15T12:00:00Z", "claude-sonnet-4", 100),
+            record("m2:r2", "2025-07-15T12:05:00Z", "claude-sonnet-4", 50),
+            record("m3:r3", "2025-07-16T12:00:00Z", "claude-opus-4", 10),
+        ];
+        assert_eq!(db.insert_usage(&records).unwrap(), 3);
+        // Transcripts are read again when they change; known responses are skipped
+        assert_eq!(db.insert_usage(&records).unwrap(), 0);
+
+        let since = TimeRange { since: Some("2025-07-15T12:01:00Z".parse().unwrap()), until: None };
+        let stored = db.usage(&since).unwrap();
+        assert_eq!(stored.iter().map(|r| r.usage.output_tokens).collect::<Vec<_>>(), vec![50, 10]);
+        assert_eq!(stored[0], records[1]);
+
+        let daily = db.daily(None, None).unwrap();
+        assert_eq!(daily.len(), 2);
+        assert_eq!((daily[0].project.as_str(), daily[0].responses, daily[0].usage.output_tokens), ("/work/api", 2, 150));
+
+        // Reopening applies no migration twice
+        drop(db);
+        let db = MetricsDb::open_at(&dir.join("metrics.db")).unwrap();
+        assert_eq!(db.usage(&TimeRange::default()).unwrap().len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tool_calls_sessions_and_errors_are_counted() {
+        let dir = temp_dir("tools");
+        let store = JsonlStore::new(&dir);
+        let tool = |ts: &str, session: &str, event: &str, name: &str, response: &str| {
+            format!(
+                r#"{{"timestamp":"2025-07-15T12:00:{}Z","hook_input":{{"hook_event_name":"{}","session_id":"{}","tool_name":"{}","tool_response":{}}}}}"#,
+                ts, event, session, name, response
+            )
+        };
+        for line in [
+            tool("01", "abc", "PreToolUse", "Bash", "null"),
+            tool("02", "abc", "PostToolUse", "Bash", r#"{"is_error": true}"#),
+            tool("03", "abc", "PreToolUse", "Bash", "null"),
+            tool("04", "abc", "PostToolUse", "Bash", r#"{"stdout": "ok"}"#),
+            tool("05", "def", "PreToolUse", "Read", "null"),
+            r#"{"timestamp":"2025-07-15T12:00:06Z","hook_input":{"hook_event_name":"Notification","session_id":"def","message":"Claude usage limit reached"}}"#.to_string(),
+        ] {
+            store.append(&line).unwrap();
+        }
+        let db = MetricsDb::open_at(&dir.join("metrics.db")).unwrap();
+        db.sync_events(&store, &AlertsConfig::default()).unwrap();
+
+        let all = TimeRange::default();
+        let tools = db.tool_counts(&all).unwrap();
+        assert_eq!(tools[0], ToolCount { tool: "Bash".to_string(), calls: 2, errors: 1 });
+        assert_eq!((tools[1].tool.as_str(), tools[1].calls), ("Read", 1));
+        assert_eq!(
+            db.error_counts(&all).unwrap(),
+            BTreeMap::from([("Notification".to_string(), 1), ("PostToolUse".to_string(), 1)])
+        );
+        let sessions = db.busiest_sessions(&all, 1).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].session_id.as_str(), sessions[0].events, sessions[0].tools), ("abc", 4, 2));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_events_and_sessions_sync_from_history() {
+        let dir = temp_dir("sync");
+        let store = JsonlStore::new(&dir);
+        let entry = |ts: &str, event: &str| {
+            format!(r#"{{"timestamp":"{}","hook_input":{{"hook_event_name":"{}","session_id":"abc"}}}}"#, ts, event)
+        };
+        let alerts = AlertsConfig::default();
+        store.append(&entry("2025-07-15T12:00:00Z", "UserPromptSubmit")).unwrap();
+        store.append(&entry("2025-07-15T12:01:00Z", "Stop")).unwrap();
+        let db = MetricsDb::open_at(&dir.join("metrics.db")).unwrap();
+        assert_eq!(db.sync_events(&store, &alerts).unwrap(), 2);
+        store.append(&entry("2025-07-15T12:02:00Z", "Stop")).unwrap();
+        assert_eq!(db.sync_events(&store, &alerts).unwrap(), 1);
+        let counts = db.event_counts(&TimeRange::default()).unwrap();
+        assert_eq!(counts, BTreeMap::from([("Stop".to_string(), 2), ("UserPromptSubmit".to_string(), 1)]));
+
+        let summary = |ended_at: &str, prompts: u64| SessionSummary {
+            session_id: "abc".to_string(),
+            started_at: "2025-07-15T12:00:00Z".parse().unwrap(),
+            ended_at: ended_at.parse().unwrap(),
+            minutes: 1.0,
+            prompts,
+            tools: 0,
+            tokens: 0,
+            cost_usd: 0.0,
+            cwd: None,
+        };
+        crate::session::append_history(&dir, &summary("2025-07-15T12:01:00Z", 1)).unwrap();
+        crate::session::append_history(&dir, &summary("2025-07-15T12:02:00Z", 2)).unwrap();
+        assert_eq!(db.sync_sessions(&dir).unwrap(), 2);
+        assert_eq!(db.sync_sessions(&dir).unwrap(), 0);
+        let sessions = db.sessions(&TimeRange::default()).unwrap();
+        assert_eq!(sessions, vec![summary("2025-07-15T12:02:00Z", 2)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
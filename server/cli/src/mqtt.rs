@@ -0,0 +1,236 @@
+use crate::config::MqttConfig;
+use crate::homeassistant::{self, SensorState};
+use crate::transport::{Transport, TransportFuture};
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+/// Longest wait for the broker to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keepalive interval negotiated with the broker
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Where the broker is and whether to use TLS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl Broker {
+    /// Parse `mqtt://host[:port]` or `mqtts://host[:port]` (TLS); a bare host is plain MQTT
+    pub fn parse(url: &str) -> Result<Self, Box<dyn Error>> {
+        let (tls, rest) = match url.split_once("://") {
+            Some(("mqtts" | "ssl", rest)) => (true, rest),
+            Some(("mqtt" | "tcp", rest)) => (false, rest),
+            Some((scheme, _)) => return Err(format!("Unsupported broker scheme `{}`; use mqtt:// or mqtts://", scheme).into()),
+            None => (false, url),
+        };
+        let rest = rest.trim_end_matches('/');
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("Invalid port in {}", url))?),
+            None => (rest, if tls { 8883 } else { 1883 }),
+        };
+        if host.is_empty() {
+            return Err(format!("No host in {}", url).into());
+        }
+        Ok(Self { host: host.to_string(), port, tls })
+    }
+}
+
+/// Topic a frame is published on: `<topic>/<event>`, e.g. `ccgadget/Usage`
+pub fn frame_topic(base: &str, frame: &[u8]) -> String {
+    let event = serde_json::from_slice::<serde_json::Value>(frame)
+        .ok()
+        .and_then(|frame| frame["event"].as_str().map(str::to_string))
+        .unwrap_or_else(|| "frame".to_string());
+    format!("{}/{}", base.trim_end_matches('/'), event)
+}
+
+/// Client options for the configured broker, credentials, and TLS files
+fn options(config: &MqttConfig) -> Result<MqttOptions, Box<dyn Error>> {
+    let url = config.broker.as_deref().ok_or("No MQTT broker configured; set mqtt.broker")?;
+    let broker = Broker::parse(url)?;
+    let client_id = config.client_id.clone().unwrap_or_else(|| format!("ccgadget-{}", std::process::id()));
+    let mut options = MqttOptions::new(client_id, broker.host, broker.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    if config.homeassistant {
+        // Home Assistant shows the gadget unavailable once the broker loses us
+        let will = LastWill::new(homeassistant::availability_topic(config), homeassistant::OFFLINE, QoS::AtLeastOnce, true);
+        options.set_last_will(will);
+    }
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    if broker.tls {
+        let client_auth = match (&config.client_cert, &config.client_key) {
+            (Some(cert), Some(key)) => Some((std::fs::read(cert)?, std::fs::read(key)?)),
+            (None, None) => None,
+            _ => return Err("mqtt.client_cert and mqtt.client_key must be set together".into()),
+        };
+        options.set_transport(match &config.ca_file {
+            Some(ca) => rumqttc::Transport::tls(std::fs::read(ca)?, client_auth, None),
+            None if client_auth.is_none() => rumqttc::Transport::tls_with_default_config(),
+            None => return Err("Client certificates need mqtt.ca_file".into()),
+        });
+    }
+    Ok(options)
+}
+
+/// Publishes frames to an MQTT broker for the gadget (or Home Assistant) to subscribe to
+pub struct Mqtt {
+    config: MqttConfig,
+    client: Option<AsyncClient>,
+    /// Drives the connection in the background
+    driver: Option<JoinHandle<()>>,
+    /// Why the background connection stopped, once it has
+    failure: Arc<Mutex<Option<String>>>,
+    /// What the Home Assistant sensors were last sent
+    sensors: Mutex<SensorState>,
+}
+
+impl Mqtt {
+    /// Transport for the configured broker
+    pub fn new(config: MqttConfig) -> Self {
+        Self { config, client: None, driver: None, failure: Arc::new(Mutex::new(None)), sensors: Mutex::default() }
+    }
+
+    /// Announce the gadget's sensors to Home Assistant and mark it available
+    async fn announce(&self, client: &AsyncClient) -> Result<(), Box<dyn Error>> {
+        for (topic, entity) in homeassistant::discovery(&self.config) {
+            client.publish(topic, QoS::AtLeastOnce, true, serde_json::to_vec(&entity)?).await?;
+        }
+        client.publish(homeassistant::availability_topic(&self.config), QoS::AtLeastOnce, true, homeassistant::ONLINE).await?;
+        let state = serde_json::to_vec(&*self.sensors.lock().unwrap())?;
+        client.publish(homeassistant::state_topic(&self.config), QoS::AtLeastOnce, true, state).await?;
+        Ok(())
+    }
+
+    /// Poll the event loop until the broker accepts the connection
+    async fn await_connack(events: &mut EventLoop) -> Result<(), Box<dyn Error>> {
+        loop {
+            if let Event::Incoming(Packet::ConnAck(_)) = events.poll().await? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Transport for Mqtt {
+    fn name(&self) -> &'static str {
+        "mqtt"
+    }
+
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let options = options(&self.config)?;
+            debug!("Connecting to MQTT broker {}:{}", options.broker_address().0, options.broker_address().1);
+            let (client, mut events) = AsyncClient::new(options, 16);
+            timeout(CONNECT_TIMEOUT, Self::await_connack(&mut events))
+                .await
+                .map_err(|_| "MQTT broker did not accept the connection in time")??;
+
+            let failure = self.failure.clone();
+            *failure.lock().unwrap() = None;
+            self.driver = Some(tokio::spawn(async move {
+                loop {
+                    if let Err(e) = events.poll().await {
+                        warn!("MQTT connection lost: {}", e);
+                        *failure.lock().unwrap() = Some(e.to_string());
+                        return;
+                    }
+                }
+            }));
+            if self.config.homeassistant {
+                self.announce(&client).await?;
+                debug!("Published Home Assistant discovery under {}", self.config.discovery_prefix);
+            }
+            self.client = Some(client);
+            Ok(())
+        })
+    }
+
+    /// Publish at least once; usage frames are retained so new subscribers see the
+    /// latest values immediately
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            if let Some(failure) = self.failure.lock().unwrap().clone() {
+                return Err(format!("MQTT connection lost: {}", failure).into());
+            }
+            let client = self.client.as_ref().ok_or("MQTT transport is not connected")?;
+            let topic = frame_topic(&self.config.topic, frame);
+            let retain = topic.ends_with("/Usage");
+            client.publish(topic, QoS::AtLeastOnce, retain, frame.to_vec()).await?;
+            if self.config.homeassistant {
+                let state = {
+                    let mut sensors = self.sensors.lock().unwrap();
+                    sensors.apply(frame).then(|| serde_json::to_vec(&*sensors)).transpose()?
+                };
+                if let Some(state) = state {
+                    client.publish(homeassistant::state_topic(&self.config), QoS::AtLeastOnce, true, state).await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Brokers carry frames one way; the gadget's state is not available
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(client) = self.client.take() {
+                if self.config.homeassistant {
+                    let offline = homeassistant::availability_topic(&self.config);
+                    let _ = client.publish(offline, QoS::AtLeastOnce, true, homeassistant::OFFLINE).await;
+                }
+                let _ = client.disconnect().await;
+            }
+            if let Some(driver) = self.driver.take() {
+                driver.abort();
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_broker() {
+        assert_eq!(
+            Broker::parse("mqtts://broker.lan").unwrap(),
+            Broker { host: "broker.lan".to_string(), port: 8883, tls: true }
+        );
+        assert_eq!(Broker::parse("192.168.1.5:1884").unwrap().port, 1884);
+        assert!(!Broker::parse("mqtt://192.168.1.5").unwrap().tls);
+        assert!(Broker::parse("http://broker.lan").is_err());
+    }
+
+    #[test]
+    fn test_frames_publish_under_their_event() {
+        assert_eq!(frame_topic("ccgadget/desk/", br#"{"event":"Usage"}"#), "ccgadget/desk/Usage");
+        assert_eq!(frame_topic("ccgadget", b"not json"), "ccgadget/frame");
+    }
+
+    #[test]
+    fn test_tls_options_need_matching_files() {
+        let config = MqttConfig {
+            broker: Some("mqtts://broker.lan".to_string()),
+            client_cert: Some("/tmp/client.pem".into()),
+            ..Default::default()
+        };
+        assert!(options(&config).unwrap_err().to_string().contains("together"));
+        assert!(options(&MqttConfig::default()).unwrap_err().to_string().contains("mqtt.broker"));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::OnEventConfig;
+use crate::payload::DevicePayload;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+/// Runs user commands configured under `[on_event]` after events are processed
+#[derive(Clone)]
+pub struct EventCommands {
+    config: Arc<OnEventConfig>,
+    permits: Arc<Semaphore>,
+}
+
+impl EventCommands {
+    /// Build a runner enforcing the configured concurrency limit
+    pub fn new(config: OnEventConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.max_concurrent.max(1)));
+        Self { config: Arc::new(config), permits }
+    }
+
+    /// Start the command configured for the payload's event, if any, without waiting for it
+    pub fn dispatch(&self, payload: &DevicePayload) {
+        let Some(command) = self.config.commands.get(&payload.event).cloned() else {
+            return;
+        };
+        let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+            warn!(
+                "on_event.{}: {} command(s) already running; skipped",
+                payload.event, self.config.max_concurrent
+            );
+            return;
+        };
+        let input = payload.to_bytes();
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        let event = payload.event.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run(&command, &input, timeout).await {
+                warn!("on_event.{} failed: {}", event, e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// Run a shell command with the payload on stdin, killing it after `timeout`
+pub async fn run(command: &str, input: &[u8], timeout: Duration) -> Result<(), String> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that ignores its input may close stdin early; that is fine
+        let _ = stdin.write_all(input).await;
+    }
+
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(format!("exited with {}", status)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => {
+            let _ = child.kill().await;
+            Err(format!("timed out after {}s", timeout.as_secs()))
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_passes_payload_and_times_out() {
+        assert!(run("grep -q Stop", br#"{"event":"Stop"}"#, Duration::from_secs(5)).await.is_ok());
+        assert!(run("grep -q Stop", br#"{"event":"Usage"}"#, Duration::from_secs(5)).await.is_err());
+
+        let err = run("sleep 5", b"", Duration::from_millis(100)).await.unwrap_err();
+        assert!(err.contains("timed out"));
+    }
+}
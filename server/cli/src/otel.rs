@@ -0,0 +1,414 @@
+use crate::config::OtelConfig;
+use crate::http::{self, Endpoint};
+use crate::payload::DevicePayload;
+use chrono::{DateTime, Duration as Elapsed, Utc};
+use serde_json::{json, Value};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Longest wait for the collector to accept a batch
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A session quiet for this long is ended, so its trace's root span is exported
+const SESSION_IDLE_MINUTES: i64 = 30;
+
+/// OTLP span kind for work inside the process
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// OTLP cumulative aggregation temporality
+const CUMULATIVE: u8 = 2;
+
+/// OTLP attribute list from string pairs
+fn attributes<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Value {
+    pairs
+        .into_iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+/// Nanoseconds since the epoch, as OTLP JSON writes 64-bit integers
+fn nanos(time: DateTime<Utc>) -> String {
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+/// Trace id for a session: its UUID when it is one, otherwise a hash of the id
+fn trace_id(session_id: &str) -> String {
+    if let Ok(uuid) = uuid::Uuid::parse_str(session_id) {
+        return uuid.simple().to_string();
+    }
+    let half = |seed: u8| {
+        let mut hasher = DefaultHasher::new();
+        (seed, session_id).hash(&mut hasher);
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(0), half(1))
+}
+
+/// A span that has started, and its events so far
+#[derive(Debug, Clone)]
+struct OpenSpan {
+    span_id: String,
+    parent_id: Option<String>,
+    name: String,
+    start: DateTime<Utc>,
+    attributes: Vec<(String, String)>,
+    /// Notifications raised while the span was open, as (time, message)
+    events: Vec<(DateTime<Utc>, String)>,
+}
+
+impl OpenSpan {
+    /// The finished span in OTLP JSON
+    fn end(self, trace_id: &str, end: DateTime<Utc>) -> Value {
+        let events: Vec<Value> = self
+            .events
+            .iter()
+            .map(|(at, message)| {
+                json!({
+                    "timeUnixNano": nanos(*at),
+                    "name": "notification",
+                    "attributes": attributes([("message", message.as_str())]),
+                })
+            })
+            .collect();
+        json!({
+            "traceId": trace_id,
+            "spanId": self.span_id,
+            "parentSpanId": self.parent_id.unwrap_or_default(),
+            "name": self.name,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": nanos(self.start),
+            "endTimeUnixNano": nanos(end.max(self.start)),
+            "attributes": attributes(self.attributes.iter().map(|(key, value)| (key.as_str(), value.as_str()))),
+            "events": events,
+        })
+    }
+}
+
+/// Open spans of one session: the session itself, the current turn, and tool calls
+/// still running
+#[derive(Debug)]
+struct SessionTrace {
+    trace_id: String,
+    root: OpenSpan,
+    turn: Option<OpenSpan>,
+    tools: Vec<OpenSpan>,
+    last_event: DateTime<Utc>,
+}
+
+impl SessionTrace {
+    /// End the running tool calls and the turn at `at`
+    fn end_turn(&mut self, at: DateTime<Utc>, finished: &mut Vec<Value>) {
+        for tool in self.tools.drain(..) {
+            finished.push(tool.end(&self.trace_id, at));
+        }
+        if let Some(turn) = self.turn.take() {
+            finished.push(turn.end(&self.trace_id, at));
+        }
+    }
+}
+
+/// Turns hook events into spans and counters: each session is a trace, each prompt a
+/// `turn` span under it, and each tool call a span from `PreToolUse` to `PostToolUse`
+#[derive(Debug)]
+pub struct Tracer {
+    sessions: HashMap<String, SessionTrace>,
+    /// Spans ended since the last export
+    finished: Vec<Value>,
+    /// Events seen, by event and tool
+    counts: BTreeMap<(String, Option<String>), u64>,
+    started_at: DateTime<Utc>,
+    ids: RandomState,
+    next_id: u64,
+}
+
+impl Tracer {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            finished: Vec::new(),
+            counts: BTreeMap::new(),
+            started_at: now,
+            ids: RandomState::new(),
+            next_id: 0,
+        }
+    }
+
+    /// A fresh random span id
+    fn span_id(&mut self) -> String {
+        self.next_id += 1;
+        let mut hasher = self.ids.build_hasher();
+        hasher.write_u64(self.next_id);
+        format!("{:016x}", hasher.finish().max(1))
+    }
+
+    /// A span starting at `start` under `parent_id`
+    fn open(&mut self, name: &str, parent_id: Option<String>, start: DateTime<Utc>, attributes: Vec<(String, String)>) -> OpenSpan {
+        OpenSpan { span_id: self.span_id(), parent_id, name: name.to_string(), start, attributes, events: Vec::new() }
+    }
+
+    /// Count a hook event and update its session's spans
+    pub fn record(&mut self, payload: &DevicePayload) {
+        *self.counts.entry((payload.event.clone(), payload.tool.clone())).or_default() += 1;
+        let Some(session_id) = payload.session_id.clone() else {
+            return;
+        };
+        let at = payload.timestamp;
+        if !self.sessions.contains_key(&session_id) {
+            let root = self.open("session", None, at, vec![("session.id".to_string(), session_id.clone())]);
+            let trace = SessionTrace { trace_id: trace_id(&session_id), root, turn: None, tools: Vec::new(), last_event: at };
+            self.sessions.insert(session_id.clone(), trace);
+        }
+        let parent_id = {
+            let trace = &self.sessions[&session_id];
+            trace.turn.as_ref().unwrap_or(&trace.root).span_id.clone()
+        };
+        let root_id = self.sessions[&session_id].root.span_id.clone();
+        let new_span = match payload.event.as_str() {
+            "UserPromptSubmit" => Some(self.open("turn", Some(root_id), at, Vec::new())),
+            "PreToolUse" => {
+                let tool = payload.tool.clone().unwrap_or_else(|| "tool".to_string());
+                let mut attributes = vec![("tool.name".to_string(), tool.clone())];
+                if let Some(activity) = payload.activity {
+                    attributes.push(("activity".to_string(), activity.to_string()));
+                }
+                Some(self.open(&tool, Some(parent_id), at, attributes))
+            }
+            _ => None,
+        };
+
+        let trace = self.sessions.get_mut(&session_id).expect("session just ensured");
+        trace.last_event = at;
+        match payload.event.as_str() {
+            "UserPromptSubmit" => {
+                trace.end_turn(at, &mut self.finished);
+                trace.turn = new_span;
+            }
+            "PreToolUse" => trace.tools.extend(new_span),
+            "PostToolUse" => {
+                let position = trace.tools.iter().position(|tool| payload.tool.as_deref().is_none_or(|name| tool.name == name));
+                if let Some(tool) = position.map(|index| trace.tools.remove(index)) {
+                    self.finished.push(tool.end(&trace.trace_id, at));
+                }
+            }
+            "Notification" => {
+                let message = payload.message.clone().unwrap_or_default();
+                trace.turn.as_mut().unwrap_or(&mut trace.root).events.push((at, message));
+            }
+            "Stop" => trace.end_turn(at, &mut self.finished),
+            "SessionEnd" => self.end_session(&session_id, at),
+            _ => {}
+        }
+    }
+
+    /// End a session's trace, its root span last
+    fn end_session(&mut self, session_id: &str, at: DateTime<Utc>) {
+        if let Some(mut trace) = self.sessions.remove(session_id) {
+            trace.end_turn(at, &mut self.finished);
+            self.finished.push(trace.root.end(&trace.trace_id, at));
+        }
+    }
+
+    /// Spans ended since the last call, ending sessions idle since before `now`, as
+    /// an OTLP traces request; `None` when there are none
+    pub fn take_spans(&mut self, now: DateTime<Utc>, resource: &Value) -> Option<Value> {
+        let idle: Vec<(String, DateTime<Utc>)> = self
+            .sessions
+            .iter()
+            .filter(|(_, trace)| now - trace.last_event >= Elapsed::minutes(SESSION_IDLE_MINUTES))
+            .map(|(id, trace)| (id.clone(), trace.last_event))
+            .collect();
+        for (session_id, last_event) in idle {
+            self.end_session(&session_id, last_event);
+        }
+        if self.finished.is_empty() {
+            return None;
+        }
+        let spans = std::mem::take(&mut self.finished);
+        Some(json!({
+            "resourceSpans": [{
+                "resource": resource,
+                "scopeSpans": [{ "scope": scope(), "spans": spans }],
+            }]
+        }))
+    }
+
+    /// Cumulative event counts as an OTLP metrics request; `None` before any event
+    pub fn metrics(&self, now: DateTime<Utc>, resource: &Value) -> Option<Value> {
+        if self.counts.is_empty() {
+            return None;
+        }
+        let points: Vec<Value> = self
+            .counts
+            .iter()
+            .map(|((event, tool), count)| {
+                let mut pairs = vec![("hook.event", event.as_str())];
+                pairs.extend(tool.as_deref().map(|tool| ("tool.name", tool)));
+                json!({
+                    "attributes": attributes(pairs),
+                    "startTimeUnixNano": nanos(self.started_at),
+                    "timeUnixNano": nanos(now),
+                    "asInt": count.to_string(),
+                })
+            })
+            .collect();
+        Some(json!({
+            "resourceMetrics": [{
+                "resource": resource,
+                "scopeMetrics": [{
+                    "scope": scope(),
+                    "metrics": [{
+                        "name": "ccgadget.hook.events",
+                        "description": "Claude Code hook events received, by event and tool",
+                        "unit": "{event}",
+                        "sum": { "aggregationTemporality": CUMULATIVE, "isMonotonic": true, "dataPoints": points },
+                    }],
+                }],
+            }]
+        }))
+    }
+}
+
+/// Instrumentation scope of everything exported
+fn scope() -> Value {
+    json!({ "name": "ccgadget", "version": env!("CARGO_PKG_VERSION") })
+}
+
+/// Sends the daemon's hook events to an OpenTelemetry collector over OTLP/HTTP (JSON)
+#[derive(Clone)]
+pub struct Exporter {
+    endpoint: Endpoint,
+    resource: Arc<Value>,
+    interval: Duration,
+    tracer: Arc<Mutex<Tracer>>,
+}
+
+impl Exporter {
+    /// Exporter for the configured collector; `None` when no endpoint is set
+    pub fn new(config: &OtelConfig) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some(url) = &config.endpoint else {
+            return Ok(None);
+        };
+        let mut pairs = vec![("service.name", config.service_name.as_str())];
+        pairs.extend(config.resource_attributes.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+        Ok(Some(Self {
+            endpoint: Endpoint::parse(url)?,
+            resource: Arc::new(json!({ "attributes": attributes(pairs) })),
+            interval: Duration::from_secs(config.export_interval_seconds.max(1)),
+            tracer: Arc::new(Mutex::new(Tracer::new(Utc::now()))),
+        }))
+    }
+
+    /// Record a hook event for the next export
+    pub fn record(&self, payload: &DevicePayload) {
+        self.tracer.lock().unwrap().record(payload);
+    }
+
+    /// Export finished spans and the event counters every interval, in the background
+    pub fn start(&self) {
+        let exporter = self.clone();
+        info!("Exporting OpenTelemetry traces and metrics to {}:{}", exporter.endpoint.host, exporter.endpoint.port);
+        tokio::spawn(async move {
+            let mut failing = false;
+            loop {
+                tokio::time::sleep(exporter.interval).await;
+                let now = Utc::now();
+                let (spans, metrics) = {
+                    let mut tracer = exporter.tracer.lock().unwrap();
+                    (tracer.take_spans(now, &exporter.resource), tracer.metrics(now, &exporter.resource))
+                };
+                let batches = [("/v1/traces", spans), ("/v1/metrics", metrics)];
+                let mut result = Ok(());
+                for (path, body) in batches.iter().filter_map(|(path, body)| Some((path, body.as_ref()?))) {
+                    result = result.and(exporter.post(path, body).await);
+                }
+                // Spans that could not be sent are dropped; the counters are cumulative
+                match result {
+                    Ok(()) if failing => {
+                        info!("OpenTelemetry export recovered");
+                        failing = false;
+                    }
+                    Ok(()) => {}
+                    Err(e) if !failing => {
+                        warn!("OpenTelemetry export failed: {}", e);
+                        failing = true;
+                    }
+                    Err(e) => debug!("OpenTelemetry export still failing: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Post one OTLP request
+    async fn post(&self, path: &str, body: &Value) -> Result<(), String> {
+        let body = serde_json::to_vec(body).map_err(|e| e.to_string())?;
+        let response = http::request(&self.endpoint, "POST", path, Some(&body), EXPORT_TIMEOUT)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.is_success() {
+            return Err(format!("{} answered {}", path, response.status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap as Widgets;
+
+    const SESSION: &str = "3451fab2-1c2d-4e5f-8a9b-0c1d2e3f4a5b";
+
+    fn event(name: &str, tool: Option<&str>, second: i64) -> DevicePayload {
+        let mut payload = DevicePayload::usage(Widgets::new());
+        payload.event = name.to_string();
+        payload.session_id = Some(SESSION.to_string());
+        payload.tool = tool.map(str::to_string);
+        payload.timestamp = "2025-07-20T12:00:00Z".parse::<DateTime<Utc>>().unwrap() + Elapsed::seconds(second);
+        payload
+    }
+
+    #[test]
+    fn test_session_turn_and_tool_spans() {
+        let mut tracer = Tracer::new(Utc::now());
+        let resource = json!({ "attributes": attributes([("service.name", "ccgadget")]) });
+        tracer.record(&event("UserPromptSubmit", None, 0));
+        tracer.record(&event("PreToolUse", Some("Bash"), 1));
+        tracer.record(&event("PostToolUse", Some("Bash"), 3));
+        tracer.record(&event("PreToolUse", Some("Edit"), 4));
+        tracer.record(&event("Stop", None, 5));
+
+        let soon = event("", None, 60).timestamp;
+        let batch = tracer.take_spans(soon, &resource).unwrap();
+        let spans = batch["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap();
+        let names: Vec<&str> = spans.iter().map(|span| span["name"].as_str().unwrap()).collect();
+        // The unfinished Edit call ends with the turn
+        assert_eq!(names, vec!["Bash", "Edit", "turn"]);
+        assert_eq!(spans[0]["traceId"], "3451fab21c2d4e5f8a9b0c1d2e3f4a5b");
+        assert_eq!(spans[0]["parentSpanId"], spans[2]["spanId"]);
+        assert_eq!(spans[0]["endTimeUnixNano"], nanos(event("", None, 3).timestamp));
+        assert!(tracer.take_spans(soon, &resource).is_none());
+
+        // An idle session ends, exporting its root span
+        let batch = tracer.take_spans(Utc::now(), &resource).unwrap();
+        let root = &batch["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!((root["name"].as_str(), root["parentSpanId"].as_str()), (Some("session"), Some("")));
+        assert_eq!(spans[2]["parentSpanId"], root["spanId"]);
+
+        let metrics = tracer.metrics(Utc::now(), &resource).unwrap();
+        let points = &metrics["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0]["sum"]["dataPoints"];
+        assert_eq!(points.as_array().unwrap().len(), 5);
+        assert_eq!(points[0]["attributes"][1]["value"]["stringValue"], "Bash");
+    }
+
+    #[test]
+    fn test_trace_ids_for_other_session_ids() {
+        assert_eq!(trace_id("not-a-uuid").len(), 32);
+        assert_eq!(trace_id("not-a-uuid"), trace_id("not-a-uuid"));
+        assert!(Exporter::new(&OtelConfig::default()).unwrap().is_none());
+    }
+}
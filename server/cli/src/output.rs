@@ -0,0 +1,178 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether output should be rendered as plain, screen-reader-friendly text
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Whether regular (non-error) output is suppressed by `--quiet`
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Whether command results are printed as JSON (`--output json`)
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// How command results are printed
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON document on stdout; progress messages go to stderr
+    Json,
+}
+
+/// Emoji that carry meaning, and the words that replace them in accessible mode
+const LABELS: &[(char, &str)] = &[
+    ('✅', "OK:"),
+    ('❌', "Error:"),
+    ('⚠', "Warning:"),
+    ('ℹ', "Note:"),
+    ('💡', "Tip:"),
+    ('⏳', "Waiting:"),
+];
+
+/// Enable or disable accessible output for the rest of the process
+pub fn set_accessible(enabled: bool) {
+    ACCESSIBLE.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether accessible output is enabled
+pub fn accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// Suppress or restore regular output for the rest of the process
+pub fn set_quiet(enabled: bool) {
+    QUIET.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether regular output is suppressed
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Select the result format for the rest of the process
+pub fn set_format(format: OutputFormat) {
+    JSON.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+/// Whether results are printed as JSON
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Print a command result as JSON on stdout
+pub fn emit<T: Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Could not serialize output: {}", e),
+    }
+}
+
+/// Print a message line; on stderr when stdout carries JSON
+pub fn message(text: &str) {
+    if json() {
+        eprintln!("{}", render(text));
+    } else {
+        println!("{}", render(text));
+    }
+}
+
+/// Print a message without a newline (for prompts); on stderr when stdout carries JSON
+pub fn message_inline(text: &str) {
+    use std::io::Write;
+    if json() {
+        eprint!("{}", render(text));
+        let _ = std::io::stderr().flush();
+    } else {
+        print!("{}", render(text));
+    }
+}
+
+/// Render a line for the terminal, converting it to plain text in accessible mode
+pub fn render(text: &str) -> String {
+    if accessible() {
+        plain(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether a character is a pictograph or emoji modifier with no plain-text meaning
+fn is_decorative(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x2300..=0x23FF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x1F000..=0x1FAFF | 0xFE0F | 0x200D
+    )
+}
+
+/// Replace meaningful emoji with words and drop purely decorative ones
+pub fn plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().filter(|&c| c != '\u{FE0F}' && c != '\u{200D}').peekable();
+    while let Some(c) = chars.next() {
+        if let Some((_, label)) = LABELS.iter().find(|(emoji, _)| *emoji == c) {
+            out.push_str(label);
+        } else if is_decorative(c) {
+            // Drop the separating space too, so "🔗 Linked" becomes "Linked"
+            if chars.peek() == Some(&' ') {
+                chars.next();
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    // Messages that already spell out the label ("ℹ️ Note: ...") should not repeat it
+    for (_, label) in LABELS {
+        out = out.replace(&format!("{} {}", label, label), label);
+    }
+    out
+}
+
+/// `println!` that honours accessible, quiet, and JSON output mode
+#[macro_export]
+macro_rules! say {
+    () => {
+        if !$crate::output::quiet() {
+            $crate::output::message("")
+        }
+    };
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            $crate::output::message(&format!($($arg)*))
+        }
+    };
+}
+
+/// `eprintln!` that honours accessible mode
+#[macro_export]
+macro_rules! esay {
+    () => {
+        eprintln!()
+    };
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::output::render(&format!($($arg)*)))
+    };
+}
+
+/// `print!` that honours accessible and JSON output mode (for prompts)
+#[macro_export]
+macro_rules! say_inline {
+    ($($arg:tt)*) => {
+        $crate::output::message_inline(&format!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_replaces_and_strips_emoji() {
+        assert_eq!(plain("   ✅ Daemon started"), "   OK: Daemon started");
+        assert_eq!(plain("   ⚠️ Device connection failed"), "   Warning: Device connection failed");
+        assert_eq!(plain("🔍 Scanning for devices..."), "Scanning for devices...");
+        assert_eq!(plain("   ℹ️ Daemon not running"), "   Note: Daemon not running");
+        assert_eq!(plain("   ℹ️ Note: simulated"), "   Note: simulated");
+        assert_eq!(plain("Cost: $1.20 (3 tokens)"), "Cost: $1.20 (3 tokens)");
+    }
+}
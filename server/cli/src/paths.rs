@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory name used under each platform base directory
+const APP_DIR: &str = "ccgadget";
+
+/// Resolve the current user's home directory. Windows uses `USERPROFILE` first, since
+/// Git Bash and MSYS set `HOME` to a path other Windows programs do not use.
+pub fn home_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let (first, second) = if cfg!(windows) { ("USERPROFILE", "HOME") } else { ("HOME", "USERPROFILE") };
+    let home_dir = std::env::var(first)
+        .or_else(|_| std::env::var(second))
+        .map_err(|_| "Could not determine home directory")?;
+    Ok(PathBuf::from(home_dir))
+}
+
+/// Single directory everything lived in before XDG support (~/.ccgadget)
+pub fn legacy_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(home_dir()?.join(".ccgadget"))
+}
+
+/// Directory for configuration: `$XDG_CONFIG_HOME/ccgadget` (~/.config/ccgadget),
+/// the platform config directory on macOS and Windows
+pub fn config_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let base = dirs::config_dir().ok_or("Could not determine config directory")?;
+    Ok(base.join(APP_DIR))
+}
+
+/// Directory for state: logs, session totals, paired devices, imported usage, and the daemon socket.
+/// `$XDG_STATE_HOME/ccgadget` (~/.local/state/ccgadget); the local data directory on
+/// platforms without a state directory
+pub fn state_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let base = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or("Could not determine state directory")?;
+    Ok(base.join(APP_DIR))
+}
+
+/// Daily trigger logs and the background daemon log
+pub fn logs_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(state_dir()?.join("logs"))
+}
+
+/// Running totals per Claude Code session, one file each
+pub fn sessions_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(state_dir()?.join("sessions"))
+}
+
+/// SQLite database of events, sessions, and usage rollups behind `usage` and `stats`
+pub fn metrics_db() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(state_dir()?.join("metrics.db"))
+}
+
+/// Claude Code configuration directory (CLAUDE_CONFIG_DIR or ~/.claude)
+pub fn claude_dir() -> Result<PathBuf, Box<dyn Error>> {
+    match std::env::var("CLAUDE_CONFIG_DIR") {
+        Ok(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok(home_dir()?.join(".claude")),
+    }
+}
+
+/// Directory for regenerable cached data: `$XDG_CACHE_HOME/ccgadget` (~/.cache/ccgadget)
+pub fn cache_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let base = dirs::cache_dir().ok_or("Could not determine cache directory")?;
+    Ok(base.join(APP_DIR))
+}
+
+/// Move a file or directory, copying when a rename is not possible (e.g. across filesystems)
+fn move_path(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)?.flatten() {
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from)?;
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Relocate the legacy ~/.ccgadget directory into the config, state, and cache
+/// directories. Entries already present at the destination are left in place. Returns
+/// the moves made; the legacy directory is removed once empty.
+pub fn migrate_legacy() -> Result<Vec<(PathBuf, PathBuf)>, Box<dyn Error>> {
+    let legacy = legacy_dir()?;
+    if !legacy.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut moved = Vec::new();
+    for entry in fs::read_dir(&legacy)?.flatten() {
+        let name = entry.file_name();
+        let destination = match name.to_str() {
+            // A socket belongs to whichever daemon is running; it is recreated on start
+            Some("daemon.sock") => continue,
+            Some("config.toml") => config_dir()?.join(&name),
+            Some("cache") => cache_dir()?,
+            _ => state_dir()?.join(&name),
+        };
+        if destination.exists() && !destination.is_dir() {
+            continue;
+        }
+        if destination.is_dir() {
+            // Merge into an existing directory entry by entry
+            for inner in fs::read_dir(entry.path())?.flatten() {
+                let target = destination.join(inner.file_name());
+                if !target.exists() {
+                    move_path(&inner.path(), &target)?;
+                    moved.push((inner.path(), target));
+                }
+            }
+            let _ = fs::remove_dir(entry.path());
+            continue;
+        }
+        move_path(&entry.path(), &destination)?;
+        moved.push((entry.path(), destination));
+    }
+    let _ = fs::remove_dir(&legacy);
+    Ok(moved)
+}
+
+/// Every location the tool reads or writes, for `ccgadget paths` and packagers
+#[cfg(feature = "full")]
+#[derive(serde::Serialize, Debug)]
+pub struct ToolPaths {
+    pub config: PathBuf,
+    pub state: PathBuf,
+    pub logs: PathBuf,
+    pub sessions: PathBuf,
+    pub metrics_db: PathBuf,
+    pub cache: PathBuf,
+    pub device: PathBuf,
+    /// Unix socket path, or localhost address where sockets are unavailable
+    pub socket: String,
+    pub service_unit: Option<PathBuf>,
+    pub completions: Vec<PathBuf>,
+}
+
+#[cfg(feature = "full")]
+impl ToolPaths {
+    /// Resolve all paths for the current user and platform
+    pub fn resolve() -> Result<Self, Box<dyn Error>> {
+        #[cfg(unix)]
+        let socket = crate::ipc::socket_path()?.display().to_string();
+        #[cfg(not(unix))]
+        let socket = crate::ipc::ipc_address();
+
+        Ok(Self {
+            config: crate::config::Config::path()?,
+            state: state_dir()?,
+            logs: logs_dir()?,
+            sessions: sessions_dir()?,
+            metrics_db: metrics_db()?,
+            cache: cache_dir()?,
+            device: crate::device::PairedDevice::path()?,
+            socket,
+            service_unit: crate::install::service_unit_path()?,
+            completions: crate::install::completion_paths()?
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect(),
+        })
+    }
+
+    /// Labelled rows for human-readable output
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![
+            ("Config", self.config.display().to_string()),
+            ("State", self.state.display().to_string()),
+            ("Logs", self.logs.display().to_string()),
+            ("Sessions", self.sessions.display().to_string()),
+            ("Metrics DB", self.metrics_db.display().to_string()),
+            ("Cache", self.cache.display().to_string()),
+            ("Device", self.device.display().to_string()),
+            ("Socket", self.socket.clone()),
+            (
+                "Service unit",
+                self.service_unit
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "(not supported on this platform)".to_string()),
+            ),
+        ];
+        rows.extend(self.completions.iter().map(|path| ("Completions", path.display().to_string())));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_path_moves_directories() {
+        let base = std::env::temp_dir().join(format!("ccgadget-paths-{}", std::process::id()));
+        let from = base.join("legacy").join("logs");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("trigger-2025-07-20.log"), "{}\n").unwrap();
+
+        let to = base.join("state").join("logs");
+        move_path(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(to.join("trigger-2025-07-20.log")).unwrap(), "{}\n");
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+use crate::burn::BurnSnapshot;
+use crate::config::{Activity, AlertStyle, DisplayPage};
+use crate::context::ContextUsage;
+use crate::device::{LinkMode, LinkPreferences};
+use crate::focus::SessionsAggregate;
+use crate::ratelimit::LimitWarning;
+use crate::session::SessionSummary;
+use crate::settings::{DeviceSettings, SETTINGS_EVENT};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Event name of maintenance control frames
+pub const MAINTENANCE_EVENT: &str = "Maintenance";
+
+/// Event name of control frames sending the gadget to its idle screen ("idle") or back
+/// to the metrics ("active")
+pub const PRESENCE_EVENT: &str = "Presence";
+
+/// Control frame switching the gadget's display page
+pub const PAGE_EVENT: &str = "Page";
+
+/// Control frame showing ("on") or clearing ("off") the gadget's do-not-disturb icon
+pub const DND_EVENT: &str = "DoNotDisturb";
+
+/// Event name of clock synchronisation frames
+pub const TIME_SYNC_EVENT: &str = "TimeSync";
+
+/// Event name of frames dismissing the alert on the gadget
+pub const ACKNOWLEDGE_EVENT: &str = "Acknowledge";
+
+/// Event name of ad-hoc text sent with `display message`
+pub const MESSAGE_EVENT: &str = "Message";
+
+/// Event name of frames making the gadget buzz or flash for a notification
+pub const ALERT_EVENT: &str = "Alert";
+
+/// Event name of frames summing up a session when Claude finishes responding
+pub const SESSION_COMPLETE_EVENT: &str = "SessionComplete";
+
+/// Event name of high-priority frames telling the gadget Claude is blocked until the
+/// user grants or denies a permission request
+pub const PERMISSION_EVENT: &str = "PermissionRequest";
+
+/// Event name of frames warning that a cost or token budget reached a threshold
+pub const BUDGET_EVENT: &str = "Budget";
+
+/// Event name of the frame summing up what happened while the gadget was offline, sent
+/// in place of the frames held for it
+pub const CATCH_UP_EVENT: &str = "CatchUp";
+
+/// Longest ad-hoc message, in characters; the screen scrolls what does not fit
+pub const MAX_MESSAGE_CHARS: usize = 120;
+
+/// How urgent a frame is; the gadget picks colours (and later alerts) by it
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Whether a notification message is Claude asking for permission, e.g. "Claude needs
+/// your permission to use Bash"
+pub fn is_permission_request(message: &str) -> bool {
+    message.to_lowercase().contains("permission")
+}
+
+/// Tool named in a permission request ("… permission to use Bash")
+pub fn permission_tool(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once("permission to use ")?;
+    let tool = rest.split_whitespace().next()?.trim_end_matches(['.', ',', '!']);
+    (!tool.is_empty()).then(|| tool.to_string())
+}
+
+/// Compact frame sent to the device for display
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DevicePayload {
+    pub timestamp: DateTime<Utc>,
+    /// When the daemon received or created the frame, in milliseconds on its monotonic
+    /// clock; unlike `timestamp` this is unaffected by system clock changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mono_ms: Option<u64>,
+    /// Hook event name, or "Usage" for periodic daemon updates
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Sent by a session other than the one driving the primary screen; the gadget
+    /// shows it without switching screens
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub background: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// What Claude is busy with, for the activity icon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<Activity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Summary of the submitted prompt, when `prompts.send` is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Whether `prompt` was cut to fit the character budget
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub prompt_truncated: bool,
+    /// Values of the metrics assigned to device widgets
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub widgets: BTreeMap<String, f64>,
+    /// Burn rate and limit projection for the active usage block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burn: Option<BurnSnapshot>,
+    /// Connection parameters the gadget should request (sent with "LinkMode" frames)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkPreferences>,
+    /// Settings for the gadget to store and apply (sent with "Settings" frames)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<DeviceSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// Seconds to keep `message` on screen; the gadget drops it once `timestamp` plus
+    /// this has passed, even if it arrives late
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+    /// How to buzz and flash (sent with "Alert" frames)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert: Option<AlertStyle>,
+    /// Earlier tool events from the same session folded into this one while they
+    /// waited for the link
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub merged: u32,
+    /// Totals for the session (sent with "SessionComplete" frames)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<SessionSummary>,
+    /// How full the session's context window is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<ContextUsage>,
+    /// Rate or usage limit Claude hit; `duration_secs` counts down to its reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<LimitWarning>,
+    /// Every active session at once, sent with "Usage" frames while several are running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<SessionsAggregate>,
+}
+
+fn is_zero(count: &u32) -> bool {
+    *count == 0
+}
+
+impl DevicePayload {
+    /// Periodic usage update carrying only widget values
+    pub fn usage(widgets: BTreeMap<String, f64>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            mono_ms: None,
+            event: "Usage".to_string(),
+            session_id: None,
+            background: false,
+            tool: None,
+            activity: None,
+            message: None,
+            prompt: None,
+            prompt_truncated: false,
+            widgets,
+            burn: None,
+            link: None,
+            settings: None,
+            severity: None,
+            duration_secs: None,
+            alert: None,
+            merged: 0,
+            summary: None,
+            context: None,
+            limit: None,
+            sessions: None,
+        }
+    }
+
+    /// Control frame telling the gadget which connection parameters to request
+    pub fn link_mode(mode: LinkMode) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = "LinkMode".to_string();
+        payload.message = Some(mode.as_str().to_string());
+        payload.link = Some(mode.preferences());
+        payload
+    }
+
+    /// Control frame entering ("on") or leaving ("off") the gadget's maintenance screen
+    pub fn maintenance(enabled: bool) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = MAINTENANCE_EVENT.to_string();
+        payload.message = Some(if enabled { "on" } else { "off" }.to_string());
+        payload
+    }
+
+    /// Control frame telling the gadget whether Claude Code is in use: "idle" lets it dim
+    /// or show a clock, "active" brings back the metrics screen
+    pub fn presence(idle: bool) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = PRESENCE_EVENT.to_string();
+        payload.message = Some(if idle { "idle" } else { "active" }.to_string());
+        payload
+    }
+
+    /// Control frame showing or clearing the gadget's do-not-disturb icon
+    pub fn dnd(enabled: bool) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = DND_EVENT.to_string();
+        payload.message = Some(if enabled { "on" } else { "off" }.to_string());
+        payload
+    }
+
+    /// Control frame switching the gadget to `page` for now, leaving the default page in
+    /// its stored settings as it is
+    pub fn page(page: DisplayPage) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = PAGE_EVENT.to_string();
+        payload.message = Some(page.to_string());
+        payload
+    }
+
+    /// Control frame setting the gadget's clock to the host time; `message` carries the
+    /// host's UTC offset so the clock can show local time
+    pub fn time_sync() -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = TIME_SYNC_EVENT.to_string();
+        payload.message = Some(chrono::Local::now().format("%:z").to_string());
+        payload
+    }
+
+    /// Control frame dismissing the alert the gadget is showing
+    pub fn acknowledge() -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = ACKNOWLEDGE_EVENT.to_string();
+        payload
+    }
+
+    /// Ad-hoc text for the gadget to show for `duration_secs`, cut to
+    /// [`MAX_MESSAGE_CHARS`]
+    pub fn screen_message(text: &str, duration_secs: u64, severity: Severity) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = MESSAGE_EVENT.to_string();
+        payload.message = Some(text.trim().chars().take(MAX_MESSAGE_CHARS).collect());
+        payload.severity = Some(severity);
+        payload.duration_secs = Some(duration_secs);
+        payload
+    }
+
+    /// Frame making the gadget buzz or flash, in `style`, for a classified notification
+    pub fn alert(notification: &DevicePayload, severity: Severity, style: AlertStyle) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = ALERT_EVENT.to_string();
+        payload.session_id = notification.session_id.clone();
+        payload.message = notification.message.clone();
+        payload.severity = Some(severity);
+        payload.alert = Some(style);
+        payload
+    }
+
+    /// Frame making the gadget buzz or flash, in `style`, for a budget threshold
+    pub fn budget_alert(message: String, severity: Severity, style: AlertStyle) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = BUDGET_EVENT.to_string();
+        payload.message = Some(message);
+        payload.severity = Some(severity);
+        payload.alert = Some(style);
+        payload
+    }
+
+    /// Frame telling the gadget a session's turn is complete, with its totals
+    pub fn session_complete(summary: SessionSummary) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = SESSION_COMPLETE_EVENT.to_string();
+        payload.session_id = Some(summary.session_id.clone());
+        payload.message = Some(summary.line());
+        payload.summary = Some(summary);
+        payload
+    }
+
+    /// Frame telling the gadget Claude is waiting for permission, with the tool it
+    /// asked for, buzzing and flashing in `style`
+    pub fn permission_request(notification: &DevicePayload, style: AlertStyle) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = PERMISSION_EVENT.to_string();
+        payload.session_id = notification.session_id.clone();
+        payload.message = notification.message.clone();
+        payload.tool = notification.message.as_deref().and_then(permission_tool);
+        payload.severity = Some(Severity::Warn);
+        payload.alert = Some(style);
+        payload
+    }
+
+    /// Frame summing up `missed` frames held while the gadget was offline for
+    /// `offline_secs`, e.g. "Offline 12m: 9 PreToolUse, 2 Stop, 1 Alert"; `merged`
+    /// counts them
+    pub fn catch_up(missed: &[DevicePayload], offline_secs: u64) -> Self {
+        let mut counts: Vec<(&str, u32)> = Vec::new();
+        for frame in missed {
+            match counts.iter_mut().find(|(event, _)| *event == frame.event) {
+                Some((_, count)) => *count += 1 + frame.merged,
+                None => counts.push((&frame.event, 1 + frame.merged)),
+            }
+        }
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let offline = match offline_secs {
+            secs if secs >= 3600 => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+            secs if secs >= 60 => format!("{}m", secs / 60),
+            secs => format!("{}s", secs),
+        };
+        let counted: Vec<String> = counts.iter().take(3).map(|(event, count)| format!("{} {}", count, event)).collect();
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = CATCH_UP_EVENT.to_string();
+        payload.message = Some(format!("Offline {}: {}", offline, counted.join(", ")));
+        payload.merged = counts.iter().map(|(_, count)| count).sum();
+        payload.session_id = missed.iter().rev().find_map(|frame| frame.session_id.clone());
+        payload
+    }
+
+    /// Control frame storing display settings on the gadget
+    pub fn settings(settings: DeviceSettings) -> Self {
+        let mut payload = Self::usage(BTreeMap::new());
+        payload.event = SETTINGS_EVENT.to_string();
+        payload.settings = Some(settings);
+        payload
+    }
+
+    /// Whether the frame's `duration_secs` ran out before `now`, so showing it would mislead
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.duration_secs
+            .is_some_and(|secs| self.timestamp + chrono::Duration::seconds(secs as i64) < now)
+    }
+
+    /// Serialize for transmission over the device link
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+}
@@ -0,0 +1,143 @@
+use crate::transcript::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// USD rates per million tokens for a model
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+impl ModelPricing {
+    /// Estimated USD cost of the given token usage at these rates
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let per_token = |rate: f64, tokens: u64| rate * tokens as f64 / 1_000_000.0;
+        per_token(self.input, usage.input_tokens)
+            + per_token(self.output, usage.output_tokens)
+            + per_token(self.cache_write, usage.cache_creation_input_tokens)
+            + per_token(self.cache_read, usage.cache_read_input_tokens)
+    }
+}
+
+/// Partial price override from the config file; unset fields keep the base rate
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PriceOverride {
+    pub input: Option<f64>,
+    pub output: Option<f64>,
+    pub cache_write: Option<f64>,
+    pub cache_read: Option<f64>,
+}
+
+impl PriceOverride {
+    /// Apply this override on top of a base price
+    fn apply(&self, base: ModelPricing) -> ModelPricing {
+        ModelPricing {
+            input: self.input.unwrap_or(base.input),
+            output: self.output.unwrap_or(base.output),
+            cache_write: self.cache_write.unwrap_or(base.cache_write),
+            cache_read: self.cache_read.unwrap_or(base.cache_read),
+        }
+    }
+}
+
+/// Pricing lookup table keyed by model family or model ID
+#[derive(Debug, Clone)]
+pub struct PricingTable {
+    entries: BTreeMap<String, ModelPricing>,
+}
+
+impl PricingTable {
+    /// Published Anthropic API list prices
+    pub fn builtin() -> Self {
+        let mut entries = BTreeMap::new();
+        entries.insert(
+            "opus".to_string(),
+            ModelPricing { input: 15.0, output: 75.0, cache_write: 18.75, cache_read: 1.5 },
+        );
+        entries.insert(
+            "sonnet".to_string(),
+            ModelPricing { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.3 },
+        );
+        entries.insert(
+            "haiku".to_string(),
+            ModelPricing { input: 0.8, output: 4.0, cache_write: 1.0, cache_read: 0.08 },
+        );
+        Self { entries }
+    }
+
+    /// Built-in prices with config overrides applied (e.g. enterprise pricing)
+    pub fn with_overrides(overrides: &BTreeMap<String, PriceOverride>) -> Self {
+        let mut table = Self::builtin();
+        for (key, price_override) in overrides {
+            let key = key.to_lowercase();
+            let base = table.lookup(&key).unwrap_or(ModelPricing {
+                input: 0.0,
+                output: 0.0,
+                cache_write: 0.0,
+                cache_read: 0.0,
+            });
+            table.entries.insert(key, price_override.apply(base));
+        }
+        table
+    }
+
+    /// Find pricing for a model ID; the longest matching key wins so model IDs beat families
+    pub fn lookup(&self, model: &str) -> Option<ModelPricing> {
+        let model = model.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|(key, _)| model.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, pricing)| *pricing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_family() {
+        let table = PricingTable::builtin();
+        let sonnet = table.lookup("claude-sonnet-4-20250514").unwrap();
+        assert_eq!(sonnet.input, 3.0);
+        let opus = table.lookup("claude-opus-4-1-20250805").unwrap();
+        assert_eq!(opus.output, 75.0);
+        assert!(table.lookup("gpt-4").is_none());
+    }
+
+    #[test]
+    fn test_cost_calculation() {
+        let pricing = PricingTable::builtin().lookup("claude-sonnet-4").unwrap();
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            cache_creation_input_tokens: 1_000_000,
+            cache_read_input_tokens: 1_000_000,
+        };
+        assert!((pricing.cost(&usage) - 22.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_overrides_by_family_and_model() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("opus".to_string(), PriceOverride { input: Some(10.0), ..Default::default() });
+        overrides.insert(
+            "claude-sonnet-4-20250514".to_string(),
+            PriceOverride { output: Some(12.0), ..Default::default() },
+        );
+        let table = PricingTable::with_overrides(&overrides);
+
+        let opus = table.lookup("claude-opus-4").unwrap();
+        assert_eq!(opus.input, 10.0);
+        assert_eq!(opus.output, 75.0);
+
+        let pinned = table.lookup("claude-sonnet-4-20250514").unwrap();
+        assert_eq!(pinned.output, 12.0);
+        assert_eq!(pinned.input, 3.0);
+        assert_eq!(table.lookup("claude-3-7-sonnet").unwrap().output, 15.0);
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Nearest ancestor of `dir` (including itself) that is a git repository root
+pub fn git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Works out which project a response belongs to, caching git root lookups
+#[derive(Default)]
+pub struct ProjectResolver {
+    roots: HashMap<PathBuf, PathBuf>,
+}
+
+impl ProjectResolver {
+    /// Project root for a directory: its git root, or the directory itself
+    pub fn root_of(&mut self, dir: &Path) -> PathBuf {
+        if let Some(root) = self.roots.get(dir) {
+            return root.clone();
+        }
+        let root = git_root(dir).unwrap_or_else(|| dir.to_path_buf());
+        self.roots.insert(dir.to_path_buf(), root.clone());
+        root
+    }
+
+    /// Project for a response made in `cwd` that touched `files`. Files outside the
+    /// working directory's project (another workspace root) take precedence; when they
+    /// span several projects, the one touched most wins.
+    pub fn attribute(&mut self, cwd: Option<&str>, files: &[String]) -> Option<String> {
+        let cwd = cwd.map(PathBuf::from);
+        let cwd_root = cwd.as_deref().map(|cwd| self.root_of(cwd));
+
+        let mut elsewhere: BTreeMap<PathBuf, usize> = BTreeMap::new();
+        for file in files {
+            let file = match &cwd {
+                Some(cwd) if Path::new(file).is_relative() => cwd.join(file),
+                _ => PathBuf::from(file),
+            };
+            if file.is_relative() || cwd_root.as_ref().is_some_and(|root| file.starts_with(root)) {
+                continue;
+            }
+            if let Some(dir) = file.parent() {
+                *elsewhere.entry(self.root_of(dir)).or_default() += 1;
+            }
+        }
+
+        let busiest = elsewhere.into_iter().max_by_key(|(_, count)| *count).map(|(root, _)| root);
+        busiest
+            .or(cwd_root)
+            .map(|root| root.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subdirectories_resolve_to_git_root() {
+        let base = std::env::temp_dir().join(format!("ccgadget-project-{}", std::process::id()));
+        let repo = base.join("api");
+        fs_setup(&repo);
+        let mut resolver = ProjectResolver::default();
+
+        let nested = repo.join("src").join("handlers");
+        assert_eq!(resolver.root_of(&nested), repo);
+        let attributed = resolver.attribute(nested.to_str(), &["lib.rs".to_string()]);
+        assert_eq!(attributed.as_deref(), repo.to_str());
+
+        // Edits in a second workspace root outweigh the session's working directory
+        let other = base.join("web");
+        fs_setup(&other);
+        let files = vec![other.join("index.ts").display().to_string(), other.join("app.ts").display().to_string()];
+        assert_eq!(resolver.attribute(repo.to_str(), &files).as_deref(), other.to_str());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    /// Create a git-like repository directory with a nested source tree
+    fn fs_setup(repo: &Path) {
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("src").join("handlers")).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+/// Placeholder for fenced code blocks, which are unreadable on the device screen
+const CODE_PLACEHOLDER: &str = "[code]";
+
+/// Prompt text fitted to the device's character budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptSummary {
+    pub text: String,
+    /// Whether anything beyond whitespace and code blocks was cut
+    pub truncated: bool,
+}
+
+/// Replace fenced code blocks with a placeholder and collapse all whitespace
+fn condense(prompt: &str) -> String {
+    let mut kept = Vec::new();
+    let mut in_code = false;
+    for line in prompt.lines() {
+        if line.trim_start().starts_with("```") {
+            if !in_code {
+                kept.push(CODE_PLACEHOLDER);
+            }
+            in_code = !in_code;
+        } else if !in_code {
+            kept.push(line);
+        }
+    }
+    kept.join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Fit a prompt into `max_chars` characters, cutting at a word boundary and marking
+/// the cut with "…". A single word longer than half the budget is cut mid-word instead
+/// of leaving little to read.
+pub fn summarize(prompt: &str, max_chars: usize) -> PromptSummary {
+    let text = condense(prompt);
+    if text.chars().count() <= max_chars {
+        return PromptSummary { text, truncated: false };
+    }
+
+    // Leave room for the ellipsis
+    let budget = max_chars.saturating_sub(1);
+    let head: String = text.chars().take(budget + 1).collect();
+    let cut = match head.rfind(' ') {
+        Some(space) if head[..space].chars().count() >= budget / 2 => &head[..space],
+        _ => &head[..head.char_indices().nth(budget).map_or(head.len(), |(i, _)| i)],
+    };
+    let cut = cut.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | ':' | '-'));
+    PromptSummary { text: format!("{}…", cut), truncated: true }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_prompts_pass_through_condensed() {
+        let summary = summarize("Fix the\n\n  failing   test", 40);
+        assert_eq!(summary, PromptSummary { text: "Fix the failing test".to_string(), truncated: false });
+
+        let summary = summarize("Why does this panic?\n```rust\nlet x = v[10];\n```\nThanks", 40);
+        assert_eq!(summary.text, "Why does this panic? [code] Thanks");
+        assert!(!summary.truncated);
+    }
+
+    #[test]
+    fn test_long_prompts_cut_at_word_boundary() {
+        let summary = summarize("Refactor the storage layer, then update every caller", 30);
+        assert_eq!(summary.text, "Refactor the storage layer…");
+        assert!(summary.truncated);
+        assert!(summary.text.chars().count() <= 30);
+
+        // Exactly at the budget is not truncated
+        assert!(!summarize("abcde fghij", 11).truncated);
+    }
+
+    #[test]
+    fn test_long_words_are_cut_mid_word() {
+        let summary = summarize("see https://example.com/a/very/long/path/to/something", 20);
+        assert_eq!(summary.text, "see https://example…");
+        assert_eq!(summary.text.chars().count(), 20);
+
+        // Multi-byte characters are counted, not bytes
+        let summary = summarize(&"é".repeat(30), 10);
+        assert_eq!(summary.text.chars().count(), 10);
+    }
+}
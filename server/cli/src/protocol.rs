@@ -0,0 +1,477 @@
+use crate::payload::{
+    DevicePayload, ACKNOWLEDGE_EVENT, DND_EVENT, MAINTENANCE_EVENT, PAGE_EVENT, PRESENCE_EVENT, TIME_SYNC_EVENT,
+};
+use crate::settings::SETTINGS_EVENT;
+use serde::de::DeserializeOwned;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{Read, Write};
+use uuid::Uuid;
+
+/// GATT service every CCGadget advertises, used to recognise devices while scanning
+pub const SERVICE_UUID: Uuid = uuid::uuid!("12345678-1234-5678-9abc-123456789abc");
+
+/// GATT characteristic the device exposes for display payloads
+pub const PAYLOAD_CHARACTERISTIC_UUID: Uuid = uuid::uuid!("87654321-4321-8765-cba9-987654321abc");
+
+/// GATT characteristic the device notifies button presses and status changes on
+pub const EVENT_CHARACTERISTIC_UUID: Uuid = uuid::uuid!("87654322-4321-8765-cba9-987654321abc");
+
+/// Read-only diagnostic characteristics: uptime in seconds and free heap in bytes
+/// (both `u32` little-endian), and the display type and Wi-Fi status as UTF-8 text
+pub const UPTIME_CHARACTERISTIC_UUID: Uuid = uuid::uuid!("87654323-4321-8765-cba9-987654321abc");
+pub const FREE_MEMORY_CHARACTERISTIC_UUID: Uuid = uuid::uuid!("87654324-4321-8765-cba9-987654321abc");
+pub const DISPLAY_TYPE_CHARACTERISTIC_UUID: Uuid = uuid::uuid!("87654325-4321-8765-cba9-987654321abc");
+pub const WIFI_STATUS_CHARACTERISTIC_UUID: Uuid = uuid::uuid!("87654326-4321-8765-cba9-987654321abc");
+
+/// Service UUIDs identifying a CCGadget in advertisements, current firmware first
+pub const SERVICE_UUIDS: &[Uuid] = &[SERVICE_UUID];
+
+/// Whether an advertised service UUID belongs to a CCGadget
+pub fn is_ccgadget_service(uuid: &Uuid) -> bool {
+    SERVICE_UUIDS.contains(uuid)
+}
+
+/// Company identifier under which the firmware advertises its manufacturer data block
+/// (0xFFFF, reserved for devices without an assigned Bluetooth SIG identifier)
+pub const VENDOR_ID: u16 = 0xFFFF;
+
+/// Layout version of the manufacturer data block this CLI understands
+const ADVERTISEMENT_FORMAT: u8 = 1;
+
+/// Capability bits in the manufacturer data block, with their names
+const CAPABILITIES: [(u8, &str); 4] = [(0x01, "display"), (0x02, "buttons"), (0x04, "wifi"), (0x08, "battery")];
+
+/// What a device announces about itself in its advertisement, before connecting
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AdvertisedInfo {
+    /// Firmware version, e.g. `1.2.0`
+    pub firmware: String,
+    /// Hardware features the device reports, e.g. `display`, `buttons`
+    pub capabilities: Vec<String>,
+}
+
+/// Parse the manufacturer data block advertised under [`VENDOR_ID`]:
+/// `[format, major, minor, patch, capability bits]`. Later formats may append
+/// fields; blocks in an unknown format or too short are ignored.
+pub fn parse_manufacturer_data(data: &[u8]) -> Option<AdvertisedInfo> {
+    let [format, major, minor, patch, bits, ..] = *data else {
+        return None;
+    };
+    if format != ADVERTISEMENT_FORMAT {
+        return None;
+    }
+    Some(AdvertisedInfo {
+        firmware: format!("{}.{}.{}", major, minor, patch),
+        capabilities: CAPABILITIES
+            .iter()
+            .filter(|(bit, _)| bits & bit != 0)
+            .map(|(_, name)| name.to_string())
+            .collect(),
+    })
+}
+
+/// Protocol version whose frames are bare JSON documents, spoken by firmware that
+/// predates the handshake
+pub const JSON_VERSION: u8 = 1;
+
+/// First protocol version with CBOR frames behind a [`FRAME_MAGIC`] header
+pub const BINARY_VERSION: u8 = 2;
+
+/// First protocol version with chunked frames and compressed bodies
+pub const CHUNKED_VERSION: u8 = 3;
+
+/// First protocol version where the device acknowledges every numbered frame
+pub const ACKED_VERSION: u8 = 4;
+
+/// Newest protocol version this CLI speaks
+pub const PROTOCOL_VERSION: u8 = ACKED_VERSION;
+
+/// Oldest protocol version this CLI still speaks
+pub const MIN_PROTOCOL_VERSION: u8 = JSON_VERSION;
+
+/// First byte of a binary frame; JSON frames start with `{`
+pub const FRAME_MAGIC: u8 = 0xCC;
+
+/// Bit set in the kind byte when the body is zlib-compressed CBOR
+const COMPRESSED: u8 = 0x80;
+
+/// Bodies larger than this many bytes are compressed, if that makes them smaller
+pub const COMPRESS_ABOVE: usize = 256;
+
+/// Header of a chunk: magic, version, kind, message, index, count
+const CHUNK_HEADER: usize = 6;
+
+/// Header of a frame: magic, version, kind
+const FRAME_HEADER: usize = 3;
+
+/// Bytes of big-endian sequence number after the header of a numbered frame
+const SEQUENCE_LEN: usize = 4;
+
+/// What a binary frame carries, from its third header byte
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameKind {
+    /// Protocol handshake
+    Hello = 0,
+    /// Periodic usage figures
+    Metrics = 1,
+    /// A hook event to show
+    Notification = 2,
+    /// Control frames: clock, link mode, maintenance, settings
+    Command = 3,
+    /// The device's state report
+    State = 4,
+    /// Part of a frame too large for one write
+    Chunk = 5,
+    /// The device confirming receipt of a numbered frame, or reassembly of a chunked one
+    Ack = 6,
+    /// A button press or status change the device reports on its own
+    Event = 7,
+}
+
+impl FrameKind {
+    /// Kind of the frame carrying a payload with this event name
+    pub fn for_event(event: &str) -> Self {
+        match event {
+            "Usage" => FrameKind::Metrics,
+            "LinkMode" | TIME_SYNC_EVENT | MAINTENANCE_EVENT | PRESENCE_EVENT | DND_EVENT | PAGE_EVENT | SETTINGS_EVENT | ACKNOWLEDGE_EVENT => {
+                FrameKind::Command
+            }
+            _ => FrameKind::Notification,
+        }
+    }
+
+    /// Whether frames of this kind carry a sequence number from [`ACKED_VERSION`] on
+    pub fn is_numbered(&self) -> bool {
+        matches!(self, FrameKind::Metrics | FrameKind::Notification | FrameKind::Command)
+    }
+
+    /// Kind with the given header byte
+    fn from_byte(byte: u8) -> Option<Self> {
+        [
+            FrameKind::Hello,
+            FrameKind::Metrics,
+            FrameKind::Notification,
+            FrameKind::Command,
+            FrameKind::State,
+            FrameKind::Chunk,
+            FrameKind::Ack,
+            FrameKind::Event,
+        ]
+        .into_iter()
+        .find(|kind| *kind as u8 == byte & !COMPRESSED)
+    }
+}
+
+/// Handshake frame: the range of protocol versions the sender speaks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hello {
+    /// Newest version the sender speaks
+    pub version: u8,
+    /// Oldest version the sender still speaks
+    pub min_version: u8,
+    /// Sender's software, e.g. `ccgadget/0.5.0` or `firmware/1.3.0`
+    pub agent: String,
+    /// ATT MTU the device negotiated, which sizes chunks; BLE devices only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u16>,
+}
+
+impl Hello {
+    /// What this CLI offers
+    pub fn ours() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            min_version: MIN_PROTOCOL_VERSION,
+            agent: format!("ccgadget/{}", env!("CARGO_PKG_VERSION")),
+            mtu: None,
+        }
+    }
+}
+
+/// What the handshake settled on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Agreement {
+    pub version: u8,
+    /// ATT MTU the device reported, if any
+    pub mtu: Option<u16>,
+}
+
+/// The device confirming receipt of a frame
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Ack {
+    /// Message number from the chunk headers, for a reassembled chunked frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<u8>,
+    /// Sequence number of a numbered frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u32>,
+}
+
+/// Header bytes before the body of a frame of `kind` at `version`
+fn header_len(version: u8, kind: FrameKind) -> usize {
+    match version >= ACKED_VERSION && kind.is_numbered() {
+        true => FRAME_HEADER + SEQUENCE_LEN,
+        false => FRAME_HEADER,
+    }
+}
+
+/// Binary frame of `kind` at `version`: the three-byte header, then `body` as CBOR,
+/// compressed from [`CHUNKED_VERSION`] on when that pays off
+pub fn frame<T: Serialize>(version: u8, kind: FrameKind, body: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+    numbered_frame(version, kind, 0, body)
+}
+
+/// As [`frame`], with sequence number `seq` after the header when the kind is
+/// numbered at `version`
+pub fn numbered_frame<T: Serialize>(version: u8, kind: FrameKind, seq: u32, body: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut header = vec![FRAME_MAGIC, version, kind as u8];
+    if header_len(version, kind) > FRAME_HEADER {
+        header.extend_from_slice(&seq.to_be_bytes());
+    }
+    let mut cbor = Vec::new();
+    ciborium::into_writer(body, &mut cbor)?;
+    if version >= CHUNKED_VERSION && cbor.len() > COMPRESS_ABOVE {
+        let mut compressed_header = header.clone();
+        compressed_header[2] |= COMPRESSED;
+        let mut encoder = ZlibEncoder::new(compressed_header, Compression::default());
+        encoder.write_all(&cbor)?;
+        let compressed = encoder.finish()?;
+        if compressed.len() < cbor.len() + header.len() {
+            return Ok(compressed);
+        }
+    }
+    header.extend_from_slice(&cbor);
+    Ok(header)
+}
+
+/// A payload as sent at the agreed protocol `version`, numbered `seq` from
+/// [`ACKED_VERSION`] on
+pub fn encode(version: u8, seq: u32, payload: &DevicePayload) -> Result<Vec<u8>, Box<dyn Error>> {
+    if version < BINARY_VERSION {
+        return Ok(payload.to_bytes());
+    }
+    numbered_frame(version, FrameKind::for_event(&payload.event), seq, payload)
+}
+
+/// Sequence number of a numbered frame, which the device acknowledges
+pub fn sequence(frame: &[u8]) -> Option<u32> {
+    let [FRAME_MAGIC, version, kind, ref rest @ ..] = *frame else {
+        return None;
+    };
+    let kind = FrameKind::from_byte(kind)?;
+    if header_len(version, kind) == FRAME_HEADER {
+        return None;
+    }
+    Some(u32::from_be_bytes(rest.get(..SEQUENCE_LEN)?.try_into().ok()?))
+}
+
+/// Decode a frame in either encoding, with its kind if it is binary
+pub fn decode<T: DeserializeOwned>(frame: &[u8]) -> Result<(Option<FrameKind>, T), Box<dyn Error>> {
+    match *frame {
+        [FRAME_MAGIC, version, kind, ..] => {
+            if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) && kind != FrameKind::Hello as u8 {
+                return Err(format!("Frame uses protocol v{}, which this ccgadget does not speak", version).into());
+            }
+            let compressed = kind & COMPRESSED != 0;
+            let kind = FrameKind::from_byte(kind).ok_or_else(|| format!("Unknown frame kind {}", kind))?;
+            let body = frame.get(header_len(version, kind)..).ok_or("Truncated frame header")?;
+            let body = if compressed {
+                let mut decompressed = Vec::new();
+                ZlibDecoder::new(body).read_to_end(&mut decompressed)?;
+                decompressed
+            } else {
+                body.to_vec()
+            };
+            Ok((Some(kind), ciborium::from_reader(body.as_slice())?))
+        }
+        [FRAME_MAGIC, ..] => Err("Truncated frame header".into()),
+        _ => Ok((None, serde_json::from_slice(frame)?)),
+    }
+}
+
+/// The version to speak with a peer offering `peer`, or why the two cannot talk
+pub fn negotiate(peer: &Hello) -> Result<u8, Box<dyn Error>> {
+    if peer.version < MIN_PROTOCOL_VERSION {
+        return Err(format!(
+            "{} speaks protocol v{}, but this ccgadget needs v{} or later; update the firmware",
+            peer.agent, peer.version, MIN_PROTOCOL_VERSION
+        )
+        .into());
+    }
+    if peer.min_version > PROTOCOL_VERSION {
+        return Err(format!(
+            "{} needs protocol v{} or later, but this ccgadget speaks up to v{}; update ccgadget",
+            peer.agent, peer.min_version, PROTOCOL_VERSION
+        )
+        .into());
+    }
+    Ok(peer.version.min(PROTOCOL_VERSION))
+}
+
+/// What was agreed from the device's answer to our [`Hello`]. No answer, or a JSON
+/// state report, comes from firmware that predates the handshake and speaks JSON.
+pub fn negotiate_reply(reply: Option<&[u8]>) -> Result<Agreement, Box<dyn Error>> {
+    match reply {
+        Some(reply) if reply.first() == Some(&FRAME_MAGIC) => match decode::<Hello>(reply)? {
+            (Some(FrameKind::Hello), hello) => Ok(Agreement { version: negotiate(&hello)?, mtu: hello.mtu }),
+            _ => Err("Device did not answer the protocol handshake".into()),
+        },
+        _ => Ok(Agreement { version: JSON_VERSION, mtu: None }),
+    }
+}
+
+/// Split `frame` into chunks of at most `size` bytes, headers included, for the
+/// device to reassemble. `message` numbers the frame in the chunk headers and in the
+/// device's [`Ack`].
+pub fn chunk(frame: &[u8], version: u8, message: u8, size: usize) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    let room = size.checked_sub(CHUNK_HEADER).filter(|room| *room > 0).ok_or("Write size too small for chunking")?;
+    let count = frame.len().div_ceil(room);
+    let count = u8::try_from(count).map_err(|_| format!("Frame of {} bytes needs more than 255 chunks", frame.len()))?;
+    Ok(frame
+        .chunks(room)
+        .enumerate()
+        .map(|(index, part)| {
+            let mut chunk = vec![FRAME_MAGIC, version, FrameKind::Chunk as u8, message, index as u8, count];
+            chunk.extend_from_slice(part);
+            chunk
+        })
+        .collect())
+}
+
+/// A reassembled frame with its message number
+pub type Message = (u8, Vec<u8>);
+
+/// Device side of chunking: collects the chunks of one message in order
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    message: Option<u8>,
+    next: u8,
+    frame: Vec<u8>,
+}
+
+impl Reassembler {
+    /// Add a chunk; returns the message number and whole frame once its last chunk
+    /// arrives. A chunk of a new message discards an unfinished one.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Option<Message>, Box<dyn Error>> {
+        let [FRAME_MAGIC, _, kind, message, index, count, ref part @ ..] = *chunk else {
+            return Err("Not a chunk".into());
+        };
+        if kind != FrameKind::Chunk as u8 {
+            return Err("Not a chunk".into());
+        }
+        if self.message != Some(message) || index == 0 {
+            *self = Self { message: Some(message), next: 0, frame: Vec::new() };
+        }
+        if index != self.next {
+            return Err(format!("Chunk {} of message {} arrived out of order", index, message).into());
+        }
+        self.frame.extend_from_slice(part);
+        self.next += 1;
+        if self.next < count {
+            return Ok(None);
+        }
+        let frame = std::mem::take(&mut self.frame);
+        *self = Self::default();
+        Ok(Some((message, frame)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manufacturer_data() {
+        let info = parse_manufacturer_data(&[1, 1, 2, 0, 0x0b]).unwrap();
+        assert_eq!(info.firmware, "1.2.0");
+        assert_eq!(info.capabilities, ["display", "buttons", "battery"]);
+
+        // Trailing fields from newer firmware are skipped
+        assert_eq!(parse_manufacturer_data(&[1, 2, 0, 1, 0x00, 0xaa]).unwrap().firmware, "2.0.1");
+        // Unknown format or truncated block
+        assert_eq!(parse_manufacturer_data(&[2, 1, 2, 0, 0x01]), None);
+        assert_eq!(parse_manufacturer_data(&[1, 1, 2]), None);
+    }
+
+    #[test]
+    fn test_binary_frames_round_trip() {
+        let payload = DevicePayload::time_sync();
+        let frame = encode(PROTOCOL_VERSION, 1, &payload).unwrap();
+        assert_eq!(frame[..3], [FRAME_MAGIC, PROTOCOL_VERSION, FrameKind::Command as u8]);
+        assert!(frame.len() < payload.to_bytes().len());
+        assert_eq!(decode::<DevicePayload>(&encode(BINARY_VERSION, 1, &payload).unwrap()).unwrap().1, payload);
+        assert_eq!(decode::<DevicePayload>(&frame).unwrap(), (Some(FrameKind::Command), payload.clone()));
+
+        // Version 1 peers get the JSON they always have
+        let json = encode(JSON_VERSION, 1, &payload).unwrap();
+        assert_eq!(json, payload.to_bytes());
+        assert_eq!(decode::<DevicePayload>(&json).unwrap(), (None, payload));
+
+        assert!(decode::<serde_json::Value>(&[FRAME_MAGIC, 9, FrameKind::State as u8, 0xa0]).is_err());
+    }
+
+    #[test]
+    fn test_handshake_settles_on_a_shared_version() {
+        let firmware = |min_version, version| Hello { version, min_version, agent: "firmware/1.3.0".to_string(), mtu: Some(247) };
+        assert_eq!(negotiate(&firmware(1, 2)).unwrap(), 2);
+        // Newer firmware that still speaks ours
+        assert_eq!(negotiate(&firmware(2, 5)).unwrap(), PROTOCOL_VERSION);
+        let error = negotiate(&firmware(5, 6)).unwrap_err().to_string();
+        assert!(error.contains("needs protocol v5") && error.contains("update ccgadget"), "{}", error);
+
+        let reply = frame(5, FrameKind::Hello, &firmware(2, 5)).unwrap();
+        assert_eq!(negotiate_reply(Some(&reply)).unwrap(), Agreement { version: PROTOCOL_VERSION, mtu: Some(247) });
+        // Firmware without the handshake answers with its JSON state report, or not at all
+        assert_eq!(negotiate_reply(Some(br#"{"timestamp":null}"#)).unwrap().version, JSON_VERSION);
+        assert_eq!(negotiate_reply(None).unwrap().version, JSON_VERSION);
+    }
+
+    #[test]
+    fn test_large_frames_are_compressed_and_chunked() {
+        let mut payload = DevicePayload::usage(Default::default());
+        payload.event = "PostToolUse".to_string();
+        payload.message = Some("cargo test output\n".repeat(100));
+        let frame = encode(PROTOCOL_VERSION, 1, &payload).unwrap();
+        assert_eq!(frame[2], FrameKind::Notification as u8 | COMPRESSED);
+        assert!(frame.len() < 200);
+        assert_eq!(decode::<DevicePayload>(&frame).unwrap().1, payload);
+
+        let chunks = chunk(&frame, PROTOCOL_VERSION, 7, 20).unwrap();
+        assert!(chunks.len() > 1 && chunks.iter().all(|chunk| chunk.len() <= 20));
+        let mut reassembler = Reassembler::default();
+        let (last, rest) = chunks.split_last().unwrap();
+        for part in rest {
+            assert_eq!(reassembler.push(part).unwrap(), None);
+        }
+        assert_eq!(reassembler.push(last).unwrap(), Some((7, frame.clone())));
+
+        // A missing chunk is detected rather than producing a corrupt frame
+        assert!(reassembler.push(&chunks[0]).unwrap().is_none());
+        assert!(reassembler.push(&chunks[2]).is_err());
+        assert!(chunk(&vec![0; 300 * 14], PROTOCOL_VERSION, 0, 20).is_err());
+    }
+
+    #[test]
+    fn test_frames_are_numbered_from_the_acked_version() {
+        let payload = DevicePayload::time_sync();
+        let numbered = encode(ACKED_VERSION, 0x0102_0304, &payload).unwrap();
+        assert_eq!(numbered[..7], [FRAME_MAGIC, ACKED_VERSION, FrameKind::Command as u8, 1, 2, 3, 4]);
+        assert_eq!(sequence(&numbered), Some(0x0102_0304));
+        assert_eq!(decode::<DevicePayload>(&numbered).unwrap().1, payload);
+
+        // Compressed bodies keep the number in the clear
+        let mut large = DevicePayload::usage(Default::default());
+        large.message = Some("cargo test output\n".repeat(100));
+        assert_eq!(sequence(&encode(ACKED_VERSION, 9, &large).unwrap()), Some(9));
+
+        // Older versions, handshakes, and acks are not numbered
+        assert_eq!(sequence(&encode(CHUNKED_VERSION, 9, &payload).unwrap()), None);
+        assert_eq!(sequence(&frame(ACKED_VERSION, FrameKind::Hello, &Hello::ours()).unwrap()), None);
+        let ack = frame(ACKED_VERSION, FrameKind::Ack, &Ack { seq: Some(9), ..Default::default() }).unwrap();
+        assert_eq!(decode::<Ack>(&ack).unwrap().1.seq, Some(9));
+    }
+}
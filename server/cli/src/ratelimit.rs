@@ -0,0 +1,183 @@
+use crate::timerange;
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// How long a warning without a reset time stays current
+const UNTIMED_WARNING_HOURS: i64 = 1;
+
+/// Which limit Claude ran into
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LimitKind {
+    /// Too many requests in a short time (HTTP 429); clears within minutes
+    RateLimit,
+    /// The plan's usage allowance is used up until its window resets
+    UsageLimit,
+}
+
+impl std::fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LimitKind::RateLimit => "rate limit",
+            LimitKind::UsageLimit => "usage limit",
+        })
+    }
+}
+
+/// A rate or usage limit Claude hit, with when it lifts if the message said
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LimitWarning {
+    pub kind: LimitKind,
+    pub message: String,
+    pub seen_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<DateTime<Utc>>,
+}
+
+impl LimitWarning {
+    /// Whether the limit may still be in force at `now`
+    pub fn active(&self, now: DateTime<Utc>) -> bool {
+        match self.resets_at {
+            Some(resets_at) => now < resets_at,
+            None => now - self.seen_at < Duration::hours(UNTIMED_WARNING_HOURS),
+        }
+    }
+
+    /// Seconds until the limit lifts, for the device countdown
+    pub fn seconds_left(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.resets_at.map(|resets_at| (resets_at - now).num_seconds().max(0))
+    }
+
+    /// Time left until the limit lifts, e.g. "2h 05m" or "4m 30s"
+    pub fn countdown(&self, now: DateTime<Utc>) -> Option<String> {
+        let secs = self.seconds_left(now)?;
+        Some(if secs >= 3600 {
+            format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+        } else {
+            format!("{}m {:02}s", secs / 60, secs % 60)
+        })
+    }
+}
+
+/// A limit named in a notification or API error message, seen at `now`. Reset times
+/// are read from an epoch after `|` ("usage limit reached|1735689600"), a clock time
+/// ("resets 3pm", "reset at 15:30", the next time the local clock shows it), or a delay ("try again in 30 seconds").
+pub fn detect(message: &str, now: DateTime<Local>) -> Option<LimitWarning> {
+    let lower = message.to_lowercase();
+    let kind = if ["usage limit", "limit reached", "limit will reset"].iter().any(|p| lower.contains(p)) {
+        LimitKind::UsageLimit
+    } else if ["rate limit", "rate_limit", "429", "too many requests"].iter().any(|p| lower.contains(p)) {
+        LimitKind::RateLimit
+    } else {
+        return None;
+    };
+    Some(LimitWarning {
+        kind,
+        message: message.split('|').next().unwrap_or(message).trim().to_string(),
+        seen_at: now.with_timezone(&Utc),
+        resets_at: reset_time(&lower, now),
+    })
+}
+
+/// When a limit lifts, from its message (already lowercased)
+fn reset_time(message: &str, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let epoch = Regex::new(r"\|(\d{10})\b").expect("valid regex");
+    if let Some(captures) = epoch.captures(message) {
+        return captures[1].parse().ok().and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+    }
+    let delay = Regex::new(r"(?:try again|retry) in (\d+)\s*(second|sec|minute|min|hour)").expect("valid regex");
+    if let Some(captures) = delay.captures(message) {
+        let amount: i64 = captures[1].parse().ok()?;
+        let delay = match &captures[2] {
+            "second" | "sec" => Duration::seconds(amount),
+            "minute" | "min" => Duration::minutes(amount),
+            _ => Duration::hours(amount),
+        };
+        return Some((now + delay).with_timezone(&Utc));
+    }
+    let clock = Regex::new(r"resets?(?: at)? (\d{1,2}(?::\d{2})?\s*(?:am|pm)?)").expect("valid regex");
+    let time = timerange::parse_time(clock.captures(message)?[1].trim())?;
+    let today = timerange::resolve(&Local, now.date_naive().and_time(time));
+    if today > now.with_timezone(&Utc) {
+        Some(today)
+    } else {
+        Some(timerange::resolve(&Local, (now.date_naive() + Duration::days(1)).and_time(time)))
+    }
+}
+
+/// A limit the session's latest response ran into, from the API error Claude Code
+/// wrote to its transcript. Once a later response succeeds there is none.
+pub fn from_transcript(transcript: &Path, now: DateTime<Local>) -> Option<LimitWarning> {
+    let content = fs::read_to_string(transcript).ok()?;
+    let latest = content.lines().rev().find_map(|line| {
+        let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+        (entry["type"] == "assistant" && entry["isSidechain"] != true).then_some(entry)
+    })?;
+    if latest["isApiErrorMessage"] != true {
+        return None;
+    }
+    let content = &latest["message"]["content"];
+    let text = match content.as_str() {
+        Some(text) => text.to_string(),
+        None => content
+            .as_array()?
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    detect(&text, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(local: &str) -> DateTime<Local> {
+        Local.from_local_datetime(&local.parse().unwrap()).single().unwrap()
+    }
+
+    #[test]
+    fn test_limit_messages_and_reset_times() {
+        let now = at("2025-07-20T14:10:00");
+        let warning = detect("Claude usage limit reached. Your limit will reset at 3pm (Europe/London).", now).unwrap();
+        assert_eq!(warning.kind, LimitKind::UsageLimit);
+        assert_eq!(warning.resets_at, Some(at("2025-07-20T15:00:00").with_timezone(&Utc)));
+        assert_eq!(warning.seconds_left(now.with_timezone(&Utc)), Some(50 * 60));
+        assert_eq!(warning.countdown(now.with_timezone(&Utc)).unwrap(), "50m 00s");
+
+        // A time already past today is tomorrow's
+        let warning = detect("5-hour limit reached ∙ resets 9:30am", now).unwrap();
+        assert_eq!(warning.resets_at, Some(at("2025-07-21T09:30:00").with_timezone(&Utc)));
+
+        let warning = detect("Claude AI usage limit reached|1753023600", now).unwrap();
+        assert_eq!(warning.message, "Claude AI usage limit reached");
+        assert_eq!(warning.resets_at.unwrap().timestamp(), 1753023600);
+
+        let warning = detect("API Error: 429 rate_limit_error, try again in 30 seconds", now).unwrap();
+        assert_eq!(warning.kind, LimitKind::RateLimit);
+        assert_eq!(warning.seconds_left(now.with_timezone(&Utc)), Some(30));
+        assert!(!warning.active((now + Duration::minutes(1)).with_timezone(&Utc)));
+
+        assert!(detect("Claude needs your permission to use Bash", now).is_none());
+        let untimed = detect("Rate limit exceeded", now).unwrap();
+        assert!(untimed.resets_at.is_none() && untimed.active(now.with_timezone(&Utc)));
+    }
+
+    #[test]
+    fn test_transcript_api_errors() {
+        let path = std::env::temp_dir().join(format!("ccgadget-ratelimit-{}.jsonl", std::process::id()));
+        let error = r#"{"type": "assistant", "isApiErrorMessage": true, "message": {"content": [{"type": "text", "text": "Claude AI usage limit reached|1753023600"}]}}"#;
+        let ok = r#"{"type": "assistant", "message": {"content": [{"type": "text", "text": "Done"}]}}"#;
+        let now = at("2025-07-20T14:10:00");
+
+        fs::write(&path, format!("{}\n{}\n", ok, error)).unwrap();
+        assert_eq!(from_transcript(&path, now).unwrap().kind, LimitKind::UsageLimit);
+        fs::write(&path, format!("{}\n{}\n", error, ok)).unwrap();
+        assert!(from_transcript(&path, now).is_none());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use crate::config::Config;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::Value;
+use std::error::Error;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// Settings the daemon only reads when it starts: how it reaches the device, what it
+/// serves and exports, and what it runs. A section name covers all its keys.
+const RESTART_REQUIRED: &[&str] = &[
+    "device.alias",
+    "device.transport",
+    "device.fallback",
+    "device.fallback_after",
+    "device.wifi_url",
+    "device.serial_port",
+    "device.serial_baud",
+    "device.prefer_usb",
+    "device.ble_mtu",
+    "device.adapter",
+    "broadcast",
+    "mqtt",
+    "otel",
+    "api",
+    "on_event",
+    "daemon.observe_only",
+    "notifications.digest_minutes",
+];
+
+/// A setting that differs between two versions of the config
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// `section.key`, or the top-level key for settings outside a section
+    pub key: String,
+    /// Whether the daemon keeps using the old value until it is restarted
+    pub restart: bool,
+}
+
+/// Settings changed from `old` to `new`, sorted by key. Profiles are compared by
+/// their effect, since both configs already have the selected one applied.
+pub fn diff(old: &Config, new: &Config) -> Vec<ConfigChange> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        return Vec::new();
+    };
+    let mut keys = Vec::new();
+    for (section, new_value) in new.iter().filter(|(section, _)| *section != "profiles") {
+        let old_value = old.get(section).unwrap_or(&Value::Null);
+        match (old_value, new_value) {
+            (Value::Object(old_fields), Value::Object(new_fields)) if section != "pricing" && section != "buttons" => {
+                let names = new_fields.keys().chain(old_fields.keys().filter(|name| !new_fields.contains_key(*name)));
+                for name in names {
+                    if old_fields.get(name) != new_fields.get(name) {
+                        keys.push(format!("{}.{}", section, name));
+                    }
+                }
+            }
+            _ if old_value != new_value => keys.push(section.clone()),
+            _ => {}
+        }
+    }
+    keys.into_iter()
+        .map(|key| {
+            let section = key.split('.').next().unwrap_or(&key);
+            let restart = RESTART_REQUIRED.iter().any(|setting| *setting == key || *setting == section);
+            ConfigChange { key, restart }
+        })
+        .collect()
+}
+
+/// Watch the config file at `path`, sending on the returned channel whenever it may
+/// have changed. The directory is watched rather than the file, so the file can be
+/// created later and editors that save by replacing it are followed. Dropping the
+/// watcher stops watching.
+pub fn watch(path: &Path) -> Result<(RecommendedWatcher, mpsc::UnboundedReceiver<()>), Box<dyn Error>> {
+    let dir = path.parent().ok_or("Config file has no directory")?;
+    std::fs::create_dir_all(dir)?;
+    let name = path.file_name().map(|name| name.to_os_string());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+            && event.paths.iter().any(|path| path.file_name().map(|name| name.to_os_string()) == name);
+        if relevant {
+            let _ = tx.send(());
+        }
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok((watcher, rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn parse(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_diff_separates_live_and_restart_settings() {
+        let old = parse("[daemon]\ninterval_seconds = 30\n[device]\ntransport = \"ble\"\n");
+        let new = parse(
+            "[daemon]\ninterval_seconds = 10\n[device]\ntransport = \"wifi\"\n\
+             [budgets]\ndaily_usd = 5.0\n[redaction]\npatterns = [\"tok_[a-z]+\"]\n",
+        );
+        let changes = diff(&old, &new);
+        let keys: Vec<(&str, bool)> = changes.iter().map(|change| (change.key.as_str(), change.restart)).collect();
+        assert!(keys.contains(&("daemon.interval_seconds", false)));
+        assert!(keys.contains(&("device.transport", true)));
+        assert!(keys.contains(&("budgets.daily_usd", false)));
+        assert!(keys.contains(&("redaction.patterns", false)));
+        assert_eq!(keys.len(), 4);
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_replaced_file() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-reload-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        let (_watcher, mut changes) = watch(&path).unwrap();
+
+        // Saved the way editors do: written beside the file, then renamed over it
+        std::fs::write(dir.join("other.toml"), "").unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(300), changes.recv()).await.is_err());
+        std::fs::write(dir.join(".config.toml.tmp"), "[daemon]\n").unwrap();
+        std::fs::rename(dir.join(".config.toml.tmp"), &path).unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), changes.recv()).await;
+        assert_eq!(changed.unwrap(), Some(()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
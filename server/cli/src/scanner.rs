@@ -0,0 +1,471 @@
+#[cfg(feature = "ble")]
+use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
+use crate::bluetooth::BluetoothError;
+use crate::device::AdapterChoice;
+use crate::protocol;
+#[cfg(feature = "ble")]
+use btleplug::platform::{Adapter, Manager, Peripheral, PeripheralId};
+#[cfg(feature = "ble")]
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::error::Error;
+use std::time::Duration;
+use tokio::time::sleep;
+#[cfg(feature = "ble")]
+use tokio::time::Instant;
+
+/// Default length of a discovery scan, in seconds
+pub const DEFAULT_SCAN_SECONDS: u64 = 10;
+
+/// Default upper bound on waiting for a device to advertise when pairing, in seconds
+pub const DEFAULT_PAIR_TIMEOUT_SECONDS: u64 = 15;
+
+/// How long to keep listening for other devices once the first match advertised, so
+/// a selection list is not cut down to whichever device happened to be fastest
+#[cfg(feature = "ble")]
+pub const SETTLE: Duration = Duration::from_millis(1500);
+
+/// Which advertising devices a scan reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceFilter {
+    /// Devices advertising a CCGadget service UUID or manufacturer data
+    Service,
+    /// Also devices whose name merely looks like a CCGadget (`--loose`)
+    Loose,
+    /// Every BLE device (`--all`)
+    All,
+}
+
+impl DeviceFilter {
+    /// Filter for the `--all` and `--loose` flags
+    pub fn from_flags(all: bool, loose: bool) -> Self {
+        match (all, loose) {
+            (true, _) => DeviceFilter::All,
+            (false, true) => DeviceFilter::Loose,
+            (false, false) => DeviceFilter::Service,
+        }
+    }
+
+    /// Adapter-side filter; name matching needs every advertisement
+    #[cfg(feature = "ble")]
+    fn scan_filter(self) -> ScanFilter {
+        match self {
+            DeviceFilter::Service => ScanFilter { services: protocol::SERVICE_UUIDS.to_vec() },
+            DeviceFilter::Loose | DeviceFilter::All => ScanFilter::default(),
+        }
+    }
+
+    /// Whether a scanned device passes the filter; adapters may report devices outside
+    /// the scan filter, so this is checked again
+    pub fn accepts(self, device: &ScannedDevice) -> bool {
+        match self {
+            DeviceFilter::Service => device.advertises_ccgadget(),
+            DeviceFilter::Loose => device.advertises_ccgadget() || device.name.as_deref().is_some_and(is_ccgadget_device),
+            DeviceFilter::All => true,
+        }
+    }
+}
+
+/// A device seen advertising during a scan
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScannedDevice {
+    /// Advertised local name, if any
+    pub name: Option<String>,
+    pub address: String,
+    /// Signal strength in dBm, when the adapter reports it
+    pub rssi: Option<i16>,
+    /// Service UUIDs in the advertisement
+    pub services: Vec<String>,
+    /// Firmware version from the CCGadget manufacturer data, if advertised
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    /// Capabilities from the CCGadget manufacturer data
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+}
+
+impl ScannedDevice {
+    /// Advertised name, or a placeholder for anonymous devices
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("Unknown Device")
+    }
+
+    /// Signal strength for display, e.g. `-45dBm`
+    pub fn signal(&self) -> String {
+        self.rssi.map(|rssi| format!("{}dBm", rssi)).unwrap_or_else(|| "N/A".to_string())
+    }
+
+    /// Whether the advertisement carries a CCGadget service UUID or manufacturer data
+    pub fn advertises_ccgadget(&self) -> bool {
+        self.firmware.is_some()
+            || self.services.iter().any(|uuid| uuid.parse().is_ok_and(|uuid| protocol::is_ccgadget_service(&uuid)))
+    }
+
+    /// Fill in firmware and capabilities from a manufacturer data block
+    fn with_manufacturer_data(mut self, data: Option<&[u8]>) -> Self {
+        if let Some(info) = data.and_then(protocol::parse_manufacturer_data) {
+            self.firmware = Some(info.firmware);
+            self.capabilities = info.capabilities;
+        }
+        self
+    }
+}
+
+/// Check if a device name matches CCGadget patterns (the `--loose` fallback)
+pub fn is_ccgadget_device(name: &str) -> bool {
+    let name_lower = name.to_lowercase();
+    name_lower.contains("ccgadget") ||
+    name_lower.starts_with("ccg-") ||
+    name_lower.contains("esp32-ccg")
+}
+
+/// Keep the devices `filter` accepts, strongest signal first
+pub fn select(mut devices: Vec<ScannedDevice>, filter: DeviceFilter) -> Vec<ScannedDevice> {
+    devices.retain(|device| filter.accepts(device));
+    devices.sort_by_key(|device| std::cmp::Reverse(device.rssi.unwrap_or(i16::MIN)));
+    devices
+}
+
+/// Each adapter's description (e.g. `hci0 (usb:v1D6Bp0246d0540)`), in adapter order
+#[cfg(feature = "ble")]
+pub async fn describe_adapters(adapters: &[Adapter]) -> Vec<String> {
+    let mut names = Vec::with_capacity(adapters.len());
+    for adapter in adapters {
+        names.push(adapter.adapter_info().await.unwrap_or_else(|_| "unknown adapter".to_string()));
+    }
+    names
+}
+
+/// The adapter `choice` selects, or the first one
+#[cfg(feature = "ble")]
+pub async fn choose_adapter(adapters: Vec<Adapter>, choice: Option<&AdapterChoice>) -> Result<Adapter, Box<dyn Error>> {
+    let Some(choice) = choice else {
+        return Ok(adapters.into_iter().next().ok_or(BluetoothError::NoAdapter)?);
+    };
+    let names = describe_adapters(&adapters).await;
+    let index = choice.position(&names).ok_or_else(|| {
+        let available: Vec<String> = names.iter().enumerate().map(|(index, name)| format!("{}: {}", index, name)).collect();
+        let available = if available.is_empty() { "none".to_string() } else { available.join(", ") };
+        BluetoothError::AdapterNotFound { choice: choice.to_string(), available }
+    })?;
+    Ok(adapters.into_iter().nth(index).expect("position is within the adapter list"))
+}
+
+/// Every Bluetooth adapter on this machine
+#[cfg(feature = "ble")]
+pub async fn list_adapters() -> Result<Vec<Adapter>, Box<dyn Error>> {
+    let manager = tokio::time::timeout(Duration::from_secs(5), Manager::new())
+        .await
+        .map_err(|_| BluetoothError::Timeout("Bluetooth manager initialization"))?
+        .map_err(|e| BluetoothError::from_btleplug(e, BluetoothError::Init))?;
+    Ok(tokio::time::timeout(Duration::from_secs(2), manager.adapters())
+        .await
+        .map_err(|_| BluetoothError::Timeout("Bluetooth adapter detection"))?
+        .map_err(|e| BluetoothError::from_btleplug(e, BluetoothError::Adapter))?)
+}
+
+/// The chosen Bluetooth adapter (the first by default), explaining on stderr what to
+/// check when there is none
+#[cfg(feature = "ble")]
+pub async fn open_adapter(choice: Option<&AdapterChoice>) -> Result<Adapter, Box<dyn Error>> {
+    // Get the Bluetooth manager with timeout and better error handling
+    say!("   🔍 Initializing Bluetooth manager...");
+    let manager = match tokio::time::timeout(Duration::from_secs(5), Manager::new()).await {
+        Ok(Ok(manager)) => {
+            say!("   ✅ Bluetooth manager initialized");
+            manager
+        },
+        Ok(Err(e)) => {
+            esay!("   ❌ Failed to initialize Bluetooth manager: {}", e);
+            esay!("   💡 Possible solutions:");
+            esay!("      - Enable Bluetooth in System Settings");
+            esay!("      - Grant Bluetooth permission to Terminal/CLI in Privacy & Security settings");
+            esay!("      - Run: sudo xcode-select --install (if needed)");
+            return Err(BluetoothError::from_btleplug(e, BluetoothError::Init).into());
+        },
+        Err(_) => {
+            esay!("   ❌ Bluetooth manager initialization timed out");
+            esay!("   💡 This may indicate:");
+            esay!("      - Bluetooth service is not running");
+            esay!("      - Permission issues (check Privacy & Security settings)");
+            esay!("      - Hardware compatibility issues");
+            return Err(BluetoothError::Timeout("Bluetooth manager initialization").into());
+        }
+    };
+
+    // Get the first Bluetooth adapter
+    say!("   🔍 Finding Bluetooth adapters...");
+    let adapters = match tokio::time::timeout(Duration::from_secs(2), manager.adapters()).await {
+        Ok(Ok(adapters)) => adapters,
+        Ok(Err(e)) => {
+            esay!("   ❌ Failed to get Bluetooth adapters: {}", e);
+            esay!("   💡 This usually indicates permission or hardware issues");
+            return Err(BluetoothError::from_btleplug(e, BluetoothError::Adapter).into());
+        },
+        Err(_) => {
+            esay!("   ❌ Bluetooth adapter detection timed out");
+            esay!("   💡 Bluetooth adapters are taking too long to respond");
+            esay!("      This often means permission issues or system Bluetooth problems");
+            return Err(BluetoothError::Timeout("Bluetooth adapter detection").into());
+        }
+    };
+
+    if adapters.is_empty() {
+        return Err(BluetoothError::NoAdapter.into());
+    }
+    let central = choose_adapter(adapters, choice).await?;
+
+    match choice {
+        Some(_) => say!("   ✅ Bluetooth adapter found: {}", central.adapter_info().await.unwrap_or_default()),
+        None => say!("   ✅ Bluetooth adapter found"),
+    }
+    Ok(central)
+}
+
+/// A peripheral's latest advertisement, if the adapter has one
+#[cfg(feature = "ble")]
+async fn describe(peripheral: &Peripheral) -> Result<Option<ScannedDevice>, Box<dyn Error>> {
+    let Some(props) = peripheral.properties().await? else {
+        return Ok(None);
+    };
+    tracing::debug!("Discovered {:?} ({}, rssi {:?})", props.local_name, props.address, props.rssi);
+    let device = ScannedDevice {
+        name: props.local_name,
+        address: props.address.to_string(),
+        rssi: props.rssi,
+        services: props.services.iter().map(|uuid| uuid.to_string()).collect(),
+        firmware: None,
+        capabilities: Vec::new(),
+    };
+    Ok(Some(device.with_manufacturer_data(props.manufacturer_data.get(&protocol::VENDOR_ID).map(Vec::as_slice))))
+}
+
+/// Scan for `duration` and return every device the adapter reported
+#[cfg(feature = "ble")]
+pub async fn discover(central: &Adapter, duration: Duration, filter: DeviceFilter) -> Result<Vec<ScannedDevice>, Box<dyn Error>> {
+    central.start_scan(filter.scan_filter()).await?;
+    sleep(duration).await;
+    central.stop_scan().await?;
+
+    let mut devices = Vec::new();
+    for peripheral in central.peripherals().await? {
+        devices.extend(describe(&peripheral).await?);
+    }
+    Ok(devices)
+}
+
+/// The peripheral an adapter event reports an advertisement from
+#[cfg(feature = "ble")]
+fn advertised_by(event: CentralEvent) -> Option<PeripheralId> {
+    match event {
+        CentralEvent::DeviceDiscovered(id)
+        | CentralEvent::DeviceUpdated(id)
+        | CentralEvent::ManufacturerDataAdvertisement { id, .. }
+        | CentralEvent::ServiceDataAdvertisement { id, .. }
+        | CentralEvent::ServicesAdvertisement { id, .. } => Some(id),
+        _ => None,
+    }
+}
+
+/// Listen to advertisements passing `scan_filter` and collect the devices `wanted`
+/// accepts, returning `settle` after the first one arrives or at `timeout` at the latest
+#[cfg(feature = "ble")]
+pub async fn watch(
+    central: &Adapter,
+    scan_filter: ScanFilter,
+    timeout: Duration,
+    settle: Duration,
+    wanted: impl Fn(&ScannedDevice) -> bool,
+) -> Result<Vec<(ScannedDevice, Peripheral)>, Box<dyn Error>> {
+    // Subscribe before scanning so no advertisement slips through
+    let mut events = central.events().await?;
+    central.start_scan(scan_filter).await?;
+
+    let mut deadline = Instant::now() + timeout;
+    let mut found: Vec<(ScannedDevice, Peripheral)> = Vec::new();
+    // Devices the adapter already knows about count as advertising right away
+    let mut pending = central.peripherals().await?;
+    loop {
+        for peripheral in pending.drain(..) {
+            let Some(device) = describe(&peripheral).await? else { continue };
+            if !wanted(&device) {
+                continue;
+            }
+            if found.is_empty() {
+                deadline = deadline.min(Instant::now() + settle);
+            }
+            match found.iter_mut().find(|(known, _)| known.address == device.address) {
+                Some(entry) => entry.0 = device,
+                None => found.push((device, peripheral)),
+            }
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        match tokio::time::timeout_at(deadline, events.next()).await {
+            Ok(Some(event)) => {
+                if let Some(id) = advertised_by(event) {
+                    pending.extend(central.peripheral(&id).await.ok());
+                }
+            }
+            Ok(None) | Err(_) => break,
+        }
+    }
+    central.stop_scan().await?;
+    Ok(found)
+}
+
+/// Start scanning and yield every advertisement from a device `filter` accepts, starting
+/// with the devices the adapter already knows about; the caller stops the scan
+#[cfg(feature = "ble")]
+pub async fn advertisements(
+    central: &Adapter,
+    filter: DeviceFilter,
+) -> Result<impl Stream<Item = (ScannedDevice, Peripheral)> + '_, Box<dyn Error>> {
+    let events = central.events().await?;
+    central.start_scan(filter.scan_filter()).await?;
+    let known = futures::stream::iter(central.peripherals().await?);
+    let advertised = events.filter_map(move |event| async move { central.peripheral(&advertised_by(event)?).await.ok() });
+    Ok(known.chain(advertised).filter_map(move |peripheral| async move {
+        let device = describe(&peripheral).await.ok().flatten()?;
+        filter.accepts(&device).then_some((device, peripheral))
+    }))
+}
+
+/// Wait up to `timeout` for a device with the given name or address to advertise,
+/// returning as soon as it does
+#[cfg(feature = "ble")]
+pub async fn find(central: &Adapter, identifier: &str, timeout: Duration) -> Result<Option<Peripheral>, Box<dyn Error>> {
+    let matches = |device: &ScannedDevice| {
+        device.address.eq_ignore_ascii_case(identifier)
+            || device.name.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(identifier))
+    };
+    // Named explicitly, so it need not advertise the service
+    let found = watch(central, ScanFilter::default(), timeout, Duration::ZERO, matches).await?;
+    Ok(found.into_iter().next().map(|(_, peripheral)| peripheral))
+}
+
+/// Wait up to `timeout` for devices `filter` accepts to advertise, strongest signal
+/// first; returns shortly after the first one does
+#[cfg(feature = "ble")]
+pub async fn await_ccgadgets(central: &Adapter, timeout: Duration, filter: DeviceFilter) -> Result<Vec<ScannedDevice>, Box<dyn Error>> {
+    let found = watch(central, filter.scan_filter(), timeout, SETTLE, |device| filter.accepts(device)).await?;
+    Ok(select(found.into_iter().map(|(device, _)| device).collect(), filter))
+}
+
+/// Devices reported by a simulated scan in demo mode
+fn demo_devices() -> Vec<ScannedDevice> {
+    let device = |name: Option<&str>, address: &str, rssi: i16, services: &[&str], data: Option<&[u8]>| {
+        ScannedDevice {
+            name: name.map(str::to_string),
+            address: address.to_string(),
+            rssi: Some(rssi),
+            services: services.iter().map(|uuid| uuid.to_string()).collect(),
+            firmware: None,
+            capabilities: Vec::new(),
+        }
+        .with_manufacturer_data(data)
+    };
+    let ccgadget = [protocol::SERVICE_UUID.to_string()];
+    let ccgadget: Vec<&str> = ccgadget.iter().map(String::as_str).collect();
+    vec![
+        device(Some("CCGadget-Demo"), "AA:BB:CC:DD:EE:FF", -45, &ccgadget, Some(&[1, 1, 2, 0, 0x0b])),
+        device(Some("CCG-Office"), "11:22:33:44:55:66", -67, &ccgadget, Some(&[1, 1, 1, 3, 0x01])),
+        // Firmware from before the manufacturer data block
+        device(Some("ESP32-CCG-Lab"), "99:88:77:66:55:44", -72, &ccgadget, None),
+        device(Some("AirPods"), "4C:56:9D:01:02:03", -58, &[], None),
+        device(None, "7E:1F:2A:3B:4C:5D", -88, &[], None),
+        // Looks like a CCGadget by name only
+        device(Some("CCG-Speaker"), "C0:FF:EE:00:11:22", -80, &[], None),
+    ]
+}
+
+/// Scan for `duration` on the chosen adapter and return the nearby devices `filter`
+/// accepts, strongest signal first; simulated under `CCGADGET_DEMO_MODE`
+pub async fn scan(duration: Duration, filter: DeviceFilter, adapter: Option<&AdapterChoice>) -> Result<Vec<ScannedDevice>, Box<dyn Error>> {
+    if std::env::var("CCGADGET_DEMO_MODE").is_ok() {
+        say!("   📡 Simulating device scan...");
+        sleep(Duration::from_millis(800).min(duration)).await;
+        return Ok(select(demo_devices(), filter));
+    }
+
+    scan_adapter(duration, filter, adapter).await
+}
+
+/// Scan the chosen adapter for `duration`
+#[cfg(feature = "ble")]
+async fn scan_adapter(duration: Duration, filter: DeviceFilter, adapter: Option<&AdapterChoice>) -> Result<Vec<ScannedDevice>, Box<dyn Error>> {
+    let central = open_adapter(adapter).await?;
+    say!("   📡 Starting Bluetooth scan ({} seconds)...", duration.as_secs());
+    Ok(select(discover(&central, duration, filter).await?, filter))
+}
+
+#[cfg(not(feature = "ble"))]
+async fn scan_adapter(_duration: Duration, _filter: DeviceFilter, _adapter: Option<&AdapterChoice>) -> Result<Vec<ScannedDevice>, Box<dyn Error>> {
+    Err(BluetoothError::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_filters_and_orders_by_signal() {
+        let names = |filter| select(demo_devices(), filter).iter().map(|device| device.display_name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(DeviceFilter::Service), ["CCGadget-Demo", "CCG-Office", "ESP32-CCG-Lab"]);
+        // Name matching picks up the lookalike that does not advertise the service
+        assert_eq!(names(DeviceFilter::Loose), ["CCGadget-Demo", "CCG-Office", "ESP32-CCG-Lab", "CCG-Speaker"]);
+
+        let everything = select(demo_devices(), DeviceFilter::All);
+        assert_eq!(everything.len(), 6);
+        assert_eq!(everything[1].display_name(), "AirPods");
+        assert_eq!(everything[5].display_name(), "Unknown Device");
+        assert_eq!(everything[5].signal(), "-88dBm");
+
+        assert_eq!(everything[0].firmware.as_deref(), Some("1.2.0"));
+        assert_eq!(everything[0].capabilities, ["display", "buttons", "battery"]);
+        assert_eq!(everything[3].firmware, None);
+
+        // Manufacturer data alone identifies a CCGadget
+        let renamed = ScannedDevice { services: Vec::new(), ..everything[3].clone() };
+        assert!(!renamed.advertises_ccgadget());
+        assert!(renamed.with_manufacturer_data(Some(&[1, 1, 0, 0, 0x01])).advertises_ccgadget());
+    }
+
+    #[test]
+    fn test_adapter_choice() {
+        let names = vec!["hci0 (usb:v1D6Bp0246d0540)".to_string(), "hci1 (usb:v0A12p0001)".to_string()];
+        assert_eq!("1".parse::<AdapterChoice>().unwrap().position(&names), Some(1));
+        assert_eq!("HCI1".parse::<AdapterChoice>().unwrap().position(&names), Some(1));
+        assert_eq!("usb:v1d6b".parse::<AdapterChoice>().unwrap().position(&names), Some(0));
+        assert_eq!("2".parse::<AdapterChoice>().unwrap().position(&names), None);
+        assert!("".parse::<AdapterChoice>().is_err());
+
+        // Config files may give either form
+        let parsed: crate::config::DeviceConfig = toml::from_str("adapter = 1").unwrap();
+        assert_eq!(parsed.adapter, Some(AdapterChoice::Index(1)));
+        let parsed: crate::config::DeviceConfig = toml::from_str("adapter = \"hci1\"").unwrap();
+        assert_eq!(parsed.adapter, Some(AdapterChoice::Name("hci1".to_string())));
+    }
+
+    #[test]
+    fn test_device_name_filtering() {
+        // Test CCGadget device name patterns
+        assert!(is_ccgadget_device("CCGadget-Demo"));
+        assert!(is_ccgadget_device("ccgadget-home")); // case insensitive
+        assert!(is_ccgadget_device("My CCGadget Device"));
+        assert!(is_ccgadget_device("CCG-Office"));
+        assert!(is_ccgadget_device("ccg-lab"));
+        assert!(is_ccgadget_device("ESP32-CCG-Test"));
+        assert!(is_ccgadget_device("esp32-ccg-home"));
+        
+        // Test non-CCGadget device names should be filtered out
+        assert!(!is_ccgadget_device("iPhone"));
+        assert!(!is_ccgadget_device("MacBook Pro"));
+        assert!(!is_ccgadget_device("AirPods"));
+        assert!(!is_ccgadget_device("Unknown Device"));
+        assert!(!is_ccgadget_device("ESP32-Other"));
+        assert!(!is_ccgadget_device("CCG")); // too short
+        assert!(!is_ccgadget_device(""));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::payload::DevicePayload;
+
+/// Characters across the device screen, excluding the border
+const SCREEN_WIDTH: usize = 30;
+
+/// Format a metric value the way the device shows it
+pub fn format_metric(name: &str, value: f64) -> String {
+    if name.starts_with("cost") {
+        format!("${:.2}", value)
+    } else if name.starts_with("tokens") {
+        match value {
+            v if v >= 1_000_000.0 => format!("{:.1}M", v / 1_000_000.0),
+            v if v >= 1_000.0 => format!("{:.1}k", v / 1_000.0),
+            v => format!("{:.0}", v),
+        }
+    } else if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Pad or truncate text to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    if count > width {
+        text.chars().take(width.saturating_sub(1)).collect::<String>() + "…"
+    } else {
+        format!("{}{}", text, " ".repeat(width - count))
+    }
+}
+
+/// Label on the left, value on the right, filling one screen line
+fn spread(label: &str, value: &str) -> String {
+    let value_width = value.chars().count().min(SCREEN_WIDTH);
+    format!("{}{}", fit(label, SCREEN_WIDTH - value_width), fit(value, value_width))
+}
+
+/// Render what the device would show for a payload, widgets in `layout` order.
+/// `ansi` adds bold/dim styling for terminals.
+pub fn render(payload: &DevicePayload, layout: &[String], ansi: bool) -> String {
+    let (bold, dim, reset) = if ansi { ("\x1b[1m", "\x1b[2m", "\x1b[0m") } else { ("", "", "") };
+    let line = |text: String| format!("│ {} │\n", text);
+    let rule = |left: char, right: char| format!("{}{}{}\n", left, "─".repeat(SCREEN_WIDTH + 2), right);
+
+    let time = payload.timestamp.with_timezone(&chrono::Local).format("%H:%M").to_string();
+    let title = spread("CCGadget", &time);
+    let mut out = rule('┌', '┐');
+    out.push_str(&line(format!("{}{}{}", bold, title, reset)));
+    out.push_str(&rule('├', '┤'));
+
+    let mut status = payload.event.clone();
+    if let (true, Some(session_id)) = (payload.background, &payload.session_id) {
+        // Another session's event: shown inline, tagged with its short id
+        let short: String = session_id.chars().take(8).collect();
+        status = format!("[{}] {}", short, status);
+    }
+    if let Some(tool) = &payload.tool {
+        status.push_str(&format!(" · {}", tool));
+    }
+    if let Some(activity) = payload.activity {
+        status.push_str(&format!(" · {}", activity));
+    }
+    if let Some(severity) = payload.severity.filter(|severity| *severity > crate::payload::Severity::Info) {
+        status.push_str(&format!(" · {}", severity.to_string().to_uppercase()));
+    }
+    out.push_str(&line(fit(&status, SCREEN_WIDTH)));
+    if let Some(message) = &payload.message {
+        out.push_str(&line(format!("{}{}{}", dim, fit(message, SCREEN_WIDTH), reset)));
+    }
+    if let Some(prompt) = &payload.prompt {
+        out.push_str(&line(format!("{}{}{}", dim, fit(&format!("> {}", prompt), SCREEN_WIDTH), reset)));
+    }
+
+    out.push_str(&rule('├', '┤'));
+    if let Some(context) = &payload.context {
+        let value = if context.compacting { "compacting".to_string() } else { format!("{:.0}%", context.percent) };
+        out.push_str(&line(spread("context", &value)));
+    }
+    for name in layout {
+        let value = payload
+            .widgets
+            .get(name)
+            .map(|value| format_metric(name, *value))
+            .unwrap_or_else(|| "n/a".to_string());
+        out.push_str(&line(spread(name, &value)));
+    }
+    if let Some(burn) = &payload.burn {
+        let rate = format!("{}/min", format_metric("tokens", burn.tokens_per_minute));
+        out.push_str(&line(spread("burn rate", &rate)));
+        if let Some(at) = burn.limit_at {
+            let at = at.with_timezone(&chrono::Local).format("%H:%M").to_string();
+            out.push_str(&line(spread("limit at", &at)));
+        }
+    }
+    out.push_str(&rule('└', '┘'));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_metric() {
+        assert_eq!(format_metric("cost_today", 1.5), "$1.50");
+        assert_eq!(format_metric("tokens_session", 12_345.0), "12.3k");
+        assert_eq!(format_metric("git_commits", 3.0), "3");
+        assert_eq!(format_metric("ratio", 0.256), "0.26");
+    }
+
+    #[test]
+    fn test_render_lines_have_equal_width() {
+        let mut payload = DevicePayload::usage([("cost_today".to_string(), 2.0)].into_iter().collect());
+        payload.message = Some("A message far too long to fit on the small device screen".to_string());
+        let layout = vec!["cost_today".to_string(), "missing".to_string()];
+        let screen = render(&payload, &layout, false);
+
+        assert!(screen.contains("$2.00"));
+        assert!(screen.contains("n/a"));
+        let widths: Vec<usize> = screen.lines().map(|l| l.chars().count()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::config::{self, Config};
+use crate::device::PairedDevice;
+use crate::ipc::{self, DaemonStatus, IpcRequest, IpcResponse, LinkState};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Session id of the synthetic event, so it is recognisable in logs and `session list`
+pub const SESSION_ID: &str = "ccgadget-self-test";
+
+/// Pipeline stages, in the order they are checked
+const STAGES: [&str; 6] = ["config", "daemon", "link", "trigger", "ipc", "delivery"];
+
+/// How often the daemon is polled while waiting
+const POLL: Duration = Duration::from_millis(200);
+
+/// Outcome of one stage
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// Not run because an earlier stage failed
+    Skip,
+}
+
+/// Result of one stage of the self-test
+#[derive(Serialize, Debug, Clone)]
+pub struct StageReport {
+    pub stage: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+    pub millis: u64,
+}
+
+/// Daemon started for the test, stopped again when the test ends
+struct TestDaemon {
+    child: Child,
+}
+
+impl TestDaemon {
+    /// Launch this binary as a foreground daemon, against the simulated device if
+    /// `simulated`
+    fn spawn(simulated: bool) -> Result<Self, Box<dyn Error>> {
+        let mut command = Command::new(std::env::current_exe()?);
+        command
+            .args(["--quiet", "start", "--foreground"])
+            .args(config::profile().map(|profile| format!("--profile={}", profile)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if simulated {
+            command.env("CCGADGET_DEMO_MODE", "1");
+        }
+        Ok(Self { child: command.spawn()? })
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        // Killed daemons leave their socket behind
+        ipc::cleanup();
+    }
+}
+
+/// The daemon's status, if one answers
+fn status() -> Option<DaemonStatus> {
+    match ipc::request(&IpcRequest::Status, Duration::from_millis(500)) {
+        Ok(IpcResponse::Status(status)) => Some(*status),
+        _ => None,
+    }
+}
+
+/// Poll the daemon until `check` accepts its status or `deadline` passes
+async fn wait_for(deadline: Instant, check: impl Fn(&DaemonStatus) -> bool) -> Option<DaemonStatus> {
+    loop {
+        let current = status();
+        if current.as_ref().is_some_and(&check) || Instant::now() >= deadline {
+            return current;
+        }
+        sleep(POLL).await;
+    }
+}
+
+/// Hook input for the synthetic event
+fn synthetic_event() -> serde_json::Value {
+    serde_json::json!({
+        "session_id": SESSION_ID,
+        "hook_event_name": "PreToolUse",
+        "tool_name": "SelfTest",
+    })
+}
+
+/// Feed the synthetic event to `ccgadget trigger`, as a hook would
+fn run_trigger() -> Result<String, Box<dyn Error>> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["--quiet", "trigger", "--wait"])
+        .args(config::profile().map(|profile| format!("--profile={}", profile)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().ok_or("trigger has no stdin")?.write_all(synthetic_event().to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("trigger exited with {}: {}", output.status, stderr.trim()).into());
+    }
+    Ok("hook event logged and handed to the daemon".to_string())
+}
+
+/// Runs stages in order, skipping the rest after the first failure
+struct Run {
+    reports: Vec<StageReport>,
+    started: Instant,
+}
+
+impl Run {
+    /// Record the outcome of the next stage
+    fn record(&mut self, result: Result<String, String>) -> bool {
+        let stage = STAGES[self.reports.len()];
+        let (outcome, detail) = match result {
+            Ok(detail) => (Outcome::Pass, detail),
+            Err(detail) => (Outcome::Fail, detail),
+        };
+        let millis = self.started.elapsed().as_millis() as u64;
+        self.reports.push(StageReport { stage, outcome, detail, millis });
+        self.started = Instant::now();
+        outcome == Outcome::Pass
+    }
+
+    /// Reports for every stage, marking those never reached as skipped
+    fn finish(mut self) -> Vec<StageReport> {
+        for stage in &STAGES[self.reports.len()..] {
+            self.reports.push(StageReport { stage, outcome: Outcome::Skip, detail: String::new(), millis: 0 });
+        }
+        self.reports
+    }
+}
+
+/// Send a synthetic event through trigger, IPC, daemon, and transport, reporting each
+/// stage. Uses the running daemon if there is one; otherwise starts a daemon for the
+/// test, driving the paired device or, without one or with `simulator`, the simulated
+/// device.
+pub async fn run(timeout: Duration, simulator: bool) -> Vec<StageReport> {
+    let mut run = Run { reports: Vec::new(), started: Instant::now() };
+    let deadline = Instant::now() + timeout;
+
+    let config = Config::load().map(|_| "config file is valid".to_string()).map_err(|e| e.to_string());
+    if !run.record(config) {
+        return run.finish();
+    }
+
+    let mut _daemon = None;
+    let daemon = match (status(), simulator) {
+        (Some(status), true) => Err(format!(
+            "a daemon is already running (pid {}); stop it to test against the simulator",
+            status.pid
+        )),
+        (Some(status), false) => Ok(format!(
+            "using running daemon (pid {}, device {})",
+            status.pid,
+            status.device.as_deref().unwrap_or("none")
+        )),
+        (None, _) => {
+            let simulated = simulator || std::env::var("CCGADGET_DEMO_MODE").is_ok() || !matches!(PairedDevice::load(), Ok(Some(_)));
+            let kind = if simulated { "simulator" } else { "paired device" };
+            match TestDaemon::spawn(simulated) {
+                Ok(spawned) => {
+                    _daemon = Some(spawned);
+                    match wait_for(deadline, |_| true).await {
+                        Some(status) => Ok(format!("started daemon for the {} (pid {})", kind, status.pid)),
+                        None => Err("test daemon did not start".to_string()),
+                    }
+                }
+                Err(e) => Err(format!("could not start a daemon: {}", e)),
+            }
+        }
+    };
+    if !run.record(daemon) {
+        return run.finish();
+    }
+
+    let ready = |status: &DaemonStatus| matches!(status.state, LinkState::Ready | LinkState::Observing);
+    let settled = |status: &DaemonStatus| ready(status) || status.state == LinkState::Unpaired;
+    let link = match wait_for(deadline, settled).await {
+        Some(status) if status.maintenance => Err("device is in maintenance mode; run `ccgadget device maintenance off`".to_string()),
+        Some(status) if status.state == LinkState::Observing => Ok("observe-only: frames are logged, not sent".to_string()),
+        Some(status) if ready(&status) => Ok(format!("link ready ({})", status.link_mode)),
+        Some(status) if status.state == LinkState::Unpaired => Err("no paired device; run `ccgadget pair` or use --simulator".to_string()),
+        Some(status) => Err(format!("link not ready after {}s (state: {})", timeout.as_secs(), status.state)),
+        None => Err("daemon stopped answering".to_string()),
+    };
+    if !run.record(link) {
+        return run.finish();
+    }
+
+    let delivered_before = status().map_or(0, |status| status.delivered);
+    if !run.record(run_trigger().map_err(|e| e.to_string())) {
+        return run.finish();
+    }
+
+    let received = match ipc::request(&IpcRequest::Sessions, Duration::from_secs(1)) {
+        Ok(IpcResponse::Sessions { sessions, .. }) if sessions.iter().any(|session| session.session_id == SESSION_ID) => {
+            Ok("daemon received the event".to_string())
+        }
+        Ok(IpcResponse::Sessions { .. }) => Err("the daemon has no record of the event".to_string()),
+        Ok(_) => Err("daemon does not track sessions; restart it after upgrading".to_string()),
+        Err(e) => Err(format!("daemon stopped answering: {}", e)),
+    };
+    if !run.record(received) {
+        return run.finish();
+    }
+
+    let delivered = |status: &DaemonStatus| status.delivered > delivered_before && status.queued == 0;
+    let delivery = match wait_for(deadline, delivered).await {
+        Some(status) if delivered(&status) => Ok(format!("transport accepted the frame ({} delivered)", status.delivered)),
+        Some(status) => Err(format!("frame not delivered; {} queued, link {}", status.queued, status.state)),
+        None => Err("daemon stopped answering".to_string()),
+    };
+    run.record(delivery);
+    run.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages_after_a_failure_are_skipped() {
+        let mut run = Run { reports: Vec::new(), started: Instant::now() };
+        assert!(run.record(Ok("fine".to_string())));
+        assert!(!run.record(Err("no daemon".to_string())));
+        let reports = run.finish();
+
+        let outcomes: Vec<Outcome> = reports.iter().map(|report| report.outcome).collect();
+        assert_eq!(outcomes[..3], [Outcome::Pass, Outcome::Fail, Outcome::Skip]);
+        assert_eq!(reports.len(), STAGES.len());
+        assert_eq!(reports[5].stage, "delivery");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::transport::{Transport, TransportFuture};
+use serialport::{SerialPort, SerialPortType};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::debug;
+
+/// USB vendor ID of Espressif, whose ESP32 boards enumerate as USB CDC devices
+pub const ESPRESSIF_VID: u16 = 0x303A;
+
+/// Longest wait for the device to answer a state request
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest frame the length prefix can describe
+pub const MAX_FRAME: usize = u16::MAX as usize;
+
+/// Prefix `frame` with its length as a big-endian u16. A zero-length frame from the
+/// host asks the device for its state report; the device answers with one frame, empty
+/// if it has none.
+pub fn encode_frame(frame: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if frame.len() > MAX_FRAME {
+        return Err(format!("Frame of {} bytes exceeds the {} byte serial limit", frame.len(), MAX_FRAME).into());
+    }
+    let mut encoded = Vec::with_capacity(frame.len() + 2);
+    encoded.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+    encoded.extend_from_slice(frame);
+    Ok(encoded)
+}
+
+/// Read one length-prefixed frame
+pub fn read_frame(reader: &mut impl Read) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut length = [0u8; 2];
+    reader.read_exact(&mut length)?;
+    let mut frame = vec![0u8; u16::from_be_bytes(length) as usize];
+    reader.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+/// Whether a USB device looks like a CCGadget: it names itself one, or it is an
+/// Espressif board
+fn is_ccgadget_port(vid: u16, product: Option<&str>) -> bool {
+    product.is_some_and(|product| product.to_lowercase().contains("ccgadget")) || vid == ESPRESSIF_VID
+}
+
+/// Path of a plugged-in CCGadget, preferring one that names itself over a bare ESP32
+pub fn detect() -> Option<String> {
+    let ports = serialport::available_ports().ok()?;
+    let mut candidates: Vec<(bool, String)> = ports
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            SerialPortType::UsbPort(usb) if is_ccgadget_port(usb.vid, usb.product.as_deref()) => {
+                let named = usb.product.is_some_and(|product| product.to_lowercase().contains("ccgadget"));
+                Some((named, port.port_name))
+            }
+            _ => None,
+        })
+        .collect();
+    candidates.sort_by_key(|(named, _)| !named);
+    candidates.into_iter().next().map(|(_, path)| path)
+}
+
+/// Open port, shared with the blocking tasks that read and write it
+type SharedPort = Arc<Mutex<Box<dyn SerialPort>>>;
+
+/// Length-prefixed frames over a USB CDC serial port
+pub struct Serial {
+    /// Configured port; detected when `None`
+    path: Option<String>,
+    baud: u32,
+    port: Option<SharedPort>,
+}
+
+impl Serial {
+    /// Transport for the port at `path`, or the detected CCGadget without one
+    pub fn new(path: Option<String>, baud: u32) -> Self {
+        Self { path, baud, port: None }
+    }
+
+    /// The open port
+    fn port(&self) -> Result<SharedPort, Box<dyn Error>> {
+        self.port.clone().ok_or_else(|| "Serial transport is not connected".into())
+    }
+}
+
+impl Transport for Serial {
+    fn name(&self) -> &'static str {
+        "serial"
+    }
+
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let path = match self.path.clone().or_else(detect) {
+                Some(path) => path,
+                None => return Err("No CCGadget found on USB; set device.serial_port if it is plugged in".into()),
+            };
+            debug!("Opening {} at {} baud", path, self.baud);
+            let port = serialport::new(&path, self.baud).timeout(READ_TIMEOUT).open()?;
+            self.port = Some(Arc::new(Mutex::new(port)));
+            Ok(())
+        })
+    }
+
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let port = self.port()?;
+            let encoded = encode_frame(frame)?;
+            tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+                let mut port = port.lock().unwrap();
+                port.write_all(&encoded)?;
+                port.flush()
+            })
+            .await??;
+            Ok(())
+        })
+    }
+
+    fn max_write(&self) -> Option<usize> {
+        Some(MAX_FRAME)
+    }
+
+    /// Ask for the state report with an empty frame and read the reply
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let port = self.port()?;
+            let reply = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, String> {
+                let mut port = port.lock().unwrap();
+                port.write_all(&[0, 0]).and_then(|_| port.flush()).map_err(|e| e.to_string())?;
+                read_frame(&mut *port).map_err(|e| e.to_string())
+            })
+            .await??;
+            Ok((!reply.is_empty()).then_some(reply))
+        })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        self.port = None;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let encoded = encode_frame(b"{\"event\":\"Stop\"}").unwrap();
+        assert_eq!(&encoded[..2], &[0, 16]);
+        assert_eq!(read_frame(&mut encoded.as_slice()).unwrap(), b"{\"event\":\"Stop\"}");
+
+        assert!(encode_frame(&vec![0; MAX_FRAME + 1]).is_err());
+        // A truncated frame is an error, not a short read
+        assert!(read_frame(&mut [0u8, 5, b'a'].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_recognises_ccgadget_ports() {
+        assert!(is_ccgadget_port(0x1234, Some("CCGadget Desk")));
+        assert!(is_ccgadget_port(ESPRESSIF_VID, Some("USB JTAG/serial debug unit")));
+        assert!(!is_ccgadget_port(0x2341, Some("Arduino Uno")));
+    }
+}
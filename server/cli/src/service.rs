@@ -0,0 +1,261 @@
+use crate::bluetooth::BluetoothError;
+use crate::config::Config;
+use crate::device::TransportKind;
+use crate::install::{self, LAUNCHD_LABEL, SCHEDULED_TASK, SYSTEMD_UNIT};
+use crate::ipc::{self, IpcRequest, IpcResponse, LinkState};
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Longest wait for the daemon to answer a status request
+const STATUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What starts the daemon at login
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceManager {
+    /// A LaunchAgent in `~/Library/LaunchAgents` (macOS)
+    Launchd,
+    /// A user unit in `~/.config/systemd/user` (Linux and other Unix)
+    Systemd,
+    /// A Task Scheduler task run at logon, registered from XML in the state directory (Windows)
+    #[serde(rename = "schtasks")]
+    TaskScheduler,
+}
+
+impl ServiceManager {
+    /// The service manager of this platform, if ccgadget supports one
+    pub fn current() -> Option<Self> {
+        if cfg!(target_os = "macos") {
+            Some(ServiceManager::Launchd)
+        } else if cfg!(unix) {
+            Some(ServiceManager::Systemd)
+        } else if cfg!(windows) {
+            Some(ServiceManager::TaskScheduler)
+        } else {
+            None
+        }
+    }
+
+    /// Commands that load the written `unit` and start the daemon. `domain` is the
+    /// user's launchd domain, `gui/<uid>`.
+    fn start_commands(self, unit: &Path, domain: &str) -> Vec<Vec<String>> {
+        match self {
+            ServiceManager::Launchd => vec![args(&["launchctl", "bootstrap", domain, &unit.display().to_string()])],
+            ServiceManager::Systemd => vec![
+                args(&["systemctl", "--user", "daemon-reload"]),
+                args(&["systemctl", "--user", "enable", "--now", SYSTEMD_UNIT]),
+            ],
+            ServiceManager::TaskScheduler => vec![
+                args(&["schtasks", "/Create", "/TN", SCHEDULED_TASK, "/XML", &unit.display().to_string(), "/F"]),
+                args(&["schtasks", "/Run", "/TN", SCHEDULED_TASK]),
+            ],
+        }
+    }
+
+    /// Commands that stop the daemon and unload the service. All but the last may fail,
+    /// as ending a task that is not running does.
+    fn stop_commands(self, domain: &str) -> Vec<Vec<String>> {
+        match self {
+            ServiceManager::Launchd => vec![args(&["launchctl", "bootout", &format!("{}/{}", domain, LAUNCHD_LABEL)])],
+            ServiceManager::Systemd => vec![args(&["systemctl", "--user", "disable", "--now", SYSTEMD_UNIT])],
+            ServiceManager::TaskScheduler => vec![
+                args(&["schtasks", "/End", "/TN", SCHEDULED_TASK]),
+                args(&["schtasks", "/Delete", "/TN", SCHEDULED_TASK, "/F"]),
+            ],
+        }
+    }
+
+    /// Command that succeeds while the service is loaded
+    fn loaded_command(self, domain: &str) -> Vec<String> {
+        match self {
+            ServiceManager::Launchd => args(&["launchctl", "print", &format!("{}/{}", domain, LAUNCHD_LABEL)]),
+            ServiceManager::Systemd => args(&["systemctl", "--user", "is-enabled", "--quiet", SYSTEMD_UNIT]),
+            ServiceManager::TaskScheduler => args(&["schtasks", "/Query", "/TN", SCHEDULED_TASK]),
+        }
+    }
+
+    /// launchd domain of the current user; unused by the others
+    fn domain(self) -> Result<String, Box<dyn Error>> {
+        if self != ServiceManager::Launchd {
+            return Ok(String::new());
+        }
+        let output = Command::new("id").arg("-u").stderr(Stdio::null()).output()?;
+        let uid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || uid.is_empty() {
+            return Err("Could not determine the user id for launchctl".into());
+        }
+        Ok(format!("gui/{}", uid))
+    }
+}
+
+impl std::fmt::Display for ServiceManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ServiceManager::Launchd => "launchd",
+            ServiceManager::Systemd => "systemd",
+            ServiceManager::TaskScheduler => "Task Scheduler",
+        })
+    }
+}
+
+fn args(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+}
+
+/// Run a service manager command, failing with its error output
+fn run(command: &[String]) -> Result<(), Box<dyn Error>> {
+    let output = Command::new(&command[0]).args(&command[1..]).stdin(Stdio::null()).output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(format!("`{}` failed: {}", command.join(" "), stderr.trim()).into())
+}
+
+/// Stop the service, tolerating failures of all but the last command
+fn stop(manager: ServiceManager, domain: &str) -> Result<(), Box<dyn Error>> {
+    let commands = manager.stop_commands(domain);
+    let Some((last, rest)) = commands.split_last() else {
+        return Ok(());
+    };
+    for command in rest {
+        let _ = run(command);
+    }
+    run(last)
+}
+
+/// The platform's service manager, or an error naming the platform
+fn manager() -> Result<ServiceManager, Box<dyn Error>> {
+    ServiceManager::current().ok_or_else(|| format!("Running the daemon as a service is not supported on {}", std::env::consts::OS).into())
+}
+
+/// Write the service definition for this executable, load it, and start the daemon.
+/// An already loaded service is reloaded, so reinstalling picks up a moved binary.
+pub fn install() -> Result<(ServiceManager, PathBuf), Box<dyn Error>> {
+    let manager = manager()?;
+    let domain = manager.domain()?;
+    let exe = std::env::current_exe()?;
+    let (Some(path), Some(unit)) = (install::service_unit_path()?, install::service_unit(&exe)) else {
+        return Err(format!("No service definition for {}", std::env::consts::OS).into());
+    };
+    // systemd restarts a running unit on its own; the others would keep the old daemon
+    if manager != ServiceManager::Systemd && run(&manager.loaded_command(&domain)).is_ok() {
+        stop(manager, &domain)?;
+    }
+    install::write_file(&path, &install::service_unit_bytes(&unit))?;
+    for command in manager.start_commands(&path, &domain) {
+        run(&command)?;
+    }
+    Ok((manager, path))
+}
+
+/// Stop the service and remove its definition; `None` when it was not installed
+pub fn uninstall() -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let manager = manager()?;
+    let domain = manager.domain()?;
+    let Some(path) = install::service_unit_path()?.filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+    if run(&manager.loaded_command(&domain)).is_ok() {
+        stop(manager, &domain)?;
+    }
+    fs::remove_file(&path)?;
+    Ok(Some(path))
+}
+
+/// Whether the service is set up and what the daemon it runs is doing
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ServiceStatus {
+    pub manager: ServiceManager,
+    pub unit: PathBuf,
+    pub installed: bool,
+    pub loaded: bool,
+    /// Link state of the running daemon, whoever started it
+    pub daemon: Option<LinkState>,
+    /// The daemon was refused Bluetooth access, as macOS does for agents it has no
+    /// permission recorded for
+    pub bluetooth_denied: bool,
+}
+
+/// Check that the daemon will be able to use Bluetooth before starting it at every
+/// logon. Only done for Task Scheduler: the WinRT backend answers for the task as it
+/// does for the terminal, while on macOS the check would prompt for the terminal rather
+/// than for the agent. Nothing is checked when the device is not reached over BLE.
+pub async fn check_bluetooth(config: &Config) -> Result<(), Box<dyn Error>> {
+    if ServiceManager::current() != Some(ServiceManager::TaskScheduler) || !config.device.transports().contains(&TransportKind::Ble) {
+        return Ok(());
+    }
+    #[cfg(feature = "ble")]
+    {
+        if crate::scanner::list_adapters().await?.is_empty() {
+            return Err(BluetoothError::NoAdapter.into());
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "ble"))]
+    Err(BluetoothError::Unsupported.into())
+}
+
+/// State of the service and of the daemon it runs
+pub fn status() -> Result<ServiceStatus, Box<dyn Error>> {
+    let manager = manager()?;
+    let domain = manager.domain()?;
+    let unit = install::service_unit_path()?.ok_or("No service definition for this platform")?;
+    let daemon = match ipc::request(&IpcRequest::Status, STATUS_TIMEOUT) {
+        Ok(IpcResponse::Status(status)) => Some(*status),
+        _ => None,
+    };
+    Ok(ServiceStatus {
+        manager,
+        installed: unit.exists(),
+        unit,
+        loaded: run(&manager.loaded_command(&domain)).is_ok(),
+        daemon: daemon.as_ref().map(|status| status.state),
+        bluetooth_denied: daemon.as_ref().is_some_and(|status| bluetooth_denied(status.link_error.as_deref())),
+    })
+}
+
+/// Whether a connection error says Bluetooth access was refused
+pub fn bluetooth_denied(link_error: Option<&str>) -> bool {
+    link_error.is_some_and(|error| error.contains(&BluetoothError::PermissionDenied.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_manager_commands() {
+        let plist = Path::new("/Users/me/Library/LaunchAgents/com.ccgadget.daemon.plist");
+        let launchd = ServiceManager::Launchd;
+        assert_eq!(
+            launchd.start_commands(plist, "gui/501"),
+            vec![args(&["launchctl", "bootstrap", "gui/501", "/Users/me/Library/LaunchAgents/com.ccgadget.daemon.plist"])]
+        );
+        assert_eq!(launchd.stop_commands("gui/501"), vec![args(&["launchctl", "bootout", "gui/501/com.ccgadget.daemon"])]);
+
+        let systemd = ServiceManager::Systemd;
+        assert_eq!(systemd.start_commands(plist, "").last().unwrap(), &args(&["systemctl", "--user", "enable", "--now", "ccgadget.service"]));
+        assert_eq!(systemd.domain().unwrap(), "");
+
+        let task = Path::new(r"C:\Users\me\AppData\Local\ccgadget\ccgadget-task.xml");
+        let schtasks = ServiceManager::TaskScheduler;
+        assert_eq!(
+            schtasks.start_commands(task, "")[0],
+            args(&["schtasks", "/Create", "/TN", "CCGadget", "/XML", r"C:\Users\me\AppData\Local\ccgadget\ccgadget-task.xml", "/F"])
+        );
+        assert_eq!(schtasks.stop_commands("").last().unwrap(), &args(&["schtasks", "/Delete", "/TN", "CCGadget", "/F"]));
+        assert_eq!(serde_json::to_value(schtasks).unwrap(), "schtasks");
+    }
+
+    #[test]
+    fn test_bluetooth_denied_from_link_error() {
+        assert!(bluetooth_denied(Some("Bluetooth permission denied")));
+        assert!(!bluetooth_denied(Some("Paired device CCGadget-1 not found")));
+        assert!(!bluetooth_denied(None));
+    }
+}
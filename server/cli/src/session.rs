@@ -0,0 +1,279 @@
+use crate::cost::CostSnapshot;
+use crate::fs_util::{self, FileLock};
+use crate::paths;
+use crate::trigger::HookInput;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Running totals for one Claude Code session, built up across trigger invocations
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionState {
+    pub session_id: String,
+    pub started_at: DateTime<Utc>,
+    pub last_event_at: DateTime<Utc>,
+    /// Set by `SessionEnd`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Hook events seen, of any kind
+    #[serde(default)]
+    pub events: u64,
+    /// Prompts submitted (`UserPromptSubmit`)
+    #[serde(default)]
+    pub prompts: u64,
+    /// Tool calls started (`PreToolUse`)
+    #[serde(default)]
+    pub tools: u64,
+    /// Tokens and cost so far, from the session's transcript
+    #[serde(default)]
+    pub tokens: u64,
+    #[serde(default)]
+    pub cost_usd: f64,
+}
+
+impl SessionState {
+    /// A session first seen at `now`
+    pub fn new(session_id: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            started_at: now,
+            last_event_at: now,
+            ended_at: None,
+            cwd: None,
+            events: 0,
+            prompts: 0,
+            tools: 0,
+            tokens: 0,
+            cost_usd: 0.0,
+        }
+    }
+
+    /// Count a hook event. Tokens and cost are transcript totals, so they replace the
+    /// previous values rather than add to them.
+    pub fn record(&mut self, input: &HookInput, cost: Option<&CostSnapshot>, now: DateTime<Utc>) {
+        self.events += 1;
+        self.last_event_at = now;
+        match input.hook_event_name.as_deref() {
+            Some("UserPromptSubmit") => self.prompts += 1,
+            Some("PreToolUse") => self.tools += 1,
+            Some("SessionEnd") => self.ended_at = Some(now),
+            _ => {}
+        }
+        if input.cwd.is_some() {
+            self.cwd.clone_from(&input.cwd);
+        }
+        if let Some(session) = cost.and_then(|cost| cost.session.as_ref()) {
+            self.tokens = session.usage.total();
+            self.cost_usd = session.cost_usd;
+        }
+    }
+
+    /// Minutes from the first event to `now`, or to the end of the session
+    pub fn minutes(&self, now: DateTime<Utc>) -> f64 {
+        let end = self.ended_at.unwrap_or(now);
+        (end - self.started_at).num_seconds().max(0) as f64 / 60.0
+    }
+
+    /// The session so far, up to its latest event
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            session_id: self.session_id.clone(),
+            started_at: self.started_at,
+            ended_at: self.last_event_at,
+            minutes: self.minutes(self.last_event_at),
+            prompts: self.prompts,
+            tools: self.tools,
+            tokens: self.tokens,
+            cost_usd: self.cost_usd,
+            cwd: self.cwd.clone(),
+        }
+    }
+}
+
+/// What a session amounted to when Claude finished responding (`Stop`), kept in the
+/// session history and sent to the device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub minutes: f64,
+    pub prompts: u64,
+    pub tools: u64,
+    pub tokens: u64,
+    pub cost_usd: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+impl SessionSummary {
+    /// One line for the device and terminal, e.g. "12 min · 3 prompts · 20 tools · $1.23"
+    pub fn line(&self) -> String {
+        format!(
+            "{:.0} min · {} prompts · {} tools · ${:.2}",
+            self.minutes, self.prompts, self.tools, self.cost_usd
+        )
+    }
+}
+
+/// Append a summary to the monthly session history (`sessions-YYYY-MM.jsonl`) in
+/// `dir`; returns the file written
+pub fn append_history(dir: &Path, summary: &SessionSummary) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("sessions-{}.jsonl", summary.ended_at.format("%Y-%m")));
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.lock()?;
+    file.write_all(format!("{}\n", serde_json::to_string(summary)?).as_bytes())?;
+    Ok(path)
+}
+
+/// Lock file taken while a session's state is read, updated, and saved
+const LOCK_FILE: &str = ".lock";
+
+/// Per-session state files, one JSON document per session id
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// The store in the state directory
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        Ok(Self::in_dir(&paths::sessions_dir()?))
+    }
+
+    /// A store kept in `dir`
+    pub fn in_dir(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf() }
+    }
+
+    /// State file for a session. Ids come from hook input, so anything but letters,
+    /// digits, `-`, and `_` is replaced to keep the name inside the store.
+    fn path(&self, session_id: &str) -> PathBuf {
+        let name: String = session_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// The saved state of a session, if it has been seen
+    pub fn load(&self, session_id: &str) -> Option<SessionState> {
+        let contents = fs::read_to_string(self.path(session_id)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Count a hook event towards its session and save the result. Events without a
+    /// session id are not tracked.
+    pub fn record(
+        &self,
+        input: &HookInput,
+        cost: Option<&CostSnapshot>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<SessionState>, Box<dyn Error>> {
+        let Some(session_id) = input.session_id.as_deref().filter(|id| !id.is_empty()) else {
+            return Ok(None);
+        };
+        fs::create_dir_all(&self.dir)?;
+        let _lock = FileLock::acquire(&self.dir.join(LOCK_FILE))?;
+        let mut state = self.load(session_id).unwrap_or_else(|| SessionState::new(session_id, now));
+        state.record(input, cost, now);
+        fs_util::atomic_write(&self.path(session_id), &serde_json::to_vec_pretty(&state)?)?;
+        Ok(Some(state))
+    }
+
+    /// Delete the state of sessions without events for more than `retention_days`
+    /// (0 keeps everything); returns how many were deleted
+    pub fn prune(&self, retention_days: u64) -> Result<usize, Box<dyn Error>> {
+        if retention_days == 0 || !self.dir.exists() {
+            return Ok(0);
+        }
+        let max_age = Duration::from_secs(retention_days * 24 * 60 * 60);
+        let _lock = FileLock::acquire(&self.dir.join(LOCK_FILE))?;
+        let mut deleted = 0;
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            if entry.file_name() == LOCK_FILE {
+                continue;
+            }
+            let modified = entry.metadata().and_then(|metadata| metadata.modified());
+            let stale = modified.is_ok_and(|modified| {
+                SystemTime::now().duration_since(modified).is_ok_and(|age| age > max_age)
+            });
+            if stale && fs::remove_file(entry.path()).is_ok() {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::CostSummary;
+
+    fn event(session_id: &str, name: &str) -> HookInput {
+        HookInput {
+            session_id: Some(session_id.to_string()),
+            hook_event_name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sessions_accumulate_across_invocations() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-sessions-{}", std::process::id()));
+        let store = SessionStore::in_dir(&dir);
+        let start = Utc::now();
+
+        for (offset, name) in ["UserPromptSubmit", "PreToolUse", "PostToolUse", "PreToolUse"].iter().enumerate() {
+            store.record(&event("abc", name), None, start + chrono::Duration::minutes(offset as i64)).unwrap();
+        }
+        let cost = CostSnapshot { session: Some(CostSummary { cost_usd: 1.5, ..Default::default() }), today: None };
+        let state = store.record(&event("abc", "Stop"), Some(&cost), start + chrono::Duration::minutes(10)).unwrap().unwrap();
+
+        assert_eq!((state.events, state.prompts, state.tools), (5, 1, 2));
+        assert_eq!(state.cost_usd, 1.5);
+        assert_eq!(state.minutes(start + chrono::Duration::minutes(30)), 30.0);
+        assert_eq!(store.load("abc"), Some(state.clone()));
+
+        let summary = state.summary();
+        assert_eq!((summary.minutes, summary.prompts, summary.tools), (10.0, 1, 2));
+        assert_eq!(summary.line(), "10 min · 1 prompts · 2 tools · $1.50");
+        let history = append_history(&dir.join("logs"), &summary).unwrap();
+        append_history(&dir.join("logs"), &summary).unwrap();
+        assert!(history.file_name().unwrap().to_string_lossy().starts_with("sessions-"));
+        assert_eq!(fs::read_to_string(history).unwrap().lines().count(), 2);
+
+        // Other sessions are tracked apart, and events without one are not tracked
+        assert_eq!(store.record(&event("def", "Stop"), None, start).unwrap().unwrap().events, 1);
+        assert!(store.record(&HookInput::default(), None, start).unwrap().is_none());
+        assert!(store.path("../escape").starts_with(&dir));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parallel_events_are_all_counted() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-sessions-parallel-{}", std::process::id()));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let store = SessionStore::in_dir(&dir);
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        store.record(&event("abc", "PreToolUse"), None, Utc::now()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+        assert_eq!(SessionStore::in_dir(&dir).load("abc").unwrap().tools, 80);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use crate::config::{self, Config, DisplayConfig};
+use crate::device::DeviceError;
+use crate::fs_util;
+use crate::ipc::{self, IpcRequest, IpcResponse, LinkState};
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Event name of frames carrying settings for the device to store
+pub const SETTINGS_EVENT: &str = "Settings";
+
+/// How long to wait for the daemon to read the settings back from the device
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings the gadget stores and applies itself, mirroring their config sections
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DeviceSettings {
+    pub display: DisplayConfig,
+}
+
+/// Whether pushing the local settings would overwrite anything
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushCheck {
+    /// The device already has the local settings
+    InSync,
+    /// The device still has what was last pushed from here (or does not report)
+    Safe,
+    /// The device's settings were changed since the last push from here, or were
+    /// never pushed from here
+    Conflict,
+}
+
+impl DeviceSettings {
+    /// The desired device settings from the config file
+    pub fn from_config(config: &Config) -> Self {
+        Self { display: config.display.clone() }
+    }
+
+    /// TOML rendering, as the settings would appear in config.toml
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap_or_default()
+    }
+
+    /// Every setting as a dotted config key and its value, e.g. `display.widgets`
+    pub fn entries(&self) -> Vec<(String, toml::Value)> {
+        let Ok(toml::Value::Table(sections)) = toml::Value::try_from(self) else {
+            return Vec::new();
+        };
+        let mut entries = Vec::new();
+        for (section, table) in sections {
+            if let toml::Value::Table(table) = table {
+                entries.extend(table.into_iter().map(|(key, value)| (format!("{}.{}", section, key), value)));
+            }
+        }
+        entries
+    }
+
+    /// Write these settings into the config file, so the device's state becomes the
+    /// desired state
+    pub fn save_to_config(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = Config::path()?;
+        for (key, value) in self.entries() {
+            path = Config::set_value(&key, config::parse_value(&value.to_string()))?;
+        }
+        Ok(path)
+    }
+}
+
+/// Compare the local settings with the device's and with what was last pushed from here
+pub fn check_push(local: &DeviceSettings, device: Option<&DeviceSettings>, pushed: Option<&DeviceSettings>) -> PushCheck {
+    match device {
+        None => PushCheck::Safe,
+        Some(device) if device == local => PushCheck::InSync,
+        Some(device) if pushed == Some(device) => PushCheck::Safe,
+        Some(_) => PushCheck::Conflict,
+    }
+}
+
+/// Location of the record of the settings last pushed (`display-pushed-<profile>.json`
+/// under a profile)
+fn pushed_path() -> Result<PathBuf, Box<dyn Error>> {
+    let name = match config::profile() {
+        Some(profile) => format!("display-pushed-{}.json", profile),
+        None => "display-pushed.json".to_string(),
+    };
+    Ok(paths::state_dir()?.join(name))
+}
+
+/// The settings last pushed to the device from this machine, if any
+pub fn load_pushed() -> Option<DeviceSettings> {
+    let content = fs::read_to_string(pushed_path().ok()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Remember `settings` as the ones last pushed to the device
+pub fn save_pushed(settings: &DeviceSettings) -> Result<(), Box<dyn Error>> {
+    let path = pushed_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs_util::atomic_write(&path, serde_json::to_string_pretty(settings)?.as_bytes())?;
+    Ok(())
+}
+
+/// Have the daemon read the settings back from the device and return them; `None` when
+/// the device does not report its settings
+pub fn pull() -> Result<Option<DeviceSettings>, Box<dyn Error>> {
+    let read = |refresh: bool| match ipc::request(&IpcRequest::ReadSettings { refresh }, Duration::from_secs(1)) {
+        Ok(IpcResponse::Settings { state, settings, reads }) => Ok((state, settings, reads)),
+        Ok(IpcResponse::Error { message }) => Err(message.into()),
+        Ok(_) => Err("Unexpected reply from the daemon".into()),
+        Err(_) => Err::<_, Box<dyn Error>>("Daemon not running; start it with `ccgadget start`".into()),
+    };
+
+    let (_, _, before) = read(true)?;
+    let started = Instant::now();
+    loop {
+        let (state, settings, reads) = read(false)?;
+        match state {
+            LinkState::Observing => return Err("The daemon is observe-only and has no device link".into()),
+            LinkState::Unpaired => return Err(DeviceError::NotPaired.into()),
+            _ if reads > before => return Ok(settings),
+            _ if started.elapsed() >= READ_TIMEOUT => {
+                return Err(format!("Device did not answer within {}s (link {})", READ_TIMEOUT.as_secs(), state).into())
+            }
+            _ => std::thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings showing the given widgets
+    fn showing(widgets: &[&str]) -> DeviceSettings {
+        let widgets = widgets.iter().map(|w| w.to_string()).collect();
+        DeviceSettings { display: DisplayConfig { widgets, ..Default::default() } }
+    }
+
+    #[test]
+    fn test_push_check_detects_changes_made_on_device() {
+        let local = showing(&["cost_today"]);
+        let pushed = showing(&["cost_session"]);
+
+        assert_eq!(check_push(&local, Some(&local), Some(&pushed)), PushCheck::InSync);
+        assert_eq!(check_push(&local, Some(&pushed), Some(&pushed)), PushCheck::Safe);
+        assert_eq!(check_push(&local, None, None), PushCheck::Safe);
+        // Changed on the device after the last push, or never pushed from here
+        assert_eq!(check_push(&local, Some(&showing(&["tokens_today"])), Some(&pushed)), PushCheck::Conflict);
+        assert_eq!(check_push(&local, Some(&pushed), None), PushCheck::Conflict);
+    }
+
+    #[test]
+    fn test_entries_use_config_keys() {
+        let entries = showing(&["cost_today"]).entries();
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["display.brightness", "display.dark_mode", "display.page", "display.widgets"]);
+        assert_eq!(entries[2].1.to_string(), "\"session-cost\"");
+        assert_eq!(entries[3].1.to_string(), "[\"cost_today\"]");
+    }
+}
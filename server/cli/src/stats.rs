@@ -0,0 +1,184 @@
+use crate::metrics_db::{MetricsDb, SessionActivity, ToolCount};
+use crate::timerange::TimeRange;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// How often tool calls and notifications were errors
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct ErrorRates {
+    /// Tool results seen (`PostToolUse`)
+    pub tool_results: u64,
+    pub tool_errors: u64,
+    /// Share of tool results that were errors, 0 to 100
+    pub tool_error_percent: f64,
+    pub notifications: u64,
+    /// Notifications rated `error` by `[alerts]`, such as rate and usage limits
+    pub error_notifications: u64,
+    pub notification_error_percent: f64,
+}
+
+/// Summary of the trigger history within a time range, for `ccgadget stats`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub range: String,
+    /// Hook events recorded, by event name
+    pub events: BTreeMap<String, u64>,
+    pub busiest_sessions: Vec<SessionActivity>,
+    pub top_tools: Vec<ToolCount>,
+    /// Sessions with a summary (written on `Stop`) in the range
+    pub sessions: usize,
+    /// Mean session length in minutes, from first event to last `Stop`
+    pub average_session_minutes: Option<f64>,
+    pub errors: ErrorRates,
+}
+
+impl Stats {
+    /// Gather stats for `range` from the metrics database, listing the `top` busiest
+    /// sessions and most used tools
+    pub fn collect(db: &MetricsDb, range: &TimeRange, top: usize) -> Result<Self, Box<dyn Error>> {
+        let events = db.event_counts(range)?;
+        let errors = db.error_counts(range)?;
+        let mut top_tools = db.tool_counts(range)?;
+        top_tools.truncate(top);
+        let summaries = db.sessions(range)?;
+        let average_session_minutes = (!summaries.is_empty())
+            .then(|| summaries.iter().map(|summary| summary.minutes).sum::<f64>() / summaries.len() as f64);
+
+        let count = |counts: &BTreeMap<String, u64>, event: &str| counts.get(event).copied().unwrap_or(0);
+        let (tool_results, tool_errors) = (count(&events, "PostToolUse"), count(&errors, "PostToolUse"));
+        let (notifications, error_notifications) = (count(&events, "Notification"), count(&errors, "Notification"));
+        Ok(Self {
+            range: range.describe(),
+            busiest_sessions: db.busiest_sessions(range, top)?,
+            top_tools,
+            sessions: summaries.len(),
+            average_session_minutes,
+            errors: ErrorRates {
+                tool_results,
+                tool_errors,
+                tool_error_percent: percent(tool_errors, tool_results),
+                notifications,
+                error_notifications,
+                notification_error_percent: percent(error_notifications, notifications),
+            },
+            events,
+        })
+    }
+
+    /// Whether nothing was recorded in the range
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Render as text tables, one section per statistic
+    pub fn render(&self) -> String {
+        let mut out = format!("Trigger history, {}\n\n", self.range);
+
+        let total: u64 = self.events.values().sum();
+        out.push_str(&format!("{:<20}  {:>8}  {:>6}\n", "Event", "Count", "Share"));
+        let mut events: Vec<_> = self.events.iter().collect();
+        events.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (event, count) in events {
+            out.push_str(&format!("{:<20}  {:>8}  {:>5.1}%\n", event, count, percent(*count, total)));
+        }
+        out.push_str(&format!("{:<20}  {:>8}\n", "Total", total));
+
+        if !self.busiest_sessions.is_empty() {
+            out.push_str(&format!("\n{:<10}  {:>7}  {:>7}  {:>6}  {:>8}  {}\n", "Session", "Events", "Prompts", "Tools", "Minutes", "Directory"));
+            for session in &self.busiest_sessions {
+                let minutes = (session.last_event_at - session.first_event_at).num_seconds() as f64 / 60.0;
+                out.push_str(&format!(
+                    "{:<10}  {:>7}  {:>7}  {:>6}  {:>8.0}  {}\n",
+                    session.session_id.chars().take(8).collect::<String>(),
+                    session.events,
+                    session.prompts,
+                    session.tools,
+                    minutes,
+                    session.cwd.as_deref().unwrap_or("-"),
+                ));
+            }
+        }
+
+        if !self.top_tools.is_empty() {
+            let width = self.top_tools.iter().map(|tool| tool.tool.len()).max().unwrap_or(0).max(4);
+            out.push_str(&format!("\n{:<w$}  {:>7}  {:>7}\n", "Tool", "Calls", "Errors", w = width));
+            for tool in &self.top_tools {
+                out.push_str(&format!("{:<w$}  {:>7}  {:>7}\n", tool.tool, tool.calls, tool.errors, w = width));
+            }
+        }
+
+        out.push('\n');
+        match self.average_session_minutes {
+            Some(minutes) => out.push_str(&format!("Sessions:  {} finished, {:.0} min on average\n", self.sessions, minutes)),
+            None => out.push_str("Sessions:  none finished\n"),
+        }
+        let errors = &self.errors;
+        out.push_str(&format!(
+            "Errors:    {} of {} tool results ({:.1}%), {} of {} notifications ({:.1}%)\n",
+            errors.tool_errors,
+            errors.tool_results,
+            errors.tool_error_percent,
+            errors.error_notifications,
+            errors.notifications,
+            errors.notification_error_percent,
+        ));
+        out
+    }
+}
+
+/// `part` as a percentage of `whole`; 0 when there is nothing to divide
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AlertsConfig;
+    use crate::storage::{EventStore, JsonlStore};
+    use std::fs;
+
+    #[test]
+    fn test_stats_summarize_the_history() {
+        let dir = std::env::temp_dir().join(format!("ccgadget-stats-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = JsonlStore::new(&dir);
+        let event = |second: u32, session: &str, event: &str, extra: &str| {
+            format!(
+                r#"{{"timestamp":"2025-07-15T12:00:{:02}Z","hook_input":{{"hook_event_name":"{}","session_id":"{}"{}}}}}"#,
+                second, event, session, extra
+            )
+        };
+        for line in [
+            event(1, "session-a", "UserPromptSubmit", ""),
+            event(2, "session-a", "PreToolUse", r#","tool_name":"Edit""#),
+            event(3, "session-a", "PostToolUse", r#","tool_name":"Edit","tool_response":{"success":false}"#),
+            event(4, "session-a", "PreToolUse", r#","tool_name":"Edit""#),
+            event(5, "session-a", "PostToolUse", r#","tool_name":"Edit","tool_response":{"success":true}"#),
+            event(6, "session-b", "Notification", r#","message":"Claude is waiting for your input""#),
+        ] {
+            store.append(&line).unwrap();
+        }
+        let db = MetricsDb::open_at(&dir.join("metrics.db")).unwrap();
+        db.sync_events(&store, &AlertsConfig::default()).unwrap();
+
+        let stats = Stats::collect(&db, &TimeRange::default(), 5).unwrap();
+        assert_eq!(stats.events["PreToolUse"], 2);
+        assert_eq!(stats.busiest_sessions[0].session_id, "session-a");
+        assert_eq!(stats.top_tools, vec![ToolCount { tool: "Edit".to_string(), calls: 2, errors: 1 }]);
+        assert_eq!((stats.errors.tool_results, stats.errors.tool_errors, stats.errors.tool_error_percent), (2, 1, 50.0));
+        assert_eq!(stats.errors.notification_error_percent, 0.0);
+        assert_eq!(stats.average_session_minutes, None);
+
+        let table = stats.render();
+        assert!(table.contains("PostToolUse"));
+        assert!(table.contains("1 of 2 tool results (50.0%)"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
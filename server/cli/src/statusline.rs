@@ -0,0 +1,138 @@
+use crate::ipc::{DaemonStatus, LinkState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Read};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Longest wait for the daemon; statuslines are redrawn often and must not lag
+pub const DAEMON_TIMEOUT: Duration = Duration::from_millis(40);
+
+/// Longest wait for piped input, in case whatever runs us leaves stdin open
+const STDIN_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// The parts of the JSON Claude Code pipes to its `statusLine` command that are shown
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct StatuslineInput {
+    pub session_id: Option<String>,
+    pub model: Option<ModelInfo>,
+    pub cost: Option<CostInfo>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct ModelInfo {
+    pub id: Option<String>,
+    pub display_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct CostInfo {
+    pub total_cost_usd: Option<f64>,
+}
+
+/// Claude Code's statusline input from stdin; empty when stdin is a terminal, carries
+/// something else, or stays open (as under tmux or a prompt framework)
+pub fn read_input() -> StatuslineInput {
+    if io::stdin().is_terminal() {
+        return StatuslineInput::default();
+    }
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut text = String::new();
+        let _ = io::stdin().read_to_string(&mut text);
+        let _ = tx.send(text);
+    });
+    rx.recv_timeout(STDIN_TIMEOUT)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// What the statusline shows
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Statusline {
+    pub model: Option<String>,
+    pub session_cost_usd: Option<f64>,
+    /// Minutes until the current usage block resets
+    pub block_minutes_left: Option<i64>,
+    /// Device link state; `None` while the daemon is not running
+    pub link: Option<LinkState>,
+}
+
+impl Statusline {
+    /// Combine Claude Code's input with the daemon's `status`. The daemon's session cost
+    /// is used only when Claude Code gives none and it is for the same session.
+    pub fn new(input: &StatuslineInput, status: Option<&DaemonStatus>, now: DateTime<Utc>) -> Self {
+        let model = input.model.as_ref().and_then(|model| model.display_name.clone().or_else(|| model.id.clone()));
+        let daemon_cost = status
+            .and_then(|status| status.session_cost.as_ref())
+            .filter(|cost| input.session_id.is_none() || cost.session_id == input.session_id)
+            .map(|cost| cost.cost_usd);
+        let block_minutes_left = status
+            .and_then(|status| status.burn.as_ref())
+            .map(|burn| (burn.block_end - now).num_minutes())
+            .filter(|minutes| *minutes >= 0);
+        Self {
+            model,
+            session_cost_usd: input.cost.as_ref().and_then(|cost| cost.total_cost_usd).or(daemon_cost),
+            block_minutes_left,
+            link: status.map(|status| status.state),
+        }
+    }
+
+    /// One line, e.g. "Opus 4.1 · $1.23 · 🕔 2h 14m left · 📟 ready"
+    pub fn render(&self) -> String {
+        let mut parts = Vec::new();
+        parts.extend(self.model.clone());
+        parts.extend(self.session_cost_usd.map(|cost| format!("${:.2}", cost)));
+        parts.extend(self.block_minutes_left.map(|minutes| format!("🕔 {}h {:02}m left", minutes / 60, minutes % 60)));
+        parts.push(match self.link {
+            Some(state) => format!("📟 {}", state),
+            None => "📟 daemon off".to_string(),
+        });
+        parts.join(" · ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burn::BurnSnapshot;
+    use crate::ipc::SessionCost;
+    use chrono::Duration as Elapsed;
+
+    #[test]
+    fn test_statusline_from_input_and_daemon() {
+        let now = Utc::now();
+        let input: StatuslineInput = serde_json::from_str(
+            r#"{"session_id":"abc","model":{"id":"claude-opus-4-1","display_name":"Opus 4.1"},"cost":{"total_cost_usd":1.234},"cwd":"/tmp"}"#,
+        )
+        .unwrap();
+        assert_eq!(Statusline::new(&input, None, now).render(), "Opus 4.1 · $1.23 · 📟 daemon off");
+
+        let mut status: DaemonStatus = serde_json::from_str(
+            r#"{"state":"ready","pid":1,"started_at":"2025-07-20T12:00:00Z","device":null,"queued":0,"delivered":0}"#,
+        )
+        .unwrap();
+        status.burn = Some(BurnSnapshot {
+            block_start: now - Elapsed::hours(3),
+            block_end: now + Elapsed::minutes(134) + Elapsed::seconds(30),
+            block_tokens: 0,
+            block_cost_usd: 0.0,
+            tokens_per_minute: 0.0,
+            cost_per_hour: 0.0,
+            token_limit: None,
+            limit_at: None,
+        });
+        status.session_cost = Some(SessionCost { session_id: Some("other".to_string()), cost_usd: 9.0 });
+        let tmux = StatuslineInput::default();
+        assert_eq!(Statusline::new(&tmux, Some(&status), now).render(), "$9.00 · 🕔 2h 14m left · 📟 ready");
+        // Another session's cost is not shown for this one
+        let input = StatuslineInput { session_id: Some("abc".to_string()), ..Default::default() };
+        assert_eq!(Statusline::new(&input, Some(&status), now).session_cost_usd, None);
+    }
+}
@@ -0,0 +1,446 @@
+use crate::paths;
+use crate::timerange::TimeRange;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often `follow` checks for new entries
+const FOLLOW_POLL: Duration = Duration::from_millis(500);
+
+/// File name of the SQLite event database in the logs directory
+#[cfg(feature = "full")]
+const SQLITE_FILE: &str = "events.db";
+
+/// How trigger log entries are persisted
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Daily `trigger-YYYY-MM-DD.log` files with one JSON object per line
+    #[default]
+    Jsonl,
+    /// A single SQLite database (`events.db`)
+    Sqlite,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Backend::Jsonl => "jsonl",
+            Backend::Sqlite => "sqlite",
+        })
+    }
+}
+
+/// Persistence for trigger log entries. Entries are JSON objects with a `timestamp`
+/// field, passed around as their serialized text so they round-trip unchanged.
+pub trait EventStore {
+    /// Where the entries are kept, for messages
+    fn location(&self) -> PathBuf;
+
+    /// Append one entry; returns the file it was written to
+    fn append(&self, entry: &str) -> Result<PathBuf, Box<dyn Error>>;
+
+    /// Entries recorded within `range`, oldest first. Backends may return a little more
+    /// than the range (e.g. whole days); callers filter on exact times.
+    fn read(&self, range: &TimeRange) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Call `on_entry` with each entry appended from now on. Runs until interrupted or
+    /// the store becomes unreadable.
+    fn follow(&self, on_entry: &mut dyn FnMut(&str)) -> Result<(), Box<dyn Error>>;
+
+    /// Delete entries older than `retention_days` before `today` (0 keeps everything);
+    /// returns how many entries or files were removed
+    fn prune(&self, retention_days: u64, today: NaiveDate) -> Result<usize, Box<dyn Error>>;
+
+    /// Add entries that are not stored yet; returns how many were added
+    fn import(&self, entries: &[String]) -> Result<usize, Box<dyn Error>> {
+        let mut known: HashSet<String> = self.read(&TimeRange::default())?.into_iter().collect();
+        let mut added = 0;
+        for entry in entries {
+            if known.insert(entry.clone()) {
+                self.append(entry)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+}
+
+/// Time an entry was recorded, from its `timestamp` field
+fn entry_time(entry: &str) -> Option<DateTime<Utc>> {
+    let value: serde_json::Value = serde_json::from_str(entry).ok()?;
+    value["timestamp"].as_str()?.parse().ok()
+}
+
+/// Open the store for `backend` in the logs directory
+pub fn open(backend: Backend) -> Result<Box<dyn EventStore>, Box<dyn Error>> {
+    open_in(backend, &paths::logs_dir()?)
+}
+
+/// Open the store for `backend` in `dir`
+pub fn open_in(backend: Backend, dir: &Path) -> Result<Box<dyn EventStore>, Box<dyn Error>> {
+    Ok(match backend {
+        Backend::Jsonl => Box::new(JsonlStore::new(dir)),
+        #[cfg(feature = "full")]
+        Backend::Sqlite => Box::new(SqliteStore::open(&dir.join(SQLITE_FILE))?),
+        #[cfg(not(feature = "full"))]
+        Backend::Sqlite => return Err("This build has no SQLite support; set `logs.backend = \"jsonl\"`".into()),
+    })
+}
+
+/// Copy every entry from one backend into another, skipping entries already there, so a
+/// migration can be repeated until the new backend is switched on. Returns (copied, total).
+pub fn migrate(from: &dyn EventStore, to: &dyn EventStore) -> Result<(usize, usize), Box<dyn Error>> {
+    let entries = from.read(&TimeRange::default())?;
+    let copied = to.import(&entries)?;
+    Ok((copied, entries.len()))
+}
+
+/// Daily JSONL files named by UTC date, like the timestamps inside them
+pub struct JsonlStore {
+    dir: PathBuf,
+}
+
+impl JsonlStore {
+    /// Store using the daily logs in `dir`
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf() }
+    }
+
+    /// Path of the log holding entries from `date`
+    fn day_file(&self, date: NaiveDate) -> PathBuf {
+        self.dir.join(format!("trigger-{}.log", date.format("%Y-%m-%d")))
+    }
+
+    /// Daily logs with their dates, oldest first, skipping days outside `range`
+    fn files(&self, range: &TimeRange) -> Vec<(NaiveDate, PathBuf)> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let first_day = range.since.map(|since| since.date_naive());
+        let last_day = range.until.map(|until| until.date_naive());
+        let mut files: Vec<(NaiveDate, PathBuf)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter_map(|path| log_date(&path).map(|date| (date, path)))
+            .filter(|(date, _)| first_day.is_none_or(|first| *date >= first) && last_day.is_none_or(|last| *date <= last))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+/// Date of a daily trigger log from its file name
+fn log_date(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    let date = name.strip_prefix("trigger-")?.strip_suffix(".log")?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+impl EventStore for JsonlStore {
+    fn location(&self) -> PathBuf {
+        self.dir.clone()
+    }
+
+    fn append(&self, entry: &str) -> Result<PathBuf, Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let date = entry_time(entry).unwrap_or_else(Utc::now).date_naive();
+        let path = self.day_file(date);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // One locked write per entry, so lines from parallel hooks never interleave
+        file.lock()?;
+        file.write_all(format!("{}\n", entry).as_bytes())?;
+        Ok(path)
+    }
+
+    fn read(&self, range: &TimeRange) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        for (_, path) in self.files(range) {
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            entries.extend(content.lines().filter(|line| !line.trim().is_empty()).map(str::to_string));
+        }
+        Ok(entries)
+    }
+
+    fn follow(&self, on_entry: &mut dyn FnMut(&str)) -> Result<(), Box<dyn Error>> {
+        let latest = || self.files(&TimeRange::default()).pop().map(|(_, path)| path);
+        let mut current = latest();
+        let mut offset = current.as_ref().and_then(|path| fs::metadata(path).ok()).map_or(0, |meta| meta.len());
+        let mut pending = String::new();
+
+        loop {
+            std::thread::sleep(FOLLOW_POLL);
+            let newest = latest();
+            if newest != current {
+                // A new day's log started; read it from the top
+                current = newest;
+                offset = 0;
+                pending.clear();
+            }
+            let Some(path) = &current else {
+                continue;
+            };
+            let Ok(mut file) = fs::File::open(path) else {
+                continue;
+            };
+            if file.metadata().is_ok_and(|meta| meta.len() < offset) {
+                // Truncated or replaced
+                offset = 0;
+            }
+            if file.seek(SeekFrom::Start(offset)).is_err() {
+                continue;
+            }
+            let mut chunk = String::new();
+            let Ok(read) = file.read_to_string(&mut chunk) else {
+                continue;
+            };
+            offset += read as u64;
+            pending.push_str(&chunk);
+
+            // Only complete lines; a half-written entry waits for the next poll
+            if let Some(end) = pending.rfind('\n') {
+                let complete: String = pending.drain(..=end).collect();
+                complete.lines().filter(|line| !line.trim().is_empty()).for_each(&mut *on_entry);
+            }
+        }
+    }
+
+    fn prune(&self, retention_days: u64, today: NaiveDate) -> Result<usize, Box<dyn Error>> {
+        if retention_days == 0 {
+            return Ok(0);
+        }
+        let mut removed = 0;
+        for (date, path) in self.files(&TimeRange::default()) {
+            if (today - date).num_days() > retention_days as i64 && fs::remove_file(path).is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Entries in one SQLite table, indexed by time
+#[cfg(feature = "full")]
+pub struct SqliteStore {
+    path: PathBuf,
+    connection: rusqlite::Connection,
+}
+
+#[cfg(feature = "full")]
+impl SqliteStore {
+    /// Open (creating if needed) the database at `path`
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let connection = rusqlite::Connection::open(path)?;
+        // Hooks from parallel sessions write at the same time as `logs --follow` reads
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                event TEXT,
+                session_id TEXT,
+                entry TEXT NOT NULL,
+                UNIQUE (timestamp, entry)
+            );
+            CREATE INDEX IF NOT EXISTS events_by_time ON events (timestamp);",
+        )?;
+        Ok(Self { path: path.to_path_buf(), connection })
+    }
+
+    /// Insert an entry unless it is already stored; returns whether it was added
+    fn insert(&self, entry: &str) -> Result<bool, Box<dyn Error>> {
+        let value: serde_json::Value = serde_json::from_str(entry)?;
+        let at = value["timestamp"].as_str().and_then(|ts| ts.parse().ok()).unwrap_or_else(Utc::now);
+        let changed = self.connection.execute(
+            "INSERT OR IGNORE INTO events (timestamp, event, session_id, entry) VALUES (?1, ?2, ?3, ?4)",
+            (
+                sortable_time(at),
+                value["hook_input"]["hook_event_name"].as_str(),
+                value["hook_input"]["session_id"].as_str(),
+                entry,
+            ),
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+/// Fixed-width UTC timestamp, so text order is time order
+#[cfg(feature = "full")]
+pub fn sortable_time(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+#[cfg(feature = "full")]
+impl EventStore for SqliteStore {
+    fn location(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn append(&self, entry: &str) -> Result<PathBuf, Box<dyn Error>> {
+        self.insert(entry)?;
+        Ok(self.path.clone())
+    }
+
+    fn read(&self, range: &TimeRange) -> Result<Vec<String>, Box<dyn Error>> {
+        let since = range.since.map(sortable_time).unwrap_or_default();
+        let until = range.until.map(sortable_time);
+        let mut statement = self.connection.prepare(
+            "SELECT entry FROM events WHERE timestamp >= ?1 AND (?2 IS NULL OR timestamp < ?2) ORDER BY timestamp, id",
+        )?;
+        let entries = statement
+            .query_map((since, until), |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(entries)
+    }
+
+    fn follow(&self, on_entry: &mut dyn FnMut(&str)) -> Result<(), Box<dyn Error>> {
+        let mut last_id: i64 = self.connection.query_row("SELECT COALESCE(MAX(id), 0) FROM events", [], |row| row.get(0))?;
+        let mut statement = self.connection.prepare("SELECT id, entry FROM events WHERE id > ?1 ORDER BY id")?;
+        loop {
+            std::thread::sleep(FOLLOW_POLL);
+            let rows = statement
+                .query_map([last_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (id, entry) in rows {
+                last_id = id;
+                on_entry(&entry);
+            }
+        }
+    }
+
+    fn prune(&self, retention_days: u64, today: NaiveDate) -> Result<usize, Box<dyn Error>> {
+        if retention_days == 0 {
+            return Ok(0);
+        }
+        // Same cut-off as the daily files: keep whole days within the retention period
+        let Some(cutoff) = today
+            .checked_sub_days(chrono::Days::new(retention_days))
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+        else {
+            return Ok(0);
+        };
+        let removed = self
+            .connection
+            .execute("DELETE FROM events WHERE timestamp < ?1", [sortable_time(cutoff.and_utc())])?;
+        Ok(removed)
+    }
+
+    fn import(&self, entries: &[String]) -> Result<usize, Box<dyn Error>> {
+        self.connection.execute_batch("BEGIN")?;
+        let mut added = 0;
+        for entry in entries {
+            match self.insert(entry) {
+                Ok(inserted) => added += inserted as usize,
+                // Skip lines that are not JSON, as the readers do
+                Err(e) if e.is::<serde_json::Error>() => {}
+                Err(e) => {
+                    self.connection.execute_batch("ROLLBACK")?;
+                    return Err(e);
+                }
+            }
+        }
+        self.connection.execute_batch("COMMIT")?;
+        Ok(added)
+    }
+}
+
+// Every test covers the SQLite backend, which only the full build has
+#[cfg(all(test, feature = "full"))]
+mod tests {
+    use super::*;
+
+    /// Fresh directory for one test's stores
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ccgadget-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Trigger log entry at a time
+    fn entry(timestamp: &str, event: &str) -> String {
+        serde_json::json!({ "timestamp": timestamp, "hook_input": { "hook_event_name": event, "session_id": "s1" } }).to_string()
+    }
+
+    /// Range starting at an RFC 3339 time
+    fn since(ts: &str) -> TimeRange {
+        TimeRange { since: Some(ts.parse().unwrap()), until: None }
+    }
+
+    #[test]
+    fn test_backends_store_read_and_prune_alike() {
+        let dir = temp_dir("backends");
+        for backend in [Backend::Jsonl, Backend::Sqlite] {
+            let store = open_in(backend, &dir).unwrap();
+            store.append(&entry("2025-07-18T23:00:00Z", "Stop")).unwrap();
+            store.append(&entry("2025-07-20T10:00:00Z", "PreToolUse")).unwrap();
+            store.append(&entry("2025-07-20T11:00:00Z", "Stop")).unwrap();
+
+            assert_eq!(store.read(&TimeRange::default()).unwrap().len(), 3, "{}", backend);
+            // Whole days are fine for JSONL; exact filtering happens in the caller
+            let recent = store.read(&since("2025-07-20T10:30:00Z")).unwrap();
+            assert!(recent.contains(&entry("2025-07-20T11:00:00Z", "Stop")), "{}", backend);
+            assert!(!recent.contains(&entry("2025-07-18T23:00:00Z", "Stop")), "{}", backend);
+
+            let today = NaiveDate::from_ymd_opt(2025, 7, 20).unwrap();
+            assert_eq!(store.prune(1, today).unwrap(), 1, "{}", backend);
+            assert_eq!(store.read(&TimeRange::default()).unwrap().len(), 2, "{}", backend);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parallel_appends_keep_whole_lines() {
+        let dir = temp_dir("parallel");
+        let long = "x".repeat(64 * 1024);
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let (dir, long) = (dir.clone(), long.clone());
+                std::thread::spawn(move || {
+                    let store = JsonlStore::new(&dir);
+                    for _ in 0..10 {
+                        let entry = serde_json::json!({ "timestamp": "2025-07-20T10:00:00Z", "thread": thread, "data": long });
+                        store.append(&entry.to_string()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+
+        let entries = JsonlStore::new(&dir).read(&TimeRange::default()).unwrap();
+        assert_eq!(entries.len(), 80);
+        assert!(entries.iter().all(|entry| serde_json::from_str::<serde_json::Value>(entry).is_ok()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_is_repeatable() {
+        let dir = temp_dir("migrate");
+        let jsonl = open_in(Backend::Jsonl, &dir).unwrap();
+        let sqlite = open_in(Backend::Sqlite, &dir).unwrap();
+        jsonl.append(&entry("2025-07-20T10:00:00Z", "PreToolUse")).unwrap();
+        jsonl.append(&entry("2025-07-20T11:00:00Z", "Stop")).unwrap();
+
+        assert_eq!(migrate(jsonl.as_ref(), sqlite.as_ref()).unwrap(), (2, 2));
+        jsonl.append(&entry("2025-07-21T09:00:00Z", "Stop")).unwrap();
+        assert_eq!(migrate(jsonl.as_ref(), sqlite.as_ref()).unwrap(), (1, 3));
+        assert_eq!(sqlite.read(&TimeRange::default()).unwrap(), jsonl.read(&TimeRange::default()).unwrap());
+
+        // And back, into an empty directory
+        let back = open_in(Backend::Jsonl, &dir.join("back")).unwrap();
+        assert_eq!(migrate(sqlite.as_ref(), back.as_ref()).unwrap(), (3, 3));
+        assert_eq!(migrate(sqlite.as_ref(), back.as_ref()).unwrap(), (0, 3));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
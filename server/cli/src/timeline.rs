@@ -0,0 +1,461 @@
+use crate::pricing::PricingTable;
+use crate::timerange::TimeRange;
+use crate::transcript::{self, TokenUsage};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Longest response text kept per event; the transcript has the rest
+const MAX_TEXT_CHARS: usize = 500;
+
+/// Output format for `export timeline`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TimelineFormat {
+    /// Self-contained, zoomable HTML page
+    Html,
+    /// The timeline events as JSON
+    Json,
+}
+
+impl TimelineFormat {
+    /// File extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TimelineFormat::Html => "html",
+            TimelineFormat::Json => "json",
+        }
+    }
+}
+
+/// What happened during a stretch of the session
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// The user submitted a prompt
+    Prompt,
+    /// The model answered, from the request that led to it until the reply
+    Response,
+    /// A tool call, from the model asking for it until its result came back
+    Tool,
+}
+
+/// One entry on the timeline. Prompt text and tool input use the hook field names so
+/// `[redaction]` applies to them as it does to trigger logs.
+#[derive(Serialize, Debug, Clone)]
+pub struct TimelineEvent {
+    pub kind: EventKind,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Model for responses, tool name for tool calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_input: Option<Value>,
+    /// Start of the response text, truncated to `MAX_TEXT_CHARS`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl TimelineEvent {
+    /// Event of the given kind spanning `start..end` with no details yet
+    fn new(kind: EventKind, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            kind,
+            start,
+            end,
+            label: None,
+            prompt: None,
+            tool_input: None,
+            text: None,
+            is_error: false,
+            tokens: None,
+            cost_usd: None,
+        }
+    }
+}
+
+/// Everything that happened in one session, in transcript order
+#[derive(Serialize, Debug, Clone)]
+pub struct Timeline {
+    pub session_id: String,
+    pub cwd: Option<String>,
+    pub tokens: u64,
+    pub cost_usd: f64,
+    pub events: Vec<TimelineEvent>,
+}
+
+impl Timeline {
+    /// Keep only events that start within `range`, with totals to match
+    pub fn retain(&mut self, range: &TimeRange) {
+        self.events.retain(|event| range.contains(event.start));
+        self.tokens = self.events.iter().filter_map(|event| event.tokens).sum();
+        self.cost_usd = self.events.iter().filter_map(|event| event.cost_usd).sum();
+    }
+}
+
+/// Transcript for a session id, a unique prefix of one, or a path to a transcript file
+pub fn find_transcript(session: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = Path::new(session);
+    if path.extension().is_some_and(|ext| ext == "jsonl") && path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    let stem = |path: &PathBuf| path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let matches: Vec<PathBuf> = transcript::transcript_files(None)?
+        .into_iter()
+        .filter(|path| stem(path).starts_with(session))
+        .collect();
+    if let Some(exact) = matches.iter().find(|path| stem(path) == session) {
+        return Ok(exact.clone());
+    }
+    match matches.as_slice() {
+        [] => Err(format!("No transcript found for session '{}'", session).into()),
+        [only] => Ok(only.clone()),
+        many => Err(format!("Session '{}' is ambiguous: {} transcripts match", session, many.len()).into()),
+    }
+}
+
+/// Build a timeline from transcript text. Split messages (one line per content block)
+/// are merged into one response, and their repeated usage is counted once.
+pub fn build(session_id: &str, content: &str, pricing: &PricingTable) -> Timeline {
+    let mut timeline = Timeline { session_id: session_id.to_string(), cwd: None, tokens: 0, cost_usd: 0.0, events: Vec::new() };
+    let mut responses: HashMap<String, usize> = HashMap::new();
+    let mut open_tools: HashMap<String, usize> = HashMap::new();
+    let mut counted = HashSet::new();
+    let mut last_request: Option<DateTime<Utc>> = None;
+
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let Some(timestamp) = entry["timestamp"].as_str().and_then(|ts| ts.parse::<DateTime<Utc>>().ok()) else {
+            continue;
+        };
+        if timeline.cwd.is_none() {
+            timeline.cwd = entry["cwd"].as_str().map(str::to_string);
+        }
+        let message = &entry["message"];
+
+        match entry["type"].as_str() {
+            Some("user") if entry["isMeta"] != true => {
+                last_request = Some(timestamp);
+                if let Some(text) = message["content"].as_str() {
+                    let mut event = TimelineEvent::new(EventKind::Prompt, timestamp, timestamp);
+                    event.prompt = Some(text.to_string());
+                    timeline.events.push(event);
+                    continue;
+                }
+                for block in message["content"].as_array().into_iter().flatten() {
+                    match block["type"].as_str() {
+                        Some("text") => {
+                            let mut event = TimelineEvent::new(EventKind::Prompt, timestamp, timestamp);
+                            event.prompt = block["text"].as_str().map(str::to_string);
+                            timeline.events.push(event);
+                        }
+                        Some("tool_result") => {
+                            let id = block["tool_use_id"].as_str().unwrap_or_default();
+                            if let Some(index) = open_tools.remove(id) {
+                                let event = &mut timeline.events[index];
+                                event.end = timestamp.max(event.start);
+                                event.is_error = block["is_error"] == true;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Some("assistant") => {
+                let key = message["id"].as_str().map(|id| format!("{}:{}", id, entry["requestId"].as_str().unwrap_or("")));
+                let index = match key.as_ref().and_then(|key| responses.get(key)) {
+                    Some(&index) => index,
+                    None => {
+                        // Transcript lines are not always in time order (e.g. around a compaction)
+                        let requested = last_request.filter(|request| *request <= timestamp).unwrap_or(timestamp);
+                        let mut event = TimelineEvent::new(EventKind::Response, requested, timestamp);
+                        event.label = message["model"].as_str().filter(|m| *m != "<synthetic>").map(str::to_string);
+                        timeline.events.push(event);
+                        if let Some(key) = &key {
+                            responses.insert(key.clone(), timeline.events.len() - 1);
+                        }
+                        timeline.events.len() - 1
+                    }
+                };
+                timeline.events[index].end = timestamp.max(timeline.events[index].end);
+
+                let usage = serde_json::from_value::<TokenUsage>(message["usage"].clone()).ok();
+                let first_sighting = key.as_ref().is_none_or(|key| counted.insert(key.clone()));
+                if let (Some(usage), Some(model), true) = (usage, &timeline.events[index].label, first_sighting) {
+                    let cost = pricing.lookup(model).map(|rates| rates.cost(&usage));
+                    timeline.tokens += usage.total();
+                    timeline.cost_usd += cost.unwrap_or_default();
+                    let event = &mut timeline.events[index];
+                    event.tokens = Some(usage.total());
+                    event.cost_usd = cost;
+                }
+
+                for block in message["content"].as_array().into_iter().flatten() {
+                    match block["type"].as_str() {
+                        Some("text") => {
+                            let event = &mut timeline.events[index];
+                            let text = event.text.get_or_insert_with(String::new);
+                            let room = MAX_TEXT_CHARS.saturating_sub(text.chars().count());
+                            text.extend(block["text"].as_str().unwrap_or_default().chars().take(room));
+                        }
+                        Some("tool_use") => {
+                            let mut event = TimelineEvent::new(EventKind::Tool, timestamp, timestamp);
+                            event.label = block["name"].as_str().map(str::to_string);
+                            event.tool_input = Some(block["input"].clone());
+                            if let Some(id) = block["id"].as_str() {
+                                open_tools.insert(id.to_string(), timeline.events.len());
+                            }
+                            timeline.events.push(event);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    timeline
+}
+
+/// Escape text for an HTML document
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// One-line summary of an event for the table and chart tooltips
+fn summary(event: &Value) -> String {
+    let first_line = |text: &str| text.lines().next().unwrap_or_default().chars().take(120).collect::<String>();
+    if let Some(prompt) = event["prompt"].as_str() {
+        return first_line(prompt);
+    }
+    let input = &event["tool_input"];
+    if let Some(input) = input.as_object() {
+        let detail = ["command", "file_path", "pattern", "url", "description"]
+            .iter()
+            .find_map(|field| input.get(*field).and_then(Value::as_str));
+        return detail.map(first_line).unwrap_or_default();
+    }
+    if let Some(redacted) = input.as_str() {
+        return redacted.to_string();
+    }
+    event["text"].as_str().map(first_line).unwrap_or_default()
+}
+
+/// Render a timeline (already serialized and redacted) as a self-contained HTML page
+/// with a zoomable chart and a table of every event
+pub fn render_html(timeline: &Value) -> String {
+    let session = timeline["session_id"].as_str().unwrap_or_default();
+    let events = timeline["events"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+    let mut rows = String::new();
+    for event in events {
+        let start = event["start"].as_str().unwrap_or_default();
+        let duration = match (
+            start.parse::<DateTime<Utc>>(),
+            event["end"].as_str().unwrap_or_default().parse::<DateTime<Utc>>(),
+        ) {
+            (Ok(start), Ok(end)) if end > start => format!("{:.1}s", (end - start).num_milliseconds() as f64 / 1000.0),
+            _ => String::new(),
+        };
+        rows.push_str(&format!(
+            "<tr class=\"{kind}{error}\"><td>{time}</td><td>{kind}</td><td>{label}</td><td>{duration}</td><td>{tokens}</td><td>{cost}</td><td>{summary}</td></tr>\n",
+            kind = event["kind"].as_str().unwrap_or_default(),
+            error = if event["is_error"] == true { " error" } else { "" },
+            time = escape_html(start.get(11..19).unwrap_or(start)),
+            label = escape_html(event["label"].as_str().unwrap_or_default()),
+            tokens = event["tokens"].as_u64().map(|t| t.to_string()).unwrap_or_default(),
+            cost = event["cost_usd"].as_f64().map(|c| format!("${:.4}", c)).unwrap_or_default(),
+            summary = escape_html(&summary(event)),
+        ));
+    }
+
+    // `</` would end the script element early
+    let data = serde_json::to_string(timeline).unwrap_or_default().replace("</", "<\\/");
+    let title = escape_html(&format!("Session {}", session));
+    let totals = escape_html(&format!(
+        "{} events, {} tokens, ${:.2}{}",
+        events.len(),
+        timeline["tokens"].as_u64().unwrap_or_default(),
+        timeline["cost_usd"].as_f64().unwrap_or_default(),
+        timeline["cwd"].as_str().map(|cwd| format!(" in {}", cwd)).unwrap_or_default(),
+    ));
+    fill(TEMPLATE, &[("TITLE", &title), ("SUMMARY", &totals), ("ROWS", &rows), ("DATA", &data)])
+}
+
+/// Substitute `{{NAME}}` placeholders in one pass, so session text that happens to
+/// contain a placeholder is left alone
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((before, after)) = rest.split_once("{{") {
+        out.push_str(before);
+        let Some((name, tail)) = after.split_once("}}") else {
+            out.push_str("{{");
+            rest = after;
+            continue;
+        };
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&format!("{{{{{}}}}}", name)),
+        }
+        rest = tail;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Page layout for `render_html`; the chart is drawn from the embedded JSON
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { font: 14px system-ui, sans-serif; margin: 1.5em; color: #222; }
+h1 { font-size: 1.3em; margin-bottom: 0.2em; }
+.controls { margin: 1em 0 0.5em; }
+.controls button { margin-right: 0.3em; }
+#chart { overflow-x: auto; border: 1px solid #ccc; position: relative; }
+#lanes { position: relative; height: 96px; }
+.lane-label { position: sticky; left: 0; font-size: 11px; color: #666; background: #fffc; padding: 0 4px; z-index: 1; }
+.bar { position: absolute; height: 22px; min-width: 3px; border-radius: 3px; cursor: pointer; }
+.prompt { background: #4a7fd4; }
+.response { background: #8bc48a; }
+.tool { background: #e0a040; }
+.error { background: #d9534f; }
+#detail { white-space: pre-wrap; font: 12px ui-monospace, monospace; background: #f6f6f6; padding: 0.6em; min-height: 2em; }
+table { border-collapse: collapse; margin-top: 1em; width: 100%; }
+td, th { border-bottom: 1px solid #eee; padding: 3px 6px; text-align: left; vertical-align: top; }
+tr.prompt td, tr.response td, tr.tool td, tr.error td { background: none; }
+tr.error td:nth-child(2) { color: #d9534f; }
+</style>
+</head>
+<body>
+<h1>{{TITLE}}</h1>
+<div>{{SUMMARY}}</div>
+<div class="controls">
+<button id="zoom-in">Zoom in</button><button id="zoom-out">Zoom out</button><button id="zoom-reset">Fit</button>
+<small>Ctrl + scroll also zooms. Click a bar for details.</small>
+</div>
+<div id="chart"><div id="lanes"></div></div>
+<div id="detail"></div>
+<table>
+<thead><tr><th>Time (UTC)</th><th>Kind</th><th>Name</th><th>Duration</th><th>Tokens</th><th>Cost</th><th>Summary</th></tr></thead>
+<tbody>
+{{ROWS}}</tbody>
+</table>
+<script id="timeline-data" type="application/json">{{DATA}}</script>
+<script>
+const timeline = JSON.parse(document.getElementById("timeline-data").textContent);
+const events = timeline.events.map(e => ({ ...e, t0: Date.parse(e.start), t1: Date.parse(e.end) }));
+const lanes = { prompt: 0, response: 1, tool: 2 };
+const chart = document.getElementById("chart");
+const container = document.getElementById("lanes");
+const detail = document.getElementById("detail");
+const first = Math.min(...events.map(e => e.t0));
+const last = Math.max(...events.map(e => e.t1));
+const span = Math.max(last - first, 1000);
+let zoom = 1;
+
+function draw() {
+  const width = chart.clientWidth * zoom;
+  container.style.width = width + "px";
+  container.innerHTML = "";
+  for (const [name, lane] of Object.entries(lanes)) {
+    const label = document.createElement("div");
+    label.className = "lane-label";
+    label.style.position = "absolute";
+    label.style.top = (lane * 30 + 4) + "px";
+    label.textContent = name;
+    container.appendChild(label);
+  }
+  events.forEach(e => {
+    const bar = document.createElement("div");
+    bar.className = "bar " + e.kind + (e.is_error ? " error" : "");
+    bar.style.left = ((e.t0 - first) / span * width) + "px";
+    bar.style.width = ((e.t1 - e.t0) / span * width) + "px";
+    bar.style.top = (lanes[e.kind] * 30 + 4) + "px";
+    bar.title = [e.label, ((e.t1 - e.t0) / 1000).toFixed(1) + "s"].filter(Boolean).join(" · ");
+    bar.onclick = () => { detail.textContent = JSON.stringify(e, (k, v) => (k === "t0" || k === "t1") ? undefined : v, 2); };
+    container.appendChild(bar);
+  });
+}
+
+function setZoom(value, anchor) {
+  const ratio = (chart.scrollLeft + anchor) / (chart.clientWidth * zoom);
+  zoom = Math.min(Math.max(value, 1), 500);
+  draw();
+  chart.scrollLeft = ratio * chart.clientWidth * zoom - anchor;
+}
+
+document.getElementById("zoom-in").onclick = () => setZoom(zoom * 2, chart.clientWidth / 2);
+document.getElementById("zoom-out").onclick = () => setZoom(zoom / 2, chart.clientWidth / 2);
+document.getElementById("zoom-reset").onclick = () => setZoom(1, 0);
+chart.addEventListener("wheel", ev => {
+  if (!ev.ctrlKey) return;
+  ev.preventDefault();
+  setZoom(zoom * (ev.deltaY < 0 ? 1.25 : 0.8), ev.clientX - chart.getBoundingClientRect().left);
+}, { passive: false });
+window.addEventListener("resize", draw);
+draw();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A short session: a prompt, a split response with a tool call, and the tool result
+    const SESSION: &str = r#"{"type":"user","timestamp":"2025-07-20T10:00:00Z","cwd":"/work/api","message":{"content":"fix the </script> bug"}}
+{"type":"assistant","timestamp":"2025-07-20T10:00:04Z","requestId":"r1","message":{"id":"m1","model":"claude-sonnet-4","usage":{"output_tokens":1000000},"content":[{"type":"text","text":"Running tests"}]}}
+{"type":"assistant","timestamp":"2025-07-20T10:00:05Z","requestId":"r1","message":{"id":"m1","model":"claude-sonnet-4","usage":{"output_tokens":1000000},"content":[{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test"}}]}}
+{"type":"user","timestamp":"2025-07-20T10:00:35Z","message":{"content":[{"type":"tool_result","tool_use_id":"t1","is_error":true,"content":"1 failed"}]}}"#;
+
+    #[test]
+    fn test_build_merges_split_responses_and_times_tools() {
+        let timeline = build("abc", SESSION, &PricingTable::builtin());
+        let kinds: Vec<EventKind> = timeline.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::Prompt, EventKind::Response, EventKind::Tool]);
+        assert_eq!(timeline.cwd.as_deref(), Some("/work/api"));
+
+        let response = &timeline.events[1];
+        assert_eq!((response.end - response.start).num_seconds(), 5);
+        assert_eq!(response.text.as_deref(), Some("Running tests"));
+        assert_eq!(response.tokens, Some(1_000_000));
+        assert!((timeline.cost_usd - 15.0).abs() < 1e-9);
+
+        let tool = &timeline.events[2];
+        assert_eq!(tool.label.as_deref(), Some("Bash"));
+        assert_eq!((tool.end - tool.start).num_seconds(), 30);
+        assert!(tool.is_error);
+    }
+
+    #[test]
+    fn test_render_html_escapes_session_content() {
+        let timeline = build("abc", SESSION, &PricingTable::builtin());
+        let html = render_html(&serde_json::to_value(&timeline).unwrap());
+        assert!(html.contains("fix the &lt;/script&gt; bug"));
+        assert!(!html.contains("fix the </script>"));
+        assert!(html.contains("cargo test"));
+        assert!(html.contains("3 events, 1000000 tokens, $15.00 in /work/api"));
+    }
+}
@@ -0,0 +1,268 @@
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+
+/// Which end of a range a value bounds. A value naming a whole day (`2025-07-20`,
+/// `yesterday`) starts at that day's midnight and ends at the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Start,
+    End,
+}
+
+/// A `--since`/`--until` range: `since` is inclusive, `until` exclusive
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /// Whether an instant falls inside the range
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| at >= since) && self.until.is_none_or(|until| at < until)
+    }
+
+    /// Short description in local time, e.g. "2025-07-01..2025-07-08" (whole days,
+    /// inclusive) or "since 2025-07-20 09:00"
+    pub fn describe(&self) -> String {
+        let show = |at: DateTime<Utc>, end: bool| {
+            let local = at.with_timezone(&Local);
+            match (local.time() == NaiveTime::MIN, end) {
+                // An exclusive midnight end is the close of the previous day
+                (true, true) => local.date_naive().pred_opt().unwrap_or(local.date_naive()).to_string(),
+                (true, false) => local.date_naive().to_string(),
+                _ => local.format("%Y-%m-%d %H:%M").to_string(),
+            }
+        };
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => format!("{}..{}", show(since, false), show(until, true)),
+            (Some(since), None) => format!("since {}", show(since, false)),
+            (None, Some(until)) => format!("until {}", show(until, true)),
+            (None, None) => "all time".to_string(),
+        }
+    }
+}
+
+/// Parse a `--since` value in the local time zone (see `parse_in`)
+pub fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_in(value, &Local::now(), Edge::Start)
+}
+
+/// Parse an `--until` value in the local time zone (see `parse_in`)
+pub fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_in(value, &Local::now(), Edge::End)
+}
+
+/// Parse a `--for` value such as `1h` or `30m` into when that span from now ends
+pub fn parse_for(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_duration(value.trim())
+        .filter(|duration| *duration > Duration::zero())
+        .map(|duration| Utc::now() + duration)
+        .ok_or_else(|| format!("Invalid duration '{}': use e.g. 45m, 1h, or 2 hours", value))
+}
+
+/// Midnight starting a local calendar day
+pub fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    resolve(&Local, date.and_time(NaiveTime::MIN))
+}
+
+/// Parse a human-friendly time relative to `now`, in `now`'s time zone:
+///
+/// - `now`, or an RFC 3339 timestamp with an offset
+/// - durations before now: `2h ago`, `30 minutes ago`, `90s` (the `ago` is optional)
+/// - days: `today`, `yesterday`, `tomorrow`, `monday`/`last monday` (the most recent
+///   one before today), `2025-07-20`, `20250720`
+/// - any of those days followed by a time (`yesterday 9am`, `2025-07-20 14:30`), or a
+///   bare time for today (`9:30pm`)
+///
+/// Durations are exact elapsed time, so `24h ago` and `yesterday` differ across a DST
+/// change. Local times that a DST change skips are moved forward by the gap, and times
+/// it repeats resolve to the first occurrence.
+pub fn parse_in<Tz: TimeZone>(value: &str, now: &DateTime<Tz>, edge: Edge) -> Result<DateTime<Utc>, String> {
+    let text = value.trim().to_lowercase();
+    let invalid = || {
+        format!(
+            "Invalid time '{}', expected e.g. 2h ago, yesterday 9am, 2025-07-20, or 2025-07-20T14:30:00Z",
+            value.trim()
+        )
+    };
+    if text == "now" {
+        return Ok(now.with_timezone(&Utc));
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(value.trim()) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    if let Some(ago) = parse_duration(text.strip_suffix("ago").unwrap_or(&text).trim()) {
+        return Ok((now.clone() - ago).with_timezone(&Utc));
+    }
+
+    let tz = now.timezone();
+    let today = now.date_naive();
+    // "2025-07-20T14:30" reads like "2025-07-20 14:30"
+    let text = match text.split_once('t') {
+        Some((date, time)) if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => format!("{} {}", date, time),
+        _ => text,
+    };
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return Err(invalid());
+    }
+
+    // Longest prefix of the words that names a day; the rest is a time of day
+    let (day, rest) = (1..=words.len().min(2))
+        .rev()
+        .find_map(|n| parse_day(&words[..n].join(" "), today).map(|day| (day, &words[n..])))
+        .unwrap_or((today, &words[..]));
+    if rest.len() == words.len() {
+        // No day named: the whole value must be a time today
+        let time = parse_time(&words.join(" ")).ok_or_else(invalid)?;
+        return Ok(resolve(&tz, today.and_time(time)));
+    }
+    if rest.is_empty() {
+        let day = match edge {
+            Edge::Start => day,
+            Edge::End => day.succ_opt().ok_or_else(invalid)?,
+        };
+        return Ok(resolve(&tz, day.and_time(NaiveTime::MIN)));
+    }
+    let time = parse_time(&rest.join(" ")).ok_or_else(invalid)?;
+    Ok(resolve(&tz, day.and_time(time)))
+}
+
+/// A duration such as `90s`, `30m`, `2 hours`, or `1w`
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = text.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    let unit = unit.trim();
+    let unit = unit.strip_suffix('s').filter(|u| u.len() > 1).unwrap_or(unit);
+    match unit {
+        "s" | "sec" | "second" => Some(Duration::seconds(amount)),
+        "m" | "min" | "minute" => Some(Duration::minutes(amount)),
+        "h" | "hr" | "hour" => Some(Duration::hours(amount)),
+        "d" | "day" => Some(Duration::days(amount)),
+        "w" | "wk" | "week" => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// A calendar day named by a word or date, relative to `today`
+fn parse_day(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match text {
+        "today" => return Some(today),
+        "yesterday" => return today.pred_opt(),
+        "tomorrow" => return today.succ_opt(),
+        _ => {}
+    }
+    if let Ok(weekday) = text.strip_prefix("last ").unwrap_or(text).parse::<Weekday>() {
+        let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday() - 1) % 7 + 1;
+        return today.checked_sub_signed(Duration::days(back as i64));
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(text, "%Y%m%d"))
+        .ok()
+}
+
+/// A time of day: `14:30`, `14:30:05`, `9am`, `9:30 pm`
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    let compact = text.replace(' ', "");
+    for format in ["%H:%M", "%H:%M:%S"] {
+        if let Ok(time) = NaiveTime::parse_from_str(&compact, format) {
+            return Some(time);
+        }
+    }
+    let (clock, pm) = match (compact.strip_suffix("am"), compact.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, false),
+        (_, Some(clock)) => (clock, true),
+        _ => return None,
+    };
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "0"));
+    let (hour, minute): (u32, u32) = (hour.parse().ok()?, minute.parse().ok()?);
+    if !(1..=12).contains(&hour) {
+        return None;
+    }
+    NaiveTime::from_hms_opt(hour % 12 + if pm { 12 } else { 0 }, minute, 0)
+}
+
+/// Instant of a local time in `tz`. Times skipped by a DST change keep the offset from
+/// before it (so 02:30 becomes 03:30); repeated times take the first occurrence.
+pub fn resolve<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => at.with_timezone(&Utc),
+        LocalResult::None => {
+            // DST gaps are at most a few hours; the offset just before still applies
+            let before = tz.offset_from_utc_datetime(&(local - Duration::hours(3)));
+            let offset = chrono::Offset::fix(&before);
+            (local - Duration::seconds(offset.local_minus_utc() as i64)).and_utc()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::{New_York, Sao_Paulo};
+
+    /// Parse relative to a New York wall time
+    fn ny(value: &str, now: &str, edge: Edge) -> String {
+        let now = New_York.from_local_datetime(&now.parse().unwrap()).unwrap();
+        parse_in(value, &now, edge).unwrap().to_rfc3339()
+    }
+
+    #[test]
+    fn test_relative_and_named_days() {
+        let now = "2025-07-16T15:00:00";
+        assert_eq!(ny("2h ago", now, Edge::Start), "2025-07-16T17:00:00+00:00");
+        assert_eq!(ny("30 minutes ago", now, Edge::Start), "2025-07-16T18:30:00+00:00");
+        assert_eq!(ny("90s", now, Edge::Start), "2025-07-16T18:58:30+00:00");
+        assert_eq!(ny("yesterday 9am", now, Edge::Start), "2025-07-15T13:00:00+00:00");
+        assert_eq!(ny("today 9:30 PM", now, Edge::End), "2025-07-17T01:30:00+00:00");
+        assert_eq!(ny("14:30", now, Edge::Start), "2025-07-16T18:30:00+00:00");
+        // 2025-07-16 is a Wednesday; "monday" is the one before, "wednesday" a week back
+        assert_eq!(ny("monday", now, Edge::Start), "2025-07-14T04:00:00+00:00");
+        assert_eq!(ny("last wednesday", now, Edge::Start), "2025-07-09T04:00:00+00:00");
+        assert_eq!(ny("2025-07-01", now, Edge::Start), "2025-07-01T04:00:00+00:00");
+        assert_eq!(ny("2025-07-01", now, Edge::End), "2025-07-02T04:00:00+00:00");
+        assert_eq!(ny("20250701", now, Edge::Start), "2025-07-01T04:00:00+00:00");
+        assert_eq!(ny("2025-07-01T08:15", now, Edge::Start), "2025-07-01T12:15:00+00:00");
+        assert_eq!(ny("2025-07-01T08:15:00Z", now, Edge::End), "2025-07-01T08:15:00+00:00");
+        for bad in ["soon", "13pm", "5y ago", "yesterday at noon", ""] {
+            let now = New_York.from_local_datetime(&now.parse().unwrap()).unwrap();
+            assert!(parse_in(bad, &now, Edge::Start).is_err(), "{}", bad);
+        }
+        let hour = parse_for("1h").unwrap() - Utc::now();
+        assert!(hour > Duration::minutes(59) && hour <= Duration::hours(1));
+        assert!(parse_for("0m").is_err() && parse_for("forever").is_err());
+    }
+
+    #[test]
+    fn test_dst_edges() {
+        // Spring forward (2025-03-09): the day is 23 hours long
+        let after = "2025-03-10T12:00:00";
+        assert_eq!(ny("yesterday", after, Edge::Start), "2025-03-09T05:00:00+00:00");
+        assert_eq!(ny("yesterday", after, Edge::End), "2025-03-10T04:00:00+00:00");
+        // 02:30 never happens; it is read as 03:30 EDT
+        assert_eq!(ny("2025-03-09 2:30am", after, Edge::Start), "2025-03-09T07:30:00+00:00");
+        // Durations are elapsed time: 03:30 EDT minus 2h is 00:30 EST
+        assert_eq!(ny("2h ago", "2025-03-09T03:30:00", Edge::Start), "2025-03-09T05:30:00+00:00");
+
+        // Fall back (2025-11-02): 01:30 happens twice; the first (EDT) wins
+        assert_eq!(ny("2025-11-02 01:30", "2025-11-03T12:00:00", Edge::Start), "2025-11-02T05:30:00+00:00");
+        assert_eq!(ny("2025-11-02", "2025-11-03T12:00:00", Edge::End), "2025-11-03T05:00:00+00:00");
+
+        // Where DST starts at midnight, the day begins at 01:00
+        let now = Sao_Paulo.with_ymd_and_hms(2018, 11, 5, 12, 0, 0).unwrap();
+        assert_eq!(parse_in("2018-11-04", &now, Edge::Start).unwrap().to_rfc3339(), "2018-11-04T03:00:00+00:00");
+    }
+
+    #[test]
+    fn test_range_contains_and_describe() {
+        let at = |ts: &str| ts.parse::<DateTime<Utc>>().unwrap();
+        let range = TimeRange { since: Some(at("2025-07-01T00:00:00Z")), until: Some(at("2025-07-02T00:00:00Z")) };
+        assert!(range.contains(at("2025-07-01T00:00:00Z")));
+        assert!(!range.contains(at("2025-07-02T00:00:00Z")));
+        assert!(TimeRange::default().contains(at("1999-01-01T00:00:00Z")));
+        assert_eq!(TimeRange::default().describe(), "all time");
+        let days = TimeRange { since: Some(parse_since("2025-07-01").unwrap()), until: Some(parse_until("2025-07-08").unwrap()) };
+        assert_eq!(days.describe(), "2025-07-01..2025-07-08");
+    }
+}
@@ -0,0 +1,299 @@
+use crate::burn::{self, BurnSnapshot};
+use crate::config::Config;
+use crate::focus::SessionInfo;
+use crate::ipc::{self, DaemonStatus, FeedEntry, IpcRequest, IpcResponse, LinkState};
+use crate::output;
+use chrono::{DateTime, Local, Utc};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the daemon is polled and the screen redrawn
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// How often the burn rate is recomputed from usage records
+const BURN_REFRESH: Duration = Duration::from_secs(10);
+
+/// Longest wait for each daemon request
+const IPC_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Feed lines kept for the event list
+const FEED_LINES: usize = 200;
+
+/// Whether the dashboard can take over the terminal
+pub fn supported() -> bool {
+    io::stdout().is_terminal() && !output::accessible() && !output::json()
+}
+
+/// What the dashboard shows, refreshed from the daemon
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    /// `None` while the daemon is not running
+    status: Option<DaemonStatus>,
+    sessions: Vec<SessionInfo>,
+    focused: Option<String>,
+    burn: Option<BurnSnapshot>,
+    /// Hook events the daemon received, oldest first
+    feed: VecDeque<FeedEntry>,
+}
+
+impl Dashboard {
+    /// Ask the daemon for its status, sessions, and the events received since the last
+    /// refresh
+    pub fn refresh(&mut self) {
+        let status = match ipc::request(&IpcRequest::Status, IPC_TIMEOUT) {
+            Ok(IpcResponse::Status(status)) => *status,
+            _ => {
+                self.status = None;
+                self.sessions.clear();
+                return;
+            }
+        };
+        // A restarted daemon numbers its feed from 1 again
+        if self.status.as_ref().is_some_and(|known| known.started_at != status.started_at) {
+            self.feed.clear();
+        }
+        self.status = Some(status);
+        if let Ok(IpcResponse::Sessions { focused, sessions, .. }) = ipc::request(&IpcRequest::Sessions, IPC_TIMEOUT) {
+            self.focused = focused;
+            self.sessions = sessions;
+        }
+        let after = self.feed.back().map_or(0, |entry| entry.seq);
+        if let Ok(IpcResponse::Feed { events }) = ipc::request(&IpcRequest::Feed { after }, IPC_TIMEOUT) {
+            self.push_feed(events);
+        }
+    }
+
+    /// Add events to the feed, dropping the oldest beyond its length
+    fn push_feed(&mut self, events: Vec<FeedEntry>) {
+        let after = self.feed.back().map_or(0, |entry| entry.seq);
+        for entry in events.into_iter().filter(|entry| entry.seq > after) {
+            if self.feed.len() >= FEED_LINES {
+                self.feed.pop_front();
+            }
+            self.feed.push_back(entry);
+        }
+    }
+
+    /// Draw the dashboard: daemon and link state, active sessions, the usage block, and
+    /// the event feed, newest at the bottom
+    pub fn draw(&self, frame: &mut Frame, now: DateTime<Utc>) {
+        let [header, middle, feed, hint] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(8),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [sessions, usage] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(middle);
+
+        frame.render_widget(Paragraph::new(self.header()), header);
+        frame.render_widget(
+            Paragraph::new(self.session_lines(now)).block(Block::bordered().title(" Sessions ")),
+            sessions,
+        );
+        frame.render_widget(
+            Paragraph::new(self.usage_lines(now)).block(Block::bordered().title(" Usage block ")),
+            usage,
+        );
+
+        // Only the newest events that fit, so the list scrolls as they arrive
+        let rows = feed.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self.feed.iter().skip(self.feed.len().saturating_sub(rows)).map(feed_line).collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Events ")), feed);
+        frame.render_widget(Paragraph::new("q quit").dim(), hint);
+    }
+
+    /// Daemon, link, and device state on one line
+    fn header(&self) -> Line<'static> {
+        let Some(status) = &self.status else {
+            return Line::from(vec![
+                Span::raw("ccgadget top  "),
+                Span::raw("daemon not running").red(),
+                Span::raw(" (start it with `ccgadget start`)"),
+            ]);
+        };
+        let state = Span::raw(status.state.to_string());
+        let state = match status.state {
+            LinkState::Ready => state.green(),
+            LinkState::Disconnected => state.red(),
+            _ => state.yellow(),
+        };
+        let mut spans = vec![Span::raw("ccgadget top  link: "), state];
+        spans.push(Span::raw(format!(
+            "  device: {}  queued: {}  sent: {}",
+            status.device.as_deref().unwrap_or("none"),
+            status.queued,
+            status.delivered
+        )));
+        if status.maintenance {
+            spans.push(Span::raw("  maintenance").yellow());
+        }
+        if status.dnd.is_some() {
+            spans.push(Span::raw("  do not disturb").yellow());
+        } else if status.quiet_hours {
+            spans.push(Span::raw("  quiet hours").yellow());
+        }
+        Line::from(spans)
+    }
+
+    /// Active sessions, the focused one marked, with how long ago each was heard from
+    fn session_lines(&self, now: DateTime<Utc>) -> Vec<Line<'static>> {
+        if self.sessions.is_empty() {
+            return vec![Line::from("No active sessions").dim()];
+        }
+        self.sessions
+            .iter()
+            .rev()
+            .map(|session| {
+                let focused = self.focused.as_deref() == Some(session.session_id.as_str());
+                let text = format!(
+                    "{} {:<8}  {:>4}s ago  {}",
+                    if focused { "▶" } else { " " },
+                    session.session_id.chars().take(8).collect::<String>(),
+                    (now - session.last_event).num_seconds().max(0),
+                    session
+                        .tokens_per_minute
+                        .map(|rate| format!("{:.0} tok/min", rate))
+                        .unwrap_or_default(),
+                );
+                if focused {
+                    Line::from(text).bold()
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect()
+    }
+
+    /// Burn rate and block countdown, then the focused session's context and any
+    /// limit or permission Claude is waiting on
+    fn usage_lines(&self, now: DateTime<Utc>) -> Vec<Line<'static>> {
+        let mut lines: Vec<Line> = burn::render_live(self.burn.as_ref(), now).lines().map(|line| Line::from(line.to_string())).collect();
+        let Some(status) = &self.status else {
+            return lines;
+        };
+        if let Some(context) = status.context {
+            lines.push(Line::from(format!("Context:    {:.0}% of {} tokens", context.percent, context.limit)));
+        }
+        if let Some(limit) = status.limit.as_ref().filter(|limit| limit.active(now)) {
+            let text = match limit.countdown(now) {
+                Some(countdown) => format!("Claude hit its {}; resets in {}", limit.kind, countdown),
+                None => format!("Claude hit its {}", limit.kind),
+            };
+            lines.push(Line::from(text).red());
+        }
+        if let Some(wait) = &status.awaiting_permission {
+            let tool = wait.tool.as_deref().map(|tool| format!(" to use {}", tool)).unwrap_or_default();
+            lines.push(Line::from(format!("Waiting for permission{}", tool)).yellow());
+        }
+        lines
+    }
+}
+
+/// One feed row: local time, session, event, and what it was about
+fn feed_line(entry: &FeedEntry) -> Line<'static> {
+    let detail = entry
+        .tool
+        .as_deref()
+        .or(entry.message.as_deref())
+        .map(str::to_string)
+        .or_else(|| entry.activity.map(|activity| activity.to_string()))
+        .unwrap_or_default();
+    Line::from(vec![
+        Span::raw(format!("{}  ", entry.at.with_timezone(&Local).format("%H:%M:%S"))).dim(),
+        Span::raw(format!("{:<8}  ", entry.session_id.as_deref().unwrap_or("-").chars().take(8).collect::<String>())),
+        Span::styled(format!("{:<18}", entry.event), Style::new().cyan()),
+        Span::raw(detail),
+    ])
+}
+
+/// Run the dashboard until `q`, Esc, or Ctrl-C. The burn rate is computed on a
+/// background thread, since reading usage can take a moment.
+pub fn run(config: &Config) -> io::Result<()> {
+    let (burn_tx, burn_rx) = mpsc::channel();
+    let config = config.clone();
+    thread::spawn(move || loop {
+        if burn_tx.send(burn::current(&config)).is_err() {
+            break;
+        }
+        thread::sleep(BURN_REFRESH);
+    });
+
+    let mut terminal = ratatui::try_init()?;
+    let mut dashboard = Dashboard::default();
+    let result = loop {
+        dashboard.refresh();
+        while let Ok(burn) = burn_rx.try_recv() {
+            dashboard.burn = burn;
+        }
+        if let Err(e) = terminal.draw(|frame| dashboard.draw(frame, Utc::now())) {
+            break Err(e);
+        }
+        match quit_pressed(REFRESH) {
+            Ok(false) => {}
+            Ok(true) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+/// Wait up to `timeout` for a key; returns whether it asks to quit
+fn quit_pressed(timeout: Duration) -> io::Result<bool> {
+    if !event::poll(timeout)? {
+        return Ok(false);
+    }
+    let Event::Key(key) = event::read()? else {
+        return Ok(false);
+    };
+    Ok(key.kind == KeyEventKind::Press
+        && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn entry(seq: u64, event: &str, tool: Option<&str>) -> FeedEntry {
+        FeedEntry {
+            seq,
+            at: Utc::now(),
+            event: event.to_string(),
+            session_id: Some("3451fab2-aaaa".to_string()),
+            tool: tool.map(str::to_string),
+            activity: None,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_feed_keeps_new_events_and_draws_them() {
+        let mut dashboard = Dashboard::default();
+        dashboard.push_feed(vec![entry(1, "UserPromptSubmit", None), entry(2, "PreToolUse", Some("Bash"))]);
+        // Events already shown are not added twice
+        dashboard.push_feed(vec![entry(2, "PreToolUse", Some("Bash")), entry(3, "Stop", None)]);
+        assert_eq!(dashboard.feed.iter().map(|entry| entry.seq).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame, Utc::now())).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("daemon not running"));
+        assert!(screen.contains("No active usage block"));
+        assert!(screen.contains("PreToolUse"));
+        assert!(screen.contains("Bash"));
+        assert!(screen.contains("3451fab2"));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::paths;
+use crate::project::ProjectResolver;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Token counts reported by the API for one or more assistant messages
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Accumulate another usage block into this one
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+
+    /// Total tokens across all categories
+    pub fn total(&self) -> u64 {
+        self.input_tokens
+            + self.output_tokens
+            + self.cache_creation_input_tokens
+            + self.cache_read_input_tokens
+    }
+}
+
+/// A single billable assistant response extracted from a transcript
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Utc>,
+    pub session_id: Option<String>,
+    /// Working directory the session was running in
+    pub cwd: Option<String>,
+    pub model: String,
+    pub usage: TokenUsage,
+    /// Message and request IDs used to drop duplicated entries
+    pub dedup_key: Option<String>,
+    /// Project root the response worked in, when it could be determined
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawEntry {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+    #[serde(rename = "sessionId")]
+    session_id: Option<String>,
+    #[serde(rename = "requestId")]
+    request_id: Option<String>,
+    cwd: Option<String>,
+    message: Option<RawMessage>,
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    id: Option<String>,
+    model: Option<String>,
+    usage: Option<TokenUsage>,
+    content: Option<serde_json::Value>,
+}
+
+/// Tool input fields that name the file a tool worked on
+const PATH_FIELDS: [&str; 3] = ["file_path", "notebook_path", "path"];
+
+/// Files named by the tool calls in an assistant message's content
+fn touched_files(content: &serde_json::Value) -> Vec<String> {
+    let Some(blocks) = content.as_array() else {
+        return Vec::new();
+    };
+    blocks
+        .iter()
+        .filter(|block| block["type"] == "tool_use")
+        .flat_map(|block| PATH_FIELDS.iter().filter_map(|field| block["input"][field].as_str()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Usage record for a parsed transcript line, if it carries usage data, with the files
+/// its tool calls touched
+fn usage_record(entry: RawEntry) -> Option<(UsageRecord, Vec<String>)> {
+    if entry.kind.as_deref() != Some("assistant") {
+        return None;
+    }
+    let message = entry.message?;
+    let usage = message.usage?;
+    let touched = message.content.as_ref().map(touched_files).unwrap_or_default();
+    let model = message.model.filter(|m| m != "<synthetic>")?;
+    let dedup_key = match (&message.id, &entry.request_id) {
+        (Some(id), Some(req)) => Some(format!("{}:{}", id, req)),
+        (Some(id), None) => Some(id.clone()),
+        _ => None,
+    };
+
+    let record = UsageRecord {
+        timestamp: entry.timestamp?,
+        session_id: entry.session_id,
+        cwd: entry.cwd,
+        model,
+        usage,
+        dedup_key,
+        project: None,
+    };
+    Some((record, touched))
+}
+
+/// Read all usage records from a transcript file, skipping duplicated messages and
+/// attributing each to a project (see `attribute_projects`)
+pub fn read_usage_records(path: &Path) -> Result<Vec<UsageRecord>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut records = attribute_projects(&content, &mut ProjectResolver::default());
+    let mut seen = HashSet::new();
+    records.retain(|record| match &record.dedup_key {
+        Some(key) => seen.insert(key.clone()),
+        None => true,
+    });
+    Ok(records)
+}
+
+/// Usage records from transcript text with their project set from the working directory
+/// in effect at that point (carried forward when a line lacks one) and the files the
+/// message's tool calls touched, merged across the lines of a split message
+fn attribute_projects(content: &str, resolver: &mut ProjectResolver) -> Vec<UsageRecord> {
+    let mut cwd: Option<String> = None;
+    let mut records = Vec::new();
+    let mut files: HashMap<String, Vec<String>> = HashMap::new();
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<RawEntry>(line) else {
+            continue;
+        };
+        if entry.cwd.is_some() {
+            cwd = entry.cwd.clone();
+        }
+        let Some((mut record, touched)) = usage_record(entry) else {
+            continue;
+        };
+        record.cwd = record.cwd.or_else(|| cwd.clone());
+        files.entry(record.dedup_key.clone().unwrap_or_default()).or_default().extend(touched);
+        records.push(record);
+    }
+    for record in &mut records {
+        let touched = files.get(record.dedup_key.as_deref().unwrap_or("")).map(Vec::as_slice).unwrap_or(&[]);
+        record.project = resolver.attribute(record.cwd.as_deref(), touched);
+    }
+    records
+}
+
+/// List transcript files under Claude's projects directory modified since `since`
+pub fn transcript_files(since: Option<SystemTime>) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let projects_dir = paths::claude_dir()?.join("projects");
+    let mut files = Vec::new();
+    if !projects_dir.is_dir() {
+        return Ok(files);
+    }
+
+    for project in fs::read_dir(&projects_dir)?.flatten() {
+        let project_path = project.path();
+        if !project_path.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&project_path)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            if let Some(since) = since {
+                let modified = entry.metadata().and_then(|m| m.modified());
+                if matches!(modified, Ok(time) if time < since) {
+                    continue;
+                }
+            }
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse one transcript JSONL line into a usage record, if it carries usage data
+    fn parse_usage_line(line: &str) -> Option<UsageRecord> {
+        let entry: RawEntry = serde_json::from_str(line).ok()?;
+        usage_record(entry).map(|(record, _)| record)
+    }
+
+    #[test]
+    fn test_parse_assistant_usage_line() {
+        let line = r#"{"type":"assistant","sessionId":"abc","requestId":"req_1","timestamp":"2025-07-20T10:00:00.000Z","message":{"id":"msg_1","model":"claude-sonnet-4-20250514","usage":{"input_tokens":10,"output_tokens":20,"cache_creation_input_tokens":30,"cache_read_input_tokens":40}}}"#;
+        let record = parse_usage_line(line).expect("usage record");
+        assert_eq!(record.model, "claude-sonnet-4-20250514");
+        assert_eq!(record.session_id.as_deref(), Some("abc"));
+        assert_eq!(record.usage.total(), 100);
+        assert_eq!(record.dedup_key.as_deref(), Some("msg_1:req_1"));
+    }
+
+    #[test]
+    fn test_attribution_carries_cwd_and_follows_touched_files() {
+        let content = [
+            r#"{"type":"user","cwd":"/work/api","message":{"content":"hi"}}"#,
+            r#"{"type":"assistant","timestamp":"2025-07-20T10:00:00Z","message":{"id":"m1","model":"claude-sonnet-4","usage":{"output_tokens":1},"content":[{"type":"text","text":"ok"}]}}"#,
+            r#"{"type":"assistant","timestamp":"2025-07-20T10:01:00Z","cwd":"/work/api","message":{"id":"m2","model":"claude-sonnet-4","usage":{"output_tokens":1},"content":[{"type":"text","text":"editing"}]}}"#,
+            r#"{"type":"assistant","timestamp":"2025-07-20T10:01:00Z","cwd":"/work/api","message":{"id":"m2","model":"claude-sonnet-4","usage":{"output_tokens":1},"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/work/web/app.ts"}}]}}"#,
+        ]
+        .join("\n");
+        let records = attribute_projects(&content, &mut ProjectResolver::default());
+        assert_eq!(records[0].cwd.as_deref(), Some("/work/api"));
+        assert_eq!(records[0].project.as_deref(), Some("/work/api"));
+        assert_eq!(records[1].project.as_deref(), Some("/work/web"));
+        assert_eq!(records[2].project.as_deref(), Some("/work/web"));
+    }
+
+    #[test]
+    fn test_parse_skips_non_usage_lines() {
+        assert!(parse_usage_line(r#"{"type":"user","message":{"content":"hi"}}"#).is_none());
+        assert!(parse_usage_line("not json").is_none());
+        let synthetic = r#"{"type":"assistant","timestamp":"2025-07-20T10:00:00Z","message":{"model":"<synthetic>","usage":{"input_tokens":1}}}"#;
+        assert!(parse_usage_line(synthetic).is_none());
+    }
+}
@@ -0,0 +1,699 @@
+use crate::bluetooth::BluetoothError;
+use crate::config::{Config, DeviceConfig};
+#[cfg(feature = "ble")]
+use crate::device::AdapterChoice;
+use crate::device::{PairedDevice, TransportKind};
+use crate::http::{self, Endpoint};
+use crate::mqtt::Mqtt;
+use crate::output;
+use crate::payload::DevicePayload;
+use crate::protocol::{self, Ack, Agreement, FrameKind, Hello, Reassembler};
+#[cfg(feature = "ble")]
+use crate::scanner;
+use crate::serial::{self, Serial};
+use crate::screen;
+use crate::settings::DeviceSettings;
+#[cfg(feature = "ble")]
+use btleplug::api::{Central as _, CharPropFlags, Characteristic, Manager as _, Peripheral as _, WriteType};
+#[cfg(feature = "ble")]
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+#[cfg(feature = "ble")]
+use futures::stream::StreamExt;
+use std::error::Error;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Longest wait for the paired device to advertise per connection attempt
+#[cfg(feature = "ble")]
+const SCAN_SECONDS: u64 = 15;
+
+/// Longest wait for a direct connection to the paired device before scanning for it
+#[cfg(feature = "ble")]
+const DIRECT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Longest wait for one HTTP exchange with the gadget
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Gadget web server path that accepts frames
+const PAYLOAD_PATH: &str = "/payload";
+
+/// Gadget web server path serving the state report
+const STATE_PATH: &str = "/state";
+
+/// Pending result of a transport operation; boxed so transports can be chosen at runtime
+pub type TransportFuture<'a, T> = BoxFuture<'a, Result<T, Box<dyn Error>>>;
+
+/// Frames the device sends on its own, such as button presses
+pub type EventStream = BoxStream<'static, Vec<u8>>;
+
+/// A way of reaching the device. The daemon writes frames with `send` and reads the
+/// device's state report (a JSON object with e.g. `timestamp` and `settings`) with
+/// `receive`, whatever carries them.
+pub trait Transport: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Open the connection
+    fn connect(&mut self) -> TransportFuture<'_, ()>;
+
+    /// Write one raw frame to the device
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()>;
+
+    /// Largest frame one write can carry; larger frames are chunked. `None` for
+    /// transports without a limit.
+    fn max_write(&self) -> Option<usize> {
+        None
+    }
+
+    /// Read the device's state report; `None` if the device does not send one
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>>;
+
+    /// Subscribe to frames the device sends on its own; `None` if the transport or the
+    /// device does not support them
+    fn events(&self) -> TransportFuture<'_, Option<EventStream>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// Confirm the connection is still up without showing anything on the device; by
+    /// default a read of the state report
+    fn check(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async move { self.receive().await.map(|_| ()) })
+    }
+
+    /// Close the connection
+    fn close(&mut self) -> TransportFuture<'_, ()>;
+}
+
+/// Unconnected transport of the given kind for the paired device
+#[cfg_attr(not(feature = "ble"), allow(unused_variables))]
+pub fn for_kind(kind: TransportKind, device: &PairedDevice, config: &Config) -> Box<dyn Transport> {
+    match kind {
+        #[cfg(feature = "ble")]
+        TransportKind::Ble => Box::new(Ble::new(device.clone(), config.device.ble_mtu, config.device.adapter.clone())),
+        #[cfg(not(feature = "ble"))]
+        TransportKind::Ble => Box::new(NoBluetooth),
+        TransportKind::Wifi => Box::new(Wifi::new(config.device.wifi_url.clone())),
+        TransportKind::Serial => Box::new(Serial::new(config.device.serial_port.clone(), config.device.serial_baud)),
+        TransportKind::Mqtt => Box::new(Mqtt::new(config.mqtt.clone())),
+    }
+}
+
+/// Offer the protocol versions this CLI speaks and settle on one with the device.
+/// Firmware that predates the handshake, and may reject the binary frame, is spoken
+/// to in JSON.
+pub async fn handshake(transport: &dyn Transport) -> Result<Agreement, Box<dyn Error>> {
+    let hello = protocol::frame(protocol::PROTOCOL_VERSION, FrameKind::Hello, &Hello::ours())?;
+    if let Err(e) = transport.send(&hello).await {
+        debug!("Device refused the handshake ({}); speaking JSON", e);
+        return Ok(Agreement { version: protocol::JSON_VERSION, mtu: None });
+    }
+    let reply = transport.receive().await?;
+    let agreement = protocol::negotiate_reply(reply.as_deref())?;
+    debug!("Speaking protocol v{} over {}", agreement.version, transport.name());
+    Ok(agreement)
+}
+
+/// Connects over the preferred transport, and over the fallbacks as well once the
+/// preferred one has failed `fallback_after` times in a row. A gadget plugged in over
+/// USB is preferred over everything else when `prefer_usb` is on.
+#[derive(Debug, Default)]
+pub struct Connector {
+    /// Consecutive failed attempts over the preferred transport
+    failures: u32,
+}
+
+impl Connector {
+    /// Transports to try on the next attempt, in order
+    fn order(&self, config: &DeviceConfig, usb_plugged_in: bool) -> Vec<TransportKind> {
+        let mut order = config.transports();
+        if self.failures < config.fallback_after {
+            order.truncate(1);
+        }
+        if usb_plugged_in && config.prefer_usb {
+            order.retain(|kind| *kind != TransportKind::Serial);
+            order.insert(0, TransportKind::Serial);
+        }
+        order
+    }
+
+    /// Connect over the first transport that succeeds; the error is the last
+    /// transport's when none does
+    pub async fn connect(&mut self, device: &PairedDevice, config: &Config) -> Result<Box<dyn Transport>, Box<dyn Error>> {
+        let usb_plugged_in = match &config.device.serial_port {
+            Some(path) => std::path::Path::new(path).exists(),
+            None => serial::detect().is_some(),
+        };
+        let order = self.order(&config.device, usb_plugged_in);
+        let mut last_error: Box<dyn Error> = "No transport configured".into();
+        for (attempt, kind) in order.iter().enumerate() {
+            if attempt > 0 {
+                info!("Falling back to {}", kind);
+            } else if *kind == TransportKind::Serial && usb_plugged_in {
+                info!("CCGadget plugged in over USB; connecting over serial");
+            }
+            let mut transport = for_kind(*kind, device, config);
+            match transport.connect().await {
+                Ok(()) => {
+                    if *kind == config.device.transport {
+                        self.failures = 0;
+                    }
+                    return Ok(transport);
+                }
+                Err(e) => {
+                    if *kind == config.device.transport {
+                        self.failures += 1;
+                    }
+                    if attempt + 1 < order.len() {
+                        warn!("Connecting over {} failed: {}", kind, e);
+                    }
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+/// GATT connection to the paired device's payload characteristic
+#[cfg(feature = "ble")]
+pub struct Ble {
+    device: PairedDevice,
+    /// ATT MTU assumed when the device does not report one
+    mtu: u16,
+    /// Adapter to scan and connect with; the first one when unset
+    adapter: Option<AdapterChoice>,
+    link: Option<(Peripheral, Characteristic)>,
+}
+
+/// Smallest ATT MTU, which every BLE stack supports
+#[cfg(feature = "ble")]
+const MIN_ATT_MTU: u16 = 23;
+
+/// ATT header bytes in each write, on top of the value
+pub const ATT_OVERHEAD: usize = 3;
+
+#[cfg(feature = "ble")]
+impl Ble {
+    /// Transport for `device` through `adapter`, connected by [`Transport::connect`]
+    pub fn new(device: PairedDevice, mtu: u16, adapter: Option<AdapterChoice>) -> Self {
+        Self { device, mtu: mtu.max(MIN_ATT_MTU), adapter, link: None }
+    }
+
+    /// Transport over a peripheral that is already connected, as during pairing
+    pub fn attached(device: PairedDevice, peripheral: Peripheral) -> Result<Self, Box<dyn Error>> {
+        let characteristic = Self::payload_characteristic(&peripheral)?;
+        Ok(Self { device, mtu: MIN_ATT_MTU, adapter: None, link: Some((peripheral, characteristic)) })
+    }
+
+    /// The payload characteristic among the peripheral's discovered services
+    fn payload_characteristic(peripheral: &Peripheral) -> Result<Characteristic, Box<dyn Error>> {
+        peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == protocol::PAYLOAD_CHARACTERISTIC_UUID)
+            .ok_or_else(|| "Device does not expose the CCGadget payload characteristic".into())
+    }
+
+    /// Connect straight to the paired device when the adapter already knows it (BlueZ
+    /// keeps paired and recently seen devices), skipping the scan. `None` when it is not
+    /// known or does not answer in time.
+    async fn connect_directly(&self, central: &Adapter) -> Option<Peripheral> {
+        let known = central.peripherals().await.ok()?;
+        let peripheral = known
+            .into_iter()
+            .find(|peripheral| self.device.is_peripheral(&peripheral.id().to_string(), &peripheral.address().to_string()))?;
+        debug!("Connecting directly to {}", peripheral.address());
+        match tokio::time::timeout(DIRECT_CONNECT_TIMEOUT, peripheral.connect()).await {
+            Ok(Ok(())) => Some(peripheral),
+            Ok(Err(e)) => {
+                debug!("Direct connection failed ({}); scanning", e);
+                None
+            }
+            Err(_) => {
+                debug!("Direct connection timed out after {:?}; scanning", DIRECT_CONNECT_TIMEOUT);
+                // Cancel the pending connection so it does not race the scan
+                let _ = peripheral.disconnect().await;
+                None
+            }
+        }
+    }
+
+    /// The connected peripheral, for reads beyond the payload characteristic
+    pub fn peripheral(&self) -> Option<&Peripheral> {
+        self.link.as_ref().map(|(peripheral, _)| peripheral)
+    }
+
+    /// The connected peripheral and payload characteristic
+    fn link(&self) -> Result<&(Peripheral, Characteristic), Box<dyn Error>> {
+        self.link.as_ref().ok_or_else(|| "BLE transport is not connected".into())
+    }
+}
+
+#[cfg(feature = "ble")]
+impl Transport for Ble {
+    fn name(&self) -> &'static str {
+        "ble"
+    }
+
+    /// Scan for the paired device and open a GATT connection to it
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let manager = Manager::new().await.map_err(|e| BluetoothError::from_btleplug(e, BluetoothError::Init))?;
+            let adapters = manager.adapters().await.map_err(|e| BluetoothError::from_btleplug(e, BluetoothError::Adapter))?;
+            let central = scanner::choose_adapter(adapters, self.adapter.as_ref()).await?;
+
+            let peripheral = match self.connect_directly(&central).await {
+                Some(peripheral) => peripheral,
+                None => {
+                    debug!("Scanning for {} for up to {}s", self.device.address, SCAN_SECONDS);
+                    let found = scanner::find(&central, &self.device.address, Duration::from_secs(SCAN_SECONDS)).await?;
+                    let peripheral = found.ok_or_else(|| format!("Paired device {} not found", self.device.label()))?;
+                    debug!("Connecting to {}", peripheral.address());
+                    peripheral.connect().await?;
+                    peripheral
+                }
+            };
+            peripheral.discover_services().await?;
+            debug!("Discovered {} characteristics", peripheral.characteristics().len());
+            let characteristic = Self::payload_characteristic(&peripheral)?;
+
+            self.link = Some((peripheral, characteristic));
+            Ok(())
+        })
+    }
+
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let (peripheral, characteristic) = self.link()?;
+            debug!("Writing {} byte frame to {}", frame.len(), peripheral.address());
+            peripheral.write(characteristic, frame, WriteType::WithResponse).await?;
+            Ok(())
+        })
+    }
+
+    fn max_write(&self) -> Option<usize> {
+        Some(self.mtu as usize - ATT_OVERHEAD)
+    }
+
+    /// Notifications on the event characteristic, if the firmware has one
+    fn events(&self) -> TransportFuture<'_, Option<EventStream>> {
+        Box::pin(async move {
+            let (peripheral, _) = self.link()?;
+            let Some(characteristic) = peripheral.characteristics().into_iter().find(|c| {
+                c.uuid == protocol::EVENT_CHARACTERISTIC_UUID && c.properties.contains(CharPropFlags::NOTIFY)
+            }) else {
+                return Ok(None);
+            };
+            peripheral.subscribe(&characteristic).await?;
+            let uuid = characteristic.uuid;
+            let events = peripheral
+                .notifications()
+                .await?
+                .filter_map(move |notification| futures::future::ready((notification.uuid == uuid).then_some(notification.value)));
+            Ok(Some(events.boxed()))
+        })
+    }
+
+    /// A read of the payload characteristic, if the firmware makes it readable
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let (peripheral, characteristic) = self.link()?;
+            if !characteristic.properties.contains(CharPropFlags::READ) {
+                return Ok(None);
+            }
+            Ok(Some(peripheral.read(characteristic).await?))
+        })
+    }
+
+    /// The stack's view of the connection, confirmed with a read when the firmware
+    /// allows one
+    fn check(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let (peripheral, _) = self.link()?;
+            if !peripheral.is_connected().await? {
+                return Err(format!("{} disconnected", self.device.label()).into());
+            }
+            self.receive().await.map(|_| ())
+        })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some((peripheral, _)) = self.link.take() {
+                peripheral.disconnect().await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Stands in for the BLE transport in builds without Bluetooth; never connects
+#[cfg(not(feature = "ble"))]
+struct NoBluetooth;
+
+#[cfg(not(feature = "ble"))]
+impl Transport for NoBluetooth {
+    fn name(&self) -> &'static str {
+        "ble"
+    }
+
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Err(BluetoothError::Unsupported.into()) })
+    }
+
+    fn send<'a>(&'a self, _frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async { Err(BluetoothError::Unsupported.into()) })
+    }
+
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async { Err(BluetoothError::Unsupported.into()) })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// HTTP to the gadget's web server: frames are POSTed to `/payload`, and `/state`
+/// returns the state report
+pub struct Wifi {
+    url: Option<String>,
+    endpoint: Option<Endpoint>,
+}
+
+impl Wifi {
+    /// Transport for the gadget at `url`, checked by [`Transport::connect`]
+    pub fn new(url: Option<String>) -> Self {
+        Self { url, endpoint: None }
+    }
+
+    /// The endpoint found reachable by `connect`
+    fn endpoint(&self) -> Result<&Endpoint, Box<dyn Error>> {
+        self.endpoint.as_ref().ok_or_else(|| "Wi-Fi transport is not connected".into())
+    }
+}
+
+impl Transport for Wifi {
+    fn name(&self) -> &'static str {
+        "wifi"
+    }
+
+    /// Check that the gadget's web server answers
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let url = self.url.as_deref().ok_or("No Wi-Fi address configured; set device.wifi_url")?;
+            let endpoint = Endpoint::parse(url)?;
+            debug!("Checking {}:{} over HTTP", endpoint.host, endpoint.port);
+            http::request(&endpoint, "GET", STATE_PATH, None, HTTP_TIMEOUT).await?;
+            self.endpoint = Some(endpoint);
+            Ok(())
+        })
+    }
+
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            let endpoint = self.endpoint()?;
+            let response = http::request(endpoint, "POST", PAYLOAD_PATH, Some(frame), HTTP_TIMEOUT).await?;
+            if !response.is_success() {
+                return Err(format!("Device rejected frame with HTTP {}", response.status).into());
+            }
+            Ok(())
+        })
+    }
+
+    /// The state report, or `None` if the firmware does not serve one
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let endpoint = self.endpoint()?;
+            let response = http::request(endpoint, "GET", STATE_PATH, None, HTTP_TIMEOUT).await?;
+            match response.status {
+                404 => Ok(None),
+                _ if response.is_success() => Ok(Some(response.body)),
+                status => Err(format!("Device state request failed with HTTP {}", status).into()),
+            }
+        })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        self.endpoint = None;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Simulated device used in demo/test mode; renders frames using the widget layout
+/// from its stored settings. Once greeted it speaks the newest protocol version,
+/// reassembling chunked frames and acknowledging numbered ones as the firmware does.
+pub struct Demo {
+    settings: Mutex<DeviceSettings>,
+    /// Protocol version agreed in the handshake
+    version: AtomicU8,
+    /// Answer to the last handshake or chunked frame, returned by the next read
+    reply: Mutex<Option<Vec<u8>>>,
+    reassembler: Mutex<Reassembler>,
+    /// Sequence number of the last numbered frame shown, to skip retransmits
+    last_seq: Mutex<Option<u32>>,
+    /// Sequence numbers of the numbered frames shown, in order
+    shown: Arc<Mutex<Vec<u32>>>,
+}
+
+/// ATT MTU the simulated device reports
+const DEMO_MTU: u16 = 247;
+
+impl Demo {
+    /// Simulated device holding `settings`
+    pub fn new(settings: DeviceSettings) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            version: AtomicU8::new(protocol::JSON_VERSION),
+            reply: Mutex::new(None),
+            reassembler: Mutex::new(Reassembler::default()),
+            last_seq: Mutex::new(None),
+            shown: Arc::default(),
+        }
+    }
+
+    /// Sequence numbers of the numbered frames shown so far, still readable once the
+    /// device has been handed to a link
+    pub fn shown(&self) -> Arc<Mutex<Vec<u32>>> {
+        self.shown.clone()
+    }
+
+    /// Act on one whole frame
+    fn receive_frame(&self, frame: &[u8]) -> Result<(), Box<dyn Error>> {
+        match protocol::decode::<serde_json::Value>(frame)? {
+            (Some(FrameKind::Hello), hello) => {
+                let hello: Hello = serde_json::from_value(hello)?;
+                info!("[demo] handshake from {}", hello.agent);
+                let version = protocol::negotiate(&hello)?;
+                self.version.store(version, Ordering::Relaxed);
+                let ours = Hello { agent: "ccgadget-simulator".to_string(), mtu: Some(DEMO_MTU), ..Hello::ours() };
+                *self.reply.lock().unwrap() = Some(protocol::frame(version, FrameKind::Hello, &ours)?);
+            }
+            (Some(FrameKind::Chunk), _) => {
+                if let Some((message, whole)) = self.reassembler.lock().unwrap().push(frame)? {
+                    debug!("[demo] reassembled message {} ({} bytes)", message, whole.len());
+                    self.receive_frame(&whole)?;
+                    // Numbered frames are acknowledged by sequence number instead
+                    if protocol::sequence(&whole).is_none() {
+                        let version = self.version.load(Ordering::Relaxed);
+                        let ack = Ack { message: Some(message), ..Default::default() };
+                        *self.reply.lock().unwrap() = Some(protocol::frame(version, FrameKind::Ack, &ack)?);
+                    }
+                }
+            }
+            (_, payload) => {
+                let payload: DevicePayload = serde_json::from_value(payload)?;
+                if let Some(seq) = protocol::sequence(frame) {
+                    let mut last_seq = self.last_seq.lock().unwrap();
+                    // The host waits for each ack before numbering the next frame, so
+                    // anything further ahead has a damaged header and goes unacknowledged
+                    if let Some(last) = last_seq.filter(|last| seq > last.saturating_add(1)) {
+                        return Err(format!("frame {} is out of sequence after {}", seq, last).into());
+                    }
+                    let version = self.version.load(Ordering::Relaxed);
+                    let ack = Ack { seq: Some(seq), ..Default::default() };
+                    *self.reply.lock().unwrap() = Some(protocol::frame(version, FrameKind::Ack, &ack)?);
+                    if last_seq.is_some_and(|last| seq <= last) {
+                        debug!("[demo] frame {} already shown; acknowledged again", seq);
+                        return Ok(());
+                    }
+                    *last_seq = Some(seq);
+                    self.shown.lock().unwrap().push(seq);
+                }
+                // Logged as JSON whichever encoding it arrived in
+                info!("[demo] {}", String::from_utf8_lossy(&payload.to_bytes()));
+                match payload {
+                    DevicePayload { settings: Some(stored), .. } => *self.settings.lock().unwrap() = stored,
+                    // The simulated screen stands in for the device, so it is output, not a log line
+                    payload if !output::quiet() => {
+                        let layout = self.settings.lock().unwrap().display.widgets.clone();
+                        print!("{}", screen::render(&payload, &layout, false));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport for Demo {
+    fn name(&self) -> &'static str {
+        "demo"
+    }
+
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        if let Err(e) = self.receive_frame(frame) {
+            warn!("[demo] unreadable frame: {}", e);
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Writes are limited as over BLE, so chunking is exercised
+    fn max_write(&self) -> Option<usize> {
+        Some(DEMO_MTU as usize - ATT_OVERHEAD)
+    }
+
+    /// The answer to a handshake or chunked frame, else the stored settings and the
+    /// host clock, which the simulated device shares
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        let reply = match self.reply.lock().unwrap().take() {
+            Some(reply) => Ok(reply),
+            None => {
+                let state = serde_json::json!({
+                    "timestamp": Utc::now(),
+                    "settings": *self.settings.lock().unwrap(),
+                });
+                match self.version.load(Ordering::Relaxed) {
+                    protocol::JSON_VERSION => serde_json::to_vec(&state).map_err(Into::into),
+                    version => protocol::frame(version, FrameKind::State, &state),
+                }
+            }
+        };
+        // Errors are not Send, so only the message crosses into the future
+        let reply = reply.map_err(|e| e.to_string());
+        Box::pin(async move { Ok(Some(reply?)) })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Observe-only mode: frames are logged locally and never leave the machine
+pub struct Observe;
+
+impl Transport for Observe {
+    fn name(&self) -> &'static str {
+        "observe-only"
+    }
+
+    fn connect(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send<'a>(&'a self, frame: &'a [u8]) -> TransportFuture<'a, ()> {
+        info!("[observe-only] not sent: {}", String::from_utf8_lossy(frame));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn receive(&self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn close(&mut self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallbacks_join_after_repeated_failures() {
+        let config = DeviceConfig { fallback: vec![TransportKind::Wifi], fallback_after: 2, ..Default::default() };
+        let mut connector = Connector::default();
+        assert_eq!(connector.order(&config, false), [TransportKind::Ble]);
+        connector.failures = 2;
+        assert_eq!(connector.order(&config, false), [TransportKind::Ble, TransportKind::Wifi]);
+        assert_eq!(connector.order(&config, true), [TransportKind::Serial, TransportKind::Ble, TransportKind::Wifi]);
+
+        let config = DeviceConfig { prefer_usb: false, ..config };
+        assert_eq!(connector.order(&config, true), [TransportKind::Ble, TransportKind::Wifi]);
+    }
+
+    #[tokio::test]
+    async fn test_wifi_needs_an_address() {
+        let error = Wifi::new(None).connect().await.unwrap_err();
+        assert!(error.to_string().contains("device.wifi_url"));
+    }
+
+    #[tokio::test]
+    async fn test_demo_reports_the_settings_it_was_sent() {
+        let mut demo = Demo::new(DeviceSettings::default());
+        demo.connect().await.unwrap();
+        let mut settings = DeviceSettings::default();
+        settings.display.widgets = vec!["cost_today".to_string()];
+        demo.send(&DevicePayload::settings(settings.clone()).to_bytes()).await.unwrap();
+
+        let state: serde_json::Value = serde_json::from_slice(&demo.receive().await.unwrap().unwrap()).unwrap();
+        assert_eq!(serde_json::from_value::<DeviceSettings>(state["settings"].clone()).unwrap(), settings);
+        assert!(state["timestamp"].is_string());
+        assert_eq!(Observe.receive().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_upgrades_only_greeted_devices() {
+        let demo = Demo::new(DeviceSettings::default());
+        assert_eq!(handshake(&demo).await.unwrap(), Agreement { version: protocol::PROTOCOL_VERSION, mtu: Some(DEMO_MTU) });
+        // The state report now comes back as a binary frame
+        let (kind, state) = protocol::decode::<serde_json::Value>(&demo.receive().await.unwrap().unwrap()).unwrap();
+        assert_eq!(kind, Some(FrameKind::State));
+        assert!(state["settings"].is_object());
+
+        assert_eq!(handshake(&Observe).await.unwrap().version, protocol::JSON_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_demo_acknowledges_chunked_frames() {
+        let demo = Demo::new(DeviceSettings::default());
+        handshake(&demo).await.unwrap();
+        let mut settings = DeviceSettings::default();
+        settings.display.widgets = vec!["cost_today".to_string(); 40];
+        let frame = protocol::encode(protocol::CHUNKED_VERSION, 0, &DevicePayload::settings(settings.clone())).unwrap();
+        for chunk in protocol::chunk(&frame, protocol::CHUNKED_VERSION, 3, 20).unwrap() {
+            demo.send(&chunk).await.unwrap();
+        }
+
+        let (kind, ack) = protocol::decode::<Ack>(&demo.receive().await.unwrap().unwrap()).unwrap();
+        assert_eq!((kind, ack), (Some(FrameKind::Ack), Ack { message: Some(3), seq: None }));
+        assert_eq!(*demo.settings.lock().unwrap(), settings);
+    }
+
+    #[tokio::test]
+    async fn test_demo_acknowledges_retransmitted_frames_once_shown() {
+        let demo = Demo::new(DeviceSettings::default());
+        handshake(&demo).await.unwrap();
+        let mut settings = DeviceSettings::default();
+        settings.display.widgets = vec!["cost_today".to_string()];
+        let frame = protocol::encode(protocol::ACKED_VERSION, 5, &DevicePayload::settings(settings.clone())).unwrap();
+        let stale = protocol::encode(protocol::ACKED_VERSION, 4, &DevicePayload::settings(DeviceSettings::default())).unwrap();
+        for frame in [&frame, &frame, &stale] {
+            demo.send(frame).await.unwrap();
+            let (_, ack) = protocol::decode::<Ack>(&demo.receive().await.unwrap().unwrap()).unwrap();
+            assert_eq!(ack.seq, Some(protocol::sequence(frame).unwrap()));
+        }
+        // The late retransmit of an older frame did not overwrite the newer settings
+        assert_eq!(*demo.settings.lock().unwrap(), settings);
+    }
+}
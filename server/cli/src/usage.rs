@@ -0,0 +1,402 @@
+use crate::cost::CostSummary;
+use crate::import;
+use crate::config::Config;
+use crate::pricing::PricingTable;
+use crate::storage;
+use crate::timerange::TimeRange;
+use crate::transcript::{self, UsageRecord};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Dimension used to bucket usage records
+#[derive(clap::ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// Local calendar day
+    Day,
+    /// ISO week (Monday start)
+    Week,
+    /// Calendar month
+    Month,
+    /// Project directory the session ran in
+    Project,
+    /// Claude model
+    Model,
+    /// Claude Code session
+    Session,
+}
+
+/// Layout of the `usage` report
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum UsageFormat {
+    /// Text table, or ccgadget's own JSON with `--output json`
+    Table,
+    /// The JSON `ccusage daily|weekly|monthly|session --json` prints
+    Ccusage,
+}
+
+impl GroupBy {
+    /// Column heading for the group key
+    fn heading(&self) -> &'static str {
+        match self {
+            GroupBy::Day => "Date",
+            GroupBy::Week => "Week",
+            GroupBy::Month => "Month",
+            GroupBy::Project => "Project",
+            GroupBy::Model => "Model",
+            GroupBy::Session => "Session",
+        }
+    }
+
+    /// The ccusage report list and its key field for this grouping; ccusage has no
+    /// report by project or model
+    pub fn ccusage_names(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            GroupBy::Day => Some(("daily", "date")),
+            GroupBy::Week => Some(("weekly", "week")),
+            GroupBy::Month => Some(("monthly", "month")),
+            GroupBy::Session => Some(("sessions", "sessionId")),
+            GroupBy::Project | GroupBy::Model => None,
+        }
+    }
+
+    /// Bucket key for a usage record
+    fn key(&self, record: &UsageRecord) -> String {
+        let date = record.timestamp.with_timezone(&Local).date_naive();
+        match self {
+            GroupBy::Day => date.format("%Y-%m-%d").to_string(),
+            GroupBy::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            GroupBy::Month => date.format("%Y-%m").to_string(),
+            GroupBy::Project => project_name(record.project.as_deref().or(record.cwd.as_deref())),
+            GroupBy::Model => record.model.clone(),
+            GroupBy::Session => record.session_id.clone().unwrap_or_else(|| "unknown".to_string()),
+        }
+    }
+}
+
+/// One aggregated row of the usage report
+#[derive(Serialize, Debug, Clone)]
+pub struct UsageRow {
+    pub key: String,
+    pub summary: CostSummary,
+}
+
+/// Parse a `--since`/`--until` date (YYYY-MM-DD or YYYYMMDD)
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d"))
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", value))
+}
+
+/// Short project name from a project root or working directory
+fn project_name(dir: Option<&str>) -> String {
+    dir.and_then(|cwd| Path::new(cwd).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Transcript paths recorded by `ccgadget trigger` in the trigger logs since `since`;
+/// a session with usage after then had hook events logged after then too
+fn logged_transcript_paths(since: Option<DateTime<Utc>>) -> BTreeSet<PathBuf> {
+    let mut paths_found = BTreeSet::new();
+    let backend = Config::load_or_default().logs.backend;
+    let range = TimeRange { since, until: None };
+    let Ok(entries) = storage::open(backend).and_then(|store| store.read(&range)) else {
+        return paths_found;
+    };
+    for line in entries {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if let Some(path) = value["hook_input"]["transcript_path"].as_str() {
+            paths_found.insert(PathBuf::from(path));
+        }
+    }
+    paths_found
+}
+
+/// Load deduplicated usage records, plus imported history not covered by transcripts, within a time range.
+/// The metrics database answers when it is on and can be opened; otherwise transcripts are read.
+pub fn load_records(range: &TimeRange) -> Result<Vec<UsageRecord>, Box<dyn Error>> {
+    #[cfg(feature = "full")]
+    {
+        let config = Config::load_or_default();
+        if config.logs.metrics_db {
+            match crate::metrics_db::MetricsDb::open(&config).and_then(|db| db.usage(range)) {
+                Ok(records) => return with_imported(records, range),
+                Err(e) => tracing::warn!("Metrics database unavailable, reading transcripts: {}", e),
+            }
+        }
+    }
+    with_imported(scan_records(range)?, range)
+}
+
+/// Usage records within a time range, read from every transcript that may hold some
+fn scan_records(range: &TimeRange) -> Result<Vec<UsageRecord>, Box<dyn Error>> {
+    let modified_since = range.since.map(SystemTime::from);
+
+    let mut files: BTreeSet<PathBuf> = transcript::transcript_files(modified_since)?.into_iter().collect();
+    files.extend(logged_transcript_paths(range.since).into_iter().filter(|path| path.exists()));
+
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+    for file in files {
+        let Ok(file_records) = transcript::read_usage_records(&file) else {
+            continue;
+        };
+        for record in file_records {
+            if !range.contains(record.timestamp) {
+                continue;
+            }
+            if let Some(key) = &record.dedup_key {
+                if !seen.insert(key.clone()) {
+                    continue;
+                }
+            }
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Add imported history within a time range that transcripts do not cover
+fn with_imported(records: Vec<UsageRecord>, range: &TimeRange) -> Result<Vec<UsageRecord>, Box<dyn Error>> {
+    let imported = import::load_imported()?.into_iter().filter(|record| range.contains(record.timestamp));
+    Ok(import::merge(records, imported.collect()))
+}
+
+/// Aggregate records into rows sorted by group key
+pub fn aggregate(records: &[UsageRecord], group_by: GroupBy, pricing: &PricingTable) -> Vec<UsageRow> {
+    let mut groups: BTreeMap<String, CostSummary> = BTreeMap::new();
+    for record in records {
+        groups.entry(group_by.key(record)).or_default().add_record(record, pricing);
+    }
+    groups
+        .into_iter()
+        .map(|(key, summary)| UsageRow { key, summary })
+        .collect()
+}
+
+/// Sum of all rows
+pub fn total(rows: &[UsageRow]) -> CostSummary {
+    let mut total = CostSummary::default();
+    for row in rows {
+        total.add(&row.summary);
+    }
+    total
+}
+
+/// Token and cost fields as ccusage names them, with the cost under `cost_field`
+fn ccusage_counts(summary: &CostSummary, cost_field: &str) -> serde_json::Map<String, Value> {
+    let usage = &summary.usage;
+    let mut counts = serde_json::Map::new();
+    counts.insert("inputTokens".to_string(), usage.input_tokens.into());
+    counts.insert("outputTokens".to_string(), usage.output_tokens.into());
+    counts.insert("cacheCreationTokens".to_string(), usage.cache_creation_input_tokens.into());
+    counts.insert("cacheReadTokens".to_string(), usage.cache_read_input_tokens.into());
+    if cost_field == "totalCost" {
+        counts.insert("totalTokens".to_string(), usage.total().into());
+    }
+    counts.insert(cost_field.to_string(), summary.cost_usd.into());
+    counts
+}
+
+/// Usage in the shape of ccusage's `--json` reports, so existing dashboards and
+/// scripts can read it and `ccgadget import --from ccusage` can read back daily and
+/// session reports. Weeks are keyed by their Monday, sessions carry the date of their
+/// last response and the project they ran in.
+pub fn ccusage_report(records: &[UsageRecord], group_by: GroupBy, pricing: &PricingTable) -> Result<Value, String> {
+    let (list, key_field) = group_by
+        .ccusage_names()
+        .ok_or_else(|| format!("ccusage has no report grouped by {}", group_by.heading().to_lowercase()))?;
+
+    let mut groups: BTreeMap<String, BTreeMap<String, CostSummary>> = BTreeMap::new();
+    let mut latest: HashMap<String, &UsageRecord> = HashMap::new();
+    for record in records {
+        let key = match group_by {
+            GroupBy::Week => {
+                let date = record.timestamp.with_timezone(&Local).date_naive();
+                (date - Duration::days(date.weekday().num_days_from_monday() as i64)).format("%Y-%m-%d").to_string()
+            }
+            _ => group_by.key(record),
+        };
+        groups.entry(key.clone()).or_default().entry(record.model.clone()).or_default().add_record(record, pricing);
+        let last = latest.entry(key).or_insert(record);
+        if record.timestamp > last.timestamp {
+            *last = record;
+        }
+    }
+
+    let mut totals = CostSummary::default();
+    let mut entries = Vec::new();
+    for (key, models) in groups {
+        let mut summary = CostSummary::default();
+        let mut breakdowns = Vec::new();
+        for (model, model_summary) in &models {
+            summary.add(model_summary);
+            let mut breakdown = serde_json::Map::new();
+            breakdown.insert("modelName".to_string(), model.clone().into());
+            breakdown.extend(ccusage_counts(model_summary, "cost"));
+            breakdowns.push(Value::Object(breakdown));
+        }
+        totals.add(&summary);
+
+        let mut entry = serde_json::Map::new();
+        entry.insert(key_field.to_string(), key.clone().into());
+        entry.extend(ccusage_counts(&summary, "totalCost"));
+        if group_by == GroupBy::Session {
+            let last = latest[&key];
+            let date = last.timestamp.with_timezone(&Local).date_naive();
+            entry.insert("lastActivity".to_string(), date.format("%Y-%m-%d").to_string().into());
+            let project = last.project.as_deref().or(last.cwd.as_deref()).unwrap_or("Unknown Project");
+            entry.insert("projectPath".to_string(), project.into());
+        }
+        entry.insert("modelsUsed".to_string(), json!(models.keys().collect::<Vec<_>>()));
+        entry.insert("modelBreakdowns".to_string(), Value::Array(breakdowns));
+        entries.push(Value::Object(entry));
+    }
+    Ok(json!({ list: entries, "totals": ccusage_counts(&totals, "totalCost") }))
+}
+
+/// Render rows as a fixed-width text table with a totals line
+pub fn render_table(rows: &[UsageRow], group_by: GroupBy) -> String {
+    let key_width = rows
+        .iter()
+        .map(|row| row.key.len())
+        .chain(std::iter::once(group_by.heading().len()))
+        .max()
+        .unwrap_or(0)
+        .max(5);
+
+    let line = |key: &str, summary: &CostSummary| {
+        format!(
+            "{:<kw$}  {:>12}  {:>12}  {:>12}  {:>14}  {:>14}  {:>10}\n",
+            key,
+            summary.usage.input_tokens,
+            summary.usage.output_tokens,
+            summary.usage.cache_creation_input_tokens,
+            summary.usage.cache_read_input_tokens,
+            summary.usage.total(),
+            format!("${:.2}", summary.cost_usd),
+            kw = key_width,
+        )
+    };
+
+    let mut out = format!(
+        "{:<kw$}  {:>12}  {:>12}  {:>12}  {:>14}  {:>14}  {:>10}\n",
+        group_by.heading(),
+        "Input",
+        "Output",
+        "Cache Write",
+        "Cache Read",
+        "Total Tokens",
+        "Cost",
+        kw = key_width,
+    );
+    let rule = "-".repeat(out.trim_end().len());
+    out.push_str(&rule);
+    out.push('\n');
+    for row in rows {
+        out.push_str(&line(&row.key, &row.summary));
+    }
+    out.push_str(&rule);
+    out.push('\n');
+    out.push_str(&line("Total", &total(rows)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::TokenUsage;
+    use chrono::Utc;
+
+    /// Usage record at a fixed UTC time for grouping tests
+    fn record(timestamp: &str, model: &str, cwd: &str, output_tokens: u64) -> UsageRecord {
+        UsageRecord {
+            timestamp: timestamp.parse::<chrono::DateTime<Utc>>().unwrap(),
+            session_id: None,
+            cwd: Some(cwd.to_string()),
+            model: model.to_string(),
+            usage: TokenUsage { output_tokens, ..Default::default() },
+            dedup_key: None,
+            project: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_date_formats() {
+        assert_eq!(parse_date("2025-07-20"), Ok(NaiveDate::from_ymd_opt(2025, 7, 20).unwrap()));
+        assert_eq!(parse_date("20250720"), Ok(NaiveDate::from_ymd_opt(2025, 7, 20).unwrap()));
+        assert!(parse_date("July 20").is_err());
+    }
+
+    #[test]
+    fn test_group_by_project_and_model() {
+        let pricing = PricingTable::builtin();
+        let records = vec![
+            record("2025-07-15T12:00:00Z", "claude-sonnet-4", "/work/api", 1_000_000),
+            record("2025-07-16T12:00:00Z", "claude-opus-4", "/work/api", 1_000_000),
+            record("2025-07-16T12:00:00Z", "claude-sonnet-4", "/work/web", 1_000_000),
+        ];
+
+        let by_project = aggregate(&records, GroupBy::Project, &pricing);
+        assert_eq!(by_project.len(), 2);
+        assert_eq!(by_project[0].key, "api");
+        assert!((by_project[0].summary.cost_usd - 90.0).abs() < 1e-9);
+
+        let by_model = aggregate(&records, GroupBy::Model, &pricing);
+        assert_eq!(by_model.iter().map(|r| r.key.as_str()).collect::<Vec<_>>(), vec!["claude-opus-4", "claude-sonnet-4"]);
+        assert!((total(&by_model).cost_usd - 105.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_render_table_includes_total() {
+        let pricing = PricingTable::builtin();
+        let records = vec![record("2025-07-15T12:00:00Z", "claude-sonnet-4", "/work/api", 1_000_000)];
+        let table = render_table(&aggregate(&records, GroupBy::Month, &pricing), GroupBy::Month);
+        assert!(table.contains("Month"));
+        assert!(table.contains("2025-07"));
+        assert!(table.contains("Total"));
+        assert!(table.contains("$15.00"));
+    }
+
+    #[test]
+    fn test_ccusage_report_reads_back_through_the_importer() {
+        let pricing = PricingTable::builtin();
+        let mut records = vec![
+            record("2025-07-15T12:00:00Z", "claude-sonnet-4", "/work/api", 1_000_000),
+            record("2025-07-15T13:00:00Z", "claude-opus-4", "/work/api", 1_000_000),
+            record("2025-07-16T12:00:00Z", "claude-sonnet-4", "/work/web", 1_000_000),
+        ];
+        records[0].session_id = Some("a".to_string());
+        records[1].session_id = Some("a".to_string());
+
+        let daily = ccusage_report(&records, GroupBy::Day, &pricing).unwrap();
+        assert_eq!(daily["daily"].as_array().unwrap().len(), 2);
+        assert_eq!(daily["daily"][0]["modelsUsed"], json!(["claude-opus-4", "claude-sonnet-4"]));
+        assert_eq!(daily["daily"][0]["totalTokens"], 2_000_000);
+        assert!((daily["totals"]["totalCost"].as_f64().unwrap() - 105.0).abs() < 1e-9);
+        let imported = import::parse_ccusage(&daily.to_string()).unwrap();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported.iter().map(|r| r.usage.output_tokens).sum::<u64>(), 3_000_000);
+
+        let sessions = ccusage_report(&records, GroupBy::Session, &pricing).unwrap();
+        assert_eq!(sessions["sessions"][0]["sessionId"], "a");
+        assert_eq!(sessions["sessions"][0]["projectPath"], "/work/api");
+        let imported = import::parse_ccusage(&sessions.to_string()).unwrap();
+        assert_eq!(imported[0].session_id.as_deref(), Some("a"));
+
+        let weekly = ccusage_report(&records, GroupBy::Week, &pricing).unwrap();
+        assert_eq!(weekly["weekly"][0]["week"], "2025-07-14");
+        assert!(ccusage_report(&records, GroupBy::Model, &pricing).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::output;
+use crate::scanner::ScannedDevice;
+use futures::{Stream, StreamExt};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::future::Future;
+use std::io::{self, IsTerminal};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::sleep;
+
+/// How often the screen is redrawn and the keyboard checked
+const TICK: Duration = Duration::from_millis(100);
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Whether the pairing wizard can take over the terminal; otherwise `pair` falls back
+/// to a numbered list read from stdin
+pub fn supported() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal() && !output::accessible() && !output::json()
+}
+
+/// What the user did with the device list
+pub enum Choice<P> {
+    Selected(ScannedDevice, P),
+    /// Left with Esc, `q`, or Ctrl-C
+    Cancelled,
+    /// Nothing advertised before the timeout
+    NothingFound,
+}
+
+/// Devices seen so far, strongest signal first, and the highlighted row
+struct DeviceList<P> {
+    devices: Vec<(ScannedDevice, P)>,
+    state: ListState,
+}
+
+impl<P> DeviceList<P> {
+    fn new() -> Self {
+        Self { devices: Vec::new(), state: ListState::default() }
+    }
+
+    /// Add a device or refresh its advertisement, keeping the highlight on the same device
+    fn update(&mut self, device: ScannedDevice, peripheral: P) {
+        let highlighted = self.state.selected().map(|index| self.devices[index].0.address.clone());
+        match self.devices.iter_mut().find(|(known, _)| known.address == device.address) {
+            Some(entry) => *entry = (device, peripheral),
+            None => self.devices.push((device, peripheral)),
+        }
+        self.devices.sort_by_key(|(device, _)| std::cmp::Reverse(device.rssi.unwrap_or(i16::MIN)));
+        let index = highlighted
+            .and_then(|address| self.devices.iter().position(|(device, _)| device.address == address))
+            .unwrap_or(0);
+        self.state.select(Some(index));
+    }
+
+    /// Move the highlight by `step` rows, stopping at either end
+    fn step(&mut self, step: isize) {
+        if let Some(index) = self.state.selected() {
+            let last = self.devices.len().saturating_sub(1);
+            self.state.select(Some(index.saturating_add_signed(step).min(last)));
+        }
+    }
+
+    fn take_highlighted(&mut self) -> Option<(ScannedDevice, P)> {
+        let index = self.state.selected()?;
+        Some(self.devices.remove(index))
+    }
+}
+
+/// The full-screen pairing flow: device list, connection progress, and outcome. The
+/// terminal is restored when it is dropped.
+pub struct Wizard {
+    terminal: DefaultTerminal,
+    /// Progress lines reported while connecting
+    log: Vec<String>,
+    ticks: usize,
+}
+
+impl Wizard {
+    /// Switch the terminal to raw mode and the alternate screen
+    pub fn enter() -> io::Result<Self> {
+        Ok(Self { terminal: ratatui::try_init()?, log: Vec::new(), ticks: 0 })
+    }
+
+    /// List devices from `advertisements` as they arrive and let the user pick one with
+    /// the arrow keys and Enter. Gives up when nothing advertised within `timeout`.
+    pub async fn choose<P>(
+        &mut self,
+        advertisements: impl Stream<Item = (ScannedDevice, P)>,
+        timeout: Duration,
+    ) -> io::Result<Choice<P>> {
+        let mut advertisements = std::pin::pin!(advertisements);
+        let mut list = DeviceList::new();
+        let started = Instant::now();
+        loop {
+            let spinner = self.spinner();
+            self.terminal.draw(|frame| draw_list(frame, &mut list, spinner, started.elapsed()))?;
+            match pressed_key()? {
+                Some(key) if is_cancel(&key) => return Ok(Choice::Cancelled),
+                Some(key) => match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => list.step(-1),
+                    KeyCode::Down | KeyCode::Char('j') => list.step(1),
+                    KeyCode::Enter => {
+                        if let Some((device, peripheral)) = list.take_highlighted() {
+                            return Ok(Choice::Selected(device, peripheral));
+                        }
+                    }
+                    _ => {}
+                },
+                None => {}
+            }
+            if list.devices.is_empty() && started.elapsed() >= timeout {
+                return Ok(Choice::NothingFound);
+            }
+            tokio::select! {
+                Some((device, peripheral)) = advertisements.next() => list.update(device, peripheral),
+                _ = sleep(TICK) => self.ticks += 1,
+            }
+        }
+    }
+
+    /// Show the lines reported on `progress` until `pairing` finishes
+    pub async fn connect<T>(
+        &mut self,
+        device: &ScannedDevice,
+        mut progress: UnboundedReceiver<String>,
+        pairing: impl Future<Output = T>,
+    ) -> io::Result<T> {
+        let title = format!(" Pairing with {} ", device.display_name());
+        let mut pairing = std::pin::pin!(pairing);
+        loop {
+            let status = format!("{} Connecting...", self.spinner());
+            self.draw_log(&title, Line::from(status).cyan(), "")?;
+            tokio::select! {
+                result = &mut pairing => {
+                    while let Ok(line) = progress.try_recv() {
+                        self.log.push(line);
+                    }
+                    return Ok(result);
+                }
+                Some(line) = progress.recv() => self.log.push(line),
+                _ = sleep(TICK) => self.ticks += 1,
+            }
+        }
+    }
+
+    /// Show the outcome under the connection progress and wait for a key
+    pub fn finish(&mut self, headline: &str, ok: bool) -> io::Result<()> {
+        let headline = Line::from(headline.to_string()).bold();
+        let headline = if ok { headline.green() } else { headline.red() };
+        self.draw_log(" Pairing ", headline, "Press any key to exit")?;
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn spinner(&self) -> &'static str {
+        SPINNER[self.ticks % SPINNER.len()]
+    }
+
+    /// Draw the progress log in a box with `status` and a key hint below it
+    fn draw_log(&mut self, title: &str, status: Line<'static>, hint: &str) -> io::Result<()> {
+        let lines: Vec<Line> = self.log.iter().map(|line| Line::from(line.as_str())).collect();
+        self.terminal.draw(|frame| {
+            let [log, status_area, hint_area] =
+                Layout::vertical([Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)]).areas(frame.area());
+            frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title.to_string())), log);
+            frame.render_widget(Paragraph::new(status), status_area);
+            frame.render_widget(Paragraph::new(hint).dim(), hint_area);
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Wizard {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// A key pressed since the last check, without waiting
+fn pressed_key() -> io::Result<Option<KeyEvent>> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                return Ok(Some(key));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Raw mode turns Ctrl-C into a key press, so it is handled here
+fn is_cancel(key: &KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Esc | KeyCode::Char('q'))
+        || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+/// The scanning screen: a live device list with a status line and key hints
+fn draw_list<P>(frame: &mut Frame, list: &mut DeviceList<P>, spinner: &str, elapsed: Duration) {
+    let [header, body, footer]: [Rect; 3] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+
+    let status = format!("{} Scanning for CCGadget devices ({}s)...", spinner, elapsed.as_secs());
+    frame.render_widget(Paragraph::new(Line::from(status).cyan()), header);
+
+    let name_width = list.devices.iter().map(|(device, _)| device.display_name().chars().count()).max().unwrap_or(0).max(4);
+    let items: Vec<ListItem> = list
+        .devices
+        .iter()
+        .map(|(device, _)| {
+            let firmware = device.firmware.as_ref().map(|version| format!("  firmware {}", version)).unwrap_or_default();
+            ListItem::new(format!(
+                "{:<name_width$}  {:<17}  {:>7}{}",
+                device.display_name(),
+                device.address,
+                device.signal(),
+                firmware
+            ))
+        })
+        .collect();
+    let title = format!(" {} device(s), strongest signal first ", items.len());
+    let devices = List::new(items)
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("▶ ");
+    frame.render_stateful_widget(devices, body, &mut list.state);
+
+    frame.render_widget(Paragraph::new("↑/↓ select   Enter pair   Esc cancel").dim(), footer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(address: &str, rssi: i16) -> ScannedDevice {
+        ScannedDevice {
+            name: Some(format!("CCG-{}", rssi)),
+            address: address.to_string(),
+            rssi: Some(rssi),
+            services: Vec::new(),
+            firmware: None,
+            capabilities: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_device_list_keeps_highlight_while_resorting() {
+        let mut list = DeviceList::new();
+        list.update(device("AA", -70), ());
+        list.update(device("BB", -50), ());
+        assert_eq!(list.devices[0].0.address, "BB");
+        assert_eq!(list.devices[list.state.selected().unwrap()].0.address, "AA");
+
+        // A stronger advertisement moves the device up; the highlight follows it
+        list.update(device("AA", -40), ());
+        assert_eq!(list.devices.len(), 2);
+        assert_eq!(list.state.selected(), Some(0));
+
+        list.step(5);
+        assert_eq!(list.state.selected(), Some(1));
+        list.step(-5);
+        assert_eq!(list.take_highlighted().unwrap().0.address, "AA");
+    }
+}
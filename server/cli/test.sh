@@ -43,7 +43,7 @@ fi
 
 # Run unit tests  
 print_status "Running unit tests..."
-if cargo test --bin ccgadget --quiet; then
+if cargo test --lib --bin ccgadget --quiet; then
     print_success "Unit tests passed"
 else
     print_error "Unit tests failed"
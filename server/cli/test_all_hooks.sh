@@ -21,19 +21,19 @@ echo "Binary version:"
 $BINARY --help | head -3
 
 echo -e "\n1. Testing UserPromptSubmit hook:"
-cat test_data/user_prompt_submit.json | $BINARY trigger
+cat test_data/user_prompt_submit.json | $BINARY trigger --wait
 
 echo -e "\n2. Testing Notification hook:"
-cat test_data/notification.json | $BINARY trigger
+cat test_data/notification.json | $BINARY trigger --wait
 
 echo -e "\n3. Testing PreToolUse hook:"
-cat test_data/pre_tool_use.json | $BINARY trigger
+cat test_data/pre_tool_use.json | $BINARY trigger --wait
 
 echo -e "\n4. Testing PostToolUse hook:"
-cat test_data/post_tool_use.json | $BINARY trigger
+cat test_data/post_tool_use.json | $BINARY trigger --wait
 
 echo -e "\n5. Testing pair command help:"
 $BINARY pair --help
 
 echo -e "\n✅ All tests completed successfully!"
-echo "📋 Check logs at: ~/.ccgadget/logs/trigger-$(date +%Y-%m-%d).log"
\ No newline at end of file
+echo "📋 Check logs at: ~/.local/state/ccgadget/logs/trigger-$(date +%Y-%m-%d).log"
\ No newline at end of file
@@ -58,7 +58,7 @@ fn test_trigger_command_with_no_input() {
     ensure_binary_exists();
     
     let output = Command::new(BINARY_PATH)
-        .args(["trigger"])
+        .args(["trigger", "--wait"])
         .output()
         .expect("Failed to execute binary");
     
@@ -68,6 +68,21 @@ fn test_trigger_command_with_no_input() {
     assert!(stdout.contains("Status: Payload logged for debugging"));
 }
 
+#[test]
+fn test_trigger_detaches_by_default() {
+    ensure_binary_exists();
+    
+    let output = Command::new(BINARY_PATH)
+        .args(["trigger", "--event", "Stop"])
+        .output()
+        .expect("Failed to execute binary");
+    
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Handed off to a background trigger"));
+    assert!(!stdout.contains("Status: Payload logged for debugging"));
+}
+
 #[test]
 fn test_trigger_command_with_json_input() {
     ensure_binary_exists();
@@ -134,22 +149,112 @@ fn test_setup_hook_command_help() {
     assert!(stdout.contains("--scope"));
 }
 
+#[test]
+fn test_trigger_batch_processes_each_line() {
+    ensure_binary_exists();
+    
+    let events = concat!(
+        r#"{"session_id": "batch-session", "hook_event_name": "UserPromptSubmit"}"#, "\n",
+        "not json\n",
+        "\n",
+        r#"{"session_id": "batch-session", "hook_event_name": "Stop"}"#, "\n",
+    );
+    
+    let mut child = Command::new(BINARY_PATH)
+        .args(["trigger", "--batch"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+    child.stdin.take().unwrap().write_all(events.as_bytes()).expect("Failed to write to stdin");
+    let output = child.wait_with_output().expect("Failed to wait for command");
+    
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2 processed"), "{}", stdout);
+    assert!(stdout.contains("1 invalid"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Line 2"));
+}
+
 #[test]
 fn test_log_directory_creation() {
     ensure_binary_exists();
     
     // Run trigger command to ensure log directory is created
     let _output = Command::new(BINARY_PATH)
-        .args(["trigger"])
+        .args(["trigger", "--wait"])
         .output()
         .expect("Failed to execute binary");
     
-    // Check that log directory exists
-    let home_dir = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .expect("Could not determine home directory");
+    // Check that the log directory reported by `paths` exists
+    let output = Command::new(BINARY_PATH)
+        .args(["paths", "--json"])
+        .output()
+        .expect("Failed to execute binary");
+    let paths: serde_json::Value = serde_json::from_slice(&output.stdout).expect("paths --json output");
     
-    let log_dir = std::path::PathBuf::from(home_dir).join(".ccgadget").join("logs");
+    let log_dir = std::path::PathBuf::from(paths["logs"].as_str().expect("logs path"));
     assert!(log_dir.exists());
     assert!(log_dir.is_dir());
-}
\ No newline at end of file
+}
+#[test]
+fn test_output_json_is_machine_readable() {
+    ensure_binary_exists();
+
+    // Isolated home so no real daemon or pairing is involved
+    let home = std::env::temp_dir().join(format!("ccgadget-json-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let run = |args: &[&str]| {
+        Command::new(BINARY_PATH)
+            .args(args)
+            .env("HOME", &home)
+            .env("CCGADGET_DEMO_MODE", "1")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("XDG_CONFIG_HOME")
+            .output()
+            .expect("Failed to execute binary")
+    };
+
+    let output = run(&["pair", "--device", "Desk", "--output", "json"]);
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).expect("pair JSON");
+    assert_eq!(result["paired"], true);
+    assert_eq!(result["device"]["name"], "Desk");
+
+    let output = run(&["status", "--output", "json"]);
+    assert!(!output.status.success());
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).expect("status JSON");
+    assert_eq!(status["running"], false);
+
+    std::fs::remove_dir_all(home).unwrap();
+}
+
+#[test]
+fn test_exit_codes() {
+    ensure_binary_exists();
+
+    let home = std::env::temp_dir().join(format!("ccgadget-exit-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let run = |args: &[&str], env: &[(&str, &str)]| {
+        Command::new(BINARY_PATH)
+            .args(args)
+            .env("HOME", &home)
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("XDG_CONFIG_HOME")
+            .envs(env.iter().copied())
+            .output()
+            .expect("Failed to execute binary")
+    };
+
+    // Daemon not running
+    assert_eq!(run(&["status"], &[]).status.code(), Some(6));
+    // Invalid config override
+    let output = run(&["config", "get", "device.ble_mtu"], &[("CCGADGET_DEVICE_BLE_MTU", "lots")]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("CCGADGET_DEVICE_BLE_MTU"));
+    // Unparseable command line
+    assert_eq!(run(&["pair", "--bogus"], &[]).status.code(), Some(64));
+
+    std::fs::remove_dir_all(home).unwrap();
+}